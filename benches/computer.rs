@@ -0,0 +1,53 @@
+//! Benchmarks of the 8 bit computer from the examples, run them with `cargo bench --features bench`.
+#![allow(clippy::upper_case_acronyms)]
+use criterion::{criterion_group, criterion_main, Criterion};
+#[macro_use]
+extern crate strum_macros;
+
+#[macro_use]
+#[allow(dead_code)]
+#[path = "../examples/computer/assembler.rs"]
+mod assembler;
+#[allow(dead_code)]
+#[path = "../examples/computer/computer.rs"]
+mod computer;
+#[allow(dead_code)]
+#[path = "../examples/computer/control_logic.rs"]
+mod control_logic;
+#[allow(dead_code)]
+#[path = "../examples/computer/instruction_set.rs"]
+mod instruction_set;
+#[allow(dead_code)]
+#[path = "../examples/computer/programs/mod.rs"]
+mod programs;
+use computer::ComputerBuilder;
+
+/// Number of clock cycles measured in every iteration of the cycle benchmark.
+const CYCLES: usize = 100;
+
+fn computer(c: &mut Criterion) {
+    let program = programs::program("multiply").unwrap();
+    let builder = ComputerBuilder::headless(&program.rom())
+        .ram_address_space_bits(program.ram_address_space_bits());
+
+    let build_builder = builder.clone();
+    c.bench_function("computer/build", move |b| b.iter(|| build_builder.build()));
+
+    let mut computer = builder.build();
+    c.bench_function("computer/cycles", move |b| {
+        b.iter(|| {
+            for _ in 0..CYCLES * 2 {
+                computer.flip_clock();
+            }
+            // Don't let the output pile up.
+            computer.output_receiver().unwrap().try_iter().count()
+        })
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = computer
+}
+criterion_main!(benches);
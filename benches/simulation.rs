@@ -0,0 +1,68 @@
+//! Benchmarks of the [reference circuits](logicsim::bench), run them with `cargo bench --features bench`.
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use logicsim::bench::{drive, reference_circuits};
+use logicsim::graph::{OptimizationPass, OptimizerConfig};
+
+/// Number of lever flips measured in every iteration of the tick benchmarks.
+const STEPS: usize = 100;
+
+fn init(c: &mut Criterion) {
+    for circuit in reference_circuits() {
+        let graph = circuit.graph;
+        c.bench_function(&format!("init/{}", circuit.name), move |b| {
+            b.iter_batched(|| graph.clone(), |g| g.init(), BatchSize::LargeInput)
+        });
+    }
+}
+
+fn tick(c: &mut Criterion) {
+    for circuit in reference_circuits() {
+        let mut ig = circuit.graph.init();
+        let levers = circuit.levers;
+        let mut seed = 0;
+        c.bench_function(&format!("tick/{}", circuit.name), move |b| {
+            b.iter(|| {
+                // A new seed every iteration so the same flips don't repeat.
+                seed += 1;
+                drive(&mut ig, &levers, STEPS, seed)
+            })
+        });
+    }
+}
+
+fn optimization_passes(c: &mut Criterion) {
+    for circuit in reference_circuits() {
+        for pass in OptimizationPass::ALL.iter() {
+            let graph = circuit.graph.clone();
+            let config = OptimizerConfig {
+                passes: vec![*pass],
+                iterations: 1,
+                log: false,
+            };
+            c.bench_function(
+                &format!(
+                    "optimize/{}/{}",
+                    pass.name().replace(' ', "_"),
+                    circuit.name
+                ),
+                move |b| {
+                    b.iter_batched(
+                        || graph.clone(),
+                        |mut g| {
+                            g.run_optimizations(&config);
+                            g
+                        },
+                        BatchSize::LargeInput,
+                    )
+                },
+            );
+        }
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = init, tick, optimization_passes
+}
+criterion_main!(benches);
@@ -1,6 +1,5 @@
 use super::instruction_set::{Instruction, InstructionType};
 use auto_from::From;
-pub use logicsim::data_structures::BitIter;
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
 pub enum PointerType {
     RAM,
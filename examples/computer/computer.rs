@@ -1,227 +1,419 @@
 use super::control_logic::*;
-use logicsim::*;
-
-pub struct ComputerIO {
-    pub ig: InitializedGateGraph,
-    pub clock: LeverHandle,
-    pub reset: LeverHandle,
-    pub ack: LeverHandle,
-    pub input: WordInput,
-    pub write_input: LeverHandle,
-    pub input_busy: OutputHandle,
-    pub output: OutputHandle,
-    pub output_updated: OutputHandle,
+use logicsim::prelude::*;
+use logicsim::testing::{MEMORY_READS, MEMORY_WRITES};
+use logicsim::{alu, circuit, io_register, AluOp, InputChannel, OutputChannel, RamPorts};
+use std::sync::mpsc::{Receiver, Sender};
+use std::time::{Duration, Instant};
+
+/// What drives the clock of a [Computer], see [Computer::flip_clock].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClockSource {
+    /// The clock flips as fast as the simulation allows.
+    FreeRunning,
+    /// The clock is throttled to this many cycles per second.
+    Frequency(f64),
+}
+
+/// How much of the inner workings of a [Computer] gets recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TraceLevel {
+    /// Nothing, the fastest option.
+    Off,
+    /// Retired instructions and memory accesses are recorded as events, see [logicsim::testing::PerfCounters].
+    Events,
+    /// Events plus probes on the bus and the registers, see them with RUST_LOG=logicsim=info.
+    Probes,
+}
+
+/// Builds a [Computer], start from one of the presets and change what you need:
+///
+/// - [interactive](ComputerBuilder::interactive): stdin and stdout, with performance counters.
+/// - [headless](ComputerBuilder::headless): output only and no tracing, for benchmarks and tests.
+/// - [debug](ComputerBuilder::debug): slow clock and probes everywhere, to follow the computer as it runs.
+#[derive(Debug, Clone)]
+pub struct ComputerBuilder {
+    rom: Vec<u16>,
+    ram_address_space_bits: usize,
+    input_channel: bool,
+    output_channel: bool,
+    clock_source: ClockSource,
+    trace_level: TraceLevel,
+}
+#[allow(dead_code)]
+impl ComputerBuilder {
+    /// Returns the [interactive](ComputerBuilder::interactive) preset.
+    pub fn new(rom: &[u16]) -> Self {
+        Self::interactive(rom)
+    }
+
+    /// Both io channels, free running clock and [TraceLevel::Events].
+    pub fn interactive(rom: &[u16]) -> Self {
+        Self {
+            rom: rom.to_vec(),
+            ram_address_space_bits: 7,
+            input_channel: true,
+            output_channel: true,
+            clock_source: ClockSource::FreeRunning,
+            trace_level: TraceLevel::Events,
+        }
+    }
+
+    /// Only the output channel, free running clock and [TraceLevel::Off].
+    pub fn headless(rom: &[u16]) -> Self {
+        Self {
+            input_channel: false,
+            trace_level: TraceLevel::Off,
+            ..Self::interactive(rom)
+        }
+    }
+
+    /// Both io channels, a 100Hz clock and [TraceLevel::Probes].
+    pub fn debug(rom: &[u16]) -> Self {
+        Self {
+            clock_source: ClockSource::Frequency(100.0),
+            trace_level: TraceLevel::Probes,
+            ..Self::interactive(rom)
+        }
+    }
+
+    /// Returns the preset called `name`, one of "interactive", "headless" or "debug".
+    pub fn preset(name: &str, rom: &[u16]) -> Option<Self> {
+        Some(match name {
+            "interactive" => Self::interactive(rom),
+            "headless" => Self::headless(rom),
+            "debug" => Self::debug(rom),
+            _ => return None,
+        })
+    }
+
+    /// Number of address bits used by the RAM, the RAM has 2^bits bytes, at most 7.
+    pub fn ram_address_space_bits(mut self, bits: usize) -> Self {
+        self.ram_address_space_bits = bits;
+        self
+    }
+
+    /// Attaches an [InputChannel] to the input register, see [Computer::take_input_sender].
+    pub fn input_channel(mut self, attach: bool) -> Self {
+        self.input_channel = attach;
+        self
+    }
+
+    /// Attaches an [OutputChannel] to the output register, see [Computer::output_receiver].
+    ///
+    /// Without it nothing acknowledges the output register, so programs stall on their second write.
+    pub fn output_channel(mut self, attach: bool) -> Self {
+        self.output_channel = attach;
+        self
+    }
+
+    pub fn clock_source(mut self, clock_source: ClockSource) -> Self {
+        self.clock_source = clock_source;
+        self
+    }
+
+    pub fn trace_level(mut self, trace_level: TraceLevel) -> Self {
+        self.trace_level = trace_level;
+        self
+    }
+
+    /// Builds, initializes and resets the computer.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the RAM address space doesn't fit in the RAM half of the address space.
+    pub fn build(&self) -> Computer {
+        let bits = 8;
+        assert!(
+            self.ram_address_space_bits < bits,
+            "The RAM only has {} bits of address space",
+            bits - 1
+        );
+        let events = self.trace_level >= TraceLevel::Events;
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let bus = Bus::new(g, bits, "main_bus");
+        wire!(g, clock);
+        wire!(g, reset);
+        let clock_lever = clock.make_lever(g);
+        let reset_lever = reset.make_lever(g);
+        let ack_lever = g.lever("ack");
+        let nclock = g.not1(clock.bit(), "nclock");
+
+        let mut rom_data = Vec::new();
+        for word in &self.rom {
+            rom_data.extend_from_slice(&word.to_ne_bytes())
+        }
+
+        let signals = ControlSignalsSet::new(g);
+
+        circuit! { g;
+            // PROGRAM COUNTER
+            let pc = counter(
+                clock.bit(),
+                signals.pc_enable().bit(),
+                signals.jmp().bit(),
+                signals.pc_out().bit(),
+                reset.bit(),
+                bus.bits(),
+            );
+            connect bus <- &pc;
+
+            // REGISTER A
+            let rega_buffer = register(clock.bit(), signals.rega_in().bit(), ON, reset.bit(), bus.bits());
+            let rega = register(nclock, ON, ON, reset.bit(), &rega_buffer);
+            let rega_bus = bus_multiplexer(&[signals.rega_out().bit()], &[&zeros(bits), &rega]);
+            connect bus <- &rega_bus;
+
+            // REGISTER B
+            let regb = register(clock.bit(), signals.regb_in().bit(), ON, reset.bit(), bus.bits());
+            let regb_bus = bus_multiplexer(&[signals.regb_out().bit()], &[&zeros(bits), &regb]);
+            connect bus <- &regb_bus;
+
+            // ALU
+            let alu_output = alu(
+                signals.alu_out().bit(),
+                &[signals.alu_sub().bit(), OFF, OFF],
+                &rega,
+                &regb,
+            );
+            connect bus <- &alu_output.result;
+            // Register A or 0 is register A, the optimizer reduces this ALU to its zero flag.
+            let rega_flags = alu(OFF, &AluOp::Or.bits(), &rega, &zeros(bits));
+        }
+
+        // ADDRESS REGISTER
+        let address_reg_output = register(
+            g,
+            clock.bit(),
+            signals.address_reg_in().bit(),
+            ON,
+            reset.bit(),
+            bus.bits(),
+            "areg",
+        );
+        let address_reg_bus_output = bus_multiplexer(
+            g,
+            &[signals.address_reg_out().bit()],
+            &[&zeros(bits), &address_reg_output],
+            "areg_bus",
+        );
+        bus.connect(g, &address_reg_bus_output);
+        // The first 2^(bits-1) addresses are ROM
+        // The last 2^(bits-1) addresses are RAM
+        // AKA if the last bit of the address register is set it is ROM, RAM otherwise.
+        let ram_address_space_bit = address_reg_output[bits - 1];
+        let rom_address_space_bit = g.not1(ram_address_space_bit, "ram_address_bit");
+
+        // Gates get names like "memory/rom/..." from here on.
+        g.begin_module("memory");
+
+        // ROM
+        let rom_read_enable = g.and2(
+            signals.rom_out().bit(),
+            rom_address_space_bit,
+            "rom_read_enable",
+        );
+        let rom_output = rom(g, rom_read_enable, &address_reg_output, &rom_data, "rom");
+        //g.probe(&rom_output, "rom");
+        bus.connect(g, &rom_output);
+
+        // RAM
+        let ram_read_enable = g.and2(
+            signals.ram_out().bit(),
+            ram_address_space_bit,
+            "ram_read_enable",
+        );
+        let ram_write_enable = g.and2(
+            signals.ram_in().bit(),
+            ram_address_space_bit,
+            "ram_write_enable",
+        );
+        let ram_output = RamPorts {
+            read: ram_read_enable,
+            write: ram_write_enable,
+            clock: clock.bit(),
+            reset: reset.bit(),
+            address: &address_reg_output[0..self.ram_address_space_bits],
+            input: bus.bits(),
+        }
+        .connect(g, "ram");
+        bus.connect(g, &ram_output);
+        if events {
+            g.event(MEMORY_READS, ram_read_enable, &address_reg_output);
+            g.event(MEMORY_WRITES, ram_write_enable, &address_reg_output);
+        }
+        g.end_module();
+
+        g.begin_module("io");
+
+        // OUTPUT REGISTER
+        let rego_output = io_register(
+            g,
+            clock.bit(),
+            signals.rego_in().bit(),
+            ON,
+            reset.bit(),
+            bus.bits(),
+            ack_lever.bit(),
+            "rego",
+        );
+
+        // INPUT REGISTER
+        let regi_input = WordInput::new(g, bits, "regi_input");
+        let regi_write = g.lever("regi_write");
+        let regi_clock = g.or2(clock.bit(), regi_write.bit(), "regi_clock");
+        let (regi_changed, regi_output) = io_register(
+            g,
+            regi_clock,
+            regi_write.bit(),
+            signals.regi_out().bit(),
+            reset.bit(),
+            &regi_input.bits(),
+            signals.regi_ack().bit(),
+            "regi",
+        );
+        let regi_busy_buffer = d_flip_flop(
+            g,
+            regi_changed,
+            nclock,
+            reset.bit(),
+            ON,
+            ON,
+            "regi_busy_buffer",
+        );
+        let regi_busy = g.output1(regi_busy_buffer, "regi_busy");
+
+        bus.connect(g, &regi_output);
+        g.end_module();
+
+        g.module("control_logic", |g| {
+            setup_control_logic(
+                g,
+                rega_flags.zero,
+                regi_changed,
+                bus.clone(),
+                clock.bit(),
+                reset.bit(),
+                signals,
+                events,
+            )
+        });
+
+        #[cfg(feature = "debug_gates")]
+        if self.trace_level >= TraceLevel::Probes {
+            g.probe(bus.bits(), "bus");
+            g.probe(&pc, "pc");
+            g.probe(&rega, "rega");
+            g.probe(&regb, "regb");
+            g.probe(&address_reg_output, "areg");
+        }
+
+        let t = std::time::Instant::now();
+        let output = g.output(&rego_output.1, "output");
+        let output_updated = g.output1(rego_output.0, "updated");
+
+        // Optimizing the computer takes a while, reuse the result from the last run if the circuit hasn't changed.
+        #[cfg(feature = "serde")]
+        let mut ig = graph.init_cached(std::env::temp_dir().join("logicsim_computer.cache"));
+        #[cfg(not(feature = "serde"))]
+        let mut ig = graph.init();
+        ig.run_until_stable(100).unwrap();
+
+        // RESET
+        ig.pulse_lever_stable(reset_lever);
+        println!("Init+reset time: {}ms", t.elapsed().as_millis());
+        println!();
+
+        // The channels take care of the handshakes with the input and output registers on every clock cycle.
+        let input_sender = if self.input_channel {
+            let (channel, sender) = InputChannel::new(regi_input, regi_write, regi_busy);
+            ig.add_peripheral(clock_lever, channel);
+            Some(sender)
+        } else {
+            None
+        };
+        let output_receiver = if self.output_channel {
+            let (channel, receiver) = OutputChannel::new(output, output_updated, ack_lever);
+            ig.add_peripheral(clock_lever, channel);
+            Some(receiver)
+        } else {
+            None
+        };
+
+        Computer {
+            ig,
+            clock: clock_lever,
+            reset: reset_lever,
+            input_sender,
+            output_receiver,
+            clock_source: self.clock_source,
+            trace_level: self.trace_level,
+            last_flip: Instant::now(),
+        }
+    }
 }
 
-pub fn mk_computer(rom_in: &[u16], ram_address_space: usize) -> ComputerIO {
-    let mut graph = GateGraphBuilder::new();
-    let g = &mut graph;
-    let bits = 8;
-
-    let bus = Bus::new(g, bits, "main_bus");
-    wire!(g, clock);
-    wire!(g, reset);
-    let clock_lever = clock.make_lever(g);
-    let reset_lever = reset.make_lever(g);
-    let ack_lever = g.lever("ack");
-    let nclock = g.not1(clock.bit(), "nclock");
-
-    let mut rom_data = Vec::new();
-    for word in rom_in {
-        rom_data.extend_from_slice(&word.to_ne_bytes())
-    }
-
-    let signals = ControlSignalsSet::new(g);
-
-    // PROGRAM COUNTER
-    let pc_output = counter(
-        g,
-        clock.bit(),
-        signals.pc_enable().bit(),
-        signals.jmp().bit(),
-        signals.pc_out().bit(),
-        reset.bit(),
-        bus.bits(),
-        "pc",
-    );
-    bus.connect(g, &pc_output);
-
-    // REGISTER A
-    let rega_buffer = register(
-        g,
-        clock.bit(),
-        signals.rega_in().bit(),
-        ON,
-        reset.bit(),
-        bus.bits(),
-        "rega_buffer",
-    );
-    let rega_output = register(g, nclock, ON, ON, reset.bit(), &rega_buffer, "rega");
-    let rega_bus_output = bus_multiplexer(
-        g,
-        &[signals.rega_out().bit()],
-        &[&zeros(bits), &rega_output],
-        "rega_bus",
-    );
-    bus.connect(g, &rega_bus_output);
-
-    // REGISTER B
-    let regb_output = register(
-        g,
-        clock.bit(),
-        signals.regb_in().bit(),
-        ON,
-        reset.bit(),
-        bus.bits(),
-        "regb",
-    );
-    let regb_bus_output = bus_multiplexer(
-        g,
-        &[signals.regb_out().bit()],
-        &[&zeros(bits), &regb_output],
-        "regb_bus",
-    );
-    bus.connect(g, &regb_bus_output);
-
-    // ALU
-    let alu_output = aluish(
-        g,
-        signals.cin().bit(),
-        signals.alu_out().bit(),
-        signals.alu_invert_regb().bit(),
-        &rega_output,
-        &regb_output,
-        "alu",
-    );
-    bus.connect(g, &alu_output);
-
-    // ADDRESS REGISTER
-    let address_reg_output = register(
-        g,
-        clock.bit(),
-        signals.address_reg_in().bit(),
-        ON,
-        reset.bit(),
-        bus.bits(),
-        "areg",
-    );
-    let address_reg_bus_output = bus_multiplexer(
-        g,
-        &[signals.address_reg_out().bit()],
-        &[&zeros(bits), &address_reg_output],
-        "areg_bus",
-    );
-    bus.connect(g, &address_reg_bus_output);
-    // The first 2^(bits-1) addresses are ROM
-    // The last 2^(bits-1) addresses are RAM
-    // AKA if the last bit of the address register is set it is ROM, RAM otherwise.
-    let ram_address_space_bit = address_reg_output[bits - 1];
-    let rom_address_space_bit = g.not1(ram_address_space_bit, "ram_address_bit");
-
-    // ROM
-    let rom_read_enable = g.and2(
-        signals.rom_out().bit(),
-        rom_address_space_bit,
-        "rom_read_enable",
-    );
-    let rom_output = rom(g, rom_read_enable, &address_reg_output, &rom_data, "rom");
-    //g.probe(&rom_output, "rom");
-    bus.connect(g, &rom_output);
-
-    // RAM
-    let ram_read_enable = g.and2(
-        signals.ram_out().bit(),
-        ram_address_space_bit,
-        "ram_read_enable",
-    );
-    let ram_write_enable = g.and2(
-        signals.ram_in().bit(),
-        ram_address_space_bit,
-        "ram_write_enable",
-    );
-    let ram_output = ram(
-        g,
-        ram_read_enable,
-        ram_write_enable,
-        clock.bit(),
-        reset.bit(),
-        &address_reg_output[0..ram_address_space],
-        bus.bits(),
-        "ram",
-    );
-    bus.connect(g, &ram_output);
-
-    // OUTPUT REGISTER
-    let rego_output = io_register(
-        g,
-        clock.bit(),
-        signals.rego_in().bit(),
-        ON,
-        reset.bit(),
-        bus.bits(),
-        ack_lever.bit(),
-        "rego",
-    );
-
-    // INPUT REGISTER
-    let regi_input = WordInput::new(g, bits, "regi_input");
-    let regi_write = g.lever("regi_write");
-    let regi_clock = g.or2(clock.bit(), regi_write.bit(), "regi_clock");
-    let (regi_changed, regi_output) = io_register(
-        g,
-        regi_clock,
-        regi_write.bit(),
-        signals.regi_out().bit(),
-        reset.bit(),
-        &regi_input.bits(),
-        signals.regi_ack().bit(),
-        "regi",
-    );
-    let regi_busy_buffer = d_flip_flop(
-        g,
-        regi_changed,
-        nclock,
-        reset.bit(),
-        ON,
-        ON,
-        "regi_busy_buffer",
-    );
-    let regi_busy = g.output1(regi_busy_buffer, "regi_busy");
-
-    bus.connect(g, &regi_output);
-
-    let rega_zero = bus_multiplexer(g, &rega_output, &[&ones(1)], "rega_zero");
-    setup_control_logic(
-        g,
-        rega_zero[0],
-        regi_changed,
-        bus.clone(),
-        clock.bit(),
-        reset.bit(),
-        signals,
-    );
-
-    let t = std::time::Instant::now();
-    let output = g.output(&rego_output.1, "output");
-    let output_updated = g.output1(rego_output.0, "updated");
-
-    let mut ig = graph.init();
-    ig.run_until_stable(100).unwrap();
-
-    // RESET
-    ig.pulse_lever_stable(reset_lever);
-    println!("Init+reset time: {}ms", t.elapsed().as_millis());
-    println!("");
-
-    ComputerIO {
-        ig,
-        clock: clock_lever,
-        reset: reset_lever,
-        ack: ack_lever,
-        input: regi_input,
-        write_input: regi_write,
-        input_busy: regi_busy,
-        output,
-        output_updated,
+/// 8 bit computer built by a [ComputerBuilder].
+pub struct Computer {
+    ig: InitializedGateGraph,
+    clock: LeverHandle,
+    reset: LeverHandle,
+    input_sender: Option<Sender<u8>>,
+    output_receiver: Option<Receiver<u8>>,
+    clock_source: ClockSource,
+    trace_level: TraceLevel,
+    last_flip: Instant,
+}
+#[allow(dead_code)]
+impl Computer {
+    pub fn ig(&self) -> &InitializedGateGraph {
+        &self.ig
+    }
+
+    pub fn ig_mut(&mut self) -> &mut InitializedGateGraph {
+        &mut self.ig
+    }
+
+    pub fn clock(&self) -> LeverHandle {
+        self.clock
+    }
+
+    pub fn reset(&self) -> LeverHandle {
+        self.reset
+    }
+
+    pub fn clock_source(&self) -> ClockSource {
+        self.clock_source
+    }
+
+    pub fn trace_level(&self) -> TraceLevel {
+        self.trace_level
+    }
+
+    /// Returns the [Sender] of the input channel so it can be moved to another thread,
+    /// [None] if it wasn't attached or it has already been taken.
+    pub fn take_input_sender(&mut self) -> Option<Sender<u8>> {
+        self.input_sender.take()
+    }
+
+    /// Returns the [Receiver] of the output channel, [None] if it wasn't attached.
+    pub fn output_receiver(&self) -> Option<&Receiver<u8>> {
+        self.output_receiver.as_ref()
+    }
+
+    /// Flips the clock and runs the computer until it's stable, every 2 flips are a clock cycle.
+    ///
+    /// With [ClockSource::Frequency] it first waits until half a period has passed since the last flip.
+    pub fn flip_clock(&mut self) {
+        if let ClockSource::Frequency(hz) = self.clock_source {
+            let half_period = Duration::from_secs_f64(0.5 / hz);
+            let elapsed = self.last_flip.elapsed();
+            if elapsed < half_period {
+                std::thread::sleep(half_period - elapsed);
+            }
+            self.last_flip = Instant::now();
+        }
+        self.ig.flip_lever_stable(self.clock);
     }
 }
@@ -1,5 +1,7 @@
 use super::instruction_set::{InstructionType, DATA_LENGTH, OPCODE_LENGTH};
-use logicsim::*;
+use logicsim::prelude::*;
+use logicsim::testing::INSTRUCTIONS_RETIRED;
+use logicsim::{matches_constant, minimized_rom};
 use std::convert::TryInto;
 use strum::EnumCount;
 
@@ -17,8 +19,7 @@ control_signal_set!(
     address_reg_out,
     jmp,
     pc_out,
-    cin,
-    alu_invert_regb,
+    alu_sub,
     address_reg_in,
     ior_in,
     idr_in,
@@ -28,9 +29,10 @@ control_signal_set!(
     regi_out,
     regi_ack
 );
-// 22
+// 21
 
 const INSTRUCTION_COUNTER_BITS: u32 = 3;
+const INSTRUCTION_FETCH_STEPS: u8 = 4;
 const IS_REGA_ZERO_BITS: u32 = 1;
 const HAS_REGI_CHANGED_BITS: u32 = 1;
 const MICROINSTRUCTION_INPUT_BITS: u32 =
@@ -44,8 +46,9 @@ const OPCODE_OFFSET: u32 = HAS_REGI_CHANGED_OFFSET + HAS_REGI_CHANGED_BITS;
 // | INSTRUCTION COUNTER | IS REGA ZERO | HAS REGI CHANGED |    INSTRUCTION OPCODE      |
 // |         3 bits      |     1bit     |       1bit       |          8 bits            |
 // |        b0 b1 b2     |      b3      |        b4        | b5 b6 b7 b8 b9 b10 b11 b12 |
-fn build_microinstructions() -> Vec<u32> {
-    let mut out = vec![0; 1 << MICROINSTRUCTION_INPUT_BITS];
+// Opcodes that don't correspond to any instruction are left as don't cares.
+fn build_microinstructions() -> Vec<Option<u32>> {
+    let mut out = vec![None; 1 << MICROINSTRUCTION_INPUT_BITS];
     // FIXED SECTION
     let instruction_fetch = [
         signals_to_bits!(ControlSignalsSet, pc_out, address_reg_in),
@@ -66,8 +69,9 @@ fn build_microinstructions() -> Vec<u32> {
                         | (opcode << OPCODE_OFFSET);
 
                     // The first 2 microinstructions are always the instruction fetch.
+                    out[input] = Some(0);
                     if instruction_step < instruction_fetch.len() {
-                        out[input as usize] = instruction_fetch[instruction_step as usize];
+                        out[input] = Some(instruction_fetch[instruction_step]);
                     } else {
                         // Instruction step after fetch.
                         let relative_instruction_step = instruction_step - instruction_fetch.len();
@@ -75,12 +79,12 @@ fn build_microinstructions() -> Vec<u32> {
                         if let (Ok(instruction), 0..=2) =
                             ((opcode as u8).try_into(), relative_instruction_step)
                         {
-                            out[input] = microinstructions_from_instruction(
+                            out[input] = Some(microinstructions_from_instruction(
                                 instruction,
                                 relative_instruction_step,
                                 is_rega_zero,
                                 has_regi_changed,
-                            )
+                            ))
                         }
                     }
                 }
@@ -147,14 +151,7 @@ fn microinstructions_from_instruction(
             0,
         ],
         SUB => [
-            signals_to_bits!(
-                ControlSignalsSet,
-                alu_invert_regb,
-                cin,
-                alu_out,
-                rega_in,
-                ic_reset
-            ),
+            signals_to_bits!(ControlSignalsSet, alu_sub, alu_out, rega_in, ic_reset),
             0,
             0,
         ],
@@ -197,6 +194,7 @@ fn microinstructions_from_instruction(
     micro[instruction_step]
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn setup_control_logic(
     g: &mut GateGraphBuilder,
     rega_zero: GateIndex,
@@ -205,6 +203,7 @@ pub fn setup_control_logic(
     clock: GateIndex,
     reset: GateIndex,
     mut signals: ControlSignalsSet,
+    events: bool,
 ) {
     // INSTRUCTION OPCODE REGISTER
     let ior_output = register(
@@ -247,6 +246,19 @@ pub fn setup_control_logic(
         "ic",
     );
 
+    // INSTRUCTION TRACE
+    // Once the fetch is over the opcode register holds the instruction that is about to execute,
+    // from then on it will retire, there is no pipeline to flush.
+    if events {
+        let executing = matches_constant(
+            g,
+            &instruction_counter,
+            INSTRUCTION_FETCH_STEPS,
+            "executing",
+        );
+        g.event(INSTRUCTIONS_RETIRED, executing, &ior_output);
+    }
+
     // MICROINSTRUCTION ROM
     let microinstruction_input: Vec<_> = instruction_counter
         .into_iter()
@@ -255,7 +267,7 @@ pub fn setup_control_logic(
         .chain(ior_output)
         .collect();
 
-    let microinstruction_rom_output = rom(
+    let microinstruction_rom_output = minimized_rom(
         g,
         ON,
         &microinstruction_input,
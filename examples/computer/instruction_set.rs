@@ -48,9 +48,9 @@ impl InstructionType {
         Instruction { ty: *self, data: 0 }
     }
 }
-impl Into<u16> for InstructionType {
-    fn into(self) -> u16 {
-        self.with_0().into()
+impl From<InstructionType> for u16 {
+    fn from(ty: InstructionType) -> Self {
+        ty.with_0().into()
     }
 }
 
@@ -59,8 +59,8 @@ pub struct Instruction {
     pub ty: InstructionType,
     pub data: u8,
 }
-impl Into<u16> for Instruction {
-    fn into(self) -> u16 {
-        self.ty as u16 | ((self.data as u16) << OPCODE_LENGTH)
+impl From<Instruction> for u16 {
+    fn from(instruction: Instruction) -> Self {
+        instruction.ty as u16 | ((instruction.data as u16) << OPCODE_LENGTH)
     }
 }
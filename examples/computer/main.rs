@@ -1,3 +1,4 @@
+#![allow(clippy::upper_case_acronyms)]
 #[macro_use]
 #[allow(dead_code)]
 mod assembler;
@@ -6,100 +7,124 @@ mod computer;
 mod instruction_set;
 #[allow(dead_code)]
 mod programs;
-mod stdin_peekable;
 use clock_timer::ClockTimer;
-use computer::{mk_computer, ComputerIO};
+use computer::{ComputerBuilder, TraceLevel};
+use instruction_set::InstructionType;
+use logicsim::testing::{PerfCounters, INSTRUCTIONS_RETIRED};
 use programs::{list_programs, program, OutputType};
-use std::io::Write;
+use std::convert::TryFrom;
+use std::io::{BufReader, Read, Write};
 use std::sync::atomic::{AtomicBool, Ordering};
-use stdin_peekable::StdinPeekable;
 #[macro_use]
 extern crate strum_macros;
 mod control_logic;
 
 fn main() {
+    // Optimizer statistics and probes are logged, see them with RUST_LOG=logicsim=info.
+    env_logger::init();
+
     // Handle ctrl-c
     static STOP: AtomicBool = AtomicBool::new(false);
     ctrlc::set_handler(|| STOP.store(true, Ordering::Relaxed)).unwrap();
 
     let program_name = std::env::args()
-        .skip(1)
-        .next()
+        .nth(1)
         .expect("Please provide a program name as the first argument.");
 
     let selected_program = if let Some(p) = program(&program_name) {
         p
     } else {
         panic!(
-            "\nSelected program not available: {}, available programs:\n{}\n",
+            "\nSelected program not available: {}, available programs:\n{}\nor a ROM image in a .hex or .bin file\n",
             program_name,
             list_programs().join("\n")
         )
     };
 
-    let ComputerIO {
-        ack,
-        clock,
-        mut ig,
-        write_input,
-        input,
-        input_busy,
-        output,
-        output_updated,
-        ..
-    } = mk_computer(
-        &selected_program.rom(),
-        selected_program.ram_address_space_bits(),
-    );
+    // Pick a preset with COMPUTER_PRESET=interactive|headless|debug, see ComputerBuilder.
+    let preset = std::env::var("COMPUTER_PRESET").unwrap_or_else(|_| "interactive".to_string());
+    let rom = selected_program.rom();
+    let builder = ComputerBuilder::preset(&preset, &rom)
+        .unwrap_or_else(|| panic!("Unknown preset: {}", preset))
+        .ram_address_space_bits(selected_program.ram_address_space_bits());
+    let mut computer = builder.build();
 
-    let ig = &mut ig;
-    ig.dump_dot("computer_optimized.dot");
+    let dot_path = std::env::temp_dir().join("computer_optimized.dot");
+    computer.ig().dump_dot(&dot_path).unwrap();
 
-    let mut should_reset_ack = false;
-    let mut stdin = StdinPeekable::new();
+    // Optional state file to suspend the computer on ctrl-c and resume it on the next run.
+    #[cfg(feature = "serde")]
+    let state_path = std::env::args().nth(2);
+    #[cfg(feature = "serde")]
+    let resumed = match &state_path {
+        Some(path) if std::path::Path::new(path).exists() => {
+            computer
+                .ig_mut()
+                .load_state(path)
+                .expect("Failed to load the computer state");
+            println!("Resumed from {}", path);
+            true
+        }
+        _ => false,
+    };
+    #[cfg(not(feature = "serde"))]
+    let resumed = false;
+
+    if let Some(input_sender) = computer.take_input_sender() {
+        std::thread::spawn(move || {
+            for byte in BufReader::new(std::io::stdin()).bytes().flatten() {
+                if input_sender.send(byte).is_err() {
+                    break;
+                }
+            }
+        });
+    }
     let output_type = selected_program.output_type();
 
+    let mut counters = PerfCounters::new();
     let mut timer = ClockTimer::new(selected_program.clock_print_interval());
-    for i in 0..std::u32::MAX {
-        if STOP.load(Ordering::Relaxed) {
+    // The state is saved in between clock cycles, after the channels have been updated.
+    for i in (resumed as u32)..u32::MAX {
+        if i % 2 == 1 && STOP.load(Ordering::Relaxed) {
+            #[cfg(feature = "serde")]
+            if let Some(path) = &state_path {
+                computer
+                    .ig_mut()
+                    .save_state(path)
+                    .expect("Failed to save the computer state");
+                eprintln!("\nSuspended to {}", path);
+            }
             break;
         }
 
-        ig.flip_lever_stable(clock);
+        computer.flip_clock();
 
-        // If there's data in stdin and the computer is not busy handling input,
-        // input some data.
-        if let (Some(c), false) = (stdin.peek(), input_busy.b0(ig)) {
-            input.set_to(ig, c);
-            ig.pulse_lever_stable(write_input);
-            stdin.next();
+        // Instruction trace, see it with RUST_LOG=computer::trace=debug.
+        let events = computer.ig_mut().take_events();
+        for event in events.iter().filter(|e| e.name == INSTRUCTIONS_RETIRED) {
+            let instruction = InstructionType::try_from(event.value as u8);
+            tracing::debug!(target: "computer::trace", "{}: {:?}", event.tick, instruction);
         }
+        counters.record(&events);
 
-        // Since the acknowledgement is synchronous we need to leave the lever on
-        // during a clock cycle and then turn it off.
-        if should_reset_ack {
-            ig.reset_lever(ack);
-            should_reset_ack = false
-        }
-
-        // If the computer has updated its output, print it, and send an acknowledgement
-        // to the computer that we have consumed its output.
-        if output_updated.b0(ig) && i % 2 == 1 {
-            match output_type {
-                OutputType::Number => {
-                    print!("{}", output.u8(ig));
-                }
-                OutputType::Text => {
-                    print!("{}", output.char(ig));
-                }
-            }
-            std::io::stdout().flush().unwrap();
-            ig.set_lever(ack);
-            should_reset_ack = true;
-        }
         if i % 2 == 1 {
             // Every 2 flips it's a clock cycle.
+            for c in computer
+                .output_receiver()
+                .into_iter()
+                .flat_map(|r| r.try_iter())
+            {
+                match output_type {
+                    OutputType::Number => print!("{}", c),
+                    OutputType::Text => print!("{}", c as char),
+                }
+                std::io::stdout().flush().unwrap();
+            }
             timer.clock();
+            counters.cycle();
         }
     }
+    if computer.trace_level() >= TraceLevel::Events {
+        eprintln!("\n{}", counters);
+    }
 }
@@ -5,7 +5,7 @@ pub struct Greeter();
 impl Program for Greeter {
     fn clock_print_interval(&self) -> u64 {
         // We don't want the clock times to interrupt our nice dialog.
-        std::u64::MAX
+        u64::MAX
     }
     fn output_type(&self) -> OutputType {
         OutputType::Text
@@ -15,7 +15,7 @@ impl Program for Greeter {
         5
     }
     fn rom(&self) -> Vec<u16> {
-        let newline = '\n' as u8;
+        let newline = b'\n';
         let hello_data = "\nWhat's your name? ".chars().map(|c| c as u8);
         let nice_to_meet_data = "Nice to meet you ".chars().map(|c| c as u8);
         assemble!(
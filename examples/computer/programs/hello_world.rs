@@ -3,7 +3,7 @@ use super::{super::instruction_set::InstructionType::*, OutputType, Program};
 pub struct HelloWorld();
 impl Program for HelloWorld {
     fn clock_print_interval(&self) -> u64 {
-        std::u64::MAX
+        u64::MAX
     }
     fn output_type(&self) -> OutputType {
         OutputType::Text
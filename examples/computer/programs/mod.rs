@@ -1,6 +1,7 @@
 mod greeter;
 mod hello_world;
 mod multiply;
+use logicsim::circuits::{read_rom_image, RomFormat};
 
 pub enum OutputType {
     Text,
@@ -17,6 +18,11 @@ pub fn list_programs() -> Vec<&'static str> {
 }
 // I'll forgive myself for using dynamic dispatch on this one.
 pub fn program(name: &str) -> Option<Box<dyn Program>> {
+    if let Some(format) = RomFormat::from_extension(name) {
+        let image = read_rom_image(name, format)
+            .unwrap_or_else(|e| panic!("Failed to read ROM image {}: {}", name, e));
+        return Some(Box::new(RomImage(image)));
+    }
     Some(match name {
         "greeter" => Box::new(greeter::Greeter()),
         "hello_world" => Box::new(hello_world::HelloWorld()),
@@ -24,3 +30,23 @@ pub fn program(name: &str) -> Option<Box<dyn Program>> {
         _ => return None,
     })
 }
+
+/// Program assembled elsewhere and loaded from an Intel HEX (.hex) or raw binary (.bin) file.
+struct RomImage(Vec<u8>);
+impl Program for RomImage {
+    fn output_type(&self) -> OutputType {
+        OutputType::Text
+    }
+    fn ram_address_space_bits(&self) -> usize {
+        7
+    }
+    fn clock_print_interval(&self) -> u64 {
+        u64::MAX
+    }
+    fn rom(&self) -> Vec<u16> {
+        self.0
+            .chunks(2)
+            .map(|word| u16::from_ne_bytes([word[0], word.get(1).copied().unwrap_or(0)]))
+            .collect()
+    }
+}
@@ -8,7 +8,7 @@ const NUMBER2: u8 = 7;
 pub struct Multiply();
 impl Program for Multiply {
     fn clock_print_interval(&self) -> u64 {
-        std::u64::MAX
+        u64::MAX
     }
     fn output_type(&self) -> OutputType {
         OutputType::Number
@@ -0,0 +1,10 @@
+//! Writes the circuit gallery to the directory passed as the first argument, "gallery" by default.
+use logicsim::testing::gallery;
+
+fn main() {
+    let dir = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "gallery".to_string());
+    let names = gallery(&dir).expect("Failed to write the gallery");
+    println!("Wrote {} circuits to {}", names.len(), dir);
+}
@@ -0,0 +1,81 @@
+//! Writes a message to the character RAM of a text mode display, runs it for a frame and prints what the monitor saw.
+//! If a path is passed as the first argument, the frame is also written there as a PBM image.
+use logicsim::{text_display, DisplayMonitor, GateGraphBuilder, TextDisplayTiming, WordInput};
+
+const MESSAGE: &[u8] = b"HELLO WORLD!";
+
+/// Returns a font for the 128 ASCII characters where only the ones in [MESSAGE] are drawn.
+fn font() -> Vec<u8> {
+    let glyphs: &[(u8, [u8; 8])] = &[
+        (b'H', [0x44, 0x44, 0x44, 0x7c, 0x44, 0x44, 0x44, 0x00]),
+        (b'E', [0x7c, 0x40, 0x40, 0x78, 0x40, 0x40, 0x7c, 0x00]),
+        (b'L', [0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x7c, 0x00]),
+        (b'O', [0x38, 0x44, 0x44, 0x44, 0x44, 0x44, 0x38, 0x00]),
+        (b'W', [0x44, 0x44, 0x44, 0x54, 0x54, 0x6c, 0x44, 0x00]),
+        (b'R', [0x78, 0x44, 0x44, 0x78, 0x50, 0x48, 0x44, 0x00]),
+        (b'D', [0x78, 0x44, 0x44, 0x44, 0x44, 0x44, 0x78, 0x00]),
+        (b'!', [0x10, 0x10, 0x10, 0x10, 0x10, 0x00, 0x10, 0x00]),
+    ];
+    let mut font = vec![0; 128 * 8];
+    for (c, glyph) in glyphs {
+        let start = *c as usize * 8;
+        font[start..start + 8].copy_from_slice(glyph);
+    }
+    font
+}
+
+fn main() {
+    let timing = TextDisplayTiming {
+        columns: MESSAGE.len(),
+        rows: 1,
+        h_front_porch: 2,
+        h_sync: 4,
+        h_back_porch: 2,
+        v_front_porch: 1,
+        v_sync: 2,
+        v_back_porch: 1,
+    };
+
+    let mut g = GateGraphBuilder::new();
+    let clock = g.lever("clock");
+    let write = g.lever("write");
+    let reset = g.lever("reset");
+    let address = WordInput::new(&mut g, timing.address_bits(), "address");
+    let input = WordInput::new(&mut g, 7, "input");
+    let display = text_display(
+        &mut g,
+        clock.bit(),
+        write.bit(),
+        reset.bit(),
+        &address.bits(),
+        &input.bits(),
+        &font(),
+        timing,
+        "display",
+    );
+    let (monitor, frames) = DisplayMonitor::new(&mut g, &display, "monitor");
+
+    let ig = &mut g.init();
+    ig.pulse_lever_stable(reset);
+    for (column, c) in MESSAGE.iter().enumerate() {
+        address.set_to(ig, column as u8);
+        input.set_to(ig, *c);
+        ig.set_lever_stable(write);
+        ig.pulse_lever_stable(clock);
+        ig.reset_lever_stable(write);
+    }
+
+    // The monitor needs a vertical sync before it can show a whole frame.
+    ig.add_peripheral(clock, monitor);
+    for _ in 0..timing.frame_cycles() * 2 {
+        ig.pulse_lever_stable(clock);
+    }
+    let frame = frames.try_iter().last().expect("No frame was completed");
+    print!("{}", frame);
+
+    if let Some(path) = std::env::args().nth(1) {
+        let file = std::fs::File::create(&path).expect("Failed to create the image");
+        frame.write_pbm(file).expect("Failed to write the image");
+        println!("Wrote the frame to {}", path);
+    }
+}
@@ -0,0 +1,320 @@
+//! Web based logic analyzer to watch many outputs at once while the simulation runs.
+//!
+//! [LogicAnalyzer] serves a viewer on a local port and streams the values of the outputs to it at the end of every
+//! tick, using the lines of an [InitializedGateGraph::add_json_trace] trace. Recorded traces can also be turned
+//! into a self contained HTML file with [write_offline_viewer].
+//!
+//! Probes are printed through the logger, to see their gates in the analyzer declare an output next to them.
+//!
+//! Only available with the "logic_analyzer" feature.
+use crate::graph::{InitializedGateGraph, JsonTraceHandle, OutputHandle};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Condvar, Mutex};
+
+/// Viewer served by [LogicAnalyzer] and written by [write_offline_viewer].
+const VIEWER: &str = include_str!("viewer.html");
+/// Placeholder in [VIEWER] replaced by the samples in offline viewers.
+const EMBEDDED_TRACE: &str = "/*TRACE*/null";
+
+/// Trace lines shared between the simulation and the connections to the viewer.
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    updated: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    lines: Vec<String>,
+    closed: bool,
+}
+
+/// Local web server that shows the values of outputs of an [InitializedGateGraph] in a logic analyzer like viewer,
+/// open the address returned by [local_addr](LogicAnalyzer::local_addr) in a browser.
+///
+/// Every connected viewer receives every sample since the analyzer was created, followed by the new ones as they
+/// are recorded. The server stops when the [LogicAnalyzer] is dropped.
+///
+/// # Example
+/// ```no_run
+/// # use logicsim::{GateGraphBuilder,counter,zeros,ON,OFF};
+/// # use logicsim::analyzer::LogicAnalyzer;
+/// let mut g = GateGraphBuilder::new();
+/// let clock = g.lever("clock");
+/// let reset = g.lever("reset");
+/// let count = counter(&mut g, clock.bit(), ON, OFF, ON, reset.bit(), &zeros(8), "counter");
+/// let count = g.output(&count, "count");
+///
+/// let ig = &mut g.init();
+/// let analyzer = LogicAnalyzer::serve("127.0.0.1:8080").unwrap();
+/// analyzer.attach(ig, &[count]);
+/// println!("Open http://{}", analyzer.local_addr());
+/// ig.pulse_lever_stable(reset);
+/// loop {
+///     ig.pulse_lever_stable(clock);
+///     std::thread::sleep(std::time::Duration::from_millis(100));
+/// }
+/// ```
+pub struct LogicAnalyzer {
+    shared: Arc<Shared>,
+    addr: SocketAddr,
+}
+impl LogicAnalyzer {
+    /// Starts serving the viewer on `addr` from a background thread.
+    /// Use port 0 to let the operating system pick a free one.
+    pub fn serve<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let shared = Arc::new(Shared::default());
+        let server_shared = shared.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if server_shared.state.lock().unwrap().closed {
+                    return;
+                }
+                if let Ok(stream) = stream {
+                    let shared = server_shared.clone();
+                    // Errors only mean the viewer went away.
+                    std::thread::spawn(move || handle_connection(stream, &shared).ok());
+                }
+            }
+        });
+        Ok(Self { shared, addr })
+    }
+
+    /// Returns the address the viewer is being served on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns a writer for [InitializedGateGraph::add_json_trace] that sends every line to the viewers.
+    pub fn writer(&self) -> LogicAnalyzerWriter {
+        LogicAnalyzerWriter {
+            shared: self.shared.clone(),
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Starts sending the values of `outputs` at the end of every tick of `ig`, see
+    /// [InitializedGateGraph::add_json_trace].
+    pub fn attach(
+        &self,
+        ig: &mut InitializedGateGraph,
+        outputs: &[OutputHandle],
+    ) -> JsonTraceHandle {
+        ig.add_json_trace(outputs, self.writer())
+    }
+}
+
+impl Drop for LogicAnalyzer {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().closed = true;
+        self.shared.updated.notify_all();
+        // Wakes up the server thread so it sees it's closed.
+        TcpStream::connect(self.addr).ok();
+    }
+}
+
+/// Writer returned by [LogicAnalyzer::writer].
+pub struct LogicAnalyzerWriter {
+    shared: Arc<Shared>,
+    /// Start of a line that hasn't been completely written yet.
+    buffer: Vec<u8>,
+}
+impl Write for LogicAnalyzerWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if let Some(end) = self.buffer.iter().rposition(|c| *c == b'\n') {
+            let complete: Vec<u8> = self.buffer.drain(..=end).collect();
+            let complete = String::from_utf8(complete)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+            let mut state = self.shared.state.lock().unwrap();
+            state
+                .lines
+                .extend(complete.lines().map(|line| line.to_string()));
+            self.shared.updated.notify_all();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Serves the viewer on "/" and the samples as [server-sent events](https://html.spec.whatwg.org/multipage/server-sent-events.html)
+/// on "/events".
+fn handle_connection(mut stream: TcpStream, shared: &Shared) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // Skip the headers.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let path = request.split_whitespace().nth(1).unwrap_or("");
+    match path {
+        "/" => write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            VIEWER.len(),
+            VIEWER
+        ),
+        "/events" => {
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\r\n"
+            )?;
+            let mut sent = 0;
+            loop {
+                let lines: Vec<String> = {
+                    let mut state = shared.state.lock().unwrap();
+                    while state.lines.len() == sent && !state.closed {
+                        state = shared.updated.wait(state).unwrap();
+                    }
+                    if state.closed {
+                        return Ok(());
+                    }
+                    state.lines[sent..].to_vec()
+                };
+                sent += lines.len();
+                for line in lines {
+                    write!(stream, "data: {}\n\n", line)?;
+                }
+                stream.flush()?;
+            }
+        }
+        _ => write!(
+            stream,
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        ),
+    }
+}
+
+/// Writes a self contained HTML viewer, like the one served by [LogicAnalyzer], with the samples of `trace`
+/// embedded in it, `trace` must contain lines written by a trace added with [InitializedGateGraph::add_json_trace].
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,counter,zeros,ON,OFF};
+/// # use logicsim::analyzer::write_offline_viewer;
+/// let mut g = GateGraphBuilder::new();
+/// let clock = g.lever("clock");
+/// let reset = g.lever("reset");
+/// let count = counter(&mut g, clock.bit(), ON, OFF, ON, reset.bit(), &zeros(4), "counter");
+/// let count = g.output(&count, "count");
+///
+/// let ig = &mut g.init();
+/// ig.pulse_lever_stable(reset);
+/// let trace = std::env::temp_dir().join("logicsim_analyzer_doctest.ndjson");
+/// let handle = ig.add_json_trace(&[count], std::fs::File::create(&trace).unwrap());
+/// for _ in 0..10 {
+///     ig.pulse_lever_stable(clock);
+/// }
+/// ig.remove_json_trace(handle).unwrap();
+///
+/// let mut html = Vec::new();
+/// let file = std::io::BufReader::new(std::fs::File::open(&trace).unwrap());
+/// write_offline_viewer(file, &mut html).unwrap();
+/// assert!(String::from_utf8(html).unwrap().contains(r#""count":10}"#));
+/// # std::fs::remove_file(&trace).unwrap();
+/// ```
+pub fn write_offline_viewer<R: BufRead, W: Write>(trace: R, mut writer: W) -> io::Result<()> {
+    let mut samples = String::from("[");
+    for line in trace.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if samples.len() > 1 {
+            samples.push(',');
+        }
+        // Names can't close the script tag.
+        samples.push_str(&line.replace("</", "<\\/"));
+    }
+    samples.push(']');
+    writer.write_all(VIEWER.replacen(EMBEDDED_TRACE, &samples, 1).as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuits::{counter, zeros};
+    use crate::graph::{GateGraphBuilder, OFF, ON};
+    use std::io::Read;
+
+    fn get(addr: SocketAddr, path: &str) -> TcpStream {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        stream
+            .set_read_timeout(Some(std::time::Duration::from_secs(10)))
+            .unwrap();
+        stream
+    }
+
+    #[test]
+    fn test_logic_analyzer() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let count = counter(
+            g,
+            clock.bit(),
+            ON,
+            OFF,
+            ON,
+            reset.bit(),
+            &zeros(4),
+            "counter",
+        );
+        let count = g.output(&count, "count");
+        let ig = &mut graph.init();
+        ig.pulse_lever_stable(reset);
+
+        let analyzer = LogicAnalyzer::serve("127.0.0.1:0").unwrap();
+        analyzer.attach(ig, &[count]);
+        ig.pulse_lever_stable(clock);
+
+        let mut viewer = String::new();
+        get(analyzer.local_addr(), "/")
+            .read_to_string(&mut viewer)
+            .unwrap();
+        assert!(viewer.starts_with("HTTP/1.1 200 OK"));
+        assert!(viewer.contains("EventSource"));
+
+        let mut events = BufReader::new(get(analyzer.local_addr(), "/events"));
+        // Samples recorded before connecting and after.
+        ig.pulse_lever_stable(clock);
+        let mut data = Vec::new();
+        let mut line = String::new();
+        while !line.contains(r#""count":2}"#) {
+            line.clear();
+            events.read_line(&mut line).unwrap();
+            if let Some(sample) = line.strip_prefix("data: ") {
+                data.push(sample.trim().to_string());
+            }
+        }
+        assert!(data.iter().any(|sample| sample.ends_with(r#""count":1}"#)));
+        assert!(data[0].starts_with(r#"{"tick":"#));
+
+        let mut not_found = String::new();
+        get(analyzer.local_addr(), "/nope")
+            .read_to_string(&mut not_found)
+            .unwrap();
+        assert!(not_found.starts_with("HTTP/1.1 404"));
+    }
+
+    #[test]
+    fn test_offline_viewer() {
+        let trace = "{\"tick\":1,\"</script>\":1}\n\n{\"tick\":2,\"</script>\":0}\n";
+        let mut html = Vec::new();
+        write_offline_viewer(trace.as_bytes(), &mut html).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.contains(r#"[{"tick":1,"<\/script>":1},{"tick":2,"<\/script>":0}]"#));
+        assert!(!html.contains(EMBEDDED_TRACE));
+    }
+}
@@ -0,0 +1,137 @@
+//! Reference circuits used by the [Criterion](https://docs.rs/criterion) benchmarks in the `benches` folder,
+//! available with the "bench" feature.
+//!
+//! They are public so the same circuits can be measured by anyone, the numbers of different releases can
+//! only be compared if they simulate the same thing. Run the benchmarks with:
+//! ```sh
+//! cargo bench --features bench
+//! ```
+//!
+//! # Example
+//! ```
+//! use logicsim::bench::{adder_circuit, drive};
+//!
+//! let circuit = adder_circuit(8);
+//! let ig = &mut circuit.graph.init();
+//! drive(ig, &circuit.levers, 100, 42);
+//! ```
+use crate::circuits::{adder, counter, WordInput};
+use crate::data_structures::SplitMix64;
+use crate::graph::*;
+use crate::testing::{random_graph, RandomGraphParams};
+
+/// Circuit to benchmark along with the levers that drive it.
+#[derive(Debug, Clone)]
+pub struct ReferenceCircuit {
+    /// Name of the circuit, including its size, like "adder_64".
+    pub name: String,
+    /// The circuit, not initialized so initialization and optimization can be measured too.
+    pub graph: GateGraphBuilder,
+    /// Every lever of the circuit, [drive] flips them.
+    pub levers: Vec<LeverHandle>,
+}
+
+/// Returns a [ReferenceCircuit] with an [adder] of 2 `bits` wide inputs, a deep chain of combinational logic.
+pub fn adder_circuit(bits: usize) -> ReferenceCircuit {
+    let mut g = GateGraphBuilder::new();
+    let a = WordInput::new(&mut g, bits, "a");
+    let b = WordInput::new(&mut g, bits, "b");
+    let carry = g.lever("carry");
+    let sum = adder(&mut g, carry.bit(), &a.bits(), &b.bits(), "adder");
+    g.output(&sum, "sum");
+
+    let mut levers = a.levers().to_vec();
+    levers.extend_from_slice(b.levers());
+    levers.push(carry);
+    ReferenceCircuit {
+        name: format!("adder_{}", bits),
+        graph: g,
+        levers,
+    }
+}
+
+/// Returns a [ReferenceCircuit] with a [counter] `bits` wide, driven by its clock, state and feedback.
+pub fn counter_circuit(bits: usize) -> ReferenceCircuit {
+    let mut g = GateGraphBuilder::new();
+    let clock = g.lever("clock");
+    let count = counter(
+        &mut g,
+        clock.bit(),
+        ON,
+        OFF,
+        ON,
+        OFF,
+        &vec![OFF; bits],
+        "counter",
+    );
+    g.output(&count, "count");
+    ReferenceCircuit {
+        name: format!("counter_{}", bits),
+        graph: g,
+        levers: vec![clock],
+    }
+}
+
+/// Returns a [ReferenceCircuit] with a [random_graph] of `gates` gates, the same `seed` always returns the same one.
+pub fn random_circuit(gates: usize, seed: u64) -> ReferenceCircuit {
+    let params = RandomGraphParams {
+        levers: 32,
+        gates,
+        outputs: 16,
+        max_output_width: 64,
+        ..Default::default()
+    };
+    let random = random_graph(seed, &params);
+    ReferenceCircuit {
+        name: format!("random_{}", gates),
+        graph: random.graph,
+        levers: random.levers,
+    }
+}
+
+/// Returns the circuits measured by the benchmarks, from smallest to biggest.
+pub fn reference_circuits() -> Vec<ReferenceCircuit> {
+    vec![
+        adder_circuit(8),
+        adder_circuit(64),
+        counter_circuit(32),
+        random_circuit(10_000, 42),
+    ]
+}
+
+/// Flips one of `levers` picked pseudo randomly `steps` times, running `ig` until it's stable after every flip,
+/// the same `seed` always flips the same levers.
+///
+/// The levers of [counter_circuit] and [random_circuit] always stabilize, circuits that don't are run for
+/// [DEFAULT_STABLE_MAX] ticks after every flip.
+///
+/// # Panics
+///
+/// Will panic if `levers` is empty.
+pub fn drive(ig: &mut InitializedGateGraph, levers: &[LeverHandle], steps: usize, seed: u64) {
+    let mut rng = SplitMix64(seed);
+    for _ in 0..steps {
+        ig.flip_lever(levers[rng.below(levers.len())]);
+        // Circuits that oscillate still get measured.
+        let _ = ig.run_until_stable(DEFAULT_STABLE_MAX);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reference_circuits() {
+        for circuit in [adder_circuit(8), counter_circuit(8), random_circuit(500, 1)] {
+            let ig = &mut circuit.graph.init();
+            drive(ig, &circuit.levers, 50, 7);
+            ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        }
+        let names: Vec<_> = reference_circuits()
+            .into_iter()
+            .map(|circuit| circuit.name)
+            .collect();
+        assert_eq!(names, ["adder_8", "adder_64", "counter_32", "random_10000"]);
+    }
+}
@@ -28,17 +28,27 @@ fn mkname(name: String) -> String {
 /// Will panic if `input1.len()` != `input2.len()`.
 pub fn adder<S: Into<String>>(
     g: &mut GateGraphBuilder,
-    mut cin: GateIndex,
+    cin: GateIndex,
     input1: &[GateIndex],
     input2: &[GateIndex],
     name: S,
 ) -> Vec<GateIndex> {
+    adder_with_carry(g, cin, input1, input2, name).0
+}
+
+/// Returns the output of an [adder] and its carry out.
+pub(super) fn adder_with_carry<S: Into<String>>(
+    g: &mut GateGraphBuilder,
+    mut cin: GateIndex,
+    input1: &[GateIndex],
+    input2: &[GateIndex],
+    name: S,
+) -> (Vec<GateIndex>, GateIndex) {
     assert_eq!(input1.len(), input2.len());
     let name = mkname(name.into());
 
     let bits = input1.len();
-    let mut outputs = Vec::new();
-    outputs.reserve(bits);
+    let mut outputs = Vec::with_capacity(bits);
     for i in 0..bits {
         let x = g.xor2(input1[i], input2[i], name.clone());
         let output = g.xor2(x, cin, name.clone());
@@ -47,5 +57,5 @@ pub fn adder<S: Into<String>>(
         cin = g.or2(a2, a, name.clone());
         outputs.push(output)
     }
-    outputs
+    (outputs, cin)
 }
@@ -0,0 +1,275 @@
+use super::adder::adder_with_carry;
+use super::{bus_multiplexer, decoder, multiplexer};
+use crate::graph::*;
+
+fn mkname(name: String) -> String {
+    format!("ALU:{}", name)
+}
+
+/// Number of bits of the `op` input of an [alu].
+pub const ALU_OP_BITS: usize = 3;
+
+/// Operation performed by an [alu], the value of the enum is the value of its `op` input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AluOp {
+    /// `input1 + input2`.
+    Add = 0,
+    /// `input1 - input2`.
+    Sub = 1,
+    /// Bitwise `input1 & input2`.
+    And = 2,
+    /// Bitwise `input1 | input2`.
+    Or = 3,
+    /// Bitwise `input1 ^ input2`.
+    Xor = 4,
+    /// `input1 << 1`.
+    ShiftLeft = 5,
+    /// `input1 >> 1`, filling the most significant bit with 0.
+    ShiftRight = 6,
+    /// `input1 >> 1`, keeping the sign bit.
+    ArithmeticShiftRight = 7,
+}
+impl AluOp {
+    /// Every operation, in the order of their values.
+    pub const ALL: [AluOp; 8] = [
+        AluOp::Add,
+        AluOp::Sub,
+        AluOp::And,
+        AluOp::Or,
+        AluOp::Xor,
+        AluOp::ShiftLeft,
+        AluOp::ShiftRight,
+        AluOp::ArithmeticShiftRight,
+    ];
+
+    /// Returns the constant `op` input that selects the operation.
+    pub fn bits(self) -> [GateIndex; ALU_OP_BITS] {
+        let value = self as usize;
+        let mut bits = [OFF; ALU_OP_BITS];
+        for (i, bit) in bits.iter_mut().enumerate() {
+            *bit = if value >> i & 1 == 1 { ON } else { OFF };
+        }
+        bits
+    }
+}
+
+/// Outputs of an [alu].
+#[derive(Debug, Clone)]
+pub struct AluOutput {
+    /// Result of the operation, [OFF] while `read` is inactive.
+    pub result: Vec<GateIndex>,
+    /// Active when every bit of the result is 0.
+    pub zero: GateIndex,
+    /// Carry out of [AluOp::Add], active when there is no borrow in [AluOp::Sub],
+    /// the bit shifted out in shifts and inactive in bitwise operations.
+    pub carry: GateIndex,
+    /// Active when the result of [AluOp::Add] or [AluOp::Sub] doesn't fit in a signed word, inactive otherwise.
+    pub overflow: GateIndex,
+    /// Most significant bit of the result.
+    pub negative: GateIndex,
+}
+
+/// Returns the output of an [ALU](https://en.wikipedia.org/wiki/Arithmetic_logic_unit)
+/// performing the [AluOp] selected by `op` on `input1` and `input2`.
+///
+/// The flags describe the result whether `read` is active or not.
+///
+/// # Inputs
+///
+/// `read` Enables the result.
+///
+/// `op` Selects the [AluOp], see [AluOp::bits].
+///
+/// `input1` First word input to the ALU.
+///
+/// `input2` Second word input to the ALU, ignored by shifts.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,constant,alu,AluOp,ON};
+/// # let mut g = GateGraphBuilder::new();
+/// let input1 = constant(3i8);
+/// let input2 = constant(5i8);
+///
+/// let alu = alu(&mut g, ON, &AluOp::Sub.bits(), &input1, &input2, "alu");
+/// let result = g.output(&alu.result, "result");
+/// let negative = g.output1(alu.negative, "negative");
+/// let carry = g.output1(alu.carry, "carry");
+///
+/// let ig = &g.init();
+/// assert_eq!(result.i8(ig), -2);
+/// assert_eq!(negative.b0(ig), true);
+/// // 3 - 5 borrows.
+/// assert_eq!(carry.b0(ig), false);
+/// ```
+///
+/// # Panics
+///
+/// Will panic if `input1.len()` != `input2.len()`, if they are empty or if `op.len()` != [ALU_OP_BITS].
+pub fn alu<S: Into<String>>(
+    g: &mut GateGraphBuilder,
+    read: GateIndex,
+    op: &[GateIndex],
+    input1: &[GateIndex],
+    input2: &[GateIndex],
+    name: S,
+) -> AluOutput {
+    assert_eq!(input1.len(), input2.len());
+    assert!(!input1.is_empty(), "The ALU inputs can't be empty");
+    assert_eq!(op.len(), ALU_OP_BITS, "`op` must have {} bits", ALU_OP_BITS);
+    let name = mkname(name.into());
+    let msb = input1.len() - 1;
+
+    let lines = decoder(g, op, name.clone());
+    let subtract = lines[AluOp::Sub as usize];
+    // 2s complement subtraction, invert input2 and carry in 1.
+    let addend: Vec<_> = input2
+        .iter()
+        .map(|b| g.xor2(*b, subtract, name.clone()))
+        .collect();
+    let (sum, carry_out) = adder_with_carry(g, subtract, input1, &addend, name.clone());
+
+    let and: Vec<_> = input1
+        .iter()
+        .zip(input2)
+        .map(|(a, b)| g.and2(*a, *b, name.clone()))
+        .collect();
+    let or: Vec<_> = input1
+        .iter()
+        .zip(input2)
+        .map(|(a, b)| g.or2(*a, *b, name.clone()))
+        .collect();
+    let xor: Vec<_> = input1
+        .iter()
+        .zip(input2)
+        .map(|(a, b)| g.xor2(*a, *b, name.clone()))
+        .collect();
+    let shift_left: Vec<_> = std::iter::once(OFF)
+        .chain(input1[..msb].iter().copied())
+        .collect();
+    let shift_right: Vec<_> = input1[1..]
+        .iter()
+        .copied()
+        .chain(std::iter::once(OFF))
+        .collect();
+    let arithmetic_shift_right: Vec<_> = input1[1..]
+        .iter()
+        .copied()
+        .chain(std::iter::once(input1[msb]))
+        .collect();
+
+    let result = bus_multiplexer(
+        g,
+        op,
+        &[
+            &sum,
+            &sum,
+            &and,
+            &or,
+            &xor,
+            &shift_left,
+            &shift_right,
+            &arithmetic_shift_right,
+        ],
+        name.clone(),
+    );
+
+    let zero = g.nor(name.clone());
+    for bit in &result {
+        g.dpush(zero, *bit);
+    }
+    let negative = result[msb];
+    let carry = multiplexer(
+        g,
+        op,
+        &[
+            carry_out,
+            carry_out,
+            OFF,
+            OFF,
+            OFF,
+            input1[msb],
+            input1[0],
+            input1[0],
+        ],
+        name.clone(),
+    );
+    // Overflow happens when both addends have the same sign and the sum has a different one.
+    let same_sign = g.xnor2(input1[msb], addend[msb], name.clone());
+    let sign_changed = g.xor2(input1[msb], sum[msb], name.clone());
+    let sum_overflow = g.and2(same_sign, sign_changed, name.clone());
+    let overflow = multiplexer(g, op, &[sum_overflow, sum_overflow], name.clone());
+
+    let result = result
+        .into_iter()
+        .map(|bit| g.and2(bit, read, name.clone()))
+        .collect();
+
+    AluOutput {
+        result,
+        zero,
+        carry,
+        overflow,
+        negative,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuits::WordInput;
+    use crate::verify::exhaustive;
+
+    #[test]
+    fn test_alu() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let read = g.lever("read");
+        let op = WordInput::new(g, ALU_OP_BITS, "op");
+        let a = WordInput::new(g, 4, "a");
+        let b = WordInput::new(g, 4, "b");
+        let out = alu(g, read.bit(), &op.bits(), &a.bits(), &b.bits(), "alu");
+        let result = g.output(&out.result, "result");
+        let flags = g.output(&[out.zero, out.carry, out.overflow, out.negative], "flags");
+        let ig = &mut graph.init();
+        ig.update_lever(read, true);
+
+        let checked = exhaustive(
+            ig,
+            &[op.levers(), a.levers(), b.levers()],
+            &[result, flags],
+            |inputs| {
+                let (x, y) = (inputs[1] as u8, inputs[2] as u8);
+                let sx = (x as i8) << 4 >> 4;
+                let sy = (y as i8) << 4 >> 4;
+                let (expected, carry, overflow) = match AluOp::ALL[inputs[0] as usize] {
+                    AluOp::Add => (x + y, x + y > 15, !(-8..8).contains(&(sx + sy))),
+                    AluOp::Sub => (x.wrapping_sub(y), x >= y, !(-8..8).contains(&(sx - sy))),
+                    AluOp::And => (x & y, false, false),
+                    AluOp::Or => (x | y, false, false),
+                    AluOp::Xor => (x ^ y, false, false),
+                    AluOp::ShiftLeft => (x << 1, x & 8 != 0, false),
+                    AluOp::ShiftRight => (x >> 1, x & 1 != 0, false),
+                    AluOp::ArithmeticShiftRight => ((sx >> 1) as u8, x & 1 != 0, false),
+                };
+                let expected = expected & 15;
+                let flags = (expected == 0) as u8
+                    | (carry as u8) << 1
+                    | (overflow as u8) << 2
+                    | (expected >> 3) << 3;
+                vec![expected as u128, flags as u128]
+            },
+        );
+        assert_eq!(checked, Ok(8 * 16 * 16));
+
+        // The flags don't depend on read.
+        WordInput::set_many_stable(ig, &[(&op, AluOp::Add as u128), (&a, 0), (&b, 0)]);
+        ig.update_lever(read, false);
+        ig.run_until_stable(100).unwrap();
+        assert_eq!(result.u8(ig), 0);
+        assert_eq!(flags.b0(ig), true);
+        WordInput::set_many_stable(ig, &[(&a, 9), (&b, 0)]);
+        assert_eq!(result.u8(ig), 0);
+        assert_eq!(flags.u8(ig), 0b1000);
+    }
+}
@@ -59,3 +59,55 @@ pub fn aluish<S: Into<String>>(
         .map(|out| g.and2(out, read, name.clone()))
         .collect()
 }
+
+/// Named inputs of an [aluish], an alternative to its positional arguments which makes mix-ups like swapping
+/// `cin` and `invert_input_2` stand out. See [aluish] for what every input does.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,AluishPorts,constant,ON};
+/// # let mut g = GateGraphBuilder::new();
+/// let alu_output = AluishPorts {
+///     cin: ON,
+///     read: ON,
+///     invert_input_2: ON,
+///     input1: &constant(3i8),
+///     input2: &constant(5i8),
+/// }
+/// .connect(&mut g, "alu");
+/// let output = g.output(&alu_output, "output");
+///
+/// let ig = &g.init();
+/// assert_eq!(output.i8(ig), -2);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct AluishPorts<'a> {
+    /// Carry in to the adder.
+    pub cin: GateIndex,
+    /// Enables the output.
+    pub read: GateIndex,
+    /// Inverts the bits in `input2`.
+    pub invert_input_2: GateIndex,
+    /// First word input to the ALU.
+    pub input1: &'a [GateIndex],
+    /// Second word input to the ALU.
+    pub input2: &'a [GateIndex],
+}
+impl AluishPorts<'_> {
+    /// Returns the output of an [aluish] connected to these ports.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `input1.len()` != `input2.len()`.
+    pub fn connect<S: Into<String>>(self, g: &mut GateGraphBuilder, name: S) -> Vec<GateIndex> {
+        aluish(
+            g,
+            self.cin,
+            self.read,
+            self.invert_input_2,
+            self.input1,
+            self.input2,
+            name,
+        )
+    }
+}
@@ -0,0 +1,413 @@
+use super::{adder, bus_multiplexer, decoder, edge_register, zeros, Bus};
+use crate::graph::*;
+
+fn mkname(name: String) -> String {
+    format!("AFIFO:{}", name)
+}
+
+/// Outputs of an [async_fifo].
+#[derive(Debug, Clone)]
+pub struct AsyncFifoOutput {
+    /// Oldest word in the FIFO, only meaningful while `empty` is inactive.
+    pub output: Vec<GateIndex>,
+    /// Active when no more words can be written, belongs to the write clock domain.
+    pub full: GateIndex,
+    /// Active when there are no words to read, belongs to the read clock domain.
+    pub empty: GateIndex,
+    /// Active when the FIFO holds at least `almost_full_threshold` words, belongs to the write clock domain.
+    pub almost_full: GateIndex,
+    /// Active when the FIFO holds at most `almost_empty_threshold` words, belongs to the read clock domain.
+    pub almost_empty: GateIndex,
+}
+
+/// Returns `bits` converted to [gray code](https://en.wikipedia.org/wiki/Gray_code).
+fn gray_encode(g: &mut GateGraphBuilder, bits: &[GateIndex], name: String) -> Vec<GateIndex> {
+    let mut out: Vec<_> = bits
+        .windows(2)
+        .map(|pair| g.xor2(pair[0], pair[1], name.clone()))
+        .collect();
+    out.extend(bits.last());
+    out
+}
+
+/// Returns `gray` converted back to binary.
+fn gray_decode(g: &mut GateGraphBuilder, gray: &[GateIndex], name: String) -> Vec<GateIndex> {
+    let mut out = gray.to_vec();
+    for i in (0..gray.len().saturating_sub(1)).rev() {
+        out[i] = g.xor2(gray[i], out[i + 1], name.clone());
+    }
+    out
+}
+
+/// Returns `input1` - `input2`, wrapping around.
+fn subtract(
+    g: &mut GateGraphBuilder,
+    input1: &[GateIndex],
+    input2: &[GateIndex],
+    name: String,
+) -> Vec<GateIndex> {
+    let ninput2: Vec<_> = input2
+        .iter()
+        .map(|bit| g.not1(*bit, name.clone()))
+        .collect();
+    adder(g, ON, input1, &ninput2, name)
+}
+
+/// Returns a bit which is active if `input` >= `threshold`.
+fn at_least(
+    g: &mut GateGraphBuilder,
+    input: &[GateIndex],
+    threshold: usize,
+    name: String,
+) -> GateIndex {
+    // input + 2^(n+1) - threshold only has bit n set if it didn't overflow, which means input < threshold.
+    let width = input.len() + 1;
+    let complement = (1u128 << width) - threshold as u128;
+    let complement: Vec<_> = (0..width)
+        .map(|i| if complement >> i & 1 == 1 { ON } else { OFF })
+        .collect();
+    let mut input = input.to_vec();
+    input.push(OFF);
+    let sum = adder(g, OFF, &input, &complement, name.clone());
+    g.not1(sum[width - 1], name)
+}
+
+/// Returns `input` after passing through 2 registers clocked by `clock`.
+fn synchronizer(
+    g: &mut GateGraphBuilder,
+    clock: GateIndex,
+    reset: GateIndex,
+    input: &[GateIndex],
+    name: String,
+) -> Vec<GateIndex> {
+    let first = edge_register(g, clock, ON, reset, input, name.clone());
+    edge_register(g, clock, ON, reset, &first, name)
+}
+
+/// Returns the outputs of a [FIFO](https://en.wikipedia.org/wiki/FIFO_(computing_and_electronics)) of
+/// 2^`depth_bits` words of `input.len()` bits, whose write and read sides are clocked by different clocks.
+///
+/// It's built like a hardware asynchronous FIFO, the read and write pointers are [gray coded](https://en.wikipedia.org/wiki/Gray_code)
+/// and cross to the other clock domain through 2 register synchronizers,
+/// that means the flags of each side see the other side's operations 2 clock cycles late.
+/// They are conservative, `full` and `almost_full` may stay active and `empty` and `almost_empty` may stay active
+/// a bit longer than necessary but never less.
+///
+/// The output shows the oldest word in the FIFO without needing a read.
+///
+/// # Inputs
+///
+/// `write_clock` Write side clock, activated on the raising edge.
+///
+/// `write` If active during the `write_clock` raising edge and the FIFO is not full, `input` will be pushed to the FIFO.
+///
+/// `read_clock` Read side clock, activated on the raising edge.
+///
+/// `read` If active during the `read_clock` raising edge and the FIFO is not empty, the oldest word will be removed from the FIFO.
+///
+/// `reset` Will empty the FIFO. This is an async reset.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,async_fifo,WordInput};
+/// # let mut g = GateGraphBuilder::new();
+/// let input = WordInput::new(&mut g, 8, "input");
+/// let write_clock = g.lever("write_clock");
+/// let write = g.lever("write");
+/// let read_clock = g.lever("read_clock");
+/// let read = g.lever("read");
+/// let reset = g.lever("reset");
+///
+/// let fifo = async_fifo(
+///     &mut g,
+///     write_clock.bit(),
+///     write.bit(),
+///     read_clock.bit(),
+///     read.bit(),
+///     reset.bit(),
+///     &input.bits(),
+///     2,  // depth_bits
+///     3,  // almost_full_threshold
+///     1,  // almost_empty_threshold
+///     "fifo",
+/// );
+/// let output = g.output(&fifo.output, "output");
+/// let empty = g.output1(fifo.empty, "empty");
+///
+/// let ig = &mut g.init();
+/// ig.pulse_lever_stable(reset);
+/// assert_eq!(empty.b0(ig), true);
+///
+/// ig.set_lever(write);
+/// for value in 1..=2u8 {
+///     input.set_to(ig, value);
+///     ig.pulse_lever_stable(write_clock);
+/// }
+/// ig.reset_lever(write);
+///
+/// // The write pointer needs 2 read clock cycles to reach the read side.
+/// ig.pulse_lever_stable(read_clock);
+/// ig.pulse_lever_stable(read_clock);
+/// assert_eq!(empty.b0(ig), false);
+/// assert_eq!(output.u8(ig), 1);
+///
+/// ig.set_lever(read);
+/// ig.pulse_lever_stable(read_clock);
+/// assert_eq!(output.u8(ig), 2);
+///
+/// ig.pulse_lever_stable(read_clock);
+/// assert_eq!(empty.b0(ig), true);
+/// ```
+/// # Panics
+///
+/// Will panic if `depth_bits` == 0 or if any of the thresholds is bigger than 2^`depth_bits`.
+// rust-analyzer makes this a non issue.
+#[allow(clippy::too_many_arguments)]
+pub fn async_fifo<S: Into<String>>(
+    g: &mut GateGraphBuilder,
+    write_clock: GateIndex,
+    write: GateIndex,
+    read_clock: GateIndex,
+    read: GateIndex,
+    reset: GateIndex,
+    input: &[GateIndex],
+    depth_bits: usize,
+    almost_full_threshold: usize,
+    almost_empty_threshold: usize,
+    name: S,
+) -> AsyncFifoOutput {
+    assert!(
+        depth_bits > 0,
+        "An async FIFO needs at least 1 bit of depth"
+    );
+    assert!(
+        depth_bits < 64,
+        "An async FIFO can't have more than 2^63 words, depth_bits: {}",
+        depth_bits
+    );
+    let depth = 1usize << depth_bits;
+    assert!(
+        almost_full_threshold <= depth && almost_empty_threshold <= depth,
+        "Thresholds can't be bigger than the FIFO depth, depth: {}, almost_full_threshold: {}, almost_empty_threshold: {}",
+        depth,
+        almost_full_threshold,
+        almost_empty_threshold
+    );
+    let name = mkname(name.into());
+
+    // Pointers have an extra bit to tell full and empty apart.
+    let pointer_bits = depth_bits + 1;
+    let write_pointer = Bus::new(g, pointer_bits, name.clone());
+    let read_pointer = Bus::new(g, pointer_bits, name.clone());
+
+    let write_pointer_gray = gray_encode(g, write_pointer.bits(), name.clone());
+    let read_pointer_gray = gray_encode(g, read_pointer.bits(), name.clone());
+
+    // WRITE CLOCK DOMAIN
+    let synced_read_pointer_gray =
+        synchronizer(g, write_clock, reset, &read_pointer_gray, name.clone());
+    let synced_read_pointer = gray_decode(g, &synced_read_pointer_gray, name.clone());
+    let write_count = subtract(g, write_pointer.bits(), &synced_read_pointer, name.clone());
+
+    // Full when the pointers point to the same word but the write pointer has wrapped around once more.
+    let full_bits: Vec<_> = write_pointer
+        .bits()
+        .iter()
+        .zip(synced_read_pointer.iter())
+        .enumerate()
+        .map(|(i, (w, r))| {
+            if i == depth_bits {
+                g.xor2(*w, *r, name.clone())
+            } else {
+                g.xnor2(*w, *r, name.clone())
+            }
+        })
+        .collect();
+    let full = g.andx(full_bits.into_iter(), name.clone());
+    let almost_full = at_least(g, &write_count, almost_full_threshold, name.clone());
+
+    let nfull = g.not1(full, name.clone());
+    let push = g.and2(write, nfull, name.clone());
+    let write_pointer_next = adder(
+        g,
+        ON,
+        write_pointer.bits(),
+        &zeros(pointer_bits),
+        name.clone(),
+    );
+    let write_pointer_output = edge_register(
+        g,
+        write_clock,
+        push,
+        reset,
+        &write_pointer_next,
+        name.clone(),
+    );
+    write_pointer.connect(g, &write_pointer_output);
+
+    let write_address = decoder(g, &write_pointer.bits()[..depth_bits], name.clone());
+    let words: Vec<_> = write_address
+        .into_iter()
+        .map(|selected| {
+            let word_write = g.and2(push, selected, name.clone());
+            edge_register(g, write_clock, word_write, reset, input, name.clone())
+        })
+        .collect();
+
+    // READ CLOCK DOMAIN
+    let synced_write_pointer_gray =
+        synchronizer(g, read_clock, reset, &write_pointer_gray, name.clone());
+    let synced_write_pointer = gray_decode(g, &synced_write_pointer_gray, name.clone());
+    let read_count = subtract(g, &synced_write_pointer, read_pointer.bits(), name.clone());
+
+    let empty_bits: Vec<_> = read_pointer_gray
+        .iter()
+        .zip(synced_write_pointer_gray.iter())
+        .map(|(r, w)| g.xnor2(*r, *w, name.clone()))
+        .collect();
+    let empty = g.andx(empty_bits.into_iter(), name.clone());
+    let more_than_almost_empty = at_least(g, &read_count, almost_empty_threshold + 1, name.clone());
+    let almost_empty = g.not1(more_than_almost_empty, name.clone());
+
+    let nempty = g.not1(empty, name.clone());
+    let pop = g.and2(read, nempty, name.clone());
+    let read_pointer_next = adder(
+        g,
+        ON,
+        read_pointer.bits(),
+        &zeros(pointer_bits),
+        name.clone(),
+    );
+    let read_pointer_output =
+        edge_register(g, read_clock, pop, reset, &read_pointer_next, name.clone());
+    read_pointer.connect(g, &read_pointer_output);
+
+    let words: Vec<&[GateIndex]> = words.iter().map(|word| word.as_slice()).collect();
+    let output = bus_multiplexer(g, &read_pointer.bits()[..depth_bits], &words, name);
+
+    AsyncFifoOutput {
+        output,
+        full,
+        empty,
+        almost_full,
+        almost_empty,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::WordInput;
+    use super::*;
+
+    #[test]
+    fn test_gray_code() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let input = WordInput::new(g, 4, "input");
+        let gray = gray_encode(g, &input.bits(), "gray".to_string());
+        let binary = gray_decode(g, &gray, "binary".to_string());
+        let gray_output = g.output(&gray, "gray");
+        let binary_output = g.output(&binary, "binary");
+
+        let g = &mut graph.init();
+        let mut last_gray = 0u8;
+        for i in 0..16u8 {
+            input.set_to(g, i);
+            g.run_until_stable(10).unwrap();
+            let gray = gray_output.u8(g);
+            assert_eq!(gray, i ^ (i >> 1));
+            if i != 0 {
+                assert_eq!((gray ^ last_gray).count_ones(), 1);
+            }
+            last_gray = gray;
+            assert_eq!(binary_output.u8(g), i);
+        }
+    }
+
+    #[test]
+    fn test_async_fifo() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let input = WordInput::new(g, 8, "input");
+        let write_clock = g.lever("write_clock");
+        let write = g.lever("write");
+        let read_clock = g.lever("read_clock");
+        let read = g.lever("read");
+        let reset = g.lever("reset");
+
+        let fifo = async_fifo(
+            g,
+            write_clock.bit(),
+            write.bit(),
+            read_clock.bit(),
+            read.bit(),
+            reset.bit(),
+            &input.bits(),
+            2,
+            3,
+            1,
+            "fifo",
+        );
+        let output = g.output(&fifo.output, "output");
+        let full = g.output1(fifo.full, "full");
+        let empty = g.output1(fifo.empty, "empty");
+        let almost_full = g.output1(fifo.almost_full, "almost_full");
+        let almost_empty = g.output1(fifo.almost_empty, "almost_empty");
+
+        let g = &mut graph.init();
+        g.run_until_stable(10).unwrap();
+        g.pulse_lever_stable(reset);
+
+        assert_eq!(full.b0(g), false);
+        assert_eq!(empty.b0(g), true);
+        assert_eq!(almost_full.b0(g), false);
+        assert_eq!(almost_empty.b0(g), true);
+
+        // Fill it up, the 5th write is ignored.
+        g.set_lever_stable(write);
+        for i in 0..5u8 {
+            input.set_to(g, i + 10);
+            g.pulse_lever_stable(write_clock);
+            assert_eq!(almost_full.b0(g), i >= 2);
+            assert_eq!(full.b0(g), i >= 3);
+        }
+        g.reset_lever_stable(write);
+
+        // The read side hasn't seen the writes yet.
+        assert_eq!(empty.b0(g), true);
+        g.pulse_lever_stable(read_clock);
+        assert_eq!(empty.b0(g), true);
+        g.pulse_lever_stable(read_clock);
+        assert_eq!(empty.b0(g), false);
+        assert_eq!(almost_empty.b0(g), false);
+        assert_eq!(output.u8(g), 10);
+
+        g.set_lever_stable(read);
+        for i in 0..4u8 {
+            assert_eq!(output.u8(g), i + 10);
+            g.pulse_lever_stable(read_clock);
+            assert_eq!(almost_empty.b0(g), i >= 2);
+            assert_eq!(empty.b0(g), i == 3);
+        }
+        g.reset_lever_stable(read);
+
+        // The write side doesn't know about the reads yet.
+        assert_eq!(full.b0(g), true);
+        g.pulse_lever_stable(write_clock);
+        g.pulse_lever_stable(write_clock);
+        assert_eq!(full.b0(g), false);
+        assert_eq!(almost_full.b0(g), false);
+
+        // Wrap around.
+        g.set_lever_stable(write);
+        input.set_to(g, 42u8);
+        g.pulse_lever_stable(write_clock);
+        g.reset_lever_stable(write);
+        g.pulse_lever_stable(read_clock);
+        g.pulse_lever_stable(read_clock);
+        assert_eq!(empty.b0(g), false);
+        assert_eq!(output.u8(g), 42);
+    }
+}
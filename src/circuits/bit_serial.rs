@@ -0,0 +1,372 @@
+use super::{adder, bus_multiplexer, edge_register, ones, zeros, Bus, Wire};
+use crate::graph::*;
+
+fn mkname(name: String) -> String {
+    format!("BITSERIAL:{}", name)
+}
+
+/// Returns `bits` shifted one position towards bit 0, with `msb` shifted into the most significant bit.
+fn shift_in(bits: &[GateIndex], msb: GateIndex) -> Vec<GateIndex> {
+    bits.iter()
+        .skip(1)
+        .copied()
+        .chain(std::iter::once(msb))
+        .collect()
+}
+
+/// Returns a bit which is active while a bit-serial operation of `width` steps is running.
+///
+/// A `clock` raising edge with `start` active begins the operation, every raising edge after
+/// that completes a step.
+fn sequencer(
+    g: &mut GateGraphBuilder,
+    clock: GateIndex,
+    start: GateIndex,
+    reset: GateIndex,
+    width: usize,
+    name: String,
+) -> GateIndex {
+    assert!(
+        width > 0,
+        "Bit-serial circuits need at least 1 bit of width"
+    );
+
+    // Thermometer code, each step shifts a 1 out, once they are all gone we're done.
+    let steps = Bus::new(g, width, name.clone());
+    let shifted = shift_in(steps.bits(), OFF);
+    let next = bus_multiplexer(g, &[start], &[&shifted, &ones(width)], name.clone());
+    let steps_output = edge_register(g, clock, ON, reset, &next, name);
+    steps.connect(g, &steps_output);
+
+    steps.b0()
+}
+
+/// Returns a pair representing a "done" bit and the output of a bit-serial adder respectively.
+///
+/// It computes the same result as [adder] but it uses a single full adder to do it 1 bit per `clock` cycle,
+/// trading time for a lot less gates.
+///
+/// # Inputs
+///
+/// `clock` Clock input, activated on the raising edge.
+///
+/// `start` If active during the `clock` raising edge, `cin`, `input1` and `input2` are stored and the addition begins.
+/// After that, `input1.len()` raising edges of `clock` are needed to complete the addition.
+///
+/// `reset` Will set all the internal registers to zero. This is an async reset.
+///
+/// `cin` Carry in bit.
+///
+/// The "done" bit will be inactive while the addition is in progress, once it becomes active,
+/// the output holds the result until the next `start`.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,bit_serial_adder,WordInput,OFF};
+/// # let mut g = GateGraphBuilder::new();
+/// let input1 = WordInput::new(&mut g, 8, "input1");
+/// let input2 = WordInput::new(&mut g, 8, "input2");
+/// let clock = g.lever("clock");
+/// let start = g.lever("start");
+/// let reset = g.lever("reset");
+///
+/// let (done, result) = bit_serial_adder(
+///     &mut g,
+///     clock.bit(),
+///     start.bit(),
+///     reset.bit(),
+///     OFF, // cin
+///     &input1.bits(),
+///     &input2.bits(),
+///     "adder",
+/// );
+/// let done = g.output1(done, "done");
+/// let output = g.output(&result, "result");
+///
+/// let ig = &mut g.init();
+/// ig.pulse_lever_stable(reset);
+///
+/// input1.set_to(ig, 3u8);
+/// input2.set_to(ig, 5u8);
+/// ig.set_lever(start);
+/// ig.pulse_lever_stable(clock);
+/// ig.reset_lever(start);
+///
+/// for _ in 0..8 {
+///     assert_eq!(done.b0(ig), false);
+///     ig.pulse_lever_stable(clock);
+/// }
+/// assert_eq!(done.b0(ig), true);
+/// assert_eq!(output.u8(ig), 8);
+/// ```
+/// # Panics
+///
+/// Will panic if `input1.len()` != `input2.len()` or if they are empty.
+// rust-analyzer makes this a non issue.
+#[allow(clippy::too_many_arguments)]
+pub fn bit_serial_adder<S: Into<String>>(
+    g: &mut GateGraphBuilder,
+    clock: GateIndex,
+    start: GateIndex,
+    reset: GateIndex,
+    cin: GateIndex,
+    input1: &[GateIndex],
+    input2: &[GateIndex],
+    name: S,
+) -> (GateIndex, Vec<GateIndex>) {
+    assert_eq!(input1.len(), input2.len());
+    let name = mkname(name.into());
+    let width = input1.len();
+
+    let running = sequencer(g, clock, start, reset, width, name.clone());
+    let write = g.or2(start, running, name.clone());
+
+    let a = Bus::new(g, width, name.clone());
+    let b = Bus::new(g, width, name.clone());
+    let carry = Wire::new(g, name.clone());
+    let sum = Bus::new(g, width, name.clone());
+
+    // Full adder.
+    let x = g.xor2(a.b0(), b.b0(), name.clone());
+    let s = g.xor2(x, carry.bit(), name.clone());
+    let and1 = g.and2(a.b0(), b.b0(), name.clone());
+    let and2 = g.and2(x, carry.bit(), name.clone());
+    let cout = g.or2(and1, and2, name.clone());
+
+    for (operand, input) in [(&a, input1), (&b, input2)].iter() {
+        let shifted = shift_in(operand.bits(), OFF);
+        let next = bus_multiplexer(g, &[start], &[&shifted, input], name.clone());
+        let output = edge_register(g, clock, write, reset, &next, name.clone());
+        operand.connect(g, &output);
+    }
+
+    let carry_next = bus_multiplexer(g, &[start], &[&[cout], &[cin]], name.clone());
+    let carry_output = edge_register(g, clock, write, reset, &carry_next, name.clone());
+    carry.connect(g, carry_output[0]);
+
+    let sum_next = shift_in(sum.bits(), s);
+    let sum_output = edge_register(g, clock, running, reset, &sum_next, name.clone());
+    sum.connect(g, &sum_output);
+
+    let done = g.not1(running, name);
+    (done, sum_output)
+}
+
+/// Returns a pair representing a "done" bit and the output of a bit-serial multiplier respectively.
+///
+/// It is a [shift-and-add multiplier](https://en.wikipedia.org/wiki/Binary_multiplier#Unsigned_integers)
+/// which processes 1 bit of `input2` per `clock` cycle using a single [adder] of the width of the inputs.
+///
+/// The output width will be double the width of the inputs so the result never overflows.
+///
+/// # Inputs
+///
+/// `clock` Clock input, activated on the raising edge.
+///
+/// `start` If active during the `clock` raising edge, `input1` and `input2` are stored and the multiplication begins.
+/// After that, `input1.len()` raising edges of `clock` are needed to complete the multiplication.
+///
+/// `reset` Will set all the internal registers to zero. This is an async reset.
+///
+/// The "done" bit will be inactive while the multiplication is in progress, once it becomes active,
+/// the output holds the result until the next `start`.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,bit_serial_multiplier,WordInput};
+/// # let mut g = GateGraphBuilder::new();
+/// let input1 = WordInput::new(&mut g, 8, "input1");
+/// let input2 = WordInput::new(&mut g, 8, "input2");
+/// let clock = g.lever("clock");
+/// let start = g.lever("start");
+/// let reset = g.lever("reset");
+///
+/// let (done, result) = bit_serial_multiplier(
+///     &mut g,
+///     clock.bit(),
+///     start.bit(),
+///     reset.bit(),
+///     &input1.bits(),
+///     &input2.bits(),
+///     "multiplier",
+/// );
+/// let done = g.output1(done, "done");
+/// let output = g.output(&result, "result");
+///
+/// let ig = &mut g.init();
+/// ig.pulse_lever_stable(reset);
+///
+/// input1.set_to(ig, 200u8);
+/// input2.set_to(ig, 100u8);
+/// ig.set_lever(start);
+/// ig.pulse_lever_stable(clock);
+/// ig.reset_lever(start);
+///
+/// while !done.b0(ig) {
+///     ig.pulse_lever_stable(clock);
+/// }
+/// assert_eq!(output.u16(ig), 20000);
+/// ```
+/// # Panics
+///
+/// Will panic if `input1.len()` != `input2.len()` or if they are empty.
+pub fn bit_serial_multiplier<S: Into<String>>(
+    g: &mut GateGraphBuilder,
+    clock: GateIndex,
+    start: GateIndex,
+    reset: GateIndex,
+    input1: &[GateIndex],
+    input2: &[GateIndex],
+    name: S,
+) -> (GateIndex, Vec<GateIndex>) {
+    assert_eq!(input1.len(), input2.len());
+    let name = mkname(name.into());
+    let width = input1.len();
+
+    let running = sequencer(g, clock, start, reset, width, name.clone());
+    let write = g.or2(start, running, name.clone());
+
+    let multiplicand = edge_register(g, clock, start, reset, input1, name.clone());
+    // Holds the bits of input2 which haven't been processed yet, as they get shifted out,
+    // the low half of the result gets shifted in.
+    let multiplier = Bus::new(g, width, name.clone());
+    let accumulator = Bus::new(g, width, name.clone());
+
+    // The extra bit holds the carry.
+    let mut addend: Vec<_> = multiplicand
+        .iter()
+        .map(|bit| g.and2(*bit, multiplier.b0(), name.clone()))
+        .collect();
+    addend.push(OFF);
+    let mut augend = accumulator.bits().to_vec();
+    augend.push(OFF);
+    let sum = adder(g, OFF, &augend, &addend, name.clone());
+
+    let multiplier_shifted = shift_in(multiplier.bits(), sum[0]);
+    let multiplier_next =
+        bus_multiplexer(g, &[start], &[&multiplier_shifted, input2], name.clone());
+    let multiplier_output = edge_register(g, clock, write, reset, &multiplier_next, name.clone());
+    multiplier.connect(g, &multiplier_output);
+
+    let accumulator_next = bus_multiplexer(g, &[start], &[&sum[1..], &zeros(width)], name.clone());
+    let accumulator_output = edge_register(g, clock, write, reset, &accumulator_next, name.clone());
+    accumulator.connect(g, &accumulator_output);
+
+    let done = g.not1(running, name);
+    (
+        done,
+        multiplier_output
+            .into_iter()
+            .chain(accumulator_output)
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::WordInput;
+    use super::*;
+
+    #[test]
+    fn test_bit_serial_adder() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let input1 = WordInput::new(g, 4, "input1");
+        let input2 = WordInput::new(g, 4, "input2");
+        let cin = g.lever("cin");
+        let clock = g.lever("clock");
+        let start = g.lever("start");
+        let reset = g.lever("reset");
+
+        let (done, result) = bit_serial_adder(
+            g,
+            clock.bit(),
+            start.bit(),
+            reset.bit(),
+            cin.bit(),
+            &input1.bits(),
+            &input2.bits(),
+            "adder",
+        );
+        let done = g.output1(done, "done");
+        let output = g.output(&result, "result");
+
+        let g = &mut graph.init();
+        g.run_until_stable(10).unwrap();
+        g.pulse_lever_stable(reset);
+        assert_eq!(done.b0(g), true);
+
+        for a in 0..16u8 {
+            for b in (0..16u8).step_by(3) {
+                let c = (a + b) % 2 == 0;
+                input1.set_to(g, a);
+                input2.set_to(g, b);
+                g.update_lever(cin, c);
+                g.set_lever(start);
+                g.pulse_lever_stable(clock);
+                g.reset_lever(start);
+                for _ in 0..4 {
+                    assert_eq!(done.b0(g), false);
+                    g.pulse_lever_stable(clock);
+                }
+                assert_eq!(done.b0(g), true);
+                assert_eq!(output.u8(g), (a + b + c as u8) % 16);
+
+                // Holds the result.
+                g.pulse_lever_stable(clock);
+                assert_eq!(done.b0(g), true);
+                assert_eq!(output.u8(g), (a + b + c as u8) % 16);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bit_serial_multiplier() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let input1 = WordInput::new(g, 4, "input1");
+        let input2 = WordInput::new(g, 4, "input2");
+        let clock = g.lever("clock");
+        let start = g.lever("start");
+        let reset = g.lever("reset");
+
+        let (done, result) = bit_serial_multiplier(
+            g,
+            clock.bit(),
+            start.bit(),
+            reset.bit(),
+            &input1.bits(),
+            &input2.bits(),
+            "multiplier",
+        );
+        assert_eq!(result.len(), 8);
+        let done = g.output1(done, "done");
+        let output = g.output(&result, "result");
+
+        let g = &mut graph.init();
+        g.run_until_stable(10).unwrap();
+        g.pulse_lever_stable(reset);
+        assert_eq!(done.b0(g), true);
+
+        for a in 0..16u8 {
+            for b in (0..16u8).step_by(3) {
+                input1.set_to(g, a);
+                input2.set_to(g, b);
+                g.set_lever(start);
+                g.pulse_lever_stable(clock);
+                g.reset_lever(start);
+                for _ in 0..4 {
+                    assert_eq!(done.b0(g), false);
+                    g.pulse_lever_stable(clock);
+                }
+                assert_eq!(done.b0(g), true);
+                assert_eq!(output.u8(g), a * b);
+
+                g.pulse_lever_stable(clock);
+                assert_eq!(output.u8(g), a * b);
+            }
+        }
+    }
+}
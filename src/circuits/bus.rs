@@ -8,7 +8,10 @@ fn mkname(name: String) -> String {
 /// Data structure that helps with managing buses, it allows you to connect &[[GateIndex]] to it as well as providing
 /// a &[[GateIndex]] to connect to other components.
 ///
-/// It is basically syntactic sugar for a set of or gates.
+/// It is basically syntactic sugar for a set of or gates, components connected with [connect](Bus::connect) are
+/// always driving it. To share it between components that take turns, connect them with
+/// [connect_tristate](Bus::connect_tristate) so the simulation can detect
+/// [contention](InitializedGateGraph::contentions).
 ///
 /// # Example
 /// ```
@@ -29,14 +32,17 @@ fn mkname(name: String) -> String {
 #[derive(Debug, Clone)]
 pub struct Bus {
     bits: Vec<GateIndex>,
+    /// Id of the bus in [GateGraphBuilder::validate].
+    port: usize,
 }
 impl Bus {
-    /// Returns a new [Bus] of width `width` with name `name`.
+    /// Returns a new [Bus] of width `width` with name `name`, its connections are checked by
+    /// [GateGraphBuilder::validate].
     pub fn new<S: Into<String>>(g: &mut GateGraphBuilder, width: usize, name: S) -> Self {
         let name = mkname(name.into());
-        Self {
-            bits: (0..width).map(|_| g.or(name.clone())).collect(),
-        }
+        let bits: Vec<_> = (0..width).map(|_| g.or(name.clone())).collect();
+        let port = g.add_port(name, bits.clone());
+        Self { bits, port }
     }
 
     /// Connects a &[[GateIndex]] to the bus, each bit of the output of the bus will be set to the or
@@ -60,12 +66,63 @@ impl Bus {
     ///
     /// If there are excess bits in `other`, they won't get connected to the bus.
     /// If there are missing bits in `other` only other.len() will be connected to the bus.
+    /// Either way [GateGraphBuilder::validate] will report it, in case it wasn't intentional.
     pub fn connect_some(&self, g: &mut GateGraphBuilder, other: &[GateIndex]) {
+        g.connect_port(self.port, other.len());
         for (or, bit) in self.bits.iter().zip(other) {
             g.dpush(*or, *bit);
         }
     }
 
+    /// Connects a &[[GateIndex]] to the bus through a [tri-state buffer](GateGraphBuilder::tristate) per bit,
+    /// which only drive the bus while `enable` is active.
+    ///
+    /// If 2 components connected this way are enabled at the same time and drive different values,
+    /// the bus is in [contention](InitializedGateGraph::contentions).
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `other.len()` != `self.len()`.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::{GateGraphBuilder,constant,Bus};
+    /// # let mut g = GateGraphBuilder::new();
+    /// let register1 = constant(0x01u8);
+    /// let register2 = constant(0x10u8);
+    /// let enable1 = g.lever("enable1");
+    /// let enable2 = g.lever("enable2");
+    ///
+    /// let bus = Bus::new(&mut g, 8, "bus");
+    /// bus.connect_tristate(&mut g, enable1.bit(), &register1);
+    /// bus.connect_tristate(&mut g, enable2.bit(), &register2);
+    /// let output = g.output(bus.bits(), "result");
+    ///
+    /// let ig = &mut g.init();
+    /// ig.set_lever_stable(enable2);
+    /// assert_eq!(output.u8(ig), 0x10);
+    /// ig.set_lever_stable(enable1);
+    /// assert_eq!(output.u8(ig), 0x11);
+    /// // Bits 0 and 4.
+    /// assert_eq!(ig.contentions().len(), 2);
+    /// ```
+    pub fn connect_tristate(
+        &self,
+        g: &mut GateGraphBuilder,
+        enable: GateIndex,
+        other: &[GateIndex],
+    ) {
+        assert_eq!(
+            self.bits.len(),
+            other.len(),
+            "Tri-state connections must have the width of the bus"
+        );
+        for (or, bit) in self.bits.iter().zip(other) {
+            let buffer = g.tristate(*bit, enable, "BUS:tristate");
+            g.dpush(*or, buffer);
+        }
+    }
+
     /// Connects the bits of `other` to `self` and returns a clone of `self`.
     // The signature is very intentional, one does not simply merge buses.
     pub fn merge(&self, g: &mut GateGraphBuilder, other: Bus) -> Bus {
@@ -119,8 +176,8 @@ impl Bus {
     }
 }
 
-impl Into<Vec<GateIndex>> for Bus {
-    fn into(self) -> Vec<GateIndex> {
-        self.bits
+impl From<Bus> for Vec<GateIndex> {
+    fn from(bus: Bus) -> Self {
+        bus.bits
     }
 }
@@ -17,8 +17,7 @@ use crate::graph::*;
 /// ```
 pub fn constant<T: Copy + Sized + 'static>(value: T) -> Vec<GateIndex> {
     let width = std::mem::size_of::<T>() * 8;
-    let mut out = Vec::new();
-    out.reserve(width);
+    let mut out = Vec::with_capacity(width);
 
     for bit in BitIter::new(value) {
         if bit {
@@ -31,6 +30,56 @@ pub fn constant<T: Copy + Sized + 'static>(value: T) -> Vec<GateIndex> {
     out
 }
 
+/// Returns a [Vec] of exactly `width` [ON] or [OFF] values representing the bits of `value`,
+/// the excess bits are ignored and missing bits are 0 extended. See [constant_signed_n] to sign extend negative values.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,constant_n};
+/// # let mut g = GateGraphBuilder::new();
+/// let c = constant_n(0x1f, 4);
+/// assert_eq!(c.len(), 4);
+///
+/// let output = g.output(&c, "const");
+/// let gi = &mut g.init();
+///
+/// assert_eq!(output.u8(gi), 0xf);
+/// ```
+pub fn constant_n(value: u128, width: usize) -> Vec<GateIndex> {
+    constant_from_iter((0..width).map(|i| i < 128 && value >> i & 1 == 1))
+}
+
+/// Returns a [Vec] of exactly `width` [ON] or [OFF] values representing the two's complement bits of `value`,
+/// the excess bits are ignored and missing bits are sign extended.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,constant_signed_n};
+/// # let mut g = GateGraphBuilder::new();
+/// let output = g.output(&constant_signed_n(-2, 5), "const");
+/// let gi = &mut g.init();
+///
+/// assert_eq!(output.signed(gi), -2);
+/// assert_eq!(output.u8(gi), 0b11110);
+/// ```
+pub fn constant_signed_n(value: i128, width: usize) -> Vec<GateIndex> {
+    constant_from_iter((0..width).map(|i| value >> i.min(127) & 1 == 1))
+}
+
+/// Returns a [Vec] with an [ON] for every true value in `bits` and an [OFF] for every false one, bit 0 first.
+///
+/// # Example
+/// ```
+/// # use logicsim::{constant_from_iter,ON,OFF};
+/// let c = constant_from_iter("1101".chars().rev().map(|c| c == '1'));
+/// assert_eq!(c, [ON, OFF, ON, ON]);
+/// ```
+pub fn constant_from_iter<I: IntoIterator<Item = bool>>(bits: I) -> Vec<GateIndex> {
+    bits.into_iter()
+        .map(|bit| if bit { ON } else { OFF })
+        .collect()
+}
+
 /// Returns a [Vec] of size `n` full of [OFF].
 pub fn zeros(n: usize) -> Vec<GateIndex> {
     (0..n).map(|_| OFF).collect()
@@ -40,10 +89,54 @@ pub fn ones(n: usize) -> Vec<GateIndex> {
     (0..n).map(|_| ON).collect()
 }
 
+/// Returns `bits` sign extended to `width` bits by repeating its most significant bit,
+/// so two's complement words of different widths can be connected to the same circuit.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,constant,sign_extend};
+/// # let mut g = GateGraphBuilder::new();
+/// let c = constant(-3i8);
+///
+/// let output = g.output(&sign_extend(&c[..4], 16), "extended");
+/// let ig = &g.init();
+///
+/// assert_eq!(output.i16(ig), -3);
+/// ```
+///
+/// # Panics
+///
+/// Will panic if `bits` is empty or wider than `width`.
+pub fn sign_extend(bits: &[GateIndex], width: usize) -> Vec<GateIndex> {
+    assert!(!bits.is_empty(), "Can't sign extend an empty word");
+    assert!(
+        bits.len() <= width,
+        "Can't sign extend {} bits to {} bits",
+        bits.len(),
+        width
+    );
+    let sign = bits[bits.len() - 1];
+    bits.iter()
+        .copied()
+        .chain(std::iter::repeat(sign).take(width - bits.len()))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_constant_n() {
+        assert_eq!(constant_n(0b1011, 3), [ON, ON, OFF]);
+        assert_eq!(constant_n(1, 6), [ON, OFF, OFF, OFF, OFF, OFF]);
+        assert_eq!(constant_n(u128::MAX, 130)[127..], [ON, OFF, OFF]);
+        assert_eq!(constant_signed_n(-1, 130), ones(130));
+        assert_eq!(constant_signed_n(-4, 3), [OFF, OFF, ON]);
+        assert_eq!(constant_signed_n(5, 4), constant_n(5, 4));
+        assert!(constant_n(7, 0).is_empty());
+    }
+
     #[test]
     fn test_constant() {
         let constants = [0, 0b1u8, 0b10010010];
@@ -1,5 +1,6 @@
 #![allow(unused_imports)]
 
+#[doc(hidden)]
 #[macro_export]
 // https://danielkeep.github.io/tlborm/book/blk-counting.html
 // much better than my old recursive solution
@@ -13,13 +14,14 @@ macro_rules! count_unique_arguments {
         }
     };
 }
+#[doc(hidden)]
 #[macro_export]
 macro_rules! generate_signal_getters {
     ($signal:ident, $($rest:ident),+) => {
-        logicsim::generate_signal_getters!(0,count_unique_arguments!($($rest),+), $signal, $($rest),+);
+        $crate::generate_signal_getters!(0,$crate::count_unique_arguments!($($rest),+), $signal, $($rest),+);
     };
     ($n:expr, $all:expr, $signal:ident) => {
-        pub fn $signal(&self) -> &logicsim::Wire {
+        pub fn $signal(&self) -> &$crate::Wire {
             &self.signals[$n]
         }
         __concat_idents!(signal_index = $signal, _, index {
@@ -29,8 +31,8 @@ macro_rules! generate_signal_getters {
         });
     };
     ($n:expr, $all:expr, $signal:ident, $($rest:ident),+) => {
-        logicsim::generate_signal_getters!(($all-count_unique_arguments!($($rest),+)), $all, $signal);
-        logicsim::generate_signal_getters!(($all-count_unique_arguments!($($rest),+) + 1), $all, $($rest),+);
+        $crate::generate_signal_getters!(($all-$crate::count_unique_arguments!($($rest),+)), $all, $signal);
+        $crate::generate_signal_getters!(($all-$crate::count_unique_arguments!($($rest),+) + 1), $all, $($rest),+);
     };
 }
 #[macro_export]
@@ -38,43 +40,43 @@ macro_rules! generate_signal_getters {
 /// See the `computer/control_logic.rs` example for detailed usage.
 macro_rules! control_signal_set {
     ($name:ident, $($signals:ident),+) => {
-        control_signal_set!(logicsim::count_unique_arguments!($($signals),+),$name,$($signals),+);
+        $crate::control_signal_set!($crate::count_unique_arguments!($($signals),+),$name,$($signals),+);
     };
     ($n:expr, $name:ident, $($signals:ident),+) => {
         pub struct $name {
-            signals: [logicsim::Wire; $n],
+            signals: [$crate::Wire; $n],
         }
 
         // Sorry for polluting your namespace.
-        use concat_idents::concat_idents as __concat_idents;
+        use $crate::concat_idents::concat_idents as __concat_idents;
 
         #[allow(dead_code)]
         impl $name {
-            pub fn new(g:&mut logicsim::GateGraphBuilder) -> Self {
+            pub fn new(g:&mut $crate::GateGraphBuilder) -> Self {
                 use std::mem::MaybeUninit;
                 use std::mem::transmute;
                 // I wish there was a safer way.
                 // This is safe because I initialize the memory immediately afterwards.
                 // https://stackoverflow.com/questions/36258417/using-a-macro-to-initialize-a-big-array-of-non-copy-elements
                 // https://doc.rust-lang.org/std/mem/union.MaybeUninit.html#initializing-an-array-element-by-element
-                let mut signals: [MaybeUninit<logicsim::Wire>;$n] = unsafe { MaybeUninit::uninit().assume_init() };
+                let mut signals: [MaybeUninit<$crate::Wire>;$n] = unsafe { MaybeUninit::uninit().assume_init() };
                 for elem in &mut signals[..] {
                     // TODO per wire names.
-                    *elem = MaybeUninit::new(logicsim::Wire::new(g,stringify!($name)));
+                    *elem = MaybeUninit::new($crate::Wire::new(g,stringify!($name)));
                 }
                 Self {
-                    signals: unsafe{ transmute(signals) }
+                    signals: unsafe{ transmute::<[MaybeUninit<$crate::Wire>;$n], [$crate::Wire;$n]>(signals) }
                 }
             }
             pub fn len() -> usize {
                 $n
             }
-            pub fn connect(&mut self, g: &mut logicsim::GateGraphBuilder, input: &[logicsim::GateIndex; $n]) {
+            pub fn connect(&mut self, g: &mut $crate::GateGraphBuilder, input: &[$crate::GateIndex; $n]) {
                 for (signal, input) in self.signals.iter_mut().zip(input) {
                     signal.connect(g, *input)
                 }
             }
-            logicsim::generate_signal_getters!($($signals),+);
+            $crate::generate_signal_getters!($($signals),+);
         }
     };
 }
@@ -87,17 +89,17 @@ macro_rules! signals_to_bits {
     };
     ($signal_set:ty, $($signals:ident),+) => {
         {
-            use concat_idents::concat_idents;
-            logicsim::signals_to_bits!(0, $signal_set, $($signals),+)
+            use $crate::concat_idents::concat_idents;
+            $crate::signals_to_bits!(0, $signal_set, $($signals),+)
         }
     };
     ($bits:expr, $signal_set:ty, $signal:ident) => {
         concat_idents!(signal_index = $signal, _, index {
             ($bits | (1 << $signal_set::signal_index()))
-        });
+        })
     };
     ($bits:expr, $signal_set:ty, $signal:ident, $($rest:ident),+) => {
-        logicsim::signals_to_bits!(logicsim::signals_to_bits!($bits, $signal_set, $signal), $signal_set, $($rest),+);
+        $crate::signals_to_bits!($crate::signals_to_bits!($bits, $signal_set, $signal), $signal_set, $($rest),+)
     };
 }
 
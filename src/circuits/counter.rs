@@ -116,11 +116,70 @@ pub fn counter<S: Into<String>>(
         name,
     )
 }
+/// Named inputs of a [counter], an alternative to its positional arguments which makes mix-ups like swapping
+/// `enable` and `write` stand out. See [counter] for what every input does.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,CounterPorts,zeros,ON,OFF};
+/// # let mut g = GateGraphBuilder::new();
+/// let clock = g.lever("clock");
+/// let reset = g.lever("reset");
+///
+/// let counter_output = CounterPorts {
+///     clock: clock.bit(),
+///     enable: ON,
+///     write: OFF,
+///     read: ON,
+///     reset: reset.bit(),
+///     input: &zeros(4),
+/// }
+/// .connect(&mut g, "counter");
+/// let output = g.output(&counter_output, "output");
+///
+/// let ig = &mut g.init();
+/// ig.pulse_lever_stable(reset);
+/// ig.pulse_lever_stable(clock);
+/// ig.pulse_lever_stable(clock);
+/// assert_eq!(output.u8(ig), 2);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct CounterPorts<'a> {
+    /// Clock input to the register, activated on the raising edge.
+    pub clock: GateIndex,
+    /// Counter enable, if it is active during a `clock` raising edge, the counter will increment.
+    pub enable: GateIndex,
+    /// If active during the `clock` raising edge, the `input` will be stored in the internal register.
+    pub write: GateIndex,
+    /// If inactive the output will be inactive.
+    pub read: GateIndex,
+    /// Will set the internal register to zero. This is an async reset.
+    pub reset: GateIndex,
+    /// Will override the contents of the internal register if `write` is active on the `clock` raising edge.
+    pub input: &'a [GateIndex],
+}
+impl CounterPorts<'_> {
+    /// Returns the output of a [counter] connected to these ports.
+    pub fn connect<S: Into<String>>(self, g: &mut GateGraphBuilder, name: S) -> Vec<GateIndex> {
+        counter(
+            g,
+            self.clock,
+            self.enable,
+            self.write,
+            self.read,
+            self.reset,
+            self.input,
+            name,
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::constant;
     use super::*;
     use crate::assert_propagation;
+    use crate::testing::{StimulusRecord, TestBench};
 
     #[test]
     fn test_counter_counts() {
@@ -177,6 +236,44 @@ mod tests {
         assert_eq!(output.bx(g, 1), true);
     }
     #[test]
+    fn test_counter_test_bench() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let clock = g.lever("clock");
+        let enable = g.lever("enable");
+        let read = g.lever("read");
+        let reset = g.lever("reset");
+
+        let c = counter(
+            g,
+            clock.bit(),
+            enable.bit(),
+            OFF,
+            read.bit(),
+            reset.bit(),
+            &zeros(2),
+            "counter",
+        );
+        let output = g.output(&c, "counter");
+
+        let g = &mut graph.init();
+        g.run_until_stable(100).unwrap();
+
+        let bench = TestBench::clocked(clock)
+            .cycle(
+                StimulusRecord::new()
+                    .set(&[reset, read], 0b11)
+                    .expect(output, 0),
+            )
+            // Disabled counters hold their value.
+            .cycle(StimulusRecord::new().set(&[reset], 0).expect(output, 0))
+            .cycle(StimulusRecord::new().set(&[enable], 1).expect(output, 1))
+            .cycle(StimulusRecord::new().expect(output, 2))
+            .cycle(StimulusRecord::new().expect(output, 3));
+        assert_eq!(bench.run(g), Ok(5));
+    }
+    #[test]
     fn test_counter_write() {
         let mut graph = GateGraphBuilder::new();
         let g = &mut graph;
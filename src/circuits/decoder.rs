@@ -33,8 +33,7 @@ pub fn decoder<S: Into<String>>(
 ) -> Vec<GateIndex> {
     let name = mkname(name.into());
 
-    let mut out = Vec::new();
-    out.reserve(1 << address.len());
+    let mut out = Vec::with_capacity(1 << address.len());
 
     let naddress: Vec<GateIndex> = address
         .iter()
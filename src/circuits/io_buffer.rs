@@ -222,7 +222,6 @@ mod tests {
         buffer.write(g, 1, 3);
         g.run_until_stable(10).unwrap();
 
-        println!("here");
         g.set_lever_stable(read);
         assert_eq!(output.u8(g), 0);
 
@@ -0,0 +1,335 @@
+use crate::{graph::*, WordInput};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+fn mkname(name: String) -> String {
+    format!("IOBUS:{}", name)
+}
+
+/// Rust model of a memory mapped device connected to an [IoBus], like a keyboard or a terminal.
+pub trait IoDevice: Send {
+    /// Returns the word at `offset` from the start of the address range of the device.
+    fn read(&mut self, offset: usize) -> u128;
+    /// Stores `value` at `offset` from the start of the address range of the device.
+    fn write(&mut self, offset: usize, value: u128);
+    /// Called once per clock cycle, before any read or write of the cycle.
+    fn tick(&mut self) {}
+}
+
+/// Device mapped to a range of addresses.
+struct Mapping {
+    range: Range<usize>,
+    device: Box<dyn IoDevice>,
+}
+
+/// Host side of a memory mapped IO bus, which forwards the reads and writes of a circuit to the [IoDevices](IoDevice)
+/// mapped to the address ranges they touch, so every device doesn't need its own address decoder in the circuit.
+///
+/// It has to be added to the graph as a [HostPeripheral] with [InitializedGateGraph::add_peripheral].
+/// After every falling edge of the clock, if `write` is active the word on `data` is written to the device mapped at
+/// `address`, if `read` is active the word read from it is put on [IoBus::output] until the next falling edge.
+/// Unmapped addresses read as 0.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,IoBus,KeyboardDevice,TerminalDevice,WordInput};
+/// let mut g = GateGraphBuilder::new();
+/// let clock = g.lever("clock");
+/// let read = g.lever("read");
+/// let write = g.lever("write");
+/// let address = WordInput::new(&mut g, 4, "address");
+/// let data = WordInput::new(&mut g, 8, "data");
+///
+/// let mut io = IoBus::new(&mut g, &address.bits(), &data.bits(), read.bit(), write.bit(), "io");
+/// let output = g.output(&io.output(), "output");
+/// let (keyboard, keys) = KeyboardDevice::new();
+/// let (terminal, screen) = TerminalDevice::new();
+/// io.map(0, 2, keyboard);
+/// io.map(2, 1, terminal);
+///
+/// let ig = &mut g.init();
+/// ig.add_peripheral(clock, io);
+///
+/// keys.send(b'k').unwrap();
+/// address.set_to(ig, 1);
+/// ig.set_lever_stable(read);
+/// ig.pulse_lever_stable(clock);
+/// assert_eq!(output.u8(ig), b'k');
+/// ig.reset_lever_stable(read);
+///
+/// address.set_to(ig, 2);
+/// data.set_to(ig, b't');
+/// ig.set_lever_stable(write);
+/// ig.pulse_lever_stable(clock);
+/// assert_eq!(screen.try_recv(), Ok(b't'));
+/// ```
+pub struct IoBus {
+    address: OutputHandle,
+    data: OutputHandle,
+    read: OutputHandle,
+    write: OutputHandle,
+    output: WordInput,
+    /// Word currently on `output`.
+    driven: u128,
+    mappings: Vec<Mapping>,
+}
+impl IoBus {
+    /// Returns a new [IoBus] with no devices, which serves the reads and writes of a circuit to `address`.
+    ///
+    /// `data` is the word written by the circuit while `write` is active, the word read while `read` is active is
+    /// put on [IoBus::output], which has the same width.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `address` or `data` are more than 128 bits wide.
+    pub fn new<S: Into<String>>(
+        g: &mut GateGraphBuilder,
+        address: &[GateIndex],
+        data: &[GateIndex],
+        read: GateIndex,
+        write: GateIndex,
+        name: S,
+    ) -> Self {
+        assert!(
+            address.len() <= 128 && data.len() <= 128,
+            "The address and data of an IoBus can be at most 128 bits wide"
+        );
+        let name = mkname(name.into());
+        Self {
+            address: g.output(address, name.clone()),
+            data: g.output(data, name.clone()),
+            read: g.output1(read, name.clone()),
+            write: g.output1(write, name.clone()),
+            output: WordInput::new(g, data.len(), name),
+            driven: 0,
+            mappings: Vec::new(),
+        }
+    }
+
+    /// Returns the word read from the devices, connect it to the data bus of the circuit.
+    pub fn output(&self) -> Vec<GateIndex> {
+        self.output.bits().to_vec()
+    }
+
+    /// Maps `device` to the `len` addresses starting at `start`.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the range overlaps the range of a device mapped before.
+    pub fn map<D: IoDevice + 'static>(&mut self, start: usize, len: usize, device: D) {
+        let range = start..start + len;
+        assert!(
+            self.mappings
+                .iter()
+                .all(|m| m.range.end <= range.start || range.end <= m.range.start),
+            "The range {:?} overlaps another device",
+            range
+        );
+        self.mappings.push(Mapping {
+            range,
+            device: Box::new(device),
+        });
+    }
+}
+impl HostPeripheral for IoBus {
+    fn tick(&mut self, bus: &mut BusView<'_>) {
+        for mapping in &mut self.mappings {
+            mapping.device.tick();
+        }
+        let address = bus.u128(self.address) as usize;
+        let mut value = 0;
+        if let Some(mapping) = self
+            .mappings
+            .iter_mut()
+            .find(|m| m.range.contains(&address))
+        {
+            let offset = address - mapping.range.start;
+            if bus.b0(self.write) {
+                mapping.device.write(offset, bus.u128(self.data));
+            }
+            if bus.b0(self.read) {
+                value = mapping.device.read(offset);
+            }
+        }
+        if value != self.driven {
+            bus.set_levers(self.output.levers(), value);
+            self.driven = value;
+        }
+    }
+}
+
+/// [IoDevice] which passes the bytes sent to a [Sender] to the circuit, it takes 2 addresses.
+///
+/// Reading offset 0 returns 1 if there is a byte available and 0 otherwise, reading offset 1 returns
+/// the next byte, or 0 if there isn't one.
+pub struct KeyboardDevice {
+    receiver: Receiver<u8>,
+    next: Option<u8>,
+}
+impl KeyboardDevice {
+    /// Returns a new [KeyboardDevice] along with the [Sender] of its bytes.
+    pub fn new() -> (Self, Sender<u8>) {
+        let (sender, receiver) = channel();
+        (
+            Self {
+                receiver,
+                next: None,
+            },
+            sender,
+        )
+    }
+}
+impl IoDevice for KeyboardDevice {
+    fn read(&mut self, offset: usize) -> u128 {
+        match offset {
+            0 => self.next.is_some() as u128,
+            _ => self.next.take().unwrap_or(0) as u128,
+        }
+    }
+    fn write(&mut self, _offset: usize, _value: u128) {}
+    fn tick(&mut self) {
+        if self.next.is_none() {
+            self.next = self.receiver.try_recv().ok();
+        }
+    }
+}
+
+/// [IoDevice] which sends the bytes written by the circuit to a [Receiver], it takes 1 address.
+///
+/// Only the first 8 bits of every word written are taken into account, reading returns 0.
+pub struct TerminalDevice {
+    sender: Sender<u8>,
+}
+impl TerminalDevice {
+    /// Returns a new [TerminalDevice] along with the [Receiver] of its bytes.
+    pub fn new() -> (Self, Receiver<u8>) {
+        let (sender, receiver) = channel();
+        (Self { sender }, receiver)
+    }
+}
+impl IoDevice for TerminalDevice {
+    fn read(&mut self, _offset: usize) -> u128 {
+        0
+    }
+    fn write(&mut self, _offset: usize, value: u128) {
+        // Nobody is listening if the receiver was dropped.
+        let _ = self.sender.send(value as u8);
+    }
+}
+
+/// [IoDevice] which stores bytes in a host file, every address maps to the byte at the same offset in the file.
+///
+/// Bytes past the end of the file read as 0, writing them extends the file.
+/// Only the first 8 bits of every word written are taken into account.
+///
+/// # Panics
+///
+/// Reads and writes will panic if the file can't be accessed.
+pub struct FileStorageDevice {
+    file: File,
+}
+impl FileStorageDevice {
+    /// Returns a new [FileStorageDevice] backed by the file in `path`, which is created if it doesn't exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        Ok(Self { file })
+    }
+}
+impl IoDevice for FileStorageDevice {
+    fn read(&mut self, offset: usize) -> u128 {
+        let mut byte = [0];
+        self.file.seek(SeekFrom::Start(offset as u64)).unwrap();
+        match self.file.read(&mut byte).unwrap() {
+            0 => 0,
+            _ => byte[0] as u128,
+        }
+    }
+    fn write(&mut self, offset: usize, value: u128) {
+        self.file.seek(SeekFrom::Start(offset as u64)).unwrap();
+        self.file.write_all(&[value as u8]).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_bus_devices() {
+        let path = std::env::temp_dir().join("logicsim_test_io_bus_devices.bin");
+        std::fs::write(&path, [1, 2, 3]).unwrap();
+
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let clock = g.lever("clock");
+        let read = g.lever("read");
+        let write = g.lever("write");
+        let address = WordInput::new(g, 8, "address");
+        let data = WordInput::new(g, 8, "data");
+        let mut io = IoBus::new(
+            g,
+            &address.bits(),
+            &data.bits(),
+            read.bit(),
+            write.bit(),
+            "io",
+        );
+        let output = g.output(&io.output(), "output");
+        let (keyboard, keys) = KeyboardDevice::new();
+        io.map(0x10, 2, keyboard);
+        io.map(0x20, 0x10, FileStorageDevice::open(&path).unwrap());
+
+        let ig = &mut graph.init();
+        ig.add_peripheral(clock, io);
+        let access = |ig: &mut InitializedGateGraph, lever, at: u8, value: u8| {
+            address.set_to(ig, at);
+            data.set_to(ig, value);
+            ig.set_lever_stable(lever);
+            ig.pulse_lever_stable(clock);
+            let result = output.u8(ig);
+            ig.reset_lever_stable(lever);
+            // The output goes back to 0 on the next cycle.
+            ig.pulse_lever_stable(clock);
+            assert_eq!(output.u8(ig), 0);
+            result
+        };
+
+        // Keyboard.
+        assert_eq!(access(ig, read, 0x10, 0), 0);
+        keys.send(7).unwrap();
+        assert_eq!(access(ig, read, 0x10, 0), 1);
+        assert_eq!(access(ig, read, 0x11, 0), 7);
+        assert_eq!(access(ig, read, 0x10, 0), 0);
+
+        // Storage.
+        assert_eq!(access(ig, read, 0x21, 0), 2);
+        assert_eq!(access(ig, read, 0x25, 0), 0);
+        access(ig, write, 0x24, 9);
+        access(ig, write, 0x20, 8);
+        assert_eq!(access(ig, read, 0x24, 0), 9);
+
+        // Unmapped.
+        access(ig, write, 0x50, 9);
+        assert_eq!(access(ig, read, 0x50, 0), 0);
+
+        assert_eq!(std::fs::read(&path).unwrap(), [8, 2, 3, 0, 9]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_io_bus_overlap() {
+        let mut g = GateGraphBuilder::new();
+        let mut io = IoBus::new(&mut g, &[OFF], &[OFF], OFF, OFF, "io");
+        io.map(0, 2, TerminalDevice::new().0);
+        io.map(1, 1, TerminalDevice::new().0);
+    }
+}
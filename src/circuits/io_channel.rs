@@ -0,0 +1,299 @@
+use crate::{graph::*, WordInput};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// Host side of a stream of words coming out of a circuit, like the output of an [io_register](super::io_register),
+/// which sends every word to a [Receiver] and takes care of the acknowledgement.
+///
+/// The protocol is the one of [io_register](super::io_register): while `updated` is active, `data` holds a new word
+/// which the host acknowledges by keeping `ack` active during a clock cycle.
+///
+/// [OutputChannel::update] should be called once after every clock cycle of the circuit,
+/// or the channel can be added to the graph as a [HostPeripheral] with [InitializedGateGraph::add_peripheral].
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,OutputChannel,WordInput,io_register,ON};
+/// let mut g = GateGraphBuilder::new();
+/// let clock = g.lever("clock");
+/// let write = g.lever("write");
+/// let reset = g.lever("reset");
+/// let ack = g.lever("ack");
+/// let input = WordInput::new(&mut g, 8, "input");
+/// let (updated, output) = io_register(
+///     &mut g, clock.bit(), write.bit(), ON, reset.bit(), &input.bits(), ack.bit(), "output",
+/// );
+/// let updated = g.output1(updated, "updated");
+/// let data = g.output(&output, "data");
+///
+/// let (mut channel, receiver) = OutputChannel::new(data, updated, ack);
+/// let ig = &mut g.init();
+/// ig.pulse_lever_stable(reset);
+/// for c in b"hi" {
+///     input.set_to(ig, *c);
+///     ig.set_lever_stable(write);
+///     ig.pulse_lever_stable(clock);
+///     ig.reset_lever_stable(write);
+///     channel.update(ig);
+///
+///     // A cycle for the acknowledgement to go through.
+///     ig.pulse_lever_stable(clock);
+///     channel.update(ig);
+/// }
+/// assert_eq!(receiver.try_iter().collect::<Vec<_>>(), b"hi");
+/// ```
+pub struct OutputChannel {
+    data: OutputHandle,
+    updated: OutputHandle,
+    ack: LeverHandle,
+    sender: Sender<u8>,
+    acking: bool,
+}
+impl OutputChannel {
+    /// Returns a new [OutputChannel] which reads words from `data` whenever `updated` is active,
+    /// and acknowledges them with `ack`, along with the [Receiver] of those words.
+    ///
+    /// Only the first 8 bits of `data` are taken into account.
+    pub fn new(
+        data: OutputHandle,
+        updated: OutputHandle,
+        ack: LeverHandle,
+    ) -> (Self, Receiver<u8>) {
+        let (sender, receiver) = channel();
+        let channel = Self {
+            data,
+            updated,
+            ack,
+            sender,
+            acking: false,
+        };
+        (channel, receiver)
+    }
+
+    /// Sends the word in `data` if there is a new one and starts acknowledging it,
+    /// or stops acknowledging the previous one.
+    ///
+    /// Words that arrive after the [Receiver] has been dropped are acknowledged and discarded.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the circuit does not stabilize.
+    pub fn update(&mut self, ig: &mut InitializedGateGraph) {
+        if self.acking {
+            // The acknowledgement went through during the last clock cycle.
+            ig.reset_lever_stable(self.ack);
+            self.acking = false;
+        } else if self.updated.b0(ig) {
+            let _ = self.sender.send(self.data.u8(ig));
+            ig.set_lever_stable(self.ack);
+            self.acking = true;
+        }
+    }
+}
+impl HostPeripheral for OutputChannel {
+    fn tick(&mut self, bus: &mut BusView<'_>) {
+        if self.acking {
+            bus.set_lever(self.ack, false);
+            self.acking = false;
+        } else if bus.b0(self.updated) {
+            let _ = self.sender.send(bus.u128(self.data) as u8);
+            bus.set_lever(self.ack, true);
+            self.acking = true;
+        }
+    }
+}
+
+/// Host side of a stream of words going into a circuit, which takes the words sent to a [Sender] and writes them
+/// to the circuit one at a time, waiting while the circuit is busy.
+///
+/// Every word is set on `data` and `write` is pulsed, like in the input register of the computer example,
+/// an [io_register](super::io_register) clocked by `write` whose "updated" bit is `busy`.
+///
+/// [InputChannel::update] should be called once after every clock cycle of the circuit,
+/// or the channel can be added to the graph as a [HostPeripheral] with [InitializedGateGraph::add_peripheral].
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,InputChannel,WordInput,io_register,ON};
+/// let mut g = GateGraphBuilder::new();
+/// let clock = g.lever("clock");
+/// let reset = g.lever("reset");
+/// let ack = g.lever("ack");
+/// let write = g.lever("write");
+/// let data = WordInput::new(&mut g, 8, "data");
+/// let register_clock = g.or2(clock.bit(), write.bit(), "register_clock");
+/// let (updated, output) = io_register(
+///     &mut g, register_clock, write.bit(), ON, reset.bit(), &data.bits(), ack.bit(), "input",
+/// );
+/// let busy = g.output1(updated, "busy");
+/// let output = g.output(&output, "output");
+///
+/// let (mut channel, sender) = InputChannel::new(data, write, busy);
+/// sender.send(b'h').unwrap();
+/// sender.send(b'i').unwrap();
+///
+/// let ig = &mut g.init();
+/// ig.pulse_lever_stable(reset);
+/// channel.update(ig);
+/// assert_eq!(output.u8(ig), b'h');
+///
+/// // The circuit is busy until it acknowledges the word.
+/// channel.update(ig);
+/// assert_eq!(output.u8(ig), b'h');
+/// ig.set_lever_stable(ack);
+/// ig.pulse_lever_stable(clock);
+/// ig.reset_lever_stable(ack);
+///
+/// channel.update(ig);
+/// assert_eq!(output.u8(ig), b'i');
+/// ```
+pub struct InputChannel {
+    data: WordInput,
+    write: LeverHandle,
+    busy: OutputHandle,
+    receiver: Receiver<u8>,
+    pending: Option<u8>,
+}
+impl InputChannel {
+    /// Returns a new [InputChannel] which writes words to `data` by pulsing `write` whenever `busy` is inactive,
+    /// along with the [Sender] of those words.
+    pub fn new(data: WordInput, write: LeverHandle, busy: OutputHandle) -> (Self, Sender<u8>) {
+        let (sender, receiver) = channel();
+        let channel = Self {
+            data,
+            write,
+            busy,
+            receiver,
+            pending: None,
+        };
+        (channel, sender)
+    }
+
+    /// Writes the next word to the circuit if there is one and the circuit is not busy.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the circuit does not stabilize.
+    pub fn update(&mut self, ig: &mut InitializedGateGraph) {
+        if self.pending.is_none() {
+            self.pending = self.receiver.try_recv().ok();
+        }
+        if let (Some(word), false) = (self.pending, self.busy.b0(ig)) {
+            self.data.set_to(ig, word);
+            ig.pulse_lever_stable(self.write);
+            self.pending = None;
+        }
+    }
+}
+impl HostPeripheral for InputChannel {
+    fn tick(&mut self, bus: &mut BusView<'_>) {
+        if self.pending.is_none() {
+            self.pending = self.receiver.try_recv().ok();
+        }
+        if let (Some(word), false) = (self.pending, bus.b0(self.busy)) {
+            bus.set_levers(self.data.levers(), word as u128);
+            bus.pulse_lever(self.write);
+            self.pending = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{d_flip_flop, io_register, Wire};
+
+    /// Input register feeding an output register, the input gets acknowledged when it's copied.
+    /// Returns the graph, the channels, the sender and receiver of the channels, the clock and the reset.
+    fn loopback() -> (
+        GateGraphBuilder,
+        InputChannel,
+        Sender<u8>,
+        OutputChannel,
+        Receiver<u8>,
+        LeverHandle,
+        LeverHandle,
+    ) {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let clock = g.lever("clock");
+        let write = g.lever("write");
+        let ack = g.lever("ack");
+        let reset = g.lever("reset");
+        let data = WordInput::new(g, 8, "data");
+        let input_clock = g.or2(clock.bit(), write.bit(), "input_clock");
+        let copy = Wire::new(g, "copy");
+        let (input_updated, input_output) = io_register(
+            g,
+            input_clock,
+            write.bit(),
+            ON,
+            reset.bit(),
+            &data.bits(),
+            copy.bit(),
+            "input",
+        );
+        let (output_updated, output_output) = io_register(
+            g,
+            clock.bit(),
+            copy.bit(),
+            ON,
+            reset.bit(),
+            &input_output,
+            ack.bit(),
+            "output",
+        );
+        let output_updated_handle = g.output1(output_updated, "output_updated");
+        // Only copy when the output is free, deciding on the falling edge so copy is stable on the raising edge.
+        let output_free = g.not1(output_updated, "output_free");
+        let should_copy = g.and2(input_updated, output_free, "should_copy");
+        let nclock = g.not1(clock.bit(), "nclock");
+        let copy_buffer = d_flip_flop(g, should_copy, nclock, reset.bit(), ON, ON, "copy_buffer");
+        copy.connect(g, copy_buffer);
+        let busy = g.output1(input_updated, "busy");
+        let output = g.output(&output_output, "output");
+
+        let (input, sender) = InputChannel::new(data, write, busy);
+        let (output, receiver) = OutputChannel::new(output, output_updated_handle, ack);
+        (graph, input, sender, output, receiver, clock, reset)
+    }
+
+    #[test]
+    fn test_channels_loopback() {
+        let (graph, mut input, sender, mut output, receiver, clock, reset) = loopback();
+
+        let message = b"hello world";
+        for c in message {
+            sender.send(*c).unwrap();
+        }
+        drop(sender);
+
+        let ig = &mut graph.init();
+        ig.pulse_lever_stable(reset);
+        for _ in 0..100 {
+            input.update(ig);
+            ig.pulse_lever_stable(clock);
+            output.update(ig);
+        }
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), message);
+    }
+
+    #[test]
+    fn test_channels_loopback_peripherals() {
+        let (graph, input, sender, output, receiver, clock, reset) = loopback();
+
+        let message = b"hello world";
+        for c in message {
+            sender.send(*c).unwrap();
+        }
+
+        let ig = &mut graph.init();
+        ig.pulse_lever_stable(reset);
+        ig.add_peripheral(clock, input);
+        ig.add_peripheral(clock, output);
+        for _ in 0..100 {
+            ig.pulse_lever_stable(clock);
+        }
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), message);
+    }
+}
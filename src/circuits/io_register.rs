@@ -33,6 +33,71 @@ pub fn io_register<S: Into<String>>(
     (updated_output, register_output)
 }
 
+/// Named inputs of an [io_register], an alternative to its positional arguments which makes mix-ups like swapping
+/// `read` and `write` stand out.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,IoRegisterPorts,WordInput,ON,OFF};
+/// # let mut g = GateGraphBuilder::new();
+/// let clock = g.lever("clock");
+/// let write = g.lever("write");
+/// let ack = g.lever("ack");
+/// let input = WordInput::new(&mut g, 8, "input");
+///
+/// let (updated, register_output) = IoRegisterPorts {
+///     clock: clock.bit(),
+///     write: write.bit(),
+///     read: ON,
+///     reset: OFF,
+///     input: &input.bits(),
+///     ack: ack.bit(),
+/// }
+/// .connect(&mut g, "io");
+/// let updated = g.output1(updated, "updated");
+/// let output = g.output(&register_output, "output");
+///
+/// let ig = &mut g.init();
+/// input.set_to(ig, 7);
+/// ig.set_lever_stable(write);
+/// ig.pulse_lever_stable(clock);
+/// ig.reset_lever_stable(write);
+/// assert_eq!(output.u8(ig), 7);
+/// assert_eq!(updated.b0(ig), true);
+///
+/// ig.set_lever_stable(ack);
+/// ig.pulse_lever_stable(clock);
+/// ig.reset_lever_stable(ack);
+/// assert_eq!(updated.b0(ig), false);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct IoRegisterPorts<'a> {
+    /// Clock input to the register, activated on the raising edge.
+    pub clock: GateIndex,
+    /// If active during the `clock` raising edge, the `input` will be stored in the register and "updated" gets set.
+    pub write: GateIndex,
+    /// If inactive the output will be inactive.
+    pub read: GateIndex,
+    /// Will set the register to zero and clear "updated". This is an async reset.
+    pub reset: GateIndex,
+    /// Will override the contents of the register if `write` is active on the `clock` raising edge.
+    pub input: &'a [GateIndex],
+    /// If active during the `clock` raising edge, "updated" gets cleared.
+    pub ack: GateIndex,
+}
+impl IoRegisterPorts<'_> {
+    /// Returns the "updated" bit and the output of an [io_register] connected to these ports.
+    pub fn connect<S: Into<String>>(
+        self,
+        g: &mut GateGraphBuilder,
+        name: S,
+    ) -> (GateIndex, Vec<GateIndex>) {
+        io_register(
+            g, self.clock, self.write, self.read, self.reset, self.input, self.ack, name,
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
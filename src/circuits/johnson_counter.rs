@@ -0,0 +1,125 @@
+use super::edge_register;
+use crate::graph::*;
+
+fn mkname(name: String) -> String {
+    format!("JOHNSONCNTR:{}", name)
+}
+
+/// Returns the decoded phases of a [Johnson counter](https://en.wikipedia.org/wiki/Ring_counter#Johnson_counter)
+/// of width `width`.
+///
+/// A Johnson counter cycles through `2 * width` states using only `width` flip flops,
+/// the output has `2 * width` bits, exactly one of them is active at a time, it starts at bit 0
+/// and moves to the next bit on every `clock` raising edge, wrapping around after the last one.
+///
+/// # Inputs
+///
+/// `clock` Clock input to the counter, activated on the raising edge.
+///
+/// `enable` Counter enable, if it is active during a `clock` raising edge, the counter will advance.
+///
+/// `reset` Will set the counter back to phase 0. This is an async reset.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,johnson_counter,ON};
+/// # let mut g = GateGraphBuilder::new();
+/// let reset = g.lever("reset");
+/// let clock = g.lever("clock");
+///
+/// let phases = johnson_counter(&mut g, clock.bit(), ON, reset.bit(), 2, "johnson");
+/// assert_eq!(phases.len(), 4);
+/// let output = g.output(&phases, "result");
+///
+/// let ig = &mut g.init();
+/// ig.pulse_lever_stable(reset);
+/// assert_eq!(output.u8(ig), 0b0001);
+///
+/// ig.pulse_lever_stable(clock);
+/// assert_eq!(output.u8(ig), 0b0010);
+///
+/// ig.pulse_lever_stable(clock);
+/// assert_eq!(output.u8(ig), 0b0100);
+///
+/// ig.pulse_lever_stable(clock);
+/// assert_eq!(output.u8(ig), 0b1000);
+///
+/// ig.pulse_lever_stable(clock);
+/// assert_eq!(output.u8(ig), 0b0001);
+/// ```
+/// # Panics
+///
+/// Will panic if `width` == 0.
+pub fn johnson_counter<S: Into<String>>(
+    g: &mut GateGraphBuilder,
+    clock: GateIndex,
+    enable: GateIndex,
+    reset: GateIndex,
+    width: usize,
+    name: S,
+) -> Vec<GateIndex> {
+    assert!(width > 0, "A Johnson counter needs at least 1 bit");
+    let name = mkname(name.into());
+
+    let state: Vec<_> = (0..width).map(|_| g.or(name.clone())).collect();
+    let mut next = Vec::with_capacity(width);
+    next.push(g.not1(state[width - 1], name.clone()));
+    next.extend_from_slice(&state[..width - 1]);
+
+    let state_output = edge_register(g, clock, enable, reset, &next, name.clone());
+    for (bit, state_bit) in state.iter().zip(state_output) {
+        g.dpush(*bit, state_bit);
+    }
+
+    // The state fills up with ones from bit 0 and then empties from bit 0,
+    // so each phase can be decoded by looking at the boundary between ones and zeros.
+    let nstate: Vec<_> = state.iter().map(|bit| g.not1(*bit, name.clone())).collect();
+    let mut phases = Vec::with_capacity(width * 2);
+    phases.push(g.and2(nstate[0], nstate[width - 1], name.clone()));
+    for i in 1..width {
+        phases.push(g.and2(state[i - 1], nstate[i], name.clone()));
+    }
+    phases.push(g.and2(state[width - 1], state[0], name.clone()));
+    for i in 1..width {
+        phases.push(g.and2(nstate[i - 1], state[i], name.clone()));
+    }
+    phases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_johnson_counter() {
+        for width in 1..6 {
+            let mut graph = GateGraphBuilder::new();
+            let g = &mut graph;
+
+            let clock = g.lever("clock");
+            let enable = g.lever("enable");
+            let reset = g.lever("reset");
+
+            let phases = johnson_counter(g, clock.bit(), enable.bit(), reset.bit(), width, "j");
+            assert_eq!(phases.len(), width * 2);
+            let output = g.output(&phases, "out");
+
+            let g = &mut graph.init();
+            g.run_until_stable(10).unwrap();
+            g.pulse_lever_stable(reset);
+            assert_eq!(output.u16(g), 1);
+
+            g.pulse_lever_stable(clock);
+            assert_eq!(output.u16(g), 1);
+
+            g.set_lever_stable(enable);
+            for i in 1..30 {
+                g.pulse_lever_stable(clock);
+                assert_eq!(output.u16(g), 1 << (i % (width * 2)));
+            }
+
+            g.pulse_lever_stable(reset);
+            assert_eq!(output.u16(g), 1);
+        }
+    }
+}
@@ -0,0 +1,365 @@
+use super::{edge_register, multiplexer, Bus};
+use crate::graph::*;
+
+fn mkname(name: String) -> String {
+    format!("LUTF:{}", name)
+}
+
+/// Number of inputs of every [LUT](https://en.wikipedia.org/wiki/Lookup_table#Hardware_LUTs) of a [lut_fabric].
+pub const LUT_INPUTS: usize = 4;
+/// Number of configuration bits of the truth table of a LUT.
+const LUT_TABLE_BITS: usize = 1 << LUT_INPUTS;
+
+/// Signals that every LUT input of a [lut_fabric] can be routed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LutRouting {
+    /// LUTs in the first column read the fabric inputs and the rest read the outputs of the previous column.
+    PreviousColumn,
+    /// LUTs read the fabric inputs followed by the outputs of every previous column, in column order.
+    AllPrevious,
+}
+impl LutRouting {
+    /// Returns the number of signals the LUTs in column `col` can be routed from.
+    fn source_count(self, rows: usize, inputs: usize, col: usize) -> usize {
+        match (self, col) {
+            (LutRouting::PreviousColumn, 0) => inputs,
+            (LutRouting::PreviousColumn, _) => rows,
+            (LutRouting::AllPrevious, _) => inputs + rows * col,
+        }
+    }
+}
+
+/// Returns the number of bits needed to select one of `n` signals.
+fn select_bits(n: usize) -> usize {
+    (usize::BITS - n.saturating_sub(1).leading_zeros()) as usize
+}
+
+/// Returns the number of configuration bits of a LUT routed from `sources` signals.
+fn cell_bits(sources: usize) -> usize {
+    LUT_INPUTS * select_bits(sources) + LUT_TABLE_BITS
+}
+
+/// Returns the outputs of a tiny [FPGA](https://en.wikipedia.org/wiki/Field-programmable_gate_array) like fabric,
+/// a grid of `rows`x`cols` [LUT_INPUTS] input LUTs whose truth tables and input routing come from
+/// a configuration shift register. The outputs are the outputs of the LUTs of the last column, one per row.
+///
+/// The fabric is purely combinational, the configuration decides what `inputs` are routed to every LUT
+/// according to `routing` and what function it computes. Use [LutFabricConfig] to build the bitstream.
+///
+/// # Inputs
+///
+/// `config_clock` Configuration clock, activated on the raising edge.
+///
+/// `load` While active, every `config_clock` raising edge shifts `config_data` into the configuration
+/// and the outputs are inactive.
+///
+/// `config_data` Next bit of the bitstream.
+///
+/// `inputs` Inputs of the fabric.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,lut_fabric,LutFabricConfig,LutRouting,WordInput};
+/// # let mut g = GateGraphBuilder::new();
+/// let config_clock = g.lever("config_clock");
+/// let load = g.lever("load");
+/// let config_data = g.lever("config_data");
+/// let input = WordInput::new(&mut g, 2, "input");
+///
+/// let outputs = lut_fabric(
+///     &mut g,
+///     1,  // rows
+///     2,  // cols
+///     LutRouting::PreviousColumn,
+///     config_clock.bit(),
+///     load.bit(),
+///     config_data.bit(),
+///     &input.bits(),
+///     "fabric",
+/// );
+/// let output = g.output(&outputs, "output");
+///
+/// // A xor of both inputs followed by a not.
+/// let mut config = LutFabricConfig::new(1, 2, 2, LutRouting::PreviousColumn);
+/// config.lut(0, 0, &[0, 1], |i| i[0] ^ i[1]);
+/// config.lut(0, 1, &[0], |i| !i[0]);
+///
+/// let ig = &mut g.init();
+/// config.load(ig, config_clock, load, config_data);
+/// for (value, expected) in [(0u8, 1), (1, 0), (2, 0), (3, 1)] {
+///     input.set_to(ig, value);
+///     ig.run_until_stable(20).unwrap();
+///     assert_eq!(output.u8(ig), expected);
+/// }
+/// ```
+/// # Panics
+///
+/// Will panic if `rows`, `cols` or `inputs` are 0.
+// rust-analyzer makes this a non issue.
+#[allow(clippy::too_many_arguments)]
+pub fn lut_fabric<S: Into<String>>(
+    g: &mut GateGraphBuilder,
+    rows: usize,
+    cols: usize,
+    routing: LutRouting,
+    config_clock: GateIndex,
+    load: GateIndex,
+    config_data: GateIndex,
+    inputs: &[GateIndex],
+    name: S,
+) -> Vec<GateIndex> {
+    assert!(
+        rows > 0 && cols > 0 && !inputs.is_empty(),
+        "A LUT fabric needs at least 1 row, 1 column and 1 input"
+    );
+    let name = mkname(name.into());
+
+    // Configuration shift register, every stage is fed by the previous one.
+    let config_len: usize = (0..cols)
+        .map(|col| rows * cell_bits(routing.source_count(rows, inputs.len(), col)))
+        .sum();
+    let chain = Bus::new(g, config_len, name.clone());
+    let chain_input: Vec<_> = std::iter::once(config_data)
+        .chain(chain.bits()[..config_len - 1].iter().copied())
+        .collect();
+    let config = edge_register(g, config_clock, load, OFF, &chain_input, name.clone());
+    chain.connect(g, &config);
+
+    let mut config = config.into_iter();
+    let mut columns: Vec<Vec<GateIndex>> = Vec::new();
+    for col in 0..cols {
+        let sources: Vec<GateIndex> = match (routing, col) {
+            (LutRouting::PreviousColumn, 0) => inputs.to_vec(),
+            (LutRouting::PreviousColumn, _) => columns[col - 1].clone(),
+            (LutRouting::AllPrevious, _) => inputs
+                .iter()
+                .chain(columns.concat().iter())
+                .copied()
+                .collect(),
+        };
+        let column = (0..rows)
+            .map(|_| {
+                let lut_inputs: Vec<_> = (0..LUT_INPUTS)
+                    .map(|_| {
+                        let select: Vec<_> =
+                            config.by_ref().take(select_bits(sources.len())).collect();
+                        if select.is_empty() {
+                            // A single source doesn't need selecting.
+                            sources[0]
+                        } else {
+                            multiplexer(g, &select, &sources, name.clone())
+                        }
+                    })
+                    .collect();
+                let table: Vec<_> = config.by_ref().take(LUT_TABLE_BITS).collect();
+                multiplexer(g, &lut_inputs, &table, name.clone())
+            })
+            .collect();
+        columns.push(column);
+    }
+
+    let nload = g.not1(load, name.clone());
+    columns
+        .pop()
+        .unwrap()
+        .into_iter()
+        .map(|output| g.and2(output, nload, name.clone()))
+        .collect()
+}
+
+/// Configuration of a single LUT of a [LutFabricConfig].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+struct LutConfig {
+    sources: [usize; LUT_INPUTS],
+    table: u16,
+}
+
+/// Host side description of the configuration of a [lut_fabric], which can be turned into its bitstream.
+///
+/// Unconfigured LUTs output [OFF].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LutFabricConfig {
+    rows: usize,
+    cols: usize,
+    inputs: usize,
+    routing: LutRouting,
+    /// Indexed by column then by row.
+    luts: Vec<LutConfig>,
+}
+impl LutFabricConfig {
+    /// Returns an empty configuration of a [lut_fabric] with the same `rows`, `cols`, `routing`
+    /// and number of `inputs`.
+    pub fn new(rows: usize, cols: usize, inputs: usize, routing: LutRouting) -> Self {
+        Self {
+            rows,
+            cols,
+            inputs,
+            routing,
+            luts: vec![Default::default(); rows * cols],
+        }
+    }
+
+    /// Returns the number of signals the LUTs in column `col` can be routed from, see [LutRouting].
+    pub fn source_count(&self, col: usize) -> usize {
+        self.routing.source_count(self.rows, self.inputs, col)
+    }
+
+    /// Configures the LUT at `row`, `col` to compute `f` of the signals in `sources`,
+    /// [LutRouting] explains which signal every source index refers to.
+    /// `f` gets the values of the sources in the same order.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the LUT is outside of the fabric, if there are more than [LUT_INPUTS] `sources`
+    /// or if any of them is bigger than [source_count](LutFabricConfig::source_count).
+    pub fn lut<F: Fn(&[bool]) -> bool>(
+        &mut self,
+        row: usize,
+        col: usize,
+        sources: &[usize],
+        f: F,
+    ) -> &mut Self {
+        assert!(
+            row < self.rows && col < self.cols,
+            "LUT ({}, {}) is outside of the {}x{} fabric",
+            row,
+            col,
+            self.rows,
+            self.cols
+        );
+        assert!(
+            sources.len() <= LUT_INPUTS,
+            "A LUT has {} inputs, got {} sources",
+            LUT_INPUTS,
+            sources.len()
+        );
+        let source_count = self.source_count(col);
+        assert!(
+            sources.iter().all(|source| *source < source_count),
+            "LUTs in column {} can only be routed from {} sources, got: {:?}",
+            col,
+            source_count,
+            sources
+        );
+
+        let mut lut = LutConfig::default();
+        lut.sources[..sources.len()].copy_from_slice(sources);
+        // Unused inputs are routed from source 0, so the table ignores them.
+        let mut values = vec![false; sources.len()];
+        for i in 0..LUT_TABLE_BITS {
+            for (bit, value) in values.iter_mut().enumerate() {
+                *value = i >> bit & 1 == 1;
+            }
+            if f(&values) {
+                lut.table |= 1 << i;
+            }
+        }
+        self.luts[col * self.rows + row] = lut;
+        self
+    }
+
+    /// Returns the bitstream in the order it has to be shifted into the fabric.
+    pub fn bitstream(&self) -> Vec<bool> {
+        // Bits in the order of the shift register stages, the first bit shifted in ends up in the last stage.
+        let mut bits = Vec::new();
+        for col in 0..self.cols {
+            let select_bits = select_bits(self.source_count(col));
+            for lut in &self.luts[col * self.rows..(col + 1) * self.rows] {
+                for source in &lut.sources {
+                    bits.extend((0..select_bits).map(|bit| source >> bit & 1 == 1));
+                }
+                bits.extend((0..LUT_TABLE_BITS).map(|bit| lut.table >> bit & 1 == 1));
+            }
+        }
+        bits.reverse();
+        bits
+    }
+
+    /// Shifts the bitstream into a [lut_fabric] through its `config_clock`, `load` and `config_data` levers.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the circuit does not stabilize.
+    pub fn load(
+        &self,
+        ig: &mut InitializedGateGraph,
+        config_clock: LeverHandle,
+        load: LeverHandle,
+        config_data: LeverHandle,
+    ) {
+        ig.set_lever_stable(load);
+        for bit in self.bitstream() {
+            ig.update_lever(config_data, bit);
+            ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+            ig.pulse_lever_stable(config_clock);
+        }
+        ig.reset_lever_stable(load);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::WordInput;
+    use super::*;
+
+    #[test]
+    fn test_select_bits() {
+        assert_eq!(select_bits(1), 0);
+        assert_eq!(select_bits(2), 1);
+        assert_eq!(select_bits(3), 2);
+        assert_eq!(select_bits(4), 2);
+        assert_eq!(select_bits(5), 3);
+    }
+
+    #[test]
+    fn test_lut_fabric_full_adder_and_reconfiguration() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let config_clock = g.lever("config_clock");
+        let load = g.lever("load");
+        let config_data = g.lever("config_data");
+        let input = WordInput::new(g, 3, "input");
+        let outputs = lut_fabric(
+            g,
+            2,
+            2,
+            LutRouting::AllPrevious,
+            config_clock.bit(),
+            load.bit(),
+            config_data.bit(),
+            &input.bits(),
+            "fabric",
+        );
+        let output = g.output(&outputs, "output");
+        let ig = &mut graph.init();
+
+        // Full adder, the first column computes a ^ b and a & b, the second one the sum and the carry.
+        // Sources of the second column: a, b, cin, a ^ b, a & b.
+        let mut adder = LutFabricConfig::new(2, 2, 3, LutRouting::AllPrevious);
+        adder
+            .lut(0, 0, &[0, 1], |i| i[0] ^ i[1])
+            .lut(1, 0, &[0, 1], |i| i[0] & i[1])
+            .lut(0, 1, &[3, 2], |i| i[0] ^ i[1])
+            .lut(1, 1, &[4, 3, 2], |i| i[0] | (i[1] & i[2]));
+        adder.load(ig, config_clock, load, config_data);
+        for value in 0..8u8 {
+            input.set_to(ig, value);
+            ig.run_until_stable(50).unwrap();
+            let expected = (value & 1) + (value >> 1 & 1) + (value >> 2 & 1);
+            assert_eq!(output.u8(ig), expected, "{:03b}", value);
+        }
+
+        // Majority and parity, straight from the inputs in the second column.
+        let mut other = LutFabricConfig::new(2, 2, 3, LutRouting::AllPrevious);
+        other
+            .lut(0, 1, &[0, 1, 2], |i| i.iter().filter(|b| **b).count() >= 2)
+            .lut(1, 1, &[0, 1, 2], |i| i[0] ^ i[1] ^ i[2]);
+        other.load(ig, config_clock, load, config_data);
+        for value in 0..8u8 {
+            input.set_to(ig, value);
+            ig.run_until_stable(50).unwrap();
+            let ones = value.count_ones() as u8;
+            assert_eq!(output.u8(ig), (ones >= 2) as u8 | (ones & 1) << 1);
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use crate::{graph::*, Bus, Wire, WordInput};
+use memmap2::MmapMut;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::Path;
+
+fn mkname(name: String) -> String {
+    format!("MMAPMEM:{}", name)
+}
+
+/// Data structure that represents a piece of memory whose contents live in a memory mapped file in the host
+/// instead of in gates, so other processes can inspect and modify the memory of the simulated circuit while it runs.
+/// For example a separate process could render a framebuffer.
+///
+/// Word `n` is stored little endian at byte offset `n * bytes_per_word` of the file,
+/// where `bytes_per_word` is the width rounded up to whole bytes.
+///
+/// The circuit side works like [ram](super::ram) without a clock, the accesses are performed by
+/// [MappedMemory::update], which should be called after every clock edge of the circuit that uses the memory.
+///
+/// Only available with the "memmap" feature.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,MappedMemory,WordInput};
+/// # let mut g = GateGraphBuilder::new();
+/// # let path = std::env::temp_dir().join(format!("logicsim_mapped_memory_doctest_{}", std::process::id()));
+/// let address = WordInput::new(&mut g, 4, "address");
+/// let input = WordInput::new(&mut g, 8, "input");
+/// let read = g.lever("read");
+/// let write = g.lever("write");
+///
+/// let mut memory = MappedMemory::new(&mut g, &path, 8, 4, "memory").unwrap();
+/// let output = memory.connect(&mut g, read.bit(), write.bit(), &address.bits(), &input.bits());
+/// let output = g.output(&output, "output");
+///
+/// let ig = &mut g.init();
+///
+/// // Write from the circuit.
+/// address.set_to(ig, 3u8);
+/// input.set_to(ig, 42u8);
+/// ig.set_lever_stable(write);
+/// memory.update(ig);
+/// ig.reset_lever_stable(write);
+/// assert_eq!(memory.as_slice()[3], 42);
+///
+/// // Write from the host, or any other process that maps the file.
+/// memory.as_mut_slice()[3] += 1;
+///
+/// ig.set_lever_stable(read);
+/// memory.update(ig);
+/// assert_eq!(output.u8(ig), 43);
+/// ```
+pub struct MappedMemory {
+    mmap: MmapMut,
+    bytes_per_word: usize,
+    address_bus: Bus,
+    input_bus: Bus,
+    read: Wire,
+    write: Wire,
+    address_output: OutputHandle,
+    input_output: OutputHandle,
+    read_output: OutputHandle,
+    write_output: OutputHandle,
+    data: WordInput,
+}
+impl MappedMemory {
+    /// Returns a new [MappedMemory] of 2^`address_bits` words of `width` bits, backed by the file at `path`.
+    ///
+    /// The file is created if it doesn't exist and grown if it is too small, its existing contents are kept.
+    ///
+    /// Returns an error of kind [InvalidInput](io::ErrorKind::InvalidInput) if the memory is too big
+    /// to be mapped on this machine.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `width` > 128 or `address_bits` > 63.
+    pub fn new<P: AsRef<Path>, S: Into<String>>(
+        g: &mut GateGraphBuilder,
+        path: P,
+        width: usize,
+        address_bits: usize,
+        name: S,
+    ) -> io::Result<Self> {
+        assert!(
+            width <= 128,
+            "MappedMemory words can't be wider than 128 bits, width: {}",
+            width
+        );
+        assert!(
+            address_bits < 64,
+            "MappedMemory can't have more than 2^63 words, address_bits: {}",
+            address_bits
+        );
+        let name = mkname(name.into());
+
+        let bytes_per_word = width.div_ceil(8);
+        let len = 1usize
+            .checked_shl(address_bits as u32)
+            .and_then(|words| words.checked_mul(bytes_per_word))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "MappedMemory of 2^{} words of {} bytes doesn't fit in the address space",
+                        address_bits, bytes_per_word
+                    ),
+                )
+            })? as u64;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        if file.metadata()?.len() < len {
+            file.set_len(len)?;
+        }
+        // This is safe as long as nobody truncates the file while it's mapped,
+        // concurrent modifications are the whole point.
+        let mmap = unsafe { MmapMut::map_mut(&file)? };
+
+        let address_bus = Bus::new(g, address_bits, name.clone());
+        let input_bus = Bus::new(g, width, name.clone());
+        let read = Wire::new(g, name.clone());
+        let write = Wire::new(g, name.clone());
+
+        let address_output = g.output(address_bus.bits(), name.clone());
+        let input_output = g.output(input_bus.bits(), name.clone());
+        let read_output = g.output1(read.bit(), name.clone());
+        let write_output = g.output1(write.bit(), name.clone());
+
+        let data = WordInput::new(g, width, name);
+
+        Ok(Self {
+            mmap,
+            bytes_per_word,
+            address_bus,
+            input_bus,
+            read,
+            write,
+            address_output,
+            input_output,
+            read_output,
+            write_output,
+            data,
+        })
+    }
+
+    /// Connects the [MappedMemory] to a circuit and returns its output,
+    /// which will hold the word at `address` after an [update](MappedMemory::update) with `read` active,
+    /// and zero after an update with `read` inactive.
+    ///
+    /// If `write` is active during an update, `input` will be stored at `address`.
+    pub fn connect(
+        &self,
+        g: &mut GateGraphBuilder,
+        read: GateIndex,
+        write: GateIndex,
+        address: &[GateIndex],
+        input: &[GateIndex],
+    ) -> Vec<GateIndex> {
+        self.address_bus.connect(g, address);
+        self.input_bus.connect(g, input);
+        self.read.connect(g, read);
+        self.write.connect(g, write);
+        self.data.bits().into_vec()
+    }
+
+    /// Performs the read or write requested by the circuit and runs the circuit until the result is stable.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the circuit doesn't stabilize before or after the access.
+    pub fn update(&mut self, g: &mut InitializedGateGraph) {
+        g.run_until_stable(10).unwrap();
+        let offset = self.address_output.u64(g) as usize * self.bytes_per_word;
+
+        if self.write_output.b0(g) {
+            let value = self.input_output.u128(g).to_le_bytes();
+            self.mmap[offset..offset + self.bytes_per_word]
+                .copy_from_slice(&value[..self.bytes_per_word]);
+        }
+
+        let mut value = [0; 16];
+        if self.read_output.b0(g) {
+            value[..self.bytes_per_word]
+                .copy_from_slice(&self.mmap[offset..offset + self.bytes_per_word]);
+        }
+        self.data.set_to(g, u128::from_le_bytes(value));
+        g.run_until_stable(10).unwrap();
+    }
+
+    /// Returns the contents of the memory.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.mmap
+    }
+
+    /// Returns the contents of the memory mutably.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.mmap
+    }
+
+    /// Flushes the contents of the memory to the file.
+    pub fn flush(&self) -> io::Result<()> {
+        self.mmap.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mapped_memory_shared() {
+        let path = std::env::temp_dir().join(format!(
+            "logicsim_mapped_memory_test_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let address = WordInput::new(g, 2, "address");
+        let input = WordInput::new(g, 12, "input");
+        let read = g.lever("read");
+        let write = g.lever("write");
+
+        let mut memory = MappedMemory::new(g, &path, 12, 2, "memory").unwrap();
+        let output = memory.connect(g, read.bit(), write.bit(), &address.bits(), &input.bits());
+        let output = g.output(&output, "output");
+
+        let g = &mut graph.init();
+
+        g.set_lever_stable(write);
+        for i in 0..4u16 {
+            address.set_to(g, i);
+            input.set_to(g, 0xf00 | i);
+            memory.update(g);
+        }
+        g.reset_lever_stable(write);
+        memory.flush().unwrap();
+
+        // Another mapping of the same file sees the writes.
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        let mut other = unsafe { MmapMut::map_mut(&file).unwrap() };
+        assert_eq!(other.len(), 8);
+        assert_eq!(&other[2..4], &[0x01, 0x0f]);
+
+        // And the circuit sees its writes.
+        other[6] = 0xab;
+        other[7] = 0x0c;
+        g.set_lever_stable(read);
+        address.set_to(g, 3u8);
+        memory.update(g);
+        assert_eq!(output.u16(g), 0xcab);
+
+        g.reset_lever_stable(read);
+        memory.update(g);
+        assert_eq!(output.u16(g), 0);
+
+        drop(other);
+        drop(memory);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_mapped_memory_too_big() {
+        let path = std::env::temp_dir().join(format!(
+            "logicsim_mapped_memory_too_big_test_{}",
+            std::process::id()
+        ));
+        let mut g = GateGraphBuilder::new();
+        let error = MappedMemory::new(&mut g, &path, 128, 63, "memory")
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(!path.exists());
+    }
+}
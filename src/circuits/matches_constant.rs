@@ -0,0 +1,169 @@
+use crate::data_structures::BitIter;
+use crate::graph::*;
+use std::iter::repeat;
+
+fn mkname(name: String) -> String {
+    format!("MATCHCONST:{}", name)
+}
+
+/// Returns a gate that is active when `bits` hold the native endian bits of any
+/// [Copy] + [Sized] + ['static](https://doc.rust-lang.org/rust-by-example/scope/lifetime/static_lifetime.html) `value`.
+///
+/// If `bits` is wider than `value`, the value will be 0 extended.
+/// If it is narrower, the excess bits of `value` will be ignored.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,matches_constant,WordInput};
+/// # let mut g = GateGraphBuilder::new();
+/// let input = WordInput::new(&mut g, 8, "input");
+/// let is_42 = matches_constant(&mut g, &input.bits(), 42u8, "is_42");
+///
+/// let output = g.output1(is_42, "result");
+/// let ig = &mut g.init();
+///
+/// input.set_to(ig, 41u8);
+/// ig.run_until_stable(2).unwrap();
+/// assert_eq!(output.b0(ig), false);
+///
+/// input.set_to(ig, 42u8);
+/// ig.run_until_stable(2).unwrap();
+/// assert_eq!(output.b0(ig), true);
+/// ```
+pub fn matches_constant<T: Copy + Sized + 'static, S: Into<String>>(
+    g: &mut GateGraphBuilder,
+    bits: &[GateIndex],
+    value: T,
+    name: S,
+) -> GateIndex {
+    let expected = BitIter::new(value).chain(repeat(false)).map(Some);
+    matches_inner(g, bits, expected, name.into())
+}
+
+/// Returns a gate that is active when the bits of `bits` which are set in `mask` match those of `value`,
+/// the bits which are not set in `mask` are don't cares.
+///
+/// If `bits` is wider than `value` and `mask`, the excess bits will be don't cares.
+/// If it is narrower, the excess bits of `value` and `mask` will be ignored.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,matches_constant_masked,WordInput};
+/// # let mut g = GateGraphBuilder::new();
+/// let opcode = WordInput::new(&mut g, 8, "opcode");
+/// // Matches any opcode of the form 0b1010xxxx.
+/// let is_jump = matches_constant_masked(&mut g, &opcode.bits(), 0b1010_0000u8, 0b1111_0000u8, "is_jump");
+///
+/// let output = g.output1(is_jump, "result");
+/// let ig = &mut g.init();
+///
+/// opcode.set_to(ig, 0b1010_0110u8);
+/// ig.run_until_stable(2).unwrap();
+/// assert_eq!(output.b0(ig), true);
+///
+/// opcode.set_to(ig, 0b1011_0110u8);
+/// ig.run_until_stable(2).unwrap();
+/// assert_eq!(output.b0(ig), false);
+/// ```
+pub fn matches_constant_masked<T: Copy + Sized + 'static, S: Into<String>>(
+    g: &mut GateGraphBuilder,
+    bits: &[GateIndex],
+    value: T,
+    mask: T,
+    name: S,
+) -> GateIndex {
+    let expected = BitIter::new(value)
+        .zip(BitIter::new(mask))
+        .map(|(bit, care)| if care { Some(bit) } else { None })
+        .chain(repeat(None));
+    matches_inner(g, bits, expected, name.into())
+}
+
+/// Returns an and gate of every bit that is expected to be 1 and a nor gate of every bit that is expected to be 0.
+fn matches_inner<I: Iterator<Item = Option<bool>>>(
+    g: &mut GateGraphBuilder,
+    bits: &[GateIndex],
+    expected: I,
+    name: String,
+) -> GateIndex {
+    let name = mkname(name);
+
+    let out = g.and(name.clone());
+    let zeros = g.nor(name);
+    for (bit, expected) in bits.iter().zip(expected) {
+        match expected {
+            Some(true) => g.dpush(out, *bit),
+            Some(false) => g.dpush(zeros, *bit),
+            None => {}
+        }
+    }
+    g.dpush(out, zeros);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::WordInput;
+    use super::*;
+
+    #[test]
+    fn test_matches_constant() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let input = WordInput::new(g, 4, "input");
+        let matches: Vec<_> = (0..16u8)
+            .map(|i| matches_constant(g, &input.bits(), i, "match"))
+            .collect();
+        let output = g.output(&matches, "out");
+
+        let g = &mut graph.init();
+        for i in 0..16u8 {
+            input.set_to(g, i);
+            g.run_until_stable(10).unwrap();
+            assert_eq!(output.u16(g), 1 << i);
+        }
+    }
+
+    #[test]
+    fn test_matches_constant_extension() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let input = WordInput::new(g, 12, "input");
+        let narrow = matches_constant(g, &input.bits(), 0x0fu8, "narrow");
+        let wide = matches_constant(g, &input.bits()[..4], 0xffffu16, "wide");
+        let output = g.output(&[narrow, wide], "out");
+
+        let g = &mut graph.init();
+        input.set_to(g, 0x00fu16);
+        g.run_until_stable(10).unwrap();
+        assert_eq!(output.u8(g), 0b11);
+
+        input.set_to(g, 0x10fu16);
+        g.run_until_stable(10).unwrap();
+        assert_eq!(output.u8(g), 0b10);
+    }
+
+    #[test]
+    fn test_matches_constant_masked() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let input = WordInput::new(g, 6, "input");
+        let odd = matches_constant_masked(g, &input.bits(), 1u8, 1u8, "odd");
+        let always = matches_constant_masked(g, &input.bits(), 0u8, 0u8, "always");
+        let pattern = matches_constant_masked(g, &input.bits(), 0b100100u8, 0b110110u8, "pattern");
+        let output = g.output(&[odd, always, pattern], "out");
+
+        let g = &mut graph.init();
+        for i in 0..64u8 {
+            input.set_to(g, i);
+            g.run_until_stable(10).unwrap();
+            assert_eq!(output.bx(g, 0), i % 2 == 1);
+            assert_eq!(output.bx(g, 1), true);
+            assert_eq!(output.bx(g, 2), i & 0b110110 == 0b100100);
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use crate::{data_structures::BitIter, graph::*};
+use std::collections::HashMap;
+
+fn mkname(name: String) -> String {
+    format!("MINROM:{}", name)
+}
+
+/// Product term of a sum of products, it contains every address where `address & mask == value`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Cube {
+    mask: usize,
+    value: usize,
+}
+impl Cube {
+    /// Calls `f` with every address in the cube, stops early and returns false if `f` returns false.
+    fn all<F: FnMut(usize) -> bool>(self, width_mask: usize, mut f: F) -> bool {
+        let free = !self.mask & width_mask;
+        let mut sub = 0;
+        loop {
+            if !f(self.value | sub) {
+                return false;
+            }
+            if sub == free {
+                return true;
+            }
+            sub = (sub.wrapping_sub(free)) & free;
+        }
+    }
+}
+
+/// Value of a single output bit at some address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Entry {
+    Off,
+    On,
+    DontCare,
+}
+
+/// Returns a small set of [Cube]s which contain every [Entry::On] and no [Entry::Off] in `table`.
+///
+/// It is a simplified version of the expand and irredundant steps of
+/// [Espresso](https://en.wikipedia.org/wiki/Espresso_heuristic_logic_minimizer),
+/// every uncovered on address gets expanded into the largest cube that doesn't contain an off address,
+/// then the cubes whose on addresses are all contained in other cubes get removed.
+fn minimize(table: &[Entry], address_bits: usize) -> Vec<Cube> {
+    let width_mask = table.len() - 1;
+    let mut covered = vec![0u32; table.len()];
+    let mut cubes = Vec::new();
+
+    for address in 0..table.len() {
+        if table[address] != Entry::On || covered[address] != 0 {
+            continue;
+        }
+        let mut cube = Cube {
+            mask: width_mask,
+            value: address,
+        };
+        for bit in (0..address_bits).rev() {
+            // Only the half of the expanded cube that is not in the current one needs checking.
+            let other_half = Cube {
+                mask: cube.mask,
+                value: cube.value ^ (1 << bit),
+            };
+            if other_half.all(width_mask, |a| table[a] != Entry::Off) {
+                cube.mask &= !(1 << bit);
+                cube.value &= cube.mask;
+            }
+        }
+        cube.all(width_mask, |a| {
+            if table[a] == Entry::On {
+                covered[a] += 1;
+            }
+            true
+        });
+        cubes.push(cube);
+    }
+
+    // The last cubes tend to be the ones that only cover leftovers, try removing those first.
+    let mut i = cubes.len();
+    while i > 0 {
+        i -= 1;
+        let redundant = cubes[i].all(width_mask, |a| table[a] != Entry::On || covered[a] > 1);
+        if redundant {
+            cubes[i].all(width_mask, |a| {
+                if table[a] == Entry::On {
+                    covered[a] -= 1;
+                }
+                true
+            });
+            cubes.remove(i);
+        }
+    }
+    cubes
+}
+
+/// Returns the output of a piece of addressable [ROM](https://en.wikipedia.org/wiki/Read-only_memory) filled with `data`,
+/// built as a minimized sum of products instead of a full [decoder](super::decoder) like [rom](super::rom).
+///
+/// [None] values in `data` are don't cares, the output for those addresses can be anything,
+/// which lets the minimization merge them with their neighbors.
+/// If `data` is not long enough to fill the entire address space, the rest will be filled with [OFF].
+///
+/// This is great for tables that are mostly zeros or have a lot of unused combinations, like microcode ROMs.
+/// Minimization takes time proportional to the size of the address space, so it's meant for addresses of
+/// up to ~20 bits.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,minimized_rom,WordInput,ON};
+/// # let mut g = GateGraphBuilder::new();
+/// let address = WordInput::new(&mut g, 3, "address");
+/// // Addresses 2 and 3 are never used.
+/// let out = minimized_rom(&mut g, ON, &address.bits(), &[Some(3u8), Some(9), None, None, Some(9)], "rom");
+///
+/// let output = g.output(&out, "result");
+///
+/// let ig = &mut g.init();
+/// ig.run_until_stable(2).unwrap();
+/// assert_eq!(output.u8(ig), 3);
+///
+/// address.set_to(ig, 1);
+/// ig.run_until_stable(2).unwrap();
+/// assert_eq!(output.u8(ig), 9);
+///
+/// address.set_to(ig, 4);
+/// ig.run_until_stable(2).unwrap();
+/// assert_eq!(output.u8(ig), 9);
+///
+/// address.set_to(ig, 5);
+/// ig.run_until_stable(2).unwrap();
+/// assert_eq!(output.u8(ig), 0);
+/// ```
+///
+/// # Panics
+///
+/// Will panic if not enough `address` bits are provided to address every value in `data`.
+pub fn minimized_rom<T: Copy + 'static + Sized, S: Into<String>>(
+    g: &mut GateGraphBuilder,
+    read: GateIndex,
+    address: &[GateIndex],
+    data: &[Option<T>],
+    name: S,
+) -> Vec<GateIndex> {
+    assert!(
+        2usize.pow(address.len() as u32) >= data.len(),
+        "`address` doesn't have enough bits to address every input, address bits: {} input len:{}",
+        address.len(),
+        data.len(),
+    );
+    let name = mkname(name.into());
+    let word_length = std::mem::size_of::<T>() * 8;
+    let size = 1 << address.len();
+
+    let words: Vec<Option<Vec<bool>>> = data
+        .iter()
+        .map(|word| word.map(|word| BitIter::new(word).collect()))
+        .collect();
+
+    let naddress: Vec<GateIndex> = address
+        .iter()
+        .map(|bit| g.not1(*bit, name.clone()))
+        .collect();
+    // Product terms are shared between output bits.
+    let mut products = HashMap::<Cube, GateIndex>::new();
+
+    let mut table = vec![Entry::Off; size];
+    let mut out = Vec::with_capacity(word_length);
+    for bit in 0..word_length {
+        for (entry, word) in table.iter_mut().zip(&words) {
+            *entry = match word {
+                Some(word) if word[bit] => Entry::On,
+                Some(_) => Entry::Off,
+                None => Entry::DontCare,
+            };
+        }
+
+        let or = g.or(name.clone());
+        for cube in minimize(&table, address.len()) {
+            let product = *products.entry(cube).or_insert_with(|| {
+                if cube.mask == 0 {
+                    return ON;
+                }
+                let and = g.and(name.clone());
+                for (i, (a, na)) in address.iter().zip(&naddress).enumerate() {
+                    if cube.mask & (1 << i) == 0 {
+                        continue;
+                    }
+                    if cube.value & (1 << i) != 0 {
+                        g.dpush(and, *a);
+                    } else {
+                        g.dpush(and, *na);
+                    }
+                }
+                and
+            });
+            g.dpush(or, product);
+        }
+        out.push(g.and2(or, read, name.clone()));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{rom, WordInput};
+    use super::*;
+
+    fn entries(table: &[u8]) -> Vec<Entry> {
+        table
+            .iter()
+            .map(|e| match e {
+                0 => Entry::Off,
+                1 => Entry::On,
+                _ => Entry::DontCare,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_minimize() {
+        // Only depends on bit 0.
+        let cubes = minimize(&entries(&[0, 1, 0, 1]), 2);
+        assert_eq!(cubes, vec![Cube { mask: 1, value: 1 }]);
+
+        // The don't cares make it a single cube.
+        let cubes = minimize(&entries(&[1, 2, 2, 1, 0, 0, 0, 0]), 3);
+        assert_eq!(cubes, vec![Cube { mask: 4, value: 0 }]);
+
+        let cubes = minimize(&entries(&[2, 2, 2, 2]), 2);
+        assert!(cubes.is_empty());
+
+        let cubes = minimize(&entries(&[1, 2, 1, 1]), 2);
+        assert_eq!(cubes, vec![Cube { mask: 0, value: 0 }]);
+
+        // xor can't be minimized.
+        let cubes = minimize(&entries(&[0, 1, 1, 0]), 2);
+        assert_eq!(cubes.len(), 2);
+    }
+
+    #[test]
+    fn test_minimized_rom_matches_rom() {
+        let mut data = Vec::new();
+        let mut seed = 0x1234_5678u32;
+        for _ in 0..50 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let word = (seed >> 16) as u16;
+            data.push(if word % 5 == 0 { None } else { Some(word) });
+        }
+
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let address = WordInput::new(g, 6, "address");
+        let read = g.lever("read");
+        let minimized = minimized_rom(g, read.bit(), &address.bits(), &data, "minimized");
+        let full_data: Vec<_> = data.iter().map(|word| word.unwrap_or(0)).collect();
+        let full = rom(g, read.bit(), &address.bits(), &full_data, "full");
+        let minimized = g.output(&minimized, "minimized");
+        let full = g.output(&full, "full");
+
+        let g = &mut graph.init();
+        g.run_until_stable(10).unwrap();
+        assert_eq!(minimized.u16(g), 0);
+
+        g.set_lever_stable(read);
+        for i in 0..64 {
+            address.set_to(g, i);
+            g.run_until_stable(10).unwrap();
+            if data.get(i).map_or(true, |word| word.is_some()) {
+                assert_eq!(minimized.u16(g), full.u16(g));
+            }
+        }
+    }
+
+    #[test]
+    fn test_minimized_rom_is_smaller() {
+        // Only the first 4 addresses of the space are used.
+        let data: Vec<_> = (0..256u16)
+            .map(|i| if i < 4 { Some(i as u8) } else { None })
+            .collect();
+
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let address = WordInput::new(g, 8, "address");
+        let before = g.len();
+        let out = minimized_rom(g, ON, &address.bits(), &data, "rom");
+        // 8 nots, 8 ors, 8 ands with read and a product term for each of the 2 low address bits.
+        assert_eq!(g.len() - before, 8 + 8 + 8 + 2);
+        let output = g.output(&out, "out");
+
+        let g = &mut graph.init();
+        for i in 0..4u8 {
+            address.set_to(g, i);
+            g.run_until_stable(10).unwrap();
+            assert_eq!(output.u8(g), i);
+        }
+    }
+}
@@ -1,5 +1,8 @@
 mod adder;
+mod alu;
 mod aluish;
+mod async_fifo;
+mod bit_serial;
 mod bus;
 mod bus_multiplexer;
 mod constant;
@@ -8,16 +11,36 @@ mod counter;
 mod d_flip_flop;
 mod decoder;
 mod io_buffer;
+mod io_bus;
+mod io_channel;
 mod io_register;
+mod johnson_counter;
+mod lut_fabric;
+#[cfg(feature = "memmap")]
+mod mapped_memory;
+mod matches_constant;
+mod minimized_rom;
 mod multiplexer;
+mod netlist;
 mod ram;
+mod random_input;
 mod register;
+mod ring_counter;
 mod rom;
+mod rom_image;
+mod sparse_rom;
+mod spi;
 mod sr_latch;
+mod text_display;
+mod threshold;
+mod uart;
 mod wire;
 mod word_input;
 pub use adder::*;
+pub use alu::*;
 pub use aluish::*;
+pub use async_fifo::*;
+pub use bit_serial::*;
 pub use bus::*;
 pub use bus_multiplexer::*;
 pub use constant::*;
@@ -25,11 +48,28 @@ pub use counter::*;
 pub use d_flip_flop::*;
 pub use decoder::*;
 pub use io_buffer::*;
+pub use io_bus::*;
+pub use io_channel::*;
 pub use io_register::*;
+pub use johnson_counter::*;
+pub use lut_fabric::*;
+#[cfg(feature = "memmap")]
+pub use mapped_memory::*;
+pub use matches_constant::*;
+pub use minimized_rom::minimized_rom;
 pub use multiplexer::*;
+pub use netlist::*;
 pub use ram::*;
+pub use random_input::*;
 pub use register::*;
+pub use ring_counter::*;
 pub use rom::rom;
+pub use rom_image::*;
+pub use sparse_rom::sparse_rom;
+pub use spi::*;
 pub use sr_latch::*;
+pub use text_display::*;
+pub use threshold::*;
+pub use uart::*;
 pub use wire::*;
 pub use word_input::*;
@@ -0,0 +1,162 @@
+#[macro_export]
+/// Declares a circuit in a netlist-like syntax that expands to [GateGraphBuilder](crate::GateGraphBuilder) calls,
+/// naming every component after the variable it gets bound to.
+///
+/// The first argument is the graph, optionally followed by a prefix for all of the names, separated by ":".
+/// Every statement declares variables which are available after the macro, just like [wire!](crate::wire):
+///
+/// - `lever a, b;` creates [levers](crate::GateGraphBuilder::lever).
+/// - `wire a, b;` creates [Wires](crate::Wire).
+/// - `bus a[width], b[width];` creates [Buses](crate::Bus).
+/// - `input a[width], b[width];` creates [WordInputs](crate::WordInput).
+/// - `let a = circuit(args...);` instantiates a circuit, which is any function called like
+///   `circuit(g, args..., name)`, like [adder](crate::adder) or [register](crate::register).
+/// - `gate a = and2(args...);` creates a gate, calling the [GateGraphBuilder](crate::GateGraphBuilder) method like
+///   `g.and2(args..., name)`.
+/// - `connect a <- source;` connects `source` to the [Wire](crate::Wire) or [Bus](crate::Bus) `a`.
+/// - `output a = &bits;` creates an [OutputHandle](crate::OutputHandle).
+///
+/// Keep in mind every statement is a level of macro recursion, very long netlists might need a higher `recursion_limit`.
+///
+/// # Example
+/// ```
+/// # use logicsim::{circuit,register,GateGraphBuilder,ON};
+/// let mut g = GateGraphBuilder::new();
+///
+/// circuit! { &mut g, "parity";
+///     lever clock, reset;
+///     input data[8];
+///     wire write;
+///
+///     let reg = register(clock.bit(), write.bit(), ON, reset.bit(), &data.bits());
+///     gate nreset = not1(reset.bit());
+///     connect write <- nreset;
+///     gate odd = xorx(reg.iter().copied());
+///     output value = &reg;
+///     output is_odd = &[odd];
+/// }
+///
+/// let ig = &mut g.init();
+/// ig.pulse_lever_stable(reset);
+/// data.set_to(ig, 0b1011u8);
+/// ig.pulse_lever_stable(clock);
+///
+/// assert_eq!(value.u8(ig), 0b1011);
+/// assert_eq!(is_odd.b0(ig), true);
+/// assert_eq!(value.name(ig), "parity:value");
+/// ```
+macro_rules! circuit {
+    (@munch $g:expr, $prefix:expr;) => {};
+    (@munch $g:expr, $prefix:expr; lever $($name:ident),+; $($rest:tt)*) => {
+        $(let $name = $g.lever($crate::circuits::netlist_name($prefix, stringify!($name)));)+
+        $crate::circuit!(@munch $g, $prefix; $($rest)*);
+    };
+    (@munch $g:expr, $prefix:expr; wire $($name:ident),+; $($rest:tt)*) => {
+        $(
+            #[allow(unused_mut)]
+            let mut $name = $crate::Wire::new($g, $crate::circuits::netlist_name($prefix, stringify!($name)));
+        )+
+        $crate::circuit!(@munch $g, $prefix; $($rest)*);
+    };
+    (@munch $g:expr, $prefix:expr; bus $($name:ident[$width:expr]),+; $($rest:tt)*) => {
+        $(
+            let $name = $crate::Bus::new(
+                $g,
+                $width,
+                $crate::circuits::netlist_name($prefix, stringify!($name)),
+            );
+        )+
+        $crate::circuit!(@munch $g, $prefix; $($rest)*);
+    };
+    (@munch $g:expr, $prefix:expr; input $($name:ident[$width:expr]),+; $($rest:tt)*) => {
+        $(
+            let $name = $crate::WordInput::new(
+                $g,
+                $width,
+                $crate::circuits::netlist_name($prefix, stringify!($name)),
+            );
+        )+
+        $crate::circuit!(@munch $g, $prefix; $($rest)*);
+    };
+    (@munch $g:expr, $prefix:expr; let $name:ident = $circuit:ident($($arg:expr),* $(,)?); $($rest:tt)*) => {
+        let $name = $circuit($g, $($arg,)* $crate::circuits::netlist_name($prefix, stringify!($name)));
+        $crate::circuit!(@munch $g, $prefix; $($rest)*);
+    };
+    (@munch $g:expr, $prefix:expr; gate $name:ident = $method:ident($($arg:expr),* $(,)?); $($rest:tt)*) => {
+        let $name = $g.$method($($arg,)* $crate::circuits::netlist_name($prefix, stringify!($name)));
+        $crate::circuit!(@munch $g, $prefix; $($rest)*);
+    };
+    (@munch $g:expr, $prefix:expr; connect $target:ident <- $source:expr; $($rest:tt)*) => {
+        $target.connect($g, $source);
+        $crate::circuit!(@munch $g, $prefix; $($rest)*);
+    };
+    (@munch $g:expr, $prefix:expr; output $name:ident = $bits:expr; $($rest:tt)*) => {
+        let $name = $g.output($bits, $crate::circuits::netlist_name($prefix, stringify!($name)));
+        $crate::circuit!(@munch $g, $prefix; $($rest)*);
+    };
+    ($g:expr; $($body:tt)*) => {
+        $crate::circuit!(@munch $g, ""; $($body)*);
+    };
+    ($g:expr, $prefix:expr; $($body:tt)*) => {
+        $crate::circuit!(@munch $g, $prefix; $($body)*);
+    };
+}
+
+/// Returns the name of the component `name` in a [circuit!] with prefix `prefix`.
+#[doc(hidden)]
+pub fn netlist_name(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}:{}", prefix, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::graph::*;
+
+    #[test]
+    fn test_circuit_half_adder() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        circuit! { g;
+            lever a, b;
+            gate sum = xor2(a.bit(), b.bit());
+            gate carry = and2(a.bit(), b.bit());
+            output result = &[sum, carry];
+        }
+
+        let table = graph.init().truth_table(result, &[a, b]);
+        assert_eq!(table, vec![(0, 0), (1, 1), (2, 1), (3, 2)]);
+    }
+
+    #[test]
+    fn test_circuit_bus() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        circuit! { g, "top";
+            input a[4], b[4];
+            lever a_out, b_out;
+            bus data[4];
+            let a_bus = bus_multiplexer(&[a_out.bit()], &[&zeros(4), &a.bits()]);
+            let b_bus = bus_multiplexer(&[b_out.bit()], &[&zeros(4), &b.bits()]);
+            connect data <- &a_bus;
+            connect data <- &b_bus;
+            output result = data.bits();
+        }
+
+        let ig = &mut graph.init();
+        a.set_to(ig, 5);
+        b.set_to(ig, 10);
+        ig.set_lever_stable(b_out);
+        assert_eq!(result.u8(ig), 10);
+        ig.reset_lever_stable(b_out);
+        ig.set_lever_stable(a_out);
+        assert_eq!(result.u8(ig), 5);
+        assert_eq!(result.name(ig), "top:result");
+    }
+}
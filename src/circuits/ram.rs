@@ -36,6 +36,69 @@ pub fn ram<S: Into<String>>(
 
     outputs
 }
+
+/// Named inputs of a [ram], an alternative to its positional arguments which makes mix-ups like swapping
+/// `read` and `write` stand out.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,RamPorts,WordInput,ON,OFF};
+/// # let mut g = GateGraphBuilder::new();
+/// let clock = g.lever("clock");
+/// let write = g.lever("write");
+/// let address = WordInput::new(&mut g, 2, "address");
+/// let input = WordInput::new(&mut g, 8, "input");
+///
+/// let ram_output = RamPorts {
+///     read: ON,
+///     write: write.bit(),
+///     clock: clock.bit(),
+///     reset: OFF,
+///     address: &address.bits(),
+///     input: &input.bits(),
+/// }
+/// .connect(&mut g, "ram");
+/// let output = g.output(&ram_output, "output");
+///
+/// let ig = &mut g.init();
+/// address.set_to(ig, 2);
+/// input.set_to(ig, 42);
+/// ig.set_lever(write);
+/// ig.pulse_lever_stable(clock);
+/// ig.reset_lever_stable(write);
+/// assert_eq!(output.u8(ig), 42);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct RamPorts<'a> {
+    /// If inactive the output will be inactive.
+    pub read: GateIndex,
+    /// If active during the `clock` raising edge, `input` will be stored at `address`.
+    pub write: GateIndex,
+    /// Clock input to the memory cells, activated on the raising edge.
+    pub clock: GateIndex,
+    /// Will set every memory cell to zero. This is an async reset.
+    pub reset: GateIndex,
+    /// Address of the memory cell to read or write.
+    pub address: &'a [GateIndex],
+    /// Data to write, the output will have the same width.
+    pub input: &'a [GateIndex],
+}
+impl RamPorts<'_> {
+    /// Returns the output of a [ram] connected to these ports.
+    pub fn connect<S: Into<String>>(self, g: &mut GateGraphBuilder, name: S) -> Vec<GateIndex> {
+        ram(
+            g,
+            self.read,
+            self.write,
+            self.clock,
+            self.reset,
+            self.address,
+            self.input,
+            name,
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::WordInput;
@@ -0,0 +1,128 @@
+use super::WordInput;
+use crate::data_structures::SplitMix64;
+use crate::graph::*;
+use smallvec::SmallVec;
+
+/// [WordInput] that sets its levers to pseudo random values generated from a seed,
+/// the same seed always generates the same sequence of values.
+///
+/// It's meant to drive the inputs of a circuit in fuzz tests, where a failing seed reproduces the failure.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,RandomInput,adder,OFF};
+/// let mut g = GateGraphBuilder::new();
+/// let mut a = RandomInput::new(&mut g, 8, 1, "a");
+/// let mut b = RandomInput::new(&mut g, 8, 2, "b");
+/// let sum = adder(&mut g, OFF, &a.bits(), &b.bits(), "adder");
+/// let sum = g.output(&sum, "sum");
+///
+/// let ig = &mut g.init();
+/// for _ in 0..100 {
+///     let a = a.randomize(ig);
+///     let b = b.randomize_stable(ig);
+///     assert_eq!(sum.u8(ig), (a as u8).wrapping_add(b as u8));
+/// }
+/// ```
+pub struct RandomInput {
+    input: WordInput,
+    rng: SplitMix64,
+}
+
+impl RandomInput {
+    /// Returns a new [RandomInput] of width `width` with name `name` whose values are generated from `seed`.
+    pub fn new<S: Into<String>>(
+        g: &mut GateGraphBuilder,
+        width: usize,
+        seed: u64,
+        name: S,
+    ) -> Self {
+        Self {
+            input: WordInput::new(g, width, name),
+            rng: SplitMix64(seed),
+        }
+    }
+
+    /// Sets the levers to the next pseudo random value and returns it,
+    /// only the first 128 bits are returned, wider inputs get random values in every bit.
+    ///
+    /// Calls [tick](InitializedGateGraph::tick) once, like [WordInput::set_to].
+    pub fn randomize(&mut self, g: &mut InitializedGateGraph) -> u128 {
+        let mut values = Vec::with_capacity(self.len());
+        let mut first = 0;
+        for start in (0..self.len()).step_by(128) {
+            let bits = (self.len() - start).min(128);
+            let value = self.rng.bits(bits);
+            if start == 0 {
+                first = value;
+            }
+            values.extend((0..bits).map(|bit| (value >> bit) & 1 == 1));
+        }
+        g.update_levers(self.input.levers(), values.into_iter());
+        first
+    }
+
+    /// Stable version of [randomize](RandomInput::randomize).
+    pub fn randomize_stable(&mut self, g: &mut InitializedGateGraph) -> u128 {
+        let value = self.randomize(g);
+        g.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        value
+    }
+
+    /// Returns the [WordInput] driven by the generator, to set it to specific values.
+    pub fn input(&self) -> &WordInput {
+        &self.input
+    }
+
+    /// Returns the [LeverHandles](LeverHandle) of the [RandomInput], bit 0 first.
+    pub fn levers(&self) -> &[LeverHandle] {
+        self.input.levers()
+    }
+
+    /// Returns a [SmallVec]<[GateIndex]> to connect to other components.
+    pub fn bits(&self) -> SmallVec<[GateIndex; 8]> {
+        self.input.bits()
+    }
+
+    /// Returns the width of the [RandomInput].
+    pub fn len(&self) -> usize {
+        self.input.len()
+    }
+
+    /// Returns true the width of the [RandomInput] == 0.
+    pub fn is_empty(&self) -> bool {
+        self.input.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_input_is_deterministic() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let mut a = RandomInput::new(g, 200, 7, "a");
+        let mut b = RandomInput::new(g, 200, 7, "b");
+        let mut c = RandomInput::new(g, 200, 8, "c");
+        let a_output = g.output(&a.bits(), "a");
+        let b_output = g.output(&b.bits(), "b");
+        let ig = &mut graph.init();
+
+        let mut differences = 0;
+        for _ in 0..10 {
+            let value = a.randomize(ig);
+            assert_eq!(b.randomize(ig), value);
+            assert_eq!(a_output.u128(ig), value);
+            assert_eq!(a_output.bits(ig), b_output.bits(ig));
+            if c.randomize(ig) != value {
+                differences += 1
+            }
+        }
+        assert!(differences > 0);
+        // The bits above 128 are random too.
+        assert!(a_output.bits(ig)[128..].iter().any(|bit| *bit));
+        assert!(!a_output.bits(ig)[128..].iter().all(|bit| *bit));
+    }
+}
@@ -65,9 +65,8 @@ pub fn register<S: Into<String>>(
     let name = mkname(name.into());
 
     let width = input.len();
-    let mut out = Vec::new();
+    let mut out = Vec::with_capacity(width);
 
-    out.reserve(width);
     for bit in input {
         out.push(d_flip_flop(
             g,
@@ -81,6 +80,78 @@ pub fn register<S: Into<String>>(
     }
     out
 }
+
+/// Named inputs of a [register], an alternative to its positional arguments which makes mix-ups like swapping
+/// `read` and `write` stand out. See [register] for what every input does.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,RegisterPorts,WordInput,ON};
+/// # let mut g = GateGraphBuilder::new();
+/// let clock = g.lever("clock");
+/// let reset = g.lever("reset");
+/// let input = WordInput::new(&mut g, 4, "input");
+///
+/// let register_output = RegisterPorts {
+///     clock: clock.bit(),
+///     write: ON,
+///     read: ON,
+///     reset: reset.bit(),
+///     input: &input.bits(),
+/// }
+/// .connect(&mut g, "register");
+/// let output = g.output(&register_output, "output");
+///
+/// let ig = &mut g.init();
+/// ig.pulse_lever_stable(reset);
+/// input.set_to(ig, 9);
+/// ig.pulse_lever_stable(clock);
+/// assert_eq!(output.u8(ig), 9);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct RegisterPorts<'a> {
+    /// Clock input to the register, activated on the raising edge.
+    pub clock: GateIndex,
+    /// If active during the `clock` raising edge, the `input` will be stored in the register.
+    pub write: GateIndex,
+    /// If inactive the output will be inactive.
+    pub read: GateIndex,
+    /// Will set the register to zero. This is an async reset.
+    pub reset: GateIndex,
+    /// Will override the contents of the register if `write` is active on the `clock` raising edge.
+    pub input: &'a [GateIndex],
+}
+impl RegisterPorts<'_> {
+    /// Returns the output of a [register] connected to these ports.
+    pub fn connect<S: Into<String>>(self, g: &mut GateGraphBuilder, name: S) -> Vec<GateIndex> {
+        register(
+            g, self.clock, self.write, self.read, self.reset, self.input, name,
+        )
+    }
+}
+
+/// Returns the output of a master-slave register which stores `input` on the `clock` raising edge
+/// if `write` is active.
+///
+/// The slave is only enabled once the master has been disabled, otherwise short feedback paths
+/// like the ones in shift registers would race through both latches in a single edge.
+pub(super) fn edge_register(
+    g: &mut GateGraphBuilder,
+    clock: GateIndex,
+    write: GateIndex,
+    reset: GateIndex,
+    input: &[GateIndex],
+    name: String,
+) -> Vec<GateIndex> {
+    let nclock = g.not1(clock, name.clone());
+    let master_clock = g.and2(nclock, write, name.clone());
+    let nmaster_clock = g.not1(master_clock, name.clone());
+    let slave_clock = g.and2(clock, nmaster_clock, name.clone());
+
+    let master = register(g, master_clock, ON, ON, reset, input, name.clone());
+    register(g, slave_clock, ON, ON, reset, &master, name)
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::WordInput;
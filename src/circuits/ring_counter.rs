@@ -0,0 +1,126 @@
+use super::edge_register;
+use crate::graph::*;
+
+fn mkname(name: String) -> String {
+    format!("RINGCNTR:{}", name)
+}
+
+/// Returns the output of a [ring counter](https://en.wikipedia.org/wiki/Ring_counter) of width `width`.
+///
+/// Exactly one bit of the output is active at a time, it starts at bit 0 and moves to the next bit
+/// on every `clock` raising edge, wrapping around after the last one.
+///
+/// # Inputs
+///
+/// `clock` Clock input to the counter, activated on the raising edge.
+///
+/// `enable` Counter enable, if it is active during a `clock` raising edge, the counter will advance.
+///
+/// `reset` Will set the counter back to bit 0. This is an async reset.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,ring_counter,ON};
+/// # let mut g = GateGraphBuilder::new();
+/// let reset = g.lever("reset");
+/// let clock = g.lever("clock");
+///
+/// let phases = ring_counter(&mut g, clock.bit(), ON, reset.bit(), 3, "ring");
+/// let output = g.output(&phases, "result");
+///
+/// let ig = &mut g.init();
+/// ig.pulse_lever_stable(reset);
+/// assert_eq!(output.u8(ig), 0b001);
+///
+/// ig.pulse_lever_stable(clock);
+/// assert_eq!(output.u8(ig), 0b010);
+///
+/// ig.pulse_lever_stable(clock);
+/// assert_eq!(output.u8(ig), 0b100);
+///
+/// ig.pulse_lever_stable(clock);
+/// assert_eq!(output.u8(ig), 0b001);
+/// ```
+/// # Panics
+///
+/// Will panic if `width` == 0.
+pub fn ring_counter<S: Into<String>>(
+    g: &mut GateGraphBuilder,
+    clock: GateIndex,
+    enable: GateIndex,
+    reset: GateIndex,
+    width: usize,
+    name: S,
+) -> Vec<GateIndex> {
+    assert!(width > 0, "A ring counter needs at least 1 bit");
+    let name = mkname(name.into());
+
+    // Bit 0 is stored inverted so that the reset state has exactly 1 active bit.
+    let out: Vec<_> = (0..width).map(|_| g.or(name.clone())).collect();
+    let mut next = Vec::with_capacity(width);
+    next.push(g.not1(out[width - 1], name.clone()));
+    next.extend_from_slice(&out[..width - 1]);
+
+    let state = edge_register(g, clock, enable, reset, &next, name.clone());
+    let nstate0 = g.not1(state[0], name);
+    g.dpush(out[0], nstate0);
+    for (bit, state_bit) in out.iter().zip(state).skip(1) {
+        g.dpush(*bit, state_bit);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_counter() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let clock = g.lever("clock");
+        let enable = g.lever("enable");
+        let reset = g.lever("reset");
+
+        let phases = ring_counter(g, clock.bit(), enable.bit(), reset.bit(), 5, "ring");
+        let output = g.output(&phases, "out");
+
+        let g = &mut graph.init();
+        g.run_until_stable(10).unwrap();
+        g.pulse_lever_stable(reset);
+        assert_eq!(output.u8(g), 1);
+
+        g.pulse_lever_stable(clock);
+        assert_eq!(output.u8(g), 1);
+
+        g.set_lever_stable(enable);
+        for i in 1..20 {
+            g.pulse_lever_stable(clock);
+            assert_eq!(output.u8(g), 1 << (i % 5));
+        }
+
+        g.pulse_lever_stable(reset);
+        assert_eq!(output.u8(g), 1);
+    }
+
+    #[test]
+    fn test_ring_counter_1_bit() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+
+        let phases = ring_counter(g, clock.bit(), ON, reset.bit(), 1, "ring");
+        let output = g.output(&phases, "out");
+
+        let g = &mut graph.init();
+        g.run_until_stable(10).unwrap();
+        g.pulse_lever_stable(reset);
+        for _ in 0..4 {
+            assert_eq!(output.b0(g), true);
+            g.pulse_lever_stable(clock);
+        }
+    }
+}
@@ -9,6 +9,8 @@ fn mkname(name: String) -> String {
 /// Returns the output of a piece of addressable [ROM](https://en.wikipedia.org/wiki/Read-only_memory) filled with `data`.
 /// If `data` is not long enough to fill the entire address space, it will be filled with [OFF].
 ///
+/// It builds a gate for every address, for big address spaces that are mostly empty see [sparse_rom](super::sparse_rom).
+///
 /// # Example
 /// ```
 /// # use logicsim::{GateGraphBuilder,rom,WordInput,ON,OFF};
@@ -58,13 +60,13 @@ pub fn rom<T: Copy + 'static + Sized, S: Into<String>>(
     let decoded = decoder(g, address, name.clone());
     let out: Vec<GateIndex> = (0..word_length).map(|_| g.or(name.clone())).collect();
 
-    for (word, d) in data.iter().zip(decoded.into_iter()) {
+    for (word, d) in data.iter().zip(decoded) {
         // Toss a coin to your const propagator every once in a while.
         // He already has enough work.
         if BitIter::new(*word).is_zero() {
             continue;
         }
-        for (or, node) in out.iter().zip(constant(*word).into_iter()) {
+        for (or, node) in out.iter().zip(constant(*word)) {
             let and = g.and2(d, node, name.clone());
             g.dpush(*or, and);
         }
@@ -0,0 +1,365 @@
+use super::rom;
+use crate::graph::*;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io;
+use std::path::Path;
+
+/// Format of a ROM image file read by [read_rom_image].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RomFormat {
+    /// Raw bytes, the first byte of the file goes to address 0.
+    Binary,
+    /// [Intel HEX](https://en.wikipedia.org/wiki/Intel_HEX), addresses not present in the file are filled with 0.
+    /// Data, end of file and extended segment/linear address records are supported, start address records are ignored.
+    /// Images are limited to 16MiB, data past that address is rejected.
+    IntelHex,
+}
+impl RomFormat {
+    /// Returns the format of `path` from its extension, ".hex" and ".ihex" are [RomFormat::IntelHex]
+    /// and ".bin" is [RomFormat::Binary].
+    pub fn from_extension<P: AsRef<Path>>(path: P) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()? {
+            "hex" | "ihex" => Some(RomFormat::IntelHex),
+            "bin" => Some(RomFormat::Binary),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the bytes of the ROM image in `path`.
+pub fn read_rom_image<P: AsRef<Path>>(path: P, format: RomFormat) -> io::Result<Vec<u8>> {
+    match format {
+        RomFormat::Binary => std::fs::read(path),
+        RomFormat::IntelHex => parse_intel_hex(&std::fs::read_to_string(path)?),
+    }
+}
+
+/// Returns the output of a [rom] of bytes filled with the image in `path`, see [read_rom_image].
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,rom_from_file,RomFormat,WordInput,ON};
+/// let path = std::env::temp_dir().join("logicsim_rom_doctest.hex");
+/// std::fs::write(&path, ":0300020003090EE1\n:00000001FF\n").unwrap();
+///
+/// let mut g = GateGraphBuilder::new();
+/// let address = WordInput::new(&mut g, 3, "address");
+/// let out = rom_from_file(&mut g, ON, &address.bits(), &path, RomFormat::IntelHex, "rom").unwrap();
+/// let output = g.output(&out, "result");
+///
+/// let ig = &mut g.init();
+/// address.set_to_stable(ig, 3);
+/// assert_eq!(output.u8(ig), 9);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+///
+/// # Panics
+///
+/// Will panic if not enough `address` bits are provided to address every byte in the image.
+pub fn rom_from_file<P: AsRef<Path>, S: Into<String>>(
+    g: &mut GateGraphBuilder,
+    read: GateIndex,
+    address: &[GateIndex],
+    path: P,
+    format: RomFormat,
+    name: S,
+) -> io::Result<Vec<GateIndex>> {
+    let data = read_rom_image(path, format)?;
+    Ok(rom(g, read, address, &data, name))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Intel HEX records can address 4GiB, far more than any [rom] made of gates, so addresses past this are rejected
+/// instead of allocating an image that big.
+const INTEL_HEX_MAX_LEN: usize = 1 << 24;
+
+/// Returns the bytes described by the Intel HEX records in `text`.
+fn parse_intel_hex(text: &str) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    let mut base: usize = 0;
+    for (line_number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |message: &str| invalid_data(format!("Line {}: {}", line_number + 1, message));
+        let hex = line
+            .strip_prefix(':')
+            .ok_or_else(|| error("records must start with ':'"))?;
+        if hex.len() % 2 != 0 || !hex.is_ascii() {
+            return Err(error("invalid record"));
+        }
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| error("invalid hexadecimal digit"))?;
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(error("the byte count doesn't match the record length"));
+        }
+        if bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+            return Err(error("wrong checksum"));
+        }
+
+        let offset = (bytes[1] as usize) << 8 | bytes[2] as usize;
+        let record = &bytes[4..bytes.len() - 1];
+        let value = || {
+            record
+                .iter()
+                .fold(0, |value, byte| value << 8 | *byte as usize)
+        };
+        match bytes[3] {
+            0x00 => {
+                let end = base
+                    .checked_add(offset)
+                    .and_then(|start| start.checked_add(record.len()))
+                    .filter(|end| *end <= INTEL_HEX_MAX_LEN)
+                    .ok_or_else(|| error("data past the maximum image size of 16MiB"))?;
+                let start = end - record.len();
+                if data.len() < end {
+                    data.resize(end, 0);
+                }
+                data[start..end].copy_from_slice(record);
+            }
+            0x01 => return Ok(data),
+            0x02 | 0x04 if record.len() != 2 => {
+                return Err(error("extended address records must have 2 bytes of data"))
+            }
+            0x02 => base = value() << 4,
+            0x04 => base = value() << 16,
+            0x03 | 0x05 => {}
+            _ => return Err(error("unknown record type")),
+        }
+    }
+    Err(invalid_data("Missing end of file record".to_string()))
+}
+
+/// Lays out the contents of a [rom] word by word, with labels that other words can point to,
+/// so small programs can be written without computing addresses by hand.
+///
+/// Words are written at the current address, which starts at 0 and advances with every word,
+/// [org](RomBuilder::org) moves it anywhere, the gaps are filled with 0.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,rom,RomBuilder,WordInput,ON};
+/// let data = RomBuilder::<u8>::new()
+///     .word(0x10)
+///     .label_address("message")
+///     .org(4)
+///     .label("message")
+///     .words(b"hi")
+///     .finish()
+///     .unwrap();
+/// assert_eq!(data, [0x10, 4, 0, 0, b'h', b'i']);
+///
+/// let mut g = GateGraphBuilder::new();
+/// let address = WordInput::new(&mut g, 3, "address");
+/// let out = rom(&mut g, ON, &address.bits(), &data, "rom");
+/// let output = g.output(&out, "result");
+///
+/// let ig = &mut g.init();
+/// address.set_to_stable(ig, 1);
+/// assert_eq!(output.u8(ig), 4);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RomBuilder<T> {
+    words: Vec<T>,
+    address: usize,
+    labels: HashMap<String, usize>,
+    /// Addresses of the words to replace with the address of a label.
+    references: Vec<(usize, String)>,
+}
+impl<T: Copy + Default + TryFrom<usize>> RomBuilder<T> {
+    /// Returns an empty [RomBuilder].
+    pub fn new() -> Self {
+        Self {
+            words: Vec::new(),
+            address: 0,
+            labels: HashMap::new(),
+            references: Vec::new(),
+        }
+    }
+
+    /// Returns the address the next word will be written to.
+    pub fn address(&self) -> usize {
+        self.address
+    }
+
+    /// Moves the current address to `address`.
+    pub fn org(mut self, address: usize) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// Gives the current address the name `name`.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if there is already a label named `name`.
+    pub fn label<S: Into<String>>(mut self, name: S) -> Self {
+        let name = name.into();
+        assert!(
+            !self.labels.contains_key(&name),
+            "Label {} defined twice",
+            name
+        );
+        self.labels.insert(name, self.address);
+        self
+    }
+
+    /// Writes `value` at the current address and advances it.
+    pub fn word(mut self, value: T) -> Self {
+        if self.words.len() <= self.address {
+            self.words.resize(self.address + 1, T::default());
+        }
+        self.words[self.address] = value;
+        self.address += 1;
+        self
+    }
+
+    /// Writes every value in `values` starting at the current address.
+    pub fn words(self, values: &[T]) -> Self {
+        values.iter().fold(self, |rom, value| rom.word(*value))
+    }
+
+    /// Writes the address of the label `name`, which can be defined later, and advances the current address.
+    pub fn label_address<S: Into<String>>(mut self, name: S) -> Self {
+        self.references.push((self.address, name.into()));
+        self.word(T::default())
+    }
+
+    /// Returns the words to pass to [rom] with every label address filled in.
+    ///
+    /// Returns an error if a label is used but never defined or if its address doesn't fit in a word.
+    pub fn finish(&self) -> Result<Vec<T>, &'static str> {
+        let mut words = self.words.clone();
+        for (position, name) in &self.references {
+            let address = *self
+                .labels
+                .get(name)
+                .ok_or("The address of a label that was never defined is used")?;
+            words[*position] =
+                T::try_from(address).map_err(|_| "The address of a label doesn't fit in a word")?;
+        }
+        Ok(words)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intel_hex() {
+        let hex = "
+            :020000040001F9
+            :03000000010203F7
+            :020000022000DC
+            :01000200AA53
+            :0400000500000000F7
+            :00000001FF
+            :01000000FF00
+        ";
+        let data = parse_intel_hex(hex).unwrap();
+        assert_eq!(data.len(), 0x2_0003);
+        assert_eq!(&data[0x1_0000..0x1_0003], &[1, 2, 3]);
+        assert_eq!(data[0x2_0002], 0xAA);
+        assert!(data[..0x1_0000].iter().all(|byte| *byte == 0));
+
+        assert!(parse_intel_hex(":03000000010203F8\n:00000001FF").is_err());
+        assert!(parse_intel_hex(":0300000001020F7\n:00000001FF").is_err());
+        assert!(parse_intel_hex("03000000010203F7\n:00000001FF").is_err());
+        assert!(parse_intel_hex(":03000000010203F7").is_err());
+    }
+
+    /// Returns an Intel HEX record with the right byte count and checksum.
+    fn record(ty: u8, offset: u16, data: &[u8]) -> String {
+        let mut bytes = vec![data.len() as u8, (offset >> 8) as u8, offset as u8, ty];
+        bytes.extend_from_slice(data);
+        let sum = bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        bytes.push(sum.wrapping_neg());
+        let hex: String = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        format!(":{}\n", hex)
+    }
+
+    #[test]
+    fn test_intel_hex_malformed() {
+        let eof = record(1, 0, &[]);
+        let parse = |records: &[String]| parse_intel_hex(&(records.concat() + &eof));
+        let kind = |records: &[String]| parse(records).unwrap_err().kind();
+
+        // Extended address records with the wrong length.
+        let oversized = record(4, 0, &[0xFF; 9]);
+        assert_eq!(
+            kind(&[oversized, record(0, 0, &[1])]),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(kind(&[record(2, 0, &[0x10])]), io::ErrorKind::InvalidData);
+        // Valid records that point past the maximum image size.
+        assert_eq!(
+            kind(&[record(4, 0, &[0x0F, 0xFF]), record(0, 0, &[1])]),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            kind(&[record(4, 0, &[0xFF, 0xFF]), record(0, 0xFFFF, &[1, 2])]),
+            io::ErrorKind::InvalidData
+        );
+        // Right up to the limit is fine.
+        let data = parse(&[record(4, 0, &[0x00, 0xFF]), record(0, 0xFFFF, &[7])]).unwrap();
+        assert_eq!(data.len(), INTEL_HEX_MAX_LEN);
+        assert_eq!(data[INTEL_HEX_MAX_LEN - 1], 7);
+    }
+
+    #[test]
+    fn test_read_rom_image() {
+        let path = std::env::temp_dir().join("logicsim_test_read_rom_image.bin");
+        std::fs::write(&path, [1, 2, 3]).unwrap();
+        assert_eq!(RomFormat::from_extension(&path), Some(RomFormat::Binary));
+        assert_eq!(read_rom_image(&path, RomFormat::Binary).unwrap(), [1, 2, 3]);
+        assert!(read_rom_image(&path, RomFormat::IntelHex).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            RomFormat::from_extension("rom.hex"),
+            Some(RomFormat::IntelHex)
+        );
+        assert_eq!(RomFormat::from_extension("rom"), None);
+    }
+
+    #[test]
+    fn test_rom_builder() {
+        let words = RomBuilder::<u16>::new()
+            .label_address("end")
+            .label("loop")
+            .word(7)
+            .label_address("loop")
+            .org(10)
+            .label("end")
+            .finish()
+            .unwrap();
+        assert_eq!(words, [10, 7, 1]);
+
+        assert!(RomBuilder::<u8>::new()
+            .label_address("nowhere")
+            .finish()
+            .is_err());
+        assert!(RomBuilder::<u8>::new()
+            .label_address("far")
+            .org(256)
+            .label("far")
+            .finish()
+            .is_err());
+        // Overwriting.
+        let words = RomBuilder::<u8>::new()
+            .words(&[1, 2, 3])
+            .org(1)
+            .word(5)
+            .finish()
+            .unwrap();
+        assert_eq!(words, [1, 5, 3]);
+    }
+}
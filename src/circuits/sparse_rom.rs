@@ -0,0 +1,225 @@
+use crate::{data_structures::BitIter, graph::*};
+use std::collections::{HashMap, HashSet};
+
+fn mkname(name: String) -> String {
+    format!("SPARSEROM:{}", name)
+}
+
+/// Lines of a decoder for `address` which are only created the first time they are needed.
+struct LazyDecoder<'a> {
+    address: &'a [GateIndex],
+    naddress: &'a [GateIndex],
+    lines: HashMap<usize, GateIndex>,
+}
+impl<'a> LazyDecoder<'a> {
+    fn new(address: &'a [GateIndex], naddress: &'a [GateIndex]) -> Self {
+        Self {
+            address,
+            naddress,
+            lines: HashMap::new(),
+        }
+    }
+
+    /// Returns the gate which is on while `address` is `value`, [ON] if there are no address bits.
+    fn line(&mut self, g: &mut GateGraphBuilder, value: usize, name: &str) -> GateIndex {
+        if self.address.is_empty() {
+            return ON;
+        }
+        let (address, naddress) = (self.address, self.naddress);
+        *self.lines.entry(value).or_insert_with(|| {
+            let line = g.and(name);
+            for (i, (a, na)) in address.iter().zip(naddress).enumerate() {
+                if value & (1 << i) != 0 {
+                    g.dpush(line, *a);
+                } else {
+                    g.dpush(line, *na);
+                }
+            }
+            line
+        })
+    }
+}
+
+/// Returns the output of a piece of addressable [ROM](https://en.wikipedia.org/wiki/Read-only_memory)
+/// that contains the words in `words` at their addresses and [OFF] everywhere else.
+///
+/// [rom](super::rom) builds a full [decoder](super::decoder), with a gate for every address, even the empty ones.
+/// This one only builds logic for the non zero words in `words`: the address is split in two halves with
+/// a small decoder each, which only get the lines some word uses, and every word is selected by the and of
+/// a line of each half. A 16 bit ROM with a few hundred words takes a few hundred gates instead of 65536,
+/// which makes it a good fit for program and microcode ROMs that are mostly empty.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,sparse_rom,WordInput,ON};
+/// # let mut g = GateGraphBuilder::new();
+/// let address = WordInput::new(&mut g, 16, "address");
+/// let out = sparse_rom(&mut g, ON, &address.bits(), &[(0, 3u8), (0x1234, 9), (0xffff, 1)], "rom");
+/// assert!(g.len() < 100);
+///
+/// let output = g.output(&out, "result");
+///
+/// let ig = &mut g.init();
+/// ig.run_until_stable(2).unwrap();
+/// assert_eq!(output.u8(ig), 3);
+///
+/// address.set_to(ig, 0x1234);
+/// ig.run_until_stable(2).unwrap();
+/// assert_eq!(output.u8(ig), 9);
+///
+/// address.set_to(ig, 0xffff);
+/// ig.run_until_stable(2).unwrap();
+/// assert_eq!(output.u8(ig), 1);
+///
+/// address.set_to(ig, 0x1235);
+/// ig.run_until_stable(2).unwrap();
+/// assert_eq!(output.u8(ig), 0);
+/// ```
+///
+/// # Panics
+///
+/// Will panic if an address in `words` doesn't fit in `address` or appears more than once.
+pub fn sparse_rom<T: Copy + 'static + Sized, S: Into<String>>(
+    g: &mut GateGraphBuilder,
+    read: GateIndex,
+    address: &[GateIndex],
+    words: &[(usize, T)],
+    name: S,
+) -> Vec<GateIndex> {
+    let name = mkname(name.into());
+    let word_length = std::mem::size_of::<T>() * 8;
+
+    let naddress: Vec<GateIndex> = address
+        .iter()
+        .map(|bit| g.not1(*bit, name.clone()))
+        .collect();
+    let half = address.len() / 2;
+    let mut low = LazyDecoder::new(&address[..half], &naddress[..half]);
+    let mut high = LazyDecoder::new(&address[half..], &naddress[half..]);
+
+    let out: Vec<GateIndex> = (0..word_length).map(|_| g.or(name.clone())).collect();
+    let mut used = HashSet::with_capacity(words.len());
+    for (word_address, word) in words {
+        assert!(
+            address.len() >= usize::BITS as usize || *word_address >> address.len() == 0,
+            "`address` doesn't have enough bits to address {}, address bits: {}",
+            word_address,
+            address.len(),
+        );
+        assert!(
+            used.insert(*word_address),
+            "Address {} appears more than once",
+            word_address
+        );
+        let bits = BitIter::new(*word);
+        if bits.is_zero() {
+            continue;
+        }
+
+        let low_line = low.line(g, word_address & ((1 << half) - 1), &name);
+        let high_line = high.line(g, word_address >> half, &name);
+        let line = if low_line == ON {
+            high_line
+        } else if high_line == ON {
+            low_line
+        } else {
+            g.and2(low_line, high_line, name.clone())
+        };
+        for (or, bit) in out.iter().zip(bits) {
+            if bit {
+                g.dpush(*or, line);
+            }
+        }
+    }
+
+    out.into_iter()
+        .map(|or| g.and2(or, read, name.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{rom, WordInput};
+    use super::*;
+    use crate::data_structures::SplitMix64;
+
+    #[test]
+    fn test_sparse_rom_matches_rom() {
+        let mut rng = SplitMix64(7);
+        let mut data = vec![0u16; 256];
+        let mut words = Vec::new();
+        for (address, data) in data.iter_mut().enumerate() {
+            if rng.one_in(8) {
+                *data = rng.next() as u16;
+                words.push((address, *data));
+            }
+        }
+        // Zero words are skipped.
+        words.push((1, 0));
+        data[1] = 0;
+
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let address = WordInput::new(g, 8, "address");
+        let read = g.lever("read");
+        let sparse = sparse_rom(g, read.bit(), &address.bits(), &words, "sparse");
+        let full = rom(g, read.bit(), &address.bits(), &data, "full");
+        let sparse = g.output(&sparse, "sparse");
+        let full = g.output(&full, "full");
+
+        let g = &mut graph.init();
+        g.run_until_stable(10).unwrap();
+        assert_eq!(sparse.u16(g), 0);
+
+        g.set_lever_stable(read);
+        for i in 0..256 {
+            address.set_to(g, i);
+            g.run_until_stable(10).unwrap();
+            assert_eq!(sparse.u16(g), full.u16(g));
+        }
+    }
+
+    #[test]
+    fn test_sparse_rom_size() {
+        let words: Vec<(usize, u8)> = (0..32).map(|i| (i * 2011 % 65536, i as u8 + 1)).collect();
+
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let address = WordInput::new(g, 16, "address");
+        let before = g.len();
+        let out = sparse_rom(g, ON, &address.bits(), &words, "rom");
+        // 16 nots, 8 ors, 8 ands with read, at most 32 lines in each half and a word line per word.
+        assert!(g.len() - before <= 16 + 8 + 8 + 32 * 3);
+        let output = g.output(&out, "out");
+
+        let g = &mut graph.init();
+        for (address_value, word) in &words {
+            address.set_to(g, *address_value);
+            g.run_until_stable(10).unwrap();
+            assert_eq!(output.u8(g), *word);
+        }
+
+        // Edge cases, no address bits and a single one.
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let address = WordInput::new(g, 1, "address");
+        let none = sparse_rom(g, ON, &[], &[(0, 5u8)], "none");
+        let one = sparse_rom(g, ON, &address.bits(), &[(1, 6u8)], "one");
+        let none = g.output(&none, "none");
+        let one = g.output(&one, "one");
+        let g = &mut graph.init();
+        g.run_until_stable(10).unwrap();
+        assert_eq!((none.u8(g), one.u8(g)), (5, 0));
+        address.set_to(g, 1);
+        g.run_until_stable(10).unwrap();
+        assert_eq!((none.u8(g), one.u8(g)), (5, 6));
+    }
+
+    #[test]
+    #[should_panic(expected = "appears more than once")]
+    fn test_sparse_rom_duplicate_address() {
+        let mut g = GateGraphBuilder::new();
+        let address = WordInput::new(&mut g, 4, "address");
+        sparse_rom(&mut g, ON, &address.bits(), &[(3, 1u8), (3, 2)], "rom");
+    }
+}
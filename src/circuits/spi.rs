@@ -0,0 +1,458 @@
+use super::edge_register;
+use crate::graph::*;
+
+fn mkname(name: String) -> String {
+    format!("SPI:{}", name)
+}
+
+/// Number of bits in the words exchanged by an [SpiHostDevice].
+pub const SPI_HOST_BITS: usize = 8;
+
+/// Outputs of an [spi_master].
+#[derive(Debug, Clone)]
+pub struct SpiMasterOutput {
+    /// Serial clock.
+    pub sclk: GateIndex,
+    /// Master out slave in, data sent by the master.
+    pub mosi: GateIndex,
+    /// Active during a transfer, connect it to the select input of the slave.
+    pub select: GateIndex,
+    /// Word received during the last transfer.
+    pub data: Vec<GateIndex>,
+    /// Active during the clock cycle after a transfer has finished.
+    pub done: GateIndex,
+}
+
+/// Outputs of an [spi_slave].
+#[derive(Debug, Clone)]
+pub struct SpiSlaveOutput {
+    /// Master in slave out, data sent by the slave.
+    pub miso: GateIndex,
+    /// Word received during the last transfer, complete once `select` goes inactive.
+    pub data: Vec<GateIndex>,
+}
+
+/// Returns the output of the master of an [SPI](https://en.wikipedia.org/wiki/Serial_Peripheral_Interface) bus,
+/// which exchanges `data` for a word of the same width from the slave, most significant bit first.
+///
+/// The bus works in mode 0: `sclk` idles inactive, both ends sample on its raising edge and change their output on
+/// its falling edge. Every bit takes 2 cycles of `clock`.
+///
+/// # Inputs
+///
+/// `clock` System clock, every register changes on its raising edge.
+///
+/// `reset` Stops the transfer. This is an async reset.
+///
+/// `start` Starts a transfer of `data` if there isn't one going on.
+///
+/// `data` Word to send.
+///
+/// `miso` Master in slave out, data sent by the slave.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,spi_master,spi_slave,WordInput,Wire};
+/// let mut g = GateGraphBuilder::new();
+/// let clock = g.lever("clock");
+/// let reset = g.lever("reset");
+/// let start = g.lever("start");
+/// let master_data = WordInput::new(&mut g, 8, "master_data");
+/// let slave_data = WordInput::new(&mut g, 8, "slave_data");
+///
+/// let miso = Wire::new(&mut g, "miso");
+/// let master = spi_master(&mut g, clock.bit(), reset.bit(), start.bit(), &master_data.bits(), miso.bit(), "master");
+/// let slave = spi_slave(&mut g, master.sclk, master.select, master.mosi, &slave_data.bits(), "slave");
+/// miso.connect(&mut g, slave.miso);
+/// let from_slave = g.output(&master.data, "from_slave");
+/// let from_master = g.output(&slave.data, "from_master");
+/// let done = g.output1(master.done, "done");
+///
+/// let ig = &mut g.init();
+/// ig.pulse_lever_stable(reset);
+/// master_data.set_to(ig, 0x12);
+/// slave_data.set_to(ig, 0x34);
+/// ig.set_lever_stable(start);
+/// ig.pulse_lever_stable(clock);
+/// ig.reset_lever_stable(start);
+/// while !done.b0(ig) {
+///     ig.pulse_lever_stable(clock);
+/// }
+/// assert_eq!(from_slave.u8(ig), 0x34);
+/// assert_eq!(from_master.u8(ig), 0x12);
+/// ```
+///
+/// # Panics
+///
+/// Will panic if `data` is empty.
+pub fn spi_master<S: Into<String>>(
+    g: &mut GateGraphBuilder,
+    clock: GateIndex,
+    reset: GateIndex,
+    start: GateIndex,
+    data: &[GateIndex],
+    miso: GateIndex,
+    name: S,
+) -> SpiMasterOutput {
+    assert!(!data.is_empty(), "`data` can't be empty");
+    let name = mkname(name.into());
+    let width = data.len();
+
+    // `pending` has a bit active for every clock cycle left in the transfer, 2 per bit.
+    let pending_next: Vec<_> = (0..width * 2).map(|_| g.or(name.clone())).collect();
+    let pending = edge_register(g, clock, ON, reset, &pending_next, name.clone());
+    let busy = pending[0];
+    let nbusy = g.not1(busy, name.clone());
+    let load = g.and2(start, nbusy, name.clone());
+    let nload = g.not1(load, name.clone());
+    for (i, next) in pending_next.iter().enumerate() {
+        let pending_shifted = pending.get(i + 1).copied().unwrap_or(OFF);
+        g.dpush(*next, load);
+        g.dpush(*next, pending_shifted);
+    }
+
+    let sclk_next = g.and(name.clone());
+    let sclk = edge_register(g, clock, ON, reset, &[sclk_next], name.clone())[0];
+    let nsclk = g.not1(sclk, name.clone());
+    g.dpush(sclk_next, busy);
+    g.dpush(sclk_next, nsclk);
+
+    // Sent on the falling edges of sclk.
+    let shift_out_next: Vec<_> = (0..width).map(|_| g.or(name.clone())).collect();
+    let shift_out_write = g.or2(load, sclk, name.clone());
+    let shift_out = edge_register(
+        g,
+        clock,
+        shift_out_write,
+        reset,
+        &shift_out_next,
+        name.clone(),
+    );
+    for i in 0..width {
+        let shifted = if i == 0 { OFF } else { shift_out[i - 1] };
+        let loaded = g.and2(load, data[i], name.clone());
+        let kept = g.and2(nload, shifted, name.clone());
+        g.dpush(shift_out_next[i], loaded);
+        g.dpush(shift_out_next[i], kept);
+    }
+    let mosi = g.and2(busy, shift_out[width - 1], name.clone());
+
+    // Received on the raising edges of sclk.
+    let shift_in_next: Vec<_> = std::iter::once(miso)
+        .chain(std::iter::repeat(OFF).take(width - 1))
+        .map(|bit| g.or1(bit, name.clone()))
+        .collect();
+    let shift_in = edge_register(g, clock, sclk_next, reset, &shift_in_next, name.clone());
+    for i in 1..width {
+        g.dpush(shift_in_next[i], shift_in[i - 1]);
+    }
+
+    let last_pending = pending.get(1).copied().unwrap_or(OFF);
+    let not_last_pending = g.not1(last_pending, name.clone());
+    let finishing = g.and2(busy, not_last_pending, name.clone());
+    let done = edge_register(g, clock, ON, reset, &[finishing], name)[0];
+
+    SpiMasterOutput {
+        sclk,
+        mosi,
+        select: busy,
+        data: shift_in,
+        done,
+    }
+}
+
+/// Returns the output of a slave of an [SPI](https://en.wikipedia.org/wiki/Serial_Peripheral_Interface) bus,
+/// which sends `data` while it receives a word of the same width from the master, see [spi_master].
+///
+/// # Inputs
+///
+/// `sclk` Serial clock from the master.
+///
+/// `select` Enables the slave, the transfer starts over every time it becomes active.
+///
+/// `mosi` Master out slave in, data sent by the master.
+///
+/// `data` Word to send, it must not change during a transfer.
+///
+/// # Panics
+///
+/// Will panic if `data` is empty.
+pub fn spi_slave<S: Into<String>>(
+    g: &mut GateGraphBuilder,
+    sclk: GateIndex,
+    select: GateIndex,
+    mosi: GateIndex,
+    data: &[GateIndex],
+    name: S,
+) -> SpiSlaveOutput {
+    assert!(!data.is_empty(), "`data` can't be empty");
+    let name = mkname(name.into());
+    let width = data.len();
+
+    let shift_in_next: Vec<_> = std::iter::once(mosi)
+        .chain(std::iter::repeat(OFF).take(width - 1))
+        .map(|bit| g.or1(bit, name.clone()))
+        .collect();
+    let shift_in = edge_register(g, sclk, select, OFF, &shift_in_next, name.clone());
+    for i in 1..width {
+        g.dpush(shift_in_next[i], shift_in[i - 1]);
+    }
+
+    // `sent` has a bit active for every bit that has been sent, it moves on the falling edges of sclk.
+    let nsclk = g.not1(sclk, name.clone());
+    let nselect = g.not1(select, name.clone());
+    let sent_next: Vec<_> = std::iter::once(ON)
+        .chain(std::iter::repeat(OFF).take(width - 1))
+        .map(|bit| g.or1(bit, name.clone()))
+        .collect();
+    let sent = edge_register(g, nsclk, ON, nselect, &sent_next, name.clone());
+    for i in 1..width {
+        g.dpush(sent_next[i], sent[i - 1]);
+    }
+
+    let miso = g.or(name.clone());
+    for i in 0..width {
+        let previous_sent = if i == 0 { ON } else { sent[i - 1] };
+        let not_sent = g.not1(sent[i], name.clone());
+        let current = g.andx(
+            [previous_sent, not_sent, data[width - 1 - i]]
+                .iter()
+                .copied(),
+            name.clone(),
+        );
+        g.dpush(miso, current);
+    }
+    let miso = g.and2(miso, select, name);
+
+    SpiSlaveOutput {
+        miso,
+        data: shift_in,
+    }
+}
+
+/// Rust model of a device connected to an [SpiHostDevice], like a sensor.
+pub trait SpiDevice: Send {
+    /// Called at the end of every transfer with the word `received` from the master,
+    /// returns the word to send during the next transfer. The first transfer sends 0.
+    fn transfer(&mut self, received: u8) -> u8;
+}
+
+/// [HostPeripheral] which acts as the slave of an [spi_master], exchanging [SPI_HOST_BITS] bit words with an
+/// [SpiDevice], so circuits can talk to devices modeled in Rust.
+///
+/// It has to be added to the graph with the clock of the [spi_master], see [InitializedGateGraph::add_peripheral].
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,spi_master,SpiDevice,SpiHostDevice,WordInput};
+/// // Replies to every read of register 1 with the temperature.
+/// struct Thermometer {
+///     temperature: u8,
+/// }
+/// impl SpiDevice for Thermometer {
+///     fn transfer(&mut self, received: u8) -> u8 {
+///         if received == 1 { self.temperature } else { 0 }
+///     }
+/// }
+///
+/// let mut g = GateGraphBuilder::new();
+/// let clock = g.lever("clock");
+/// let reset = g.lever("reset");
+/// let start = g.lever("start");
+/// let data = WordInput::new(&mut g, 8, "data");
+/// let miso = g.lever("miso");
+/// let master = spi_master(&mut g, clock.bit(), reset.bit(), start.bit(), &data.bits(), miso.bit(), "master");
+/// let sensor = SpiHostDevice::new(&mut g, master.sclk, master.select, master.mosi, miso, Thermometer { temperature: 21 }, "sensor");
+/// let received = g.output(&master.data, "received");
+/// let done = g.output1(master.done, "done");
+///
+/// let ig = &mut g.init();
+/// ig.pulse_lever_stable(reset);
+/// ig.add_peripheral(clock, sensor);
+/// let mut transfer = |ig: &mut logicsim::InitializedGateGraph, value: u8| {
+///     data.set_to(ig, value);
+///     ig.set_lever_stable(start);
+///     ig.pulse_lever_stable(clock);
+///     ig.reset_lever_stable(start);
+///     while !done.b0(ig) {
+///         ig.pulse_lever_stable(clock);
+///     }
+///     received.u8(ig)
+/// };
+/// // Select register 1, then read it.
+/// transfer(ig, 1);
+/// assert_eq!(transfer(ig, 0), 21);
+/// ```
+pub struct SpiHostDevice<D> {
+    sclk: OutputHandle,
+    select: OutputHandle,
+    mosi: OutputHandle,
+    miso: LeverHandle,
+    device: D,
+    /// Bits exchanged so far in the current transfer.
+    bit: usize,
+    received: u8,
+    sending: u8,
+}
+impl<D: SpiDevice> SpiHostDevice<D> {
+    /// Returns a new [SpiHostDevice] which connects `device` to an [spi_master] through `sclk`, `select`, `mosi`
+    /// and the lever `miso`.
+    pub fn new<S: Into<String>>(
+        g: &mut GateGraphBuilder,
+        sclk: GateIndex,
+        select: GateIndex,
+        mosi: GateIndex,
+        miso: LeverHandle,
+        device: D,
+        name: S,
+    ) -> Self {
+        let name = mkname(name.into());
+        Self {
+            sclk: g.output1(sclk, name.clone()),
+            select: g.output1(select, name.clone()),
+            mosi: g.output1(mosi, name),
+            miso,
+            device,
+            bit: 0,
+            received: 0,
+            sending: 0,
+        }
+    }
+}
+impl<D: SpiDevice> HostPeripheral for SpiHostDevice<D> {
+    fn tick(&mut self, bus: &mut BusView<'_>) {
+        if !bus.b0(self.select) {
+            self.bit = 0;
+            bus.set_lever(self.miso, false);
+            return;
+        }
+        // The next raising edge of the clock is a raising edge of sclk, both ends sample then.
+        if !bus.b0(self.sclk) {
+            let shift = SPI_HOST_BITS - 1 - self.bit;
+            bus.set_lever(self.miso, self.sending >> shift & 1 == 1);
+            self.received = self.received << 1 | bus.b0(self.mosi) as u8;
+            self.bit += 1;
+            if self.bit == SPI_HOST_BITS {
+                self.sending = self.device.transfer(self.received);
+                self.bit = 0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::*;
+
+    #[test]
+    fn test_spi_widths() {
+        for width in 1..=4 {
+            let mut graph = GateGraphBuilder::new();
+            let g = &mut graph;
+            let clock = g.lever("clock");
+            let reset = g.lever("reset");
+            let start = g.lever("start");
+            let master_data = WordInput::new(g, width, "master_data");
+            let slave_data = WordInput::new(g, width, "slave_data");
+            let miso = Wire::new(g, "miso");
+            let master = spi_master(
+                g,
+                clock.bit(),
+                reset.bit(),
+                start.bit(),
+                &master_data.bits(),
+                miso.bit(),
+                "master",
+            );
+            let slave = spi_slave(
+                g,
+                master.sclk,
+                master.select,
+                master.mosi,
+                &slave_data.bits(),
+                "slave",
+            );
+            miso.connect(g, slave.miso);
+            let from_slave = g.output(&master.data, "from_slave");
+            let from_master = g.output(&slave.data, "from_master");
+            let done = g.output1(master.done, "done");
+            let sclk = g.output1(master.sclk, "sclk");
+            let ig = &mut graph.init();
+            ig.pulse_lever_stable(reset);
+
+            for value in 0..1u8 << width {
+                let reply = !value & ((1 << width) - 1);
+                master_data.set_to(ig, value);
+                slave_data.set_to(ig, reply);
+                ig.set_lever_stable(start);
+                ig.pulse_lever_stable(clock);
+                ig.reset_lever_stable(start);
+                let mut sclk_cycles = Vec::new();
+                while !done.b0(ig) {
+                    sclk_cycles.push(sclk.b0(ig));
+                    ig.pulse_lever_stable(clock);
+                }
+                let expected: Vec<bool> = (0..width * 2).map(|i| i % 2 == 1).collect();
+                assert_eq!(sclk_cycles, expected);
+                assert_eq!(from_slave.u8(ig), reply, "width: {}", width);
+                assert_eq!(from_master.u8(ig), value, "width: {}", width);
+            }
+        }
+    }
+
+    /// Echoes the previous word.
+    struct Echo;
+    impl SpiDevice for Echo {
+        fn transfer(&mut self, received: u8) -> u8 {
+            received
+        }
+    }
+
+    #[test]
+    fn test_spi_host_device() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let start = g.lever("start");
+        let data = WordInput::new(g, SPI_HOST_BITS, "data");
+        let miso = g.lever("miso");
+        let master = spi_master(
+            g,
+            clock.bit(),
+            reset.bit(),
+            start.bit(),
+            &data.bits(),
+            miso.bit(),
+            "master",
+        );
+        let echo = SpiHostDevice::new(
+            g,
+            master.sclk,
+            master.select,
+            master.mosi,
+            miso,
+            Echo,
+            "echo",
+        );
+        let received = g.output(&master.data, "received");
+        let done = g.output1(master.done, "done");
+        let ig = &mut graph.init();
+        ig.pulse_lever_stable(reset);
+        ig.add_peripheral(clock, echo);
+
+        // Transfers back to back.
+        data.set_to(ig, 0xA5);
+        ig.set_lever_stable(start);
+        let mut words = Vec::new();
+        for _ in 0..17 * 3 {
+            ig.pulse_lever_stable(clock);
+            if done.b0(ig) {
+                words.push(received.u8(ig));
+                data.set_to(ig, words.len() as u8);
+            }
+        }
+        assert_eq!(words, [0, 0xA5, 1]);
+    }
+}
@@ -20,6 +20,7 @@ fn mkname(name: String) -> String {
 /// let ig = &mut g.init();
 /// // With latches, the initial state should be treated as undefined,
 /// // so remember to always reset your latches at the beginning of the simulation.
+/// // InitializedGateGraph::four_state catches the ones you forget.
 /// ig.pulse_lever_stable(r);
 /// assert_eq!(q_output.b0(ig), false);
 ///
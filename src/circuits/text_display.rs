@@ -0,0 +1,563 @@
+use super::{bus_multiplexer, counter, multiplexer, ram, rom, zeros, Wire};
+use crate::graph::*;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Write};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+fn mkname(name: String) -> String {
+    format!("TXTD:{}", name)
+}
+
+/// Width and height in pixels of the glyphs of a [text_display].
+pub const GLYPH_SIZE: usize = 8;
+/// Number of bits needed to address a pixel or a row inside a glyph.
+const GLYPH_BITS: usize = 3;
+
+/// Flags of the timing ROMs of a [text_display], one word per position of the horizontal or vertical counter.
+const TIMING_VISIBLE: u8 = 1;
+const TIMING_SYNC: u8 = 1 << 1;
+const TIMING_LAST: u8 = 1 << 2;
+
+/// Screen size and sync timings of a [text_display].
+///
+/// Horizontal timings are measured in pixels, one per clock cycle, and vertical timings in lines.
+/// A line is made of the visible pixels followed by the front porch, the sync pulse and the back porch,
+/// and a frame is made of the visible lines followed by the same vertical intervals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextDisplayTiming {
+    /// Characters per row.
+    pub columns: usize,
+    /// Rows of characters.
+    pub rows: usize,
+    pub h_front_porch: usize,
+    pub h_sync: usize,
+    pub h_back_porch: usize,
+    pub v_front_porch: usize,
+    pub v_sync: usize,
+    pub v_back_porch: usize,
+}
+impl TextDisplayTiming {
+    /// Returns the standard VGA 640x480 timing, 80 columns by 60 rows.
+    ///
+    /// It's a big circuit, a character RAM of 128x64 cells, expect it to take a while to build and simulate.
+    pub fn vga_640x480() -> Self {
+        Self {
+            columns: 80,
+            rows: 60,
+            h_front_porch: 16,
+            h_sync: 96,
+            h_back_porch: 48,
+            v_front_porch: 10,
+            v_sync: 2,
+            v_back_porch: 33,
+        }
+    }
+
+    /// Returns the number of visible pixels per line.
+    pub fn width(&self) -> usize {
+        self.columns * GLYPH_SIZE
+    }
+
+    /// Returns the number of visible lines per frame.
+    pub fn height(&self) -> usize {
+        self.rows * GLYPH_SIZE
+    }
+
+    /// Returns the number of clock cycles per line.
+    pub fn h_total(&self) -> usize {
+        self.width() + self.h_front_porch + self.h_sync + self.h_back_porch
+    }
+
+    /// Returns the number of lines per frame.
+    pub fn v_total(&self) -> usize {
+        self.height() + self.v_front_porch + self.v_sync + self.v_back_porch
+    }
+
+    /// Returns the number of clock cycles per frame.
+    pub fn frame_cycles(&self) -> usize {
+        self.h_total() * self.v_total()
+    }
+
+    /// Returns the number of bits of the column part of the character RAM address.
+    pub fn column_bits(&self) -> usize {
+        bits_for(self.columns)
+    }
+
+    /// Returns the number of bits of the character RAM address,
+    /// the row goes in the high bits and the column in the low [column_bits](TextDisplayTiming::column_bits).
+    pub fn address_bits(&self) -> usize {
+        self.column_bits() + bits_for(self.rows)
+    }
+}
+
+/// Outputs of a [text_display].
+#[derive(Debug, Clone, Copy)]
+pub struct TextDisplayOutput {
+    /// Active if the current pixel is lit.
+    pub pixel: GateIndex,
+    /// Horizontal sync pulse, active high.
+    pub hsync: GateIndex,
+    /// Vertical sync pulse, active high.
+    pub vsync: GateIndex,
+    /// Active during the visible part of the frame, like the data enable signal of digital displays.
+    pub visible: GateIndex,
+}
+
+/// Returns the number of bits needed to count from 0 to `n` - 1, at least 1.
+fn bits_for(n: usize) -> usize {
+    ((usize::BITS - n.saturating_sub(1).leading_zeros()) as usize).max(1)
+}
+
+/// Returns a counter of `bits` bits and `total` states which advances on the `clock` raising edges when `enable`
+/// is active, along with the flags of its current state read from a timing ROM.
+/// `visible`, `sync_start` and `sync_len` are measured in counter states.
+// rust-analyzer makes this a non issue.
+#[allow(clippy::too_many_arguments)]
+fn timing_counter(
+    g: &mut GateGraphBuilder,
+    clock: GateIndex,
+    enable: GateIndex,
+    reset: GateIndex,
+    bits: usize,
+    total: usize,
+    visible: usize,
+    sync_start: usize,
+    sync_len: usize,
+    name: String,
+) -> (Vec<GateIndex>, Vec<GateIndex>) {
+    let wrap = Wire::new(g, name.clone());
+    let count = counter(
+        g,
+        clock,
+        enable,
+        wrap.bit(),
+        ON,
+        reset,
+        &zeros(bits.max(bits_for(total))),
+        name.clone(),
+    );
+    let flags: Vec<u8> = (0..total)
+        .map(|i| {
+            let mut flags = 0;
+            if i < visible {
+                flags |= TIMING_VISIBLE;
+            }
+            if (sync_start..sync_start + sync_len).contains(&i) {
+                flags |= TIMING_SYNC;
+            }
+            if i == total - 1 {
+                flags |= TIMING_LAST;
+            }
+            flags
+        })
+        .collect();
+    let flags = rom(g, ON, &count, &flags, name.clone());
+    // Loading zero instead of counting past the last state.
+    let last = g.and2(enable, flag(&flags, TIMING_LAST), name);
+    wrap.connect(g, last);
+    (count, flags)
+}
+
+/// Returns the bit of `flags` corresponding to `flag`.
+fn flag(flags: &[GateIndex], flag: u8) -> GateIndex {
+    flags[flag.trailing_zeros() as usize]
+}
+
+/// Returns the video signals of a character based
+/// [text mode display](https://en.wikipedia.org/wiki/Text_mode) controller, which outputs one pixel per `clock` cycle.
+///
+/// A horizontal and a vertical counter scan the screen, the character under the beam is read from a character RAM
+/// and its glyph row is read from a font ROM holding [GLYPH_SIZE]x[GLYPH_SIZE] glyphs.
+/// The sync pulses and the visible area come from timing ROMs indexed by the counters.
+/// Use a [DisplayMonitor] to turn the signals back into frames.
+///
+/// # Inputs
+///
+/// `clock` Pixel clock, the counters advance on the raising edge.
+///
+/// `write` If active during the `clock` raising edge, `input` will be stored in the character RAM at `address`.
+/// While active, the display reads the character RAM at `address` too, like the snow of old video cards.
+///
+/// `reset` Will set the counters and the character RAM to zero. This is an async reset.
+///
+/// `address` Character RAM address to write, see [TextDisplayTiming::address_bits].
+///
+/// `input` Character code to write, the width of the character codes.
+///
+/// `font` Rows of the glyphs, [GLYPH_SIZE] per character code in order, with the leftmost pixel in the highest bit.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,text_display,DisplayMonitor,TextDisplayTiming,WordInput};
+/// # let mut g = GateGraphBuilder::new();
+/// let clock = g.lever("clock");
+/// let write = g.lever("write");
+/// let reset = g.lever("reset");
+/// let timing = TextDisplayTiming {
+///     columns: 2,
+///     rows: 1,
+///     h_front_porch: 1,
+///     h_sync: 2,
+///     h_back_porch: 1,
+///     v_front_porch: 1,
+///     v_sync: 1,
+///     v_back_porch: 1,
+/// };
+/// let address = WordInput::new(&mut g, timing.address_bits(), "address");
+/// let input = WordInput::new(&mut g, 1, "input");
+/// // Character 0 is blank and character 1 is a box.
+/// let font = [0, 0, 0, 0, 0, 0, 0, 0, 255, 129, 129, 129, 129, 129, 129, 255];
+///
+/// let display = text_display(
+///     &mut g, clock.bit(), write.bit(), reset.bit(), &address.bits(), &input.bits(), &font, timing, "display",
+/// );
+/// let (monitor, frames) = DisplayMonitor::new(&mut g, &display, "monitor");
+///
+/// let ig = &mut g.init();
+/// ig.pulse_lever_stable(reset);
+/// address.set_to(ig, 1);
+/// input.set_to(ig, 1);
+/// ig.set_lever_stable(write);
+/// ig.pulse_lever_stable(clock);
+/// ig.reset_lever_stable(write);
+///
+/// ig.add_peripheral(clock, monitor);
+/// for _ in 0..timing.frame_cycles() * 2 {
+///     ig.pulse_lever_stable(clock);
+/// }
+/// let frame = frames.try_iter().last().unwrap();
+/// assert_eq!(frame.to_string().lines().nth(1).unwrap(), "........#......#");
+/// ```
+/// # Panics
+///
+/// Will panic if `address` doesn't have [TextDisplayTiming::address_bits] bits,
+/// if `font` has more glyphs than `input` can encode or if the display has no columns or rows.
+// rust-analyzer makes this a non issue.
+#[allow(clippy::too_many_arguments)]
+pub fn text_display<S: Into<String>>(
+    g: &mut GateGraphBuilder,
+    clock: GateIndex,
+    write: GateIndex,
+    reset: GateIndex,
+    address: &[GateIndex],
+    input: &[GateIndex],
+    font: &[u8],
+    timing: TextDisplayTiming,
+    name: S,
+) -> TextDisplayOutput {
+    assert!(
+        timing.columns > 0 && timing.rows > 0,
+        "A text display needs at least 1 column and 1 row"
+    );
+    assert_eq!(
+        address.len(),
+        timing.address_bits(),
+        "`address` must have {} bits to address every character",
+        timing.address_bits()
+    );
+    let name = mkname(name.into());
+    let column_bits = timing.column_bits();
+    let row_bits = address.len() - column_bits;
+
+    let (h, h_flags) = timing_counter(
+        g,
+        clock,
+        ON,
+        reset,
+        GLYPH_BITS + column_bits,
+        timing.h_total(),
+        timing.width(),
+        timing.width() + timing.h_front_porch,
+        timing.h_sync,
+        format!("{}:h", name),
+    );
+    // The vertical counter advances at the end of every line.
+    let (v, v_flags) = timing_counter(
+        g,
+        clock,
+        flag(&h_flags, TIMING_LAST),
+        reset,
+        GLYPH_BITS + row_bits,
+        timing.v_total(),
+        timing.height(),
+        timing.height() + timing.v_front_porch,
+        timing.v_sync,
+        format!("{}:v", name),
+    );
+
+    // Character under the beam, the column and row come from the counters without the pixel inside the glyph.
+    let beam_address: Vec<_> = h[GLYPH_BITS..GLYPH_BITS + column_bits]
+        .iter()
+        .chain(&v[GLYPH_BITS..GLYPH_BITS + row_bits])
+        .copied()
+        .collect();
+    let ram_address = bus_multiplexer(g, &[write], &[&beam_address, address], name.clone());
+    let character = ram(
+        g,
+        ON,
+        write,
+        clock,
+        reset,
+        &ram_address,
+        input,
+        name.clone(),
+    );
+
+    // Row of the glyph under the beam.
+    let font_address: Vec<_> = v[..GLYPH_BITS].iter().chain(&character).copied().collect();
+    let glyph_row = rom(g, ON, &font_address, font, name.clone());
+    let glyph_pixels: Vec<_> = glyph_row.into_iter().rev().collect();
+    let glyph_pixel = multiplexer(g, &h[..GLYPH_BITS], &glyph_pixels, name.clone());
+
+    let visible = g.and2(
+        flag(&h_flags, TIMING_VISIBLE),
+        flag(&v_flags, TIMING_VISIBLE),
+        name.clone(),
+    );
+    TextDisplayOutput {
+        pixel: g.and2(visible, glyph_pixel, name),
+        hsync: flag(&h_flags, TIMING_SYNC),
+        vsync: flag(&v_flags, TIMING_SYNC),
+        visible,
+    }
+}
+
+/// Frame rendered by a [DisplayMonitor].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DisplayFrame {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+impl DisplayFrame {
+    /// Returns the width of the frame in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the height of the frame in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns true if the pixel at column `x` and row `y` is lit.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the pixel is outside of the frame.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        assert!(
+            x < self.width && y < self.height,
+            "Pixel ({}, {}) is outside of the {}x{} frame",
+            x,
+            y,
+            self.width,
+            self.height
+        );
+        self.pixels[y * self.width + x]
+    }
+
+    /// Writes the frame as a [PBM](https://en.wikipedia.org/wiki/Netpbm) image, lit pixels are black.
+    pub fn write_pbm<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "P1\n{} {}", self.width, self.height)?;
+        for line in self.pixels.chunks(self.width.max(1)) {
+            let line: Vec<_> = line.iter().map(|p| if *p { "1" } else { "0" }).collect();
+            writeln!(writer, "{}", line.join(" "))?;
+        }
+        Ok(())
+    }
+}
+/// Renders the frame as text, a line per row with `#` for lit pixels and `.` for the rest.
+impl Display for DisplayFrame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for line in self.pixels.chunks(self.width.max(1)) {
+            let line: String = line.iter().map(|p| if *p { '#' } else { '.' }).collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Host side model of a monitor connected to a [text_display], which rebuilds the frames from the video signals
+/// and sends every complete frame to a [Receiver].
+///
+/// Like a real monitor it doesn't know the timing of the display, it starts a new line on every horizontal sync pulse
+/// and a new frame on every vertical sync pulse, and only keeps the pixels during which `visible` is active.
+/// Frames are only complete after a vertical sync pulse, so the first frame is dropped if the monitor started
+/// in the middle of it.
+///
+/// [DisplayMonitor::update] should be called once after every clock cycle of the display,
+/// or the monitor can be added to the graph as a [HostPeripheral] with [InitializedGateGraph::add_peripheral].
+pub struct DisplayMonitor {
+    pixel: OutputHandle,
+    hsync: OutputHandle,
+    vsync: OutputHandle,
+    visible: OutputHandle,
+    sender: Sender<DisplayFrame>,
+    line: Vec<bool>,
+    lines: Vec<Vec<bool>>,
+    last_hsync: bool,
+    last_vsync: bool,
+    synced: bool,
+}
+impl DisplayMonitor {
+    /// Returns a new [DisplayMonitor] connected to the signals of `display`, along with the [Receiver] of the frames.
+    pub fn new<S: Into<String>>(
+        g: &mut GateGraphBuilder,
+        display: &TextDisplayOutput,
+        name: S,
+    ) -> (Self, Receiver<DisplayFrame>) {
+        let name = name.into();
+        let (sender, receiver) = channel();
+        let monitor = Self {
+            pixel: g.output1(display.pixel, format!("{}:pixel", name)),
+            hsync: g.output1(display.hsync, format!("{}:hsync", name)),
+            vsync: g.output1(display.vsync, format!("{}:vsync", name)),
+            visible: g.output1(display.visible, format!("{}:visible", name)),
+            sender,
+            line: Vec::new(),
+            lines: Vec::new(),
+            last_hsync: false,
+            last_vsync: false,
+            synced: false,
+        };
+        (monitor, receiver)
+    }
+
+    /// Samples the video signals for the current clock cycle.
+    ///
+    /// Frames that complete after the [Receiver] has been dropped are discarded.
+    pub fn update(&mut self, ig: &InitializedGateGraph) {
+        self.sample(
+            self.pixel.b0(ig),
+            self.hsync.b0(ig),
+            self.vsync.b0(ig),
+            self.visible.b0(ig),
+        );
+    }
+
+    fn sample(&mut self, pixel: bool, hsync: bool, vsync: bool, visible: bool) {
+        if visible {
+            self.line.push(pixel);
+        }
+        if hsync && !self.last_hsync && !self.line.is_empty() {
+            self.lines.push(std::mem::take(&mut self.line));
+        }
+        if vsync && !self.last_vsync {
+            let lines = std::mem::take(&mut self.lines);
+            if self.synced && !lines.is_empty() {
+                let frame = DisplayFrame {
+                    width: lines[0].len(),
+                    height: lines.len(),
+                    pixels: lines.concat(),
+                };
+                let _ = self.sender.send(frame);
+            }
+            self.synced = true;
+        }
+        self.last_hsync = hsync;
+        self.last_vsync = vsync;
+    }
+}
+impl HostPeripheral for DisplayMonitor {
+    fn tick(&mut self, bus: &mut BusView<'_>) {
+        self.sample(
+            bus.b0(self.pixel),
+            bus.b0(self.hsync),
+            bus.b0(self.vsync),
+            bus.b0(self.visible),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::WordInput;
+    use super::*;
+
+    #[test]
+    fn test_bits_for() {
+        assert_eq!(bits_for(1), 1);
+        assert_eq!(bits_for(2), 1);
+        assert_eq!(bits_for(3), 2);
+        assert_eq!(bits_for(8), 3);
+        assert_eq!(bits_for(9), 4);
+    }
+
+    #[test]
+    fn test_text_display_frames() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let clock = g.lever("clock");
+        let write = g.lever("write");
+        let reset = g.lever("reset");
+        let timing = TextDisplayTiming {
+            columns: 3,
+            rows: 2,
+            h_front_porch: 2,
+            h_sync: 3,
+            h_back_porch: 1,
+            v_front_porch: 1,
+            v_sync: 2,
+            v_back_porch: 3,
+        };
+        let address = WordInput::new(g, timing.address_bits(), "address");
+        let input = WordInput::new(g, 2, "input");
+        // Blank, a vertical bar on the left, a horizontal bar on top and a dot in the middle.
+        let mut font = vec![0u8; 8];
+        font.extend(&[0x80; 8]);
+        font.extend(&[0xff, 0, 0, 0, 0, 0, 0, 0]);
+        font.extend(&[0, 0, 0, 0x10, 0, 0, 0, 0]);
+        let display = text_display(
+            g,
+            clock.bit(),
+            write.bit(),
+            reset.bit(),
+            &address.bits(),
+            &input.bits(),
+            &font,
+            timing,
+            "display",
+        );
+        let (mut monitor, frames) = DisplayMonitor::new(g, &display, "monitor");
+
+        let ig = &mut graph.init();
+        ig.pulse_lever_stable(reset);
+        // Row 0: 1 2 0, row 1: 0 3 1.
+        let column_bits = timing.column_bits();
+        for (row, column, character) in [(0, 0, 1u8), (0, 1, 2), (1, 1, 3), (1, 2, 1)] {
+            address.set_to(ig, (row << column_bits | column) as u8);
+            input.set_to(ig, character);
+            ig.set_lever_stable(write);
+            ig.pulse_lever_stable(clock);
+            ig.reset_lever_stable(write);
+        }
+
+        for _ in 0..timing.frame_cycles() * 3 {
+            ig.pulse_lever_stable(clock);
+            monitor.update(ig);
+        }
+        let frames: Vec<_> = frames.try_iter().collect();
+        // The first vertical sync only syncs the monitor.
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0], frames[1]);
+
+        let frame = &frames[0];
+        assert_eq!(frame.width(), 24);
+        assert_eq!(frame.height(), 16);
+        for y in 0..16 {
+            assert_eq!(frame.pixel(0, y), y < 8, "left bar at row {}", y);
+            assert_eq!(frame.pixel(16, y), y >= 8, "second left bar at row {}", y);
+        }
+        for x in 8..16 {
+            assert!(frame.pixel(x, 0), "top bar at column {}", x);
+        }
+        assert!(frame.pixel(11, 11));
+        let lit = frame.pixels.iter().filter(|p| **p).count();
+        assert_eq!(lit, 8 + 8 + 8 + 1);
+
+        let mut pbm = Vec::new();
+        frame.write_pbm(&mut pbm).unwrap();
+        assert!(pbm.starts_with(b"P1\n24 16\n1 0 0"));
+    }
+}
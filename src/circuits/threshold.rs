@@ -0,0 +1,142 @@
+use crate::graph::*;
+
+fn mkname(name: String) -> String {
+    format!("THRESH:{}", name)
+}
+
+/// Returns a gate that is active when at least `k` of the `inputs` are active.
+///
+/// It's built incrementally like a unary counter, the gate for "at least j of the first i inputs" is
+/// "at least j of the first i - 1" or "input i and at least j - 1 of the first i - 1",
+/// which takes about 2 * `k` * `inputs.len()` gates instead of one and gate for every combination of `k` inputs.
+///
+/// If `k` == 0 the result is [ON], if `k` > `inputs.len()` the result is [OFF].
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,threshold,WordInput};
+/// # let mut g = GateGraphBuilder::new();
+/// let input = WordInput::new(&mut g, 5, "input");
+/// let at_least_3 = threshold(&mut g, &input.bits(), 3, "at_least_3");
+///
+/// let output = g.output1(at_least_3, "result");
+/// let ig = &mut g.init();
+///
+/// input.set_to(ig, 0b10010u8);
+/// ig.run_until_stable(10).unwrap();
+/// assert_eq!(output.b0(ig), false);
+///
+/// input.set_to(ig, 0b10110u8);
+/// ig.run_until_stable(10).unwrap();
+/// assert_eq!(output.b0(ig), true);
+/// ```
+pub fn threshold<S: Into<String>>(
+    g: &mut GateGraphBuilder,
+    inputs: &[GateIndex],
+    k: usize,
+    name: S,
+) -> GateIndex {
+    if k > inputs.len() {
+        return OFF;
+    }
+    let name = mkname(name.into());
+
+    // at_least[j] is active when at least j of the inputs processed so far are active.
+    let mut at_least = vec![OFF; k + 1];
+    at_least[0] = ON;
+    for (i, input) in inputs.iter().enumerate() {
+        // Going down so that at_least[j - 1] still refers to the previous inputs.
+        // Counts that the remaining inputs can't reach anymore are skipped.
+        let lowest = (k + i + 1).saturating_sub(inputs.len()).max(1);
+        for j in (lowest..=k.min(i + 1)).rev() {
+            let and = g.and2(*input, at_least[j - 1], name.clone());
+            at_least[j] = g.or2(at_least[j], and, name.clone());
+        }
+    }
+    at_least[k]
+}
+
+/// Returns a gate that is active when more than half of the `inputs` are active,
+/// see [threshold].
+///
+/// With an even number of inputs, a tie is inactive.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,majority};
+/// # let mut g = GateGraphBuilder::new();
+/// let a = g.lever("a");
+/// let b = g.lever("b");
+/// let c = g.lever("c");
+/// let vote = majority(&mut g, &[a.bit(), b.bit(), c.bit()], "vote");
+///
+/// let output = g.output1(vote, "result");
+/// let ig = &mut g.init();
+///
+/// ig.set_lever_stable(b);
+/// assert_eq!(output.b0(ig), false);
+///
+/// ig.set_lever_stable(c);
+/// assert_eq!(output.b0(ig), true);
+/// ```
+pub fn majority<S: Into<String>>(
+    g: &mut GateGraphBuilder,
+    inputs: &[GateIndex],
+    name: S,
+) -> GateIndex {
+    threshold(g, inputs, inputs.len() / 2 + 1, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::WordInput;
+    use super::*;
+
+    #[test]
+    fn test_threshold_exhaustive() {
+        let width = 6;
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let input = WordInput::new(g, width, "input");
+        let bits: Vec<_> = (0..=width + 1)
+            .map(|k| threshold(g, &input.bits(), k, "threshold"))
+            .collect();
+        let output = g.output(&bits, "thresholds");
+
+        let table = graph.init().truth_table(output, input.levers());
+        for (input, output) in table {
+            for k in 0..=width + 1 {
+                let expected = input.count_ones() as usize >= k;
+                assert_eq!(output & (1 << k) != 0, expected, "{:b} >= {}", input, k);
+            }
+        }
+    }
+
+    #[test]
+    fn test_majority() {
+        for width in 1..=6 {
+            let mut graph = GateGraphBuilder::new();
+            let g = &mut graph;
+
+            let input = WordInput::new(g, width, "input");
+            let vote = majority(g, &input.bits(), "majority");
+            let output = g.output1(vote, "majority");
+
+            let table = graph.init().truth_table(output, input.levers());
+            for (input, output) in table {
+                let expected = input.count_ones() as usize * 2 > width;
+                assert_eq!(output == 1, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_threshold_size() {
+        let mut g = GateGraphBuilder::new();
+        let input = WordInput::new(&mut g, 16, "input");
+        let before = g.len();
+        threshold(&mut g, &input.bits(), 8, "threshold");
+        assert!(g.len() - before <= 2 * 8 * 16);
+    }
+}
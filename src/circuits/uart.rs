@@ -0,0 +1,458 @@
+use super::edge_register;
+use crate::graph::*;
+use std::collections::VecDeque;
+
+fn mkname(name: String) -> String {
+    format!("UART:{}", name)
+}
+
+/// Number of data bits in the frames sent and received by an [UartHost].
+pub const UART_HOST_BITS: usize = 8;
+
+/// Outputs of an [uart_tx].
+#[derive(Debug, Clone, Copy)]
+pub struct UartTxOutput {
+    /// Serial line, active while idle.
+    pub tx: GateIndex,
+    /// Active while `send` is ignored because a frame is being sent.
+    pub busy: GateIndex,
+}
+
+/// Outputs of an [uart_rx].
+#[derive(Debug, Clone)]
+pub struct UartRxOutput {
+    /// Last word received.
+    pub data: Vec<GateIndex>,
+    /// Active during the clock cycle after a word has been received.
+    pub ready: GateIndex,
+    /// Active along with `ready` if the stop bit of the word was missing.
+    pub frame_error: GateIndex,
+}
+
+/// Returns the output of the transmitter half of a [UART](https://en.wikipedia.org/wiki/Universal_asynchronous_receiver-transmitter),
+/// which sends `data` one bit per cycle of `clock`.
+///
+/// Frames have a start bit (inactive), the bits of `data` starting from the least significant one and
+/// a stop bit (active), the next frame can start right after the stop bit.
+/// Every register changes on the raising edge of `clock`, [uart_rx] samples the line right before it changes.
+///
+/// # Inputs
+///
+/// `clock` Bit clock, `data` is sampled on the raising edge if `send` is active and the transmitter isn't busy.
+///
+/// `reset` Stops sending and returns the line to idle. This is an async reset.
+///
+/// `send` Starts sending `data`.
+///
+/// `data` Word to send.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,uart_tx,uart_rx,WordInput};
+/// let mut g = GateGraphBuilder::new();
+/// let clock = g.lever("clock");
+/// let reset = g.lever("reset");
+/// let send = g.lever("send");
+/// let data = WordInput::new(&mut g, 8, "data");
+/// let tx = uart_tx(&mut g, clock.bit(), reset.bit(), send.bit(), &data.bits(), "tx");
+/// let rx = uart_rx(&mut g, clock.bit(), reset.bit(), tx.tx, 8, "rx");
+/// let received = g.output(&rx.data, "received");
+/// let ready = g.output1(rx.ready, "ready");
+///
+/// let ig = &mut g.init();
+/// ig.pulse_lever_stable(reset);
+/// data.set_to(ig, b'!');
+/// ig.set_lever_stable(send);
+/// ig.pulse_lever_stable(clock);
+/// ig.reset_lever_stable(send);
+///
+/// // Start, 8 data bits and stop.
+/// let mut cycles = 0;
+/// while !ready.b0(ig) {
+///     ig.pulse_lever_stable(clock);
+///     cycles += 1;
+/// }
+/// assert_eq!(cycles, 10);
+/// assert_eq!(received.u8(ig), b'!');
+/// ```
+///
+/// # Panics
+///
+/// Will panic if `data` is empty.
+pub fn uart_tx<S: Into<String>>(
+    g: &mut GateGraphBuilder,
+    clock: GateIndex,
+    reset: GateIndex,
+    send: GateIndex,
+    data: &[GateIndex],
+    name: S,
+) -> UartTxOutput {
+    assert!(!data.is_empty(), "`data` can't be empty");
+    let name = mkname(name.into());
+    let len = data.len() + 2;
+
+    let frame: Vec<_> = std::iter::once(OFF)
+        .chain(data.iter().copied())
+        .chain(std::iter::once(ON))
+        .collect();
+
+    // `pending` has a bit active for every bit of the frame that hasn't been sent yet.
+    let shift_next: Vec<_> = (0..len).map(|_| g.or(name.clone())).collect();
+    let pending_next: Vec<_> = (0..len).map(|_| g.or(name.clone())).collect();
+    let shift = edge_register(g, clock, ON, reset, &shift_next, name.clone());
+    let pending = edge_register(g, clock, ON, reset, &pending_next, name.clone());
+    // The next frame can start while the stop bit is on the line.
+    let busy = pending[1];
+
+    let idle = g.not1(pending[0], name.clone());
+    let nbusy = g.not1(busy, name.clone());
+    let load = g.and2(send, nbusy, name.clone());
+    let nload = g.not1(load, name.clone());
+    for i in 0..len {
+        let (shifted, pending_shifted) = if i + 1 < len {
+            (shift[i + 1], pending[i + 1])
+        } else {
+            (ON, OFF)
+        };
+        let loaded = g.and2(load, frame[i], name.clone());
+        let kept = g.and2(nload, shifted, name.clone());
+        g.dpush(shift_next[i], loaded);
+        g.dpush(shift_next[i], kept);
+        g.dpush(pending_next[i], load);
+        g.dpush(pending_next[i], pending_shifted);
+    }
+
+    let tx = g.or2(idle, shift[0], name);
+    UartTxOutput { tx, busy }
+}
+
+/// Returns the output of the receiver half of a [UART](https://en.wikipedia.org/wiki/Universal_asynchronous_receiver-transmitter),
+/// which receives `width` bit words one bit per cycle of `clock`, see [uart_tx] for the format of the frames.
+///
+/// # Inputs
+///
+/// `clock` Bit clock, `rx` is sampled on the raising edge.
+///
+/// `reset` Drops the frame being received and clears the outputs. This is an async reset.
+///
+/// `rx` Serial line, active while idle.
+///
+/// `width` Number of data bits in a frame.
+///
+/// # Panics
+///
+/// Will panic if `width` is 0.
+pub fn uart_rx<S: Into<String>>(
+    g: &mut GateGraphBuilder,
+    clock: GateIndex,
+    reset: GateIndex,
+    rx: GateIndex,
+    width: usize,
+    name: S,
+) -> UartRxOutput {
+    assert_ne!(width, 0, "`width` can't be 0");
+    let name = mkname(name.into());
+    // Data bits and the stop bit.
+    let len = width + 1;
+
+    // `pending` has a bit active for every bit of the frame that hasn't been received yet.
+    let pending_next: Vec<_> = (0..len).map(|_| g.or(name.clone())).collect();
+    let pending = edge_register(g, clock, ON, reset, &pending_next, name.clone());
+    let receiving = pending[0];
+
+    let idle = g.not1(receiving, name.clone());
+    let nrx = g.not1(rx, name.clone());
+    let start = g.and2(idle, nrx, name.clone());
+    for i in 0..len {
+        let pending_shifted = if i + 1 < len { pending[i + 1] } else { OFF };
+        g.dpush(pending_next[i], start);
+        g.dpush(pending_next[i], pending_shifted);
+    }
+
+    // Bits come in through the most significant end.
+    let shift_next: Vec<_> = (0..len).map(|_| g.or(name.clone())).collect();
+    let shift = edge_register(g, clock, ON, reset, &shift_next, name.clone());
+    for i in 0..len {
+        let shifted = if i + 1 < len { shift[i + 1] } else { rx };
+        g.dpush(shift_next[i], shifted);
+    }
+
+    // Sampling the stop bit.
+    let last_pending = g.not1(pending[1], name.clone());
+    let last = g.and2(receiving, last_pending, name.clone());
+    let data = edge_register(g, clock, last, reset, &shift_next[..width], name.clone());
+    let missing_stop = g.and2(last, nrx, name.clone());
+    let flags = edge_register(g, clock, ON, reset, &[last, missing_stop], name);
+
+    UartRxOutput {
+        data,
+        ready: flags[0],
+        frame_error: flags[1],
+    }
+}
+
+/// Host side of a serial line connected to an [uart_tx] and an [uart_rx] with [UART_HOST_BITS] data bits,
+/// which lets Rust code send and receive bytes clocked by the simulation.
+///
+/// [UartHost::update] should be called once after every cycle of the bit clock of the circuit.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,uart_tx,uart_rx,UartHost,Wire};
+/// let mut g = GateGraphBuilder::new();
+/// let clock = g.lever("clock");
+/// let reset = g.lever("reset");
+///
+/// // Echoes every byte back.
+/// let mut send = Wire::new(&mut g, "send");
+/// let mut data = Vec::new();
+/// for _ in 0..8 {
+///     data.push(Wire::new(&mut g, "data"));
+/// }
+/// let data_bits: Vec<_> = data.iter().map(|w| w.bit()).collect();
+/// let tx = uart_tx(&mut g, clock.bit(), reset.bit(), send.bit(), &data_bits, "tx");
+/// let host = &mut UartHost::new(&mut g, tx.tx, "host");
+/// let rx = uart_rx(&mut g, clock.bit(), reset.bit(), host.rx(), 8, "rx");
+/// send.connect(&mut g, rx.ready);
+/// for (wire, bit) in data.iter_mut().zip(rx.data) {
+///     wire.connect(&mut g, bit);
+/// }
+///
+/// let ig = &mut g.init();
+/// ig.pulse_lever_stable(reset);
+/// host.send_byte(b'h');
+/// host.send_byte(b'i');
+/// for _ in 0..40 {
+///     ig.pulse_lever_stable(clock);
+///     host.update(ig);
+/// }
+/// assert_eq!(host.recv_byte(), Some(b'h'));
+/// assert_eq!(host.recv_byte(), Some(b'i'));
+/// assert_eq!(host.recv_byte(), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct UartHost {
+    /// Pulls the line to the circuit low, so it idles while the lever is inactive.
+    break_lever: LeverHandle,
+    /// State of the line to the circuit.
+    line: bool,
+    rx: GateIndex,
+    tx: OutputHandle,
+    to_send: VecDeque<u8>,
+    /// Bits of the frame being sent that haven't been sent yet, starting from the least significant one.
+    sending: Option<(u16, usize)>,
+    /// Bits of the byte being received and how many of them have been received.
+    receiving: Option<(u8, usize)>,
+    received: VecDeque<u8>,
+    frame_errors: usize,
+}
+impl UartHost {
+    /// Returns a new [UartHost] which receives bytes from the serial line `tx`,
+    /// connect [UartHost::rx] to the circuit to send bytes to it.
+    pub fn new<S: Into<String>>(g: &mut GateGraphBuilder, tx: GateIndex, name: S) -> Self {
+        let name = mkname(name.into());
+        let break_lever = g.lever(name.clone());
+        let rx = g.not1(break_lever.bit(), name.clone());
+        let tx = g.output1(tx, name);
+        Self {
+            break_lever,
+            line: true,
+            rx,
+            tx,
+            to_send: VecDeque::new(),
+            sending: None,
+            receiving: None,
+            received: VecDeque::new(),
+            frame_errors: 0,
+        }
+    }
+
+    /// Returns the serial line driven by the host, active while idle.
+    pub fn rx(&self) -> GateIndex {
+        self.rx
+    }
+
+    /// Queues `byte` to be sent to the circuit.
+    pub fn send_byte(&mut self, byte: u8) {
+        self.to_send.push_back(byte);
+    }
+
+    /// Returns the oldest byte received from the circuit, if any.
+    pub fn recv_byte(&mut self) -> Option<u8> {
+        self.received.pop_front()
+    }
+
+    /// Returns true if every queued byte has been sent.
+    pub fn is_idle(&self) -> bool {
+        self.sending.is_none() && self.to_send.is_empty()
+    }
+
+    /// Returns the number of received frames that were dropped because their stop bit was missing.
+    pub fn frame_errors(&self) -> usize {
+        self.frame_errors
+    }
+
+    /// Samples the line from the circuit and puts the next bit on the line to the circuit.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the circuit does not stabilize.
+    pub fn update(&mut self, ig: &mut InitializedGateGraph) {
+        let bit = self.tx.b0(ig);
+        self.receiving = match self.receiving {
+            None if !bit => Some((0, 0)),
+            None => None,
+            Some((byte, count)) if count < UART_HOST_BITS => {
+                Some((byte | (bit as u8) << count, count + 1))
+            }
+            Some((byte, _)) => {
+                if bit {
+                    self.received.push_back(byte);
+                } else {
+                    self.frame_errors += 1;
+                }
+                None
+            }
+        };
+
+        if self.sending.is_none() {
+            self.sending = self.to_send.pop_front().map(|byte| {
+                (
+                    1 << (UART_HOST_BITS + 1) | (byte as u16) << 1,
+                    UART_HOST_BITS + 2,
+                )
+            });
+        }
+        let line = match self.sending {
+            Some((frame, remaining)) => {
+                self.sending = if remaining > 1 {
+                    Some((frame >> 1, remaining - 1))
+                } else {
+                    None
+                };
+                frame & 1 == 1
+            }
+            None => true,
+        };
+        if self.line != line {
+            self.line = line;
+            ig.update_lever(self.break_lever, !line);
+            ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::*;
+
+    #[test]
+    fn test_uart_widths() {
+        for width in 1..=4 {
+            let mut graph = GateGraphBuilder::new();
+            let g = &mut graph;
+            let clock = g.lever("clock");
+            let reset = g.lever("reset");
+            let send = g.lever("send");
+            let data = WordInput::new(g, width, "data");
+            let tx = uart_tx(g, clock.bit(), reset.bit(), send.bit(), &data.bits(), "tx");
+            let rx = uart_rx(g, clock.bit(), reset.bit(), tx.tx, width, "rx");
+            let busy = g.output1(tx.busy, "busy");
+            let line = g.output1(tx.tx, "line");
+            let received = g.output(&rx.data, "received");
+            let flags = g.output(&[rx.ready, rx.frame_error], "flags");
+            let ig = &mut graph.init();
+            ig.pulse_lever_stable(reset);
+
+            for value in 0..1u8 << width {
+                data.set_to(ig, value);
+                ig.set_lever_stable(send);
+                let mut line_bits = Vec::new();
+                let mut received_at = None;
+                for cycle in 0..width + 4 {
+                    ig.pulse_lever_stable(clock);
+                    if cycle == 0 {
+                        ig.reset_lever_stable(send);
+                    }
+                    line_bits.push(line.b0(ig));
+                    if flags.u8(ig) & 1 == 1 {
+                        assert!(received_at.is_none());
+                        received_at = Some(cycle);
+                    }
+                }
+                let expected: Vec<bool> = std::iter::once(false)
+                    .chain((0..width).map(|i| value >> i & 1 == 1))
+                    .chain(std::iter::repeat(true).take(3))
+                    .collect();
+                assert_eq!(line_bits, expected, "width: {} value: {}", width, value);
+                assert_eq!(received_at, Some(width + 2));
+                assert_eq!(received.u8(ig), value);
+                assert!(!busy.b0(ig));
+            }
+        }
+    }
+
+    #[test]
+    fn test_uart_host() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let send = g.lever("send");
+        let data = WordInput::new(g, UART_HOST_BITS, "data");
+        let tx = uart_tx(g, clock.bit(), reset.bit(), send.bit(), &data.bits(), "tx");
+        let mut host = UartHost::new(g, tx.tx, "host");
+        let rx = uart_rx(g, clock.bit(), reset.bit(), host.rx(), UART_HOST_BITS, "rx");
+        let received = g.output(&rx.data, "received");
+        let flags = g.output(&[rx.ready, rx.frame_error], "flags");
+        let ig = &mut graph.init();
+        ig.pulse_lever_stable(reset);
+
+        // Host to circuit.
+        let message = b"hello";
+        for c in message {
+            host.send_byte(*c);
+        }
+        let mut from_host = Vec::new();
+        while !host.is_idle() || from_host.len() < message.len() {
+            ig.pulse_lever_stable(clock);
+            host.update(ig);
+            match flags.u8(ig) {
+                0 => {}
+                1 => from_host.push(received.u8(ig)),
+                _ => panic!("Frame error"),
+            }
+        }
+        assert_eq!(from_host, message);
+
+        // Circuit to host, back to back.
+        data.set_to(ig, b'x');
+        ig.set_lever_stable(send);
+        for _ in 0..25 {
+            ig.pulse_lever_stable(clock);
+            host.update(ig);
+        }
+        ig.reset_lever_stable(send);
+        for _ in 0..15 {
+            ig.pulse_lever_stable(clock);
+            host.update(ig);
+        }
+        assert_eq!(host.recv_byte(), Some(b'x'));
+        assert_eq!(host.recv_byte(), Some(b'x'));
+        assert_eq!(host.recv_byte(), Some(b'x'));
+        assert_eq!(host.recv_byte(), None);
+        assert_eq!(host.frame_errors(), 0);
+
+        // A break in the line shows up as frame errors.
+        ig.set_lever_stable(host.break_lever);
+        let mut frame_errors = 0;
+        for _ in 0..30 {
+            ig.pulse_lever_stable(clock);
+            if flags.u8(ig) == 3 {
+                frame_errors += 1;
+            }
+        }
+        assert!(frame_errors > 0);
+    }
+}
@@ -5,7 +5,7 @@ use crate::graph::*;
 macro_rules! wire {
     ($g:expr,$name:ident) => {
         #[allow(unused_mut)]
-        let mut $name: Wire = Wire::new($g, stringify!($name));
+        let mut $name: $crate::Wire = $crate::Wire::new($g, stringify!($name));
     };
 }
 
@@ -57,19 +57,69 @@ macro_rules! wire {
 pub struct Wire {
     bit: GateIndex,
     lever: Option<LeverHandle>,
+    /// Gate that is on while any driver connected after the last one is enabled, see [Wire::drive].
+    overridden: Option<GateIndex>,
+    /// Id of the wire in the checked wires of the graph, see [Wire::checked].
+    #[cfg(feature = "debug_gates")]
+    checked: Option<usize>,
     pub name: String,
 }
 impl Wire {
-    /// Returns a new [Wire] with name `name`.
+    /// Returns a new [Wire] with name `name`, its connections are checked by [GateGraphBuilder::validate].
     pub fn new<S: Into<String>>(g: &mut GateGraphBuilder, name: S) -> Self {
         let name = name.into();
+        let port_name = format!("WIRE:{}", name);
+        let bit = g.or(port_name.clone());
+        g.add_port(port_name, vec![bit]);
         Self {
-            bit: g.or(format!("WIRE:{}", name)),
+            bit,
             lever: None,
+            overridden: None,
+            #[cfg(feature = "debug_gates")]
+            checked: None,
             name,
         }
     }
 
+    /// Returns a new [Wire] with name `name` that records its drivers, so the simulation can report every tick in
+    /// which more than one of them is active, see [InitializedGateGraph::driver_conflicts].
+    ///
+    /// Drivers [connected](Wire::connect) directly are active while they are on and get ORed together like in any
+    /// other wire, the ones connected with [drive](Wire::drive) are active while they are enabled and the last one
+    /// wins. Either way the conflict gets reported instead of going unnoticed.
+    ///
+    /// Drivers are only recorded with the "debug_gates" feature, otherwise this is the same as [Wire::new].
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::{GateGraphBuilder,Wire,ON,OFF};
+    /// let mut g = GateGraphBuilder::new();
+    /// let alu_out = g.lever("alu_out");
+    /// let ram_out = g.lever("ram_out");
+    /// let mut bus = Wire::checked(&mut g, "bus");
+    /// bus.drive(&mut g, alu_out.bit(), ON, "alu");
+    /// bus.drive(&mut g, ram_out.bit(), OFF, "ram");
+    /// let output = g.output1(bus.bit(), "bus");
+    ///
+    /// let ig = &mut g.init();
+    /// ig.set_lever_stable(alu_out);
+    /// assert_eq!(output.b0(ig), true);
+    /// ig.set_lever_stable(ram_out);
+    /// // The last driver connected wins.
+    /// assert_eq!(output.b0(ig), false);
+    /// # #[cfg(feature = "debug_gates")]
+    /// assert_eq!(ig.driver_conflicts()[0].drivers, ["alu", "ram"]);
+    /// ```
+    pub fn checked<S: Into<String>>(g: &mut GateGraphBuilder, name: S) -> Self {
+        #[allow(unused_mut)]
+        let mut wire = Self::new(g, name);
+        #[cfg(feature = "debug_gates")]
+        {
+            wire.checked = Some(g.add_checked_wire(wire.name.clone()));
+        }
+        wire
+    }
+
     /// Makes a new lever for the wire, stores it for easy access later and returns
     /// its [LeverHandle].
     pub fn make_lever(&mut self, g: &mut GateGraphBuilder) -> LeverHandle {
@@ -92,6 +142,40 @@ impl Wire {
     /// Connects a new [GateIndex] to the wire.
     pub fn connect(&self, g: &mut GateGraphBuilder, other: GateIndex) {
         g.dpush(self.bit, other);
+        #[cfg(feature = "debug_gates")]
+        if let Some(checked) = self.checked {
+            g.add_wire_driver(checked, other, None);
+        }
+    }
+
+    /// Connects a driver named `name` that puts `value` on the wire while `enable` is on.
+    ///
+    /// If more than one driver is enabled, the one connected last wins, the ones connected before it
+    /// are masked off. Drivers [connected](Wire::connect) directly are still ORed with the result.
+    pub fn drive<S: Into<String>>(
+        &mut self,
+        g: &mut GateGraphBuilder,
+        enable: GateIndex,
+        value: GateIndex,
+        name: S,
+    ) {
+        let name = name.into();
+        let gate_name = format!("WIRE:{}:{}", self.name, name);
+        // Filled by the drivers connected after this one.
+        let overridden = g.or(gate_name.clone());
+        if let Some(previous) = self.overridden {
+            g.dpush(previous, enable);
+            g.dpush(previous, overridden);
+        }
+        self.overridden = Some(overridden);
+
+        let not_overridden = g.not1(overridden, gate_name.clone());
+        let driven = g.andx([enable, value, not_overridden].iter().copied(), gate_name);
+        g.dpush(self.bit, driven);
+        #[cfg(feature = "debug_gates")]
+        if let Some(checked) = self.checked {
+            g.add_wire_driver(checked, enable, Some(name));
+        }
     }
 
     /// Returns the [GateIndex] of the wire.
@@ -29,10 +29,19 @@ fn mkname(name: String) -> String {
 /// input.flip_bit(ig, 1);
 /// assert_eq!(output.u8(ig), 1);
 /// ```
+///
+/// Every method that updates the levers calls [tick](InitializedGateGraph::tick) once, the ones ending with `_stable`
+/// call [run_until_stable](InitializedGateGraph::run_until_stable) with [DEFAULT_STABLE_MAX] instead and
+/// panic if the circuit doesn't stabilize.
 pub struct WordInput {
     levers: Vec<LeverHandle>,
 }
-// TODO "_stable" versions.
+
+/// Runs the graph until it's stable, for the `_stable` methods.
+fn stabilize(g: &mut InitializedGateGraph) {
+    g.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+}
+
 impl WordInput {
     /// Returns a new [WordInput] of width `width` with name `name`.
     pub fn new<S: Into<String>>(g: &mut GateGraphBuilder, width: usize, name: S) -> Self {
@@ -63,7 +72,57 @@ impl WordInput {
 
     /// Sets the lever at index `bit` to false.
     pub fn reset_bit(&self, g: &mut InitializedGateGraph, bit: usize) -> Option<()> {
-        self.update_bit(g, bit, true)
+        self.update_bit(g, bit, false)
+    }
+
+    /// Stable version of [update_bit](WordInput::update_bit).
+    pub fn update_bit_stable(
+        &self,
+        g: &mut InitializedGateGraph,
+        bit: usize,
+        value: bool,
+    ) -> Option<()> {
+        self.update_bit(g, bit, value)?;
+        stabilize(g);
+        Some(())
+    }
+
+    /// Stable version of [flip_bit](WordInput::flip_bit).
+    pub fn flip_bit_stable(&self, g: &mut InitializedGateGraph, bit: usize) -> Option<()> {
+        self.flip_bit(g, bit)?;
+        stabilize(g);
+        Some(())
+    }
+
+    /// Stable version of [set_bit](WordInput::set_bit).
+    pub fn set_bit_stable(&self, g: &mut InitializedGateGraph, bit: usize) -> Option<()> {
+        self.update_bit_stable(g, bit, true)
+    }
+
+    /// Stable version of [reset_bit](WordInput::reset_bit).
+    pub fn reset_bit_stable(&self, g: &mut InitializedGateGraph, bit: usize) -> Option<()> {
+        self.update_bit_stable(g, bit, false)
+    }
+
+    /// Sets the levers to `values`, bit 0 first, all in the same tick.
+    /// If there are more values than levers, the excess values will be ignored,
+    /// if there are less, the rest of the levers keep their state.
+    pub fn update_bits<I: IntoIterator<Item = bool>>(
+        &self,
+        g: &mut InitializedGateGraph,
+        values: I,
+    ) {
+        g.update_levers(&self.levers, values.into_iter());
+    }
+
+    /// Stable version of [update_bits](WordInput::update_bits).
+    pub fn update_bits_stable<I: IntoIterator<Item = bool>>(
+        &self,
+        g: &mut InitializedGateGraph,
+        values: I,
+    ) {
+        self.update_bits(g, values);
+        stabilize(g);
     }
 
     /// Sets the levers to the native endian bits of `value`.
@@ -73,9 +132,96 @@ impl WordInput {
         g.update_levers(&self.levers, BitIter::new(value));
     }
 
+    /// Stable version of [set_to](WordInput::set_to).
+    pub fn set_to_stable<T: Copy + Sized + 'static>(&self, g: &mut InitializedGateGraph, value: T) {
+        self.set_to(g, value);
+        stabilize(g);
+    }
+
+    /// Sets the levers to the two's complement bits of `value`.
+    /// If the [WordInput] is narrower than 64 bits, it will ignore the excess bits,
+    /// if it's wider, it will sign extend the value.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::{GateGraphBuilder,WordInput,adder,OFF};
+    /// let mut g = GateGraphBuilder::new();
+    /// let a = WordInput::new(&mut g, 4, "a");
+    /// let b = WordInput::new(&mut g, 4, "b");
+    /// let sum = adder(&mut g, OFF, &a.bits(), &b.bits(), "adder");
+    /// let sum = g.output(&sum, "sum");
+    ///
+    /// let ig = &mut g.init();
+    /// a.set_to_signed(ig, -3);
+    /// b.set_to_signed_stable(ig, 1);
+    /// assert_eq!(sum.signed(ig), -2);
+    /// ```
+    pub fn set_to_signed(&self, g: &mut InitializedGateGraph, value: i64) {
+        let bits = (0..self.len()).map(|i| value >> i.min(63) & 1 == 1);
+        g.update_levers(&self.levers, bits);
+    }
+
+    /// Stable version of [set_to_signed](WordInput::set_to_signed).
+    pub fn set_to_signed_stable(&self, g: &mut InitializedGateGraph, value: i64) {
+        self.set_to_signed(g, value);
+        stabilize(g);
+    }
+
+    /// Returns the smallest and largest values a two's complement number as wide as the [WordInput] can hold.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the [WordInput] is empty or wider than 64 bits.
+    pub fn signed_range(&self) -> (i64, i64) {
+        assert!(
+            (1..=64).contains(&self.len()),
+            "Signed values need between 1 and 64 bits, width: {}",
+            self.len()
+        );
+        let limit = 1i128 << (self.len() - 1);
+        (-limit as i64, (limit - 1) as i64)
+    }
+
+    /// Sets the levers of every [WordInput] in `updates` to the bits of its value, all in the same tick,
+    /// so the circuit never sees some of them updated and others not.
+    /// Values are truncated or 0 extended to the width of their [WordInput].
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::{GateGraphBuilder,WordInput,adder,OFF};
+    /// let mut g = GateGraphBuilder::new();
+    /// let a = WordInput::new(&mut g, 8, "a");
+    /// let b = WordInput::new(&mut g, 8, "b");
+    /// let sum = adder(&mut g, OFF, &a.bits(), &b.bits(), "adder");
+    /// let sum = g.output(&sum, "sum");
+    ///
+    /// let ig = &mut g.init();
+    /// WordInput::set_many_stable(ig, &[(&a, 40), (&b, 2)]);
+    /// assert_eq!(sum.u8(ig), 42);
+    /// ```
+    pub fn set_many(g: &mut InitializedGateGraph, updates: &[(&WordInput, u128)]) {
+        let mut levers = Vec::new();
+        let mut values = Vec::new();
+        for (input, value) in updates {
+            levers.extend_from_slice(&input.levers);
+            values.extend(
+                BitIter::new(*value)
+                    .chain(std::iter::repeat(false))
+                    .take(input.len()),
+            );
+        }
+        g.update_levers(&levers, values.into_iter());
+    }
+
+    /// Stable version of [set_many](WordInput::set_many).
+    pub fn set_many_stable(g: &mut InitializedGateGraph, updates: &[(&WordInput, u128)]) {
+        Self::set_many(g, updates);
+        stabilize(g);
+    }
+
     /// Sets all the levers to true.
     pub fn set(&self, g: &mut InitializedGateGraph) {
-        g.update_levers(&self.levers, (0..self.levers.len()).map(|_| false));
+        g.update_levers(&self.levers, (0..self.levers.len()).map(|_| true));
     }
 
     /// Sets all the levers to false.
@@ -83,6 +229,23 @@ impl WordInput {
         g.update_levers(&self.levers, (0..self.levers.len()).map(|_| false));
     }
 
+    /// Stable version of [set](WordInput::set).
+    pub fn set_stable(&self, g: &mut InitializedGateGraph) {
+        self.set(g);
+        stabilize(g);
+    }
+
+    /// Stable version of [reset](WordInput::reset).
+    pub fn reset_stable(&self, g: &mut InitializedGateGraph) {
+        self.reset(g);
+        stabilize(g);
+    }
+
+    /// Returns the [LeverHandles](LeverHandle) of the [WordInput], bit 0 first.
+    pub fn levers(&self) -> &[LeverHandle] {
+        &self.levers
+    }
+
     /// Returns a [SmallVec]<[GateIndex]> to connect to other components.
     pub fn bits(&self) -> SmallVec<[GateIndex; 8]> {
         self.levers.iter().map(|lever| lever.bit()).collect()
@@ -98,3 +261,94 @@ impl WordInput {
         self.levers.len() == 0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuits::adder;
+
+    #[test]
+    fn test_word_input() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let input = WordInput::new(g, 4, "input");
+        let output = g.output(&input.bits(), "output");
+        let ig = &mut graph.init();
+
+        input.set_stable(ig);
+        assert_eq!(output.u8(ig), 0b1111);
+        input.reset_bit_stable(ig, 1).unwrap();
+        assert_eq!(output.u8(ig), 0b1101);
+        input.flip_bit_stable(ig, 0).unwrap();
+        assert_eq!(output.u8(ig), 0b1100);
+        input.set_bit_stable(ig, 0).unwrap();
+        assert_eq!(output.u8(ig), 0b1101);
+        assert_eq!(input.update_bit_stable(ig, 4, true), None);
+        input.update_bits_stable(ig, vec![false, true]);
+        assert_eq!(output.u8(ig), 0b1110);
+        input.set_to_stable(ig, 0b0101u8);
+        assert_eq!(output.u8(ig), 0b0101);
+        input.reset_stable(ig);
+        assert_eq!(output.u8(ig), 0);
+
+        assert_eq!(input.signed_range(), (-8, 7));
+        for value in -8..=7 {
+            input.set_to_signed_stable(ig, value);
+            assert_eq!(output.signed(ig), value as i128);
+            assert_eq!(output.u8(ig), value as u8 & 0xf);
+        }
+        // Truncated.
+        input.set_to_signed_stable(ig, 9);
+        assert_eq!(output.signed(ig), -7);
+    }
+
+    #[test]
+    fn test_signed_wide() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let input = WordInput::new(g, 100, "input");
+        let output = g.output(&input.bits(), "output");
+        let ig = &mut graph.init();
+
+        input.set_to_signed_stable(ig, i64::MIN);
+        assert_eq!(output.signed(ig), i64::MIN as i128);
+        assert_eq!(output.i64(ig), i64::MIN);
+        assert!(output.bx(ig, 99));
+        input.set_to_signed_stable(ig, -1);
+        assert_eq!(output.signed(ig), -1);
+        assert_eq!(output.i16(ig), -1);
+
+        assert_eq!(
+            WordInput::new(&mut GateGraphBuilder::new(), 1, "one").signed_range(),
+            (-1, 0)
+        );
+        assert_eq!(
+            WordInput::new(&mut GateGraphBuilder::new(), 64, "full").signed_range(),
+            (i64::MIN, i64::MAX)
+        );
+    }
+
+    #[test]
+    fn test_set_many_is_atomic() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let a = WordInput::new(g, 8, "a");
+        let b = WordInput::new(g, 8, "b");
+        let a_output = g.output(&a.bits(), "a");
+        let b_output = g.output(&b.bits(), "b");
+        let sum = adder(g, OFF, &a.bits(), &b.bits(), "adder");
+        let sum = g.output(&sum, "sum");
+        let ig = &mut graph.init();
+        WordInput::set_many_stable(ig, &[(&a, 255), (&b, 1)]);
+        assert_eq!(sum.u8(ig), 0);
+
+        let start = ig.now();
+        // Values are truncated to the width of each input.
+        WordInput::set_many(ig, &[(&a, 0x1fe), (&b, 0x10f)]);
+        assert_eq!(ig.now(), start + 1);
+        assert_eq!(a_output.u8(ig), 0xfe);
+        assert_eq!(b_output.u8(ig), 0x0f);
+        ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        assert_eq!(sum.u8(ig), 0x0d);
+    }
+}
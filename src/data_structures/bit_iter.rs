@@ -110,14 +110,14 @@ impl BitIter {
         let bit_size = byte_size * 8;
 
         assert!(
-            bit_size <= std::u16::MAX as usize,
+            bit_size <= u16::MAX as usize,
             "Item too big to bit iterate, If this is ever hit change the i to u32, bit_size: {}",
             bit_size
         );
 
         let as_u8s: &[u8] =
             // This is safe because any Copy + Sized + 'static item can be interpreted as a slice of bytes.
-            unsafe { std::slice::from_raw_parts(std::mem::transmute(&item), byte_size) };
+            unsafe { std::slice::from_raw_parts(&item as *const T as *const u8, byte_size) };
 
         Self {
             item: SmallVec::from_slice(as_u8s),
@@ -172,7 +172,7 @@ mod tests {
         let mut iterations = 0;
         for (i, set) in BitIter::new(n).enumerate() {
             assert_eq!(set, *result.get(i).unwrap_or(&false));
-            iterations = iterations + 1;
+            iterations += 1;
         }
         assert_eq!(iterations, std::mem::size_of_val(&n) * 8);
     }
@@ -184,7 +184,7 @@ mod tests {
         let mut iterations = 0;
         for (i, set) in BitIter::new(n).enumerate() {
             assert_eq!(set, *result.get(i).unwrap_or(&false));
-            iterations = iterations + 1;
+            iterations += 1;
         }
         assert_eq!(iterations, std::mem::size_of_val(&n) * 8);
     }
@@ -201,7 +201,7 @@ mod tests {
         let result = [true, true, false, true, false, false, false, false, true];
         let mut iterations = 0;
         for (i, set) in BitIter::new(n).enumerate() {
-            iterations = iterations + 1;
+            iterations += 1;
             assert_eq!(set, *result.get(i).unwrap_or(&false));
         }
         assert_eq!(iterations, std::mem::size_of_val(&n) * 8);
@@ -1,3 +1,5 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::iter::FromIterator;
 
 /// Data structure consisting of a write stack and a read stack, write operations are performed on the write stack,
@@ -23,6 +25,7 @@ use std::iter::FromIterator;
 /// assert_eq!(stacks.pop(), None);
 /// ```
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DoubleStack<T> {
     read_stack: Vec<T>,
     write_stack: Vec<T>,
@@ -82,7 +85,7 @@ impl<T: Clone> DoubleStack<T> {
     #[inline(always)]
     /// Clones all items from the slice to the end of the write stack.
     pub fn extend_from_slice(&mut self, v: &[T]) {
-        self.write_stack.extend_from_slice(&v)
+        self.write_stack.extend_from_slice(v)
     }
 }
 
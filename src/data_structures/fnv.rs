@@ -0,0 +1,25 @@
+use std::hash::Hasher;
+
+/// 64 bit FNV-1a hasher, for hashes that get stored in files.
+///
+/// Unlike [DefaultHasher](std::collections::hash_map::DefaultHasher) its algorithm is fixed,
+/// so the same data hashes the same with every Rust version.
+pub(crate) struct Fnv64(u64);
+impl Default for Fnv64 {
+    fn default() -> Self {
+        // http://www.isthe.com/chongo/tech/comp/fnv/index.html
+        Self(0xCBF2_9CE4_8422_2325)
+    }
+}
+impl Hasher for Fnv64 {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01B3);
+        }
+    }
+}
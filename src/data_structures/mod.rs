@@ -1,16 +1,22 @@
 mod bit_iter;
 mod double_stack;
+#[cfg(feature = "serde")]
+mod fnv;
 mod immutable;
 mod slab;
 #[cfg(feature = "logicsim_unstable")]
 mod slab_unstable;
+mod split_mix;
 mod state;
 pub use bit_iter::*;
 pub use double_stack::*;
+#[cfg(feature = "serde")]
+pub(crate) use fnv::Fnv64;
 pub use immutable::*;
 #[cfg(not(feature = "logicsim_unstable"))]
 pub use slab::Slab;
 pub use slab::SlabIndex;
 #[cfg(feature = "logicsim_unstable")]
 pub use slab_unstable::Slab;
+pub(crate) use split_mix::SplitMix64;
 pub use state::*;
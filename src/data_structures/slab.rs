@@ -1,9 +1,12 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display, Formatter};
 
 /// Transparent type that represents an index into a [Slab].
 ///
 /// used to discourage accessing the [Slab] at arbitrary indexes.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(transparent)]
 pub struct SlabIndex(pub(super) usize);
 impl SlabIndex {
@@ -43,9 +46,11 @@ impl Display for SlabIndex {
 /// assert_eq!(s.get(index), None);
 /// ```
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Slab<T: Sized> {
     data: Vec<Option<T>>,
     removed_indexes: Vec<SlabIndex>,
+    generations: Vec<u32>,
 }
 #[allow(dead_code)]
 impl<T: Sized> Slab<T> {
@@ -54,6 +59,7 @@ impl<T: Sized> Slab<T> {
         Self {
             data: Vec::new(),
             removed_indexes: Default::default(),
+            generations: Vec::new(),
         }
     }
 
@@ -67,6 +73,7 @@ impl<T: Sized> Slab<T> {
         } else {
             let index = SlabIndex(self.data.len());
             self.data.push(Some(item));
+            self.generations.push(0);
             index
         }
     }
@@ -101,11 +108,18 @@ impl<T: Sized> Slab<T> {
                 return None;
             }
             self.removed_indexes.push(index);
+            self.generations[index.0] = self.generations[index.0].wrapping_add(1);
             return position.take();
         }
         None
     }
 
+    /// Returns the number of times the slot at `index` has been removed,
+    /// which allows telling apart the items that have occupied it.
+    pub fn generation(&self, index: SlabIndex) -> u32 {
+        self.generations.get(index.0).copied().unwrap_or(0)
+    }
+
     /// Returns the number of items in the slab.
     ///
     /// This is different from the number of allocated slots in the slab, see [Slab::total_len]
@@ -125,8 +139,13 @@ impl<T: Sized> Slab<T> {
         self.data.len()
     }
 
+    /// Returns the number of slots the slab can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
     /// Returns an iterator over pairs of ```(SlabIndex, [&T])```.
-    pub fn iter(&self) -> Iter<T> {
+    pub fn iter(&self) -> Iter<'_, T> {
         Iter {
             iter: self.data.iter().enumerate(),
         }
@@ -152,7 +171,7 @@ impl<T: Sized> Slab<T> {
             "Tried to access removed index:{}",
             index
         );
-        &self.data.get_unchecked(index.0).as_ref().unwrap()
+        self.data.get_unchecked(index.0).as_ref().unwrap()
     }
 }
 
@@ -265,6 +284,21 @@ mod tests {
         assert_eq!(s.remove(new_index), Some(2));
     }
 
+    #[test]
+    fn test_generation() {
+        let mut s = Slab::new();
+
+        let index = s.insert(1);
+        assert_eq!(s.generation(index), 0);
+        s.remove(index);
+        assert_eq!(s.generation(index), 1);
+
+        let new_index = s.insert(2);
+        assert_eq!(index, new_index);
+        assert_eq!(s.generation(new_index), 1);
+        assert_eq!(s.generation(SlabIndex(5)), 0);
+    }
+
     #[test]
     fn test_len() {
         let mut s = Slab::new();
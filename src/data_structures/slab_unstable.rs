@@ -1,5 +1,7 @@
 use super::SlabIndex;
 use indexmap::IndexSet;
+#[cfg(feature = "serde")]
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
 use std::mem::MaybeUninit;
 
 /// Simple slab allocator. Stores items of the same type and can reuse removed indexes.
@@ -21,6 +23,7 @@ use std::mem::MaybeUninit;
 pub struct Slab<T: Sized> {
     data: Vec<MaybeUninit<T>>,
     removed_indexes: IndexSet<SlabIndex>,
+    generations: Vec<u32>,
 }
 impl<T: Sized> Slab<T> {
     /// Returns an empty [Slab].
@@ -28,6 +31,7 @@ impl<T: Sized> Slab<T> {
         Self {
             data: Vec::new(),
             removed_indexes: IndexSet::new(),
+            generations: Vec::new(),
         }
     }
 
@@ -41,6 +45,7 @@ impl<T: Sized> Slab<T> {
         } else {
             let index = SlabIndex(self.data.len());
             self.data.push(MaybeUninit::new(item));
+            self.generations.push(0);
             index
         }
     }
@@ -84,6 +89,7 @@ impl<T: Sized> Slab<T> {
                 return None;
             }
             self.removed_indexes.insert(index);
+            self.generations[index.0] = self.generations[index.0].wrapping_add(1);
             let item = std::mem::replace(position, MaybeUninit::uninit());
             // This is safe because we check if the item is an empty space.
             unsafe { return Some(item.assume_init()) };
@@ -91,6 +97,12 @@ impl<T: Sized> Slab<T> {
         None
     }
 
+    /// Returns the number of times the slot at `index` has been removed,
+    /// which allows telling apart the items that have occupied it.
+    pub fn generation(&self, index: SlabIndex) -> u32 {
+        self.generations.get(index.0).copied().unwrap_or(0)
+    }
+
     /// Returns the number of items in the slab.
     ///
     /// This is different from the number of allocated slots in the slab, see [Slab::total_len]
@@ -110,6 +122,11 @@ impl<T: Sized> Slab<T> {
         self.data.len()
     }
 
+    /// Returns the number of slots the slab can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
     /// Returns an iterator over pairs of ```(SlabIndex, [&T])```.
     pub fn iter(&self) -> Iter<T> {
         Iter {
@@ -158,6 +175,7 @@ impl<T: Clone> Clone for Slab<T> {
         Self {
             data,
             removed_indexes: self.removed_indexes.clone(),
+            generations: self.generations.clone(),
         }
     }
 }
@@ -230,6 +248,77 @@ impl<T> Default for Slab<T> {
     }
 }
 
+/// Serialized form of [Slab], the same one the stable slab derives.
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+struct SerializeSlab<'a, T> {
+    data: Vec<Option<&'a T>>,
+    removed_indexes: &'a IndexSet<SlabIndex>,
+    generations: &'a [u32],
+}
+
+/// Deserialized form of [Slab], see [SerializeSlab].
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct DeserializeSlab<T> {
+    data: Vec<Option<T>>,
+    removed_indexes: IndexSet<SlabIndex>,
+    generations: Vec<u32>,
+}
+
+#[cfg(feature = "serde")]
+impl<T: Serialize> Serialize for Slab<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializeSlab {
+            data: (0..self.data.len())
+                .map(|index| self.get(SlabIndex(index)))
+                .collect(),
+            removed_indexes: &self.removed_indexes,
+            generations: &self.generations,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Slab<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let DeserializeSlab {
+            data,
+            removed_indexes,
+            generations,
+        } = DeserializeSlab::deserialize(deserializer)?;
+        if generations.len() != data.len() {
+            return Err(D::Error::custom(
+                "Slab has a different number of generations and items",
+            ));
+        }
+        if removed_indexes.iter().any(|index| index.0 >= data.len()) {
+            return Err(D::Error::custom("Slab has removed indexes out of bounds"));
+        }
+        // The safety of the slab depends on removed indexes being exactly the empty slots.
+        let data = data
+            .into_iter()
+            .enumerate()
+            .map(
+                |(index, item)| match (item, removed_indexes.contains(&SlabIndex(index))) {
+                    (Some(item), false) => Ok(MaybeUninit::new(item)),
+                    (None, true) => Ok(MaybeUninit::uninit()),
+                    _ => Err(D::Error::custom(format!(
+                        "Slab slot {} doesn't match its removed indexes",
+                        index
+                    ))),
+                },
+            )
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            data,
+            removed_indexes,
+            generations,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -276,6 +365,21 @@ mod tests {
         assert_eq!(s.remove(new_index), Some(2));
     }
 
+    #[test]
+    fn test_generation() {
+        let mut s = Slab::new();
+
+        let index = s.insert(1);
+        assert_eq!(s.generation(index), 0);
+        s.remove(index);
+        assert_eq!(s.generation(index), 1);
+
+        let new_index = s.insert(2);
+        assert_eq!(index, new_index);
+        assert_eq!(s.generation(new_index), 1);
+        assert_eq!(s.generation(SlabIndex(5)), 0);
+    }
+
     #[test]
     fn test_len() {
         let mut s = Slab::new();
@@ -338,6 +442,30 @@ mod tests {
             assert_eq!(n1, n2);
         }
     }
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde() {
+        let mut s = Slab::new();
+        for i in 0..10 {
+            s.insert(i);
+        }
+        for i in (0..10).step_by(2) {
+            s.remove(SlabIndex(i));
+        }
+        let bytes = bincode::serialize(&s).unwrap();
+        let mut ss: Slab<usize> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(ss.len(), 5);
+        assert_eq!(ss.generation(SlabIndex(2)), 1);
+        for ((i1, n1), (i2, n2)) in s.iter().zip(ss.iter()) {
+            assert_eq!(i1, i2);
+            assert_eq!(n1, n2);
+        }
+        assert_eq!(ss.insert(10), SlabIndex(8));
+
+        let stable: super::super::slab::Slab<usize> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(stable.len(), 5);
+    }
+
     #[test]
     fn test_get_very_unsafely() {
         let mut s = Slab::new();
@@ -0,0 +1,33 @@
+/// Small and fast pseudo random number generator, good enough to generate graphs and stimulus
+/// and it doesn't need an extra dependency.
+pub(crate) struct SplitMix64(pub(crate) u64);
+impl SplitMix64 {
+    pub(crate) fn next(&mut self) -> u64 {
+        // https://prng.di.unimi.it/splitmix64.c
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number whose lowest `bits` bits are random and the rest 0, `bits` <= 128.
+    pub(crate) fn bits(&mut self, bits: usize) -> u128 {
+        let value = (self.next() as u128) << 64 | self.next() as u128;
+        if bits >= 128 {
+            value
+        } else {
+            value & ((1 << bits) - 1)
+        }
+    }
+
+    /// Returns a number in 0..n.
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// Returns true with a probability of 1/n.
+    pub(crate) fn one_in(&mut self, n: usize) -> bool {
+        self.below(n) == 0
+    }
+}
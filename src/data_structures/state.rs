@@ -1,5 +1,7 @@
 use super::word_mask_64;
 use num_integer::div_ceil;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use unwrap::unwrap;
 
 /// Data structure that represents a fixed size (at runtime) array of bits,
@@ -35,6 +37,7 @@ use unwrap::unwrap;
 ///
 ///
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct State {
     states: Vec<u64>,
     updated: Vec<u64>,
@@ -0,0 +1,246 @@
+use super::gate::*;
+use super::graph_builder::GateGraphBuilder;
+use super::handles::OutputHandle;
+use super::simulation_error::SimulationError;
+use super::InitializedGateGraph;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Assertion declared with [GateGraphBuilder::assert_always] or [GateGraphBuilder::assert_never].
+#[derive(Debug, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(super) struct AssertionSource {
+    message: String,
+    condition: OutputHandle,
+    /// State the condition must always have.
+    expected: bool,
+}
+
+/// Failure of an assertion declared with [GateGraphBuilder::assert_always] or [GateGraphBuilder::assert_never],
+/// see [InitializedGateGraph::assertion_failures].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AssertionFailure {
+    /// Message of the assertion.
+    pub message: String,
+    /// [Simulation time](InitializedGateGraph::now) of the tick at the end of which it failed.
+    pub tick: usize,
+}
+
+/// Assertions checked by an [InitializedGateGraph].
+#[derive(Debug, Default)]
+pub(super) struct Assertions {
+    sources: Vec<AssertionSource>,
+    /// True if the assertion was failing at the end of the last tick in which it was checked.
+    failing: Vec<bool>,
+    armed: bool,
+    failures: Vec<AssertionFailure>,
+}
+impl Assertions {
+    pub(super) fn new(sources: Vec<AssertionSource>) -> Self {
+        Self {
+            failing: vec![false; sources.len()],
+            sources,
+            armed: false,
+            failures: Vec::new(),
+        }
+    }
+
+    /// Returns the number of failures recorded so far.
+    pub(super) fn failure_count(&self) -> usize {
+        self.failures.len()
+    }
+
+    /// Returns a [SimulationError::AssertionFailed] for the failure number `n`.
+    pub(super) fn error(&self, n: usize) -> SimulationError {
+        let failure = &self.failures[n];
+        SimulationError::AssertionFailed {
+            message: failure.message.clone(),
+            tick: failure.tick,
+        }
+    }
+}
+
+impl GateGraphBuilder {
+    /// Declares an assertion that fails if `condition` is ever off once the graph is stable,
+    /// see [InitializedGateGraph::arm_assertions].
+    ///
+    /// Assertions turn the invariants of a circuit, like "only one of these enables is active" or "the stack never
+    /// overflows", into checks that run on every tick, so a bug gets reported where it happens instead of
+    /// several cycles later through a wrong output. Keep in mind that observed gates can't be optimized away.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,SimulationError};
+    /// let mut g = GateGraphBuilder::new();
+    /// let read = g.lever("read");
+    /// let write = g.lever("write");
+    /// let exclusive = g.nand2(read.bit(), write.bit(), "exclusive");
+    /// g.assert_always(exclusive, "read and write at the same time");
+    ///
+    /// let ig = &mut g.init();
+    /// ig.arm_assertions();
+    /// ig.set_lever_stable(read);
+    /// ig.reset_lever_stable(read);
+    /// ig.set_lever(write);
+    /// ig.set_lever(read);
+    /// match ig.run_until_stable(10) {
+    ///     Err(SimulationError::AssertionFailed { message, .. }) => {
+    ///         assert_eq!(message, "read and write at the same time")
+    ///     }
+    ///     other => panic!("{:?}", other),
+    /// }
+    /// assert_eq!(ig.assertion_failures().len(), 1);
+    /// ```
+    pub fn assert_always<S: Into<String>>(&mut self, condition: GateIndex, message: S) {
+        self.assertion(condition, message.into(), true)
+    }
+
+    /// Declares an assertion that fails if `condition` is ever on once the graph is stable,
+    /// see [GateGraphBuilder::assert_always].
+    pub fn assert_never<S: Into<String>>(&mut self, condition: GateIndex, message: S) {
+        self.assertion(condition, message.into(), false)
+    }
+
+    /// Declares an assertion that fails if `condition` isn't `expected`.
+    fn assertion(&mut self, condition: GateIndex, message: String, expected: bool) {
+        let condition = self.output1(condition, format!("assertion:{}", message));
+        self.assertions.push(AssertionSource {
+            message,
+            condition,
+            expected,
+        });
+    }
+
+    /// Returns true if the condition of an assertion reads `gate`.
+    pub(super) fn assertion_reads(&self, gate: GateIndex) -> bool {
+        self.assertions
+            .iter()
+            .any(|assertion| self.output_reads(assertion.condition, gate))
+    }
+}
+
+impl InitializedGateGraph {
+    /// Starts checking the assertions declared with [GateGraphBuilder::assert_always] and
+    /// [GateGraphBuilder::assert_never].
+    ///
+    /// The state of a circuit right after initialization is usually meaningless until it's been reset, so the
+    /// assertions only get checked once they are armed, call this after resetting the circuit.
+    ///
+    /// Assertions are checked at the end of every [tick](InitializedGateGraph::tick) after which the graph is
+    /// stable, changes still propagating through loops don't count. Every time one starts failing, an
+    /// [AssertionFailure] is recorded and [run_until_stable](InitializedGateGraph::run_until_stable) and
+    /// [try_tick](InitializedGateGraph::try_tick) return [SimulationError::AssertionFailed], so the methods that
+    /// end in `_stable` panic with its message.
+    pub fn arm_assertions(&mut self) {
+        self.assertions.armed = true;
+        self.check_assertions();
+    }
+
+    /// Stops checking assertions until [arm_assertions](InitializedGateGraph::arm_assertions) is called again.
+    pub fn disarm_assertions(&mut self) {
+        self.assertions.armed = false;
+        for failing in &mut self.assertions.failing {
+            *failing = false;
+        }
+    }
+
+    /// Returns every assertion failure since the graph was initialized, in order,
+    /// see [arm_assertions](InitializedGateGraph::arm_assertions).
+    pub fn assertion_failures(&self) -> &[AssertionFailure] {
+        &self.assertions.failures
+    }
+
+    /// Records the assertions that started failing if they are armed and the graph is stable.
+    pub(super) fn check_assertions(&mut self) {
+        if !self.assertions.armed || self.assertions.sources.is_empty() || !self.is_idle() {
+            return;
+        }
+        for i in 0..self.assertions.sources.len() {
+            let source = &self.assertions.sources[i];
+            let failing = self.value(self.get_output(source.condition).bits[0]) != source.expected;
+            if failing && !self.assertions.failing[i] {
+                let failure = AssertionFailure {
+                    message: source.message.clone(),
+                    tick: self.ticks,
+                };
+                tracing::error!(
+                    target: "logicsim::assertion",
+                    tick = self.ticks,
+                    "Assertion failed: {}",
+                    failure.message
+                );
+                self.assertions.failures.push(failure);
+            }
+            self.assertions.failing[i] = failing;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{counter, zeros};
+
+    #[test]
+    fn test_assert_never() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let count = counter(
+            g,
+            clock.bit(),
+            ON,
+            OFF,
+            ON,
+            reset.bit(),
+            &zeros(3),
+            "counter",
+        );
+        let seven = g.andx(count.iter().copied(), "seven");
+        g.assert_never(seven, "counter reached 7");
+        let ig = &mut graph.init();
+
+        // The counter starts at 7, nothing is checked until the assertions are armed.
+        assert!(ig.run_until_stable(10).is_ok());
+        ig.pulse_lever_stable(reset);
+        ig.arm_assertions();
+        for _ in 0..6 {
+            ig.pulse_lever_stable(clock);
+        }
+        assert!(ig.assertion_failures().is_empty());
+
+        ig.set_lever(clock);
+        let error = ig.run_until_stable(10).unwrap_err();
+        assert_eq!(error.to_string(), "Assertion failed: counter reached 7");
+        let failures = ig.assertion_failures();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].tick, ig.now());
+
+        // Still failing, it's only reported once.
+        assert_eq!(ig.run_until_stable(10), Ok(0));
+        ig.reset_lever_stable(clock);
+        ig.pulse_lever_stable(clock);
+        ig.disarm_assertions();
+        for _ in 0..8 {
+            ig.pulse_lever_stable(clock);
+        }
+        assert_eq!(ig.assertion_failures().len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "always off")]
+    fn test_stable_methods_panic() {
+        let mut g = GateGraphBuilder::new();
+        let l = g.lever("l");
+        g.assert_always(l.bit(), "always on");
+        let not = g.not1(l.bit(), "not");
+        g.assert_never(l.bit(), "always off");
+        g.output1(not, "not");
+
+        let ig = &mut g.init();
+        ig.arm_assertions();
+        assert_eq!(ig.assertion_failures()[0].message, "always on");
+        ig.set_lever_stable(l);
+    }
+}
@@ -0,0 +1,524 @@
+use super::custom_gate::{CustomGate, CUSTOM_GATE_MAX_ARITY};
+use super::gate::*;
+use super::handles::*;
+use super::{InitializedGateGraph, DEFAULT_STABLE_MAX};
+use crate::data_structures::BitIter;
+
+/// Number of input assignments evaluated at once by [InitializedGateGraph::evaluate_many], one per bit of a [u64].
+const LANES: usize = 64;
+
+/// Values of a group of levers, one of the inputs of [InitializedGateGraph::evaluate_many].
+///
+/// Levers that are not part of the assignment keep the value they have in the graph.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct LeverAssignment {
+    levers: Vec<(LeverHandle, bool)>,
+}
+impl LeverAssignment {
+    /// Returns an empty [LeverAssignment].
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets `lever` to `value`.
+    pub fn lever(mut self, lever: LeverHandle, value: bool) -> Self {
+        self.levers.push((lever, value));
+        self
+    }
+
+    /// Sets `levers` to the bits of `value`, bit 0 goes to `levers[0]`.
+    /// If there are more levers than bits in `value`, the rest will be set to false.
+    pub fn set(mut self, levers: &[LeverHandle], value: u128) -> Self {
+        self.levers.extend(
+            levers
+                .iter()
+                .copied()
+                .zip(BitIter::new(value).chain(std::iter::repeat(false))),
+        );
+        self
+    }
+}
+
+/// Values of every output of the graph for one [LeverAssignment], see [InitializedGateGraph::evaluate_many].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OutputValues {
+    values: Vec<u128>,
+}
+impl OutputValues {
+    /// Returns the value of `output` like [OutputHandle::u128].
+    pub fn get(&self, output: OutputHandle) -> u128 {
+        self.values[output.0]
+    }
+}
+
+/// Values of every output of the graph in the 64 lanes evaluated by [InitializedGateGraph::tick_vector].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LaneValues {
+    /// Lane words of every bit of every output.
+    outputs: Vec<Vec<u64>>,
+}
+impl LaneValues {
+    /// Returns a word per bit of `output`, bit `lane` of every word is the value of the output bit in that lane.
+    pub fn bits(&self, output: OutputHandle) -> &[u64] {
+        &self.outputs[output.0]
+    }
+
+    /// Returns the value of `output` in `lane` like [OutputHandle::u128].
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `lane` >= 64.
+    pub fn get(&self, output: OutputHandle, lane: usize) -> u128 {
+        assert!(lane < LANES, "There are only {} lanes", LANES);
+        self.outputs[output.0]
+            .iter()
+            .take(128)
+            .enumerate()
+            .fold(0, |acc, (bit, word)| {
+                acc | ((word >> lane & 1) as u128) << bit
+            })
+    }
+}
+
+impl InitializedGateGraph {
+    /// Returns the value of every output for each one of the lever assignments in `inputs`,
+    /// as if the levers had been set and the graph had run until it's stable.
+    /// The state of the graph is left untouched.
+    ///
+    /// Combinational circuits are evaluated in topological order, 64 assignments at a time in the bits
+    /// of a [u64], which is much faster than setting the levers and propagating the changes one assignment
+    /// at a time, for example to exhaustively test an ALU.
+    /// If the graph has loops it falls back to doing exactly that, starting from the current state
+    /// for every assignment.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,LeverAssignment};
+    /// # use logicsim::{adder,WordInput,OFF};
+    /// let mut g = GateGraphBuilder::new();
+    /// let a = WordInput::new(&mut g, 8, "a");
+    /// let b = WordInput::new(&mut g, 8, "b");
+    /// let sum = adder(&mut g, OFF, &a.bits(), &b.bits(), "adder");
+    /// let output = g.output(&sum, "sum");
+    ///
+    /// let ig = &mut g.init();
+    /// let inputs: Vec<_> = (0..=255u128)
+    ///     .map(|value| LeverAssignment::new().set(a.levers(), value).set(b.levers(), 3))
+    ///     .collect();
+    /// let outputs = ig.evaluate_many(&inputs);
+    /// for (value, output_values) in outputs.iter().enumerate() {
+    ///     assert_eq!(output_values.get(output), (value as u128 + 3) % 256);
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the graph has loops and it doesn't stabilize after [DEFAULT_STABLE_MAX] ticks for any assignment.
+    pub fn evaluate_many(&mut self, inputs: &[LeverAssignment]) -> Vec<OutputValues> {
+        match self.lane_evaluator() {
+            Some(evaluator) => inputs
+                .chunks(LANES)
+                .flat_map(|chunk| evaluator.evaluate_assignments(chunk))
+                .collect(),
+            None => self.evaluate_sequentially(inputs),
+        }
+    }
+
+    /// Returns every gate in an order where dependencies come before their dependents,
+    /// [None] if the graph has loops.
+    fn topological_order(&self) -> Option<Vec<GateIndex>> {
+        let mut missing: Vec<usize> = self
+            .nodes
+            .iter()
+            .map(|node| node.dependencies.len())
+            .collect();
+        let mut order: Vec<GateIndex> = (0..self.nodes.len())
+            .filter(|i| missing[*i] == 0)
+            .map(|i| gi!(i))
+            .collect();
+        let mut next = 0;
+        while next < order.len() {
+            let gate = order[next];
+            next += 1;
+            for dependent in &self.nodes[gate.idx].dependents {
+                // Repeated dependencies show up as repeated dependents too.
+                missing[dependent.idx] -= 1;
+                if missing[dependent.idx] == 0 {
+                    order.push(*dependent);
+                }
+            }
+        }
+        if order.len() == self.nodes.len() {
+            Some(order)
+        } else {
+            None
+        }
+    }
+
+    /// Returns a [LaneEvaluator] for the graph with the levers at their current values, [None] if it has loops.
+    pub(crate) fn lane_evaluator(&self) -> Option<LaneEvaluator<'_>> {
+        let order = self.topological_order()?;
+        Some(LaneEvaluator {
+            nodes: &self.nodes,
+            custom_gates: &self.custom_gates,
+            lever_handles: &self.lever_handles,
+            output_handles: &self.output_handles,
+            order,
+            levers: self
+                .lever_handles
+                .iter()
+                .map(|idx| if self.value(*idx) { !0 } else { 0 })
+                .collect(),
+        })
+    }
+
+    /// Evaluates the graph for 64 different values of `levers` at once and returns the value of every output in
+    /// each of them, as if the levers had been set and the graph had run until it's stable.
+    /// The state of the graph is left untouched.
+    ///
+    /// This is the raw, [bit-sliced](https://en.wikipedia.org/wiki/Bit_slicing) version of
+    /// [evaluate_many](InitializedGateGraph::evaluate_many): `inputs[n]` holds the values of `levers[n]`,
+    /// bit `lane` of every word is one input combination, or lane. Levers not in `levers` keep their value in
+    /// every lane. Exhaustively testing a combinational circuit with 20 inputs takes 2^14 calls.
+    ///
+    /// Graphs with loops are evaluated one lane at a time, like [evaluate_many](InitializedGateGraph::evaluate_many).
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// # use logicsim::{adder,WordInput,OFF};
+    /// let mut g = GateGraphBuilder::new();
+    /// let a = WordInput::new(&mut g, 3, "a");
+    /// let b = WordInput::new(&mut g, 3, "b");
+    /// let sum = adder(&mut g, OFF, &a.bits(), &b.bits(), "adder");
+    /// let output = g.output(&sum, "sum");
+    ///
+    /// let ig = &mut g.init();
+    /// let levers: Vec<_> = a.levers().iter().chain(b.levers()).copied().collect();
+    /// // Lane n has a = n % 8 and b = n / 8, every combination is tested in one call.
+    /// let inputs: Vec<u64> = (0..6)
+    ///     .map(|bit| (0..64).fold(0, |word, lane| word | ((lane >> bit) & 1) << lane))
+    ///     .collect();
+    ///
+    /// let values = ig.tick_vector(&levers, &inputs);
+    /// for lane in 0..64 {
+    ///     assert_eq!(values.get(output, lane), (lane % 8 + lane / 8) as u128 % 8);
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `levers.len()` != `inputs.len()`, or if the graph has loops and it doesn't stabilize after
+    /// [DEFAULT_STABLE_MAX] ticks for any lane.
+    pub fn tick_vector(&mut self, levers: &[LeverHandle], inputs: &[u64]) -> LaneValues {
+        assert_eq!(
+            levers.len(),
+            inputs.len(),
+            "There must be an input word for every lever"
+        );
+        let evaluator = match self.lane_evaluator() {
+            Some(evaluator) => evaluator,
+            None => {
+                let assignments: Vec<_> = (0..LANES)
+                    .map(|lane| {
+                        levers.iter().zip(inputs).fold(
+                            LeverAssignment::new(),
+                            |assignment, (lever, word)| {
+                                assignment.lever(*lever, word >> lane & 1 == 1)
+                            },
+                        )
+                    })
+                    .collect();
+                let outputs = self.evaluate_sequentially(&assignments);
+                return LaneValues {
+                    outputs: self
+                        .output_handles
+                        .iter()
+                        .enumerate()
+                        .map(|(i, output)| {
+                            (0..output.bits.len())
+                                .map(|bit| {
+                                    outputs.iter().enumerate().fold(0, |word, (lane, values)| {
+                                        let value = values.values[i].checked_shr(bit as u32);
+                                        word | ((value.unwrap_or(0) & 1) as u64) << lane
+                                    })
+                                })
+                                .collect()
+                        })
+                        .collect(),
+                };
+            }
+        };
+        evaluator.evaluate(levers, inputs)
+    }
+
+    /// Evaluates every assignment by setting the levers and running the graph until it's stable,
+    /// for graphs with loops.
+    fn evaluate_sequentially(&mut self, inputs: &[LeverAssignment]) -> Vec<OutputValues> {
+        self.with_restored_state(|ig| {
+            ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+            let initial = ig.state.clone();
+            inputs
+                .iter()
+                .map(|assignment| {
+                    ig.state = initial.clone();
+                    for (lever, value) in &assignment.levers {
+                        ig.update_lever_inner(*lever, *value);
+                    }
+                    ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+                    OutputValues {
+                        values: (0..ig.output_handles.len())
+                            .map(|i| OutputHandle(i).u128(ig))
+                            .collect(),
+                    }
+                })
+                .collect()
+        })
+    }
+}
+
+/// Loop free graph evaluated [LANES] input combinations at a time in the bits of a [u64], see
+/// [InitializedGateGraph::tick_vector].
+///
+/// It only borrows the parts of the graph it needs, so unlike the graph it can be shared between threads.
+pub(crate) struct LaneEvaluator<'a> {
+    nodes: &'a [InitializedGate],
+    custom_gates: &'a [CustomGate],
+    lever_handles: &'a [GateIndex],
+    output_handles: &'a [Output],
+    /// Every gate, dependencies before their dependents.
+    order: Vec<GateIndex>,
+    /// A word per lever, in creation order, with its value in the graph in every lane.
+    levers: Vec<u64>,
+}
+impl<'a> LaneEvaluator<'a> {
+    /// Evaluates [LANES] values of `levers` at once like [InitializedGateGraph::tick_vector].
+    pub(crate) fn evaluate(&self, levers: &[LeverHandle], inputs: &[u64]) -> LaneValues {
+        let mut words = self.levers.clone();
+        for (lever, word) in levers.iter().zip(inputs) {
+            words[lever.handle] = *word;
+        }
+        let values = self.evaluate_words(&words, LANES);
+        LaneValues {
+            outputs: self
+                .output_handles
+                .iter()
+                .map(|output| output.bits.iter().map(|gate| values[gate.idx]).collect())
+                .collect(),
+        }
+    }
+
+    /// Evaluates up to [LANES] assignments at once, lane `n` holds the value of the gates for `inputs[n]`.
+    fn evaluate_assignments(&self, inputs: &[LeverAssignment]) -> Vec<OutputValues> {
+        let mut levers = self.levers.clone();
+        for (lane, assignment) in inputs.iter().enumerate() {
+            for (lever, value) in &assignment.levers {
+                let word = &mut levers[lever.handle];
+                *word = *word & !(1 << lane) | (*value as u64) << lane;
+            }
+        }
+        let values = self.evaluate_words(&levers, inputs.len());
+
+        (0..inputs.len())
+            .map(|lane| OutputValues {
+                values: self
+                    .output_handles
+                    .iter()
+                    .map(|output| {
+                        output
+                            .bits
+                            .iter()
+                            .take(128)
+                            .enumerate()
+                            .fold(0, |acc, (bit, gate)| {
+                                acc | ((values[gate.idx] >> lane & 1) as u128) << bit
+                            })
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Returns the value of every gate in the first `lanes` lanes, with the levers set to `levers`,
+    /// a word per lever in creation order.
+    fn evaluate_words(&self, levers: &[u64], lanes: usize) -> Vec<u64> {
+        let mut values = vec![0u64; self.nodes.len()];
+        // Levers are sources, their values are set before going through the order.
+        for (idx, word) in self.lever_handles.iter().zip(levers) {
+            values[idx.idx] = *word;
+        }
+
+        for gate in &self.order {
+            let node = &self.nodes[gate.idx];
+            let dependencies = node.dependencies.iter().map(|d| values[d.idx]);
+            let value = match node.ty {
+                GateType::On => !0,
+                GateType::Off => 0,
+                GateType::Lever => continue,
+                GateType::Not => !values[node.dependencies[0].idx],
+                GateType::Delay => values[node.dependencies[0].idx],
+                GateType::TriState => {
+                    values[node.dependencies[0].idx] & values[node.dependencies[1].idx]
+                }
+                GateType::And => dependencies.fold(!0, |acc, d| acc & d),
+                GateType::Nand => !dependencies.fold(!0, |acc, d| acc & d),
+                GateType::Or => dependencies.fold(0, |acc, d| acc | d),
+                GateType::Nor => !dependencies.fold(0, |acc, d| acc | d),
+                GateType::Xor => dependencies.fold(0, |acc, d| acc ^ d),
+                GateType::Xnor => !dependencies.fold(0, |acc, d| acc ^ d),
+                GateType::Custom(id) => {
+                    let eval = self.custom_gates[id as usize].eval;
+                    let mut value = 0;
+                    let mut lane_inputs = [false; CUSTOM_GATE_MAX_ARITY];
+                    for lane in 0..lanes {
+                        for (input, dependency) in lane_inputs.iter_mut().zip(dependencies.clone())
+                        {
+                            *input = dependency >> lane & 1 == 1;
+                        }
+                        if eval(&lane_inputs[..node.dependencies.len()]) {
+                            value |= 1 << lane;
+                        }
+                    }
+                    value
+                }
+            };
+            values[gate.idx] = value;
+        }
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{adder, WordInput};
+
+    #[test]
+    fn test_evaluate_many_matches_simulation() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let a = WordInput::new(g, 4, "a");
+        let b = WordInput::new(g, 4, "b");
+        let c = g.lever("c");
+        let sum = adder(g, c.bit(), &a.bits(), &b.bits(), "adder");
+        let xnor = g.xnor2(sum[0], a.bits()[3], "xnor");
+        let maj3 = g.register_gate("MAJ3", 3, |i| i.iter().filter(|b| **b).count() >= 2);
+        let majority = g.custom(maj3, &[a.bits()[0], b.bits()[0], c.bit()], "majority");
+        let sum = g.output(&sum, "sum");
+        let other = g.output(&[xnor, majority], "other");
+
+        let ig = &mut graph.init_unoptimized();
+        // More than 64 to use several chunks, c is left at its current value in some of them.
+        ig.set_lever_stable(c);
+        let inputs: Vec<_> = (0..300u128)
+            .map(|i| {
+                let assignment = LeverAssignment::new()
+                    .set(a.levers(), i % 16)
+                    .set(b.levers(), i / 16 % 16);
+                if i < 256 {
+                    assignment.lever(c, i % 3 == 0)
+                } else {
+                    assignment
+                }
+            })
+            .collect();
+        let outputs = ig.evaluate_many(&inputs);
+        assert_eq!(outputs.len(), inputs.len());
+
+        for (i, values) in outputs.iter().enumerate() {
+            a.set_to(ig, i % 16);
+            b.set_to(ig, i / 16 % 16);
+            if i < 256 {
+                ig.update_lever(c, i % 3 == 0);
+            } else {
+                ig.update_lever(c, true);
+            }
+            ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+            assert_eq!(values.get(sum), sum.u128(ig), "{}", i);
+            assert_eq!(values.get(other), other.u128(ig), "{}", i);
+        }
+    }
+
+    #[test]
+    fn test_evaluate_many_with_loops() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let set = g.lever("set");
+        let reset = g.lever("reset");
+        let q = g.nor2(reset.bit(), OFF, "q");
+        let nq = g.nor2(set.bit(), q, "nq");
+        g.d1(q, nq);
+        let output = g.output1(q, "q");
+
+        let ig = &mut graph.init_unoptimized();
+        ig.pulse_lever_stable(set);
+        let outputs = ig.evaluate_many(&[
+            LeverAssignment::new(),
+            LeverAssignment::new().lever(reset, true),
+            LeverAssignment::new(),
+        ]);
+        // Every assignment starts from the current state.
+        let values: Vec<_> = outputs.iter().map(|v| v.get(output)).collect();
+        assert_eq!(values, [1, 0, 1]);
+        assert_eq!(output.u8(ig), 1);
+    }
+
+    #[test]
+    fn test_tick_vector_matches_evaluate_many() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let a = WordInput::new(g, 3, "a");
+        let b = g.lever("b");
+        let off = g.lever("off");
+        let xor = g.xorx(a.bits().into_iter(), "xor");
+        let maj3 = g.register_gate("MAJ3", 3, |i| i.iter().filter(|b| **b).count() >= 2);
+        let majority = g.custom(maj3, &a.bits(), "majority");
+        let and = g.and2(majority, b.bit(), "and");
+        let wide = g.output(&[xor, majority, and], "wide");
+        let not = g.not1(off.bit(), "not");
+        let inverted = g.output1(not, "inverted");
+
+        let ig = &mut graph.init_unoptimized();
+        // b keeps its value in every lane.
+        ig.set_lever_stable(b);
+        let inputs = [
+            0xAAAA_AAAA_AAAA_AAAA,
+            0xCCCC_CCCC_CCCC_CCCC,
+            0x0123_4567_89AB_CDEF,
+        ];
+        let values = ig.tick_vector(a.levers(), &inputs);
+        let assignments: Vec<_> = (0..64)
+            .map(|lane| {
+                a.levers()
+                    .iter()
+                    .zip(&inputs)
+                    .fold(LeverAssignment::new(), |assignment, (lever, word)| {
+                        assignment.lever(*lever, word >> lane & 1 == 1)
+                    })
+            })
+            .collect();
+        let expected = ig.evaluate_many(&assignments);
+        for (lane, expected) in expected.iter().enumerate() {
+            assert_eq!(values.get(wide, lane), expected.get(wide), "{}", lane);
+            assert_eq!(values.get(inverted, lane), 1);
+        }
+        assert_eq!(values.bits(wide).len(), 3);
+        assert_eq!(values.bits(wide)[2], values.bits(wide)[1]);
+
+        // Graphs with loops are evaluated one lane at a time.
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let set = g.lever("set");
+        let reset = g.lever("reset");
+        let q = g.nor2(reset.bit(), OFF, "q");
+        let nq = g.nor2(set.bit(), q, "nq");
+        g.d1(q, nq);
+        let output = g.output1(q, "q");
+
+        let ig = &mut graph.init_unoptimized();
+        ig.pulse_lever_stable(set);
+        let values = ig.tick_vector(&[reset], &[0b0110]);
+        assert_eq!(values.bits(output), [!0b0110]);
+        assert_eq!(output.u8(ig), 1);
+    }
+}
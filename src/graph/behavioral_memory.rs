@@ -0,0 +1,354 @@
+use super::gate::*;
+use super::graph_builder::GateGraphBuilder;
+use super::handles::{LeverHandle, OutputHandle};
+use super::InitializedGateGraph;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Memory declared with [GateGraphBuilder::behavioral_memory].
+#[derive(Debug, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(super) struct MemorySource {
+    address_bits: usize,
+    read: OutputHandle,
+    write: OutputHandle,
+    clock: OutputHandle,
+    address: OutputHandle,
+    input: OutputHandle,
+    /// Levers that hold the word being read.
+    data: Vec<LeverHandle>,
+}
+
+/// Contents of a behavioral memory and the state of its clock at the end of the last tick.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(super) struct MemoryState {
+    words: Vec<u64>,
+    clock: bool,
+}
+
+/// Handle to a memory declared with [GateGraphBuilder::behavioral_memory],
+/// used to access its contents in the [InitializedGateGraph].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MemoryHandle(usize);
+
+/// Output of [GateGraphBuilder::behavioral_memory].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MemoryOutput {
+    /// Handle to access the contents of the memory from the host.
+    pub memory: MemoryHandle,
+    /// Word at the address while read is active, zero otherwise.
+    pub data: Vec<GateIndex>,
+}
+
+/// Behavioral memories of an [InitializedGateGraph].
+#[derive(Debug, Default)]
+pub(super) struct Memories {
+    sources: Vec<MemorySource>,
+    pub(super) states: Vec<MemoryState>,
+}
+impl Memories {
+    pub(super) fn new(sources: Vec<MemorySource>) -> Self {
+        Self {
+            states: sources
+                .iter()
+                .map(|source| MemoryState {
+                    words: vec![0; 1 << source.address_bits],
+                    clock: false,
+                })
+                .collect(),
+            sources,
+        }
+    }
+
+    /// Returns the number of words of every memory.
+    #[cfg(feature = "serde")]
+    pub(super) fn sizes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.states.iter().map(|state| state.words.len())
+    }
+}
+
+impl GateGraphBuilder {
+    /// Returns a new memory of 2^`address.len()` words of `input.len()` bits, whose contents live in a `Vec<u64>`
+    /// in the host instead of in gates.
+    ///
+    /// A [ram](crate::ram) takes dozens of gates per bit, which puts memories of more than a few kilobytes out of
+    /// reach, a behavioral memory takes a lever per bit of its output, so a processor can have megabytes of RAM
+    /// while the rest of it stays gate level.
+    ///
+    /// It works like a [ram](crate::ram) without a reset: if `write` is active at the end of the tick in which
+    /// `clock` rises, `input` is stored at `address`. The data output holds the word at `address` while `read`
+    /// is active and zero otherwise. The accesses happen at the end of every [tick](InitializedGateGraph::tick),
+    /// after the gates, so the output follows the address one tick later, like a [delay](GateGraphBuilder::delay)
+    /// gate, and a write is visible in the same tick.
+    ///
+    /// The contents are zero after initialization, the host can access them with
+    /// [memory_contents](InitializedGateGraph::memory_contents) and [load_memory](InitializedGateGraph::load_memory).
+    /// They are part of the [snapshots](InitializedGateGraph::snapshot) of the graph.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// # use logicsim::WordInput;
+    /// let mut g = GateGraphBuilder::new();
+    /// let clock = g.lever("clock");
+    /// let write = g.lever("write");
+    /// let read = g.lever("read");
+    /// // 8 MiB.
+    /// let address = WordInput::new(&mut g, 20, "address");
+    /// let input = WordInput::new(&mut g, 64, "input");
+    /// let memory = g.behavioral_memory(
+    ///     read.bit(),
+    ///     write.bit(),
+    ///     clock.bit(),
+    ///     &address.bits(),
+    ///     &input.bits(),
+    ///     "memory",
+    /// );
+    /// let output = g.output(&memory.data, "data");
+    ///
+    /// let ig = &mut g.init();
+    /// address.set_to(ig, 0xabcde);
+    /// input.set_to(ig, 42u64);
+    /// ig.set_lever(write);
+    /// ig.pulse_lever_stable(clock);
+    /// ig.reset_lever_stable(write);
+    /// assert_eq!(ig.memory_contents(memory.memory)[0xabcde], 42);
+    ///
+    /// // The host can load the memory too.
+    /// ig.load_memory(memory.memory, 0x10, &[7, 8, 9]);
+    /// ig.set_lever_stable(read);
+    /// address.set_to_stable(ig, 0x11);
+    /// assert_eq!(output.u64(ig), 8);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `input.len()` > 64 or `address.len()` > 32.
+    pub fn behavioral_memory<S: Into<String>>(
+        &mut self,
+        read: GateIndex,
+        write: GateIndex,
+        clock: GateIndex,
+        address: &[GateIndex],
+        input: &[GateIndex],
+        name: S,
+    ) -> MemoryOutput {
+        assert!(
+            input.len() <= 64,
+            "Behavioral memory words can't be wider than 64 bits, width: {}",
+            input.len()
+        );
+        assert!(
+            address.len() <= 32,
+            "Behavioral memories can't have more than 2^32 words, address bits: {}",
+            address.len()
+        );
+        let name = format!("MEMORY:{}", name.into());
+        let data: Vec<LeverHandle> = input.iter().map(|_| self.lever(name.clone())).collect();
+        let source = MemorySource {
+            address_bits: address.len(),
+            read: self.output1(read, format!("{}:read", name)),
+            write: self.output1(write, format!("{}:write", name)),
+            clock: self.output1(clock, format!("{}:clock", name)),
+            address: self.output(address, format!("{}:address", name)),
+            input: self.output(input, format!("{}:input", name)),
+            data: data.clone(),
+        };
+        self.memories.push(source);
+        MemoryOutput {
+            memory: MemoryHandle(self.memories.len() - 1),
+            data: data.iter().map(|lever| lever.bit()).collect(),
+        }
+    }
+
+    /// Updates the data levers of every memory after [compacting](GateGraphBuilder::compact_now) the graph.
+    pub(super) fn remap_memories(&mut self, index_map: &HashMap<GateIndex, GateIndex>) {
+        for memory in &mut self.memories {
+            for lever in &mut memory.data {
+                *lever = lever.translated(index_map);
+            }
+        }
+    }
+
+    /// Returns true if the control, address or input bits of a memory read `gate`.
+    pub(super) fn memory_reads(&self, gate: GateIndex) -> bool {
+        self.memories.iter().any(|memory| {
+            [
+                memory.read,
+                memory.write,
+                memory.clock,
+                memory.address,
+                memory.input,
+            ]
+            .iter()
+            .any(|output| self.output_reads(*output, gate))
+        })
+    }
+}
+
+impl InitializedGateGraph {
+    /// Returns the contents of `memory`, see [GateGraphBuilder::behavioral_memory].
+    pub fn memory_contents(&self, memory: MemoryHandle) -> &[u64] {
+        &self.memories.states[memory.0].words
+    }
+
+    /// Writes `words` into `memory` from address `offset` on, truncated to the width of the memory,
+    /// like a program loader would. If the address being read changes, the output shows it after the next tick.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `words` don't fit in the memory.
+    pub fn load_memory(&mut self, memory: MemoryHandle, offset: usize, words: &[u64]) {
+        let width = self.memories.sources[memory.0].data.len();
+        let mask = u64::MAX.checked_shr(64 - width as u32).unwrap_or(0);
+        let contents = &mut self.memories.states[memory.0].words;
+        assert!(
+            offset + words.len() <= contents.len(),
+            "{} words at {} don't fit in a memory of {} words",
+            words.len(),
+            offset,
+            contents.len()
+        );
+        for (word, value) in contents[offset..].iter_mut().zip(words) {
+            *word = value & mask;
+        }
+        self.read_memory(memory.0);
+    }
+
+    /// Sets the state of every memory clock to the state of its clock gate, so initialization isn't a clock edge.
+    pub(super) fn arm_memories(&mut self) {
+        for i in 0..self.memories.sources.len() {
+            self.memories.states[i].clock = self.memories.sources[i].clock.b0(self);
+        }
+    }
+
+    /// Performs the writes and reads requested by every memory, called at the end of every tick.
+    pub(super) fn update_memories(&mut self) {
+        for i in 0..self.memories.sources.len() {
+            let source = &self.memories.sources[i];
+            let clock = source.clock.b0(self);
+            if clock && !self.memories.states[i].clock && source.write.b0(self) {
+                let address = source.address.u64(self) as usize;
+                let input = source.input.u64(self);
+                self.memories.states[i].words[address] = input;
+            }
+            self.memories.states[i].clock = clock;
+            self.read_memory(i);
+        }
+    }
+
+    /// Sets the data levers of the memory number `i` to the word at its address, or zero if it's not being read.
+    fn read_memory(&mut self, i: usize) {
+        let source = &self.memories.sources[i];
+        let value = if source.read.b0(self) {
+            self.memories.states[i].words[source.address.u64(self) as usize]
+        } else {
+            0
+        };
+        for bit in 0..self.memories.sources[i].data.len() {
+            let lever = self.memories.sources[i].data[bit];
+            self.update_lever_inner(lever, value >> bit & 1 == 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{ram, WordInput};
+
+    #[test]
+    fn test_behavioral_memory_matches_ram() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let clock = g.lever("clock");
+        let write = g.lever("write");
+        let read = g.lever("read");
+        let address = WordInput::new(g, 3, "address");
+        let input = WordInput::new(g, 8, "input");
+        let memory = g.behavioral_memory(
+            read.bit(),
+            write.bit(),
+            clock.bit(),
+            &address.bits(),
+            &input.bits(),
+            "memory",
+        );
+        let gates = ram(
+            g,
+            read.bit(),
+            write.bit(),
+            clock.bit(),
+            OFF,
+            &address.bits(),
+            &input.bits(),
+            "ram",
+        );
+        let behavioral = g.output(&memory.data, "behavioral");
+        let gates = g.output(&gates, "gates");
+        let ig = &mut graph.init();
+
+        for i in 0..8u8 {
+            address.set_to(ig, i);
+            input.set_to(ig, i * 3 + 1);
+            ig.set_lever_stable(write);
+            ig.pulse_lever_stable(clock);
+            ig.reset_lever_stable(write);
+            // Without a clock edge nothing gets written.
+            input.set_to_stable(ig, 0xffu8);
+        }
+        assert_eq!(
+            ig.memory_contents(memory.memory),
+            [1, 4, 7, 10, 13, 16, 19, 22]
+        );
+        for i in 0..8u8 {
+            address.set_to_stable(ig, i);
+            assert_eq!(behavioral.u8(ig), 0);
+            ig.set_lever_stable(read);
+            assert_eq!(behavioral.u8(ig), gates.u8(ig));
+            assert_eq!(behavioral.u8(ig), i * 3 + 1);
+            ig.reset_lever_stable(read);
+        }
+
+        // Like a delay gate, the data levers change at the end of the tick in which the address changes.
+        ig.set_lever_stable(read);
+        address.set_to(ig, 2u8);
+        assert_eq!(behavioral.u8(ig), 7);
+    }
+
+    #[test]
+    fn test_behavioral_memory_snapshot() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let clock = g.lever("clock");
+        let address = WordInput::new(g, 4, "address");
+        let input = WordInput::new(g, 4, "input");
+        let memory = g.behavioral_memory(ON, ON, clock.bit(), &address.bits(), &input.bits(), "m");
+        let output = g.output(&memory.data, "data");
+        let ig = &mut graph.init();
+
+        ig.load_memory(memory.memory, 14, &[0xf3, 0xff]);
+        assert_eq!(ig.memory_contents(memory.memory)[14..], [3, 15]);
+        let snapshot = ig.snapshot();
+        address.set_to(ig, 14u8);
+        input.set_to(ig, 9u8);
+        ig.pulse_lever_stable(clock);
+        assert_eq!(output.u8(ig), 9);
+
+        ig.restore(&snapshot);
+        assert_eq!(ig.memory_contents(memory.memory)[14], 3);
+        address.set_to_stable(ig, 15u8);
+        assert_eq!(output.u8(ig), 15);
+    }
+
+    #[test]
+    #[should_panic(expected = "don't fit")]
+    fn test_load_memory_out_of_bounds() {
+        let mut g = GateGraphBuilder::new();
+        let address = WordInput::new(&mut g, 2, "address");
+        let memory = g.behavioral_memory(ON, OFF, OFF, &address.bits(), &[OFF], "m");
+        g.init().load_memory(memory.memory, 3, &[1, 1]);
+    }
+}
@@ -0,0 +1,124 @@
+use super::gate::*;
+use super::graph_builder::GateGraphBuilder;
+use std::mem::size_of;
+
+impl GateGraphBuilder {
+    /// Makes every gate constructor panic once the graph has `limit` gates, including [OFF] and [ON].
+    ///
+    /// Generative code with a bug can create gates until the process runs out of memory and gets killed without
+    /// any diagnostics, with a limit it panics instead, pointing at the code that created the gate and
+    /// naming the [module](GateGraphBuilder::begin_module) it was created in.
+    /// The limit only applies to new gates, compacting and initializing the graph ignore it.
+    ///
+    /// # Example
+    /// ```should_panic
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// let mut g = GateGraphBuilder::new();
+    /// g.set_gate_limit(1000);
+    /// let l = g.lever("l");
+    /// let mut last = l.bit();
+    /// // Oops, infinite loop.
+    /// loop {
+    ///     last = g.not1(last, "not");
+    /// }
+    /// ```
+    pub fn set_gate_limit(&mut self, limit: usize) {
+        self.gate_limit = Some(limit);
+    }
+
+    /// Removes the limit set with [set_gate_limit](GateGraphBuilder::set_gate_limit).
+    pub fn clear_gate_limit(&mut self) {
+        self.gate_limit = None;
+    }
+
+    /// Returns the limit set with [set_gate_limit](GateGraphBuilder::set_gate_limit), if any.
+    pub fn gate_limit(&self) -> Option<usize> {
+        self.gate_limit
+    }
+
+    /// Returns an estimate of the memory used by the graph in bytes: the gates, their dependencies and
+    /// dependents, and their names with the "debug_gates" feature.
+    ///
+    /// It's based on the sizes of the containers, not on what the allocator actually reserved,
+    /// so it's meant to spot growth, not to account for every byte.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// let mut g = GateGraphBuilder::new();
+    /// let empty = g.memory_usage();
+    /// let l = g.lever("l");
+    /// for _ in 0..1000 {
+    ///     g.not1(l.bit(), "not");
+    /// }
+    /// assert!(g.memory_usage() > empty + 1000 * 32);
+    /// ```
+    pub fn memory_usage(&self) -> usize {
+        // Entries of the dependents sets also store their hash and a slot in the hash table.
+        let dependent_size = size_of::<GateIndex>() + 2 * size_of::<usize>();
+        let mut bytes = size_of::<Self>() + self.nodes.capacity() * size_of::<Option<BuildGate>>();
+        for (_, gate) in self.nodes.iter() {
+            if gate.dependencies.spilled() {
+                bytes += gate.dependencies.capacity() * size_of::<GateIndex>();
+            }
+            bytes += gate.dependents.capacity() * dependent_size;
+        }
+        #[cfg(feature = "debug_gates")]
+        {
+            bytes +=
+                self.names.capacity() * (size_of::<(GateIndex, String)>() + size_of::<usize>());
+            bytes += self.names.values().map(String::capacity).sum::<usize>();
+        }
+        bytes
+    }
+
+    /// Panics if creating a new gate would exceed the [gate limit](GateGraphBuilder::set_gate_limit).
+    #[track_caller]
+    pub(super) fn check_gate_limit(&self) {
+        if let Some(limit) = self.gate_limit {
+            if self.nodes.len() >= limit {
+                panic!(
+                    "Gate limit of {} gates exceeded in module \"{}\", the graph uses about {} MiB",
+                    limit,
+                    self.module_path(),
+                    self.memory_usage() >> 20
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+
+    #[test]
+    fn test_gate_limit() {
+        let mut g = GateGraphBuilder::new();
+        g.set_gate_limit(5);
+        assert_eq!(g.gate_limit(), Some(5));
+        let l = g.lever("l");
+        let not = g.not1(l.bit(), "not");
+        g.and2(l.bit(), not, "and");
+        let result = std::panic::catch_unwind(move || {
+            g.begin_module("runaway");
+            g.or2(l.bit(), not, "or");
+        });
+        let message = *result.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.starts_with("Gate limit of 5 gates exceeded in module \"runaway\""));
+
+        let mut g = GateGraphBuilder::new();
+        g.set_gate_limit(3);
+        g.lever("l");
+        g.clear_gate_limit();
+        let l2 = g.lever("l2");
+        assert_eq!(g.len(), 4);
+        // A limit below the current size only stops new gates.
+        g.set_gate_limit(2);
+        g.compact_now();
+        let output = g.output1(l2.bit(), "l2");
+        let ig = &mut g.init();
+        ig.set_lever_stable(l2);
+        assert_eq!(output.b0(ig), true);
+    }
+}
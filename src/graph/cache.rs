@@ -0,0 +1,261 @@
+use super::graph_builder::{CompactedGateGraph, GateGraphBuilder};
+use super::InitializedGateGraph;
+use super::*;
+use crate::data_structures::Fnv64;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+impl GateGraphBuilder {
+    /// Returns a new [InitializedGateGraph] created from `self` after running optimizations,
+    /// reusing the optimized graph stored in `path` if it was created from an identical [GateGraphBuilder].
+    ///
+    /// If there is no cache in `path` or it was created from a different circuit, `self` gets optimized
+    /// as in [GateGraphBuilder::init] and the result gets stored in `path` for next time.
+    /// Failing to read or write the cache is not an error, it gets logged to the "logicsim::init" target
+    /// and the graph gets initialized normally.
+    ///
+    /// Optimizing big circuits like the computer example takes a long time, with this only the first run
+    /// after changing the circuit pays for it.
+    ///
+    /// Only available with the "serde" feature.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,ON};
+    /// # let path = std::env::temp_dir().join("logicsim_init_cached_doctest");
+    /// # let _ = std::fs::remove_file(&path);
+    /// let mut g = GateGraphBuilder::new();
+    /// let l = g.lever("l");
+    /// let and = g.and2(l.bit(), ON, "and");
+    /// let output = g.output1(and, "and");
+    ///
+    /// // The first run optimizes the graph and stores it.
+    /// let ig = &mut g.clone().init_cached(&path);
+    /// assert!(path.exists());
+    ///
+    /// // The second one just loads it.
+    /// let ig = &mut g.init_cached(&path);
+    /// ig.set_lever_stable(l);
+    /// assert_eq!(output.b0(ig), true);
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn init_cached<P: AsRef<Path>>(mut self, path: P) -> InitializedGateGraph {
+        let span = tracing::info_span!(target: "logicsim::init", "init_cached", gates = self.len());
+        let _enter = span.enter();
+
+        let path = path.as_ref();
+        let hash = self.content_hash();
+        let progress = &mut |_, _, _| {};
+        let warnings = self.find_init_warnings();
+
+        match read_cache(path, hash) {
+            Ok(Some(mut compacted)) => {
+                tracing::debug!(
+                    target: "logicsim::init",
+                    "Loaded optimized graph from {}",
+                    path.display()
+                );
+                compacted.custom_gates = self.custom_gates;
+                return compacted.initialize(warnings, progress);
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!(
+                target: "logicsim::init",
+                "Ignoring invalid cache {}: {}",
+                path.display(),
+                e
+            ),
+        }
+
+        self.optimize(progress);
+        let compacted = self.compacted(progress);
+        if let Err(e) = write_cache(path, hash, &compacted) {
+            tracing::warn!(
+                target: "logicsim::init",
+                "Failed to write cache {}: {}",
+                path.display(),
+                e
+            );
+        }
+        compacted.initialize(warnings, progress)
+    }
+
+    /// Returns a hash of everything in `self` that affects the result of [GateGraphBuilder::init].
+    ///
+    /// It also includes the version of logicsim so that changes to the optimizations invalidate caches.
+    /// Custom gates are hashed by their truth table, changing what one computes invalidates caches too.
+    pub(super) fn content_hash(&self) -> u64 {
+        let mut hasher = Fnv64::default();
+        env!("CARGO_PKG_VERSION").hash(&mut hasher);
+        cfg!(feature = "debug_gates").hash(&mut hasher);
+
+        for (idx, gate) in self.nodes.iter() {
+            GateIndex::from(idx).hash(&mut hasher);
+            gate.ty.hash(&mut hasher);
+            gate.dependencies.hash(&mut hasher);
+            for dependent in &gate.dependents {
+                dependent.hash(&mut hasher);
+            }
+        }
+        self.lever_handles.hash(&mut hasher);
+        for custom in &self.custom_gates {
+            custom.name.hash(&mut hasher);
+            custom.arity.hash(&mut hasher);
+            let mut values = vec![false; custom.arity];
+            for combination in 0..1usize << custom.arity {
+                for (i, value) in values.iter_mut().enumerate() {
+                    *value = combination >> i & 1 == 1;
+                }
+                (custom.eval)(&values).hash(&mut hasher);
+            }
+        }
+        for output in &self.output_handles {
+            output.name.hash(&mut hasher);
+            output.bits.hash(&mut hasher);
+        }
+        // Sorted so the key doesn't depend on the order things were added in.
+        let mut outputs: Vec<_> = self.outputs.iter().collect();
+        outputs.sort_unstable();
+        outputs.hash(&mut hasher);
+        let mut test_points: Vec<_> = self.test_points.iter().collect();
+        test_points.sort_unstable_by_key(|(name, _)| *name);
+        test_points.hash(&mut hasher);
+        let mut kept: Vec<_> = self.kept.iter().collect();
+        kept.sort_unstable();
+        kept.hash(&mut hasher);
+        let mut gate_delays: Vec<_> = self.gate_delays.iter().collect();
+        gate_delays.sort_unstable();
+        gate_delays.hash(&mut hasher);
+        self.event_sources.hash(&mut hasher);
+        self.clocks.hash(&mut hasher);
+        self.assertions.hash(&mut hasher);
+        self.memories.hash(&mut hasher);
+
+        #[cfg(feature = "debug_gates")]
+        {
+            self.checked_wires.hash(&mut hasher);
+
+            let mut names: Vec<_> = self.names.iter().collect();
+            names.sort_unstable();
+            names.hash(&mut hasher);
+
+            let mut probes: Vec<_> = self
+                .probes
+                .iter()
+                .map(|(idx, probe)| (idx, &probe.name, &probe.bits))
+                .collect();
+            probes.sort_unstable();
+            probes.hash(&mut hasher);
+        }
+
+        hasher.finish()
+    }
+}
+
+/// Returns the [CompactedGateGraph] stored in `path` if it was stored with the same `hash`.
+fn read_cache(path: &Path, hash: u64) -> bincode::Result<Option<CompactedGateGraph>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut reader = BufReader::new(file);
+    let cached_hash: u64 = bincode::deserialize_from(&mut reader)?;
+    if cached_hash != hash {
+        tracing::debug!(
+            target: "logicsim::init",
+            "The circuit changed since {} was written",
+            path.display()
+        );
+        return Ok(None);
+    }
+    Ok(Some(bincode::deserialize_from(&mut reader)?))
+}
+
+/// Stores `graph` in `path` along with `hash`.
+fn write_cache(path: &Path, hash: u64, graph: &CompactedGateGraph) -> bincode::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    bincode::serialize_into(&mut writer, &hash)?;
+    bincode::serialize_into(&mut writer, graph)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuits::{counter, zeros};
+
+    fn counter_graph() -> (GateGraphBuilder, LeverHandle, OutputHandle) {
+        let mut g = GateGraphBuilder::new();
+        let clock = g.lever("clock");
+        let count = counter(&mut g, clock.bit(), ON, OFF, ON, OFF, &zeros(4), "counter");
+        let output = g.output(&count, "count");
+        (g, clock, output)
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let (g1, _, _) = counter_graph();
+        let (mut g2, _, _) = counter_graph();
+        assert_eq!(g1.content_hash(), g2.content_hash());
+
+        g2.not1(ON, "extra");
+        assert_ne!(g1.content_hash(), g2.content_hash());
+    }
+
+    #[test]
+    fn test_content_hash_custom_gates() {
+        let graph = |eval: fn(&[bool]) -> bool| {
+            let mut g = GateGraphBuilder::new();
+            let a = g.lever("a");
+            let b = g.lever("b");
+            let ty = g.register_gate("CUSTOM", 2, eval);
+            let custom = g.custom(ty, &[a.bit(), b.bit()], "custom");
+            g.output1(custom, "custom");
+            g
+        };
+        let and = graph(|inputs| inputs[0] && inputs[1]);
+        assert_eq!(
+            and.content_hash(),
+            graph(|inputs| inputs.iter().all(|input| *input)).content_hash()
+        );
+        assert_ne!(
+            and.content_hash(),
+            graph(|inputs| inputs[0] || inputs[1]).content_hash()
+        );
+    }
+
+    #[test]
+    fn test_init_cached() {
+        let path = std::env::temp_dir().join("logicsim_init_cached_test");
+        let _ = std::fs::remove_file(&path);
+
+        let run = |ig: &mut InitializedGateGraph, clock: LeverHandle, output: OutputHandle| {
+            (0..20)
+                .map(|_| {
+                    ig.pulse_lever_stable(clock);
+                    output.u8(ig)
+                })
+                .collect::<Vec<_>>()
+        };
+        let (g, clock, output) = counter_graph();
+        let expected = run(&mut g.init(), clock, output);
+
+        // Miss, hit and a cache for a different circuit must all behave like init.
+        for i in 0..3 {
+            let (mut g, clock, output) = counter_graph();
+            if i == 2 {
+                g.not1(ON, "extra");
+            }
+            assert_eq!(run(&mut g.init_cached(&path), clock, output), expected);
+        }
+
+        std::fs::write(&path, b"definitely not a graph").unwrap();
+        let (g, clock, output) = counter_graph();
+        assert_eq!(run(&mut g.init_cached(&path), clock, output), expected);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+use super::gate::GateIndex;
+use super::graph_builder::GateGraphBuilder;
+use super::handles::LeverHandle;
+use super::InitializedGateGraph;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Clock declared with [GateGraphBuilder::clock].
+#[derive(Debug, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(super) struct Clock {
+    lever: LeverHandle,
+    period: usize,
+    phase: usize,
+}
+impl Clock {
+    /// Returns the value of the clock at simulation time `ticks`.
+    fn value(&self, ticks: usize) -> bool {
+        let position = (ticks + self.period - self.phase) % self.period;
+        position >= self.period - self.period / 2
+    }
+}
+
+impl GateGraphBuilder {
+    /// Returns the [LeverHandle] of a new lever driven by a clock with a period of `period` ticks,
+    /// see [InitializedGateGraph::step_clocks].
+    ///
+    /// The clock is low during the first half of every period and high during the second half,
+    /// the extra tick of odd periods goes to the low half.
+    /// Clocks with different periods can be used to simulate circuits with several clock domains.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `period` < 2.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// let mut g = GateGraphBuilder::new();
+    /// let fast = g.clock(2, "fast");
+    /// let slow = g.clock(4, "slow");
+    /// let fast_output = g.output1(fast.bit(), "fast");
+    /// let slow_output = g.output1(slow.bit(), "slow");
+    ///
+    /// let ig = &mut g.init();
+    /// let mut waves = Vec::new();
+    /// for _ in 0..4 {
+    ///     ig.step_clocks(1);
+    ///     waves.push((fast_output.b0(ig), slow_output.b0(ig)));
+    /// }
+    /// assert_eq!(waves, [(false, false), (true, false), (false, true), (true, true)]);
+    /// ```
+    pub fn clock<S: Into<String>>(&mut self, period: usize, name: S) -> LeverHandle {
+        self.clock_with_phase(period, 0, name)
+    }
+
+    /// Returns the [LeverHandle] of a new lever driven by a clock with a period of `period` ticks,
+    /// delayed by `phase` ticks, see [GateGraphBuilder::clock].
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `period` < 2.
+    pub fn clock_with_phase<S: Into<String>>(
+        &mut self,
+        period: usize,
+        phase: usize,
+        name: S,
+    ) -> LeverHandle {
+        assert!(period >= 2, "Clocks need a period of at least 2 ticks");
+        let lever = self.lever(name);
+        self.clocks.push(Clock {
+            lever,
+            period,
+            phase: phase % period,
+        });
+        lever
+    }
+
+    /// Updates the lever of every clock after [compacting](GateGraphBuilder::compact_now) the graph.
+    pub(super) fn remap_clocks(&mut self, index_map: &HashMap<GateIndex, GateIndex>) {
+        for clock in &mut self.clocks {
+            clock.lever = clock.lever.translated(index_map);
+        }
+    }
+}
+
+impl InitializedGateGraph {
+    /// Runs `n` ticks, setting every lever declared with [GateGraphBuilder::clock] to the value of its clock
+    /// at the current [simulation time](InitializedGateGraph::now) before each one.
+    ///
+    /// Clocks follow the simulation time, so other ways of ticking the graph also advance them,
+    /// but their levers only get updated by this method.
+    pub fn step_clocks(&mut self, n: usize) {
+        for _ in 0..n {
+            for i in 0..self.clocks.len() {
+                let clock = &self.clocks[i];
+                let (lever, value) = (clock.lever, clock.value(self.ticks));
+                self.update_lever_inner(lever, value);
+            }
+            self.tick();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{counter, zeros};
+
+    #[test]
+    fn test_clock_waveform() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let odd = g.clock(3, "odd");
+        let shifted = g.clock_with_phase(4, 5, "shifted");
+        let odd = g.output1(odd.bit(), "odd");
+        let shifted = g.output1(shifted.bit(), "shifted");
+        let ig = &mut graph.init();
+
+        let mut odd_wave = String::new();
+        let mut shifted_wave = String::new();
+        for _ in 0..8 {
+            ig.step_clocks(1);
+            odd_wave.push(if odd.b0(ig) { '1' } else { '0' });
+            shifted_wave.push(if shifted.b0(ig) { '1' } else { '0' });
+        }
+        assert_eq!(odd_wave, "00100100");
+        assert_eq!(shifted_wave, "10011001");
+    }
+
+    #[test]
+    fn test_clock_domains() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let fast = g.clock(40, "fast");
+        let slow = g.clock(60, "slow");
+        let reset = g.lever("reset");
+        let fast_count = counter(g, fast.bit(), ON, OFF, ON, reset.bit(), &zeros(4), "fast");
+        let slow_count = counter(g, slow.bit(), ON, OFF, ON, reset.bit(), &zeros(4), "slow");
+        let fast_count = g.output(&fast_count, "fast_count");
+        let slow_count = g.output(&slow_count, "slow_count");
+        let ig = &mut graph.init();
+
+        ig.pulse_lever_stable(reset);
+        // Start at the beginning of both periods.
+        let start = ig.now();
+        ig.step_clocks(120 - start % 120);
+        let (fast_start, slow_start) = (fast_count.u8(ig), slow_count.u8(ig));
+
+        ig.step_clocks(240);
+        assert_eq!(fast_count.u8(ig).wrapping_sub(fast_start) % 16, 6);
+        assert_eq!(slow_count.u8(ig).wrapping_sub(slow_start) % 16, 4);
+    }
+}
@@ -0,0 +1,384 @@
+use super::gate::*;
+use super::handles::*;
+use super::InitializedGateGraph;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+
+/// Variables of the formula written by [InitializedGateGraph::export_cnf].
+///
+/// Variables are numbered from 1 like in DIMACS, the same number is used in the formula.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CnfMap {
+    /// Every lever in the fanin cone of the output along with its variable.
+    pub levers: Vec<(LeverHandle, usize)>,
+    /// Variable of every bit of the output, bit 0 first.
+    pub outputs: Vec<usize>,
+    /// Number of variables in the formula, including the auxiliary ones.
+    pub variables: usize,
+    /// Number of clauses in the formula.
+    pub clauses: usize,
+}
+
+/// Tseitin encoding of fanin cones, see [InitializedGateGraph::export_cnf].
+#[derive(Debug, Default)]
+pub(super) struct Cnf {
+    /// Variable of every gate encoded so far.
+    pub(super) variables: HashMap<GateIndex, i64>,
+    /// Last variable used, variables start at 1.
+    pub(super) next_variable: i64,
+    pub(super) clauses: Vec<Vec<i64>>,
+    /// Gates whose clauses have been added.
+    encoded: HashSet<GateIndex>,
+}
+impl Cnf {
+    /// Returns the variable of `gate` and whether it's new.
+    pub(super) fn variable(&mut self, gate: GateIndex) -> (i64, bool) {
+        if let Some(variable) = self.variables.get(&gate) {
+            return (*variable, false);
+        }
+        let variable = self.auxiliary();
+        self.variables.insert(gate, variable);
+        (variable, true)
+    }
+
+    /// Returns a new variable that doesn't correspond to any gate.
+    pub(super) fn auxiliary(&mut self) -> i64 {
+        self.next_variable += 1;
+        self.next_variable
+    }
+
+    /// Adds the clauses of `a` == `b`.
+    fn equal(&mut self, a: i64, b: i64) {
+        self.clauses.push(vec![-a, b]);
+        self.clauses.push(vec![a, -b]);
+    }
+
+    /// Adds the clauses of `output` == `a` xor `b`.
+    pub(super) fn xor(&mut self, output: i64, a: i64, b: i64) {
+        self.clauses.push(vec![-output, a, b]);
+        self.clauses.push(vec![-output, -a, -b]);
+        self.clauses.push(vec![output, -a, b]);
+        self.clauses.push(vec![output, a, -b]);
+    }
+
+    /// Adds the clauses of `output` == and(`inputs`).
+    fn and(&mut self, output: i64, inputs: &[i64]) {
+        for input in inputs {
+            self.clauses.push(vec![-output, *input]);
+        }
+        let mut clause: Vec<i64> = inputs.iter().map(|input| -input).collect();
+        clause.push(output);
+        self.clauses.push(clause);
+    }
+}
+
+impl InitializedGateGraph {
+    /// Writes the fanin cone of `output` to `path` as a [DIMACS CNF](https://people.sc.fsu.edu/~jburkardt/data/cnf/cnf.html)
+    /// formula, built with the [Tseitin transformation](https://en.wikipedia.org/wiki/Tseytin_transformation),
+    /// so it can be fed to off the shelf SAT solvers for property checking or test generation.
+    ///
+    /// Every gate in the cone gets a variable, and a few auxiliary variables are added for xor gates with
+    /// more than 2 dependencies. Levers are free variables, the returned [CnfMap] and the comments at the start
+    /// of the file say which variable belongs to each lever and output bit.
+    /// To check a property, add clauses on the output variables and look for a solution, for example
+    /// a unit clause with the negated variable of an output that should always be true.
+    ///
+    /// Loops are encoded as constraints like any other gate, so in circuits with state the solutions are the
+    /// stable states of the circuit, a latch that can hold either value will be free to take either.
+    /// Delay gates are encoded as buffers for the same reason.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// let mut g = GateGraphBuilder::new();
+    /// let a = g.lever("a");
+    /// let b = g.lever("b");
+    /// let and = g.and2(a.bit(), b.bit(), "and");
+    /// let output = g.output1(and, "and");
+    ///
+    /// let ig = &mut g.init();
+    /// let path = std::env::temp_dir().join("logicsim_export_cnf_doctest.cnf");
+    /// let map = ig.export_cnf(output, &path).unwrap();
+    /// assert_eq!(map.levers.len(), 2);
+    /// assert_eq!(map.outputs.len(), 1);
+    ///
+    /// let cnf = std::fs::read_to_string(&path).unwrap();
+    /// assert!(cnf.contains(&format!("p cnf {} {}", map.variables, map.clauses)));
+    /// # std::fs::remove_file(path).unwrap();
+    /// ```
+    pub fn export_cnf<P: AsRef<Path>>(
+        &self,
+        output: OutputHandle,
+        path: P,
+    ) -> std::io::Result<CnfMap> {
+        let mut f = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let map = self.write_cnf(output, &mut f)?;
+        f.flush()?;
+        Ok(map)
+    }
+
+    /// Writes the fanin cone of `output` to `writer` as a DIMACS CNF formula, see [InitializedGateGraph::export_cnf].
+    pub fn write_cnf<W: Write>(
+        &self,
+        output: OutputHandle,
+        writer: &mut W,
+    ) -> std::io::Result<CnfMap> {
+        let mut cnf = Cnf::default();
+        let bits = &self.get_output(output).bits;
+        let outputs: Vec<usize> = bits
+            .iter()
+            .map(|bit| cnf.variable(*bit).0 as usize)
+            .collect();
+        self.encode_cnf(&mut cnf, bits);
+
+        let levers: Vec<(LeverHandle, usize)> = self
+            .cnf_levers(&cnf)
+            .into_iter()
+            .map(|(lever, variable)| (lever, variable as usize))
+            .collect();
+
+        writeln!(writer, "c logicsim output {}", self.get_output(output).name)?;
+        for (lever, variable) in &levers {
+            writeln!(
+                writer,
+                "c lever {} {} {}",
+                lever.handle,
+                variable,
+                self.full_name(lever.idx)
+            )?;
+        }
+        for (bit, variable) in outputs.iter().enumerate() {
+            writeln!(writer, "c output {} {}", bit, variable)?;
+        }
+        writeln!(writer, "p cnf {} {}", cnf.next_variable, cnf.clauses.len())?;
+        for clause in &cnf.clauses {
+            for literal in clause {
+                write!(writer, "{} ", literal)?;
+            }
+            writeln!(writer, "0")?;
+        }
+
+        Ok(CnfMap {
+            levers,
+            outputs,
+            variables: cnf.next_variable as usize,
+            clauses: cnf.clauses.len(),
+        })
+    }
+    /// Adds the clauses of the fanin cones of `roots` to `cnf`, gates that are already encoded are skipped so
+    /// cones can share variables.
+    pub(super) fn encode_cnf(&self, cnf: &mut Cnf, roots: &[GateIndex]) {
+        let mut pending: Vec<GateIndex> = roots.to_vec();
+        while let Some(gate) = pending.pop() {
+            if !cnf.encoded.insert(gate) {
+                continue;
+            }
+            let node = &self.nodes[gate.idx];
+            let variable = cnf.variable(gate).0;
+            let mut inputs = Vec::with_capacity(node.dependencies.len());
+            for dependency in &node.dependencies {
+                let (input, _) = cnf.variable(*dependency);
+                if !cnf.encoded.contains(dependency) {
+                    pending.push(*dependency);
+                }
+                inputs.push(input);
+            }
+
+            // Negated gates are encoded as their positive version driving the negated variable.
+            let literal = if node.ty.is_negated() {
+                -variable
+            } else {
+                variable
+            };
+            match node.ty {
+                GateType::Lever => {}
+                GateType::On => cnf.clauses.push(vec![variable]),
+                GateType::Off => cnf.clauses.push(vec![-variable]),
+                GateType::Not => cnf.equal(variable, -inputs[0]),
+                GateType::Delay => cnf.equal(variable, inputs[0]),
+                GateType::TriState => cnf.and(variable, &inputs),
+                GateType::Custom(id) => {
+                    let eval = self.custom_gates[id as usize].eval;
+                    let mut values = vec![false; inputs.len()];
+                    for combination in 0..1usize << inputs.len() {
+                        for (i, value) in values.iter_mut().enumerate() {
+                            *value = combination >> i & 1 == 1;
+                        }
+                        // inputs == combination => variable == eval(combination).
+                        let mut clause: Vec<i64> = inputs
+                            .iter()
+                            .zip(&values)
+                            .map(|(input, value)| if *value { -input } else { *input })
+                            .collect();
+                        clause.push(if eval(&values) { variable } else { -variable });
+                        cnf.clauses.push(clause);
+                    }
+                }
+                _ if inputs.is_empty() => {
+                    let state = node.ty.init() != node.ty.is_negated();
+                    cnf.clauses
+                        .push(vec![if state { variable } else { -variable }]);
+                }
+                GateType::And | GateType::Nand => cnf.and(literal, &inputs),
+                GateType::Or | GateType::Nor => {
+                    // De Morgan, or(inputs) == !and(!inputs).
+                    let negated: Vec<i64> = inputs.iter().map(|input| -input).collect();
+                    cnf.and(-literal, &negated)
+                }
+                GateType::Xor | GateType::Xnor => {
+                    let mut acc = inputs[0];
+                    for (i, input) in inputs.iter().enumerate().skip(1) {
+                        let next = if i == inputs.len() - 1 {
+                            literal
+                        } else {
+                            cnf.auxiliary()
+                        };
+                        cnf.xor(next, acc, *input);
+                        acc = next;
+                    }
+                    if inputs.len() == 1 {
+                        cnf.equal(literal, acc);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns every lever encoded in `cnf` along with its variable, in creation order.
+    pub(super) fn cnf_levers(&self, cnf: &Cnf) -> Vec<(LeverHandle, i64)> {
+        self.lever_handles
+            .iter()
+            .enumerate()
+            .filter_map(|(handle, idx)| {
+                cnf.variables
+                    .get(idx)
+                    .map(|variable| (LeverHandle { handle, idx: *idx }, *variable))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{adder, WordInput};
+
+    /// Returns every solution of the DIMACS formula in `cnf` by brute force.
+    fn solutions(cnf: &str) -> Vec<Vec<bool>> {
+        let mut variables = 0;
+        let mut clauses: Vec<Vec<i64>> = Vec::new();
+        for line in cnf.lines() {
+            if line.starts_with('c') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix("p cnf ") {
+                variables = header.split(' ').next().unwrap().parse().unwrap();
+                continue;
+            }
+            let clause: Vec<i64> = line.split(' ').map(|l| l.parse().unwrap()).collect();
+            assert_eq!(clause.last(), Some(&0));
+            clauses.push(clause[..clause.len() - 1].to_vec());
+        }
+        assert!(variables <= 20, "Too many variables to brute force");
+
+        (0..1u32 << variables)
+            .map(|assignment| {
+                // Index 0 is unused, variables start at 1.
+                (0..=variables)
+                    .map(|v| v > 0 && assignment >> (v - 1) & 1 == 1)
+                    .collect::<Vec<bool>>()
+            })
+            .filter(|values| {
+                clauses.iter().all(|clause| {
+                    clause
+                        .iter()
+                        .any(|l| values[l.unsigned_abs() as usize] == (*l > 0))
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_cnf_matches_truth_table() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let a = WordInput::new(g, 2, "a");
+        let b = WordInput::new(g, 2, "b");
+        let c = g.lever("c");
+        let sum = adder(g, c.bit(), &a.bits(), &b.bits(), "adder");
+        let xor = g.xor("xor3");
+        for dependency in [a.bits()[0], b.bits()[1], c.bit()] {
+            g.dpush(xor, dependency);
+        }
+        let nor = g.nor2(xor, sum[0], "nor");
+        let output = g.output(&[sum[0], sum[1], nor], "output");
+        let levers = [
+            a.levers()[0],
+            a.levers()[1],
+            b.levers()[0],
+            b.levers()[1],
+            c,
+        ];
+        let ig = &mut graph.init_unoptimized();
+
+        let mut cnf = Vec::new();
+        let map = ig.write_cnf(output, &mut cnf).unwrap();
+        let cnf = String::from_utf8(cnf).unwrap();
+        assert_eq!(map.levers.len(), levers.len());
+
+        let mut from_cnf: Vec<(u64, u64)> = solutions(&cnf)
+            .into_iter()
+            .map(|values| {
+                let input = levers.iter().enumerate().fold(0, |acc, (i, lever)| {
+                    let (_, variable) = map.levers.iter().find(|(l, _)| l == lever).unwrap();
+                    acc | (values[*variable] as u64) << i
+                });
+                let output = map
+                    .outputs
+                    .iter()
+                    .enumerate()
+                    .fold(0, |acc, (i, variable)| {
+                        acc | (values[*variable] as u64) << i
+                    });
+                (input, output)
+            })
+            .collect();
+        from_cnf.sort_unstable();
+        // Exactly one solution per input, the one the circuit computes.
+        assert_eq!(from_cnf, ig.truth_table(output, &levers));
+    }
+
+    #[test]
+    fn test_cnf_latch_has_both_states() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let set = g.lever("set");
+        let reset = g.lever("reset");
+        let q = g.nor2(reset.bit(), OFF, "q");
+        let nq = g.nor2(set.bit(), q, "nq");
+        g.d1(q, nq);
+        let output = g.output1(q, "q");
+        let ig = &mut graph.init_unoptimized();
+
+        let mut cnf = Vec::new();
+        let map = ig.write_cnf(output, &mut cnf).unwrap();
+        let solutions = solutions(&String::from_utf8(cnf).unwrap());
+        let q = map.outputs[0];
+        let lever = |lever: LeverHandle| map.levers.iter().find(|(l, _)| *l == lever).unwrap().1;
+        let (set, reset) = (lever(set), lever(reset));
+
+        let states = |s: bool, r: bool| {
+            let mut states: Vec<bool> = solutions
+                .iter()
+                .filter(|v| v[set] == s && v[reset] == r)
+                .map(|v| v[q])
+                .collect();
+            states.sort_unstable();
+            states
+        };
+        assert_eq!(states(false, false), [false, true]);
+        assert_eq!(states(true, false), [true]);
+        assert_eq!(states(false, true), [false]);
+    }
+}
@@ -0,0 +1,230 @@
+use super::gate::*;
+use super::InitializedGateGraph;
+
+/// Two or more [tri-state buffers](super::GateGraphBuilder::tristate) driving different values into the same gate
+/// during the same tick, see [InitializedGateGraph::contentions].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BusContention {
+    /// Full name of the gate the buffers are connected to, usually a bit of a [Bus](crate::Bus).
+    pub gate: String,
+    /// Number of buffers that were enabled.
+    pub drivers: usize,
+    /// [Simulation time](InitializedGateGraph::now) of the tick in which the contention started.
+    pub tick: usize,
+}
+
+/// Gate with more than one tri-state buffer as a dependency.
+#[derive(Debug, Clone)]
+struct Bus {
+    gate: GateIndex,
+    drivers: Vec<GateIndex>,
+}
+
+/// Contentions detected by an [InitializedGateGraph].
+#[derive(Debug, Default)]
+pub(super) struct ContentionMonitor {
+    buses: Vec<Bus>,
+    /// Whether every bus was in contention at the end of the last tick.
+    contended: Vec<bool>,
+    contentions: Vec<BusContention>,
+    panic: bool,
+}
+impl ContentionMonitor {
+    /// Finds every gate in `nodes` driven by more than one tri-state buffer.
+    pub(super) fn new(nodes: &[InitializedGate]) -> Self {
+        let buses: Vec<Bus> = nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, node)| {
+                let drivers: Vec<GateIndex> = node
+                    .dependencies
+                    .iter()
+                    .copied()
+                    .filter(|dep| nodes[dep.idx].ty.is_tristate())
+                    .collect();
+                if drivers.len() > 1 {
+                    Some(Bus {
+                        gate: gi!(idx),
+                        drivers,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect();
+        Self {
+            contended: vec![false; buses.len()],
+            buses,
+            contentions: Vec::new(),
+            panic: false,
+        }
+    }
+}
+
+impl InitializedGateGraph {
+    /// Returns the bus contentions that have happened since the graph was initialized
+    /// or since the last call to [take_contentions](InitializedGateGraph::take_contentions).
+    ///
+    /// A gate is in contention when two of its [tri-state](super::GateGraphBuilder::tristate) dependencies
+    /// are enabled and driving different values at the end of a tick, which in real hardware would short
+    /// the bus. A contention is recorded once when it starts, not on every tick it lasts.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// # use logicsim::{Bus,ON,OFF};
+    /// let mut g = GateGraphBuilder::new();
+    /// let enable_a = g.lever("enable_a");
+    /// let enable_b = g.lever("enable_b");
+    /// let bus = Bus::new(&mut g, 1, "bus");
+    /// bus.connect_tristate(&mut g, enable_a.bit(), &[ON]);
+    /// bus.connect_tristate(&mut g, enable_b.bit(), &[OFF]);
+    /// g.output(bus.bits(), "bus");
+    ///
+    /// let ig = &mut g.init();
+    /// ig.set_lever_stable(enable_a);
+    /// assert!(ig.contentions().is_empty());
+    /// ig.set_lever_stable(enable_b);
+    /// assert_eq!(ig.contentions()[0].drivers, 2);
+    /// ```
+    pub fn contentions(&self) -> &[BusContention] {
+        &self.contention.contentions
+    }
+
+    /// Returns the contentions that have happened since the graph was initialized or since the last call to
+    /// this method and clears them, see [InitializedGateGraph::contentions].
+    pub fn take_contentions(&mut self) -> Vec<BusContention> {
+        std::mem::take(&mut self.contention.contentions)
+    }
+
+    /// Makes the graph panic at the end of the tick in which a [contention](InitializedGateGraph::contentions)
+    /// starts if `panic` is true, to catch them in tests where they mean a bug in the control logic.
+    pub fn panic_on_contention(&mut self, panic: bool) {
+        self.contention.panic = panic;
+    }
+
+    /// Records the buses that went into contention during this tick, called at the end of every tick.
+    pub(super) fn check_contention(&mut self) {
+        for i in 0..self.contention.buses.len() {
+            let bus = &self.contention.buses[i];
+            let mut drivers = 0;
+            let mut high = false;
+            let mut low = false;
+            for driver in &bus.drivers {
+                let dependencies = &self.nodes[driver.idx].dependencies;
+                if self.value(dependencies[1]) {
+                    drivers += 1;
+                    if self.value(dependencies[0]) {
+                        high = true
+                    } else {
+                        low = true
+                    }
+                }
+            }
+            let contended = high && low;
+            if contended && !self.contention.contended[i] {
+                let contention = BusContention {
+                    gate: self.full_name(bus.gate),
+                    drivers,
+                    tick: self.ticks,
+                };
+                if self.contention.panic {
+                    panic!("Bus contention: {:?}", contention);
+                }
+                self.contention.contentions.push(contention);
+            }
+            self.contention.contended[i] = contended;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{Bus, WordInput};
+
+    #[test]
+    fn test_contention() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let a = WordInput::new(g, 4, "a");
+        let b = WordInput::new(g, 4, "b");
+        let enable_a = g.lever("enable_a");
+        let enable_b = g.lever("enable_b");
+        let bus = Bus::new(g, 4, "bus");
+        bus.connect_tristate(g, enable_a.bit(), &a.bits());
+        bus.connect_tristate(g, enable_b.bit(), &b.bits());
+        let output = g.output(bus.bits(), "bus");
+        let ig = &mut graph.init();
+
+        a.set_to(ig, 0b1010);
+        b.set_to(ig, 0b1001);
+        ig.run_until_stable(10).unwrap();
+        ig.set_lever_stable(enable_a);
+        assert_eq!(output.u8(ig), 0b1010);
+        ig.reset_lever_stable(enable_a);
+        ig.set_lever_stable(enable_b);
+        assert_eq!(output.u8(ig), 0b1001);
+        // Only driving one value at a time.
+        assert!(ig.contentions().is_empty());
+
+        let start = ig.now();
+        ig.set_lever_stable(enable_a);
+        // Bits 0 and 1 are different.
+        let contentions = ig.take_contentions();
+        assert_eq!(contentions.len(), 2);
+        assert!(contentions
+            .iter()
+            .all(|contention| contention.drivers == 2 && contention.tick == start + 1));
+
+        // Only reported once.
+        ig.tick();
+        assert!(ig.contentions().is_empty());
+
+        // Agreeing drivers are fine.
+        b.set_to(ig, 0b1010);
+        ig.run_until_stable(10).unwrap();
+        assert!(ig.contentions().is_empty());
+        b.set_to(ig, 0b1000);
+        ig.run_until_stable(10).unwrap();
+        assert_eq!(ig.take_contentions().len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Bus contention")]
+    fn test_panic_on_contention() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let enable = g.lever("enable");
+        let bus = Bus::new(g, 1, "bus");
+        bus.connect_tristate(g, enable.bit(), &[ON]);
+        bus.connect_tristate(g, enable.bit(), &[OFF]);
+        g.output(bus.bits(), "bus");
+        let ig = &mut graph.init();
+
+        ig.panic_on_contention(true);
+        ig.set_lever_stable(enable);
+    }
+
+    #[test]
+    fn test_tristate_optimization() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let enable = g.lever("enable");
+        let bus = Bus::new(g, 1, "bus");
+        bus.connect_tristate(g, enable.bit(), &[ON]);
+        bus.connect_tristate(g, enable.bit(), &[OFF]);
+        // Never enabled, optimized away.
+        bus.connect_tristate(g, OFF, &[ON]);
+        let output = g.output(bus.bits(), "bus");
+        let ig = &mut graph.init();
+
+        assert_eq!(
+            ig.nodes.iter().filter(|node| node.ty.is_tristate()).count(),
+            2
+        );
+        ig.set_lever_stable(enable);
+        assert!(output.b0(ig));
+        assert_eq!(ig.contentions().len(), 1);
+    }
+}
@@ -0,0 +1,278 @@
+use super::gate::*;
+use super::InitializedGateGraph;
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+/// Area and energy of a single gate, in whatever units the [CostTable] uses.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct GateCost {
+    /// Area of the gate.
+    pub area: f64,
+    /// Energy the gate uses every time it switches.
+    pub energy: f64,
+}
+impl GateCost {
+    /// Returns a new [GateCost].
+    pub fn new(area: f64, energy: f64) -> Self {
+        Self { area, energy }
+    }
+}
+
+/// Cost of every gate type with 2 dependencies, used by [InitializedGateGraph::cost_report].
+///
+/// Gates with more dependencies are costed as a tree of 2 dependency gates, a gate with `n` dependencies costs
+/// `n - 1` times its entry. Levers and constants are free.
+///
+/// The [default](CostTable::default) uses the number of transistors of each gate in
+/// [CMOS](https://en.wikipedia.org/wiki/CMOS) for both area and energy, which is crude but good enough to compare
+/// alternative implementations of the same circuit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CostTable {
+    /// Cost of Not gates.
+    pub not: GateCost,
+    /// Cost of And gates.
+    pub and: GateCost,
+    /// Cost of Nand gates.
+    pub nand: GateCost,
+    /// Cost of Or gates.
+    pub or: GateCost,
+    /// Cost of Nor gates.
+    pub nor: GateCost,
+    /// Cost of Xor gates.
+    pub xor: GateCost,
+    /// Cost of Xnor gates.
+    pub xnor: GateCost,
+    /// Cost of delay gates.
+    pub delay: GateCost,
+    /// Cost of tri-state buffers.
+    pub tristate: GateCost,
+    /// Cost of every [custom gate](super::GateGraphBuilder::register_gate), regardless of its arity.
+    pub custom: GateCost,
+}
+impl Default for CostTable {
+    fn default() -> Self {
+        let transistors = |n| GateCost::new(n, n);
+        Self {
+            not: transistors(2.),
+            and: transistors(6.),
+            nand: transistors(4.),
+            or: transistors(6.),
+            nor: transistors(4.),
+            xor: transistors(12.),
+            xnor: transistors(12.),
+            // 2 inverters.
+            delay: transistors(4.),
+            // An inverter and a transmission gate.
+            tristate: transistors(4.),
+            custom: transistors(12.),
+        }
+    }
+}
+impl CostTable {
+    /// Returns the cost of a gate of type `ty` with `dependencies` dependencies.
+    pub(super) fn cost(&self, ty: GateType, dependencies: usize) -> GateCost {
+        use GateType::*;
+        let base = match ty {
+            Off | On | Lever => return GateCost::default(),
+            Not => return self.not,
+            Delay => return self.delay,
+            TriState => return self.tristate,
+            Custom(_) => return self.custom,
+            And => self.and,
+            Nand => self.nand,
+            Or => self.or,
+            Nor => self.nor,
+            Xor => self.xor,
+            Xnor => self.xnor,
+        };
+        let scale = dependencies.saturating_sub(1).max(1) as f64;
+        GateCost::new(base.area * scale, base.energy * scale)
+    }
+}
+
+/// Cost of a group of gates in a [CostReport].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ModuleCost {
+    /// Name of the module.
+    pub name: String,
+    /// Number of gates in the module, not counting levers and constants.
+    pub gates: usize,
+    /// Sum of the area of the gates.
+    pub area: f64,
+    /// Sum of the energy of the gates.
+    pub energy: f64,
+}
+impl ModuleCost {
+    fn add(&mut self, cost: GateCost) {
+        self.gates += 1;
+        self.area += cost.area;
+        self.energy += cost.energy;
+    }
+}
+
+/// Summary of the cost of an [InitializedGateGraph] returned by [InitializedGateGraph::cost_report].
+///
+/// Its [Display] implementation prints a table with the most expensive modules first.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CostReport {
+    /// Cost of the whole graph.
+    pub total: ModuleCost,
+    /// Cost of every module, sorted by area, biggest first.
+    pub modules: Vec<ModuleCost>,
+}
+impl CostReport {
+    /// Returns the cost of the module named `name`.
+    pub fn module(&self, name: &str) -> Option<&ModuleCost> {
+        self.modules.iter().find(|module| module.name == name)
+    }
+}
+impl Display for CostReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let width = self
+            .modules
+            .iter()
+            .map(|module| module.name.len())
+            .chain(std::iter::once(self.total.name.len()))
+            .max()
+            .unwrap_or(0);
+        writeln!(
+            f,
+            "{:width$} {:>8} {:>12} {:>12}",
+            "module",
+            "gates",
+            "area",
+            "energy",
+            width = width
+        )?;
+        for module in self.modules.iter().chain(std::iter::once(&self.total)) {
+            writeln!(
+                f,
+                "{:width$} {:>8} {:>12.1} {:>12.1}",
+                module.name,
+                module.gates,
+                module.area,
+                module.energy,
+                width = width
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl InitializedGateGraph {
+    /// Returns the cost of the graph according to `table`, after optimization.
+    ///
+    /// If the "debug_gates" feature is enabled, the gates are grouped into modules by their name,
+    /// which means every instance of a circuit, like an [adder](crate::circuits::adder) named "adder",
+    /// gets its own entry, otherwise there is a single module named "graph".
+    ///
+    /// This allows comparing alternative implementations of the same circuit quantitatively.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,CostTable,OFF};
+    /// # use logicsim::{adder,WordInput};
+    /// let mut g = GateGraphBuilder::new();
+    /// let a = WordInput::new(&mut g, 8, "a");
+    /// let b = WordInput::new(&mut g, 8, "b");
+    /// let sum = adder(&mut g, OFF, &a.bits(), &b.bits(), "ripple");
+    /// g.output(&sum, "sum");
+    ///
+    /// let ig = g.init();
+    /// let report = ig.cost_report(&CostTable::default());
+    /// println!("{}", report);
+    ///
+    /// assert!(report.total.area > 0.);
+    /// # #[cfg(feature = "debug_gates")]
+    /// assert_eq!(report.module("ADDER:ripple").unwrap().area, report.total.area);
+    /// ```
+    pub fn cost_report(&self, table: &CostTable) -> CostReport {
+        let mut modules = HashMap::<&str, ModuleCost>::new();
+        let mut total = ModuleCost {
+            name: "total".to_string(),
+            ..Default::default()
+        };
+        for (idx, gate) in self.nodes.iter().enumerate() {
+            if matches!(gate.ty, GateType::Off | GateType::On | GateType::Lever) {
+                continue;
+            }
+            let cost = table.cost(gate.ty, gate.dependencies.len());
+            total.add(cost);
+
+            let name = self.module_name(idx);
+            modules
+                .entry(name)
+                .or_insert_with(|| ModuleCost {
+                    name: name.to_string(),
+                    ..Default::default()
+                })
+                .add(cost);
+        }
+
+        let mut modules: Vec<ModuleCost> = modules.into_values().collect();
+        modules.sort_unstable_by(|a, b| {
+            b.area
+                .partial_cmp(&a.area)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        CostReport { total, modules }
+    }
+
+    /// Returns the name of the module of the gate with index `idx`, its name if the "debug_gates" feature is enabled
+    /// and "graph" otherwise, see [cost_report](InitializedGateGraph::cost_report).
+    pub(super) fn module_name(&self, idx: usize) -> &str {
+        #[cfg(feature = "debug_gates")]
+        return self
+            .names
+            .get(&GateIndex::new(idx))
+            .map(String::as_str)
+            .unwrap_or("");
+        #[cfg(not(feature = "debug_gates"))]
+        {
+            let _ = idx;
+            "graph"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::WordInput;
+
+    #[test]
+    fn test_cost_table() {
+        let table = CostTable::default();
+        assert_eq!(table.cost(GateType::Lever, 0), GateCost::default());
+        assert_eq!(table.cost(GateType::Not, 1), table.not);
+        assert_eq!(table.cost(GateType::And, 2), table.and);
+        assert_eq!(table.cost(GateType::And, 4).area, table.and.area * 3.);
+    }
+
+    #[test]
+    fn test_cost_report() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let input = WordInput::new(g, 4, "input");
+        let bits = input.bits();
+        let and = g.and2(bits[0], bits[1], "small");
+        let xor = g.xorx(bits.iter().copied(), "big");
+        let nand = g.nand2(bits[2], bits[3], "big");
+        g.output(&[and, xor, nand], "out");
+
+        let report = graph.init().cost_report(&CostTable::default());
+        assert_eq!(report.total.gates, 3);
+        assert_eq!(report.total.area, 6. + 12. * 3. + 4.);
+
+        #[cfg(feature = "debug_gates")]
+        {
+            assert_eq!(report.modules.len(), 2);
+            assert_eq!(report.modules[0].name, "big");
+            assert_eq!(report.modules[0].gates, 2);
+            assert_eq!(report.module("small").unwrap().area, 6.);
+        }
+        assert!(report.to_string().contains("total"));
+    }
+}
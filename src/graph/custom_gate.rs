@@ -0,0 +1,192 @@
+use super::gate::*;
+use super::graph_builder::GateGraphBuilder;
+use smallvec::SmallVec;
+
+/// Maximum number of dependencies a [custom gate](GateGraphBuilder::register_gate) can have.
+pub const CUSTOM_GATE_MAX_ARITY: usize = 8;
+
+/// Maximum number of custom gate types that can be [registered](GateGraphBuilder::register_gate) in a single graph.
+pub const CUSTOM_GATE_MAX_TYPES: usize = u8::MAX as usize + 1;
+
+/// Handle type that represents a custom primitive gate type registered with [GateGraphBuilder::register_gate].
+///
+/// It is only valid in the [GateGraphBuilder] that returned it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct CustomGateType(pub(super) u8);
+
+/// Data structure that stores the definition of a custom primitive gate type.
+#[derive(Debug, Copy, Clone)]
+pub(super) struct CustomGate {
+    pub name: &'static str,
+    pub arity: usize,
+    pub eval: fn(&[bool]) -> bool,
+}
+
+impl GateGraphBuilder {
+    /// Registers a new primitive gate type named `name` with `arity` dependencies,
+    /// whose state is `eval(states of the dependencies)`, and returns its [CustomGateType].
+    ///
+    /// Create gates of the new type with [GateGraphBuilder::custom].
+    ///
+    /// `eval` must be a pure function of its inputs, it gets called from the simulation loop every time
+    /// a dependency of a gate of this type changes, and the optimizations treat the gates as opaque boxes,
+    /// they won't look into them, but they will merge gates of the same type with the same dependencies.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// let mut g = GateGraphBuilder::new();
+    /// let maj3 = g.register_gate("MAJ3", 3, |inputs| {
+    ///     inputs.iter().filter(|input| **input).count() >= 2
+    /// });
+    ///
+    /// let a = g.lever("a");
+    /// let b = g.lever("b");
+    /// let c = g.lever("c");
+    /// let majority = g.custom(maj3, &[a.bit(), b.bit(), c.bit()], "majority");
+    /// let output = g.output1(majority, "majority");
+    ///
+    /// let ig = &mut g.init();
+    /// ig.set_lever_stable(a);
+    /// assert_eq!(output.b0(ig), false);
+    ///
+    /// ig.set_lever_stable(c);
+    /// assert_eq!(output.b0(ig), true);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `arity` == 0 or `arity` > [CUSTOM_GATE_MAX_ARITY].
+    ///
+    /// Will panic if [CUSTOM_GATE_MAX_TYPES] custom gate types have already been registered.
+    pub fn register_gate(
+        &mut self,
+        name: &'static str,
+        arity: usize,
+        eval: fn(&[bool]) -> bool,
+    ) -> CustomGateType {
+        assert!(
+            arity > 0 && arity <= CUSTOM_GATE_MAX_ARITY,
+            "Custom gates must have between 1 and {} dependencies, {} has {}",
+            CUSTOM_GATE_MAX_ARITY,
+            name,
+            arity
+        );
+        assert!(
+            self.custom_gates.len() < CUSTOM_GATE_MAX_TYPES,
+            "Can't register more than {} custom gate types",
+            CUSTOM_GATE_MAX_TYPES
+        );
+        self.custom_gates.push(CustomGate { name, arity, eval });
+        CustomGateType((self.custom_gates.len() - 1) as u8)
+    }
+
+    /// Returns the [GateIndex] of a new gate of the custom type `ty` with `dependencies`,
+    /// see [GateGraphBuilder::register_gate].
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `ty` hasn't been registered in `self`.
+    ///
+    /// Will panic if the number of `dependencies` is not the arity of `ty`.
+    #[track_caller]
+    pub fn custom<S: Into<String>>(
+        &mut self,
+        ty: CustomGateType,
+        dependencies: &[GateIndex],
+        name: S,
+    ) -> GateIndex {
+        let custom = self
+            .custom_gates
+            .get(ty.0 as usize)
+            .expect("Custom gate type not registered in this graph");
+        assert_eq!(
+            dependencies.len(),
+            custom.arity,
+            "{} takes {} dependencies",
+            custom.name,
+            custom.arity
+        );
+        let idx = self.insert(Gate::new(
+            GateType::Custom(ty.0),
+            SmallVec::from_slice(dependencies),
+        ));
+        self.create_gate(idx, dependencies.iter().copied(), name);
+        idx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+
+    fn mux(inputs: &[bool]) -> bool {
+        if inputs[0] {
+            inputs[2]
+        } else {
+            inputs[1]
+        }
+    }
+
+    #[test]
+    fn test_custom_gate_survives_optimizations() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let mux_type = g.register_gate("MUX", 3, mux);
+
+        let s = g.lever("s");
+        let a = g.lever("a");
+        let b = g.lever("b");
+        // The order of the dependencies matters and the same dependency can be repeated.
+        let mux1 = g.custom(mux_type, &[s.bit(), a.bit(), b.bit()], "mux1");
+        let mux2 = g.custom(mux_type, &[s.bit(), b.bit(), a.bit()], "mux2");
+        let mux3 = g.custom(mux_type, &[s.bit(), s.bit(), ON], "mux3");
+        // Equal to mux1.
+        let mux4 = g.custom(mux_type, &[s.bit(), a.bit(), b.bit()], "mux4");
+        // Keep the custom gates out of the outputs so the optimizations can touch them.
+        let nots: Vec<_> = [mux1, mux2, mux3, mux4]
+            .iter()
+            .map(|mux| g.not1(*mux, "not"))
+            .collect();
+        let output = g.output(&nots, "out");
+        let levers = [s, a, b];
+
+        let optimized = &mut graph.clone().init();
+        let reference = &mut graph.init_unoptimized();
+        let table = optimized.truth_table(output, &levers);
+        assert_eq!(table, reference.truth_table(output, &levers));
+        for (input, output) in table {
+            let s = input & 1 != 0;
+            let a = input & 2 != 0;
+            let b = input & 4 != 0;
+            let mux1 = if s { b } else { a };
+            let mux2 = if s { a } else { b };
+            let expected = mux1 as u64 | (mux2 as u64) << 1 | (s as u64) << 2 | (mux1 as u64) << 3;
+            assert_eq!(output, !expected & 0b1111);
+        }
+    }
+
+    #[test]
+    fn test_custom_gate_const_dependency() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let mux_type = g.register_gate("MUX", 3, mux);
+
+        let s = g.lever("s");
+        let mux = g.custom(mux_type, &[s.bit(), ON, OFF], "mux");
+        let output = g.output1(mux, "out");
+
+        let g = &mut graph.init();
+        assert_eq!(output.b0(g), true);
+        g.set_lever_stable(s);
+        assert_eq!(output.b0(g), false);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_custom_gate_wrong_arity() {
+        let mut g = GateGraphBuilder::new();
+        let mux_type = g.register_gate("MUX", 3, mux);
+        g.custom(mux_type, &[ON, OFF], "mux");
+    }
+}
@@ -0,0 +1,240 @@
+use super::gate::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Write};
+
+/// Configuration of the [.dot files](https://en.wikipedia.org/wiki/DOT_(graph_description_language)) written by
+/// [GateGraphBuilder::dump_dot_with_config](super::GateGraphBuilder::dump_dot_with_config) and
+/// [InitializedGateGraph::dump_dot_with_config](super::InitializedGateGraph::dump_dot_with_config).
+///
+/// The default dumps the whole graph without clusters.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DotConfig {
+    /// Names of the outputs and probes to dump the cone of, only the gates in the cone of any of them are dumped.
+    /// The whole graph is dumped if it's empty.
+    pub cone: Vec<String>,
+    /// Whether the cone includes the gates the outputs and probes depend on, directly or not.
+    pub fan_in: bool,
+    /// Whether the cone includes the gates that depend on the outputs and probes, directly or not.
+    pub fan_out: bool,
+    /// Number of prefixes of the gate names used to group gates into clusters, 0 doesn't cluster.
+    ///
+    /// Prefixes are separated by "/", like the paths added by [modules](super::GateGraphBuilder::begin_module),
+    /// or ":", like the "ADDER:" of the gates created by [adder](crate::adder). With a depth of 1 every gate in
+    /// "alu/ADDER:adder" goes in the "alu" cluster, with a depth of 2 in the "alu/ADDER" cluster.
+    /// Gates without prefixes aren't clustered, there are no names without the "debug_gates" feature.
+    pub cluster_depth: usize,
+}
+impl DotConfig {
+    /// Returns a [DotConfig] that dumps the fan-in and fan-out cones of the outputs and probes named `names`.
+    pub fn cone<S: Into<String>, I: IntoIterator<Item = S>>(names: I) -> Self {
+        Self {
+            cone: names.into_iter().map(Into::into).collect(),
+            ..Default::default()
+        }
+    }
+}
+impl Default for DotConfig {
+    fn default() -> Self {
+        Self {
+            cone: Vec::new(),
+            fan_in: true,
+            fan_out: true,
+            cluster_depth: 0,
+        }
+    }
+}
+
+/// Gate of a graph written by [write_dot].
+pub(super) struct DotGate<'a> {
+    pub(super) index: GateIndex,
+    pub(super) label: String,
+    pub(super) name: &'a str,
+    pub(super) dependencies: &'a [GateIndex],
+}
+
+/// Returns the gates of the outputs and probes named in `config.cone`, `named` are the names and gates of every
+/// output and probe in the graph.
+///
+/// Returns an error if there is no output or probe with one of the names.
+pub(super) fn cone_roots(
+    config: &DotConfig,
+    named: &[(&str, &[GateIndex])],
+) -> io::Result<Vec<GateIndex>> {
+    let mut roots = Vec::new();
+    for name in &config.cone {
+        let start = roots.len();
+        for (_, bits) in named.iter().filter(|(n, _)| n == name) {
+            roots.extend_from_slice(bits);
+        }
+        if roots.len() == start {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("There is no output or probe named {}", name),
+            ));
+        }
+    }
+    Ok(roots)
+}
+
+/// Returns `name` escaped to go between quotes in a .dot file.
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Returns the cluster of a gate named `name` with `depth` prefixes, see [DotConfig::cluster_depth].
+fn cluster(name: &str, depth: usize) -> Option<&str> {
+    let separators: Vec<usize> = name.match_indices(['/', ':']).map(|(i, _)| i).collect();
+    if depth == 0 || separators.is_empty() {
+        return None;
+    }
+    Some(&name[..separators[depth.min(separators.len()) - 1]])
+}
+
+/// Writes `gates` in dot format to `writer`, restricted to the cone of `roots` if `config.cone` isn't empty.
+pub(super) fn write_dot<W: Write>(
+    writer: &mut W,
+    gates: &[DotGate<'_>],
+    roots: &[GateIndex],
+    config: &DotConfig,
+) -> io::Result<()> {
+    let included: Option<HashSet<GateIndex>> = if config.cone.is_empty() {
+        None
+    } else {
+        let mut dependents: HashMap<GateIndex, Vec<GateIndex>> = HashMap::new();
+        let mut dependencies = HashMap::new();
+        for gate in gates {
+            dependencies.insert(gate.index, gate.dependencies);
+            for dependency in gate.dependencies {
+                dependents.entry(*dependency).or_default().push(gate.index);
+            }
+        }
+        let mut included: HashSet<GateIndex> = roots.iter().copied().collect();
+        let mut walk = |next: &dyn Fn(GateIndex) -> Vec<GateIndex>| {
+            let mut visited = HashSet::new();
+            let mut work = roots.to_vec();
+            while let Some(gate) = work.pop() {
+                if visited.insert(gate) {
+                    included.insert(gate);
+                    work.extend(next(gate));
+                }
+            }
+        };
+        if config.fan_in {
+            walk(&|gate| dependencies.get(&gate).map_or(Vec::new(), |d| d.to_vec()));
+        }
+        if config.fan_out {
+            walk(&|gate| dependents.get(&gate).cloned().unwrap_or_default());
+        }
+        Some(included)
+    };
+    let is_included = |gate: GateIndex| included.as_ref().map_or(true, |i| i.contains(&gate));
+
+    writeln!(writer, "digraph {{")?;
+    let mut clusters: BTreeMap<&str, Vec<&DotGate<'_>>> = BTreeMap::new();
+    for gate in gates.iter().filter(|gate| is_included(gate.index)) {
+        match cluster(gate.name, config.cluster_depth) {
+            Some(cluster) => clusters.entry(cluster).or_default().push(gate),
+            None => writeln!(
+                writer,
+                "    {} [label=\"{}\"]",
+                gate.index.idx,
+                escape(&gate.label)
+            )?,
+        }
+    }
+    for (i, (cluster, cluster_gates)) in clusters.iter().enumerate() {
+        writeln!(writer, "    subgraph cluster_{} {{", i)?;
+        writeln!(writer, "        label=\"{}\"", escape(cluster))?;
+        for gate in cluster_gates {
+            writeln!(
+                writer,
+                "        {} [label=\"{}\"]",
+                gate.index.idx,
+                escape(&gate.label)
+            )?;
+        }
+        writeln!(writer, "    }}")?;
+    }
+    for gate in gates.iter().filter(|gate| is_included(gate.index)) {
+        for dependency in gate.dependencies {
+            if is_included(*dependency) {
+                writeln!(writer, "    {} -> {}", dependency.idx, gate.index.idx)?;
+            }
+        }
+    }
+    writeln!(writer, "}}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::cluster;
+
+    #[test]
+    fn test_cluster() {
+        assert_eq!(cluster("alu/ADDER:adder", 0), None);
+        assert_eq!(cluster("alu/ADDER:adder", 1), Some("alu"));
+        assert_eq!(cluster("alu/ADDER:adder", 2), Some("alu/ADDER"));
+        assert_eq!(cluster("alu/ADDER:adder", 5), Some("alu/ADDER"));
+        assert_eq!(cluster("carry", 1), None);
+    }
+
+    #[test]
+    fn test_dump_dot_cone() {
+        let mut g = GateGraphBuilder::new();
+        let a = g.lever("a");
+        let b = g.lever("b");
+        g.begin_module("left");
+        let not_a = g.not1(a.bit(), "not_a");
+        g.end_module();
+        g.begin_module("right");
+        let not_b = g.not1(b.bit(), "not_b");
+        let and = g.and2(not_b, b.bit(), "and");
+        g.end_module();
+        g.output1(not_a, "left");
+        g.output1(and, "right");
+
+        let mut dot = Vec::new();
+        g.write_dot(&mut dot, &DotConfig::cone(vec!["right"]))
+            .unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.starts_with("digraph {"));
+        assert_eq!(dot.matches("label").count(), 3);
+        assert_eq!(dot.matches("->").count(), 3);
+        assert!(!dot.contains("not_a"));
+
+        let mut config = DotConfig::cone(vec!["right"]);
+        config.fan_in = false;
+        let mut dot = Vec::new();
+        g.write_dot(&mut dot, &config).unwrap();
+        assert_eq!(String::from_utf8(dot).unwrap().matches("label").count(), 1);
+
+        let config = DotConfig::cone(vec!["nowhere"]);
+        assert!(g.write_dot(&mut Vec::new(), &config).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "debug_gates")]
+    fn test_dump_dot_clusters() {
+        let mut g = GateGraphBuilder::new();
+        let a = g.lever("a");
+        let not = g.module("inverter", |g| g.not1(a.bit(), "not"));
+        g.output1(not, "out");
+        let ig = &g.init_unoptimized();
+
+        let config = DotConfig {
+            cluster_depth: 1,
+            ..Default::default()
+        };
+        let mut dot = Vec::new();
+        ig.write_dot(&mut dot, &config).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert_eq!(dot.matches("subgraph").count(), 1);
+        assert!(dot.contains("label=\"inverter\""));
+
+        let path = std::env::temp_dir().join("logicsim_test_dump_dot_clusters.dot");
+        ig.dump_dot_with_config(&path, &config).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), dot);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,338 @@
+use super::gate::*;
+use super::graph_builder::GateGraphBuilder;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+
+/// Structural mistake found by [GateGraphBuilder::drc], gates are identified by their full name,
+/// the same ones [GateGraphBuilder::dump_dot] uses.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DrcViolation {
+    /// `clock` reaches the output `output` through combinational logic, the clock is being used as data.
+    ClockAsData { clock: String, output: String },
+    /// `reset` is computed by combinational logic from more than one signal, which can glitch
+    /// and reset the circuit by accident.
+    CombinationalReset { reset: String, sources: Vec<String> },
+    /// `gate` combines `clock` with a signal derived from stored data, and the result is used to
+    /// clock more data, which makes the timing of the circuit depend on its contents.
+    GatedClock { clock: String, gate: String },
+    /// Bit `bit` of output `output` doesn't depend on any lever or stored data, it's constant.
+    ConstantOutput { output: String, bit: usize },
+}
+impl Display for DrcViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DrcViolation::ClockAsData { clock, output } => {
+                write!(f, "Clock {} is used as data by {}", clock, output)
+            }
+            DrcViolation::CombinationalReset { reset, sources } => write!(
+                f,
+                "Reset {} is driven by combinational logic from: {}",
+                reset,
+                sources.join(", ")
+            ),
+            DrcViolation::GatedClock { clock, gate } => {
+                write!(f, "Clock {} is gated by data in {}", clock, gate)
+            }
+            DrcViolation::ConstantOutput { output, bit } => {
+                write!(f, "Bit {} of output {} is constant", bit, output)
+            }
+        }
+    }
+}
+
+impl GateGraphBuilder {
+    /// Runs design rule checks on the graph and returns the structural mistakes it finds,
+    /// it's the checklist an experienced reviewer would go through when looking at your first CPU.
+    ///
+    /// `clocks` and `resets` are the clock and reset signals of the circuit, they can't be told apart from
+    /// any other signal by looking at the gates.
+    ///
+    /// Every group of gates connected in a loop is considered a latch and every lever an input,
+    /// a signal is combinational if it's computed without going through a latch. The checks are:
+    /// - A clock reaching an output through combinational logic, see [DrcViolation::ClockAsData].
+    /// - A reset combining more than one input or latch, see [DrcViolation::CombinationalReset].
+    /// - A clock gated by a signal derived from a latch, which then gets combined with more data,
+    ///   see [DrcViolation::GatedClock]. Write enables computed by control logic are a common example.
+    /// - An output that doesn't depend on any input or latch, see [DrcViolation::ConstantOutput].
+    ///
+    /// These are heuristics, a violation is not necessarily a bug, but it's worth a second look.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,DrcViolation,ON};
+    /// # use logicsim::d_flip_flop;
+    /// let mut g = GateGraphBuilder::new();
+    /// let clock = g.lever("clock");
+    /// let d = g.lever("d");
+    /// let a = g.lever("a");
+    /// let b = g.lever("b");
+    ///
+    /// let reset = g.and2(a.bit(), b.bit(), "reset");
+    /// let q = d_flip_flop(&mut g, d.bit(), clock.bit(), reset, ON, ON, "ff");
+    /// g.output1(q, "q");
+    ///
+    /// let violations = g.drc(&[clock.bit()], &[reset]);
+    /// assert_eq!(violations.len(), 1);
+    /// assert!(matches!(violations[0], DrcViolation::CombinationalReset{..}));
+    /// ```
+    pub fn drc(&self, clocks: &[GateIndex], resets: &[GateIndex]) -> Vec<DrcViolation> {
+        let latches = self.latches();
+        let is_source = |gate: GateIndex| self.get(gate).ty.is_lever() || latches.contains(&gate);
+        let mut violations = Vec::new();
+
+        // Every gate that depends on a latch.
+        let mut derived = HashSet::new();
+        let mut work: Vec<GateIndex> = latches.iter().copied().collect();
+        while let Some(gate) = work.pop() {
+            if derived.insert(gate) {
+                work.extend(self.get(gate).dependents.iter());
+            }
+        }
+
+        for clock in clocks {
+            let fanout = self.combinational_fanout(*clock, &latches);
+
+            let mut outputs: Vec<GateIndex> = fanout
+                .iter()
+                .copied()
+                .filter(|gate| self.outputs.contains(gate))
+                .collect();
+            outputs.sort_unstable();
+            for output in outputs {
+                violations.push(DrcViolation::ClockAsData {
+                    clock: self.full_name(*clock),
+                    output: self.full_name(output),
+                });
+            }
+
+            // Gates in the fan-out that combine the clock with data.
+            let mixes = |gate: GateIndex| {
+                self.get(gate)
+                    .dependencies
+                    .iter()
+                    .any(|dependency| !fanout.contains(dependency) && derived.contains(dependency))
+            };
+            let mut mixes_downstream = HashMap::new();
+            let mut gated: Vec<GateIndex> = fanout
+                .iter()
+                .copied()
+                .filter(|gate| {
+                    *gate != *clock
+                        && mixes(*gate)
+                        && self.mixes_downstream(*gate, &fanout, &mixes, &mut mixes_downstream)
+                })
+                .collect();
+            gated.sort_unstable();
+            for gate in gated {
+                violations.push(DrcViolation::GatedClock {
+                    clock: self.full_name(*clock),
+                    gate: self.full_name(gate),
+                });
+            }
+        }
+
+        for reset in resets {
+            let sources = self.combinational_sources(*reset, &is_source);
+            if sources.len() > 1 {
+                violations.push(DrcViolation::CombinationalReset {
+                    reset: self.full_name(*reset),
+                    sources: sources
+                        .into_iter()
+                        .map(|gate| self.full_name(gate))
+                        .collect(),
+                });
+            }
+        }
+
+        for output in &self.output_handles {
+            for (bit, gate) in output.bits.iter().enumerate() {
+                if self.combinational_sources(*gate, &is_source).is_empty() {
+                    violations.push(DrcViolation::ConstantOutput {
+                        output: output.name.clone(),
+                        bit,
+                    });
+                }
+            }
+        }
+        violations
+    }
+
+    /// Returns every gate that is part of a loop.
+    pub(super) fn latches(&self) -> HashSet<GateIndex> {
+        let mut graph = petgraph::Graph::<GateIndex, ()>::new();
+        let mut index = HashMap::new();
+        for (i, _) in self.nodes.iter() {
+            index.insert(GateIndex::from(i), graph.add_node(i.into()));
+        }
+        for (i, gate) in self.nodes.iter() {
+            let i = GateIndex::from(i);
+            for dependency in &gate.dependencies {
+                graph.add_edge(index[dependency], index[&i], ());
+            }
+        }
+        petgraph::algo::tarjan_scc(&graph)
+            .into_iter()
+            .filter(|scc| {
+                scc.len() > 1 || {
+                    let gate = graph[scc[0]];
+                    self.get(gate).dependencies.contains(&gate)
+                }
+            })
+            .flatten()
+            .map(|node| graph[node])
+            .collect()
+    }
+
+    /// Returns `gate` and every gate that depends on it without going through a latch.
+    fn combinational_fanout(
+        &self,
+        gate: GateIndex,
+        latches: &HashSet<GateIndex>,
+    ) -> HashSet<GateIndex> {
+        let mut fanout = HashSet::new();
+        let mut work = vec![gate];
+        while let Some(gate) = work.pop() {
+            if fanout.insert(gate) {
+                work.extend(
+                    self.get(gate)
+                        .dependents
+                        .iter()
+                        .filter(|dependent| !latches.contains(dependent)),
+                );
+            }
+        }
+        fanout
+    }
+
+    /// Returns the levers and latches `gate` is computed from, sorted.
+    fn combinational_sources<F: Fn(GateIndex) -> bool>(
+        &self,
+        gate: GateIndex,
+        is_source: &F,
+    ) -> Vec<GateIndex> {
+        let mut sources = Vec::new();
+        let mut visited = HashSet::new();
+        let mut work = vec![gate];
+        while let Some(gate) = work.pop() {
+            if !visited.insert(gate) {
+                continue;
+            }
+            if is_source(gate) {
+                sources.push(gate);
+                continue;
+            }
+            work.extend_from_slice(&self.get(gate).dependencies);
+        }
+        sources.sort_unstable();
+        sources
+    }
+
+    /// Returns true if any gate in `fanout` that depends on `gate` [mixes](GateGraphBuilder::drc) the clock with data.
+    fn mixes_downstream<F: Fn(GateIndex) -> bool>(
+        &self,
+        gate: GateIndex,
+        fanout: &HashSet<GateIndex>,
+        mixes: &F,
+        memo: &mut HashMap<GateIndex, bool>,
+    ) -> bool {
+        if let Some(result) = memo.get(&gate) {
+            return *result;
+        }
+        // The fan-out has no loops, they would be latches.
+        memo.insert(gate, false);
+        let mut result = false;
+        for dependent in &self.get(gate).dependents {
+            if fanout.contains(dependent)
+                && (mixes(*dependent) || self.mixes_downstream(*dependent, fanout, mixes, memo))
+            {
+                result = true;
+                break;
+            }
+        }
+        memo.insert(gate, result);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::DrcViolation;
+    use crate::circuits::{d_flip_flop, register, WordInput};
+
+    #[test]
+    fn test_drc_clean() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let input = WordInput::new(g, 4, "input");
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let write = g.lever("write");
+        let out = register(
+            g,
+            clock.bit(),
+            write.bit(),
+            ON,
+            reset.bit(),
+            &input.bits(),
+            "reg",
+        );
+        g.output(&out, "out");
+
+        assert_eq!(graph.drc(&[clock.bit()], &[reset.bit()]), vec![]);
+    }
+
+    #[test]
+    fn test_drc_clock_as_data() {
+        let mut g = GateGraphBuilder::new();
+        let clock = g.lever("clock");
+        let l = g.lever("l");
+        let and = g.and2(clock.bit(), l.bit(), "and");
+        g.output1(and, "out");
+
+        let violations = g.drc(&[clock.bit()], &[]);
+        assert_eq!(violations.len(), 1);
+        assert!(matches!(violations[0], DrcViolation::ClockAsData { .. }));
+    }
+
+    #[test]
+    fn test_drc_gated_clock() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let clock = g.lever("clock");
+        let d = g.lever("d");
+        let write = g.lever("write");
+        // The second flip flop stores the first one and is only written when it's active.
+        let q1 = d_flip_flop(g, d.bit(), clock.bit(), OFF, write.bit(), ON, "ff1");
+        let q2 = d_flip_flop(g, q1, clock.bit(), OFF, q1, ON, "ff2");
+        g.output1(q2, "q2");
+
+        let violations = graph.drc(&[clock.bit()], &[]);
+        assert_eq!(violations.len(), 1, "{:?}", violations);
+        assert!(matches!(violations[0], DrcViolation::GatedClock { .. }));
+    }
+
+    #[test]
+    fn test_drc_constant_output() {
+        let mut g = GateGraphBuilder::new();
+        let l = g.lever("l");
+        let and = g.and2(ON, OFF, "and");
+        g.output(&[l.bit(), and, ON], "out");
+
+        assert_eq!(
+            g.drc(&[], &[]),
+            vec![
+                DrcViolation::ConstantOutput {
+                    output: "out".to_string(),
+                    bit: 1
+                },
+                DrcViolation::ConstantOutput {
+                    output: "out".to_string(),
+                    bit: 2
+                }
+            ]
+        );
+    }
+}
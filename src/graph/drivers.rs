@@ -0,0 +1,181 @@
+use super::gate::*;
+use super::graph_builder::GateGraphBuilder;
+use super::handles::OutputHandle;
+use super::InitializedGateGraph;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// [Wire](crate::Wire) whose drivers are checked during the simulation, see [InitializedGateGraph::driver_conflicts].
+#[derive(Debug, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(super) struct CheckedWire {
+    name: String,
+    /// Name of every driver and the gate that is on while it's driving the wire.
+    drivers: Vec<(String, OutputHandle)>,
+}
+
+/// More than one driver of a [checked](crate::Wire::checked) [Wire](crate::Wire) active in the same tick,
+/// see [InitializedGateGraph::driver_conflicts].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DriverConflict {
+    /// Name of the wire.
+    pub wire: String,
+    /// Names of the active drivers, in the order they were connected.
+    pub drivers: Vec<String>,
+    /// [Simulation time](InitializedGateGraph::now) of the tick at the end of which they were active.
+    pub tick: usize,
+}
+
+/// Driver conflicts detected by an [InitializedGateGraph].
+#[derive(Debug, Default)]
+pub(super) struct DriverMonitor {
+    wires: Vec<CheckedWire>,
+    /// Drivers of every wire that were active at the end of the last tick, if there was a conflict.
+    active: Vec<Vec<usize>>,
+    conflicts: Vec<DriverConflict>,
+}
+impl DriverMonitor {
+    pub(super) fn new(wires: Vec<CheckedWire>) -> Self {
+        Self {
+            active: vec![Vec::new(); wires.len()],
+            wires,
+            conflicts: Vec::new(),
+        }
+    }
+}
+
+impl GateGraphBuilder {
+    /// Registers a wire named `name` whose drivers are checked during the simulation, returns its id.
+    pub(crate) fn add_checked_wire(&mut self, name: String) -> usize {
+        self.checked_wires.push(CheckedWire {
+            name,
+            drivers: Vec::new(),
+        });
+        self.checked_wires.len() - 1
+    }
+
+    /// Registers `active` as a driver of the checked wire with id `wire`, named `name` or like `active` if it's None.
+    pub(crate) fn add_wire_driver(&mut self, wire: usize, active: GateIndex, name: Option<String>) {
+        let name = name.unwrap_or_else(|| self.name(active).to_string());
+        let output = self.output1(
+            active,
+            format!("driver:{}:{}", self.checked_wires[wire].name, name),
+        );
+        self.checked_wires[wire].drivers.push((name, output));
+    }
+
+    /// Returns true if a driver of a checked wire reads `gate`.
+    pub(super) fn checked_wire_reads(&self, gate: GateIndex) -> bool {
+        self.checked_wires.iter().any(|wire| {
+            wire.drivers
+                .iter()
+                .any(|(_, output)| self.output_reads(*output, gate))
+        })
+    }
+}
+
+impl InitializedGateGraph {
+    /// Returns the driver conflicts that have happened since the graph was initialized
+    /// or since the last call to [take_driver_conflicts](InitializedGateGraph::take_driver_conflicts).
+    ///
+    /// A [checked](crate::Wire::checked) [Wire](crate::Wire) is in conflict when more than one of its drivers is
+    /// active at the end of a tick. A conflict is recorded when it starts and every time the set of active drivers
+    /// changes, not on every tick it lasts, and logged as a warning.
+    ///
+    /// Only available with the "debug_gates" feature.
+    pub fn driver_conflicts(&self) -> &[DriverConflict] {
+        &self.drivers.conflicts
+    }
+
+    /// Returns the driver conflicts that have happened since the graph was initialized or since the last call to
+    /// this method and clears them, see [InitializedGateGraph::driver_conflicts].
+    pub fn take_driver_conflicts(&mut self) -> Vec<DriverConflict> {
+        std::mem::take(&mut self.drivers.conflicts)
+    }
+
+    /// Records the checked wires whose active drivers changed into a conflict during this tick,
+    /// called at the end of every tick.
+    pub(super) fn check_drivers(&mut self) {
+        for i in 0..self.drivers.wires.len() {
+            let wire = &self.drivers.wires[i];
+            let mut active: Vec<usize> = wire
+                .drivers
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, output))| self.value(self.get_output(*output).bits[0]))
+                .map(|(driver, _)| driver)
+                .collect();
+            if active.len() < 2 {
+                active.clear();
+            }
+            if !active.is_empty() && active != self.drivers.active[i] {
+                let conflict = DriverConflict {
+                    wire: wire.name.clone(),
+                    drivers: active
+                        .iter()
+                        .map(|driver| wire.drivers[*driver].0.clone())
+                        .collect(),
+                    tick: self.ticks,
+                };
+                tracing::warn!(
+                    target: "logicsim::drivers",
+                    tick = self.ticks,
+                    "Wire {} driven by {}",
+                    conflict.wire,
+                    conflict.drivers.join(", ")
+                );
+                self.drivers.conflicts.push(conflict);
+            }
+            self.drivers.active[i] = active;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::Wire;
+
+    #[test]
+    fn test_checked_wire() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let a = g.lever("a");
+        let b = g.lever("b");
+        let c = g.lever("c");
+        let mut wire = Wire::checked(g, "wire");
+        wire.connect(g, a.bit());
+        wire.drive(g, b.bit(), ON, "b");
+        wire.drive(g, c.bit(), OFF, "c");
+        let output = g.output1(wire.bit(), "wire");
+        let ig = &mut graph.init();
+
+        ig.set_lever_stable(a);
+        assert_eq!(output.b0(ig), true);
+        assert!(ig.driver_conflicts().is_empty());
+
+        let start = ig.now();
+        ig.set_lever_stable(b);
+        let conflicts = ig.take_driver_conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].wire, "wire");
+        assert_eq!(conflicts[0].drivers, ["a", "b"]);
+        assert_eq!(conflicts[0].tick, start + 1);
+
+        // Only reported again when the active drivers change.
+        ig.tick();
+        assert!(ig.driver_conflicts().is_empty());
+        ig.set_lever_stable(c);
+        assert_eq!(ig.driver_conflicts()[0].drivers, ["a", "b", "c"]);
+        // The last driver wins, direct connections are still ORed.
+        assert_eq!(output.b0(ig), true);
+        ig.reset_lever_stable(a);
+        assert_eq!(output.b0(ig), false);
+        ig.set_lever_stable(a);
+        ig.reset_lever_stable(a);
+        ig.reset_lever_stable(b);
+        ig.take_driver_conflicts();
+        ig.set_lever_stable(b);
+        assert_eq!(ig.driver_conflicts()[0].drivers, ["b", "c"]);
+    }
+}
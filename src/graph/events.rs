@@ -0,0 +1,200 @@
+use super::gate::*;
+use super::graph_builder::GateGraphBuilder;
+use super::handles::OutputHandle;
+use super::InitializedGateGraph;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Event declared with [GateGraphBuilder::event].
+#[derive(Debug, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(super) struct EventSource {
+    name: String,
+    trigger: OutputHandle,
+    value: OutputHandle,
+}
+
+/// Occurrence of an event declared with [GateGraphBuilder::event], see [InitializedGateGraph::events].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Event {
+    /// Name of the event.
+    pub name: String,
+    /// Value of the event bits at the end of the tick in which it was triggered.
+    pub value: u128,
+    /// [Simulation time](InitializedGateGraph::now) of the tick in which it was triggered.
+    pub tick: usize,
+}
+
+/// Events recorded by an [InitializedGateGraph].
+#[derive(Debug, Default)]
+pub(super) struct EventLog {
+    sources: Vec<EventSource>,
+    /// State of the trigger of every source at the end of the last tick.
+    pub(super) triggered: Vec<bool>,
+    events: Vec<Event>,
+}
+impl EventLog {
+    pub(super) fn new(sources: Vec<EventSource>) -> Self {
+        Self {
+            triggered: vec![false; sources.len()],
+            sources,
+            events: Vec::new(),
+        }
+    }
+}
+
+impl GateGraphBuilder {
+    /// Declares an event named `name` which gets recorded every time `trigger` becomes active, along with the value
+    /// of the gates in `value` and the tick in which it happened, see [InitializedGateGraph::events].
+    ///
+    /// This is meant for control logic, like the microcode of a CPU, to report what it's doing at the architecture level,
+    /// for example an "instruction_retired" event with the opcode as its value produces an instruction trace
+    /// without having to decode raw signals.
+    ///
+    /// The value is collected like [OutputHandle::u128], so only the first 128 bits are taken into account.
+    /// Keep in mind that observed gates can't be optimized away.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// # use logicsim::WordInput;
+    /// let mut g = GateGraphBuilder::new();
+    /// let opcode = WordInput::new(&mut g, 4, "opcode");
+    /// let retire = g.lever("retire");
+    /// g.event("instruction_retired", retire.bit(), &opcode.bits());
+    ///
+    /// let ig = &mut g.init();
+    /// opcode.set_to(ig, 3);
+    /// ig.pulse_lever_stable(retire);
+    /// opcode.set_to(ig, 5);
+    /// ig.pulse_lever_stable(retire);
+    ///
+    /// let values: Vec<u128> = ig.events().iter().map(|event| event.value).collect();
+    /// assert_eq!(values, [3, 5]);
+    /// assert_eq!(ig.events()[0].name, "instruction_retired");
+    /// ```
+    pub fn event<S: Into<String>>(&mut self, name: S, trigger: GateIndex, value: &[GateIndex]) {
+        let name = name.into();
+        let trigger = self.output1(trigger, format!("{}:trigger", name));
+        let value = self.output(value, name.clone());
+        self.event_sources.push(EventSource {
+            name,
+            trigger,
+            value,
+        });
+    }
+
+    /// Returns true if the trigger or the value of an event reads `gate`.
+    pub(super) fn event_reads(&self, gate: GateIndex) -> bool {
+        self.event_sources.iter().any(|source| {
+            self.output_reads(source.trigger, gate) || self.output_reads(source.value, gate)
+        })
+    }
+}
+
+impl InitializedGateGraph {
+    /// Stores the initial state of every trigger, triggers that start active don't record an event.
+    pub(super) fn arm_events(&mut self) {
+        for i in 0..self.event_log.sources.len() {
+            let trigger = self.get_output(self.event_log.sources[i].trigger).bits[0];
+            self.event_log.triggered[i] = self.value(trigger);
+        }
+    }
+
+    /// Records the events whose trigger became active during the last tick.
+    pub(super) fn record_events(&mut self) {
+        for i in 0..self.event_log.sources.len() {
+            let source = &self.event_log.sources[i];
+            let triggered = self.value(self.get_output(source.trigger).bits[0]);
+            if triggered && !self.event_log.triggered[i] {
+                let event = Event {
+                    name: source.name.clone(),
+                    value: self.collect_u128_lossy(&self.get_output(source.value).bits),
+                    tick: self.ticks,
+                };
+                self.event_log.events.push(event);
+            }
+            self.event_log.triggered[i] = triggered;
+        }
+    }
+
+    /// Returns the events declared with [GateGraphBuilder::event] that have happened since the graph was initialized
+    /// or since the last call to [InitializedGateGraph::take_events], in order.
+    pub fn events(&self) -> &[Event] {
+        &self.event_log.events
+    }
+
+    /// Returns the events that have happened since the graph was initialized or since the last call to this method
+    /// and clears them, see [InitializedGateGraph::events].
+    ///
+    /// Long running simulations should call this regularly, otherwise the events pile up in memory.
+    pub fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.event_log.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{counter, zeros};
+
+    #[test]
+    fn test_event_timestamps() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let count = counter(
+            g,
+            clock.bit(),
+            ON,
+            OFF,
+            ON,
+            reset.bit(),
+            &zeros(3),
+            "counter",
+        );
+        // Triggers every time the counter reaches an odd number.
+        g.event("odd", count[0], &count);
+        let ig = &mut graph.init();
+        ig.pulse_lever_stable(reset);
+        ig.take_events();
+
+        let mut ticks = Vec::new();
+        for _ in 0..6 {
+            ticks.push(ig.ticks);
+            ig.pulse_lever_stable(clock);
+        }
+        let events = ig.take_events();
+        assert_eq!(
+            events.iter().map(|event| event.value).collect::<Vec<_>>(),
+            [1, 3, 5]
+        );
+        // The events happen while the clock is high during the cycles that end at odd numbers.
+        for (event, cycle) in events.iter().zip([0, 2, 4].iter()) {
+            assert!(event.tick > ticks[*cycle] && event.tick <= ticks[cycle + 1]);
+        }
+        assert!(ig.events().is_empty());
+    }
+
+    #[test]
+    fn test_event_optimized_trigger() {
+        let mut g = GateGraphBuilder::new();
+        let l = g.lever("l");
+        let and = g.and2(l.bit(), ON, "and");
+        g.event("on", ON, &[]);
+        g.event("and", and, &[l.bit()]);
+
+        let ig = &mut g.init();
+        ig.flip_lever_stable(l);
+        ig.flip_lever_stable(l);
+        ig.flip_lever_stable(l);
+        let events = ig.events();
+        // Triggers that are active at initialization don't count.
+        assert_eq!(events.len(), 2);
+        assert!(events
+            .iter()
+            .all(|event| event.name == "and" && event.value == 1));
+    }
+}
@@ -0,0 +1,383 @@
+use super::gate::*;
+use super::InitializedGateGraph;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Fault that can be injected in a gate of an [InitializedGateGraph], see [InitializedGateGraph::inject_fault].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Fault {
+    /// The gate has the given state no matter what its dependencies do, until the fault is
+    /// [cleared](InitializedGateGraph::clear_fault).
+    StuckAt(bool),
+    /// The state of the gate gets inverted once, like a single event upset. The gate goes back to computing its
+    /// state normally the next time one of its dependencies changes, in a latch it sticks.
+    BitFlip,
+}
+
+/// Faults injected in an [InitializedGateGraph], they are part of its [snapshots](super::StateSnapshot).
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(super) struct Faults {
+    /// Gates stuck at a state.
+    stuck: BTreeMap<GateIndex, bool>,
+    /// Flipped gates that haven't propagated yet, their dependents get updated instead of the gates themselves.
+    flipped: Vec<GateIndex>,
+    /// Faults by the simulation time at which they are injected, see [InitializedGateGraph::inject_fault_at].
+    scheduled: BTreeMap<usize, Vec<(GateIndex, Fault)>>,
+}
+impl Faults {
+    /// Returns true if there are no active or scheduled faults.
+    pub(super) fn is_empty(&self) -> bool {
+        self.stuck.is_empty() && self.flipped.is_empty() && self.scheduled.is_empty()
+    }
+
+    /// Returns the state `gate` is stuck at, if it is.
+    #[inline(always)]
+    pub(super) fn stuck(&self, gate: GateIndex) -> Option<bool> {
+        if self.stuck.is_empty() {
+            None
+        } else {
+            self.stuck.get(&gate).copied()
+        }
+    }
+
+    /// Returns true if `gate` has a state that doesn't come from its dependencies, a flipped gate only does until
+    /// this is called for it.
+    pub(super) fn take_override(&mut self, gate: GateIndex) -> bool {
+        if self.stuck.contains_key(&gate) {
+            return true;
+        }
+        match self.flipped.iter().position(|flipped| *flipped == gate) {
+            Some(i) => {
+                self.flipped.swap_remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Result of [InitializedGateGraph::fault_coverage].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FaultCoverage {
+    /// Number of faults tried, stuck at 0 and stuck at 1 for every [fault site](InitializedGateGraph::fault_sites).
+    pub faults: usize,
+    /// Faults for which the test still passed.
+    pub undetected: Vec<(GateIndex, Fault)>,
+    /// Full names of the gates in `undetected`, the same ones [InitializedGateGraph::dump_dot] uses.
+    pub names: Vec<String>,
+}
+impl FaultCoverage {
+    /// Returns the number of faults for which the test failed.
+    pub fn detected(&self) -> usize {
+        self.faults - self.undetected.len()
+    }
+
+    /// Returns the fraction of the faults that were detected, between 0 and 1.
+    pub fn ratio(&self) -> f64 {
+        if self.faults == 0 {
+            1.0
+        } else {
+            self.detected() as f64 / self.faults as f64
+        }
+    }
+}
+
+impl InitializedGateGraph {
+    /// Injects `fault` in `gate`, it takes effect like a lever change, see
+    /// [update_lever](InitializedGateGraph::update_lever).
+    ///
+    /// Faults make it possible to check how a circuit behaves when the hardware fails, like whether a
+    /// [triplicated](super::GateGraphBuilder::triplicate) module still produces the right result with a broken
+    /// replica. Use [OutputHandle::gates](super::OutputHandle::gates) to find the gates to inject faults in,
+    /// [fault_sites](InitializedGateGraph::fault_sites) returns all of them.
+    ///
+    /// Faults only affect the simulation, methods that evaluate the circuit on their own like
+    /// [tick_vector](InitializedGateGraph::tick_vector) don't see them, and they are not part of
+    /// [snapshots](InitializedGateGraph::snapshot).
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,Fault};
+    /// let mut g = GateGraphBuilder::new();
+    /// let a = g.lever("a");
+    /// let b = g.lever("b");
+    /// let and = g.and2(a.bit(), b.bit(), "and");
+    /// let not = g.not1(and, "not");
+    /// let and = g.output1(and, "and");
+    /// let not = g.output1(not, "not");
+    ///
+    /// let ig = &mut g.init();
+    /// let gate = and.gates(ig)[0];
+    /// ig.inject_fault(gate, Fault::StuckAt(true));
+    /// ig.run_until_stable(10).unwrap();
+    /// assert_eq!(and.b0(ig), true);
+    /// assert_eq!(not.b0(ig), false);
+    ///
+    /// ig.set_lever_stable(a);
+    /// ig.set_lever_stable(b);
+    /// ig.reset_lever_stable(a);
+    /// assert_eq!(and.b0(ig), true);
+    ///
+    /// ig.clear_fault(gate);
+    /// ig.run_until_stable(10).unwrap();
+    /// assert_eq!(and.b0(ig), false);
+    /// assert_eq!(not.b0(ig), true);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `gate` is not in the graph, or if it's a lever or a constant.
+    pub fn inject_fault(&mut self, gate: GateIndex, fault: Fault) {
+        assert!(gate.idx < self.len(), "The gate is not in the graph");
+        assert!(
+            !gate.is_const() && !self.nodes[gate.idx].ty.is_lever(),
+            "Levers and constants can't have faults"
+        );
+        let state = match fault {
+            Fault::StuckAt(value) => {
+                self.faults.stuck.insert(gate, value);
+                value
+            }
+            Fault::BitFlip => {
+                self.faults.flipped.push(gate);
+                !self.value(gate)
+            }
+        };
+        if self.value(gate) != state {
+            self.state.set(gate.idx, state);
+            self.pending_updates.push(gate);
+        }
+    }
+
+    /// Injects `fault` in `gate` at the start of the first [tick](InitializedGateGraph::tick) that starts at
+    /// [simulation time](InitializedGateGraph::now) `tick` or later, see
+    /// [inject_fault](InitializedGateGraph::inject_fault).
+    ///
+    /// Faults get injected by ticks, [run_until_stable](InitializedGateGraph::run_until_stable) doesn't tick an
+    /// [idle](InitializedGateGraph::is_idle) graph, [step_clocks](InitializedGateGraph::step_clocks) always does.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `gate` is not in the graph, or if it's a lever or a constant.
+    pub fn inject_fault_at(&mut self, gate: GateIndex, fault: Fault, tick: usize) {
+        assert!(gate.idx < self.len(), "The gate is not in the graph");
+        assert!(
+            !gate.is_const() && !self.nodes[gate.idx].ty.is_lever(),
+            "Levers and constants can't have faults"
+        );
+        self.faults
+            .scheduled
+            .entry(tick)
+            .or_default()
+            .push((gate, fault));
+    }
+
+    /// Removes the stuck at fault of `gate`, it goes back to computing its state from its dependencies.
+    pub fn clear_fault(&mut self, gate: GateIndex) {
+        if self.faults.stuck.remove(&gate).is_some() {
+            self.pending_updates.push(gate);
+        }
+    }
+
+    /// Removes every stuck at fault and every fault that hasn't been injected yet.
+    pub fn clear_faults(&mut self) {
+        self.faults.scheduled.clear();
+        let stuck: Vec<_> = self.faults.stuck.keys().copied().collect();
+        for gate in stuck {
+            self.clear_fault(gate);
+        }
+    }
+
+    /// Injects the faults scheduled with [inject_fault_at](InitializedGateGraph::inject_fault_at) that are due.
+    pub(super) fn inject_scheduled_faults(&mut self) {
+        while let Some(entry) = self.faults.scheduled.first_entry() {
+            if *entry.key() > self.ticks {
+                break;
+            }
+            for (gate, fault) in entry.remove() {
+                self.inject_fault(gate, fault);
+            }
+        }
+    }
+
+    /// Returns every gate that can have a fault, every gate but the levers and constants, in index order.
+    pub fn fault_sites(&self) -> Vec<GateIndex> {
+        (0..self.len())
+            .map(|i| gi!(i))
+            .filter(|gate| !gate.is_const() && !self.nodes[gate.idx].ty.is_lever())
+            .collect()
+    }
+
+    /// Measures how many faults `test` detects: for every [fault site](InitializedGateGraph::fault_sites),
+    /// stuck at 0 and stuck at 1, the state of the graph is restored, the fault injected and `test` called,
+    /// the fault is detected if it returns false.
+    ///
+    /// `test` should return true for the graph without faults. The state of the graph is restored afterwards.
+    ///
+    /// Gates that survived the optimizations are the fault sites, initialize the graph with
+    /// [init_unoptimized](super::GateGraphBuilder::init_unoptimized) to keep every gate as it was built. See
+    /// [TestBench::fault_coverage](crate::testing::TestBench::fault_coverage) to measure the coverage of test vectors.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,Fault};
+    /// let mut g = GateGraphBuilder::new();
+    /// let a = g.lever("a");
+    /// let b = g.lever("b");
+    /// let and = g.and2(a.bit(), b.bit(), "and");
+    /// let output = g.output1(and, "and");
+    ///
+    /// let ig = &mut g.init();
+    /// // Only checks that the output is off with both levers off.
+    /// let coverage = ig.fault_coverage(|ig| {
+    ///     ig.run_until_stable(10).is_ok() && !output.b0(ig)
+    /// });
+    /// assert_eq!(coverage.faults, 2);
+    /// assert_eq!(coverage.undetected, [(output.gates(ig)[0], Fault::StuckAt(false))]);
+    /// assert_eq!(coverage.ratio(), 0.5);
+    /// ```
+    pub fn fault_coverage<F: FnMut(&mut InitializedGateGraph) -> bool>(
+        &mut self,
+        mut test: F,
+    ) -> FaultCoverage {
+        let start = self.snapshot();
+        let mut faults = 0;
+        let mut undetected = Vec::new();
+        for gate in self.fault_sites() {
+            for value in [false, true] {
+                let fault = Fault::StuckAt(value);
+                self.restore(&start);
+                self.inject_fault(gate, fault);
+                faults += 1;
+                if test(self) {
+                    undetected.push((gate, fault));
+                }
+                self.clear_faults();
+            }
+        }
+        self.restore(&start);
+        let names = undetected
+            .iter()
+            .map(|(gate, _)| self.full_name(*gate))
+            .collect();
+        FaultCoverage {
+            faults,
+            undetected,
+            names,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{adder, WordInput};
+
+    #[test]
+    fn test_bit_flip_sticks_in_latches() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let set = g.lever("set");
+        let reset = g.lever("reset");
+        let q = g.nor2(reset.bit(), OFF, "q");
+        let nq = g.nor2(set.bit(), q, "nq");
+        g.d1(q, nq);
+        let q = g.output1(q, "q");
+        let l = g.lever("l");
+        let not = g.not1(l.bit(), "not");
+        let not = g.output1(not, "not");
+        let ig = &mut graph.init();
+        ig.pulse_lever_stable(reset);
+        assert_eq!(q.b0(ig), false);
+
+        ig.inject_fault(q.gates(ig)[0], Fault::BitFlip);
+        ig.run_until_stable(10).unwrap();
+        assert_eq!(q.b0(ig), true);
+        ig.pulse_lever_stable(reset);
+        assert_eq!(q.b0(ig), false);
+
+        // Combinational gates go back to normal when their dependencies change.
+        ig.inject_fault(not.gates(ig)[0], Fault::BitFlip);
+        ig.run_until_stable(10).unwrap();
+        assert_eq!(not.b0(ig), false);
+        ig.set_lever_stable(l);
+        assert_eq!(not.b0(ig), false);
+        ig.reset_lever_stable(l);
+        assert_eq!(not.b0(ig), true);
+    }
+
+    #[test]
+    fn test_scheduled_faults() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let l = g.lever("l");
+        let not = g.not1(l.bit(), "not");
+        let output = g.output1(not, "not");
+        let ig = &mut graph.init();
+        let gate = output.gates(ig)[0];
+
+        let start = ig.now();
+        ig.inject_fault_at(gate, Fault::StuckAt(false), start + 3);
+        ig.step_clocks(2);
+        assert_eq!(output.b0(ig), true);
+        ig.step_clocks(2);
+        assert_eq!(output.b0(ig), false);
+        ig.clear_faults();
+        ig.run_until_stable(10).unwrap();
+        assert_eq!(output.b0(ig), true);
+
+        ig.inject_fault_at(gate, Fault::StuckAt(false), ig.now() + 1);
+        ig.clear_faults();
+        ig.step_clocks(5);
+        assert_eq!(output.b0(ig), true);
+    }
+
+    #[test]
+    fn test_tmr_masks_single_faults() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let a = WordInput::new(g, 2, "a");
+        let b = WordInput::new(g, 2, "b");
+        let sum = adder(g, OFF, &a.bits(), &b.bits(), "adder");
+        let output = g.output(&sum, "sum");
+        let inputs: Vec<_> = a.bits().into_iter().chain(b.bits()).collect();
+        let replicas = g.triplicate(&inputs, &sum, "adder");
+        let replica = g.output(&replicas[0], "replica");
+        let ig = &mut graph.init_unoptimized();
+
+        let mut exhaustive_test = |ig: &mut InitializedGateGraph| {
+            (0..16).all(|i| {
+                a.set_to(ig, i % 4);
+                b.set_to(ig, i / 4);
+                ig.run_until_stable(DEFAULT_STABLE_MAX).is_ok()
+                    && output.u8(ig) == (i % 4 + i / 4) as u8 % 4
+            })
+        };
+        assert!(exhaustive_test(ig));
+
+        // Faults in the replicas are masked by the voters.
+        let replica_gate = replica.gates(ig)[0];
+        ig.inject_fault(replica_gate, Fault::StuckAt(true));
+        assert!(exhaustive_test(ig));
+        ig.clear_faults();
+
+        let coverage = ig.fault_coverage(&mut exhaustive_test);
+        assert!(coverage.detected() > 0);
+        assert!(coverage
+            .undetected
+            .iter()
+            .any(|(gate, _)| *gate == replica_gate));
+        assert_eq!(coverage.names.len(), coverage.undetected.len());
+        // Only the voters are single points of failure.
+        let voters = output.gates(ig).to_vec();
+        for (gate, _) in &coverage.undetected {
+            assert!(!voters.contains(gate));
+        }
+        for voter in &voters {
+            assert!(!coverage.undetected.iter().any(|(gate, _)| gate == voter));
+        }
+    }
+}
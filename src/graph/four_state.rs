@@ -0,0 +1,501 @@
+use super::custom_gate::CUSTOM_GATE_MAX_ARITY;
+use super::gate::*;
+use super::handles::*;
+use super::{InitializedGateGraph, SimulationError};
+use std::fmt::{self, Display, Formatter};
+
+/// Value of a gate in a [FourStateSimulation].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Logic {
+    /// Known to be off.
+    Zero,
+    /// Known to be on.
+    One,
+    /// Unknown, it could be either.
+    X,
+    /// High impedance, nothing is driving it.
+    Z,
+}
+use Logic::*;
+
+impl Logic {
+    /// Returns [One] if `value` is true and [Zero] otherwise.
+    pub fn from_bool(value: bool) -> Self {
+        if value {
+            One
+        } else {
+            Zero
+        }
+    }
+
+    /// Returns the value of `self` if it's [Zero] or [One], [None] otherwise.
+    pub fn to_bool(self) -> Option<bool> {
+        match self {
+            Zero => Some(false),
+            One => Some(true),
+            X | Z => None,
+        }
+    }
+
+    /// Returns true if `self` is [Zero] or [One].
+    pub fn is_known(self) -> bool {
+        self.to_bool().is_some()
+    }
+
+    /// Returns the value `self` has when a gate reads it, nobody driving an input is as good as not knowing it.
+    fn read(self) -> Self {
+        match self {
+            Z => X,
+            value => value,
+        }
+    }
+
+    fn and(self, other: Self) -> Self {
+        match (self.read(), other.read()) {
+            (Zero, _) | (_, Zero) => Zero,
+            (One, One) => One,
+            _ => X,
+        }
+    }
+
+    fn or(self, other: Self) -> Self {
+        match (self.read(), other.read()) {
+            (One, _) | (_, One) => One,
+            (Zero, Zero) => Zero,
+            _ => X,
+        }
+    }
+
+    fn xor(self, other: Self) -> Self {
+        match (self.to_bool(), other.to_bool()) {
+            (Some(a), Some(b)) => Logic::from_bool(a ^ b),
+            _ => X,
+        }
+    }
+
+    fn not(self) -> Self {
+        match self {
+            Zero => One,
+            One => Zero,
+            X | Z => X,
+        }
+    }
+
+    /// Returns the value of a wire driven by `self` and `other`.
+    fn resolve(self, other: Self) -> Self {
+        match (self, other) {
+            (Z, value) | (value, Z) => value,
+            (a, b) if a == b => a,
+            _ => X,
+        }
+    }
+}
+
+impl Display for Logic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let c = match self {
+            Zero => '0',
+            One => '1',
+            X => 'x',
+            Z => 'z',
+        };
+        write!(f, "{}", c)
+    }
+}
+
+/// Simulation of an [InitializedGateGraph] where every gate can also be unknown ([X](Logic::X)) or not driven
+/// by anything ([Z](Logic::Z)), returned by [InitializedGateGraph::four_state].
+///
+/// It starts with every gate unknown except for constants and levers, so gates whose state depends on
+/// how the graph was initialized, like the ones in a latch that hasn't been reset, stay unknown
+/// and the unknown spreads to everything that depends on them.
+/// This catches reset bugs that the normal simulation hides by starting every gate at 0.
+///
+/// [Tri-state buffers](super::GateGraphBuilder::tristate) that are not enabled are not driving anything,
+/// the dependencies of a gate that are tri-state buffers are resolved like a wire before the gate reads them:
+/// a bus nobody drives is Z and a bus with drivers that disagree is X.
+///
+/// It's much slower than the normal simulation, it's meant for tests.
+pub struct FourStateSimulation<'a> {
+    graph: &'a InitializedGateGraph,
+    values: Vec<Logic>,
+    pending: Vec<GateIndex>,
+    ticks: usize,
+}
+
+impl InitializedGateGraph {
+    /// Returns a [FourStateSimulation] of the graph, levers start with their current value and every other gate
+    /// starts unknown. The state of the graph is left untouched.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,Logic};
+    /// # use logicsim::sr_latch;
+    /// let mut g = GateGraphBuilder::new();
+    /// let s = g.lever("s");
+    /// let r = g.lever("r");
+    /// let q = sr_latch(&mut g, s.bit(), r.bit(), "latch");
+    /// let q_output = g.output1(q, "q");
+    ///
+    /// let ig = &mut g.init();
+    /// // The normal simulation makes up a value.
+    /// assert_eq!(q_output.b0(ig), false);
+    ///
+    /// let mut sim = ig.four_state();
+    /// sim.run_until_stable(10).unwrap();
+    /// assert_eq!(sim.output(q_output), [Logic::X]);
+    ///
+    /// // Reset.
+    /// sim.set_lever(r, Logic::One);
+    /// sim.run_until_stable(10).unwrap();
+    /// sim.set_lever(r, Logic::Zero);
+    /// sim.run_until_stable(10).unwrap();
+    /// assert_eq!(sim.output(q_output), [Logic::Zero]);
+    /// ```
+    pub fn four_state(&self) -> FourStateSimulation<'_> {
+        let mut values = vec![X; self.nodes.len()];
+        values[OFF.idx] = Zero;
+        values[ON.idx] = One;
+        for lever in self.lever_handles.iter() {
+            values[lever.idx] = Logic::from_bool(self.value(*lever));
+        }
+        FourStateSimulation {
+            graph: self,
+            values,
+            pending: (0..self.nodes.len())
+                .filter(|i| !self.nodes[*i].dependencies.is_empty())
+                .map(|i| gi!(i))
+                .collect(),
+            ticks: 0,
+        }
+    }
+}
+
+impl<'a> FourStateSimulation<'a> {
+    /// Returns the value of `gate`.
+    pub fn value(&self, gate: GateIndex) -> Logic {
+        self.values[gate.idx]
+    }
+
+    /// Returns the value of every bit of `output`, bit 0 first.
+    pub fn output(&self, output: OutputHandle) -> Vec<Logic> {
+        self.graph
+            .get_output(output)
+            .bits
+            .iter()
+            .map(|bit| self.value(*bit))
+            .collect()
+    }
+
+    /// Returns the value of `output` as a string, most significant bit first, like "10xz".
+    pub fn output_string(&self, output: OutputHandle) -> String {
+        self.output(output)
+            .iter()
+            .rev()
+            .map(|value| value.to_string())
+            .collect()
+    }
+
+    /// Returns the value of `output` like [OutputHandle::u128], [None] if any bit is not [Zero] or [One].
+    pub fn output_u128(&self, output: OutputHandle) -> Option<u128> {
+        self.output(output)
+            .iter()
+            .take(128)
+            .enumerate()
+            .try_fold(0, |acc, (i, value)| {
+                Some(acc | (value.to_bool()? as u128) << i)
+            })
+    }
+
+    /// Sets `lever` to `value`, it will propagate during the next [tick](FourStateSimulation::tick).
+    pub fn set_lever(&mut self, lever: LeverHandle, value: Logic) {
+        let idx = self.graph.lever_handles[lever.handle];
+        if self.values[idx.idx] != value {
+            self.values[idx.idx] = value;
+            self.pending
+                .extend(self.graph.nodes[idx.idx].dependents.iter().copied());
+        }
+    }
+
+    /// Returns the number of ticks since the simulation was created.
+    pub fn ticks(&self) -> usize {
+        self.ticks
+    }
+
+    /// Propagates the pending changes like [InitializedGateGraph::tick], gates that change twice in the same tick
+    /// and delay gates are updated in the next one.
+    ///
+    /// Returns true if there are no pending changes left.
+    pub fn tick(&mut self) -> bool {
+        let nodes = &self.graph.nodes;
+        let mut queue = std::mem::take(&mut self.pending);
+        let mut updated = vec![false; nodes.len()];
+        let mut delayed = Vec::new();
+        while let Some(idx) = queue.pop() {
+            let node = &nodes[idx.idx];
+            if node.ty.is_delay() {
+                delayed.push(idx);
+                continue;
+            }
+            if updated[idx.idx] {
+                self.pending.push(idx);
+                continue;
+            }
+            let new_value = self.evaluate(node);
+            if new_value != self.values[idx.idx] {
+                updated[idx.idx] = true;
+                self.values[idx.idx] = new_value;
+                queue.extend(node.dependents.iter().copied());
+            }
+        }
+        for idx in delayed {
+            let node = &nodes[idx.idx];
+            let new_value = self.values[node.dependencies[0].idx];
+            if new_value != self.values[idx.idx] {
+                self.values[idx.idx] = new_value;
+                self.pending.extend(node.dependents.iter().copied());
+            }
+        }
+        self.pending.sort_unstable();
+        self.pending.dedup();
+        self.ticks += 1;
+        self.pending.is_empty()
+    }
+
+    /// Ticks until there are no pending changes and returns the number of ticks it took.
+    ///
+    /// Returns [SimulationError::NotStabilized] if it doesn't stabilize after `max` ticks,
+    /// with the gates that are still pending.
+    pub fn run_until_stable(&mut self, max: usize) -> Result<usize, SimulationError> {
+        for i in 1..=max {
+            if self.tick() {
+                return Ok(i);
+            }
+        }
+        Err(SimulationError::not_stabilized(
+            self.graph,
+            max,
+            self.pending.clone(),
+        ))
+    }
+
+    /// Returns the new value of `node` from the values of its dependencies.
+    fn evaluate(&self, node: &InitializedGate) -> Logic {
+        let dependencies = &node.dependencies;
+        match node.ty {
+            GateType::On => One,
+            GateType::Off => Zero,
+            GateType::Lever | GateType::Delay => {
+                unreachable!("Levers and delay gates are not evaluated")
+            }
+            GateType::Not => self.value(dependencies[0]).not(),
+            GateType::TriState => match self.value(dependencies[1]) {
+                Zero => Z,
+                One => self.value(dependencies[0]).read(),
+                X | Z => X,
+            },
+            GateType::Custom(id) => self.evaluate_custom(id, dependencies),
+            GateType::Or
+            | GateType::Nor
+            | GateType::And
+            | GateType::Nand
+            | GateType::Xor
+            | GateType::Xnor => {
+                let is_tristate =
+                    |dependency: &&GateIndex| self.graph.nodes[dependency.idx].ty.is_tristate();
+                let wire = dependencies
+                    .iter()
+                    .filter(is_tristate)
+                    .fold(Z, |wire, dependency| wire.resolve(self.value(*dependency)));
+                let (operation, init): (fn(Logic, Logic) -> Logic, Logic) = match node.ty {
+                    GateType::Or | GateType::Nor => (Logic::or, Zero),
+                    GateType::And | GateType::Nand => (Logic::and, One),
+                    _ => (Logic::xor, Zero),
+                };
+                let mut result = dependencies
+                    .iter()
+                    .filter(|dependency| !is_tristate(dependency))
+                    .fold(init, |acc, dependency| {
+                        operation(acc, self.value(*dependency))
+                    });
+                // A bus nobody drives stays undriven.
+                if wire == Z && node.ty == GateType::Or && result == Zero {
+                    return Z;
+                }
+                if wire != Z {
+                    result = operation(result, wire);
+                }
+                if node.ty.is_negated() {
+                    result.not()
+                } else {
+                    result
+                }
+            }
+        }
+    }
+
+    /// Evaluates a custom gate with every possible value of its unknown dependencies,
+    /// the result is only known if they all agree.
+    fn evaluate_custom(&self, id: u8, dependencies: &[GateIndex]) -> Logic {
+        let eval = self.graph.custom_gates[id as usize].eval;
+        let mut inputs = [false; CUSTOM_GATE_MAX_ARITY];
+        let mut unknown = Vec::new();
+        for (i, dependency) in dependencies.iter().enumerate() {
+            match self.value(*dependency).to_bool() {
+                Some(value) => inputs[i] = value,
+                None => unknown.push(i),
+            }
+        }
+        let inputs = &mut inputs[..dependencies.len()];
+        let mut result = None;
+        for assignment in 0..1usize << unknown.len() {
+            for (bit, i) in unknown.iter().enumerate() {
+                inputs[*i] = assignment >> bit & 1 == 1;
+            }
+            let value = eval(inputs);
+            match result {
+                None => result = Some(value),
+                Some(result) if result != value => return X,
+                _ => {}
+            }
+        }
+        Logic::from_bool(result.unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::Logic::*;
+    use crate::circuits::{sr_latch, Bus, WordInput};
+
+    #[test]
+    fn test_logic() {
+        assert_eq!(Zero.and(X), Zero);
+        assert_eq!(One.and(Z), X);
+        assert_eq!(One.or(X), One);
+        assert_eq!(Zero.or(X), X);
+        assert_eq!(One.xor(Zero), One);
+        assert_eq!(One.xor(X), X);
+        assert_eq!(Z.not(), X);
+        assert_eq!(Z.resolve(One), One);
+        assert_eq!(One.resolve(One), One);
+        assert_eq!(One.resolve(Zero), X);
+        assert_eq!(Z.resolve(Z), Z);
+    }
+
+    #[test]
+    fn test_unreset_latch() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let s = g.lever("s");
+        let r = g.lever("r");
+        let q = sr_latch(g, s.bit(), r.bit(), "latch");
+        let a = g.lever("a");
+        let and = g.and2(q, a.bit(), "and");
+        let or = g.or2(q, a.bit(), "or");
+        let and = g.output1(and, "and");
+        let or = g.output1(or, "or");
+        let ig = &mut graph.init();
+        ig.set_lever_stable(a);
+
+        let mut sim = ig.four_state();
+        sim.run_until_stable(10).unwrap();
+        // The unknown propagates, unless it doesn't matter.
+        assert_eq!(sim.output(and), [X]);
+        assert_eq!(sim.output(or), [One]);
+
+        sim.set_lever(a, Zero);
+        sim.run_until_stable(10).unwrap();
+        assert_eq!(sim.output(and), [Zero]);
+        assert_eq!(sim.output(or), [X]);
+
+        sim.set_lever(s, One);
+        sim.run_until_stable(10).unwrap();
+        sim.set_lever(s, Zero);
+        sim.run_until_stable(10).unwrap();
+        assert_eq!(sim.output(or), [One]);
+        assert_eq!(sim.output_u128(and), Some(0));
+    }
+
+    #[test]
+    fn test_tristate_bus() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let a = WordInput::new(g, 4, "a");
+        let b = WordInput::new(g, 4, "b");
+        let enable_a = g.lever("enable_a");
+        let enable_b = g.lever("enable_b");
+        let bus = Bus::new(g, 4, "bus");
+        bus.connect_tristate(g, enable_a.bit(), &a.bits());
+        bus.connect_tristate(g, enable_b.bit(), &b.bits());
+        let output = g.output(bus.bits(), "bus");
+        let ig = &mut graph.init();
+        a.set_to(ig, 0b1010);
+        b.set_to(ig, 0b1001);
+        ig.run_until_stable(10).unwrap();
+
+        let mut sim = ig.four_state();
+        sim.run_until_stable(10).unwrap();
+        assert_eq!(sim.output_string(output), "zzzz");
+        assert_eq!(sim.output_u128(output), None);
+
+        sim.set_lever(enable_a, One);
+        sim.run_until_stable(10).unwrap();
+        assert_eq!(sim.output_string(output), "1010");
+        assert_eq!(sim.output_u128(output), Some(0b1010));
+
+        sim.set_lever(enable_b, One);
+        sim.run_until_stable(10).unwrap();
+        assert_eq!(sim.output_string(output), "10xx");
+
+        sim.set_lever(enable_a, Zero);
+        sim.set_lever(enable_b, X);
+        sim.run_until_stable(10).unwrap();
+        assert_eq!(sim.output_string(output), "xxxx");
+    }
+
+    #[test]
+    fn test_custom_and_delay() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let mux = g.register_gate(
+            "mux",
+            3,
+            |inputs| {
+                if inputs[0] {
+                    inputs[2]
+                } else {
+                    inputs[1]
+                }
+            },
+        );
+        let select = g.lever("select");
+        let a = g.lever("a");
+        let b = g.lever("b");
+        let gate = g.custom(mux, &[select.bit(), a.bit(), b.bit()], "mux");
+        let delayed = g.delay(gate, "delay");
+        let not = g.not1(delayed, "not");
+        let output = g.output1(delayed, "output");
+        let not = g.output1(not, "not");
+        let ig = &mut graph.init();
+
+        let mut sim = ig.four_state();
+        sim.set_lever(select, X);
+        sim.run_until_stable(10).unwrap();
+        // Both inputs are 0, select doesn't matter.
+        assert_eq!(sim.output(output), [Zero]);
+
+        sim.set_lever(a, One);
+        sim.run_until_stable(10).unwrap();
+        assert_eq!(sim.output(output), [X]);
+
+        sim.set_lever(b, One);
+        sim.tick();
+        // The delay gate updates at the end of the tick and its dependents see it in the next one.
+        assert_eq!(sim.output(output), [One]);
+        assert_eq!(sim.output(not), [X]);
+        sim.tick();
+        assert_eq!(sim.output(not), [Zero]);
+    }
+}
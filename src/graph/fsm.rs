@@ -0,0 +1,350 @@
+use super::gate::*;
+use super::handles::*;
+use super::{InitializedGateGraph, SimulationError, DEFAULT_STABLE_MAX};
+use crate::data_structures::State;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+/// Maximum number of inputs [InitializedGateGraph::extract_state_machine] will sweep in every state.
+pub const FSM_MAX_INPUTS: usize = 12;
+
+/// Transition of a [StateMachine].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Transition {
+    /// Index of the state before the clock cycle.
+    pub from: usize,
+    /// Value of the inputs during the clock cycle, bit `n` is the state of `inputs[n]`.
+    pub input: u64,
+    /// Index of the state after the clock cycle.
+    pub to: usize,
+}
+
+/// Explicit state transition graph returned by [InitializedGateGraph::extract_state_machine].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StateMachine {
+    /// Value of the state gates in every reachable state, the first one is the initial state.
+    pub states: Vec<Vec<bool>>,
+    /// One transition for every state and input value, sorted by state and then by input.
+    pub transitions: Vec<Transition>,
+    /// Number of inputs of the state machine.
+    pub inputs: usize,
+}
+impl StateMachine {
+    /// Returns the index of the state reached from `state` with `input`.
+    pub fn next(&self, state: usize, input: u64) -> usize {
+        self.transitions[(state << self.inputs) + input as usize].to
+    }
+
+    /// Returns the value of the state gates of `state` as a string of 0s and 1s, gate 0 first.
+    pub fn state_label(&self, state: usize) -> String {
+        self.states[state]
+            .iter()
+            .map(|bit| if *bit { '1' } else { '0' })
+            .collect()
+    }
+
+    /// Dumps the state machine in [dot](https://en.wikipedia.org/wiki/DOT_(graph_description_language)) format
+    /// to `path`, see [StateMachine::write_dot].
+    pub fn dump_dot<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let mut f = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_dot(&mut f)?;
+        f.flush()
+    }
+
+    /// Writes the state machine in dot format to `writer`.
+    ///
+    /// Every state is labeled with the value of its state gates and the initial one is drawn with a double circle.
+    /// Every edge is labeled with the input values that take it, most significant input first.
+    pub fn write_dot<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writeln!(writer, "digraph {{")?;
+        for state in 0..self.states.len() {
+            let shape = if state == 0 { "doublecircle" } else { "circle" };
+            writeln!(
+                writer,
+                "    s{} [label=\"{}\", shape={}]",
+                state,
+                self.state_label(state),
+                shape
+            )?;
+        }
+        // Transitions are sorted by state, so edges between the same states are grouped together per state.
+        let mut edges: Vec<((usize, usize), Vec<String>)> = Vec::new();
+        for transition in &self.transitions {
+            let input = format!("{:01$b}", transition.input, self.inputs);
+            match edges
+                .iter_mut()
+                .find(|(edge, _)| *edge == (transition.from, transition.to))
+            {
+                Some((_, inputs)) => inputs.push(input),
+                None => edges.push(((transition.from, transition.to), vec![input])),
+            }
+        }
+        for ((from, to), inputs) in edges {
+            writeln!(
+                writer,
+                "    s{} -> s{} [label=\"{}\"]",
+                from,
+                to,
+                inputs.join(",")
+            )?;
+        }
+        writeln!(writer, "}}")
+    }
+}
+
+impl InitializedGateGraph {
+    /// Returns the gates of every group of loops in the graph, each one is a block of sequential logic
+    /// whose gates feed back to each other, like a latch or a counter and the adder that increments it.
+    /// Gates that aren't part of any loop are purely combinational and don't show up.
+    ///
+    /// The clusters are in no particular order, the gates in each cluster are sorted,
+    /// use them as the state of [extract_state_machine](InitializedGateGraph::extract_state_machine).
+    pub fn register_clusters(&self) -> Vec<Vec<GateIndex>> {
+        let mut graph = petgraph::Graph::<GateIndex, ()>::new();
+        let index: Vec<_> = (0..self.nodes.len())
+            .map(|i| graph.add_node(gi!(i)))
+            .collect();
+        for (i, node) in self.nodes.iter().enumerate() {
+            for dependency in &node.dependencies {
+                graph.add_edge(index[dependency.idx], index[i], ());
+            }
+        }
+        petgraph::algo::tarjan_scc(&graph)
+            .into_iter()
+            .filter(|scc| {
+                scc.len() > 1 || {
+                    let gate = graph[scc[0]];
+                    self.nodes[gate.idx].dependencies.contains(&gate)
+                }
+            })
+            .map(|scc| {
+                let mut cluster: Vec<GateIndex> = scc.into_iter().map(|node| graph[node]).collect();
+                cluster.sort_unstable();
+                cluster
+            })
+            .collect()
+    }
+
+    /// Extracts the explicit state transition graph of the sequential block whose state is stored in `state`,
+    /// for example a cluster returned by [register_clusters](InitializedGateGraph::register_clusters).
+    ///
+    /// Starting from the state after pulsing `reset`, or from the current state if it's [None],
+    /// every reachable state is visited and for each one of them the clock is pulsed once with every
+    /// possible value of `inputs`. States are read with the clock and every input inactive, two states are
+    /// the same if all the gates in `state` have the same value.
+    ///
+    /// The state of the graph is restored at the end, peripherals, subscriptions, events and traces are paused
+    /// in the meantime.
+    ///
+    /// Returns an error if there are more than `max_states` reachable states or if the graph doesn't stabilize.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// # use logicsim::{counter,zeros,ON,OFF};
+    /// let mut g = GateGraphBuilder::new();
+    /// let clock = g.lever("clock");
+    /// let enable = g.lever("enable");
+    /// let reset = g.lever("reset");
+    /// let count = counter(&mut g, clock.bit(), enable.bit(), OFF, ON, reset.bit(), &zeros(2), "counter");
+    /// g.output(&count, "count");
+    ///
+    /// let ig = &mut g.init();
+    /// let state: Vec<_> = ig.register_clusters().concat();
+    /// let fsm = ig
+    ///     .extract_state_machine(&state, &[enable], clock, Some(reset), 16)
+    ///     .unwrap();
+    ///
+    /// // A 2 bit counter that only counts when enabled.
+    /// assert_eq!(fsm.states.len(), 4);
+    /// let mut current = 0;
+    /// for _ in 0..4 {
+    ///     assert_eq!(fsm.next(current, 0), current);
+    ///     current = fsm.next(current, 1);
+    /// }
+    /// assert_eq!(current, 0);
+    /// # let mut dot = Vec::new();
+    /// # fsm.write_dot(&mut dot).unwrap();
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `inputs.len()` > [FSM_MAX_INPUTS].
+    pub fn extract_state_machine(
+        &mut self,
+        state: &[GateIndex],
+        inputs: &[LeverHandle],
+        clock: LeverHandle,
+        reset: Option<LeverHandle>,
+        max_states: usize,
+    ) -> Result<StateMachine, SimulationError> {
+        assert!(
+            inputs.len() <= FSM_MAX_INPUTS,
+            "Refusing to sweep {} inputs, the maximum is {}",
+            inputs.len(),
+            FSM_MAX_INPUTS
+        );
+        self.with_restored_state(|ig| ig.explore_states(state, inputs, clock, reset, max_states))
+    }
+
+    /// Breadth first search of the states reachable from the initial one, see [InitializedGateGraph::extract_state_machine].
+    fn explore_states(
+        &mut self,
+        state: &[GateIndex],
+        inputs: &[LeverHandle],
+        clock: LeverHandle,
+        reset: Option<LeverHandle>,
+        max_states: usize,
+    ) -> Result<StateMachine, SimulationError> {
+        // Sets the clock and the inputs to inactive and returns the value of the state gates.
+        let settle = |ig: &mut Self| -> Result<Vec<bool>, SimulationError> {
+            ig.update_lever_inner(clock, false);
+            for input in inputs {
+                ig.update_lever_inner(*input, false);
+            }
+            ig.run_until_stable(DEFAULT_STABLE_MAX)?;
+            Ok(state.iter().map(|gate| ig.value(*gate)).collect())
+        };
+
+        if let Some(reset) = reset {
+            self.update_lever_inner(reset, true);
+            self.run_until_stable(DEFAULT_STABLE_MAX)?;
+            self.update_lever_inner(reset, false);
+        }
+        let initial = settle(self)?;
+
+        let mut fsm = StateMachine {
+            states: vec![initial.clone()],
+            transitions: Vec::new(),
+            inputs: inputs.len(),
+        };
+        // Full snapshot of the graph in every state, to go back to it.
+        let mut snapshots: Vec<State> = vec![self.state.clone()];
+        let mut indices: HashMap<Vec<bool>, usize> = HashMap::new();
+        indices.insert(initial, 0);
+
+        let mut from = 0;
+        while from < fsm.states.len() {
+            for input in 0..1u64 << inputs.len() {
+                self.state = snapshots[from].clone();
+                for (i, lever) in inputs.iter().enumerate() {
+                    self.update_lever_inner(*lever, input >> i & 1 == 1);
+                }
+                self.run_until_stable(DEFAULT_STABLE_MAX)?;
+                self.update_lever_inner(clock, true);
+                self.run_until_stable(DEFAULT_STABLE_MAX)?;
+                let next = settle(self)?;
+
+                let to = match indices.get(&next) {
+                    Some(to) => *to,
+                    None => {
+                        if fsm.states.len() == max_states {
+                            return Err(SimulationError::TooManyStates { max_states });
+                        }
+                        indices.insert(next.clone(), fsm.states.len());
+                        fsm.states.push(next);
+                        snapshots.push(self.state.clone());
+                        fsm.states.len() - 1
+                    }
+                };
+                fsm.transitions.push(Transition { from, input, to });
+            }
+            from += 1;
+        }
+        Ok(fsm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{counter, WordInput};
+
+    #[test]
+    fn test_register_clusters() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let a = g.lever("a");
+        let b = g.lever("b");
+        // Two independent SR latches and some combinational logic.
+        let q1 = g.nor2(a.bit(), OFF, "q1");
+        let nq1 = g.nor2(b.bit(), q1, "nq1");
+        g.d1(q1, nq1);
+        let q2 = g.nor2(b.bit(), OFF, "q2");
+        let nq2 = g.nor2(a.bit(), q2, "nq2");
+        g.d1(q2, nq2);
+        let and = g.and2(q1, q2, "and");
+        g.output1(and, "and");
+
+        let ig = &graph.init_unoptimized();
+        let mut clusters = ig.register_clusters();
+        clusters.sort();
+        let mut expected = vec![vec![q1, nq1], vec![q2, nq2]];
+        for cluster in &mut expected {
+            cluster.sort_unstable();
+        }
+        assert_eq!(clusters, expected);
+    }
+
+    #[test]
+    fn test_extract_state_machine_loadable_counter() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let clock = g.lever("clock");
+        let write = g.lever("write");
+        let reset = g.lever("reset");
+        let input = WordInput::new(g, 2, "input");
+        let count = counter(
+            g,
+            clock.bit(),
+            ON,
+            write.bit(),
+            ON,
+            reset.bit(),
+            &input.bits(),
+            "counter",
+        );
+        let output = g.output(&count, "count");
+
+        let ig = &mut graph.init();
+        ig.pulse_lever_stable(reset);
+        ig.pulse_lever_stable(clock);
+        let state = ig.register_clusters().concat();
+        let inputs = [write, input.levers()[0], input.levers()[1]];
+        let fsm = ig
+            .extract_state_machine(&state, &inputs, clock, Some(reset), 16)
+            .unwrap();
+
+        // The graph is left as it was.
+        assert_eq!(output.u8(ig), 1);
+
+        assert_eq!(fsm.states.len(), 4);
+        assert_eq!(fsm.transitions.len(), 4 * 8);
+        // Follow the counter by counting and loading values.
+        let mut value = 0;
+        let mut current = 0;
+        for input in [0, 0, 0b111, 0, 0b011, 0b101, 0, 0] {
+            current = fsm.next(current, input);
+            value = if input & 1 == 1 {
+                input >> 1
+            } else {
+                (value + 1) % 4
+            };
+            // Loading 0 always goes back to the initial state.
+            assert_eq!(fsm.next(current, 0b001), 0);
+            assert_eq!(fsm.next(0, 1 | value << 1), current);
+        }
+
+        // Too many states.
+        assert!(ig
+            .extract_state_machine(&state, &inputs, clock, Some(reset), 3)
+            .is_err());
+
+        let mut dot = Vec::new();
+        fsm.write_dot(&mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert_eq!(dot.matches("->").count(), 4 * 4);
+        assert!(dot.contains("doublecircle"));
+    }
+}
@@ -0,0 +1,108 @@
+use super::handles::{LeverHandle, OutputHandle};
+use super::{InitializedGateGraph, SimulationError, DEFAULT_STABLE_MAX};
+use crate::data_structures::SplitMix64;
+
+impl InitializedGateGraph {
+    /// Drives the graph with pseudo random values generated from `seed` for `cycles` cycles
+    /// and returns the values of `outputs` at the end of every cycle, the same seed always drives the same values.
+    ///
+    /// Every cycle, every lever gets set to a random value and the graph runs until it's stable,
+    /// then `outputs` are read as [u128]. `history[cycle][i]` is the value of `outputs[i]` at the end of `cycle`.
+    /// Use a [RandomInput](crate::RandomInput) instead to only randomize some inputs.
+    ///
+    /// Returns an error if the graph doesn't stabilize in [DEFAULT_STABLE_MAX] ticks in any of the cycles.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::{GateGraphBuilder,WordInput,adder,OFF};
+    /// let mut g = GateGraphBuilder::new();
+    /// let a = WordInput::new(&mut g, 8, "a");
+    /// let b = WordInput::new(&mut g, 8, "b");
+    /// let sum = adder(&mut g, OFF, &a.bits(), &b.bits(), "adder");
+    /// let a = g.output(&a.bits(), "a");
+    /// let b = g.output(&b.bits(), "b");
+    /// let sum = g.output(&sum, "sum");
+    ///
+    /// let ig = &mut g.init();
+    /// let history = ig.fuzz(&[a, b, sum], 100, 42).unwrap();
+    /// for cycle in history {
+    ///     assert_eq!(cycle[2], (cycle[0] + cycle[1]) % 256);
+    /// }
+    /// ```
+    pub fn fuzz(
+        &mut self,
+        outputs: &[OutputHandle],
+        cycles: usize,
+        seed: u64,
+    ) -> Result<Vec<Vec<u128>>, SimulationError> {
+        let mut rng = SplitMix64(seed);
+        let levers: Vec<LeverHandle> = (0..self.lever_handles.len())
+            .map(|handle| LeverHandle {
+                handle,
+                idx: self.lever_handles[handle],
+            })
+            .collect();
+        let mut history = Vec::with_capacity(cycles);
+        for _ in 0..cycles {
+            let values: Vec<bool> = levers.iter().map(|_| rng.one_in(2)).collect();
+            self.update_levers(&levers, values.into_iter());
+            self.run_until_stable(DEFAULT_STABLE_MAX)?;
+            history.push(outputs.iter().map(|output| output.u128(self)).collect());
+        }
+        Ok(history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{counter, zeros};
+
+    #[test]
+    fn test_fuzz_counter() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let count = counter(
+            g,
+            clock.bit(),
+            ON,
+            OFF,
+            ON,
+            reset.bit(),
+            &zeros(4),
+            "counter",
+        );
+        let count = g.output(&count, "count");
+        let clock = g.output1(clock.bit(), "clock");
+        let reset = g.output1(reset.bit(), "reset");
+        let ig = &mut graph.init();
+
+        let history = ig.fuzz(&[count, clock, reset], 200, 3).unwrap();
+        assert_eq!(history.len(), 200);
+        for cycles in history.windows(2) {
+            let (previous, current) = (&cycles[0], &cycles[1]);
+            let expected = if current[2] == 1 {
+                0
+            } else if previous[1] == 0 && current[1] == 1 {
+                (previous[0] + 1) % 16
+            } else {
+                previous[0]
+            };
+            assert_eq!(current[0], expected, "{:?}", cycles);
+        }
+
+        // Deterministic.
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let a = g.lever("a");
+        let b = g.lever("b");
+        let xor = g.xor2(a.bit(), b.bit(), "xor");
+        let xor = g.output1(xor, "xor");
+        let ig = &mut graph.init();
+        let first = ig.fuzz(&[xor], 50, 9).unwrap();
+        assert_eq!(ig.fuzz(&[xor], 50, 9).unwrap(), first);
+        assert_ne!(ig.fuzz(&[xor], 50, 10).unwrap(), first);
+    }
+}
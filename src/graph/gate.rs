@@ -1,14 +1,54 @@
 use crate::data_structures::SlabIndex;
 
 use indexmap::IndexSet;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
+use std::cmp::Ordering;
 use std::fmt::{self, Display, Formatter};
+use std::hash::{Hash, Hasher};
 
 /// Represents the index of a logic gate in a [super::GateGraphBuilder].
-#[repr(transparent)]
-#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Ord, PartialOrd)]
+///
+/// In debug builds it also carries the id of the builder and the generation of the slot it was handed out for,
+/// so using it with another builder, or after its gate has been removed, panics with a clear message at the call
+/// site instead of silently pointing at whichever gate has the same index. Neither takes part in comparisons or
+/// hashing.
+#[cfg_attr(not(debug_assertions), repr(transparent))]
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct GateIndex {
     pub(super) idx: usize,
+    /// Generation of the slot in the builder's [Slab](crate::data_structures::Slab) plus 1,
+    /// 0 means the index wasn't handed out by the builder and can't be checked.
+    #[cfg(debug_assertions)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(super) generation: u32,
+    /// Id of the builder that handed it out, 0 means it can be used with any builder, like [OFF] and [ON].
+    #[cfg(debug_assertions)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(super) graph: u32,
+}
+impl PartialEq for GateIndex {
+    fn eq(&self, other: &Self) -> bool {
+        self.idx == other.idx
+    }
+}
+impl Eq for GateIndex {}
+impl Hash for GateIndex {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.idx.hash(state)
+    }
+}
+impl PartialOrd for GateIndex {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for GateIndex {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.idx.cmp(&other.idx)
+    }
 }
 
 /// Returns a new GateIndex from a provided usize.
@@ -30,7 +70,13 @@ pub const ON: GateIndex = gi!(1);
 impl GateIndex {
     /// Returns a new GateIndex from a provided usize.
     pub(super) const fn new(idx: usize) -> GateIndex {
-        GateIndex { idx }
+        GateIndex {
+            idx,
+            #[cfg(debug_assertions)]
+            generation: 0,
+            #[cfg(debug_assertions)]
+            graph: 0,
+        }
     }
 
     /// Returns true if `self` is the index of the OFF constant.
@@ -88,23 +134,17 @@ impl GateIndex {
 
 impl From<SlabIndex> for GateIndex {
     fn from(i: SlabIndex) -> Self {
-        Self {
-            idx: i.i_actually_really_know_what_i_am_doing_and_i_want_the_inner_usize(),
-        }
+        Self::new(i.i_actually_really_know_what_i_am_doing_and_i_want_the_inner_usize())
     }
 }
-impl Into<SlabIndex> for GateIndex {
-    fn into(self) -> SlabIndex {
-        SlabIndex::i_actually_really_know_what_i_am_doing_and_i_want_to_construct_from_usize(
-            self.idx,
-        )
+impl From<GateIndex> for SlabIndex {
+    fn from(i: GateIndex) -> Self {
+        SlabIndex::i_actually_really_know_what_i_am_doing_and_i_want_to_construct_from_usize(i.idx)
     }
 }
-impl Into<SlabIndex> for &GateIndex {
-    fn into(self) -> SlabIndex {
-        SlabIndex::i_actually_really_know_what_i_am_doing_and_i_want_to_construct_from_usize(
-            self.idx,
-        )
+impl From<&GateIndex> for SlabIndex {
+    fn from(i: &GateIndex) -> Self {
+        SlabIndex::i_actually_really_know_what_i_am_doing_and_i_want_to_construct_from_usize(i.idx)
     }
 }
 impl Display for GateIndex {
@@ -116,6 +156,7 @@ impl Display for GateIndex {
 /// Enum representing the different types of gates in a gate graph.
 #[repr(u8)]
 #[derive(Clone, Debug, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub(super) enum GateType {
     Off = 0,
     On,
@@ -127,6 +168,13 @@ pub(super) enum GateType {
     And,
     Nand,
     Nor,
+    Delay,
+    /// Buffer with an enable, `dependencies[0]` is the data and `dependencies[1]` the enable.
+    /// It drives the data while enabled and is off otherwise, see [GateGraphBuilder::tristate](super::GateGraphBuilder::tristate).
+    TriState,
+    /// User defined primitive, the number is its index in the list of
+    /// [registered custom gates](super::GateGraphBuilder::register_gate).
+    Custom(u8),
 }
 use GateType::*;
 impl GateType {
@@ -145,17 +193,19 @@ impl GateType {
     ///
     /// # Panics
     ///
-    /// Panics if `self` is On, Off, Lever or Not because those gate types don't have
-    /// multiple dependencies.
+    /// Panics if `self` is On, Off, Lever, Not or Delay because those gate types don't have
+    /// multiple dependencies, or TriState and Custom because their dependencies have different roles.
     #[inline(always)]
     pub fn accumulate(&self, acc: bool, b: bool) -> bool {
         match self {
             Or | Nor => acc | b,
             And | Nand => acc & b,
             Xor | Xnor => acc ^ b,
-            On | Off | Lever | Not => {
+            On | Off | Lever | Not | Delay => {
                 unreachable!("Accumulate only works on gates with multiple dependencies")
             }
+            TriState => unreachable!("Accumulate doesn't work on tri-state gates"),
+            Custom(_) => unreachable!("Accumulate doesn't work on custom gates"),
         }
     }
 
@@ -165,7 +215,8 @@ impl GateType {
     ///
     /// # Panics
     ///
-    /// Panics if `self` is On, Off or Lever because those gate types don't have dependencies.
+    /// Panics if `self` is On, Off or Lever because those gate types don't have dependencies,
+    /// or Delay, TriState and Custom because their state doesn't depend on an accumulation.
     #[inline(always)]
     pub fn init(&self) -> bool {
         match self {
@@ -173,6 +224,9 @@ impl GateType {
             And | Nand => true,
             Not => false,
             On | Off | Lever => unreachable!("Init doesn't work on gates without dependencies"),
+            Delay => unreachable!("Init doesn't work on delay gates"),
+            TriState => unreachable!("Init doesn't work on tri-state gates"),
+            Custom(_) => unreachable!("Init doesn't work on custom gates"),
         }
     }
 
@@ -186,16 +240,18 @@ impl GateType {
     ///
     /// # Panics
     ///
-    /// Panics if `self` is On, Off, Lever or Not because those gate types don't have
-    /// multiple dependencies.
+    /// Panics if `self` is On, Off, Lever, Not or Delay because those gate types don't have
+    /// multiple dependencies, or TriState and Custom because their dependencies have different roles.
     #[inline(always)]
     pub fn short_circuits(&self) -> bool {
         match self {
             Xor | Xnor => false,
             Or | Nor | And | Nand => true,
-            Not | On | Off | Lever => {
+            Not | On | Off | Lever | Delay => {
                 unreachable!("Short_circuits only works on gates with multiple dependencies")
             }
+            TriState => unreachable!("Short_circuits doesn't work on tri-state gates"),
+            Custom(_) => unreachable!("Short_circuits doesn't work on custom gates"),
         }
     }
 
@@ -205,7 +261,7 @@ impl GateType {
     ///
     /// # Panics
     ///
-    /// Panics if `self` is On, Off, Lever or Not because those gate types don't have
+    /// Panics if `self` is On, Off, Lever, Not, Delay, TriState or Custom because those gate types don't have
     /// a negated equivalent.
     #[inline(always)]
     pub fn negated_version(&self) -> GateType {
@@ -216,14 +272,14 @@ impl GateType {
             Nand => And,
             Xor => Xnor,
             Xnor => Xor,
-            On | Off | Not | Lever => unreachable!(),
+            On | Off | Not | Lever | Delay | TriState | Custom(_) => unreachable!(),
         }
     }
 
     /// Returns true if the [GateType] has a negated equivalent.
     #[inline(always)]
     pub fn has_negated_version(&self) -> bool {
-        !matches!(self, On | Off | Not | Lever)
+        !matches!(self, On | Off | Not | Lever | Delay | TriState | Custom(_))
     }
 
     /// Returns true if `self` is [Lever].
@@ -236,6 +292,21 @@ impl GateType {
         matches!(self, Not)
     }
 
+    /// Returns true if `self` is [Delay].
+    pub fn is_delay(&self) -> bool {
+        matches!(self, Delay)
+    }
+
+    /// Returns true if `self` is [TriState].
+    pub fn is_tristate(&self) -> bool {
+        matches!(self, TriState)
+    }
+
+    /// Returns true if `self` is [Custom].
+    pub fn is_custom(&self) -> bool {
+        matches!(self, Custom(_))
+    }
+
     /// Returns true if `self` is [Not], [Nor], [Nand] or [Xnor].
     pub fn is_negated(&self) -> bool {
         matches!(self, Nor | Nand | Not | Xnor)
@@ -254,6 +325,9 @@ impl Display for GateType {
             Nand => write!(f, stringify!(Nand)),
             Xor => write!(f, stringify!(Xor)),
             Xnor => write!(f, stringify!(Xnor)),
+            Delay => write!(f, stringify!(Delay)),
+            TriState => write!(f, stringify!(TriState)),
+            Custom(id) => write!(f, "{}{}", stringify!(Custom), id),
         }
     }
 }
@@ -266,6 +340,7 @@ pub(super) const GATE_DEPENDENCIES_TINYVEC_SIZE: usize = 2;
 /// [Gate] is generic over the type of dependent container to provide more optimized containers for
 /// build time vs runtime.
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub(super) struct Gate<T> {
     pub ty: GateType,
     pub dependencies: SmallVec<[GateIndex; GATE_DEPENDENCIES_TINYVEC_SIZE]>,
@@ -0,0 +1,242 @@
+use super::gate::*;
+use super::graph_builder::GateGraphBuilder;
+use super::InitializedGateGraph;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// State changes of the gates with a [propagation delay](GateGraphBuilder::set_delay) which haven't been applied yet.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(super) struct ScheduledChanges {
+    /// Changes by the tick at the end of which they are applied, in the order they were scheduled.
+    changes: BTreeMap<usize, Vec<(GateIndex, bool)>>,
+    /// State every gate with scheduled changes will have once they are applied and the number of changes left.
+    projected: BTreeMap<GateIndex, (bool, usize)>,
+}
+impl ScheduledChanges {
+    /// Schedules `gate` to change to `value` at the end of tick `due`, unless `value` is the state it's already
+    /// going to have, `current` is the state it has now.
+    pub(super) fn schedule(&mut self, gate: GateIndex, value: bool, current: bool, due: usize) {
+        let projected = self.projected.get(&gate).map_or(current, |(v, _)| *v);
+        if projected == value {
+            return;
+        }
+        self.changes.entry(due).or_default().push((gate, value));
+        let entry = self.projected.entry(gate).or_insert((value, 0));
+        *entry = (value, entry.1 + 1);
+    }
+
+    /// Removes and returns the changes due at the end of tick `tick` or before, in the order they were scheduled.
+    fn take_due(&mut self, tick: usize) -> Vec<(GateIndex, bool)> {
+        let mut due = Vec::new();
+        while let Some(entry) = self.changes.first_entry() {
+            if *entry.key() > tick {
+                break;
+            }
+            due.extend(entry.remove());
+        }
+        for (gate, _) in &due {
+            let (_, left) = self.projected.get_mut(gate).unwrap();
+            *left -= 1;
+            if *left == 0 {
+                self.projected.remove(gate);
+            }
+        }
+        due
+    }
+
+    /// Returns true if there are no changes left to apply.
+    pub(super) fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl GateGraphBuilder {
+    /// Gives `gate` a propagation delay of `ticks` ticks: when its dependencies change, its new state is computed
+    /// right away but its dependents only see it `ticks` ticks later, see [InitializedGateGraph::tick].
+    ///
+    /// Without delays every change settles in as few ticks as possible and the intermediate states gates go
+    /// through are never seen, delays make the simulation follow the timing of real gates instead. Paths that
+    /// reconverge with different delays produce glitches, which is what makes static hazards observable.
+    ///
+    /// A delay of 1 tick behaves like a [delay](GateGraphBuilder::delay) gate. Delays are transport delays, every
+    /// change goes through even if it's shorter than the delay. A delay of 0 removes the delay.
+    ///
+    /// Gates with a delay are [kept](GateGraphBuilder::keep) through optimizations.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `gate` is a lever or a constant.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// let mut g = GateGraphBuilder::new();
+    /// let l = g.lever("l");
+    /// let not = g.not1(l.bit(), "not");
+    /// // l & !l is always off without delays.
+    /// let and = g.and2(l.bit(), not, "and");
+    /// let output = g.output1(and, "and");
+    /// g.set_delay(not, 2);
+    ///
+    /// let ig = &mut g.init();
+    /// ig.run_until_stable(10).unwrap();
+    /// assert_eq!(output.b0(ig), false);
+    ///
+    /// // The not gate takes 2 ticks to catch up with the lever, the and gate glitches in the meantime.
+    /// ig.set_lever(l);
+    /// let mut wave = vec![];
+    /// while !ig.tick() {
+    ///     wave.push(output.b0(ig));
+    /// }
+    /// wave.push(output.b0(ig));
+    /// assert_eq!(wave, [true, true, false]);
+    /// ```
+    pub fn set_delay(&mut self, gate: GateIndex, ticks: usize) {
+        self.check(gate);
+        assert!(
+            !gate.is_const() && !self.get(gate).ty.is_lever(),
+            "Levers and constants can't have a delay"
+        );
+        if ticks == 0 {
+            self.gate_delays.remove(&gate);
+        } else {
+            self.gate_delays.insert(gate, ticks);
+            self.keep(gate);
+        }
+    }
+}
+
+impl InitializedGateGraph {
+    /// Applies the changes of the gates with a delay that are due at the end of the current tick and adds the
+    /// gates whose state changed to the pending updates, like [release_delays](InitializedGateGraph::release_delays).
+    pub(super) fn release_scheduled(&mut self) {
+        if self.scheduled.is_empty() {
+            return;
+        }
+        // The tick in progress, the one that will be reported by now() once it's over.
+        let due = self.scheduled.take_due(self.ticks + 1);
+        let before: Vec<_> = due
+            .iter()
+            .map(|(gate, _)| (*gate, self.value(*gate)))
+            .collect();
+        for (gate, value) in due {
+            self.state.set(gate.idx, value);
+        }
+        let mut changed: Vec<_> = before
+            .into_iter()
+            .filter(|(gate, value)| self.value(*gate) != *value)
+            .map(|(gate, _)| gate)
+            .collect();
+        // A gate can have several changes due in the same tick.
+        changed.sort_unstable();
+        changed.dedup();
+        for gate in changed {
+            if let Some(toggles) = &mut self.toggles {
+                toggles.count(gate.idx);
+            }
+            self.pending_updates.push(gate);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+
+    /// Returns the value of `output` after every tick until the graph is stable.
+    fn wave(ig: &mut InitializedGateGraph, output: OutputHandle) -> Vec<bool> {
+        let mut wave = Vec::new();
+        loop {
+            let stable = ig.tick();
+            wave.push(output.b0(ig));
+            if stable {
+                return wave;
+            }
+        }
+    }
+
+    #[test]
+    fn test_delay_chain() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let l = g.lever("l");
+        let buffer = g.or1(l.bit(), "buffer");
+        let not = g.not1(buffer, "not");
+        let output = g.output1(not, "not");
+        g.set_delay(buffer, 3);
+        g.set_delay(not, 2);
+
+        let ig = &mut graph.init();
+        ig.run_until_stable(10).unwrap();
+        assert!(output.b0(ig));
+
+        ig.update_lever_inner(l, true);
+        assert_eq!(
+            wave(ig, output),
+            [true, true, true, true, true, false, false]
+        );
+    }
+
+    #[test]
+    fn test_transport_delay_keeps_short_pulses() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let l = g.lever("l");
+        let not = g.not1(l.bit(), "not");
+        let glitch = g.and2(l.bit(), not, "glitch");
+        let buffer = g.or1(glitch, "buffer");
+        let output = g.output1(buffer, "buffer");
+        g.set_delay(not, 1);
+        g.set_delay(buffer, 4);
+
+        let ig = &mut graph.init();
+        ig.run_until_stable(10).unwrap();
+        let start = ig.snapshot();
+
+        // The 1 tick glitch comes out of the buffer intact.
+        ig.set_lever(l);
+        assert!(!ig.is_idle());
+        assert_eq!(wave(ig, output), [false, false, false, true, false, false]);
+        ig.reset_lever(l);
+        ig.run_until_stable(10).unwrap();
+
+        // Restoring a snapshot brings back the scheduled changes.
+        ig.set_lever(l);
+        ig.tick();
+        ig.tick();
+        let in_flight = ig.snapshot();
+        assert_eq!(wave(ig, output), [false, true, false, false]);
+        ig.restore(&in_flight);
+        assert_eq!(wave(ig, output), [false, true, false, false]);
+        ig.restore(&start);
+        assert!(ig.is_idle());
+    }
+
+    #[test]
+    fn test_delay_zero_and_optimizations() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let l = g.lever("l");
+        let a = g.not1(l.bit(), "a");
+        let b = g.not1(a, "b");
+        let output = g.output1(b, "b");
+        g.set_delay(a, 5);
+        g.set_delay(a, 0);
+        g.set_delay(b, 1);
+
+        let ig = &mut graph.init();
+        ig.run_until_stable(10).unwrap();
+        ig.update_lever_inner(l, true);
+        assert_eq!(wave(ig, output), [false, true, true]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Levers and constants can't have a delay")]
+    fn test_lever_delay_panics() {
+        let mut g = GateGraphBuilder::new();
+        let l = g.lever("l");
+        g.set_delay(l.bit(), 1);
+    }
+}
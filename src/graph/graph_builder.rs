@@ -1,12 +1,31 @@
+use super::assertions::{AssertionSource, Assertions};
+use super::behavioral_memory::{Memories, MemorySource};
+use super::clocks::Clock;
+use super::contention::ContentionMonitor;
+use super::custom_gate::CustomGate;
+use super::dot::{cone_roots, write_dot, DotConfig, DotGate};
+#[cfg(feature = "debug_gates")]
+use super::drivers::{CheckedWire, DriverMonitor};
+use super::events::{EventLog, EventSource};
 use super::gate::*;
 use super::handles::*;
 use super::optimizations::*;
+use super::validate::Port;
+use super::warnings::InitWarning;
 use super::InitializedGateGraph;
-use crate::data_structures::{Slab, State};
+use crate::data_structures::{Slab, SlabIndex, State};
 use casey::pascal;
 use concat_idents::concat_idents;
+use indexmap::{IndexMap, IndexSet};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use smallvec::smallvec;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+#[cfg(debug_assertions)]
+use std::sync::atomic::{AtomicU32, Ordering};
 
 use GateType::*;
 
@@ -34,8 +53,9 @@ macro_rules! gate_constructors {
     };
     ($name:ident,$doc0:expr,$doc1:expr,$doc2:expr,$docx:expr) => {
         #[doc=$doc0]
+        #[track_caller]
         pub fn $name<S: Into<String>>(&mut self, name: S) -> GateIndex {
-            let idx = self.nodes.insert(Gate::new(pascal!($name), smallvec![])).into();
+            let idx = self.insert(Gate::new(pascal!($name), smallvec![]));
             self.create_gate(idx, std::iter::empty(), name);
             idx
         }
@@ -47,8 +67,9 @@ macro_rules! gate_constructors {
             ///
             /// Providing a good name allows for a great debugging experience, you can disable the "debug_gates" feature
             /// to slightly increase performance.
+            #[track_caller]
             pub fn name1<S: Into<String>>(&mut self, dep: GateIndex, name: S) -> GateIndex {
-                let idx = self.nodes.insert(Gate::new(pascal!($name), smallvec![dep])).into();
+                let idx = self.insert(Gate::new(pascal!($name), smallvec![dep]));
                 self.create_gate(idx, std::iter::once(dep), name);
                 idx
             }
@@ -61,8 +82,9 @@ macro_rules! gate_constructors {
             ///
             /// Providing a good name allows for a great debugging experience, you can disable the "debug_gates" feature
             /// to slightly increase performance.
+            #[track_caller]
             pub fn name2<S: Into<String>>(&mut self, dep1: GateIndex, dep2: GateIndex, name: S) -> GateIndex {
-                let idx = self.nodes.insert(Gate::new(pascal!($name), smallvec![dep1, dep2])).into();
+                let idx = self.insert(Gate::new(pascal!($name), smallvec![dep1, dep2]));
                 self.create_gate(idx, std::iter::once(dep1).chain(std::iter::once(dep2)), name);
                 idx
             }
@@ -75,8 +97,9 @@ macro_rules! gate_constructors {
             ///
             /// Providing a good name allows for a great debugging experience, you can disable the "debug_gates" feature
             /// to slightly increase performance.
+            #[track_caller]
             pub fn namex<S: Into<String>,I:Iterator<Item=GateIndex>+Clone>(&mut self, iter: I, name: S) -> GateIndex {
-                let idx = self.nodes.insert(Gate::new(pascal!($name), iter.clone().collect())).into();
+                let idx = self.insert(Gate::new(pascal!($name), iter.clone().collect()));
                 self.create_gate(idx, iter, name);
                 idx
             }
@@ -100,6 +123,10 @@ macro_rules! gate_constructors {
 ///
 /// **Zero overhead abstractions!**
 ///
+/// With the "serde" feature [GateGraphBuilder] implements `Serialize` and `Deserialize`, so big circuits can be
+/// built once and loaded later. Custom gate types are not serialized because their functions can't be,
+/// [register](GateGraphBuilder::register_gate) them again in the same order after loading the graph.
+///
 /// # Examples
 /// Simple gates.
 /// ```
@@ -188,30 +215,125 @@ macro_rules! gate_constructors {
 /// assert_eq!(nq_output.b0(ig), true);
 /// ```
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct GateGraphBuilder {
     pub(super) nodes: Slab<BuildGate>,
-    output_handles: Vec<Output>,
+    pub(super) output_handles: Vec<Output>,
     pub(super) lever_handles: Vec<GateIndex>,
-    outputs: HashSet<GateIndex>,
+    pub(super) outputs: IndexSet<GateIndex>,
     #[cfg(feature = "debug_gates")]
-    names: HashMap<GateIndex, String>,
+    pub(super) names: IndexMap<GateIndex, String>,
     #[cfg(feature = "debug_gates")]
-    probes: HashMap<GateIndex, Probe>,
+    pub(super) probes: IndexMap<GateIndex, Probe>,
+    pub(super) test_points: IndexMap<String, OutputHandle>,
+    // Functions can't be serialized, they have to be registered again after deserializing.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(super) custom_gates: Vec<CustomGate>,
+    pub(super) kept: IndexSet<GateIndex>,
+    pub(super) event_sources: Vec<EventSource>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(super) clocks: Vec<Clock>,
+    /// Names of the modules opened with [GateGraphBuilder::begin_module], outermost first.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(super) modules: Vec<String>,
+    /// Wires and buses checked by [GateGraphBuilder::validate].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(super) ports: Vec<Port>,
+    /// Propagation delays set with [GateGraphBuilder::set_delay].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(super) gate_delays: IndexMap<GateIndex, usize>,
+    /// Assertions declared with [GateGraphBuilder::assert_always] and [GateGraphBuilder::assert_never].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(super) assertions: Vec<AssertionSource>,
+    /// Memories declared with [GateGraphBuilder::behavioral_memory].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(super) memories: Vec<MemorySource>,
+    /// Wires created with [Wire::checked](crate::Wire::checked).
+    #[cfg(feature = "debug_gates")]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(super) checked_wires: Vec<CheckedWire>,
+    /// States after reset assumed with [GateGraphBuilder::assume_after_reset].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(super) reset_assumptions: IndexMap<GateIndex, bool>,
+    /// Maximum number of gates, see [GateGraphBuilder::set_gate_limit].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(super) gate_limit: Option<usize>,
+    /// Id that tags every [GateIndex] handed out by the builder, see [GateGraphBuilder::check].
+    /// Clones share it, so their indexes can be used with each other.
+    #[cfg(debug_assertions)]
+    #[cfg_attr(feature = "serde", serde(skip, default = "next_graph_id"))]
+    pub(super) id: u32,
+}
+
+/// Id of the next [GateGraphBuilder], starts at 1 because 0 means no builder.
+#[cfg(debug_assertions)]
+static NEXT_GRAPH_ID: AtomicU32 = AtomicU32::new(1);
+
+/// Returns a new id for a [GateGraphBuilder].
+#[cfg(debug_assertions)]
+fn next_graph_id() -> u32 {
+    NEXT_GRAPH_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Phase of [GateGraphBuilder::init_with_progress] being reported to the progress callback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InitPhase {
+    /// An optimization pass with the given name is running.
+    Optimization(&'static str),
+    /// The gates are being placed contiguously in memory.
+    Compaction,
+    /// The initial state of every gate is being computed.
+    Propagation,
 }
+
+/// Empty gate slots of a [GateGraphBuilder], see [GateGraphBuilder::fragmentation].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fragmentation {
+    /// Slots left behind by removed gates.
+    pub empty_slots: usize,
+    /// Slots allocated, empty or not.
+    pub total_slots: usize,
+}
+impl Fragmentation {
+    /// Returns the fraction of the slots which are empty, between 0 and 1.
+    pub fn ratio(&self) -> f64 {
+        if self.total_slots == 0 {
+            0.0
+        } else {
+            self.empty_slots as f64 / self.total_slots as f64
+        }
+    }
+}
+
+/// How many gates to process between progress reports.
+const PROGRESS_INTERVAL: usize = 1024;
+
 /// Intermediate representation between [GateGraphBuilder] and [InitializedGateGraph].
 /// It has the same structure as an [InitializedGateGraph] except for the initialized [State].
 ///
 /// It is only used when transforming a [GateGraphBuilder]
 /// into an [InitializedGateGraph] in the [GateGraphBuilder::init] method.
-struct CompactedGateGraph {
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(super) struct CompactedGateGraph {
     nodes: Vec<InitializedGate>,
     output_handles: Vec<Output>,
     lever_handles: Vec<GateIndex>,
-    outputs: HashSet<GateIndex>,
+    outputs: IndexSet<GateIndex>,
     #[cfg(feature = "debug_gates")]
-    names: HashMap<GateIndex, String>,
+    names: IndexMap<GateIndex, String>,
     #[cfg(feature = "debug_gates")]
-    probes: HashMap<GateIndex, Probe>,
+    probes: IndexMap<GateIndex, Probe>,
+    test_points: IndexMap<String, OutputHandle>,
+    // Functions can't be serialized, they get restored from the GateGraphBuilder.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(super) custom_gates: Vec<CustomGate>,
+    event_sources: Vec<EventSource>,
+    clocks: Vec<Clock>,
+    gate_delays: IndexMap<GateIndex, usize>,
+    assertions: Vec<AssertionSource>,
+    memories: Vec<MemorySource>,
+    #[cfg(feature = "debug_gates")]
+    checked_wires: Vec<CheckedWire>,
 }
 
 // The graph always contains OFF and ON.
@@ -233,7 +355,7 @@ impl GateGraphBuilder {
 
         #[cfg(feature = "debug_gates")]
         let names = {
-            let mut names: HashMap<_, _> = Default::default();
+            let mut names: IndexMap<_, _> = Default::default();
             names.insert(OFF, "OFF".into());
             names.insert(ON, "ON".into());
             names
@@ -248,6 +370,22 @@ impl GateGraphBuilder {
             names,
             #[cfg(feature = "debug_gates")]
             probes: Default::default(),
+            test_points: Default::default(),
+            custom_gates: Default::default(),
+            kept: Default::default(),
+            event_sources: Default::default(),
+            clocks: Default::default(),
+            modules: Default::default(),
+            ports: Default::default(),
+            gate_delays: Default::default(),
+            assertions: Default::default(),
+            memories: Default::default(),
+            #[cfg(feature = "debug_gates")]
+            checked_wires: Default::default(),
+            reset_assumptions: Default::default(),
+            gate_limit: None,
+            #[cfg(debug_assertions)]
+            id: next_graph_id(),
         }
     }
 
@@ -256,12 +394,17 @@ impl GateGraphBuilder {
     /// # Panics
     ///
     /// Will panic if `target` can't have a variable number of dependencies.
+    #[track_caller]
     pub fn dpush(&mut self, target: GateIndex, new_dep: GateIndex) {
+        self.check(new_dep);
         let gate = self.get_mut(target);
         match gate.ty {
             Off => panic!("OFF has no dependencies"),
             On => panic!("ON has no dependencies"),
             Not => panic!("Not only has one dependency"),
+            Delay => panic!("Delay only has one dependency"),
+            TriState => panic!("TriState only has a data and an enable dependency"),
+            Custom(_) => panic!("Custom gates have a fixed number of dependencies"),
             Lever => panic!("Lever has no dependencies"),
             Or | Nor | And | Nand | Xor | Xnor => {
                 gate.dependencies.push(new_dep);
@@ -280,11 +423,13 @@ impl GateGraphBuilder {
     ///
     /// Will panic if `target` has less than `x` + 1 dependencies, you probably want [GateGraphBuilder::dpush] instead.
     ///
-    /// Will panic if `target` is Not and `x` > 0.
+    /// Will panic if `target` is Not or Delay and `x` > 0, or TriState and `x` > 1.
     ///
     /// Will panic if `target` can't have dependencies.
+    #[track_caller]
     pub fn dx(&mut self, target: GateIndex, new_dep: GateIndex, x: usize) {
-        let gate = self.nodes.get_mut(target.into()).unwrap();
+        self.check(new_dep);
+        let gate = self.get_mut(target);
         match gate.ty {
             Off => panic!("OFF has no dependencies"),
             On => panic!("ON has no dependencies"),
@@ -292,17 +437,28 @@ impl GateGraphBuilder {
             Not => {
                 assert!(x == 0, "Not only has one dependency");
             }
+            Delay => {
+                assert!(x == 0, "Delay only has one dependency");
+            }
+            TriState => {
+                assert!(x < 2, "TriState only has a data and an enable dependency");
+            }
+            Custom(_) => {}
             // Left explicitly to get errors when a new gate type is added
             Or | Nor | And | Nand | Xor | Xnor => {}
         }
 
         let old_dep = std::mem::replace(&mut gate.dependencies[x], new_dep);
+        // Dependents don't count repeated dependencies.
+        let still_dependent = gate.dependencies.contains(&old_dep);
 
-        self.nodes
-            .get_mut(old_dep.into())
-            .unwrap()
-            .dependents
-            .remove(&target);
+        if !still_dependent {
+            self.nodes
+                .get_mut(old_dep.into())
+                .unwrap()
+                .dependents
+                .remove(&target);
+        }
         self.nodes
             .get_mut(new_dep.into())
             .unwrap()
@@ -317,6 +473,7 @@ impl GateGraphBuilder {
     /// Will panic if `target` has less than 1 dependency, you probably want [GateGraphBuilder::dpush] instead.
     ///
     /// Will panic if `target` can't have dependencies.
+    #[track_caller]
     pub fn d0(&mut self, target: GateIndex, new_dep: GateIndex) {
         self.dx(target, new_dep, 0)
     }
@@ -328,35 +485,289 @@ impl GateGraphBuilder {
     /// Will panic if `target` has less than 1 dependency, you probably want [GateGraphBuilder::dpush] instead.
     ///
     /// Will panic if `target` can't have more than 1 dependency.
+    #[track_caller]
     pub fn d1(&mut self, target: GateIndex, new_dep: GateIndex) {
         self.dx(target, new_dep, 1)
     }
 
+    /// Removes the dependency at index `x` from the dependencies of `target`.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `target` has less than `x` + 1 dependencies.
+    ///
+    /// Will panic if `target` can't have a variable number of dependencies, replace the dependency with
+    /// [dx](GateGraphBuilder::dx) instead.
+    #[track_caller]
+    pub fn dremove(&mut self, target: GateIndex, x: usize) {
+        let gate = self.get_mut(target);
+        assert!(
+            has_variable_dependencies(gate.ty),
+            "{} gates have a fixed number of dependencies",
+            gate.ty
+        );
+        let old_dep = gate.dependencies.remove(x);
+        // Dependents don't count repeated dependencies.
+        if !gate.dependencies.contains(&old_dep) {
+            self.get_mut(old_dep).dependents.remove(&target);
+        }
+    }
+
+    /// Removes every dependency of `target`, gates with a fixed number of dependencies get [OFF]
+    /// in all of them instead.
+    #[track_caller]
+    pub fn disconnect_all(&mut self, target: GateIndex) {
+        let gate = self.get_mut(target);
+        let placeholders = if has_variable_dependencies(gate.ty) {
+            0
+        } else {
+            gate.dependencies.len()
+        };
+        let old_deps = std::mem::replace(
+            &mut gate.dependencies,
+            std::iter::repeat(OFF).take(placeholders).collect(),
+        );
+        for dep in old_deps {
+            self.get_mut(dep).dependents.remove(&target);
+        }
+        if placeholders > 0 {
+            self.get_mut(OFF).dependents.insert(target);
+        }
+    }
+
+    /// Removes `gate` from the graph.
+    ///
+    /// It gets removed from the dependencies of its dependents, or replaced by [OFF] in the ones with a fixed number of
+    /// dependencies, outputs and probes that contained it read [OFF] in its place.
+    /// Using its [GateIndex] afterwards is a bug, which debug builds catch.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,ON};
+    /// let mut g = GateGraphBuilder::new();
+    /// let a = g.lever("a");
+    /// let not = g.not1(a.bit(), "not");
+    /// let or = g.or2(a.bit(), not, "or");
+    /// let output = g.output1(or, "output");
+    ///
+    /// g.remove_gate(not);
+    /// let ig = &mut g.init();
+    /// assert_eq!(output.b0(ig), false);
+    /// ig.set_lever_stable(a);
+    /// assert_eq!(output.b0(ig), true);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `gate` is [ON], [OFF] or a lever, because their handles would be left dangling.
+    /// Also if an event, assertion, behavioral memory or checked wire reads `gate`, reading [OFF] in its place would
+    /// silently change what they report.
+    #[track_caller]
+    pub fn remove_gate(&mut self, gate: GateIndex) {
+        assert!(!gate.is_const(), "ON and OFF can't be removed");
+        assert!(!self.get(gate).ty.is_lever(), "Levers can't be removed");
+        assert!(
+            !self.event_reads(gate) && !self.assertion_reads(gate) && !self.memory_reads(gate),
+            "Gates read by an event, assertion or behavioral memory can't be removed"
+        );
+        #[cfg(feature = "debug_gates")]
+        assert!(
+            !self.checked_wire_reads(gate),
+            "Gates that drive a checked wire can't be removed"
+        );
+        self.disconnect_all(gate);
+        let dependents = std::mem::take(&mut self.get_mut(gate).dependents);
+        for dependent in dependents {
+            if dependent == gate {
+                continue;
+            }
+            let dependent_gate = self.get_mut(dependent);
+            if has_variable_dependencies(dependent_gate.ty) {
+                dependent_gate.dependencies.retain(|dep| *dep != gate);
+            } else {
+                dependent_gate.swap_dependency(gate, OFF);
+                self.get_mut(OFF).dependents.insert(dependent);
+            }
+        }
+        self.get_mut(OFF).dependents.remove(&gate);
+
+        for output in &mut self.output_handles {
+            for bit in &mut output.bits {
+                if *bit == gate {
+                    *bit = OFF
+                }
+            }
+        }
+        self.replace_in_ports(gate, OFF);
+        self.outputs.remove(&gate);
+        self.kept.remove(&gate);
+        self.gate_delays.remove(&gate);
+        self.reset_assumptions.remove(&gate);
+        #[cfg(feature = "debug_gates")]
+        {
+            self.names.remove(&gate);
+            self.probes.remove(&gate);
+            for probe in self.probes.values_mut() {
+                for bit in &mut probe.bits {
+                    if *bit == gate {
+                        *bit = OFF
+                    }
+                }
+            }
+        }
+        self.nodes.remove(gate.into());
+    }
+
+    /// Returns true if the output with handle `output` contains `gate`.
+    pub(super) fn output_reads(&self, output: OutputHandle, gate: GateIndex) -> bool {
+        self.output_handles[output.0].bits.contains(&gate)
+    }
+
+    /// Inserts `gate` into the graph and returns its index.
+    ///
+    /// In debug builds the index is tagged with the id of the builder and the generation of its slot,
+    /// see [GateGraphBuilder::check].
+    #[track_caller]
+    pub(super) fn insert(&mut self, gate: BuildGate) -> GateIndex {
+        self.check_gate_limit();
+        let slab_index = self.nodes.insert(gate);
+        self.gate_index(slab_index)
+    }
+
+    /// Returns the [GateIndex] of the gate in `slab_index`, tagged like the ones returned by [GateGraphBuilder::insert].
+    pub(super) fn gate_index(&self, slab_index: SlabIndex) -> GateIndex {
+        #[allow(unused_mut)]
+        let mut idx = GateIndex::from(slab_index);
+        #[cfg(debug_assertions)]
+        {
+            idx.generation = self.nodes.generation(slab_index).wrapping_add(1);
+            idx.graph = self.id;
+        }
+        idx
+    }
+
+    /// Panics with a clear message if `idx` was handed out by another builder, or for a gate that has since been
+    /// removed from the graph.
+    ///
+    /// Only checked in debug builds, otherwise `idx` would silently point at whichever gate has the same index.
+    /// The panic points at the code that called the public method with `idx`.
+    #[inline(always)]
+    #[track_caller]
+    pub(super) fn check(&self, idx: GateIndex) {
+        #[cfg(debug_assertions)]
+        assert!(
+            idx.graph == 0 || idx.graph == self.id,
+            "GateIndex {} belongs to another GateGraphBuilder",
+            idx
+        );
+        #[cfg(debug_assertions)]
+        if idx.generation != 0 {
+            let generation = self.nodes.generation(idx.into()).wrapping_add(1);
+            assert!(
+                idx.generation == generation,
+                "Stale GateIndex {}: the gate it was created for has been removed from the graph",
+                idx
+            );
+        }
+        #[cfg(not(debug_assertions))]
+        let _ = idx;
+    }
+
     /// Creates the dependent edges and saves the name of new gates.
     #[allow(unused_variables)]
-    fn create_gate<S: Into<String>, I: Iterator<Item = GateIndex>>(
+    #[track_caller]
+    pub(super) fn create_gate<S: Into<String>, I: Iterator<Item = GateIndex>>(
         &mut self,
         idx: GateIndex,
         deps: I,
         name: S,
     ) {
         for dep in deps {
-            self.nodes
-                .get_mut(dep.into())
-                .unwrap()
-                .dependents
-                .insert(idx);
+            self.get_mut(dep).dependents.insert(idx);
         }
         #[cfg(feature = "debug_gates")]
-        self.names.insert(idx, name.into());
+        {
+            let name = self.qualified_name(name.into());
+            self.names.insert(idx, name);
+        }
+    }
+
+    /// Opens a module named `name` inside the current one, gates created until the matching
+    /// [end_module](GateGraphBuilder::end_module) get the path of the module as a prefix in their names,
+    /// which shows up in dot dumps, probes and error messages, like "alu/adder/carry3".
+    ///
+    /// This scales better than adding prefixes by hand to every name, see [module](GateGraphBuilder::module)
+    /// for a version that can't forget to close the module.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// let mut g = GateGraphBuilder::new();
+    /// g.begin_module("alu");
+    /// g.begin_module("adder");
+    /// assert_eq!(g.module_path(), "alu/adder");
+    /// g.end_module();
+    /// g.end_module();
+    /// assert_eq!(g.module_path(), "");
+    /// ```
+    pub fn begin_module<S: Into<String>>(&mut self, name: S) {
+        self.modules.push(name.into());
+    }
+
+    /// Closes the module opened by the last call to [begin_module](GateGraphBuilder::begin_module).
+    ///
+    /// # Panics
+    ///
+    /// Will panic if there are no open modules.
+    pub fn end_module(&mut self) {
+        self.modules
+            .pop()
+            .expect("end_module() called without a matching begin_module()");
+    }
+
+    /// Calls `f` inside a module named `name`, see [begin_module](GateGraphBuilder::begin_module),
+    /// and returns its result.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// # use logicsim::{adder,OFF};
+    /// let mut g = GateGraphBuilder::new();
+    /// let a = g.lever("a");
+    /// let b = g.lever("b");
+    /// let sum = g.module("alu", |g| adder(g, OFF, &[a.bit()], &[b.bit()], "adder"));
+    /// g.output(&sum, "sum");
+    /// ```
+    pub fn module<S: Into<String>, R, F: FnOnce(&mut Self) -> R>(&mut self, name: S, f: F) -> R {
+        self.begin_module(name);
+        let result = f(self);
+        self.end_module();
+        result
+    }
+
+    /// Returns the path of the current module, the names of the open modules separated by "/",
+    /// or an empty string outside of any module.
+    pub fn module_path(&self) -> String {
+        self.modules.join("/")
+    }
+
+    /// Returns `name` prefixed by the path of the current module.
+    #[cfg(feature = "debug_gates")]
+    fn qualified_name(&self, name: String) -> String {
+        if self.modules.is_empty() {
+            name
+        } else {
+            format!("{}/{}", self.module_path(), name)
+        }
     }
 
     /// Returns the [LeverHandle] of a new lever gate.
     ///
     /// Providing a good name allows for a great debugging experience.
     /// You can disable the "debug_gates" feature to slightly increase performance.
+    #[track_caller]
     pub fn lever<S: Into<String>>(&mut self, name: S) -> LeverHandle {
-        let idx = self.nodes.insert(Gate::new(Lever, smallvec![])).into();
+        let idx = self.insert(Gate::new(Lever, smallvec![]));
         let handle = self.lever_handles.len();
         self.lever_handles.push(idx);
         self.create_gate(idx, std::iter::empty(), name);
@@ -367,12 +778,93 @@ impl GateGraphBuilder {
     ///
     /// Providing a good name allows for a great debugging experience.
     /// You can disable the "debug_gates" feature to slightly increase performance.
+    #[track_caller]
     pub fn not1<S: Into<String>>(&mut self, dep: GateIndex, name: S) -> GateIndex {
-        let idx = self.nodes.insert(Gate::new(Not, smallvec![dep])).into();
+        let idx = self.insert(Gate::new(Not, smallvec![dep]));
+        self.create_gate(idx, std::iter::once(dep), name);
+        idx
+    }
+
+    /// Returns the [GateIndex] of a new delay gate with 1 dependency.
+    ///
+    /// At the end of every [tick](InitializedGateGraph::tick) a delay gate takes the state of `dep`,
+    /// as if it was a lever being set, so its dependents only see the change during the next tick. This makes feedback loops that go through it deterministic instead of relying on the order in which
+    /// gates get propagated. For example a not gate connected to itself through a delay gate
+    /// (using [dx](GateGraphBuilder::dx) to close the loop) is an oscillator that flips on every tick.
+    ///
+    /// Keep in mind that a tick in which a delay gate changes state doesn't count as stable,
+    /// a change that has to go through a chain of n delays needs n more ticks to settle in
+    /// [run_until_stable](InitializedGateGraph::run_until_stable), and a loop like the oscillator never settles,
+    /// so run_until_stable will always return an error for it, use [tick](InitializedGateGraph::tick) instead.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// let mut g = GateGraphBuilder::new();
+    /// let l = g.lever("l");
+    ///
+    /// let delayed = g.delay(l.bit(), "delayed");
+    /// let not = g.not1(delayed, "not");
+    /// let output = g.output1(not, "not");
+    ///
+    /// let ig = &mut g.init();
+    /// ig.run_until_stable(10).unwrap();
+    ///
+    /// ig.set_lever(l);
+    /// ig.tick();
+    /// // Without the delay, the output would already be off.
+    /// assert_eq!(output.b0(ig), true);
+    /// ig.tick();
+    /// assert_eq!(output.b0(ig), false);
+    /// ```
+    #[track_caller]
+    pub fn delay<S: Into<String>>(&mut self, dep: GateIndex, name: S) -> GateIndex {
+        let idx = self.insert(Gate::new(Delay, smallvec![dep]));
         self.create_gate(idx, std::iter::once(dep), name);
         idx
     }
 
+    /// Returns the [GateIndex] of a new tri-state buffer, which drives `data` while `enable` is active
+    /// and stops driving when it's not.
+    ///
+    /// Without a high impedance state, a tri-state buffer that is not driving is off, so connecting several of them
+    /// to an or gate like a [Bus](crate::Bus) does behaves like a bus with a pull down resistor.
+    /// The difference with an and gate is that the simulation knows which gates are drivers,
+    /// so any gate with more than one tri-state dependency is checked for
+    /// [contention](InitializedGateGraph::contentions), two enabled drivers with different values.
+    /// Tri-state buffers are only optimized away if they are never enabled.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// let mut g = GateGraphBuilder::new();
+    /// let data = g.lever("data");
+    /// let enable = g.lever("enable");
+    /// let buffer = g.tristate(data.bit(), enable.bit(), "buffer");
+    /// let output = g.output1(buffer, "output");
+    ///
+    /// let ig = &mut g.init();
+    /// ig.set_lever_stable(data);
+    /// assert_eq!(output.b0(ig), false);
+    /// ig.set_lever_stable(enable);
+    /// assert_eq!(output.b0(ig), true);
+    /// ```
+    #[track_caller]
+    pub fn tristate<S: Into<String>>(
+        &mut self,
+        data: GateIndex,
+        enable: GateIndex,
+        name: S,
+    ) -> GateIndex {
+        let idx = self.insert(Gate::new(TriState, smallvec![data, enable]));
+        self.create_gate(
+            idx,
+            std::iter::once(data).chain(std::iter::once(enable)),
+            name,
+        );
+        idx
+    }
+
     // Create constructors for all gate types with variable dependencies.
     gate_constructors!(or, nor, and, nand, xor, xnor);
 
@@ -384,7 +876,9 @@ impl GateGraphBuilder {
     ///
     /// Will panic if `idx` has been removed from self.nodes.
     #[inline(always)]
+    #[track_caller]
     pub(super) fn get(&self, idx: GateIndex) -> &BuildGate {
+        self.check(idx);
         self.nodes.get(idx.into()).unwrap()
     }
 
@@ -396,21 +890,72 @@ impl GateGraphBuilder {
     ///
     /// Will panic if `idx` has been removed from self.nodes.
     #[inline(always)]
+    #[track_caller]
     pub(super) fn get_mut(&mut self, idx: GateIndex) -> &mut BuildGate {
+        self.check(idx);
         self.nodes.get_mut(idx.into()).unwrap()
     }
 
     /// Returns a new [InitializedGateGraph] created from `self` after running optimizations.
-    pub fn init(mut self) -> InitializedGateGraph {
-        self.optimize();
-        self.init_unoptimized()
+    ///
+    /// Initialization is deterministic, identical builders produce identical graphs, down to the index of every
+    /// gate, so dot dumps and the graphs stored by `init_cached` can be compared between runs.
+    pub fn init(self) -> InitializedGateGraph {
+        self.init_with_progress(|_, _, _| {})
+    }
+
+    /// Returns a new [InitializedGateGraph] created from `self` after running optimizations,
+    /// calling `progress(phase, done, total)` as the initialization advances.
+    ///
+    /// `done` and `total` are relative to the current [InitPhase], each optimization pass reports
+    /// `(0, 1)` when it starts and `(1, 1)` when it finishes, [InitPhase::Compaction] and [InitPhase::Propagation]
+    /// report the number of gates processed so far, every phase ends with `done == total`.
+    ///
+    /// Initializing very big graphs can take minutes, this lets you show a progress bar in the meantime.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,InitPhase,ON};
+    /// let mut g = GateGraphBuilder::new();
+    /// let l = g.lever("l");
+    /// let and = g.and2(l.bit(), ON, "and");
+    /// let output = g.output1(and, "and");
+    ///
+    /// let mut phases = Vec::new();
+    /// let ig = &g.init_with_progress(|phase, done, total| {
+    ///     if done == total {
+    ///         phases.push(phase);
+    ///     }
+    /// });
+    ///
+    /// assert_eq!(output.b0(ig), false);
+    /// assert_eq!(phases.last(), Some(&InitPhase::Propagation));
+    /// ```
+    pub fn init_with_progress<F: FnMut(InitPhase, usize, usize)>(
+        mut self,
+        mut progress: F,
+    ) -> InitializedGateGraph {
+        let span = tracing::info_span!(target: "logicsim::init", "init", gates = self.len());
+        let _enter = span.enter();
+
+        let warnings = self.find_init_warnings();
+        self.optimize(&mut progress);
+        self.compacted(&mut progress)
+            .initialize(warnings, &mut progress)
     }
 
     /// Returns a new [CompactedGateGraph] created from `self`.
     ///
     /// Compacted means that all gates are placed contiguously and all references to them
     /// are updated accordingly.
-    fn compacted(self) -> CompactedGateGraph {
+    pub(super) fn compacted(
+        mut self,
+        progress: &mut dyn FnMut(InitPhase, usize, usize),
+    ) -> CompactedGateGraph {
+        let span = tracing::debug_span!(target: "logicsim::init", "compaction");
+        let _enter = span.enter();
+
+        self.compact_inner(progress);
         #[cfg(feature = "debug_gates")]
         let GateGraphBuilder {
             names,
@@ -419,6 +964,21 @@ impl GateGraphBuilder {
             outputs,
             output_handles,
             lever_handles,
+            test_points,
+            custom_gates,
+            kept: _,
+            event_sources,
+            clocks,
+            modules: _,
+            ports: _,
+            gate_delays,
+            assertions,
+            memories,
+            checked_wires,
+            reset_assumptions: _,
+            gate_limit: _,
+            #[cfg(debug_assertions)]
+                id: _,
         } = self;
         #[cfg(not(feature = "debug_gates"))]
         let GateGraphBuilder {
@@ -426,182 +986,283 @@ impl GateGraphBuilder {
             outputs,
             output_handles,
             lever_handles,
+            test_points,
+            custom_gates,
+            kept: _,
+            event_sources,
+            clocks,
+            modules: _,
+            ports: _,
+            gate_delays,
+            assertions,
+            memories,
+            reset_assumptions: _,
+            gate_limit: _,
+            #[cfg(debug_assertions)]
+                id: _,
         } = self;
-        if nodes.len() == nodes.total_len() {
-            return CompactedGateGraph {
-                nodes: nodes.into_iter().map(|(_, gate)| gate.into()).collect(),
-                #[cfg(feature = "debug_gates")]
-                names,
-                #[cfg(feature = "debug_gates")]
-                probes,
-                outputs,
-                lever_handles,
-                output_handles,
-            };
+        CompactedGateGraph {
+            nodes: nodes.into_iter().map(|(_, gate)| gate.into()).collect(),
+            #[cfg(feature = "debug_gates")]
+            names,
+            #[cfg(feature = "debug_gates")]
+            probes,
+            outputs,
+            lever_handles,
+            output_handles,
+            test_points,
+            custom_gates,
+            event_sources,
+            clocks,
+            gate_delays,
+            assertions,
+            memories,
+            #[cfg(feature = "debug_gates")]
+            checked_wires,
         }
+    }
 
-        let mut index_map = HashMap::<GateIndex, GateIndex>::new();
-        let mut new_nodes = Vec::<InitializedGate>::new();
-        index_map.reserve(nodes.len());
-        new_nodes.reserve(nodes.len());
+    /// Returns how many of the slots allocated for gates are empty, gates removed by the optimizations
+    /// leave empty slots behind which new gates reuse, until [compact_now](GateGraphBuilder::compact_now)
+    /// or [init](GateGraphBuilder::init) get rid of them.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// let mut g = GateGraphBuilder::new();
+    /// let l = g.lever("l");
+    /// g.not1(l.bit(), "not");
+    ///
+    /// let fragmentation = g.fragmentation();
+    /// assert_eq!(fragmentation.empty_slots, 0);
+    /// assert_eq!(fragmentation.total_slots, 4);
+    /// assert_eq!(fragmentation.ratio(), 0.0);
+    /// ```
+    pub fn fragmentation(&self) -> Fragmentation {
+        Fragmentation {
+            empty_slots: self.nodes.total_len() - self.nodes.len(),
+            total_slots: self.nodes.total_len(),
+        }
+    }
 
-        for (new_index, (old_index, gate)) in nodes.into_iter().enumerate() {
-            index_map.insert(old_index.into(), gi!(new_index));
+    /// Places all gates contiguously, releasing the memory of the empty slots, see
+    /// [fragmentation](GateGraphBuilder::fragmentation).
+    /// [init](GateGraphBuilder::init) always does this, calling it earlier reclaims memory during long building
+    /// sessions which remove a lot of gates.
+    ///
+    /// Gates can move, so every [GateIndex] obtained before the call must be translated with the returned map
+    /// from old to new indexes, which contains every gate. That includes the [bit](LeverHandle::bit) of
+    /// [LeverHandle]s, get the handle of the moved lever with [LeverHandle::translated].
+    /// [OutputHandle]s stay valid.
+    pub fn compact_now(&mut self) -> HashMap<GateIndex, GateIndex> {
+        self.compact_inner(&mut |_, _, _| {})
+    }
+
+    /// Does the work of [GateGraphBuilder::compact_now], reporting progress to `progress`.
+    fn compact_inner(
+        &mut self,
+        progress: &mut dyn FnMut(InitPhase, usize, usize),
+    ) -> HashMap<GateIndex, GateIndex> {
+        let total = self.nodes.len();
+        progress(InitPhase::Compaction, 0, total);
+        // Nothing moves, rebuilding the slab would only reset the generations of reused slots
+        // and leave every index stored elsewhere stale.
+        if self.nodes.len() == self.nodes.total_len() {
+            let index_map = self
+                .nodes
+                .iter()
+                .map(|(slab_index, _)| {
+                    let idx = self.gate_index(slab_index);
+                    (idx, idx)
+                })
+                .collect();
+            progress(InitPhase::Compaction, total, total);
+            return index_map;
+        }
 
-            new_nodes.push(gate.into());
+        let mut index_map = HashMap::<GateIndex, GateIndex>::new();
+        index_map.reserve(total);
+        let old_nodes = std::mem::take(&mut self.nodes);
+        for (i, (old_index, gate)) in old_nodes.into_iter().enumerate() {
+            // Moving gates doesn't count towards the gate limit.
+            let slab_index = self.nodes.insert(gate);
+            let new_index = self.gate_index(slab_index);
+            index_map.insert(old_index.into(), new_index);
+            if i % PROGRESS_INTERVAL == 0 {
+                progress(InitPhase::Compaction, i, total);
+            }
         }
-        for gate in &mut new_nodes {
+        for i in 0..self.nodes.total_len() {
+            let gate = self.get_mut(gi!(i));
             for dependency in &mut gate.dependencies {
                 *dependency = index_map[dependency];
             }
             gate.dependents = gate.dependents.iter().map(|idx| index_map[idx]).collect();
         }
+        progress(InitPhase::Compaction, total, total);
 
         #[cfg(feature = "debug_gates")]
-        let new_names = names
-            .into_iter()
-            .filter_map(|(idx, name)| Some((*index_map.get(&idx)?, name)))
+        {
+            self.names = std::mem::take(&mut self.names)
+                .into_iter()
+                .filter_map(|(idx, name)| Some((*index_map.get(&idx)?, name)))
+                .collect();
+            self.probes = std::mem::take(&mut self.probes)
+                .into_iter()
+                .map(|(idx, mut probe)| {
+                    for bit in &mut probe.bits {
+                        *bit = index_map[bit]
+                    }
+                    (index_map[&idx], probe)
+                })
+                .collect();
+        }
+        for output in &mut self.output_handles {
+            for bit in &mut output.bits {
+                *bit = index_map[bit]
+            }
+        }
+        for lever in &mut self.lever_handles {
+            *lever = index_map[lever]
+        }
+        self.outputs = self.outputs.iter().map(|idx| index_map[idx]).collect();
+        self.kept = self.kept.iter().map(|idx| index_map[idx]).collect();
+        self.gate_delays = self
+            .gate_delays
+            .iter()
+            .map(|(idx, delay)| (index_map[idx], *delay))
             .collect();
-
-        #[cfg(feature = "debug_gates")]
-        let new_probes = probes
+        self.reset_assumptions = std::mem::take(&mut self.reset_assumptions)
             .into_iter()
-            .map(|(idx, mut probe)| {
-                for bit in &mut probe.bits {
-                    *bit = index_map[bit]
-                }
-                (index_map[&idx], probe)
-            })
+            .filter_map(|(idx, on)| Some((*index_map.get(&idx)?, on)))
             .collect();
+        self.remap_ports(&index_map);
+        self.remap_clocks(&index_map);
+        self.remap_memories(&index_map);
+        index_map
+    }
 
-        let new_output_handles = output_handles
-            .into_iter()
-            .map(|mut output| {
-                for bit in &mut output.bits {
-                    *bit = index_map[bit]
-                }
-                output
-            })
-            .collect();
+    /// Returns a new [InitializedGateGraph] created from `self` without running optimizations.
+    pub fn init_unoptimized(self) -> InitializedGateGraph {
+        self.init_unoptimized_inner(&mut |_, _, _| {})
+    }
 
-        let new_lever_handles = lever_handles
-            .into_iter()
-            .map(|idx| index_map[&idx])
-            .collect();
+    /// Does the work of [GateGraphBuilder::init_unoptimized], reporting progress to `progress`.
+    fn init_unoptimized_inner(
+        self,
+        progress: &mut dyn FnMut(InitPhase, usize, usize),
+    ) -> InitializedGateGraph {
+        let span = tracing::info_span!(target: "logicsim::init", "init_unoptimized");
+        let _enter = span.enter();
 
-        let new_outputs = outputs.into_iter().map(|idx| index_map[&idx]).collect();
+        let warnings = self.find_init_warnings();
+        self.compacted(progress).initialize(warnings, progress)
+    }
 
-        CompactedGateGraph {
-            #[cfg(feature = "debug_gates")]
-            names: new_names,
-            nodes: new_nodes,
-            #[cfg(feature = "debug_gates")]
-            probes: new_probes,
-            outputs: new_outputs,
-            output_handles: new_output_handles,
-            lever_handles: new_lever_handles,
-        }
+    /// Runs all optimizations.
+    pub(super) fn optimize(&mut self, progress: &mut dyn FnMut(InitPhase, usize, usize)) {
+        self.optimize_with_config(&OptimizerConfig::default(), progress);
     }
 
-    /// Returns a new [InitializedGateGraph] created from `self` without running optimizations.
-    pub fn init_unoptimized(self) -> InitializedGateGraph {
+    /// Returns true if `gate` is a lever, it has been [kept](GateGraphBuilder::keep) or outputs/probes contain `gate`.
+    pub(super) fn is_observable(&self, gate: GateIndex) -> bool {
+        if gate.is_const() {
+            return true;
+        }
+        if self.outputs.contains(&gate) || self.kept.contains(&gate) {
+            return true;
+        }
+        if self.get(gate).ty.is_lever() {
+            return true;
+        }
         #[cfg(feature = "debug_gates")]
-        let CompactedGateGraph {
-            names,
-            nodes,
-            probes,
-            outputs,
-            output_handles,
-            lever_handles,
-        } = self.compacted();
-        #[cfg(not(feature = "debug_gates"))]
-        let CompactedGateGraph {
-            nodes,
-            outputs,
-            output_handles,
-            lever_handles,
-        } = self.compacted();
+        if self.probes.contains_key(&gate) {
+            return true;
+        }
+        false
+    }
 
-        let mut state = State::new(nodes.len());
-        state.set(OFF.idx, false);
-        state.set(ON.idx, true);
-        let mut new_graph = InitializedGateGraph {
-            #[cfg(feature = "debug_gates")]
-            names: names.into(),
-            nodes: nodes.into(),
-            #[cfg(feature = "debug_gates")]
-            probes: probes.into(),
-            outputs: outputs.into(),
-            output_handles: output_handles.into(),
-            lever_handles: lever_handles.into(),
-            propagation_queue: Default::default(),
-            pending_updates: Default::default(),
-            state,
-        };
-
-        for i in 0..new_graph.len() {
-            let idx = gi!(i);
-            if !idx.is_const() && new_graph.state.get_updated(i) {
-                continue;
-            }
-            new_graph.propagation_queue.push(idx);
-            new_graph.tick_inner();
-        }
-        new_graph.pending_updates.swap();
-        new_graph
-    }
-
-    /// Runs optimization `f` and prints the results of the optimization.
-    fn run_optimization<F: Fn(&mut GateGraphBuilder)>(&mut self, f: F, name: &'static str) {
-        let old_len = self.len();
-        f(self);
-        println!(
-            "Optimization: {}, old size:{}, new size:{}, reduction: {:.1}%",
-            name,
-            old_len,
-            self.len(),
-            (old_len - self.len()) as f32 / old_len as f32 * 100.
-        );
+    /// Prevents the optimizations from removing `gate` or merging it with other gates,
+    /// every gate that depended on it before optimization will still see its state.
+    ///
+    /// This is useful for redundant logic, like the replicas created by [triplicate](GateGraphBuilder::triplicate),
+    /// which would otherwise get merged back into a single copy.
+    pub fn keep(&mut self, gate: GateIndex) {
+        self.kept.insert(gate);
     }
 
-    /// Runs all optimizations.
-    fn optimize(&mut self) {
-        self.run_optimization(const_propagation_pass, "const propagation");
-        self.run_optimization(not_deduplication_pass, "not deduplication");
-        self.run_optimization(
-            single_dependency_collapsing_pass,
-            "single dependency collapsing",
+    /// Assumes that after the circuit is reset every gate in `bits` has the state of the constant with the same
+    /// index in `value`, and keeps it until the circuit is reset again, like a configuration register that is
+    /// never written or a flag that the rest of the circuit never sets.
+    ///
+    /// [OptimizationPass::ResetAssumptions] replaces those gates with the constants in the logic that depends on
+    /// them, which const propagation and dead code elimination then simplify. The assumptions are ignored unless
+    /// the pass runs, see [OptimizerConfig::assuming_reset], because with it the simplified logic doesn't behave
+    /// as it should before the circuit is reset, or if the assumptions are wrong.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `bits` and `value` have different lengths, if `value` contains gates other than [ON] and
+    /// [OFF] or if `bits` contains levers.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,OptimizerConfig,ON,OFF};
+    /// # use logicsim::{register,WordInput};
+    /// let mut g = GateGraphBuilder::new();
+    /// let clock = g.lever("clock");
+    /// let reset = g.lever("reset");
+    /// let a = WordInput::new(&mut g, 4, "a");
+    /// let b = WordInput::new(&mut g, 4, "b");
+    /// // Nothing ever writes the mode register, so it stays off after reset.
+    /// let mode = register(&mut g, clock.bit(), OFF, ON, reset.bit(), &[OFF], "mode");
+    /// let not_mode = g.not1(mode[0], "not_mode");
+    /// let selected: Vec<_> = a
+    ///     .bits()
+    ///     .into_iter()
+    ///     .zip(b.bits())
+    ///     .map(|(a, b)| {
+    ///         let a = g.and2(mode[0], a, "a");
+    ///         let b = g.and2(not_mode, b, "b");
+    ///         g.or2(a, b, "selected")
+    ///     })
+    ///     .collect();
+    /// let output = g.output(&selected, "selected");
+    /// g.assume_after_reset(&mode, &[OFF]);
+    ///
+    /// let default = g.clone().init();
+    /// let (ig, _) = &mut g.init_with_config(&OptimizerConfig::assuming_reset());
+    /// assert!(ig.len() < default.len());
+    ///
+    /// ig.pulse_lever_stable(reset);
+    /// a.set_to(ig, 10u8);
+    /// b.set_to_stable(ig, 5u8);
+    /// assert_eq!(output.u8(ig), 5);
+    /// ```
+    pub fn assume_after_reset(&mut self, bits: &[GateIndex], value: &[GateIndex]) {
+        assert_eq!(
+            bits.len(),
+            value.len(),
+            "Every bit needs a value after reset"
         );
-        self.run_optimization(dead_code_elimination_pass, "dead code elimination");
-        self.run_optimization(global_value_numbering_pass, "global value numbering");
-        self.run_optimization(equal_gate_merging_pass, "equal gate merging");
-        self.run_optimization(dependency_deduplication_pass, "dependency deduplication");
-        self.run_optimization(const_propagation_pass, "const propagation");
-    }
-
-    /// Returns true if `gate` is a lever or outputs/probes contain `gate`.
-    pub(super) fn is_observable(&self, gate: GateIndex) -> bool {
-        if gate.is_const() {
-            return true;
-        }
-        if self.outputs.contains(&gate) {
-            return true;
-        }
-        if self.get(gate).ty.is_lever() {
-            return true;
-        }
-        #[cfg(feature = "debug_gates")]
-        if self.probes.contains_key(&gate) {
-            return true;
+        for (bit, value) in bits.iter().zip(value) {
+            self.check(*bit);
+            assert!(value.is_const(), "Values after reset must be ON or OFF");
+            assert!(
+                !self.get(*bit).ty.is_lever(),
+                "Levers don't have a state after reset"
+            );
+            self.reset_assumptions.insert(*bit, value.is_on());
         }
-        false
     }
 
     /// Returns a new [OutputHandle] with name `name` for the gates in `bits`.
     ///
     /// See [OutputHandle] for gate querying methods.
+    #[track_caller]
     pub fn output<S: Into<String>>(&mut self, bits: &[GateIndex], name: S) -> OutputHandle {
         for bit in bits {
+            self.check(*bit);
             self.outputs.insert(*bit);
         }
         self.output_handles.push(Output {
@@ -614,10 +1275,66 @@ impl GateGraphBuilder {
     /// Returns a new [OutputHandle] with name `name` for a single gate `bit`.
     ///
     /// See [OutputHandle] for gate querying methods.
+    #[track_caller]
     pub fn output1<S: Into<String>>(&mut self, bit: GateIndex, name: S) -> OutputHandle {
         self.output(&[bit], name)
     }
 
+    /// Marks the gates in `bits` as a test point named `name`, meant for circuit authors to expose
+    /// internal signals that are interesting to test or debug without adding overhead for everyone else.
+    ///
+    /// When the "test_points" feature is disabled this does nothing.
+    /// When it's enabled, it creates an output that can be retrieved with
+    /// [InitializedGateGraph::test_point] and, if the "debug_gates" feature is enabled, [probes](GateGraphBuilder::probe) `bits`.
+    /// Keep in mind that observed gates can't be optimized away.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// let mut g = GateGraphBuilder::new();
+    /// let l1 = g.lever("l1");
+    /// let l2 = g.lever("l2");
+    ///
+    /// let and = g.and2(l1.bit(), l2.bit(), "and");
+    /// g.test_point(&[and], "and");
+    /// let xor = g.xor2(and, l2.bit(), "xor");
+    /// g.output1(xor, "xor");
+    ///
+    /// let ig = &mut g.init();
+    /// if let Some(and) = ig.test_point("and") {
+    ///     ig.set_lever_stable(l1);
+    ///     ig.set_lever_stable(l2);
+    ///     assert_eq!(and.b0(ig), true);
+    /// }
+    /// ```
+    pub fn test_point<S: Into<String>>(&mut self, bits: &[GateIndex], name: S) {
+        #[cfg(feature = "test_points")]
+        {
+            let name = name.into();
+            #[cfg(feature = "debug_gates")]
+            self.probe(bits, name.clone());
+            let output = self.output(bits, name.clone());
+            self.test_points.insert(name, output);
+        }
+        #[cfg(not(feature = "test_points"))]
+        let _ = (bits, name);
+    }
+
+    /// Returns a handle to every lever in the graph, in the order they were created.
+    pub fn lever_handles(&self) -> Vec<LeverHandle> {
+        self.lever_handles
+            .iter()
+            .enumerate()
+            .map(|(handle, idx)| LeverHandle { handle, idx: *idx })
+            .collect()
+    }
+
+    /// Returns a handle to every output in the graph, in the order they were created,
+    /// including the ones created for [test points](GateGraphBuilder::test_point).
+    pub fn output_handles(&self) -> Vec<OutputHandle> {
+        (0..self.output_handles.len()).map(OutputHandle).collect()
+    }
+
     /// Returns the number of gates in the graph.
     pub fn len(&self) -> usize {
         self.nodes.len()
@@ -649,30 +1366,83 @@ impl GateGraphBuilder {
     }
 
     /// Dumps the graph in [dot](https://en.wikipedia.org/wiki/DOT_(graph_description_language)) format
-    /// to path `filename`, to be visualized by many supported tools, I recommend [gephi](https://gephi.org/).
-    // TODO dry
-    pub fn dump_dot(&self, filename: &'static str) {
-        use petgraph::dot::{Config, Dot};
-        use std::io::Write;
-        let mut f = std::fs::File::create(filename).unwrap();
-        let mut graph = petgraph::Graph::<_, ()>::new();
-        let mut index = HashMap::new();
-        for (i, _) in self.nodes.iter() {
-            let label = self.full_name(i.into());
-            index.insert(i, graph.add_node(label));
-        }
-        for (i, node) in self.nodes.iter() {
-            graph.extend_with_edges(
-                node.dependencies
-                    .iter()
-                    .map(|dependency| (index[&dependency.into()], index[&i])),
-            );
-        }
-        write!(f, "{:?}", Dot::with_config(&graph, &[Config::EdgeNoLabel])).unwrap();
+    /// to `path`, to be visualized by many supported tools, I recommend [gephi](https://gephi.org/).
+    pub fn dump_dot<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.dump_dot_with_config(path, &DotConfig::default())
+    }
+
+    /// Dumps the part of the graph selected by `config` in dot format to `path`, clustered as it says,
+    /// see [DotConfig]. Large graphs are much easier to look at one cone at a time.
+    ///
+    /// Returns an error if `config` names an output or probe that doesn't exist.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,DotConfig};
+    /// let mut g = GateGraphBuilder::new();
+    /// let a = g.lever("a");
+    /// let not = g.module("inverter", |g| g.not1(a.bit(), "not"));
+    /// g.output1(not, "out");
+    ///
+    /// let path = std::env::temp_dir().join("logicsim_dump_dot_doctest.dot");
+    /// let config = DotConfig {
+    ///     cluster_depth: 1,
+    ///     ..DotConfig::cone(vec!["out"])
+    /// };
+    /// g.dump_dot_with_config(&path, &config).unwrap();
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn dump_dot_with_config<P: AsRef<Path>>(
+        &self,
+        path: P,
+        config: &DotConfig,
+    ) -> io::Result<()> {
+        let mut f = BufWriter::new(File::create(path)?);
+        self.write_dot(&mut f, config)?;
+        f.flush()
+    }
+
+    /// Writes the graph in dot format to `writer`, see [GateGraphBuilder::dump_dot_with_config].
+    pub(crate) fn write_dot<W: Write>(&self, writer: &mut W, config: &DotConfig) -> io::Result<()> {
+        #[allow(unused_mut)]
+        let mut named: Vec<(&str, &[GateIndex])> = self
+            .output_handles
+            .iter()
+            .map(|output| (output.name.as_str(), &output.bits[..]))
+            .collect();
+        #[cfg(feature = "debug_gates")]
+        named.extend(
+            self.probes
+                .values()
+                .map(|probe| (probe.name.as_str(), &probe.bits[..])),
+        );
+        let roots = cone_roots(config, &named)?;
+        let gates: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|(i, gate)| DotGate {
+                index: i.into(),
+                label: self.full_name(i.into()),
+                #[cfg(feature = "debug_gates")]
+                name: self.name(i.into()),
+                #[cfg(not(feature = "debug_gates"))]
+                name: "",
+                dependencies: &gate.dependencies,
+            })
+            .collect();
+        write_dot(writer, &gates, &roots, config)
     }
 
     /// "Probes" the gates in `bits`, meaning that whenever the state of any of them changes,
-    /// the new state of the group will be printed to stdout along with `name`.
+    /// the new state of the group will be logged along with `name`, and a `tick` field with the
+    /// [simulation time](InitializedGateGraph::now).
+    ///
+    /// Probes of 1 bit are logged as true or false, probes of up to 128 bits in decimal like [OutputHandle::u128],
+    /// and wider probes in hexadecimal with a digit for every 4 bits, like "0x00ff".
+    ///
+    /// Probe values are logged at the info level to the "logicsim::probe" target,
+    /// so you need to install a logger (like [env_logger](https://docs.rs/env_logger))
+    /// or a [tracing](https://docs.rs/tracing) subscriber to see them.
     ///
     /// # Example
     /// ```
@@ -704,16 +1474,20 @@ impl GateGraphBuilder {
     /// ig.reset_lever_stable(l2);
     /// assert_eq!(xor_output.b0(ig), false);
     /// ```
-    /// In the terminal you'll see:
+    /// With a logger installed, you'll see:
     /// ```sh
-    /// or_xor: 3
-    /// or_xor: 1
-    /// or_xor: 3
-    /// or_xor: 0
+    /// or_xor:2 tick=2
+    /// or_xor:3 tick=2
+    /// or_xor:1 tick=4
+    /// or_xor:0 tick=4
+    /// or_xor:2 tick=6
+    /// or_xor:3 tick=6
+    /// or_xor:1 tick=8
+    /// or_xor:0 tick=8
     /// ```
     #[cfg(feature = "debug_gates")]
     pub fn probe<S: Into<String>>(&mut self, bits: &[GateIndex], name: S) {
-        let name = name.into();
+        let name = self.qualified_name(name.into());
         for bit in bits {
             self.probes.insert(
                 *bit,
@@ -726,13 +1500,126 @@ impl GateGraphBuilder {
     }
 
     /// "Probes" the gate `bit`, meaning that whenever its state changes,
-    /// the new state will be printed to stdout along with `name`.
+    /// the new state will be logged along with `name`.
     #[cfg(feature = "debug_gates")]
     pub fn probe1<S: Into<String>>(&mut self, bit: GateIndex, name: S) {
         self.probe(&[bit], name)
     }
 }
 
+/// Returns true if gates of type `ty` can have any number of dependencies.
+fn has_variable_dependencies(ty: GateType) -> bool {
+    matches!(ty, Or | Nor | And | Nand | Xor | Xnor)
+}
+
+impl CompactedGateGraph {
+    /// Returns a new [InitializedGateGraph] created from `self` by propagating the initial state of every gate,
+    /// `warnings` are the ones [GateGraphBuilder::find_init_warnings] returned before optimizing.
+    pub(super) fn initialize(
+        self,
+        warnings: Vec<InitWarning>,
+        progress: &mut dyn FnMut(InitPhase, usize, usize),
+    ) -> InitializedGateGraph {
+        #[cfg(feature = "debug_gates")]
+        let CompactedGateGraph {
+            names,
+            nodes,
+            probes,
+            outputs,
+            output_handles,
+            lever_handles,
+            test_points,
+            custom_gates,
+            event_sources,
+            clocks,
+            gate_delays,
+            assertions,
+            memories,
+            checked_wires,
+        } = self;
+        #[cfg(not(feature = "debug_gates"))]
+        let CompactedGateGraph {
+            nodes,
+            outputs,
+            output_handles,
+            lever_handles,
+            test_points,
+            custom_gates,
+            event_sources,
+            clocks,
+            gate_delays,
+            assertions,
+            memories,
+        } = self;
+
+        let mut state = State::new(nodes.len());
+        state.set(OFF.idx, false);
+        state.set(ON.idx, true);
+        let mut new_graph = InitializedGateGraph {
+            #[cfg(feature = "debug_gates")]
+            names: names.into(),
+            nodes: nodes.into(),
+            #[cfg(feature = "debug_gates")]
+            probes: probes.into(),
+            outputs: outputs.into(),
+            output_handles: output_handles.into(),
+            lever_handles: lever_handles.into(),
+            test_points: test_points.into(),
+            custom_gates: custom_gates.into(),
+            propagation_queue: Default::default(),
+            pending_updates: Default::default(),
+            state,
+            delayed: Default::default(),
+            event_log: EventLog::new(event_sources),
+            subscriptions: Default::default(),
+            ticks: 0,
+            peripherals: Default::default(),
+            json_traces: Default::default(),
+            published_outputs: None,
+            contention: Default::default(),
+            clocks: clocks.into(),
+            profiler: None,
+            toggles: None,
+            gate_delays: gate_delays.into(),
+            scheduled: Default::default(),
+            watches: Default::default(),
+            assertions: Assertions::new(assertions),
+            memories: Memories::new(memories),
+            faults: Default::default(),
+            #[cfg(feature = "debug_gates")]
+            drivers: DriverMonitor::new(checked_wires),
+            warnings,
+        };
+        new_graph.contention = ContentionMonitor::new(&new_graph.nodes);
+
+        let total = new_graph.len();
+        progress(InitPhase::Propagation, 0, total);
+        for i in 0..total {
+            if i % PROGRESS_INTERVAL == 0 {
+                progress(InitPhase::Propagation, i, total);
+            }
+            let idx = gi!(i);
+            if !idx.is_const() && new_graph.state.get_updated(i) {
+                continue;
+            }
+            new_graph.propagation_queue.push(idx);
+            new_graph.tick_inner();
+        }
+        new_graph.release_delays();
+        new_graph.release_scheduled();
+        new_graph.pending_updates.swap();
+        new_graph.arm_events();
+        new_graph.arm_memories();
+        progress(InitPhase::Propagation, total, total);
+        tracing::debug!(
+            target: "logicsim::init",
+            "Initialized graph with {} gates",
+            new_graph.len()
+        );
+        new_graph
+    }
+}
+
 impl Default for GateGraphBuilder {
     fn default() -> Self {
         Self::new()
@@ -810,4 +1697,613 @@ mod tests {
 
         assert_eq!(output.b0(g), true)
     }
+    #[test]
+    fn test_not_of_output() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let l1 = g.lever("l1");
+        let l2 = g.lever("l2");
+        let or = g.or2(l1.bit(), l2.bit(), "or");
+        let not = g.not1(or, "not");
+        let xor = g.xor2(not, l2.bit(), "xor");
+        let or_output = g.output1(or, "or");
+        let xor_output = g.output1(xor, "xor");
+        let g = &mut graph.init();
+
+        assert_eq!(or_output.b0(g), false);
+        assert_eq!(xor_output.b0(g), true);
+
+        g.set_lever_stable(l1);
+        assert_eq!(or_output.b0(g), true);
+        assert_eq!(xor_output.b0(g), false);
+    }
+    #[test]
+    fn test_subscribe() {
+        use std::sync::{Arc, Mutex};
+
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let l = g.lever("l");
+        let n1 = g.not1(OFF, "n1");
+        let n2 = g.not1(n1, "n2");
+        let n3 = g.not1(n2, "n3");
+        g.d0(n1, n3);
+        let oscillator = g.output1(n1, "n1");
+        let lever = g.output1(l.bit(), "l");
+        let g = &mut graph.init();
+
+        let oscillator_changes = Arc::new(Mutex::new(Vec::new()));
+        let changes = oscillator_changes.clone();
+        let oscillator_subscription = g.subscribe(oscillator, move |value, tick| {
+            changes.lock().unwrap().push((value, tick))
+        });
+
+        let lever_changes = Arc::new(Mutex::new(Vec::new()));
+        let changes = lever_changes.clone();
+        g.subscribe(lever, move |value, tick| {
+            changes.lock().unwrap().push((value, tick))
+        });
+
+        for _ in 0..4 {
+            g.tick();
+        }
+        assert_eq!(
+            *oscillator_changes.lock().unwrap(),
+            [(0, 1), (1, 2), (0, 3), (1, 4)]
+        );
+
+        g.unsubscribe(oscillator_subscription);
+        // Single tick pulses are not missed.
+        g.pulse_lever(l);
+        g.tick();
+        assert_eq!(oscillator_changes.lock().unwrap().len(), 4);
+        assert_eq!(*lever_changes.lock().unwrap(), [(1, 5), (0, 6)]);
+    }
+    #[test]
+    fn test_init_with_progress() {
+        let mut g = GateGraphBuilder::new();
+        let l = g.lever("l");
+        let mut last = l.bit();
+        for i in 0..100 {
+            last = g.xor2(last, l.bit(), format!("xor{}", i));
+        }
+        // Dead gates make compaction do some work.
+        g.and2(l.bit(), l.bit(), "dead");
+        let output = g.output1(last, "out");
+
+        let mut reports = Vec::new();
+        let ig = &g.init_with_progress(|phase, done, total| reports.push((phase, done, total)));
+        assert_eq!(output.b0(ig), false);
+
+        let phases: Vec<_> = reports
+            .iter()
+            .filter(|(_, done, total)| done == total)
+            .map(|(phase, _, _)| *phase)
+            .collect();
+        assert_eq!(
+            phases.first(),
+            Some(&InitPhase::Optimization("const propagation"))
+        );
+        assert_eq!(
+            &phases[phases.len() - 2..],
+            &[InitPhase::Compaction, InitPhase::Propagation]
+        );
+
+        for window in reports.windows(2) {
+            let ((p1, d1, t1), (p2, d2, _)) = (window[0], window[1]);
+            assert!(d1 <= t1);
+            if p1 == p2 && d1 != t1 {
+                assert!(d1 <= d2);
+            }
+        }
+    }
+
+    #[test]
+    fn test_test_point() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let l = g.lever("l");
+        let not1 = g.not1(l.bit(), "not1");
+        let not2 = g.not1(not1, "not2");
+        g.test_point(&[not1, not2], "nots");
+        let output = g.output1(not2, "out");
+
+        let g = &mut graph.init();
+        g.set_lever_stable(l);
+        assert_eq!(output.b0(g), true);
+
+        #[cfg(feature = "test_points")]
+        {
+            let nots = g.test_point("nots").unwrap();
+            assert_eq!(nots.u8(g), 0b10);
+            g.reset_lever_stable(l);
+            assert_eq!(nots.u8(g), 0b01);
+        }
+        #[cfg(not(feature = "test_points"))]
+        assert!(g.test_point("nots").is_none());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Stale GateIndex")]
+    fn test_stale_gate_index() {
+        let mut g = GateGraphBuilder::new();
+        let l = g.lever("l");
+        let stale = g.not1(l.bit(), "stale");
+        g.get_mut(l.bit()).dependents.remove(&stale);
+        g.nodes.remove(stale.into());
+
+        // Reuses the slot of the removed gate.
+        let new = g.and2(l.bit(), ON, "new");
+        assert_eq!(new, stale);
+        g.get(new);
+        g.not1(stale, "not");
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "belongs to another GateGraphBuilder")]
+    fn test_foreign_gate_index() {
+        let mut g = GateGraphBuilder::new();
+        let l = g.lever("l");
+        let not = g.not1(l.bit(), "not");
+
+        // Clones and constants are fine.
+        let mut clone = g.clone();
+        clone.or2(not, ON, "or");
+        assert!(clone.contains(not));
+
+        let mut other = GateGraphBuilder::new();
+        other.lever("other");
+        assert!(!other.contains(not));
+        other.and2(not, OFF, "and");
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "belongs to another GateGraphBuilder")]
+    fn test_dx_foreign_gate_index() {
+        let mut g = GateGraphBuilder::new();
+        let l = g.lever("l");
+        let not = g.not1(l.bit(), "not");
+
+        let mut other = GateGraphBuilder::new();
+        let other_not = other.not1(OFF, "other");
+        g.d0(not, other_not);
+    }
+
+    #[test]
+    fn test_compact_now() {
+        let mut g = GateGraphBuilder::new();
+        let l = g.lever("l");
+        let dead = g.not1(l.bit(), "dead");
+        let a = g.not1(l.bit(), "a");
+        let b = g.not1(a, "b");
+        let b_output = g.output1(b, "b");
+        g.get_mut(l.bit()).dependents.remove(&dead);
+        g.nodes.remove(dead.into());
+        assert_eq!(
+            g.fragmentation(),
+            Fragmentation {
+                empty_slots: 1,
+                total_slots: 6
+            }
+        );
+
+        let map = g.compact_now();
+        assert_eq!(g.fragmentation().empty_slots, 0);
+        assert_eq!(g.len(), 5);
+        assert_eq!(map.len(), 5);
+        assert_eq!(map[&l.bit()], l.bit());
+        assert_eq!(map[&a], gi!(3));
+        assert_eq!(map[&b], gi!(4));
+
+        // Old handles keep working and new gates can use the translated indexes.
+        let c = g.not1(map[&b], "c");
+        let c_output = g.output1(c, "c");
+        let ig = &mut g.init();
+        assert_eq!(b_output.b0(ig), false);
+        assert_eq!(c_output.b0(ig), true);
+        ig.set_lever_stable(l);
+        assert_eq!(b_output.b0(ig), true);
+        assert_eq!(c_output.b0(ig), false);
+    }
+
+    #[test]
+    fn test_lever_after_compact_now() {
+        let mut g = GateGraphBuilder::new();
+        let a = g.lever("a");
+        let dead = g.not1(a.bit(), "dead");
+        let spacer = g.not1(a.bit(), "spacer");
+        g.remove_gate(dead);
+        // Reuses the slot of the removed gate.
+        let l = g.lever("l");
+        let clock = g.clock(2, "clock");
+        // Makes the clock move.
+        g.remove_gate(spacer);
+
+        let map = g.compact_now();
+        let l = l.translated(&map);
+        let clock = clock.translated(&map);
+        let and = g.and2(a.bit(), l.bit(), "and");
+        let and = g.output1(and, "and");
+        let clock_output = g.output1(clock.bit(), "clock");
+
+        let ig = &mut g.init();
+        ig.set_lever_stable(a);
+        assert_eq!(and.b0(ig), false);
+        ig.set_lever_stable(l);
+        assert_eq!(and.b0(ig), true);
+        ig.step_clocks(2);
+        assert_eq!(clock_output.b0(ig), true);
+    }
+
+    #[test]
+    fn test_compact_now_after_reusing_a_slot() {
+        let mut g = GateGraphBuilder::new();
+        let a = g.lever("a");
+        let dead = g.not1(a.bit(), "dead");
+        g.remove_gate(dead);
+        // Reuses the slot of the removed gate, nothing is left to move.
+        let not = g.not1(a.bit(), "not");
+        let or = g.or2(not, a.bit(), "or");
+        let not_output = g.output1(not, "not");
+
+        let map = g.compact_now();
+        assert_eq!(map[&not], not);
+        let or = map[&or];
+        let or_output = g.output1(or, "or");
+
+        let ig = &mut g.init();
+        ig.run_until_stable(10).unwrap();
+        assert_eq!(not_output.b0(ig), true);
+        assert_eq!(or_output.b0(ig), true);
+        ig.set_lever_stable(a);
+        assert_eq!(not_output.b0(ig), false);
+        assert_eq!(or_output.b0(ig), true);
+    }
+
+    #[test]
+    fn test_delay_chain() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let l = g.lever("l");
+        let mut last = l.bit();
+        for i in 0..3 {
+            last = g.delay(last, format!("delay{}", i));
+        }
+        // Optimization shouldn't collapse the chain.
+        let and = g.and2(last, ON, "and");
+        let output = g.output1(and, "out");
+
+        let g = &mut graph.init();
+        g.run_until_stable(10).unwrap();
+
+        // One tick for the lever and one for each delay.
+        g.set_lever(l);
+        for _ in 0..4 {
+            assert_eq!(output.b0(g), false);
+            g.tick();
+        }
+        assert_eq!(output.b0(g), true);
+        assert_eq!(g.run_until_stable(10), Ok(0));
+    }
+
+    #[test]
+    fn test_delay_oscillator() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let delay = g.delay(OFF, "delay");
+        let not = g.not1(delay, "not");
+        g.dx(delay, not, 0);
+        let output = g.output1(delay, "out");
+
+        let g = &mut graph.init();
+        let mut last = output.b0(g);
+        for _ in 0..10 {
+            g.tick();
+            assert_ne!(output.b0(g), last);
+            last = output.b0(g);
+        }
+        assert!(g.run_until_stable(100).is_err());
+    }
+
+    #[cfg(feature = "debug_gates")]
+    #[test]
+    fn test_modules() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let a = g.lever("a");
+        let (not, carry) = g.module("alu", |g| {
+            let not = g.not1(a.bit(), "not");
+            g.begin_module("adder");
+            let carry = g.and2(a.bit(), not, "carry3");
+            g.probe1(carry, "carry");
+            g.end_module();
+            (not, carry)
+        });
+        let outside = g.or2(not, carry, "outside");
+        assert_eq!(g.module_path(), "");
+        g.output1(not, "not");
+        g.output1(carry, "carry");
+        g.output1(outside, "outside");
+
+        assert_eq!(g.names[&a.bit()], "a");
+        assert_eq!(g.names[&not], "alu/not");
+        assert_eq!(g.names[&carry], "alu/adder/carry3");
+        assert_eq!(g.names[&outside], "outside");
+        assert_eq!(g.probes[&carry].name, "alu/adder/carry");
+
+        let ig = &graph.init_unoptimized();
+        assert_eq!(ig.full_name(carry), "OUT:And:alu/adder/carry3");
+        let mut dot = Vec::new();
+        ig.write_dot(&mut dot, &DotConfig::default()).unwrap();
+        assert!(String::from_utf8(dot).unwrap().contains("alu/adder/carry3"));
+    }
+
+    #[cfg(feature = "debug_gates")]
+    #[test]
+    fn test_wide_probes() {
+        use crate::circuits::WordInput;
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let inputs: Vec<WordInput> = [1, 16, 32, 64, 128, 130]
+            .iter()
+            .map(|width| {
+                let input = WordInput::new(g, *width, "input");
+                g.probe(&input.bits(), format!("probe{}", width));
+                g.output(&input.bits(), "input");
+                input
+            })
+            .collect();
+        let ig = &mut graph.init();
+        for input in &inputs {
+            input.set_to(ig, u128::MAX);
+        }
+        // Changing probes of any width doesn't panic.
+        ig.run_until_stable(10).unwrap();
+        inputs[5].set_bit(ig, 129);
+        ig.run_until_stable(10).unwrap();
+
+        let format = |ig: &InitializedGateGraph, input: &WordInput| {
+            ig.format_probe(&ig.probes[&input.bits()[0]])
+        };
+        assert_eq!(format(ig, &inputs[0]), "true");
+        assert_eq!(format(ig, &inputs[1]), "65535");
+        assert_eq!(format(ig, &inputs[2]), u32::MAX.to_string());
+        assert_eq!(format(ig, &inputs[3]), u64::MAX.to_string());
+        assert_eq!(format(ig, &inputs[4]), u128::MAX.to_string());
+        assert_eq!(
+            format(ig, &inputs[5]),
+            "0x2ffffffffffffffffffffffffffffffff"
+        );
+
+        inputs[2].set_to(ig, 0x1234u32);
+        inputs[5].set_to(ig, 0xabcu128);
+        ig.run_until_stable(10).unwrap();
+        assert_eq!(format(ig, &inputs[2]), "4660");
+        // Leading zeros show the width.
+        assert_eq!(format(ig, &inputs[5]), format!("0x2{:032x}", 0xabc));
+    }
+
+    #[test]
+    fn test_remove_gate() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let a = g.lever("a");
+        let b = g.lever("b");
+        let and = g.and2(a.bit(), b.bit(), "and");
+        let or = g.or2(and, b.bit(), "or");
+        let not = g.not1(and, "not");
+        let xor = g.xor2(and, and, "xor");
+        let and_output = g.output(&[and, a.bit()], "and");
+        let or_output = g.output1(or, "or");
+        let not_output = g.output1(not, "not");
+        let xor_output = g.output1(xor, "xor");
+
+        g.remove_gate(and);
+        assert!(g.get(a.bit()).dependents.is_empty());
+        assert_eq!(g.get(or).dependencies.as_slice(), [b.bit()]);
+        assert_eq!(g.get(not).dependencies.as_slice(), [OFF]);
+        assert!(g.get(xor).dependencies.is_empty());
+        assert!(g.get(OFF).dependents.contains(&not));
+
+        let ig = &mut graph.init();
+        ig.set_lever_stable(a);
+        assert_eq!(and_output.u8(ig), 0b10);
+        assert_eq!(or_output.b0(ig), false);
+        assert_eq!(not_output.b0(ig), true);
+        assert_eq!(xor_output.b0(ig), false);
+        ig.set_lever_stable(b);
+        assert_eq!(or_output.b0(ig), true);
+    }
+
+    #[test]
+    fn test_dremove_and_disconnect_all() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let a = g.lever("a");
+        let b = g.lever("b");
+        let or = g.or2(a.bit(), b.bit(), "or");
+        g.dpush(or, a.bit());
+        let not = g.not1(or, "not");
+
+        g.dremove(or, 0);
+        // a is still a dependency.
+        assert!(g.get(a.bit()).dependents.contains(&or));
+        g.dremove(or, 1);
+        assert!(!g.get(a.bit()).dependents.contains(&or));
+        assert_eq!(g.get(or).dependencies.as_slice(), [b.bit()]);
+
+        g.dx(or, a.bit(), 0);
+        g.dpush(or, a.bit());
+        g.dx(or, b.bit(), 0);
+        assert!(g.get(a.bit()).dependents.contains(&or));
+
+        g.disconnect_all(or);
+        assert!(g.get(or).dependencies.is_empty());
+        assert!(g.get(a.bit()).dependents.is_empty());
+        assert!(g.get(b.bit()).dependents.is_empty());
+        g.disconnect_all(not);
+        assert_eq!(g.get(not).dependencies.as_slice(), [OFF]);
+        assert!(g.get(or).dependents.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "fixed number of dependencies")]
+    fn test_dremove_fixed() {
+        let mut g = GateGraphBuilder::new();
+        let a = g.lever("a");
+        let not = g.not1(a.bit(), "not");
+        g.dremove(not, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "read by an event, assertion or behavioral memory")]
+    fn test_remove_gate_with_event() {
+        let mut g = GateGraphBuilder::new();
+        let a = g.lever("a");
+        let not = g.not1(a.bit(), "not");
+        g.event("event", a.bit(), &[not]);
+        g.remove_gate(not);
+    }
+
+    #[test]
+    #[should_panic(expected = "read by an event, assertion or behavioral memory")]
+    fn test_remove_gate_with_assertion() {
+        let mut g = GateGraphBuilder::new();
+        let a = g.lever("a");
+        let not = g.not1(a.bit(), "not");
+        g.assert_never(not, "not is never on");
+        g.remove_gate(not);
+    }
+
+    #[test]
+    #[should_panic(expected = "without a matching begin_module")]
+    fn test_unmatched_end_module() {
+        GateGraphBuilder::new().end_module();
+    }
+
+    #[test]
+    fn test_reproducible_init() {
+        use crate::circuits::{adder, counter, ram, register, zeros, WordInput};
+        let build = || {
+            let mut graph = GateGraphBuilder::new();
+            let g = &mut graph;
+            let clock = g.lever("clock");
+            let reset = g.lever("reset");
+            let input = WordInput::new(g, 4, "input");
+            let address = WordInput::new(g, 2, "address");
+            let count = counter(
+                g,
+                clock.bit(),
+                ON,
+                OFF,
+                ON,
+                reset.bit(),
+                &zeros(4),
+                "counter",
+            );
+            let sum = adder(g, OFF, &count, &input.bits(), "adder");
+            let stored = register(g, clock.bit(), ON, ON, reset.bit(), &sum, "register");
+            let memory = ram(
+                g,
+                ON,
+                ON,
+                clock.bit(),
+                reset.bit(),
+                &address.bits(),
+                &stored,
+                "ram",
+            );
+            g.output(&memory, "ram");
+            for (i, bit) in sum.iter().enumerate() {
+                #[cfg(feature = "debug_gates")]
+                g.probe1(*bit, format!("sum{}", i));
+                g.test_point(&[*bit], format!("sum{}", i));
+                g.set_delay(*bit, i);
+            }
+            g.keep(count[0]);
+            // Duplicate gates that global value numbering merges, each with its own dependents.
+            for i in 0..8 {
+                let duplicate = g.and2(clock.bit(), reset.bit(), "duplicate");
+                let other = g.lever(format!("other{}", i));
+                let dependent = g.xor2(duplicate, other.bit(), "dependent");
+                g.output1(dependent, format!("dependent{}", i));
+            }
+            graph
+        };
+        let dump = |ig: &InitializedGateGraph| {
+            let mut bytes = Vec::new();
+            ig.write_dot(&mut bytes, &Default::default()).unwrap();
+            for gate in ig.gates() {
+                let dependents: Vec<_> = ig.dependents(gate).map(|d| d.to_string()).collect();
+                writeln!(bytes, "{} {}", gate, dependents.join(" ")).unwrap();
+            }
+            bytes
+        };
+
+        assert_eq!(dump(&build().init()), dump(&build().init()));
+        #[cfg(feature = "serde")]
+        {
+            let dir = std::env::temp_dir();
+            let paths = [
+                dir.join("logicsim_reproducible_init_a"),
+                dir.join("logicsim_reproducible_init_b"),
+            ];
+            for path in &paths {
+                let _ = std::fs::remove_file(path);
+                build().init_cached(path);
+            }
+            let caches: Vec<_> = paths.iter().map(|p| std::fs::read(p).unwrap()).collect();
+            assert_eq!(caches[0], caches[1]);
+            for path in &paths {
+                std::fs::remove_file(path).unwrap();
+            }
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        use crate::circuits::{counter, zeros};
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let count = counter(
+            g,
+            clock.bit(),
+            ON,
+            OFF,
+            ON,
+            reset.bit(),
+            &zeros(4),
+            "counter",
+        );
+        let output = g.output(&count, "count");
+        g.probe(&count, "count");
+        g.test_point(&count, "count");
+
+        let bytes = bincode::serialize(&graph).unwrap();
+        let loaded: GateGraphBuilder = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(graph.content_hash(), loaded.content_hash());
+
+        let run = |g: GateGraphBuilder| {
+            let ig = &mut g.init();
+            ig.pulse_lever_stable(reset);
+            (0..20)
+                .map(|_| {
+                    ig.pulse_lever_stable(clock);
+                    output.u8(ig)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(run(graph), run(loaded));
+    }
 }
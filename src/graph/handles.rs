@@ -1,11 +1,15 @@
 use super::GateIndex;
 use super::InitializedGateGraph;
 use concat_idents::concat_idents;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
+use std::collections::HashMap;
 
 /// Data structure that represents a probe into a gate graph, whenever any of the gates in the probe changes its state,
-/// The new value of all of the bits will be printed to stdout along with the name.
+/// The new value of all of the bits will be logged to the "logicsim::probe" target along with the name.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg(feature = "debug_gates")]
 pub(super) struct Probe {
     pub name: String,
@@ -13,6 +17,7 @@ pub(super) struct Probe {
 }
 /// Handle type that represents a lever gate in an [InitializedGateGraph] or [GateGraphBuilder](super::GateGraphBuilder)
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct LeverHandle {
     pub(super) handle: usize,
     pub(super) idx: GateIndex,
@@ -22,6 +27,15 @@ impl LeverHandle {
     pub fn bit(&self) -> GateIndex {
         self.idx
     }
+
+    /// Returns the handle of the same lever after [compact_now](super::GateGraphBuilder::compact_now)
+    /// moved it, `map` is the map it returned.
+    pub fn translated(self, map: &HashMap<GateIndex, GateIndex>) -> LeverHandle {
+        LeverHandle {
+            handle: self.handle,
+            idx: map[&self.idx],
+        }
+    }
 }
 
 /// Generates the type() functions for [Output].
@@ -38,7 +52,8 @@ macro_rules! circuit_outputs {
             /// If there are more bits than [size_of::\<type\>](std::mem::size_of),
             /// the excess bits will be ignored.
             ///
-            /// If there are less bits, the value will be 0 extended.
+            /// If there are less bits, the value will be 0 extended, see [signed](OutputHandle::signed)
+            /// to sign extend it.
             pub fn $ty(self, g: &InitializedGateGraph) -> $ty {
                 g.collect_t(&g.get_output(self).bits)
             }
@@ -57,11 +72,21 @@ macro_rules! circuit_outputs {
 /// or [GateGraphBuilder](super::GateGraphBuilder) which we want to query.
 #[repr(transparent)]
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct OutputHandle(pub(super) usize);
 
+/// Handle type that represents a callback registered with [InitializedGateGraph::subscribe].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct SubscriptionHandle(pub(super) usize);
+
+/// Handle type that represents a watchpoint added with [InitializedGateGraph::add_watch].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct WatchHandle(pub(super) usize);
+
 /// Data structure that stores a set of gates in an [InitializedGateGraph]
 /// or [GateGraphBuilder](super::GateGraphBuilder) which we want to query. Along with a name.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub(super) struct Output {
     pub(super) name: String,
     pub(super) bits: SmallVec<[GateIndex; 1]>,
@@ -69,6 +94,11 @@ pub(super) struct Output {
 impl OutputHandle {
     circuit_outputs!(u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, char);
 
+    /// Returns the name of the output.
+    pub fn name(self, g: &InitializedGateGraph) -> &str {
+        &g.get_output(self).name
+    }
+
     // Returns the state of the `n` bit of the output.
     pub fn bx(self, g: &InitializedGateGraph, n: usize) -> bool {
         g.value(g.get_output(self).bits[n])
@@ -78,4 +108,98 @@ impl OutputHandle {
     pub fn b0(&self, g: &InitializedGateGraph) -> bool {
         self.bx(g, 0)
     }
+
+    /// Returns the value of the output as a two's complement number as wide as the output, sign extended to [i128].
+    /// Unlike the fixed width accessors like [i8](OutputHandle::i8), it works for outputs of any width.
+    ///
+    /// If there are more than 128 bits, the excess bits will be ignored.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::{GateGraphBuilder,WordInput};
+    /// let mut g = GateGraphBuilder::new();
+    /// let input = WordInput::new(&mut g, 4, "input");
+    /// let output = g.output(&input.bits(), "output");
+    ///
+    /// let ig = &mut g.init();
+    /// input.set_to_stable(ig, 0b1110u8);
+    /// assert_eq!(output.i8(ig), 14);
+    /// assert_eq!(output.signed(ig), -2);
+    /// ```
+    pub fn signed(self, g: &InitializedGateGraph) -> i128 {
+        let width = self.width(g).min(128);
+        if width == 0 {
+            return 0;
+        }
+        let shift = 128 - width as u32;
+        ((self.u128(g) << shift) as i128) >> shift
+    }
+
+    /// Returns the number of bits in the output.
+    pub fn width(self, g: &InitializedGateGraph) -> usize {
+        g.get_output(self).bits.len()
+    }
+
+    /// Returns the state of every bit of the output, bit 0 first, for outputs of any width.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::{GateGraphBuilder,constant};
+    /// let mut g = GateGraphBuilder::new();
+    /// let output = g.output(&constant(0x1234u16), "output");
+    ///
+    /// let ig = &g.init();
+    /// assert_eq!(output.width(ig), 16);
+    /// assert_eq!(output.u16(ig), 0x1234);
+    /// assert_eq!(output.u32(ig), 0x1234);
+    /// assert_eq!(output.bits(ig)[..4], [false, false, true, false]);
+    /// ```
+    pub fn bits(self, g: &InitializedGateGraph) -> Vec<bool> {
+        g.get_output(self)
+            .bits
+            .iter()
+            .map(|bit| g.value(*bit))
+            .collect()
+    }
+
+    /// Returns the gates of the output in `g`, bit 0 first, for example to
+    /// [inject faults](InitializedGateGraph::inject_fault) in them.
+    ///
+    /// They usually differ from the [GateIndexes](super::GateIndex) used to build the output, since the graph gets
+    /// compacted when it's initialized.
+    pub fn gates(self, g: &InitializedGateGraph) -> &[GateIndex] {
+        &g.get_output(self).bits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::WordInput;
+
+    #[test]
+    fn test_output_accessors() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let input = WordInput::new(g, 72, "input");
+        let output = g.output(&input.bits(), "output");
+        let ig = &mut graph.init();
+        input.set_to(ig, 0x8000_0000_8000_8081u64);
+        ig.run_until_stable(10).unwrap();
+
+        assert_eq!(output.width(ig), 72);
+        assert_eq!(output.u8(ig), 0x81);
+        assert_eq!(output.i8(ig), -127);
+        assert_eq!(output.u16(ig), 0x8081);
+        assert_eq!(output.i16(ig), 0x8081u16 as i16);
+        assert_eq!(output.u32(ig), 0x8000_8081);
+        assert_eq!(output.u64(ig), 0x8000_0000_8000_8081);
+        assert_eq!(output.i64(ig), 0x8000_0000_8000_8081u64 as i64);
+        assert_eq!(output.u128(ig), 0x8000_0000_8000_8081);
+
+        let bits = output.bits(ig);
+        assert_eq!(bits.len(), 72);
+        let ones: Vec<usize> = (0..72).filter(|i| bits[*i]).collect();
+        assert_eq!(ones, [0, 7, 15, 31, 63]);
+    }
 }
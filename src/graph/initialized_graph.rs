@@ -1,8 +1,31 @@
+use super::assertions::Assertions;
+use super::behavioral_memory::Memories;
+use super::clocks::Clock;
+use super::contention::ContentionMonitor;
+use super::custom_gate::{CustomGate, CUSTOM_GATE_MAX_ARITY};
+use super::dot::{cone_roots, write_dot, DotConfig, DotGate};
+#[cfg(feature = "debug_gates")]
+use super::drivers::DriverMonitor;
+use super::events::EventLog;
+use super::faults::Faults;
 use super::gate::*;
+use super::gate_delays::ScheduledChanges;
 use super::handles::*;
+use super::json_trace::JsonTrace;
+use super::peripherals::Peripherals;
+use super::power::ToggleCounter;
+use super::profile::Profiler;
+use super::published_outputs::OutputSnapshot;
+use super::simulation_error::SimulationError;
+use super::warnings::InitWarning;
+use super::watch::Watch;
 use crate::data_structures::{DoubleStack, Immutable, State};
+use arc_swap::ArcSwap;
 use concat_idents::concat_idents;
-use std::collections::{HashMap, HashSet};
+use indexmap::{IndexMap, IndexSet};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
 
 /// Generates the collect_type_lossy functions for [InitializedGateGraph].
 macro_rules! type_collectors {
@@ -40,6 +63,13 @@ macro_rules! type_collectors {
 /// before panicking.
 pub const DEFAULT_STABLE_MAX: usize = 50;
 
+/// Callback registered with [InitializedGateGraph::subscribe] along with the last value it saw.
+pub(super) struct Subscription {
+    output: OutputHandle,
+    value: u128,
+    callback: Box<dyn FnMut(u128, usize) + Send>,
+}
+
 /// Initialized version of [`GateGraphBuilder`]. See [`GateGraphBuilder`] for documentation.
 ///
 /// [`GateGraphBuilder`]: super::GateGraphBuilder
@@ -50,12 +80,39 @@ pub struct InitializedGateGraph {
     pub(super) propagation_queue: DoubleStack<GateIndex>, // Allocated outside to prevent allocations in the hot loop.
     pub(super) output_handles: Immutable<Vec<Output>>,
     pub(super) lever_handles: Immutable<Vec<GateIndex>>,
-    pub(super) outputs: Immutable<HashSet<GateIndex>>,
+    pub(super) test_points: Immutable<IndexMap<String, OutputHandle>>,
+    pub(super) custom_gates: Immutable<Vec<CustomGate>>,
+    pub(super) outputs: Immutable<IndexSet<GateIndex>>,
     pub(super) state: State,
+    /// Delay gates whose dependency changed during the current tick.
+    pub(super) delayed: Vec<GateIndex>,
+    pub(super) event_log: EventLog,
+    #[cfg(feature = "debug_gates")]
+    pub(super) names: Immutable<IndexMap<GateIndex, String>>,
     #[cfg(feature = "debug_gates")]
-    pub(super) names: Immutable<HashMap<GateIndex, String>>,
+    pub(super) probes: Immutable<IndexMap<GateIndex, Probe>>,
+    pub(super) subscriptions: Vec<Option<Subscription>>,
+    pub(super) ticks: usize,
+    pub(super) peripherals: Peripherals,
+    pub(super) json_traces: Vec<Option<JsonTrace>>,
+    pub(super) published_outputs: Option<Arc<ArcSwap<OutputSnapshot>>>,
+    pub(super) contention: ContentionMonitor,
+    pub(super) clocks: Immutable<Vec<Clock>>,
+    pub(super) profiler: Option<Profiler>,
+    pub(super) toggles: Option<ToggleCounter>,
+    /// Propagation delays set with [GateGraphBuilder::set_delay](super::GateGraphBuilder::set_delay).
+    pub(super) gate_delays: Immutable<IndexMap<GateIndex, usize>>,
+    /// Changes of the gates with a delay waiting to be applied.
+    pub(super) scheduled: ScheduledChanges,
+    pub(super) watches: Vec<Option<Watch>>,
+    pub(super) assertions: Assertions,
+    /// Memories declared with [GateGraphBuilder::behavioral_memory](super::GateGraphBuilder::behavioral_memory).
+    pub(super) memories: Memories,
+    pub(super) faults: Faults,
     #[cfg(feature = "debug_gates")]
-    pub(super) probes: Immutable<HashMap<GateIndex, Probe>>,
+    pub(super) drivers: DriverMonitor,
+    /// Likely mistakes found at initialization, see [InitializedGateGraph::warnings].
+    pub(super) warnings: Vec<InitWarning>,
 }
 
 use GateType::*;
@@ -97,6 +154,9 @@ impl InitializedGateGraph {
                 // This is safe because the propagation queue gets filled by items coming from
                 // nodes.iter() or levers, both of which are always in bounds.
                 debug_assert!(idx.idx < self.nodes.len());
+                if let Some(profiler) = &mut self.profiler {
+                    profiler.count(idx.idx);
+                }
                 let node = unsafe { self.nodes.get_unchecked(idx.idx) };
 
                 let new_state = match &node.ty {
@@ -104,7 +164,25 @@ impl InitializedGateGraph {
                     Off => false,
                     // This is safe because in an InitializedGraph nodes.len() <= state.len().
                     Lever => unsafe { self.state.get_state_very_unsafely(idx.idx) },
+                    // Delay gates get updated at the end of the tick by release_delays.
+                    Delay => {
+                        self.delayed.push(idx);
+                        continue;
+                    }
                     Not => unsafe { !self.state.get_state_very_unsafely(node.dependencies[0].idx) },
+                    // This is safe because in an InitializedGraph nodes.len() <= state.len().
+                    TriState => unsafe {
+                        self.state.get_state_very_unsafely(node.dependencies[0].idx)
+                            && self.state.get_state_very_unsafely(node.dependencies[1].idx)
+                    },
+                    Custom(id) => {
+                        let mut inputs = [false; CUSTOM_GATE_MAX_ARITY];
+                        for (input, dependency) in inputs.iter_mut().zip(&node.dependencies) {
+                            // This is safe because in an InitializedGraph nodes.len() <= state.len().
+                            *input = unsafe { self.state.get_state_very_unsafely(dependency.idx) };
+                        }
+                        (self.custom_gates[*id as usize].eval)(&inputs[..node.dependencies.len()])
+                    }
                     Or | Nor | And | Nand | Xor | Xnor => {
                         let mut new_state = if node.ty.short_circuits() {
                             self.fold_short(&node.ty, &node.dependencies)
@@ -128,9 +206,20 @@ impl InitializedGateGraph {
                         new_state
                     }
                 };
+                // Stuck gates keep their state whatever their dependencies do.
+                let new_state = self.faults.stuck(idx).unwrap_or(new_state);
                 // This is safe because in an InitializedGraph nodes.len() <= state.len().
                 let old_state = unsafe { self.state.get_state_very_unsafely(idx.idx) };
 
+                // Gates with a delay get updated at the end of a later tick by release_scheduled.
+                if !self.gate_delays.is_empty() {
+                    if let Some(delay) = self.gate_delays.get(&idx) {
+                        self.scheduled
+                            .schedule(idx, new_state, old_state, self.ticks + delay);
+                        continue;
+                    }
+                }
+
                 // This is safe because in an InitializedGraph nodes.len() <= state.len().
                 if unsafe { self.state.get_updated_very_unsafely(idx.idx) } {
                     if old_state != new_state {
@@ -139,21 +228,24 @@ impl InitializedGateGraph {
                     continue;
                 }
                 unsafe { self.state.set_very_unsafely(idx.idx, new_state) };
+                if old_state != new_state {
+                    if let Some(toggles) = &mut self.toggles {
+                        toggles.count(idx.idx);
+                    }
+                }
 
                 #[cfg(feature = "debug_gates")]
                 if old_state != new_state {
                     if let Some(probe) = self.probes.get(&idx) {
-                        match probe.bits.len() {
-                            0 => unreachable!(),
-                            1 => println!("{}:{}", probe.name, new_state),
-                            2..=8 => {
-                                println!("{}:{}", probe.name, self.collect_u8_lossy(&probe.bits))
-                            }
-                            9..=128 => {
-                                println!("{}:{}", probe.name, self.collect_u128_lossy(&probe.bits))
-                            }
-                            _ => unimplemented!("I need to improve the probes, I know..."),
-                        }
+                        // The tick in progress, the one that will be reported by now() once it's over.
+                        let tick = self.ticks + 1;
+                        tracing::info!(
+                            target: "logicsim::probe",
+                            tick,
+                            "{}:{}",
+                            probe.name,
+                            self.format_probe(probe)
+                        );
                     }
                 }
                 if node.ty.is_lever() || old_state != new_state {
@@ -167,36 +259,194 @@ impl InitializedGateGraph {
     /// These could be levers that have been updated or loops.
     /// Returns true if the graph has reached a stable state.
     pub fn tick(&mut self) -> bool {
+        self.wake_idle();
+        if !self.faults.is_empty() {
+            self.inject_scheduled_faults();
+        }
         while let Some(pending) = &self.pending_updates.pop() {
             self.state.tick();
-            self.propagation_queue.push(*pending);
+            let node = &self.nodes[pending.idx];
+            // Released delay gates, gates with a delay and faulty gates already have their new state.
+            if node.ty.is_delay()
+                || (!self.gate_delays.is_empty() && self.gate_delays.contains_key(pending))
+                || (!self.faults.is_empty() && self.faults.take_override(*pending))
+            {
+                self.propagation_queue.extend_from_slice(&node.dependents);
+            } else {
+                self.propagation_queue.push(*pending);
+            }
             self.tick_inner()
         }
+        self.release_delays();
+        self.release_scheduled();
+        self.update_memories();
         self.pending_updates.swap();
-        self.pending_updates.is_empty()
+        self.ticks += 1;
+        self.count_cycles();
+        self.record_events();
+        self.check_contention();
+        #[cfg(feature = "debug_gates")]
+        self.check_drivers();
+        self.check_assertions();
+        self.notify_subscriptions();
+        self.write_json_traces();
+        self.publish_output_snapshot();
+        self.tick_peripherals();
+        self.is_idle()
+    }
+
+    /// Sets the state of every delay gate whose dependency changed during this tick to the state of its dependency,
+    /// and adds the ones that changed to the pending updates so they propagate during the next tick, like levers.
+    pub(super) fn release_delays(&mut self) {
+        for idx in std::mem::take(&mut self.delayed) {
+            let new_state = self.value(self.nodes[idx.idx].dependencies[0]);
+            let new_state = self.faults.stuck(idx).unwrap_or(new_state);
+            if self.value(idx) != new_state {
+                self.state.set(idx.idx, new_state);
+                if let Some(toggles) = &mut self.toggles {
+                    toggles.count(idx.idx);
+                }
+                self.pending_updates.push(idx);
+            }
+        }
+    }
+
+    /// Calls the callback of every subscription whose output has changed since the last time it was called.
+    fn notify_subscriptions(&mut self) {
+        if self.subscriptions.is_empty() {
+            return;
+        }
+        let mut subscriptions = std::mem::take(&mut self.subscriptions);
+        for subscription in subscriptions.iter_mut().flatten() {
+            let value = self.collect_u128_lossy(&self.get_output(subscription.output).bits);
+            if value != subscription.value {
+                subscription.value = value;
+                (subscription.callback)(value, self.ticks);
+            }
+        }
+        self.subscriptions = subscriptions;
+    }
+
+    /// Calls `callback(new_value, tick)` at the end of every [tick](InitializedGateGraph::tick) in which the value
+    /// of `output` changed, `tick` is the [simulation time](InitializedGateGraph::now).
+    ///
+    /// Returns a [SubscriptionHandle] which can be used to [unsubscribe](InitializedGateGraph::unsubscribe).
+    ///
+    /// The value is collected like [OutputHandle::u128], so only the first 128 bits are taken into account.
+    /// Changes that revert within the same tick are not reported.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,ON,OFF};
+    /// # use std::sync::{Arc, Mutex};
+    /// let mut g = GateGraphBuilder::new();
+    /// let l = g.lever("l");
+    /// let not = g.not1(l.bit(), "not");
+    /// let output = g.output1(not, "not");
+    ///
+    /// let ig = &mut g.init();
+    /// let changes = Arc::new(Mutex::new(Vec::new()));
+    /// let changes_clone = changes.clone();
+    /// ig.subscribe(output, move |value, _tick| changes_clone.lock().unwrap().push(value));
+    ///
+    /// ig.pulse_lever_stable(l);
+    /// assert_eq!(*changes.lock().unwrap(), [0, 1]);
+    /// ```
+    pub fn subscribe<F: FnMut(u128, usize) + Send + 'static>(
+        &mut self,
+        output: OutputHandle,
+        callback: F,
+    ) -> SubscriptionHandle {
+        let value = self.collect_u128_lossy(&self.get_output(output).bits);
+        self.subscriptions.push(Some(Subscription {
+            output,
+            value,
+            callback: Box::new(callback),
+        }));
+        SubscriptionHandle(self.subscriptions.len() - 1)
+    }
+
+    /// Stops calling the callback registered with [subscribe](InitializedGateGraph::subscribe)
+    /// that returned `handle`.
+    pub fn unsubscribe(&mut self, handle: SubscriptionHandle) {
+        self.subscriptions[handle.0] = None;
+    }
+
+    /// Returns the simulation time, the number of [ticks](InitializedGateGraph::tick) since the graph was initialized.
+    ///
+    /// Everything that reports when something happened uses this clock, stamped with the tick in which it happened:
+    /// [events](InitializedGateGraph::take_events), [subscriptions](InitializedGateGraph::subscribe),
+    /// [JSON traces](InitializedGateGraph::add_json_trace), [published outputs](InitializedGateGraph::publish_outputs),
+    /// the `tick` field of the probe records and the [Waveform](crate::testing::Waveform) samples.
+    /// It only goes back when a [snapshot](InitializedGateGraph::restore) is restored.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// let mut g = GateGraphBuilder::new();
+    /// let l = g.lever("l");
+    /// let not = g.not1(l.bit(), "not");
+    /// g.output1(not, "not");
+    ///
+    /// let ig = &mut g.init();
+    /// let start = ig.now();
+    /// ig.set_lever(l);
+    /// assert_eq!(ig.now(), start + 1);
+    /// ```
+    pub fn now(&self) -> usize {
+        self.ticks
     }
 
     /// Calls [InitializedGateGraph::tick] until it returns true a maximum of `max` times.
     /// Returns Ok(number_of_iterations) if the graph stabilized.
-    /// Returns Err(&str) otherwise.
+    /// Returns [SimulationError::NotStabilized] otherwise, with the gates that changed during the last tick.
     ///
-    /// Circuits might not stabilize if they have infinite loops like a chain of 3 not gates.
-    pub fn run_until_stable(&mut self, max: usize) -> Result<usize, &'static str> {
-        if self.pending_updates.is_empty() {
+    /// Circuits might not stabilize if they have infinite loops like a chain of 3 not gates,
+    /// [find_combinational_loops](InitializedGateGraph::find_combinational_loops) tells you where they are.
+    ///
+    /// Returns [SimulationError::AssertionFailed] if an [assertion](InitializedGateGraph::arm_assertions) started
+    /// failing in the meantime.
+    pub fn run_until_stable(&mut self, max: usize) -> Result<usize, SimulationError> {
+        let failures = self.assertions.failure_count();
+        let result = self.run_until_stable_inner(max);
+        if self.assertions.failure_count() > failures {
+            return Err(self.assertions.error(failures));
+        }
+        result
+    }
+
+    /// [run_until_stable](InitializedGateGraph::run_until_stable) without checking for assertion failures.
+    fn run_until_stable_inner(&mut self, max: usize) -> Result<usize, SimulationError> {
+        if self.is_idle() {
             return Ok(0);
         }
 
-        for i in 1..=max {
+        for i in 1..max {
             if self.tick() {
                 return Ok(i);
             }
         }
-
-        Err("Your graph didn't stabilize")
+        if max == 0 {
+            return Err(SimulationError::not_stabilized(self, 0, Vec::new()));
+        }
+        // Only paying for the copy when it's about to fail.
+        let before = self.state.clone();
+        if self.tick() {
+            return Ok(max);
+        }
+        let oscillating_gates = (0..self.nodes.len())
+            .filter(|i| before.get_state(*i) != self.state.get_state(*i))
+            .map(|i| gi!(i))
+            .collect();
+        Err(SimulationError::not_stabilized(
+            self,
+            max,
+            oscillating_gates,
+        ))
     }
 
     /// Sets the state of `lever` to `value` and adds it to the pending updates if its state has changed.
-    fn update_lever_inner(&mut self, lever: LeverHandle, value: bool) {
+    pub(super) fn update_lever_inner(&mut self, lever: LeverHandle, value: bool) {
         let idx = self.lever_handles[lever.handle];
         if self.state.get_state(idx.idx) != value {
             self.state.set(idx.idx, value);
@@ -308,6 +558,13 @@ impl InitializedGateGraph {
         self.collect_u8_lossy(outputs) as char
     }
 
+    /// Returns the [OutputHandle] of the [test point](super::GateGraphBuilder::test_point) named `name`.
+    ///
+    /// Always returns [None] if the "test_points" feature is disabled.
+    pub fn test_point(&self, name: &str) -> Option<OutputHandle> {
+        self.test_points.get(name).copied()
+    }
+
     /// Returns the number of gates in the graph.
     pub fn len(&self) -> usize {
         self.nodes.len()
@@ -319,6 +576,31 @@ impl InitializedGateGraph {
         &self.names[&gate]
     }
 
+    /// Returns the value of `probe` as it gets logged: true or false for 1 bit, in decimal up to 128 bits,
+    /// and in hexadecimal with a digit for every 4 bits for wider probes.
+    #[cfg(feature = "debug_gates")]
+    pub(super) fn format_probe(&self, probe: &Probe) -> String {
+        match probe.bits.len() {
+            1 => self.value(probe.bits[0]).to_string(),
+            2..=128 => self.collect_u128_lossy(&probe.bits).to_string(),
+            width => {
+                let digits = width.div_ceil(4);
+                let mut hex = String::with_capacity(digits + 2);
+                hex.push_str("0x");
+                for digit in (0..digits).rev() {
+                    let nibble = probe.bits[digit * 4..width.min(digit * 4 + 4)]
+                        .iter()
+                        .enumerate()
+                        .fold(0, |nibble, (i, bit)| {
+                            nibble | (self.value(*bit) as u32) << i
+                        });
+                    hex.push(std::char::from_digit(nibble, 16).unwrap());
+                }
+                hex
+            }
+        }
+    }
+
     /// Returns the "full name" of `gate` in format:
     ///
     /// "OUT:?GATE_TYPE:GATE_NAME" if the "debug_gates" feature is enabled.
@@ -339,25 +621,59 @@ impl InitializedGateGraph {
     }
 
     /// Dumps the graph in [dot](https://en.wikipedia.org/wiki/DOT_(graph_description_language)) format
-    /// to path `filename`, to be visualized by many supported tools, I recommend [gephi](https://gephi.org/).
-    pub fn dump_dot(&self, filename: &'static str) {
-        use petgraph::dot::{Config, Dot};
-        use std::io::Write;
-        let mut f = std::fs::File::create(filename).unwrap();
-        let mut graph = petgraph::Graph::<_, ()>::new();
-        let mut index = HashMap::new();
-        for (i, _) in self.nodes.iter().enumerate() {
-            let label = self.full_name(gi!(i));
-            index.insert(i, graph.add_node(label));
-        }
-        for (i, node) in self.nodes.iter().enumerate() {
-            graph.extend_with_edges(
-                node.dependencies
-                    .iter()
-                    .map(|dependency| (index[&dependency.idx], index[&i])),
-            );
-        }
-        write!(f, "{:?}", Dot::with_config(&graph, &[Config::EdgeNoLabel])).unwrap();
+    /// to `path`, to be visualized by many supported tools, I recommend [gephi](https://gephi.org/).
+    pub fn dump_dot<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        self.dump_dot_with_config(path, &DotConfig::default())
+    }
+
+    /// Dumps the part of the graph selected by `config` in dot format to `path`, clustered as it says,
+    /// see [GateGraphBuilder::dump_dot_with_config](super::GateGraphBuilder::dump_dot_with_config).
+    ///
+    /// Returns an error if `config` names an output or probe that doesn't exist.
+    pub fn dump_dot_with_config<P: AsRef<Path>>(
+        &self,
+        path: P,
+        config: &DotConfig,
+    ) -> std::io::Result<()> {
+        let mut f = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_dot(&mut f, config)?;
+        f.flush()
+    }
+
+    /// Writes the graph in dot format to `writer`, see [InitializedGateGraph::dump_dot_with_config].
+    pub(crate) fn write_dot<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        config: &DotConfig,
+    ) -> std::io::Result<()> {
+        #[allow(unused_mut)]
+        let mut named: Vec<(&str, &[GateIndex])> = self
+            .output_handles
+            .iter()
+            .map(|output| (output.name.as_str(), &output.bits[..]))
+            .collect();
+        #[cfg(feature = "debug_gates")]
+        named.extend(
+            self.probes
+                .values()
+                .map(|probe| (probe.name.as_str(), &probe.bits[..])),
+        );
+        let roots = cone_roots(config, &named)?;
+        let gates: Vec<_> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, gate)| DotGate {
+                index: gi!(i),
+                label: self.full_name(gi!(i)),
+                #[cfg(feature = "debug_gates")]
+                name: self.name(gi!(i)),
+                #[cfg(not(feature = "debug_gates"))]
+                name: "",
+                dependencies: &gate.dependencies,
+            })
+            .collect();
+        write_dot(writer, &gates, &roots, config)
     }
 }
 
@@ -0,0 +1,260 @@
+use super::custom_gate::CustomGateType;
+use super::gate::*;
+use super::graph_builder::GateGraphBuilder;
+use super::InitializedGateGraph;
+
+/// Type of a gate, returned by [GateGraphBuilder::gate_type] and [InitializedGateGraph::gate_type].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum GateKind {
+    /// The [OFF] constant.
+    Off,
+    /// The [ON] constant.
+    On,
+    /// Gate whose state is set from outside the graph, see [GateGraphBuilder::lever].
+    Lever,
+    /// On while its dependency is off.
+    Not,
+    /// On while all of its dependencies are on.
+    And,
+    /// Off while all of its dependencies are on.
+    Nand,
+    /// On while any of its dependencies is on.
+    Or,
+    /// Off while any of its dependencies is on.
+    Nor,
+    /// On while an odd number of its dependencies are on.
+    Xor,
+    /// On while an even number of its dependencies are on.
+    Xnor,
+    /// Gate that takes the state of its dependency one tick later, see [GateGraphBuilder::delay].
+    Delay,
+    /// Buffer with an enable, see [GateGraphBuilder::tristate].
+    /// Its first dependency is the data and the second one the enable.
+    TriState,
+    /// Gate of a type registered with [GateGraphBuilder::register_gate].
+    Custom(CustomGateType),
+}
+impl GateKind {
+    fn new(ty: GateType) -> Self {
+        match ty {
+            GateType::Off => GateKind::Off,
+            GateType::On => GateKind::On,
+            GateType::Lever => GateKind::Lever,
+            GateType::Not => GateKind::Not,
+            GateType::And => GateKind::And,
+            GateType::Nand => GateKind::Nand,
+            GateType::Or => GateKind::Or,
+            GateType::Nor => GateKind::Nor,
+            GateType::Xor => GateKind::Xor,
+            GateType::Xnor => GateKind::Xnor,
+            GateType::Delay => GateKind::Delay,
+            GateType::TriState => GateKind::TriState,
+            GateType::Custom(id) => GateKind::Custom(CustomGateType(id)),
+        }
+    }
+}
+
+impl GateGraphBuilder {
+    /// Returns true if `gate` is a gate of the graph, false if it has been removed or it belongs to another graph.
+    ///
+    /// In release builds a [GateIndex] of a removed gate whose slot has been reused by a new gate is still valid,
+    /// it points at the new gate.
+    pub fn contains(&self, gate: GateIndex) -> bool {
+        if self.nodes.get(gate.into()).is_none() {
+            return false;
+        }
+        #[cfg(debug_assertions)]
+        if gate.graph != 0 && gate.graph != self.id {
+            return false;
+        }
+        #[cfg(debug_assertions)]
+        if gate.generation != 0 {
+            return gate.generation == self.nodes.generation(gate.into()).wrapping_add(1);
+        }
+        true
+    }
+
+    /// Returns an iterator over the [GateIndex] of every gate in the graph, including [OFF] and [ON],
+    /// in ascending order.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,GateKind,ON,OFF};
+    /// let mut g = GateGraphBuilder::new();
+    /// let a = g.lever("a");
+    /// let b = g.lever("b");
+    /// let and = g.and2(a.bit(), b.bit(), "and");
+    /// let not = g.not1(and, "not");
+    ///
+    /// assert_eq!(g.gates().collect::<Vec<_>>(), [OFF, ON, a.bit(), b.bit(), and, not]);
+    /// assert_eq!(g.gate_type(and), GateKind::And);
+    /// assert_eq!(g.dependencies(and), [a.bit(), b.bit()]);
+    /// assert_eq!(g.dependents(and).collect::<Vec<_>>(), [not]);
+    /// # #[cfg(feature = "debug_gates")]
+    /// assert_eq!(g.gate_name(not), Some("not"));
+    /// ```
+    pub fn gates(&self) -> impl Iterator<Item = GateIndex> + '_ {
+        self.nodes.iter().map(move |(i, _)| self.gate_index(i))
+    }
+
+    /// Returns the type of `gate`.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `gate` isn't [a gate of the graph](GateGraphBuilder::contains).
+    pub fn gate_type(&self, gate: GateIndex) -> GateKind {
+        GateKind::new(self.get(gate).ty)
+    }
+
+    /// Returns the dependencies of `gate`, the gates whose states determine its state, in order.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `gate` isn't [a gate of the graph](GateGraphBuilder::contains).
+    pub fn dependencies(&self, gate: GateIndex) -> &[GateIndex] {
+        &self.get(gate).dependencies
+    }
+
+    /// Returns an iterator over the dependents of `gate`, the gates that have it as a dependency.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `gate` isn't [a gate of the graph](GateGraphBuilder::contains).
+    pub fn dependents(&self, gate: GateIndex) -> impl Iterator<Item = GateIndex> + '_ {
+        self.get(gate).dependents.iter().copied()
+    }
+
+    /// Returns the name `gate` was created with, or None if the "debug_gates" feature is disabled.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `gate` isn't [a gate of the graph](GateGraphBuilder::contains).
+    pub fn gate_name(&self, gate: GateIndex) -> Option<&str> {
+        self.check(gate);
+        #[cfg(feature = "debug_gates")]
+        return self.names.get(&gate).map(String::as_str);
+        #[cfg(not(feature = "debug_gates"))]
+        None
+    }
+}
+
+impl InitializedGateGraph {
+    /// Returns true if `gate` is a gate of the graph.
+    ///
+    /// Initialization optimizes and compacts the graph, so the [GateIndex] of a gate in the [GateGraphBuilder]
+    /// doesn't point at the same gate here, or at any gate at all, get them from [InitializedGateGraph::gates]
+    /// or from the bits of outputs and levers instead.
+    pub fn contains(&self, gate: GateIndex) -> bool {
+        gate.idx < self.nodes.len()
+    }
+
+    /// Returns an iterator over the [GateIndex] of every gate in the graph, including [OFF] and [ON],
+    /// in ascending order.
+    pub fn gates(&self) -> impl Iterator<Item = GateIndex> {
+        (0..self.nodes.len()).map(GateIndex::new)
+    }
+
+    /// Returns the type of `gate`.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `gate` isn't [a gate of the graph](InitializedGateGraph::contains).
+    pub fn gate_type(&self, gate: GateIndex) -> GateKind {
+        GateKind::new(self.nodes[gate.idx].ty)
+    }
+
+    /// Returns the dependencies of `gate`, the gates whose states determine its state, in order.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `gate` isn't [a gate of the graph](InitializedGateGraph::contains).
+    pub fn dependencies(&self, gate: GateIndex) -> &[GateIndex] {
+        &self.nodes[gate.idx].dependencies
+    }
+
+    /// Returns an iterator over the dependents of `gate`, the gates that have it as a dependency.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `gate` isn't [a gate of the graph](InitializedGateGraph::contains).
+    pub fn dependents(&self, gate: GateIndex) -> impl Iterator<Item = GateIndex> + '_ {
+        self.nodes[gate.idx].dependents.iter().copied()
+    }
+
+    /// Returns the name of `gate`, or None if the "debug_gates" feature is disabled.
+    ///
+    /// Gates merged by the optimizations keep the name of one of them.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `gate` isn't [a gate of the graph](InitializedGateGraph::contains).
+    pub fn gate_name(&self, gate: GateIndex) -> Option<&str> {
+        assert!(self.contains(gate), "GateIndex {} out of bounds", gate);
+        #[cfg(feature = "debug_gates")]
+        return self.names.get(&gate).map(String::as_str);
+        #[cfg(not(feature = "debug_gates"))]
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+
+    #[test]
+    fn test_introspection() {
+        let mut g = GateGraphBuilder::new();
+        let a = g.lever("a");
+        let b = g.lever("b");
+        let xor = g.xor2(a.bit(), b.bit(), "xor");
+        let unused = g.nand2(a.bit(), xor, "unused");
+        let output = g.output1(xor, "xor");
+
+        assert!(g.contains(unused));
+        assert_eq!(g.gate_type(unused), GateKind::Nand);
+        assert_eq!(g.gate_type(OFF), GateKind::Off);
+        assert_eq!(g.gate_type(a.bit()), GateKind::Lever);
+        let mut dependents: Vec<_> = g.dependents(a.bit()).collect();
+        dependents.sort();
+        assert_eq!(dependents, [xor, unused]);
+        // Every edge can be seen from both ends.
+        for gate in g.gates() {
+            for dependency in g.dependencies(gate) {
+                assert!(g.dependents(*dependency).any(|dependent| dependent == gate));
+            }
+        }
+
+        let ig = &mut g.init();
+        assert_eq!(ig.gates().count(), ig.len());
+        let xor = output.gates(ig)[0];
+        assert!(ig.contains(xor));
+        assert!(!ig.contains(GateIndex::new(ig.len())));
+        assert_eq!(ig.gate_type(xor), GateKind::Xor);
+        assert_eq!(ig.dependencies(xor).len(), 2);
+        for dependency in ig.dependencies(xor) {
+            assert_eq!(ig.gate_type(*dependency), GateKind::Lever);
+            assert_eq!(ig.dependents(*dependency).collect::<Vec<_>>(), [xor]);
+        }
+        assert_eq!(ig.dependents(xor).count(), 0);
+        #[cfg(feature = "debug_gates")]
+        assert_eq!(ig.gate_name(xor), Some("xor"));
+    }
+
+    #[test]
+    fn test_removed_gates() {
+        let mut g = GateGraphBuilder::new();
+        let a = g.lever("a");
+        let not = g.not1(a.bit(), "not");
+        let or = g.or2(a.bit(), not, "or");
+        g.output1(or, "or");
+
+        g.remove_gate(not);
+        assert!(!g.contains(not));
+        assert!(!g.gates().any(|gate| gate == not));
+        assert_eq!(g.dependencies(or), [a.bit()]);
+        // The new gate reuses the slot, the old index only notices in debug builds.
+        let new = g.and1(a.bit(), "new");
+        assert!(g.contains(new));
+        assert_eq!(g.contains(not), !cfg!(debug_assertions));
+    }
+}
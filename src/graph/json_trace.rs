@@ -0,0 +1,205 @@
+use super::handles::{Output, OutputHandle};
+use super::InitializedGateGraph;
+use std::io::{self, Write};
+
+/// Outputs wider than this are written as strings, because most JSON parsers store numbers as [f64].
+const JSON_MAX_SAFE_BITS: usize = 53;
+
+/// Handle type that represents a trace added with [InitializedGateGraph::add_json_trace].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct JsonTraceHandle(usize);
+
+/// Trace added with [InitializedGateGraph::add_json_trace].
+pub(super) struct JsonTrace {
+    outputs: Vec<OutputHandle>,
+    writer: Box<dyn Write + Send>,
+    /// First error returned by the writer, nothing else gets written after it.
+    error: Option<io::Error>,
+}
+
+/// Writes `s` as a JSON string.
+fn write_json_string<W: Write + ?Sized>(writer: &mut W, s: &str) -> io::Result<()> {
+    write!(writer, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(writer, "\\\"")?,
+            '\\' => write!(writer, "\\\\")?,
+            '\n' => write!(writer, "\\n")?,
+            '\t' => write!(writer, "\\t")?,
+            c if (c as u32) < 0x20 => write!(writer, "\\u{:04x}", c as u32)?,
+            c => write!(writer, "{}", c)?,
+        }
+    }
+    write!(writer, "\"")
+}
+
+impl InitializedGateGraph {
+    /// Writes one line of JSON to `writer` at the end of every [tick](InitializedGateGraph::tick), with the number of
+    /// ticks since the graph was initialized and the value of every output in `outputs` keyed by its name.
+    /// Lines look like `{"tick":12,"count":3,"carry":0}`.
+    ///
+    /// Values are read like [OutputHandle::u128], outputs wider than 53 bits are written as decimal strings so
+    /// parsers that store numbers as doubles don't lose precision.
+    ///
+    /// The format is trivial to stream into tools like jq or pandas, see the [Waveform](crate::testing::Waveform)
+    /// for VCD.
+    ///
+    /// Returns a [JsonTraceHandle] which can be used to [remove](InitializedGateGraph::remove_json_trace) the trace.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// # use logicsim::{counter,zeros,ON,OFF};
+    /// # let path = std::env::temp_dir().join("logicsim_json_trace_doctest.ndjson");
+    /// let mut g = GateGraphBuilder::new();
+    /// let clock = g.lever("clock");
+    /// let reset = g.lever("reset");
+    /// let count = counter(&mut g, clock.bit(), ON, OFF, ON, reset.bit(), &zeros(4), "counter");
+    /// let count = g.output(&count, "count");
+    ///
+    /// let ig = &mut g.init();
+    /// ig.pulse_lever_stable(reset);
+    /// let file = std::io::BufWriter::new(std::fs::File::create(&path).unwrap());
+    /// let trace = ig.add_json_trace(&[count], file);
+    /// ig.pulse_lever_stable(clock);
+    /// ig.remove_json_trace(trace).unwrap();
+    ///
+    /// let lines = std::fs::read_to_string(&path).unwrap();
+    /// assert!(lines.lines().last().unwrap().ends_with(r#""count":1}"#));
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn add_json_trace<W: Write + Send + 'static>(
+        &mut self,
+        outputs: &[OutputHandle],
+        writer: W,
+    ) -> JsonTraceHandle {
+        self.json_traces.push(Some(JsonTrace {
+            outputs: outputs.to_vec(),
+            writer: Box::new(writer),
+            error: None,
+        }));
+        JsonTraceHandle(self.json_traces.len() - 1)
+    }
+
+    /// Stops the trace added with [add_json_trace](InitializedGateGraph::add_json_trace) that returned `handle`
+    /// and flushes its writer.
+    ///
+    /// Returns the first error returned by the writer, if any, the trace stops writing after an error.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the trace had already been removed.
+    pub fn remove_json_trace(&mut self, handle: JsonTraceHandle) -> io::Result<()> {
+        let mut trace = self.json_traces[handle.0]
+            .take()
+            .expect("The trace had already been removed");
+        match trace.error {
+            Some(error) => Err(error),
+            None => trace.writer.flush(),
+        }
+    }
+
+    /// Writes a line to every trace, called at the end of every tick.
+    pub(super) fn write_json_traces(&mut self) {
+        if self.json_traces.is_empty() {
+            return;
+        }
+        let mut traces = std::mem::take(&mut self.json_traces);
+        for trace in traces.iter_mut().flatten() {
+            if trace.error.is_none() {
+                if let Err(error) = self.write_json_line(trace) {
+                    trace.error = Some(error);
+                }
+            }
+        }
+        self.json_traces = traces;
+    }
+
+    /// Writes the line of the current tick to `trace`.
+    fn write_json_line(&self, trace: &mut JsonTrace) -> io::Result<()> {
+        let writer = &mut trace.writer;
+        write!(writer, "{{\"tick\":{}", self.ticks)?;
+        for output in &trace.outputs {
+            let Output { name, bits } = self.get_output(*output);
+            write!(writer, ",")?;
+            write_json_string(writer, name)?;
+            let value = self.collect_u128_lossy(bits);
+            if bits.len() > JSON_MAX_SAFE_BITS {
+                write!(writer, ":\"{}\"", value)?;
+            } else {
+                write!(writer, ":{}", value)?;
+            }
+        }
+        writeln!(writer, "}}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::write_json_string;
+    use crate::circuits::WordInput;
+    use std::sync::{Arc, Mutex};
+
+    /// Writer that can be read back after being moved into the graph.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+    impl std::io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_json_string() {
+        let mut s = Vec::new();
+        write_json_string(&mut s, "a\"b\\c\nd\u{1}é").unwrap();
+        assert_eq!(String::from_utf8(s).unwrap(), r#""a\"b\\c\nd\u0001é""#);
+    }
+
+    #[test]
+    fn test_json_trace() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let input = WordInput::new(g, 64, "input");
+        let not = g.not1(input.bits()[0], "not");
+        let narrow = g.output(&input.bits()[..8], "narrow");
+        let wide = g.output(&input.bits(), "wide \"quoted\"");
+        let not = g.output1(not, "not");
+        let ig = &mut graph.init();
+
+        let buffer = SharedBuffer::default();
+        let trace = ig.add_json_trace(&[narrow, wide, not], buffer.clone());
+        let start = ig.ticks;
+        input.set_to(ig, u64::MAX);
+        ig.run_until_stable(10).unwrap();
+        let end = ig.ticks;
+        ig.remove_json_trace(trace).unwrap();
+        // Not written anymore.
+        input.set_to(ig, 0u64);
+        ig.run_until_stable(10).unwrap();
+
+        let buffer = buffer.0.lock().unwrap();
+        let lines: Vec<&str> = std::str::from_utf8(&buffer).unwrap().lines().collect();
+        assert_eq!(lines.len(), end - start);
+        assert_eq!(
+            lines[0],
+            format!(
+                r#"{{"tick":{},"narrow":255,"wide \"quoted\"":"{}","not":1}}"#,
+                start + 1,
+                u64::MAX
+            )
+        );
+        assert_eq!(
+            *lines.last().unwrap(),
+            format!(
+                r#"{{"tick":{},"narrow":255,"wide \"quoted\"":"{}","not":0}}"#,
+                start + lines.len(),
+                u64::MAX
+            )
+        );
+    }
+}
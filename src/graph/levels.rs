@@ -0,0 +1,105 @@
+use super::gate::*;
+use super::InitializedGateGraph;
+
+/// Returns the level of every gate in `nodes`: 0 for gates without dependencies, one more than the highest level
+/// of their dependencies for the rest.
+///
+/// Gates in a loop can't be ordered, every gate in a strongly connected group gets the same level, one more than
+/// the highest level of the dependencies from outside the group. Delay gates hold their state for a tick, so edges
+/// into them are ignored like in [InitializedGateGraph::find_combinational_loops].
+pub(super) fn levelize(nodes: &[InitializedGate]) -> Vec<u32> {
+    let mut graph = petgraph::Graph::<GateIndex, ()>::with_capacity(nodes.len(), nodes.len());
+    let indices: Vec<_> = (0..nodes.len()).map(|i| graph.add_node(gi!(i))).collect();
+    for (i, gate) in nodes.iter().enumerate() {
+        if !gate.ty.is_delay() {
+            for dependency in &gate.dependencies {
+                graph.add_edge(indices[dependency.idx], indices[i], ());
+            }
+        }
+    }
+
+    let mut levels = vec![0u32; nodes.len()];
+    let mut group = vec![usize::MAX; nodes.len()];
+    // Tarjan's algorithm returns the groups in reverse topological order.
+    for (g, scc) in petgraph::algo::tarjan_scc(&graph)
+        .into_iter()
+        .rev()
+        .enumerate()
+    {
+        for node in &scc {
+            group[graph[*node].idx] = g;
+        }
+        let level = scc
+            .iter()
+            .map(|node| graph[*node].idx)
+            .filter(|i| !nodes[*i].ty.is_delay())
+            .flat_map(|i| nodes[i].dependencies.iter())
+            .filter(|dependency| group[dependency.idx] != g)
+            .map(|dependency| levels[dependency.idx] + 1)
+            .max()
+            .unwrap_or(0);
+        for node in &scc {
+            levels[graph[*node].idx] = level;
+        }
+    }
+    levels
+}
+
+impl InitializedGateGraph {
+    /// Returns the number of levels of logic in the graph, the length of the longest chain of gates a change has
+    /// to go through, with loops counting as a single gate.
+    ///
+    /// It's a lower bound of the number of gates a change goes through before reaching the outputs, useful to compare
+    /// the critical path of different implementations of a circuit.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// let mut g = GateGraphBuilder::new();
+    /// let l = g.lever("l");
+    /// let not = g.not1(l.bit(), "not");
+    /// let and = g.and2(l.bit(), not, "and");
+    /// g.output1(and, "and");
+    ///
+    /// let ig = &mut g.init_unoptimized();
+    /// // Lever -> not -> and.
+    /// assert_eq!(ig.logic_depth(), 3);
+    /// ```
+    pub fn logic_depth(&self) -> usize {
+        levelize(&self.nodes)
+            .into_iter()
+            .max()
+            .map_or(0, |level| level as usize + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::levelize;
+    use crate::circuits::sr_latch;
+
+    #[test]
+    fn test_levels() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let s = g.lever("s");
+        let r = g.lever("r");
+        let q = sr_latch(g, s.bit(), r.bit(), "latch");
+        let not = g.not1(q, "not");
+        let delayed = g.delay(not, "delayed");
+        let and = g.and2(delayed, s.bit(), "and");
+        g.output1(and, "and");
+
+        let ig = &graph.init_unoptimized();
+        let levels = levelize(&ig.nodes);
+        let level = |gate: GateIndex| levels[gate.idx];
+        assert_eq!(level(s.bit()), 0);
+        // Every gate of the latch shares the level of the loop.
+        assert!(level(q) > level(s.bit()));
+        assert_eq!(level(not), level(q) + 1);
+        // Delay gates start a new chain.
+        assert_eq!(level(delayed), 0);
+        assert_eq!(level(and), 1);
+    }
+}
@@ -0,0 +1,179 @@
+use super::gate::*;
+use super::InitializedGateGraph;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+
+/// Group of gates connected in a loop that can oscillate, found by [InitializedGateGraph::find_combinational_loops].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CombinationalLoop {
+    /// Every gate in the loop, sorted.
+    pub gates: Vec<GateIndex>,
+    /// Full names of `gates`, the same ones [InitializedGateGraph::dump_dot] uses.
+    pub names: Vec<String>,
+}
+impl Display for CombinationalLoop {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Combinational loop: {}", self.names.join(", "))
+    }
+}
+
+impl InitializedGateGraph {
+    /// Returns the groups of gates connected in loops that can oscillate, the usual reason
+    /// [run_until_stable](InitializedGateGraph::run_until_stable) never finishes.
+    ///
+    /// Loops are how latches store data, so not every loop is a mistake. A loop is reported if it isn't
+    /// broken by a delay gate and it can invert its own value: it goes through an odd number of negated gates
+    /// (not, nand, nor and xnor) or through an xor, xnor or custom gate, which can invert depending on their other
+    /// dependencies. Cross coupled nor or nand gates, like [sr_latch](crate::sr_latch), are not reported.
+    ///
+    /// The analysis runs on the optimized graph, so gates whose names were optimized away can be missing.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// # use logicsim::sr_latch;
+    /// let mut g = GateGraphBuilder::new();
+    /// let s = g.lever("s");
+    /// let r = g.lever("r");
+    /// let q = sr_latch(&mut g, s.bit(), r.bit(), "latch");
+    /// g.output1(q, "q");
+    ///
+    /// let enable = g.lever("enable");
+    /// let ring = g.nand1(enable.bit(), "ring");
+    /// g.dpush(ring, ring);
+    /// g.output1(ring, "ring");
+    ///
+    /// let ig = &mut g.init();
+    /// let loops = ig.find_combinational_loops();
+    /// assert_eq!(loops.len(), 1);
+    /// assert!(loops[0].names[0].contains("ring"));
+    /// ```
+    pub fn find_combinational_loops(&self) -> Vec<CombinationalLoop> {
+        // Delay gates hold their state for a tick, edges into them don't make a loop combinational.
+        let connects = |gate: &InitializedGate| !gate.ty.is_delay();
+        let mut graph = petgraph::Graph::<GateIndex, ()>::new();
+        let nodes: Vec<_> = (0..self.nodes.len())
+            .map(|i| graph.add_node(gi!(i)))
+            .collect();
+        for (i, gate) in self.nodes.iter().enumerate() {
+            if connects(gate) {
+                for dependency in &gate.dependencies {
+                    graph.add_edge(nodes[dependency.idx], nodes[i], ());
+                }
+            }
+        }
+
+        let mut loops: Vec<CombinationalLoop> = petgraph::algo::tarjan_scc(&graph)
+            .into_iter()
+            .filter_map(|scc| {
+                let mut gates: Vec<GateIndex> = scc.into_iter().map(|node| graph[node]).collect();
+                if gates.len() == 1 && !self.nodes[gates[0].idx].dependencies.contains(&gates[0]) {
+                    return None;
+                }
+                gates.sort_unstable();
+                if self.can_oscillate(&gates) {
+                    let names = gates.iter().map(|gate| self.full_name(*gate)).collect();
+                    Some(CombinationalLoop { gates, names })
+                } else {
+                    None
+                }
+            })
+            .collect();
+        loops.sort_unstable_by_key(|l| l.gates[0]);
+        loops
+    }
+
+    /// Returns true if the strongly connected `gates` contain a cycle that can invert its own value.
+    ///
+    /// Every cycle in a strongly connected group has an even number of inversions if and only if every gate
+    /// can be given a parity such that every edge between them flips it exactly when it inverts.
+    fn can_oscillate(&self, gates: &[GateIndex]) -> bool {
+        let members: HashSet<GateIndex> = gates.iter().copied().collect();
+        let mut parity = HashMap::new();
+        parity.insert(gates[0], false);
+        let mut work = vec![gates[0]];
+        while let Some(gate) = work.pop() {
+            let gate_parity = parity[&gate];
+            for dependent in &self.nodes[gate.idx].dependents {
+                if !members.contains(dependent) {
+                    continue;
+                }
+                let ty = self.nodes[dependent.idx].ty;
+                if ty.is_delay() {
+                    continue;
+                }
+                if matches!(ty, GateType::Xor | GateType::Xnor) || ty.is_custom() {
+                    return true;
+                }
+                let expected = gate_parity ^ ty.is_negated();
+                match parity.get(dependent) {
+                    Some(p) if *p != expected => return true,
+                    Some(_) => {}
+                    None => {
+                        parity.insert(*dependent, expected);
+                        work.push(*dependent);
+                    }
+                }
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{d_flip_flop, sr_latch};
+
+    #[test]
+    fn test_combinational_loops() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let s = g.lever("s");
+        let r = g.lever("r");
+        let enable = g.lever("enable");
+
+        // Latches.
+        let q = sr_latch(g, s.bit(), r.bit(), "latch");
+        g.output1(q, "q");
+        let q = d_flip_flop(g, s.bit(), r.bit(), enable.bit(), ON, ON, "ff");
+        g.output1(q, "ff");
+        let hold = g.or2(s.bit(), OFF, "hold");
+        g.dpush(hold, hold);
+        g.output1(hold, "hold");
+
+        // Broken by a delay.
+        let delayed = g.not1(OFF, "delayed_not");
+        let delay = g.delay(delayed, "delay");
+        g.dx(delayed, delay, 0);
+        g.output1(delayed, "delayed");
+
+        // Ring oscillator.
+        let a = g.nand2(enable.bit(), OFF, "a");
+        let b = g.not1(a, "b");
+        let c = g.not1(b, "c");
+        g.dx(a, c, 1);
+        g.output1(c, "ring");
+
+        // Xor loop.
+        let xor = g.xor2(s.bit(), OFF, "xor");
+        g.dx(xor, xor, 1);
+        g.output1(xor, "xor");
+
+        let ig = &mut graph.init();
+        let loops = ig.find_combinational_loops();
+        assert_eq!(loops.len(), 2, "{:?}", loops);
+        let is_xor_loop = |l: &&CombinationalLoop| {
+            l.gates
+                .iter()
+                .any(|gate| ig.nodes[gate.idx].ty == GateType::Xor)
+        };
+        assert_eq!(loops.iter().filter(is_xor_loop).count(), 1);
+        let ring = loops.iter().find(|l| !is_xor_loop(l)).unwrap();
+        assert!(ring.to_string().starts_with("Combinational loop: "));
+        assert_eq!(ring.names.len(), ring.gates.len());
+
+        ig.update_lever(enable, true);
+        assert!(ig.run_until_stable(100).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use super::gate::*;
+use super::graph_builder::GateGraphBuilder;
+use super::optimizations::dead_code_elimination_pass;
+
+impl GateGraphBuilder {
+    /// Returns a copy of `self` reduced as much as possible while `failing` keeps returning true.
+    ///
+    /// It uses [delta debugging](https://en.wikipedia.org/wiki/Delta_debugging), groups of gates get tied to [OFF] or [ON]
+    /// which means their dependents read the constant instead, and the gates that become dead are removed.
+    /// Levers, outputs and probes are never removed so any [LeverHandle](super::LeverHandle) or
+    /// [OutputHandle](super::OutputHandle) used by `failing` stays valid, tying a lever to a constant just
+    /// disconnects it from the rest of the circuit.
+    ///
+    /// `failing` gets called many times with a candidate graph, it should initialize it and return
+    /// true if the problem you are looking for is still there.
+    /// This is great for turning a huge design that triggers an optimizer bug into a circuit small enough
+    /// to read in a [.dot file](GateGraphBuilder::dump_dot) or attach to an issue.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,ON,OFF};
+    /// let mut g = GateGraphBuilder::new();
+    /// let l1 = g.lever("l1");
+    /// let l2 = g.lever("l2");
+    ///
+    /// // Lots of gates that have nothing to do with our "bug".
+    /// let mut noise = l2.bit();
+    /// for _ in 0..20 {
+    ///     noise = g.xor2(noise, l2.bit(), "noise");
+    /// }
+    /// let and = g.and2(noise, l1.bit(), "and");
+    /// let or = g.or2(and, l1.bit(), "or");
+    /// let output = g.output1(or, "output");
+    ///
+    /// // Our "bug" is that the output follows l1.
+    /// let minimized = g.minimize(|g| {
+    ///     let ig = &mut g.init();
+    ///     let before = output.b0(ig);
+    ///     ig.set_lever_stable(l1);
+    ///     !before && output.b0(ig)
+    /// });
+    ///
+    /// // Only OFF, ON, the levers and the or gate remain.
+    /// assert_eq!(minimized.len(), 5);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `failing` returns false for `self`.
+    pub fn minimize<F: FnMut(GateGraphBuilder) -> bool>(&self, mut failing: F) -> GateGraphBuilder {
+        assert!(
+            failing(self.clone()),
+            "The predicate must return true for the graph that is being minimized"
+        );
+        let mut current = self.clone();
+        let mut candidates = current.tie_off_candidates();
+        let mut chunks = 2;
+
+        'search: while !candidates.is_empty() {
+            chunks = chunks.min(candidates.len());
+            let chunk_size = candidates.len().div_ceil(chunks);
+
+            for chunk in candidates.chunks(chunk_size) {
+                for constant in [OFF, ON].iter() {
+                    let mut attempt = current.clone();
+                    attempt.tie_off(chunk, *constant);
+                    if failing(attempt.clone()) {
+                        tracing::debug!(
+                            target: "logicsim::minimize",
+                            "Tied {} gates to {}, {} gates left",
+                            chunk.len(),
+                            if *constant == ON { "ON" } else { "OFF" },
+                            attempt.len()
+                        );
+                        current = attempt;
+                        candidates = current.tie_off_candidates();
+                        chunks = (chunks - 1).max(2);
+                        continue 'search;
+                    }
+                }
+            }
+            if chunk_size == 1 {
+                break;
+            }
+            chunks *= 2;
+        }
+        current
+    }
+
+    /// Returns the gates that have dependents which could read a constant instead.
+    fn tie_off_candidates(&self) -> Vec<GateIndex> {
+        self.nodes
+            .iter()
+            .filter(|(_, gate)| !gate.dependents.is_empty())
+            .map(|(idx, _)| idx.into())
+            .filter(|idx: &GateIndex| !idx.is_const())
+            .collect()
+    }
+
+    /// Makes all dependents of every gate in `gates` depend on `constant` instead, and removes the dead gates.
+    fn tie_off(&mut self, gates: &[GateIndex], constant: GateIndex) {
+        for gate in gates {
+            let dependents = match self.nodes.get_mut((*gate).into()) {
+                Some(gate) => std::mem::take(&mut gate.dependents),
+                None => continue,
+            };
+            for dependent in dependents {
+                self.get_mut(dependent).swap_dependency(*gate, constant);
+                self.get_mut(constant).dependents.insert(dependent);
+            }
+        }
+        dead_code_elimination_pass(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minimize_keeps_handles() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let a = g.lever("a");
+        let b = g.lever("b");
+        let c = g.lever("c");
+
+        let and = g.and2(a.bit(), b.bit(), "and");
+        let mut noise = c.bit();
+        for _ in 0..30 {
+            noise = g.nand2(noise, c.bit(), "noise");
+        }
+        let xor = g.xor2(and, noise, "xor");
+        let output = g.output1(xor, "xor");
+        let unrelated = g.or2(noise, c.bit(), "unrelated");
+        let unrelated = g.output1(unrelated, "unrelated");
+
+        // The output needs both a and b to flip.
+        let failing = |g: GateGraphBuilder| {
+            let ig = &mut g.init();
+            let before = output.b0(ig);
+            ig.set_lever_stable(a);
+            let after_a = output.b0(ig);
+            ig.set_lever_stable(b);
+            before == after_a && after_a != output.b0(ig)
+        };
+        let minimized = graph.minimize(failing);
+
+        assert!(minimized.len() < graph.len());
+        assert!(failing(minimized.clone()));
+
+        // Handles are still usable.
+        let ig = &mut minimized.init();
+        ig.set_lever_stable(c);
+        unrelated.b0(ig);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_minimize_not_failing() {
+        let mut g = GateGraphBuilder::new();
+        let l = g.lever("l");
+        g.output1(l.bit(), "l");
+        g.minimize(|_| false);
+    }
+}
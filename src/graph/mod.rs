@@ -1,10 +1,88 @@
 mod handles;
 #[macro_use]
 mod gate;
+mod assertions;
+mod batch;
+mod behavioral_memory;
+mod budget;
+#[cfg(feature = "serde")]
+mod cache;
+mod clocks;
+mod cnf;
+mod contention;
+mod cost;
+mod custom_gate;
+mod dot;
+mod drc;
+#[cfg(feature = "debug_gates")]
+mod drivers;
+mod events;
+mod faults;
+mod four_state;
+mod fsm;
+mod fuzz;
+mod gate_delays;
 mod graph_builder;
 mod initialized_graph;
+mod introspection;
+mod json_trace;
+mod levels;
+mod loops;
+mod minimize;
 mod optimizations;
+mod paths;
+mod peripherals;
+mod power;
+mod profile;
+mod published_outputs;
+#[cfg(feature = "repl")]
+mod repl;
+#[cfg(feature = "sat")]
+mod sat;
+mod simulation_error;
+mod simulator;
+mod snapshot;
+#[cfg(feature = "serde")]
+mod state_file;
+mod synced_lever;
+mod template;
+mod tmr;
+mod truth_table;
+mod validate;
+mod verilog;
+mod warnings;
+mod watch;
+pub use assertions::AssertionFailure;
+pub use batch::{LaneValues, LeverAssignment, OutputValues};
+pub use behavioral_memory::{MemoryHandle, MemoryOutput};
+pub use cnf::CnfMap;
+pub use contention::BusContention;
+pub use cost::*;
+pub use custom_gate::{CustomGateType, CUSTOM_GATE_MAX_ARITY, CUSTOM_GATE_MAX_TYPES};
+pub use dot::DotConfig;
+pub use drc::DrcViolation;
+#[cfg(feature = "debug_gates")]
+pub use drivers::DriverConflict;
+pub use events::Event;
+pub use faults::{Fault, FaultCoverage};
+pub use four_state::{FourStateSimulation, Logic};
+pub use fsm::{StateMachine, Transition, FSM_MAX_INPUTS};
 pub use gate::*;
 pub use graph_builder::*;
 pub use handles::*;
 pub use initialized_graph::*;
+pub use introspection::GateKind;
+pub use json_trace::JsonTraceHandle;
+pub use loops::CombinationalLoop;
+pub use optimizations::{OptimizationPass, OptimizerConfig, PassStatistics};
+pub use peripherals::{BusView, HostPeripheral, PeripheralHandle};
+pub use power::{ModulePower, PowerReport};
+pub use profile::{ActivityProfile, Hotspot};
+pub use published_outputs::{OutputReader, OutputSnapshot};
+pub use simulation_error::SimulationError;
+pub use simulator::{Simulator, SimulatorConfig};
+pub use snapshot::StateSnapshot;
+pub use template::Template;
+pub use truth_table::TRUTH_TABLE_MAX_LEVERS;
+pub use validate::ValidationIssue;
+pub use warnings::InitWarning;
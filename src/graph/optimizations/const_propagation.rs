@@ -124,14 +124,29 @@ pub fn const_propagation_pass(g: &mut GateGraphBuilder) {
             .map(|idx| WorkItem { idx, on: true }),
     );
 
-    for (i, gate) in g.nodes.iter() {
-        if !GateIndex::from(i).is_const() && !gate.ty.is_lever() && gate.dependencies.is_empty() {
-            work.extend(
-                gate.dependents
-                    .iter()
-                    .copied()
-                    .map(|idx| WorkItem { idx, on: false }),
-            )
+    // Gates with no inputs, like unconnected wires, are constants too, but they are not OFF or ON so
+    // the replacements wouldn't recognize them, their dependents get connected to the constant instead.
+    let empty: Vec<GateIndex> = g
+        .nodes
+        .iter()
+        .filter(|(i, gate)| {
+            !GateIndex::from(*i).is_const()
+                && gate.dependencies.is_empty()
+                && matches!(gate.ty, And | Nand | Or | Nor | Xor | Xnor)
+        })
+        .map(|(i, _)| i.into())
+        .collect();
+    for idx in empty {
+        let ty = g.get(idx).ty;
+        let on = ty.init() ^ ty.is_negated();
+        let constant = if on { ON } else { OFF };
+        for dependent in std::mem::take(&mut g.get_mut(idx).dependents) {
+            g.get_mut(dependent).swap_dependency(idx, constant);
+            g.get_mut(constant).dependents.insert(dependent);
+            work.push(WorkItem { idx: dependent, on });
+        }
+        if !g.is_observable(idx) {
+            g.nodes.remove(idx.into());
         }
     }
 
@@ -153,6 +168,18 @@ pub fn const_propagation_pass(g: &mut GateGraphBuilder) {
             Nor => find_replacement(g, idx, on, ON, true),
             Xor => find_replacement_xor(g, idx, on, false),
             Xnor => find_replacement_xor(g, idx, on, true),
+            // Replacing it would remove the one tick delay.
+            Delay => None,
+            // Tri-state buffers are kept for contention checks, unless they never drive anything.
+            TriState => {
+                if g.get(idx).dependencies[1] == OFF {
+                    Some(OFF)
+                } else {
+                    None
+                }
+            }
+            // Custom gates are opaque.
+            Custom(_) => None,
         };
         if let Some(replacement) = replacement {
             temp_dependents.extend(&g.get(idx).dependents);
@@ -25,9 +25,8 @@ pub fn dead_code_elimination_pass(g: &mut GateGraphBuilder) {
         for dependency in temp_dependencies.drain(0..temp_dependencies.len()) {
             let dependency_gate = g.get_mut(dependency);
 
-            dependency_gate.dependents.remove(&idx);
-
-            if dependency_gate.dependents.is_empty() {
+            // A repeated dependency only gets removed the first time.
+            if dependency_gate.dependents.remove(&idx) && dependency_gate.dependents.is_empty() {
                 work.push(dependency)
             }
         }
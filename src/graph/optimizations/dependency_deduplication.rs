@@ -16,6 +16,10 @@ pub fn dependency_deduplication_pass(g: &mut GateGraphBuilder) {
         .nodes
         .iter()
         .filter_map(|(idx, gate)| {
+            // The position of every dependency matters in custom gates and tri-state buffers.
+            if gate.ty.is_custom() || gate.ty.is_tristate() {
+                return None;
+            }
             let mut dependency_multi_map = HashMap::<GateIndex, usize>::new();
             // Detect duplicate dependencies and how many times they are duplicated.
             for dependency in gate.dependencies.iter().copied() {
@@ -52,6 +56,10 @@ pub fn dependency_deduplication_pass(g: &mut GateGraphBuilder) {
                     unreachable!("Off, On, and lever nodes have no dependencies")
                 }
                 Not => unreachable!("Not gates only have 1 dependency"),
+                Delay => unreachable!("Delay gates only have 1 dependency"),
+                TriState | Custom(_) => {
+                    unreachable!("Custom gates and tri-state buffers are skipped")
+                }
 
                 And | Nand | Or | Nor => Keep1,
                 Xor | Xnor => {
@@ -66,5 +74,8 @@ pub fn dependency_deduplication_pass(g: &mut GateGraphBuilder) {
                 g.get_mut(idx).dependencies.push(duplicate)
             }
         }
+        // Sorted like the dependencies of any other gate global value numbering has visited,
+        // otherwise running both passes again would keep moving the kept copies around.
+        g.get_mut(idx).dependencies.sort();
     }
 }
@@ -12,7 +12,13 @@ pub fn equal_gate_merging_pass(g: &mut GateGraphBuilder) {
     while let Some(idx) = work.pop() {
         let gate = g.get(idx);
         let gate_ty = gate.ty;
-        if gate_ty.is_negated() {
+        // Merging a chain of delays would shorten the delay,
+        // and custom gates and tri-state buffers are not associative.
+        if gate_ty.is_negated()
+            || gate_ty.is_delay()
+            || gate_ty.is_custom()
+            || gate_ty.is_tristate()
+        {
             continue;
         }
 
@@ -24,7 +30,7 @@ pub fn equal_gate_merging_pass(g: &mut GateGraphBuilder) {
                 continue;
             }
             let dependency_gate = g.get(dependency);
-            if gate_ty == dependency_gate.ty {
+            if gate_ty == dependency_gate.ty && !g.kept.contains(&dependency) {
                 temp_deps_deps.extend_from_slice(&dependency_gate.dependencies);
                 for dep_dep in &temp_deps_deps {
                     if *dep_dep == dependency || *dep_dep == idx {
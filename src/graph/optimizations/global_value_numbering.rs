@@ -1,27 +1,31 @@
 use super::super::{gate::*, graph_builder::GateGraphBuilder};
 use super::dead_code_elimination_pass;
+use indexmap::IndexMap;
 use std::collections::{hash_map::DefaultHasher, HashMap, HashSet, VecDeque};
 use std::hash::{Hash, Hasher};
 
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
 struct ValueNumber(GateIndex);
-type NumberMap = HashMap<GateIndex, ValueNumber>;
+// Merged gates are rewired in insertion order, so their dependents end up in the same order every run.
+type NumberMap = IndexMap<GateIndex, ValueNumber>;
 type Expression = u64;
 fn lookup<I: Iterator<Item = ValueNumber>>(
     op: GateType,
-    op_hash_offset: u64,
+    kept: bool,
     dep_nums: I,
     x: GateIndex,
     hash_table: &mut HashMap<Expression, GateIndex>,
 ) -> ValueNumber {
-    let op_hash = if op.is_lever() || x.is_const() {
-        x.idx as u64
-    } else {
-        op as u64 + op_hash_offset
-    };
-
     let mut hasher = DefaultHasher::new();
-    hasher.write_u64(op_hash);
+    // The operator hash for levers, constants and kept gates is their GateIndex,
+    // the prefix keeps it from colliding with the other operator hashes.
+    if op.is_lever() || x.is_const() || kept {
+        hasher.write_u8(0);
+        hasher.write_usize(x.idx);
+    } else {
+        hasher.write_u8(1);
+        op.hash(&mut hasher);
+    }
     for dep in dep_nums {
         hasher.write_usize(dep.0.idx);
     }
@@ -44,10 +48,24 @@ pub fn global_value_numbering_pass(g: &mut GateGraphBuilder) {
     let mut hash_table = HashMap::new();
     let mut visited = HashSet::new();
 
-    // The operator hash for levers and constants is their GateIndex,
-    // in order for the other operator hashes to be unique, they must start
-    // after the last GateIndex.
-    let op_hash_offset = g.nodes.len() as u64;
+    // Dependencies that haven't been numbered yet are skipped, so gates that can't be reached from the levers and
+    // constants, like gates without dependencies or the ones const propagation left alone, are numbered as
+    // themselves, otherwise gates that only differ in them would be equal.
+    let mut work: Vec<GateIndex> = g.lever_handles.clone();
+    work.extend([OFF, ON]);
+    while let Some(x) = work.pop() {
+        if visited.insert(x) {
+            work.extend(g.get(x).dependents.iter());
+        }
+    }
+    for (x, _) in g.nodes.iter() {
+        let x = x.into();
+        if !visited.contains(&x) {
+            VN.insert(x, ValueNumber(x));
+        }
+    }
+    visited.clear();
+
     loop {
         let mut done = true;
         let mut work: VecDeque<GateIndex> = g.lever_handles.iter().copied().collect();
@@ -62,7 +80,10 @@ pub fn global_value_numbering_pass(g: &mut GateGraphBuilder) {
             }
             // TODO ensure dependencies are sorted at all times.
             // We need them sorted so that hash(a OR b) == hash(b OR a).
-            g.get_mut(x).dependencies.sort();
+            // Custom gates are not necessarily commutative, and tri-state buffers aren't.
+            if !g.get(x).ty.is_custom() && !g.get(x).ty.is_tristate() {
+                g.get_mut(x).dependencies.sort();
+            }
 
             let gate = g.get(x);
             let op = gate.ty;
@@ -70,12 +91,12 @@ pub fn global_value_numbering_pass(g: &mut GateGraphBuilder) {
             let dependency_value_numbers = gate
                 .dependencies
                 .iter()
-                .filter_map(|dep| VN.get(&dep))
+                .filter_map(|dep| VN.get(dep))
                 .copied();
 
             let temp = lookup(
                 op,
-                op_hash_offset,
+                g.kept.contains(&x),
                 dependency_value_numbers,
                 x,
                 &mut hash_table,
@@ -103,7 +124,12 @@ pub fn global_value_numbering_pass(g: &mut GateGraphBuilder) {
 
         temp_deps.extend(g.get(x).dependents.iter());
         for dep in &temp_deps {
-            g.get_mut(*dep).swap_dependency(x, a.0);
+            let gate = g.get_mut(*dep);
+            gate.swap_dependency(x, a.0);
+            // Keep them sorted, running the pass again shouldn't change anything.
+            if !gate.ty.is_custom() && !gate.ty.is_tristate() {
+                gate.dependencies.sort();
+            }
             g.get_mut(a.0).dependents.insert(*dep);
         }
         g.get_mut(x).dependents = Default::default()
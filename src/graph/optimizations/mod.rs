@@ -4,6 +4,10 @@ mod dependency_deduplication;
 mod equal_gate_merging;
 mod global_value_numbering;
 mod not_deduplication;
+#[cfg(test)]
+mod pass_testing;
+mod pipeline;
+mod reset_assumptions;
 mod single_dependency_collapsing;
 pub(super) use const_propagation::*;
 pub(super) use dead_code_elimination::*;
@@ -11,4 +15,6 @@ pub(super) use dependency_deduplication::*;
 pub(super) use equal_gate_merging::*;
 pub(super) use global_value_numbering::*;
 pub(super) use not_deduplication::*;
+pub use pipeline::{OptimizationPass, OptimizerConfig, PassStatistics};
+pub(super) use reset_assumptions::*;
 pub(super) use single_dependency_collapsing::*;
@@ -0,0 +1,839 @@
+//! Infrastructure to test the optimization passes one at a time.
+//!
+//! Graphs are written as netlists, one statement per line, `#` starts a comment:
+//!
+//! ```text
+//! lever a
+//! lever b
+//! x = and a b OFF     # gate types: and nand or nor xor xnor not delay
+//! q = nor x nq        # dependencies can be declared later, to build loops
+//! nq = nor b q
+//! output out q x      # output named out, bit 0 first
+//! keep x              # see GateGraphBuilder::keep
+//! assume q OFF        # see GateGraphBuilder::assume_after_reset
+//! ```
+//!
+//! [check_pass] runs a single pass on a netlist and checks the result against the expected netlist, the golden
+//! files in `golden/<pass name>/*.netlist` contain both separated by a line with `---`.
+//! Running the tests with `LOGICSIM_BLESS=1` rewrites the expected half of every golden file with the
+//! current result, review the diff before committing it.
+use super::super::gate::*;
+use super::super::graph_builder::GateGraphBuilder;
+use super::super::DEFAULT_STABLE_MAX;
+use super::OptimizationPass;
+use crate::data_structures::SplitMix64;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::hash::{Hash, Hasher};
+
+/// Graphs with up to this many levers are simulated with every combination of lever values.
+const EXHAUSTIVE_LEVERS: usize = 10;
+/// Number of random combinations of lever values simulated in bigger graphs.
+const RANDOM_CYCLES: usize = 256;
+/// Number of dependency levels summarized in the signatures used to pair dependencies.
+const SIGNATURE_ROUNDS: usize = 8;
+
+/// [GateGraphBuilder] parsed from a netlist, along with the names of its gates.
+#[derive(Debug, Clone)]
+pub(super) struct Netlist {
+    pub(super) graph: GateGraphBuilder,
+    names: HashMap<GateIndex, String>,
+}
+impl Netlist {
+    /// Returns the [Netlist] described by `text`, see the module docs for the format.
+    ///
+    /// # Panics
+    ///
+    /// Will panic with the line number if `text` isn't a valid netlist.
+    pub(super) fn parse(text: &str) -> Self {
+        let mut g = GateGraphBuilder::new();
+        let mut gates: HashMap<String, GateIndex> = HashMap::new();
+        gates.insert("OFF".into(), OFF);
+        gates.insert("ON".into(), ON);
+        let define = |gates: &mut HashMap<String, GateIndex>, name: &str, gate, line| {
+            if gates.insert(name.into(), gate).is_some() {
+                panic!("Line {}: {} is already defined", line, name);
+            }
+        };
+
+        // Dependencies and directives are resolved once every gate is defined.
+        let mut dependencies = Vec::new();
+        let mut directives = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.split('#').next().unwrap().trim();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[..] {
+                [] => {}
+                ["lever", name] => {
+                    let lever = g.lever(name);
+                    define(&mut gates, name, lever.bit(), line_number);
+                }
+                [name, "=", ty, ref gate_dependencies @ ..] => {
+                    let gate = match ty {
+                        "and" => g.and(name),
+                        "nand" => g.nand(name),
+                        "or" => g.or(name),
+                        "nor" => g.nor(name),
+                        "xor" => g.xor(name),
+                        "xnor" => g.xnor(name),
+                        "not" => g.not1(OFF, name),
+                        "delay" => g.delay(OFF, name),
+                        _ => panic!("Line {}: unknown gate type {}", line_number, ty),
+                    };
+                    define(&mut gates, name, gate, line_number);
+                    dependencies.push((gate, gate_dependencies.to_vec(), line_number));
+                }
+                ["output", _, ..] | ["keep", _] | ["assume", _, _] => {
+                    directives.push((words, line_number))
+                }
+                _ => panic!("Line {}: can't parse {:?}", line_number, line),
+            }
+        }
+
+        let lookup = |name: &str, line| {
+            *gates
+                .get(name)
+                .unwrap_or_else(|| panic!("Line {}: {} isn't defined", line, name))
+        };
+        for (gate, gate_dependencies, line) in dependencies {
+            let ty = g.get(gate).ty;
+            if ty.is_not() || ty.is_delay() {
+                assert_eq!(
+                    gate_dependencies.len(),
+                    1,
+                    "Line {}: {} gates have exactly 1 dependency",
+                    line,
+                    ty
+                );
+                g.d0(gate, lookup(gate_dependencies[0], line));
+            } else {
+                for dependency in gate_dependencies {
+                    g.dpush(gate, lookup(dependency, line));
+                }
+            }
+        }
+        for (words, line) in directives {
+            match words[..] {
+                ["output", name, ref bits @ ..] => {
+                    let bits: Vec<GateIndex> = bits.iter().map(|bit| lookup(bit, line)).collect();
+                    g.output(&bits, name);
+                }
+                ["keep", name] => g.keep(lookup(name, line)),
+                ["assume", name, value] => {
+                    let value = lookup(value, line);
+                    assert!(
+                        value.is_const(),
+                        "Line {}: assumptions must be ON or OFF",
+                        line
+                    );
+                    g.assume_after_reset(&[lookup(name, line)], &[value]);
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        Self {
+            graph: g,
+            names: gates.into_iter().map(|(name, gate)| (gate, name)).collect(),
+        }
+    }
+
+    /// Returns the name of `gate` in the netlist.
+    fn name(&self, gate: GateIndex) -> String {
+        self.names
+            .get(&gate)
+            .cloned()
+            .unwrap_or_else(|| format!("gate{}", gate.idx))
+    }
+
+    /// Returns the netlist that describes the current state of the graph, gates keep the names they were
+    /// parsed with.
+    pub(super) fn write(&self) -> String {
+        let g = &self.graph;
+        let mut text = String::new();
+        for (i, gate) in g.nodes.iter() {
+            let idx = GateIndex::from(i);
+            if idx.is_const() {
+                continue;
+            }
+            if gate.ty.is_lever() {
+                writeln!(text, "lever {}", self.name(idx)).unwrap();
+                continue;
+            }
+            let mut line = format!(
+                "{} = {}",
+                self.name(idx),
+                gate.ty.to_string().to_lowercase()
+            );
+            for dependency in &gate.dependencies {
+                write!(line, " {}", self.name(*dependency)).unwrap();
+            }
+            writeln!(text, "{}", line).unwrap();
+        }
+        for output in &g.output_handles {
+            let mut line = format!("output {}", output.name);
+            for bit in &output.bits {
+                write!(line, " {}", self.name(*bit)).unwrap();
+            }
+            writeln!(text, "{}", line).unwrap();
+        }
+        let mut kept: Vec<_> = g.kept.iter().map(|gate| self.name(*gate)).collect();
+        kept.sort_unstable();
+        for gate in kept {
+            writeln!(text, "keep {}", gate).unwrap();
+        }
+        let mut assumptions: Vec<_> = g
+            .reset_assumptions
+            .iter()
+            .map(|(gate, on)| (self.name(*gate), if *on { "ON" } else { "OFF" }))
+            .collect();
+        assumptions.sort_unstable();
+        for (gate, value) in assumptions {
+            writeln!(text, "assume {} {}", gate, value).unwrap();
+        }
+        text
+    }
+
+    /// Returns the levers of the graph by name.
+    fn levers(&self) -> HashMap<String, GateIndex> {
+        self.graph
+            .lever_handles
+            .iter()
+            .map(|lever| (self.name(*lever), *lever))
+            .collect()
+    }
+
+    /// Returns a hash of the structure of the graph up to [SIGNATURE_ROUNDS] dependencies behind every gate,
+    /// which only depends on the names of the levers, not on the names or the indexes of the rest of the gates.
+    fn signatures(&self) -> HashMap<GateIndex, u64> {
+        let g = &self.graph;
+        let anchor = |idx: GateIndex, ty: GateType| {
+            if ty.is_lever() {
+                self.name(idx)
+            } else if idx.is_const() {
+                idx.is_on().to_string()
+            } else {
+                String::new()
+            }
+        };
+        let mut signatures: HashMap<GateIndex, u64> = g
+            .nodes
+            .iter()
+            .map(|(i, gate)| {
+                let idx = GateIndex::from(i);
+                (idx, hash(&(gate.ty, anchor(idx, gate.ty))))
+            })
+            .collect();
+        for _ in 0..SIGNATURE_ROUNDS {
+            signatures = g
+                .nodes
+                .iter()
+                .map(|(i, gate)| {
+                    let idx = GateIndex::from(i);
+                    let mut dependencies: Vec<u64> = gate
+                        .dependencies
+                        .iter()
+                        .map(|dependency| signatures[dependency])
+                        .collect();
+                    if is_commutative(gate.ty) {
+                        dependencies.sort_unstable();
+                    }
+                    (idx, hash(&(gate.ty, anchor(idx, gate.ty), dependencies)))
+                })
+                .collect();
+        }
+        signatures
+    }
+}
+
+fn hash<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns true if the order of the dependencies of gates of type `ty` doesn't matter.
+fn is_commutative(ty: GateType) -> bool {
+    matches!(
+        ty,
+        GateType::And
+            | GateType::Nand
+            | GateType::Or
+            | GateType::Nor
+            | GateType::Xor
+            | GateType::Xnor
+    )
+}
+
+/// Pairs of gates of two netlists that have to be equivalent, see [compare].
+struct Matching<'a> {
+    actual: &'a Netlist,
+    expected: &'a Netlist,
+    actual_signatures: HashMap<GateIndex, u64>,
+    expected_signatures: HashMap<GateIndex, u64>,
+    /// Actual gate -> expected gate.
+    pairs: HashMap<GateIndex, GateIndex>,
+    /// Expected gate -> actual gate.
+    reverse: HashMap<GateIndex, GateIndex>,
+    work: Vec<(GateIndex, GateIndex)>,
+}
+impl<'a> Matching<'a> {
+    /// Pairs the gates in the work list and their dependencies, returns an error if they aren't equivalent.
+    fn run(&mut self) -> Result<(), String> {
+        while let Some((actual, expected)) = self.work.pop() {
+            let actual_name = self.actual.name(actual);
+            let expected_name = self.expected.name(expected);
+            match (self.pairs.get(&actual), self.reverse.get(&expected)) {
+                (Some(paired), _) if *paired == expected => continue,
+                (Some(paired), _) => {
+                    return Err(format!(
+                        "{} matches both {} and {} in the expected netlist",
+                        actual_name,
+                        self.expected.name(*paired),
+                        expected_name
+                    ))
+                }
+                (None, Some(paired)) => {
+                    return Err(format!(
+                        "{} and {} both match {} in the expected netlist",
+                        self.actual.name(*paired),
+                        actual_name,
+                        expected_name
+                    ))
+                }
+                (None, None) => {}
+            }
+            let actual_gate = self.actual.graph.get(actual);
+            let expected_gate = self.expected.graph.get(expected);
+            if actual_gate.ty != expected_gate.ty {
+                return Err(format!(
+                    "{} is {} but {} is {}",
+                    actual_name, actual_gate.ty, expected_name, expected_gate.ty
+                ));
+            }
+            if actual_gate.dependencies.len() != expected_gate.dependencies.len() {
+                return Err(format!(
+                    "{} has {} dependencies but {} has {}",
+                    actual_name,
+                    actual_gate.dependencies.len(),
+                    expected_name,
+                    expected_gate.dependencies.len()
+                ));
+            }
+            self.pairs.insert(actual, expected);
+            self.reverse.insert(expected, actual);
+
+            if !is_commutative(actual_gate.ty) {
+                self.work.extend(
+                    actual_gate
+                        .dependencies
+                        .iter()
+                        .copied()
+                        .zip(expected_gate.dependencies.iter().copied()),
+                );
+                continue;
+            }
+            // Every dependency gets paired with a remaining dependency with the same signature,
+            // preferring the one it's already paired with.
+            let mut remaining: Vec<GateIndex> = expected_gate.dependencies.to_vec();
+            for dependency in &actual_gate.dependencies {
+                let signature = self.actual_signatures[dependency];
+                let position = remaining
+                    .iter()
+                    .position(|candidate| self.pairs.get(dependency) == Some(candidate))
+                    .or_else(|| {
+                        remaining.iter().position(|candidate| {
+                            self.expected_signatures[candidate] == signature
+                                && !self.reverse.contains_key(candidate)
+                        })
+                    })
+                    .or_else(|| {
+                        remaining
+                            .iter()
+                            .position(|candidate| self.expected_signatures[candidate] == signature)
+                    });
+                match position {
+                    Some(position) => {
+                        self.work
+                            .push((*dependency, remaining.swap_remove(position)));
+                    }
+                    None => {
+                        return Err(format!(
+                            "No dependency of {} matches {}, a dependency of {}",
+                            expected_name,
+                            self.actual.name(*dependency),
+                            actual_name
+                        ))
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns an error describing the first difference found if the graphs of `actual` and `expected` aren't
+/// structurally equal.
+///
+/// Levers are paired by name and outputs by position, then the gates behind them are paired recursively,
+/// the names of the rest of the gates only matter for the ones which can't be reached from an output. The
+/// dependencies of commutative gates are paired by the structure behind them regardless of their order.
+pub(super) fn compare(actual: &Netlist, expected: &Netlist) -> Result<(), String> {
+    let mut matching = Matching {
+        actual,
+        expected,
+        actual_signatures: actual.signatures(),
+        expected_signatures: expected.signatures(),
+        pairs: HashMap::new(),
+        reverse: HashMap::new(),
+        work: vec![(OFF, OFF), (ON, ON)],
+    };
+
+    let actual_levers = actual.levers();
+    let expected_levers = expected.levers();
+    let mut lever_names: Vec<&String> = actual_levers.keys().collect();
+    lever_names.sort_unstable();
+    let mut expected_lever_names: Vec<&String> = expected_levers.keys().collect();
+    expected_lever_names.sort_unstable();
+    if lever_names != expected_lever_names {
+        return Err(format!(
+            "The levers are {:?} but {:?} were expected",
+            lever_names, expected_lever_names
+        ));
+    }
+    for name in lever_names {
+        matching
+            .work
+            .push((actual_levers[name], expected_levers[name]));
+    }
+
+    let actual_outputs = &actual.graph.output_handles;
+    let expected_outputs = &expected.graph.output_handles;
+    if actual_outputs.len() != expected_outputs.len() {
+        return Err(format!(
+            "There are {} outputs but {} were expected",
+            actual_outputs.len(),
+            expected_outputs.len()
+        ));
+    }
+    for (actual_output, expected_output) in actual_outputs.iter().zip(expected_outputs) {
+        if actual_output.name != expected_output.name
+            || actual_output.bits.len() != expected_output.bits.len()
+        {
+            return Err(format!(
+                "Output {} with {} bits doesn't match output {} with {} bits",
+                actual_output.name,
+                actual_output.bits.len(),
+                expected_output.name,
+                expected_output.bits.len()
+            ));
+        }
+        matching.work.extend(
+            actual_output
+                .bits
+                .iter()
+                .copied()
+                .zip(expected_output.bits.iter().copied()),
+        );
+    }
+    matching.run()?;
+
+    // Gates that can't be reached from the outputs are paired by name.
+    loop {
+        let expected_names: HashMap<String, GateIndex> = expected
+            .graph
+            .nodes
+            .iter()
+            .map(|(i, _)| GateIndex::from(i))
+            .filter(|gate| !matching.reverse.contains_key(gate))
+            .map(|gate| (expected.name(gate), gate))
+            .collect();
+        let unpaired: Vec<(GateIndex, GateIndex)> = actual
+            .graph
+            .nodes
+            .iter()
+            .map(|(i, _)| GateIndex::from(i))
+            .filter(|gate| !matching.pairs.contains_key(gate))
+            .filter_map(|gate| Some((gate, *expected_names.get(&actual.name(gate))?)))
+            .collect();
+        if unpaired.is_empty() {
+            break;
+        }
+        matching.work.extend(unpaired);
+        matching.run()?;
+    }
+
+    for (i, _) in actual.graph.nodes.iter() {
+        let gate = GateIndex::from(i);
+        if !matching.pairs.contains_key(&gate) {
+            return Err(format!(
+                "{} isn't in the expected netlist",
+                actual.name(gate)
+            ));
+        }
+    }
+    for (i, _) in expected.graph.nodes.iter() {
+        let gate = GateIndex::from(i);
+        if !matching.reverse.contains_key(&gate) {
+            return Err(format!("{} is missing", expected.name(gate)));
+        }
+    }
+    for gate in &actual.graph.kept {
+        if !expected.graph.kept.contains(&matching.pairs[gate]) {
+            return Err(format!("{} shouldn't be kept", actual.name(*gate)));
+        }
+    }
+    if actual.graph.kept.len() != expected.graph.kept.len() {
+        return Err("Some gates should be kept".into());
+    }
+    for (gate, on) in &actual.graph.reset_assumptions {
+        if expected.graph.reset_assumptions.get(&matching.pairs[gate]) != Some(on) {
+            return Err(format!(
+                "The assumption about {} isn't expected",
+                actual.name(*gate)
+            ));
+        }
+    }
+    if actual.graph.reset_assumptions.len() != expected.graph.reset_assumptions.len() {
+        return Err("Some assumptions are missing".into());
+    }
+    Ok(())
+}
+
+/// Returns an error if the dependencies and dependents of the gates in `g` don't agree with each other,
+/// or if any handle points to a removed gate.
+///
+/// The dependents of [ON] and [OFF] aren't checked, const propagation stops tracking them.
+pub(super) fn check_consistency(g: &GateGraphBuilder) -> Result<(), String> {
+    let exists = |gate: &GateIndex| g.nodes.get((*gate).into()).is_some();
+    for (i, gate) in g.nodes.iter() {
+        let idx = GateIndex::from(i);
+        for dependency in &gate.dependencies {
+            if !exists(dependency) {
+                return Err(format!("{} depends on removed gate {}", idx, dependency));
+            }
+            if !dependency.is_const() && !g.get(*dependency).dependents.contains(&idx) {
+                return Err(format!(
+                    "{} depends on {} but isn't one of its dependents",
+                    idx, dependency
+                ));
+            }
+        }
+        if idx.is_const() {
+            continue;
+        }
+        for dependent in &gate.dependents {
+            if !exists(dependent) {
+                return Err(format!(
+                    "Removed gate {} is a dependent of {}",
+                    dependent, idx
+                ));
+            }
+            if !g.get(*dependent).dependencies.contains(&idx) {
+                return Err(format!(
+                    "{} is a dependent of {} but doesn't depend on it",
+                    dependent, idx
+                ));
+            }
+        }
+    }
+    let handles = g
+        .output_handles
+        .iter()
+        .flat_map(|output| output.bits.iter())
+        .chain(&g.lever_handles)
+        .chain(&g.outputs)
+        .chain(&g.kept);
+    for gate in handles {
+        if !exists(gate) {
+            return Err(format!("A handle points to removed gate {}", gate));
+        }
+    }
+    Ok(())
+}
+
+/// Returns an error if the outputs of `before` and `after` differ for any combination of lever values, once
+/// both are stable. Both graphs are initialized without optimizations.
+///
+/// Graphs with up to [EXHAUSTIVE_LEVERS] levers are checked with every combination, bigger graphs with
+/// [RANDOM_CYCLES] random combinations generated from `seed`.
+pub(super) fn check_equivalence(
+    before: &GateGraphBuilder,
+    after: &GateGraphBuilder,
+    seed: u64,
+) -> Result<(), String> {
+    let levers = before.lever_handles();
+    let outputs = before.output_handles();
+    let before = &mut before.clone().init_unoptimized();
+    let after = &mut after.clone().init_unoptimized();
+
+    let exhaustive = levers.len() <= EXHAUSTIVE_LEVERS;
+    let cycles = if exhaustive {
+        1 << levers.len()
+    } else {
+        RANDOM_CYCLES
+    };
+    let mut rng = SplitMix64(seed);
+    for cycle in 0..cycles {
+        let values: Vec<bool> = (0..levers.len())
+            .map(|i| {
+                if exhaustive {
+                    cycle >> i & 1 == 1
+                } else {
+                    rng.one_in(2)
+                }
+            })
+            .collect();
+        for ig in [&mut *before, &mut *after] {
+            ig.update_levers(&levers, values.iter().copied());
+            ig.run_until_stable(DEFAULT_STABLE_MAX)
+                .map_err(|err| format!("Levers {:?}: {}", values, err))?;
+        }
+        for output in &outputs {
+            let expected = output.u128(before);
+            let actual = output.u128(after);
+            if actual != expected {
+                return Err(format!(
+                    "Levers {:?}: output {} is {:#b} but it was {:#b} before the pass",
+                    values,
+                    output.name(before),
+                    actual,
+                    expected
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs `pass` on the netlist `input` and returns an error if the result isn't structurally equal to the
+/// netlist `expected`, if it isn't [consistent](check_consistency) or if it doesn't behave like `input`.
+///
+/// [OptimizationPass::ResetAssumptions] changes the behavior on purpose, so it's not simulated.
+pub(super) fn check_pass(
+    pass: OptimizationPass,
+    input: &str,
+    expected: &str,
+) -> Result<Netlist, String> {
+    let before = Netlist::parse(input);
+    let mut after = before.clone();
+    pass.run(&mut after.graph);
+    let expected = Netlist::parse(expected);
+
+    let describe = |err: String| {
+        format!(
+            "{}\n--- actual:\n{}--- expected:\n{}",
+            err,
+            after.write(),
+            expected.write()
+        )
+    };
+    check_consistency(&after.graph).map_err(describe)?;
+    compare(&after, &expected).map_err(describe)?;
+    if pass != OptimizationPass::ResetAssumptions {
+        check_equivalence(&before.graph, &after.graph, 0).map_err(describe)?;
+    }
+    Ok(after)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::OptimizerConfig;
+    use crate::testing::{random_graph, RandomGraphParams};
+    use std::path::Path;
+
+    /// Directory with the golden files, relative to the root of the crate.
+    const GOLDEN_DIR: &str = "src/graph/optimizations/golden";
+    /// Separates the input from the expected result in golden files.
+    const SEPARATOR: &str = "---\n";
+
+    /// Every pass, including the opt-in ones.
+    fn passes() -> Vec<OptimizationPass> {
+        let mut passes = OptimizationPass::ALL.to_vec();
+        passes.push(OptimizationPass::ResetAssumptions);
+        passes
+    }
+
+    #[test]
+    fn test_golden_files() {
+        let bless = std::env::var_os("LOGICSIM_BLESS").is_some();
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join(GOLDEN_DIR);
+        let mut failures = Vec::new();
+        for pass in passes() {
+            let dir = root.join(pass.name().replace(' ', "_"));
+            let mut files: Vec<_> = std::fs::read_dir(&dir)
+                .unwrap_or_else(|err| panic!("{}: {}", dir.display(), err))
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "netlist"))
+                .collect();
+            files.sort();
+            assert!(!files.is_empty(), "{} has no golden files", dir.display());
+
+            for path in files {
+                let text = std::fs::read_to_string(&path).unwrap();
+                let (input, expected) = text
+                    .split_once(SEPARATOR)
+                    .unwrap_or_else(|| panic!("{} has no {:?} line", path.display(), SEPARATOR));
+                if bless {
+                    let mut after = Netlist::parse(input);
+                    pass.run(&mut after.graph);
+                    let blessed = format!("{}{}{}", input, SEPARATOR, after.write());
+                    std::fs::write(&path, blessed).unwrap();
+                    continue;
+                }
+                if let Err(err) = check_pass(pass, input, expected) {
+                    failures.push(format!("{}: {}", path.display(), err));
+                }
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+    }
+
+    #[test]
+    fn test_passes_on_random_graphs() {
+        let params = RandomGraphParams {
+            levers: 6,
+            gates: 60,
+            ..Default::default()
+        };
+        for pass in passes() {
+            for seed in 0..20 {
+                let random = random_graph(seed, &params);
+                let mut after = random.graph.clone();
+                pass.run(&mut after);
+                check_consistency(&after)
+                    .and_then(|_| check_equivalence(&random.graph, &after, seed))
+                    .unwrap_or_else(|err| panic!("{}, seed {}: {}", pass.name(), seed, err));
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_pass_sequences() {
+        let params = RandomGraphParams {
+            levers: 12,
+            gates: 100,
+            ..Default::default()
+        };
+        let passes = OptimizationPass::ALL;
+        for seed in 0..20 {
+            let mut rng = SplitMix64(seed);
+            let random = random_graph(seed, &params);
+            let mut after = random.graph.clone();
+            let mut sequence = Vec::new();
+            for _ in 0..1 + rng.below(12) {
+                let pass = passes[rng.below(passes.len())];
+                sequence.push(pass.name());
+                pass.run(&mut after);
+                check_consistency(&after)
+                    .unwrap_or_else(|err| panic!("Seed {}, {:?}: {}", seed, sequence, err));
+            }
+            check_equivalence(&random.graph, &after, seed)
+                .unwrap_or_else(|err| panic!("Seed {}, {:?}: {}", seed, sequence, err));
+        }
+    }
+
+    #[test]
+    fn test_default_config_fixpoint() {
+        let params = RandomGraphParams {
+            levers: 12,
+            gates: 100,
+            ..Default::default()
+        };
+        let once = OptimizerConfig {
+            iterations: 1,
+            log: false,
+            ..Default::default()
+        };
+        for seed in 0..100 {
+            let random = random_graph(seed, &params);
+            let mut after = random.graph.clone();
+            after.run_optimizations(&OptimizerConfig {
+                log: false,
+                ..Default::default()
+            });
+            check_consistency(&after)
+                .and_then(|_| check_equivalence(&random.graph, &after, seed))
+                .unwrap_or_else(|err| panic!("Seed {}: {}", seed, err));
+            // No pass finds anything else to optimize.
+            for statistics in after.run_optimizations(&once) {
+                assert!(!statistics.changed(), "Seed {}: {:?}", seed, statistics);
+            }
+        }
+    }
+
+    #[test]
+    fn test_netlist_round_trip() {
+        let text = "\
+lever a
+lever b
+x = and a b OFF
+q = nor x nq
+nq = nor b q
+d = delay q
+output out q x
+keep x
+assume q OFF
+";
+        let netlist = Netlist::parse(text);
+        assert_eq!(netlist.write(), text);
+        compare(&netlist, &Netlist::parse(&netlist.write())).unwrap();
+    }
+
+    #[test]
+    fn test_compare() {
+        let expected =
+            Netlist::parse("lever a\nlever b\nx = and a b\ny = not a\nz = or y x\noutput out z\n");
+        // Names of the gates and order of the dependencies don't matter.
+        let renamed =
+            Netlist::parse("lever b\nlever a\nn = not a\nm = and b a\nr = or m n\noutput out r\n");
+        compare(&renamed, &expected).unwrap();
+
+        let different_type =
+            Netlist::parse("lever a\nlever b\nx = or a b\ny = not a\nz = or y x\noutput out z\n");
+        assert!(compare(&different_type, &expected).is_err());
+        let different_dependency =
+            Netlist::parse("lever a\nlever b\nx = and a a\ny = not a\nz = or y x\noutput out z\n");
+        assert!(compare(&different_dependency, &expected).is_err());
+        let extra_gate = Netlist::parse(
+            "lever a\nlever b\nx = and a b\ny = not a\nz = or y x\nw = not z\noutput out z\n",
+        );
+        assert_eq!(
+            compare(&extra_gate, &expected),
+            Err("w isn't in the expected netlist".into())
+        );
+        let missing_lever = Netlist::parse("lever a\noutput out a\n");
+        assert!(compare(&missing_lever, &expected).is_err());
+    }
+
+    #[test]
+    fn test_check_consistency() {
+        let netlist = Netlist::parse("lever a\nx = not a\ny = or x\noutput out y\n");
+        check_consistency(&netlist.graph).unwrap();
+
+        let mut g = netlist.graph.clone();
+        let (x, y) = (gi!(3), gi!(4));
+        g.get_mut(y).dependencies.push(x);
+        g.get_mut(x).dependents.clear();
+        assert!(check_consistency(&g).is_err());
+        let mut g = netlist.graph;
+        g.nodes.remove(x.into());
+        assert!(check_consistency(&g).is_err());
+    }
+
+    #[test]
+    fn test_check_pass() {
+        let input = "lever a\nx = and a ON\ny = not x\noutput out y\n";
+        check_pass(
+            OptimizationPass::ConstPropagation,
+            input,
+            "lever a\ny = not a\noutput out y\n",
+        )
+        .unwrap();
+        // The result has to match.
+        assert!(check_pass(OptimizationPass::ConstPropagation, input, input).is_err());
+    }
+}
@@ -0,0 +1,562 @@
+use super::*;
+use crate::graph::{GateGraphBuilder, InitPhase, InitializedGateGraph};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+/// Optimization pass that can be selected in an [OptimizerConfig].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OptimizationPass {
+    /// Replaces gates whose state can't change with [ON](crate::graph::ON) or [OFF](crate::graph::OFF).
+    ConstPropagation,
+    /// Merges not gates with the same dependency.
+    NotDeduplication,
+    /// Removes and, or and xor gates with a single dependency, turns their nand, nor and xnor versions into nots.
+    SingleDependencyCollapsing,
+    /// Removes gates that don't affect any observable gate.
+    DeadCodeElimination,
+    /// Merges gates that always have the same state, including gates in loops.
+    GlobalValueNumbering,
+    /// Merges gates into dependencies of the same type, and(and(a, b), c) becomes and(a, b, c).
+    EqualGateMerging,
+    /// Removes repeated dependencies of gates.
+    DependencyDeduplication,
+    /// Replaces the gates assumed with [GateGraphBuilder::assume_after_reset] with their state after reset.
+    ///
+    /// Only [OptimizerConfig::assuming_reset] runs it, because the logic that depends on those gates doesn't
+    /// behave as it should until the circuit is reset.
+    ResetAssumptions,
+}
+impl OptimizationPass {
+    /// Every pass in the order [OptimizerConfig::default] runs them, all but the opt-in
+    /// [OptimizationPass::ResetAssumptions].
+    pub const ALL: [OptimizationPass; 7] = [
+        OptimizationPass::ConstPropagation,
+        OptimizationPass::NotDeduplication,
+        OptimizationPass::SingleDependencyCollapsing,
+        OptimizationPass::DeadCodeElimination,
+        OptimizationPass::GlobalValueNumbering,
+        OptimizationPass::EqualGateMerging,
+        OptimizationPass::DependencyDeduplication,
+    ];
+
+    /// Returns the name of the pass, as reported to [InitPhase::Optimization] and the logs.
+    pub fn name(&self) -> &'static str {
+        match self {
+            OptimizationPass::ConstPropagation => "const propagation",
+            OptimizationPass::NotDeduplication => "not deduplication",
+            OptimizationPass::SingleDependencyCollapsing => "single dependency collapsing",
+            OptimizationPass::DeadCodeElimination => "dead code elimination",
+            OptimizationPass::GlobalValueNumbering => "global value numbering",
+            OptimizationPass::EqualGateMerging => "equal gate merging",
+            OptimizationPass::DependencyDeduplication => "dependency deduplication",
+            OptimizationPass::ResetAssumptions => "reset assumptions",
+        }
+    }
+
+    /// Returns the passes that can find more to optimize after this one changes the graph, the pass manager
+    /// runs them again if they are in the [OptimizerConfig].
+    pub fn invalidates(&self) -> &'static [OptimizationPass] {
+        use OptimizationPass::*;
+        match self {
+            ConstPropagation => &[
+                NotDeduplication,
+                SingleDependencyCollapsing,
+                DeadCodeElimination,
+                GlobalValueNumbering,
+                EqualGateMerging,
+                DependencyDeduplication,
+            ],
+            // Dependents of the merged nots can end up depending on the same not twice.
+            NotDeduplication => &[DependencyDeduplication, GlobalValueNumbering],
+            // Collapsing changes gate types, moves dependencies around and can leave dependencies unused.
+            SingleDependencyCollapsing => &[
+                ConstPropagation,
+                NotDeduplication,
+                SingleDependencyCollapsing,
+                DeadCodeElimination,
+                GlobalValueNumbering,
+                EqualGateMerging,
+                DependencyDeduplication,
+            ],
+            // Dependencies of removed gates can be left with a single dependent.
+            DeadCodeElimination => &[SingleDependencyCollapsing],
+            // Dependencies are sorted by index instead of value number, merging gates can make more of them equal.
+            GlobalValueNumbering => &[
+                SingleDependencyCollapsing,
+                GlobalValueNumbering,
+                DependencyDeduplication,
+            ],
+            // Merging a dependency without dependencies leaves a gate without inputs, which is a constant.
+            EqualGateMerging => &[
+                ConstPropagation,
+                SingleDependencyCollapsing,
+                GlobalValueNumbering,
+                DependencyDeduplication,
+            ],
+            // Gates with a constant dependency can become simple enough for const propagation to replace.
+            DependencyDeduplication => &[
+                ConstPropagation,
+                SingleDependencyCollapsing,
+                GlobalValueNumbering,
+                EqualGateMerging,
+            ],
+            ResetAssumptions => &[ConstPropagation],
+        }
+    }
+
+    /// Returns the passes that have to run before this one if they are in the [OptimizerConfig] too,
+    /// the pass manager moves them ahead of it.
+    pub fn requires(&self) -> &'static [OptimizationPass] {
+        match self {
+            // The other passes can remove or merge the assumed gates, which drops their assumptions.
+            OptimizationPass::ResetAssumptions => &[],
+            _ => &[OptimizationPass::ResetAssumptions],
+        }
+    }
+
+    /// Runs the pass on `g`.
+    pub(in crate::graph) fn run(&self, g: &mut GateGraphBuilder) {
+        match self {
+            OptimizationPass::ConstPropagation => const_propagation_pass(g),
+            OptimizationPass::NotDeduplication => not_deduplication_pass(g),
+            OptimizationPass::SingleDependencyCollapsing => single_dependency_collapsing_pass(g),
+            OptimizationPass::DeadCodeElimination => dead_code_elimination_pass(g),
+            OptimizationPass::GlobalValueNumbering => global_value_numbering_pass(g),
+            OptimizationPass::EqualGateMerging => equal_gate_merging_pass(g),
+            OptimizationPass::DependencyDeduplication => dependency_deduplication_pass(g),
+            OptimizationPass::ResetAssumptions => reset_assumptions_pass(g),
+        }
+    }
+}
+
+/// Configuration of the optimizations run by [GateGraphBuilder::init_with_config].
+///
+/// The passes are run by a small pass manager: every iteration it runs, in order, the passes that haven't run yet
+/// and the ones [invalidated](OptimizationPass::invalidates) by a pass that changed the graph since they last ran.
+/// It stops when no pass is left to run, which means that running any of them again wouldn't change anything,
+/// or after `iterations` iterations.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OptimizerConfig {
+    /// Passes to run in order, the same pass can appear more than once.
+    /// The passes they [require](OptimizationPass::requires) are moved ahead of them.
+    pub passes: Vec<OptimizationPass>,
+    /// Maximum number of iterations of the pass manager, a safety cap for passes that keep finding things to change.
+    /// Use 1 to run every pass exactly once.
+    pub iterations: usize,
+    /// Whether to log the size reduction of every pass to the "logicsim::optimizer" target.
+    pub log: bool,
+}
+impl OptimizerConfig {
+    /// Returns an [OptimizerConfig] that runs no passes, like [GateGraphBuilder::init_unoptimized].
+    pub fn none() -> Self {
+        Self {
+            passes: Vec::new(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns an [OptimizerConfig] that runs [OptimizationPass::ResetAssumptions] before the
+    /// [default](OptimizerConfig::default) passes, to simplify the logic that depends on the gates assumed with
+    /// [GateGraphBuilder::assume_after_reset].
+    pub fn assuming_reset() -> Self {
+        let mut config = Self::default();
+        config.passes.insert(0, OptimizationPass::ResetAssumptions);
+        config
+    }
+}
+impl Default for OptimizerConfig {
+    /// The passes run by [GateGraphBuilder::init], until none of them changes the graph.
+    fn default() -> Self {
+        Self {
+            passes: OptimizationPass::ALL.to_vec(),
+            iterations: 16,
+            log: true,
+        }
+    }
+}
+
+/// Size of the graph before and after one run of an [OptimizationPass], returned by
+/// [GateGraphBuilder::init_with_config].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PassStatistics {
+    /// Pass that ran.
+    pub pass: OptimizationPass,
+    /// Iteration of the pass manager in which it ran, starting at 0.
+    pub iteration: usize,
+    /// Number of gates before running the pass.
+    pub gates_before: usize,
+    /// Number of gates after running the pass.
+    pub gates_after: usize,
+    /// Number of dependencies of all gates before running the pass.
+    pub dependencies_before: usize,
+    /// Number of dependencies of all gates after running the pass.
+    pub dependencies_after: usize,
+    /// Whether the pass changed the graph, even if its size stayed the same.
+    pub modified: bool,
+    /// Time it took to run the pass.
+    pub duration: Duration,
+}
+impl PassStatistics {
+    /// Returns the number of gates removed by the pass.
+    pub fn removed_gates(&self) -> usize {
+        self.gates_before.saturating_sub(self.gates_after)
+    }
+
+    /// Returns true if the pass changed the graph.
+    pub fn changed(&self) -> bool {
+        self.modified
+    }
+}
+
+impl GateGraphBuilder {
+    /// Returns a new [InitializedGateGraph] created from `self` after running the optimizations in `config`,
+    /// along with the statistics of every pass that ran.
+    ///
+    /// [init](GateGraphBuilder::init) runs [OptimizerConfig::default], use this to skip passes that don't help
+    /// your circuit or to limit how many times they run.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,OptimizerConfig,OptimizationPass};
+    /// let mut g = GateGraphBuilder::new();
+    /// let l = g.lever("l");
+    /// let not = g.not1(l.bit(), "not");
+    /// let not2 = g.not1(l.bit(), "not2");
+    /// let or = g.or2(not, not2, "or");
+    /// let output = g.output1(or, "or");
+    ///
+    /// let config = OptimizerConfig {
+    ///     passes: vec![
+    ///         OptimizationPass::NotDeduplication,
+    ///         OptimizationPass::DependencyDeduplication,
+    ///     ],
+    ///     iterations: 16,
+    ///     log: false,
+    /// };
+    /// let (ig, statistics) = &mut g.init_with_config(&config);
+    /// assert_eq!(statistics[0].removed_gates(), 1);
+    /// // Merging the nots made the or depend on the same not twice.
+    /// assert_eq!(statistics[1].dependencies_after, statistics[1].dependencies_before - 1);
+    /// // Nothing invalidated not deduplication, so it didn't run again.
+    /// assert_eq!(statistics.len(), 2);
+    /// assert_eq!(output.b0(ig), true);
+    /// ```
+    pub fn init_with_config(
+        mut self,
+        config: &OptimizerConfig,
+    ) -> (InitializedGateGraph, Vec<PassStatistics>) {
+        let span = tracing::info_span!(target: "logicsim::init", "init", gates = self.len());
+        let _enter = span.enter();
+
+        let progress = &mut |_, _, _| {};
+        let warnings = self.find_init_warnings();
+        let statistics = self.optimize_with_config(config, progress);
+        (
+            self.compacted(progress).initialize(warnings, progress),
+            statistics,
+        )
+    }
+
+    /// Runs the optimizations in `config` without initializing the graph and returns the statistics of
+    /// every pass that ran, to measure or inspect the passes on their own.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,OptimizerConfig,OptimizationPass};
+    /// let mut g = GateGraphBuilder::new();
+    /// let l = g.lever("l");
+    /// let not = g.not1(l.bit(), "not");
+    /// let not2 = g.not1(l.bit(), "not2");
+    /// let or = g.or2(not, not2, "or");
+    /// g.output1(or, "or");
+    ///
+    /// let config = OptimizerConfig {
+    ///     passes: vec![OptimizationPass::NotDeduplication],
+    ///     iterations: 1,
+    ///     log: false,
+    /// };
+    /// let statistics = g.run_optimizations(&config);
+    /// assert_eq!(statistics[0].removed_gates(), 1);
+    /// ```
+    pub fn run_optimizations(&mut self, config: &OptimizerConfig) -> Vec<PassStatistics> {
+        self.optimize_with_config(config, &mut |_, _, _| {})
+    }
+
+    /// Runs the optimizations in `config` and returns the statistics of every pass that ran.
+    pub(in crate::graph) fn optimize_with_config(
+        &mut self,
+        config: &OptimizerConfig,
+        progress: &mut dyn FnMut(InitPhase, usize, usize),
+    ) -> Vec<PassStatistics> {
+        let passes = schedule(&config.passes);
+        let mut pending = vec![true; passes.len()];
+        let mut statistics = Vec::new();
+        for iteration in 0..config.iterations {
+            if !pending.contains(&true) {
+                break;
+            }
+            for i in 0..passes.len() {
+                if !pending[i] {
+                    continue;
+                }
+                pending[i] = false;
+                let pass_statistics =
+                    self.run_optimization(passes[i], iteration, config.log, progress);
+                if pass_statistics.changed() {
+                    let invalidated = passes[i].invalidates();
+                    for (pending, pass) in pending.iter_mut().zip(&passes) {
+                        *pending |= invalidated.contains(pass);
+                    }
+                }
+                statistics.push(pass_statistics);
+            }
+        }
+        if config.log && pending.contains(&true) {
+            tracing::info!(
+                target: "logicsim::optimizer",
+                "Optimizer stopped after {} iterations with passes left to run",
+                config.iterations
+            );
+        }
+        statistics
+    }
+
+    /// Runs optimization `pass` and logs the results of the optimization and how long it took to the
+    /// "logicsim::optimizer" target if `log` is true.
+    fn run_optimization(
+        &mut self,
+        pass: OptimizationPass,
+        iteration: usize,
+        log: bool,
+        progress: &mut dyn FnMut(InitPhase, usize, usize),
+    ) -> PassStatistics {
+        let name = pass.name();
+        let span = tracing::debug_span!(target: "logicsim::optimizer", "optimization", pass = name);
+        let _enter = span.enter();
+
+        progress(InitPhase::Optimization(name), 0, 1);
+        let gates_before = self.len();
+        let (dependencies_before, hash_before) = self.structure();
+        let start = Instant::now();
+        pass.run(self);
+        let duration = start.elapsed();
+        progress(InitPhase::Optimization(name), 1, 1);
+        let (dependencies_after, hash_after) = self.structure();
+        let statistics = PassStatistics {
+            pass,
+            iteration,
+            gates_before,
+            gates_after: self.len(),
+            dependencies_before,
+            dependencies_after,
+            modified: hash_before != hash_after,
+            duration,
+        };
+        if log {
+            tracing::info!(
+                target: "logicsim::optimizer",
+                micros = duration.as_micros() as u64,
+                "Optimization: {}, old size:{}, new size:{}, reduction: {:.1}%, time: {:?}",
+                name,
+                gates_before,
+                statistics.gates_after,
+                statistics.removed_gates() as f32 / gates_before as f32 * 100.,
+                duration
+            );
+        }
+        statistics
+    }
+
+    /// Returns the number of dependencies of all gates and a hash of the index, type and dependencies of every gate,
+    /// which changes when a pass modifies the graph.
+    fn structure(&self) -> (usize, u64) {
+        let mut hasher = DefaultHasher::new();
+        let mut dependencies = 0;
+        for (i, gate) in self.nodes.iter() {
+            i.hash(&mut hasher);
+            gate.ty.hash(&mut hasher);
+            gate.dependencies.hash(&mut hasher);
+            dependencies += gate.dependencies.len();
+        }
+        (dependencies, hasher.finish())
+    }
+}
+
+/// Returns `passes` in the order the pass manager runs them, with the passes they
+/// [require](OptimizationPass::requires) moved ahead of them.
+fn schedule(passes: &[OptimizationPass]) -> Vec<OptimizationPass> {
+    fn visit(
+        i: usize,
+        passes: &[OptimizationPass],
+        visited: &mut [bool],
+        scheduled: &mut Vec<OptimizationPass>,
+    ) {
+        if visited[i] {
+            return;
+        }
+        visited[i] = true;
+        for (j, pass) in passes.iter().enumerate() {
+            if passes[i].requires().contains(pass) {
+                visit(j, passes, visited, scheduled);
+            }
+        }
+        scheduled.push(passes[i]);
+    }
+
+    let mut visited = vec![false; passes.len()];
+    let mut scheduled = Vec::with_capacity(passes.len());
+    for i in 0..passes.len() {
+        visit(i, passes, &mut visited, &mut scheduled);
+    }
+    scheduled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuits::{adder, register, WordInput};
+    use crate::graph::{OFF, ON};
+
+    #[test]
+    fn test_optimizer_config() {
+        let build = || {
+            let mut g = GateGraphBuilder::new();
+            let a = WordInput::new(&mut g, 8, "a");
+            let b = WordInput::new(&mut g, 8, "b");
+            let sum = adder(&mut g, OFF, &a.bits(), &b.bits(), "adder");
+            let sum = g.output(&sum, "sum");
+            (g, a, b, sum)
+        };
+
+        let (g, ..) = build();
+        let gates = g.len();
+        let (ig, statistics) = g.init_with_config(&OptimizerConfig::none());
+        assert!(statistics.is_empty());
+        assert_eq!(ig.len(), gates);
+
+        let (g, a, b, sum) = build();
+        let start = Instant::now();
+        let (mut default, statistics) = g.init_with_config(&OptimizerConfig::default());
+        assert!(statistics.iter().map(|s| s.duration).sum::<Duration>() <= start.elapsed());
+        let passes: Vec<_> = statistics.iter().map(|s| s.pass).collect();
+        assert_eq!(passes[..7], OptimizationPass::ALL);
+        assert!(statistics.len() > 7);
+        let (g, ..) = build();
+        assert_eq!(default.len(), g.init().len());
+        WordInput::set_many_stable(&mut default, &[(&a, 100), (&b, 55)]);
+        assert_eq!(sum.u8(&default), 155);
+
+        let (g, ..) = build();
+        let once = OptimizerConfig {
+            iterations: 1,
+            ..Default::default()
+        };
+        let (ig, statistics) = g.init_with_config(&once);
+        assert_eq!(statistics.len(), 7);
+        assert!(ig.len() >= default.len());
+
+        // The default config reaches a fixpoint.
+        let (mut g, ..) = build();
+        g.run_optimizations(&OptimizerConfig::default());
+        let statistics = g.run_optimizations(&once);
+        assert!(statistics.iter().all(|s| !s.changed()));
+    }
+
+    #[test]
+    fn test_pass_requirements() {
+        let mut g = GateGraphBuilder::new();
+        let l = g.lever("l");
+        let not = g.not1(l.bit(), "not");
+        g.output1(not, "not");
+        let config = OptimizerConfig {
+            passes: vec![
+                OptimizationPass::ConstPropagation,
+                OptimizationPass::ResetAssumptions,
+                OptimizationPass::DeadCodeElimination,
+            ],
+            iterations: 1,
+            log: false,
+        };
+        let passes: Vec<_> = g
+            .run_optimizations(&config)
+            .iter()
+            .map(|s| s.pass)
+            .collect();
+        assert_eq!(
+            passes,
+            [
+                OptimizationPass::ResetAssumptions,
+                OptimizationPass::ConstPropagation,
+                OptimizationPass::DeadCodeElimination,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gates_without_dependencies() {
+        let mut g = GateGraphBuilder::new();
+        let l = g.lever("l");
+        // Gates without dependencies have the state of their type with every input off.
+        let empty_or = g.or("empty_or");
+        let or = g.or2(l.bit(), empty_or, "or");
+        let or = g.or1(or, "or1");
+        let empty_and = g.and("empty_and");
+        let and = g.and2(l.bit(), empty_and, "and");
+        let empty_nor = g.nor("empty_nor");
+        let nor = g.xor2(l.bit(), empty_nor, "nor");
+        let output = g.output(&[or, and, nor], "output");
+
+        let ig = &mut g.init();
+        assert_eq!(output.u8(ig), 0b100);
+        ig.set_lever_stable(l);
+        assert_eq!(output.u8(ig), 0b011);
+    }
+
+    #[test]
+    fn test_reset_assumptions() {
+        let mut g = GateGraphBuilder::new();
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let write = g.lever("write");
+        let x = g.lever("x");
+        let mode = register(
+            &mut g,
+            clock.bit(),
+            write.bit(),
+            ON,
+            reset.bit(),
+            &[ON],
+            "mode",
+        );
+        let masked = g.and2(mode[0], x.bit(), "masked");
+        let not_masked = g.not1(masked, "not_masked");
+        let output = g.output1(not_masked, "output");
+        g.assume_after_reset(&mode, &[OFF]);
+        // Assumptions follow the gates when they move.
+        g.compact_now();
+
+        let (mut default, statistics) = g.clone().init_with_config(&OptimizerConfig::default());
+        assert!(statistics
+            .iter()
+            .all(|s| s.pass != OptimizationPass::ResetAssumptions));
+        let (ig, statistics) = &mut g.init_with_config(&OptimizerConfig::assuming_reset());
+        assert_eq!(statistics[0].pass, OptimizationPass::ResetAssumptions);
+        assert!(ig.len() < default.len());
+
+        ig.pulse_lever_stable(reset);
+        ig.set_lever_stable(x);
+        assert_eq!(output.b0(ig), true);
+        // Breaking the assumption doesn't change the output anymore.
+        ig.set_lever_stable(write);
+        ig.pulse_lever_stable(clock);
+        assert_eq!(output.b0(ig), true);
+
+        let ig = &mut default;
+        ig.pulse_lever_stable(reset);
+        ig.set_lever_stable(x);
+        ig.set_lever_stable(write);
+        ig.pulse_lever_stable(clock);
+        assert_eq!(output.b0(ig), false);
+    }
+}
@@ -0,0 +1,21 @@
+use super::super::{gate::*, graph_builder::GateGraphBuilder};
+
+// Connects the dependents of the gates assumed with GateGraphBuilder::assume_after_reset to the constant
+// they are assumed to be equal to, const propagation simplifies them afterwards.
+// The assumptions are consumed, so running the pass again doesn't touch gates that reused their indexes.
+pub fn reset_assumptions_pass(g: &mut GateGraphBuilder) {
+    let mut assumptions: Vec<_> = std::mem::take(&mut g.reset_assumptions)
+        .into_iter()
+        .collect();
+    assumptions.sort_unstable();
+    for (idx, on) in assumptions {
+        if g.nodes.get(idx.into()).is_none() {
+            continue;
+        }
+        let constant = if on { ON } else { OFF };
+        for dependent in std::mem::take(&mut g.get_mut(idx).dependents) {
+            g.get_mut(dependent).swap_dependency(idx, constant);
+            g.get_mut(constant).dependents.insert(dependent);
+        }
+    }
+}
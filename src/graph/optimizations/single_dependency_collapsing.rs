@@ -37,14 +37,16 @@ pub fn single_dependency_collapsing_pass(g: &mut GateGraphBuilder) {
         let dependency = g.get(idx).dependencies[0];
         match ty {
             Off | On | Lever => unreachable!("Off, On, and lever nodes have no dependencies"),
+            Delay | TriState | Custom(_) => {}
             Not | Nand | Nor | Xnor => {
-                if !g.get(dependency).ty.has_negated_version() {
+                // Negating a kept dependency in place would skip it.
+                if !g.get(dependency).ty.has_negated_version() || g.kept.contains(&dependency) {
                     g.get_mut(idx).ty = Not;
                     continue;
                 }
                 // if the dependency has only one dependent (idx) then we can move idx.dependents to
-                // the dependency and negate it.
-                if g.get(dependency).dependents.len() == 1 {
+                // the dependency and negate it, unless someone is looking at the dependency.
+                if g.get(dependency).dependents.len() == 1 && !g.is_observable(dependency) {
                     let dependents = std::mem::take(&mut g.get_mut(idx).dependents);
                     g.get_mut(dependency).dependents.remove(&idx);
                     for dependant in dependents {
@@ -0,0 +1,191 @@
+use super::gate::*;
+use super::InitializedGateGraph;
+use std::collections::VecDeque;
+
+impl InitializedGateGraph {
+    /// Returns up to `max_paths` paths through which the signal named `from` reaches the signal named `to`,
+    /// shortest first, each path is a list of gate [full names](InitializedGateGraph::dump_dot)
+    /// starting at a gate of `from` and ending at a gate of `to`.
+    ///
+    /// A signal name can be the name of an [output](super::GateGraphBuilder::output) or,
+    /// if the "debug_gates" feature is enabled, the name of any gate that survived optimization.
+    ///
+    /// The paths are representative rather than exhaustive, every gate shows up in at most `max_paths`
+    /// of the candidate paths that get explored. Which is enough to figure out how a control signal
+    /// reaches a misbehaving output without digging through a [.dot file](InitializedGateGraph::dump_dot).
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// let mut g = GateGraphBuilder::new();
+    /// let l1 = g.lever("l1");
+    /// let l2 = g.lever("l2");
+    ///
+    /// let and = g.and2(l1.bit(), l2.bit(), "and");
+    /// let or = g.or2(and, l1.bit(), "or");
+    /// g.output1(l1.bit(), "l1");
+    /// g.output1(or, "or");
+    ///
+    /// let ig = g.init();
+    /// let paths = ig.paths_between("l1", "or", 10);
+    ///
+    /// assert_eq!(paths.len(), 2);
+    /// // The direct connection is shorter.
+    /// assert_eq!(paths[0].len(), 2);
+    /// assert_eq!(paths[1].len(), 3);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Will panic if there is no signal named `from` or `to`.
+    pub fn paths_between(&self, from: &str, to: &str, max_paths: usize) -> Vec<Vec<String>> {
+        let from = self.signal_gates(from);
+        let to = self.signal_gates(to);
+
+        // Only gates that can reach `to` are worth exploring.
+        let mut reaches_to = vec![false; self.len()];
+        let mut queue: VecDeque<GateIndex> = to.iter().copied().collect();
+        for gate in &to {
+            reaches_to[gate.idx] = true;
+        }
+        while let Some(gate) = queue.pop_front() {
+            for dependency in &self.nodes[gate.idx].dependencies {
+                if !reaches_to[dependency.idx] {
+                    reaches_to[dependency.idx] = true;
+                    queue.push_back(*dependency);
+                }
+            }
+        }
+
+        // Breadth first search over partial paths stored as (tip, parent) pairs.
+        let mut tree: Vec<(GateIndex, Option<usize>)> = Vec::new();
+        let mut times_explored = vec![0; self.len()];
+        let mut queue = VecDeque::new();
+        for gate in from {
+            if reaches_to[gate.idx] {
+                tree.push((gate, None));
+                queue.push_back(tree.len() - 1);
+            }
+        }
+
+        let mut paths = Vec::new();
+        while let Some(node) = queue.pop_front() {
+            if paths.len() >= max_paths {
+                break;
+            }
+            let (tip, _) = tree[node];
+            if to.contains(&tip) {
+                paths.push(self.path_names(&tree, node));
+                continue;
+            }
+            for dependent in &self.nodes[tip.idx].dependents {
+                if !reaches_to[dependent.idx]
+                    || times_explored[dependent.idx] >= max_paths
+                    || path_contains(&tree, node, *dependent)
+                {
+                    continue;
+                }
+                times_explored[dependent.idx] += 1;
+                tree.push((*dependent, Some(node)));
+                queue.push_back(tree.len() - 1);
+            }
+        }
+        paths
+    }
+
+    /// Returns the gates of the outputs or gates named `name`.
+    fn signal_gates(&self, name: &str) -> Vec<GateIndex> {
+        let mut gates: Vec<GateIndex> = self
+            .output_handles
+            .iter()
+            .filter(|output| output.name == name)
+            .flat_map(|output| output.bits.iter().copied())
+            .collect();
+        #[cfg(feature = "debug_gates")]
+        gates.extend(
+            self.names
+                .iter()
+                .filter(|(_, gate_name)| *gate_name == name)
+                .map(|(gate, _)| *gate),
+        );
+        gates.sort_unstable();
+        gates.dedup();
+        assert!(!gates.is_empty(), "There is no signal named {}", name);
+        gates
+    }
+
+    /// Returns the full names of the gates in the path that ends at `node`, starting from the root.
+    fn path_names(&self, tree: &[(GateIndex, Option<usize>)], node: usize) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut current = Some(node);
+        while let Some(node) = current {
+            let (gate, parent) = tree[node];
+            names.push(self.full_name(gate));
+            current = parent;
+        }
+        names.reverse();
+        names
+    }
+}
+
+/// Returns true if `gate` is in the path that ends at `node`.
+fn path_contains(tree: &[(GateIndex, Option<usize>)], node: usize, gate: GateIndex) -> bool {
+    let mut current = Some(node);
+    while let Some(node) = current {
+        let (tip, parent) = tree[node];
+        if tip == gate {
+            return true;
+        }
+        current = parent;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::GateGraphBuilder;
+
+    #[test]
+    fn test_paths_between() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let clock = g.lever("clock");
+        let data = g.lever("data");
+        let unrelated = g.lever("unrelated");
+
+        // A loop shouldn't produce infinite paths.
+        let latch = g.or("latch");
+        let and = g.and2(latch, clock.bit(), "and");
+        g.dpush(latch, and);
+        g.dpush(latch, data.bit());
+        let xor = g.xor2(and, unrelated.bit(), "xor");
+        g.output1(clock.bit(), "clock");
+        g.output1(data.bit(), "data");
+        g.output1(unrelated.bit(), "unrelated");
+        g.output1(xor, "xor");
+        g.output1(latch, "latch");
+
+        let ig = graph.init();
+        let paths = ig.paths_between("data", "xor", 10);
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].len(), 4);
+
+        assert!(ig.paths_between("xor", "data", 10).is_empty());
+        assert_eq!(ig.paths_between("clock", "xor", 10).len(), 1);
+        assert_eq!(ig.paths_between("latch", "latch", 10).len(), 1);
+
+        let paths = ig.paths_between("data", "latch", 1);
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].len(), 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_paths_between_unknown() {
+        let mut g = GateGraphBuilder::new();
+        let l = g.lever("l");
+        g.output1(l.bit(), "l");
+        g.init().paths_between("l", "nope", 1);
+    }
+}
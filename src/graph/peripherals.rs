@@ -0,0 +1,359 @@
+use super::handles::{LeverHandle, OutputHandle};
+use super::{InitializedGateGraph, SimulationError};
+use crate::data_structures::BitIter;
+
+/// Rust model of a device connected to a circuit, like a keyboard or a terminal,
+/// which gets called automatically once per clock cycle after being added with
+/// [InitializedGateGraph::add_peripheral].
+///
+/// # Example
+/// ```
+/// # use logicsim::graph::{GateGraphBuilder,BusView,HostPeripheral,LeverHandle,OutputHandle};
+/// # use logicsim::{counter,zeros,ON,OFF};
+/// // Resets the counter every time it reaches 3.
+/// struct Resetter {
+///     count: OutputHandle,
+///     reset: LeverHandle,
+/// }
+/// impl HostPeripheral for Resetter {
+///     fn tick(&mut self, bus: &mut BusView<'_>) {
+///         if bus.u128(self.count) == 3 {
+///             bus.pulse_lever(self.reset);
+///         }
+///     }
+/// }
+///
+/// let mut g = GateGraphBuilder::new();
+/// let clock = g.lever("clock");
+/// let reset = g.lever("reset");
+/// let count = counter(&mut g, clock.bit(), ON, OFF, ON, reset.bit(), &zeros(4), "counter");
+/// let count = g.output(&count, "count");
+///
+/// let ig = &mut g.init();
+/// ig.pulse_lever_stable(reset);
+/// ig.add_peripheral(clock, Resetter { count, reset });
+///
+/// let mut values = Vec::new();
+/// for _ in 0..6 {
+///     ig.pulse_lever_stable(clock);
+///     values.push(count.u8(ig));
+/// }
+/// assert_eq!(values, [1, 2, 0, 1, 2, 0]);
+/// ```
+pub trait HostPeripheral: Send {
+    /// Called when the graph becomes stable after every falling edge of the clock the peripheral was added with,
+    /// when the synchronous logic is done with the cycle and the inputs for the next one can be set.
+    fn tick(&mut self, bus: &mut BusView<'_>);
+}
+
+/// Access to the graph given to [HostPeripheral::tick].
+///
+/// Outputs are read right away, lever changes propagate through the graph after the peripherals return,
+/// in the following ticks of the same [run_until_stable](InitializedGateGraph::run_until_stable).
+pub struct BusView<'a> {
+    ig: &'a mut InitializedGateGraph,
+    pulsed: &'a mut Vec<LeverHandle>,
+}
+impl BusView<'_> {
+    /// Returns the value of `output` like [OutputHandle::u128].
+    pub fn u128(&self, output: OutputHandle) -> u128 {
+        output.u128(self.ig)
+    }
+
+    /// Returns the value of the first bit of `output` like [OutputHandle::b0].
+    pub fn b0(&self, output: OutputHandle) -> bool {
+        output.b0(self.ig)
+    }
+
+    /// Sets the state of `lever` to `value`.
+    pub fn set_lever(&mut self, lever: LeverHandle, value: bool) {
+        self.ig.update_lever_inner(lever, value);
+    }
+
+    /// Sets the state of `levers` to the bits of `value` like [WordInput::set_to](crate::WordInput::set_to),
+    /// bit 0 goes to `levers[0]`.
+    pub fn set_levers(&mut self, levers: &[LeverHandle], value: u128) {
+        for (lever, bit) in levers.iter().zip(BitIter::new(value)) {
+            self.ig.update_lever_inner(*lever, bit);
+        }
+    }
+
+    /// Sets the state of `lever` to true, and back to false once the graph is stable.
+    pub fn pulse_lever(&mut self, lever: LeverHandle) {
+        self.set_lever(lever, true);
+        self.pulsed.push(lever);
+    }
+
+    /// Returns the number of ticks since the graph was initialized.
+    pub fn ticks(&self) -> usize {
+        self.ig.ticks
+    }
+}
+
+/// Handle to a peripheral added with [InitializedGateGraph::add_peripheral].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PeripheralHandle(usize);
+
+/// Peripheral added with [InitializedGateGraph::add_peripheral] along with the state of its clock the last time
+/// the graph was stable.
+struct Registered {
+    clock: LeverHandle,
+    clock_state: bool,
+    peripheral: Box<dyn HostPeripheral>,
+}
+
+/// Callback registered with [InitializedGateGraph::on_idle].
+type IdleCallback = Box<dyn FnMut(&mut BusView<'_>) + Send>;
+
+/// Peripherals of an [InitializedGateGraph].
+#[derive(Default)]
+pub(super) struct Peripherals {
+    registered: Vec<Option<Registered>>,
+    /// Levers to reset once the graph is stable, see [BusView::pulse_lever].
+    pulsed: Vec<LeverHandle>,
+    idle: Option<IdleCallback>,
+}
+
+impl InitializedGateGraph {
+    /// Adds `peripheral` to the graph, its [tick](HostPeripheral::tick) will be called once the graph is stable after
+    /// every falling edge of `clock`, during [tick](InitializedGateGraph::tick).
+    ///
+    /// Returns a [PeripheralHandle] which can be used to [remove](InitializedGateGraph::remove_peripheral) it.
+    pub fn add_peripheral<P: HostPeripheral + 'static>(
+        &mut self,
+        clock: LeverHandle,
+        peripheral: P,
+    ) -> PeripheralHandle {
+        let clock_state = self.value(self.lever_handles[clock.handle]);
+        self.peripherals.registered.push(Some(Registered {
+            clock,
+            clock_state,
+            peripheral: Box::new(peripheral),
+        }));
+        PeripheralHandle(self.peripherals.registered.len() - 1)
+    }
+
+    /// Removes the peripheral added with [add_peripheral](InitializedGateGraph::add_peripheral) that returned `handle`
+    /// and returns it, [None] if it had already been removed.
+    pub fn remove_peripheral(
+        &mut self,
+        handle: PeripheralHandle,
+    ) -> Option<Box<dyn HostPeripheral>> {
+        self.peripherals.registered[handle.0]
+            .take()
+            .map(|registered| registered.peripheral)
+    }
+
+    /// Returns true if there is nothing left to propagate through the graph, so [ticking](InitializedGateGraph::tick)
+    /// it won't change anything until a lever is updated.
+    pub fn is_idle(&self) -> bool {
+        self.pending_updates.is_empty() && self.scheduled.is_empty()
+    }
+
+    /// Calls `callback` every time the graph is [ticked](InitializedGateGraph::tick) while it's [idle](InitializedGateGraph::is_idle),
+    /// replacing the previous callback.
+    ///
+    /// A harness that ticks the graph waiting for an output to change, with nothing left to update the levers,
+    /// would spin forever. This happens when the circuit and the host get out of sync, for example if a handshake
+    /// gets lost. The callback can report it, panic or set levers through the [BusView] to wake the circuit up,
+    /// the changes propagate during the same tick.
+    ///
+    /// See [try_tick](InitializedGateGraph::try_tick) to get an error instead.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,ON,OFF};
+    /// let mut g = GateGraphBuilder::new();
+    /// let request = g.lever("request");
+    /// let ack = g.output1(request.bit(), "ack");
+    ///
+    /// let ig = &mut g.init();
+    /// // The host forgot to send the request, so it gets sent when the circuit goes idle.
+    /// ig.on_idle(move |bus| bus.set_lever(request, true));
+    /// while !ack.b0(ig) {
+    ///     ig.tick();
+    /// }
+    /// ```
+    pub fn on_idle<F: FnMut(&mut BusView<'_>) + Send + 'static>(&mut self, callback: F) {
+        self.peripherals.idle = Some(Box::new(callback));
+    }
+
+    /// Removes the callback registered with [on_idle](InitializedGateGraph::on_idle).
+    pub fn clear_on_idle(&mut self) {
+        self.peripherals.idle = None;
+    }
+
+    /// Like [tick](InitializedGateGraph::tick) but returns an error instead of doing nothing if the graph
+    /// is still [idle](InitializedGateGraph::is_idle) after calling the [on_idle](InitializedGateGraph::on_idle)
+    /// callback.
+    ///
+    /// Returns [SimulationError::AssertionFailed] if an [assertion](InitializedGateGraph::arm_assertions) started
+    /// failing during the tick.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,ON,OFF};
+    /// let mut g = GateGraphBuilder::new();
+    /// let request = g.lever("request");
+    /// let ack = g.output1(request.bit(), "ack");
+    ///
+    /// let ig = &mut g.init();
+    /// let mut result = Ok(true);
+    /// while !ack.b0(ig) && result.is_ok() {
+    ///     result = ig.try_tick();
+    /// }
+    /// assert!(result.is_err());
+    /// ```
+    pub fn try_tick(&mut self) -> Result<bool, SimulationError> {
+        self.wake_idle();
+        if self.is_idle() {
+            return Err(SimulationError::Idle);
+        }
+        let failures = self.assertions.failure_count();
+        let stable = self.tick();
+        if self.assertions.failure_count() > failures {
+            return Err(self.assertions.error(failures));
+        }
+        Ok(stable)
+    }
+
+    /// Calls the [on_idle](InitializedGateGraph::on_idle) callback if the graph is idle.
+    pub(super) fn wake_idle(&mut self) {
+        if !self.is_idle() {
+            return;
+        }
+        if let Some(mut callback) = self.peripherals.idle.take() {
+            let mut pulsed = std::mem::take(&mut self.peripherals.pulsed);
+            callback(&mut BusView {
+                ig: self,
+                pulsed: &mut pulsed,
+            });
+            self.peripherals.pulsed = pulsed;
+            // The read stack is empty while idle, this makes the changes propagate during the current tick.
+            self.pending_updates.swap();
+            // The callback could have registered a new one.
+            self.peripherals.idle.get_or_insert(callback);
+        }
+    }
+
+    /// Calls the peripherals whose clock had a falling edge if the graph is stable,
+    /// and resets the levers pulsed by peripherals.
+    pub(super) fn tick_peripherals(&mut self) {
+        if (self.peripherals.registered.is_empty() && self.peripherals.pulsed.is_empty())
+            || !self.is_idle()
+        {
+            return;
+        }
+        let mut peripherals = std::mem::take(&mut self.peripherals);
+        for lever in peripherals.pulsed.drain(..) {
+            self.update_lever_inner(lever, false);
+        }
+        for registered in peripherals.registered.iter_mut().flatten() {
+            let clock_state = self.value(self.lever_handles[registered.clock.handle]);
+            if registered.clock_state && !clock_state {
+                registered.peripheral.tick(&mut BusView {
+                    ig: self,
+                    pulsed: &mut peripherals.pulsed,
+                });
+            }
+            registered.clock_state = clock_state;
+        }
+        self.peripherals = peripherals;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{register, WordInput};
+
+    /// Writes the next value of a sequence into a register every cycle.
+    struct Feeder {
+        input: Vec<LeverHandle>,
+        write: LeverHandle,
+        next: u128,
+    }
+    impl HostPeripheral for Feeder {
+        fn tick(&mut self, bus: &mut BusView<'_>) {
+            bus.set_levers(&self.input, self.next);
+            bus.pulse_lever(self.write);
+            self.next += 1;
+        }
+    }
+
+    #[test]
+    fn test_peripheral_feeds_register() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let clock = g.lever("clock");
+        let write = g.lever("write");
+        let reset = g.lever("reset");
+        let input = WordInput::new(g, 4, "input");
+        let stored = register(g, write.bit(), ON, ON, reset.bit(), &input.bits(), "stored");
+        let stored = g.output(&stored, "stored");
+
+        let ig = &mut graph.init();
+        ig.pulse_lever_stable(reset);
+        let handle = ig.add_peripheral(
+            clock,
+            Feeder {
+                input: input.levers().to_vec(),
+                write,
+                next: 5,
+            },
+        );
+
+        let mut values = Vec::new();
+        for _ in 0..3 {
+            ig.pulse_lever_stable(clock);
+            values.push(stored.u8(ig));
+        }
+        assert_eq!(values, [5, 6, 7]);
+
+        // Raising edges don't call the peripheral.
+        ig.set_lever_stable(clock);
+        assert_eq!(stored.u8(ig), 7);
+
+        assert!(ig.remove_peripheral(handle).is_some());
+        assert!(ig.remove_peripheral(handle).is_none());
+        ig.reset_lever_stable(clock);
+        assert_eq!(stored.u8(ig), 7);
+    }
+
+    #[test]
+    fn test_on_idle() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let request = g.lever("request");
+        let ack = g.output1(request.bit(), "ack");
+        let ig = &mut graph.init();
+
+        assert!(ig.is_idle());
+        assert!(ig.try_tick().is_err());
+
+        // Flips the request every time the graph goes idle.
+        let wakes = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let wakes_clone = wakes.clone();
+        let mut value = false;
+        ig.on_idle(move |bus| {
+            wakes_clone.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            value = !value;
+            bus.set_lever(request, value);
+        });
+        assert_eq!(ig.try_tick(), Ok(true));
+        assert!(ack.b0(ig));
+        assert_eq!(ig.try_tick(), Ok(true));
+        assert!(!ack.b0(ig));
+        assert_eq!(wakes.load(std::sync::atomic::Ordering::Relaxed), 2);
+
+        // Levers pulsed by the callback are reset once the graph is stable.
+        ig.on_idle(move |bus| bus.pulse_lever(request));
+        assert_eq!(ig.try_tick(), Ok(false));
+        assert!(!ack.b0(ig));
+        ig.run_until_stable(10).unwrap();
+        assert!(!ack.b0(ig) && ig.is_idle());
+
+        ig.clear_on_idle();
+        assert!(ig.try_tick().is_err());
+    }
+}
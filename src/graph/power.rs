@@ -0,0 +1,364 @@
+use super::cost::CostTable;
+use super::gate::*;
+use super::handles::LeverHandle;
+use super::InitializedGateGraph;
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+/// Toggle counts collected while toggle counting is enabled, see [InitializedGateGraph::enable_toggle_counting].
+#[derive(Debug, Clone)]
+pub(super) struct ToggleCounter {
+    /// Number of times the state of every gate changed.
+    toggles: Vec<u64>,
+    /// [Simulation time](InitializedGateGraph::now) when toggle counting was enabled.
+    start: usize,
+    /// Lever whose rising edges are counted as cycles, every tick is a cycle if there is none.
+    clock: Option<GateIndex>,
+    /// State of the clock at the end of the last tick.
+    clock_state: bool,
+    /// Rising edges of the clock seen so far.
+    cycles: u64,
+}
+impl ToggleCounter {
+    /// Counts a change of state of the gate with index `idx`.
+    #[inline(always)]
+    pub(super) fn count(&mut self, idx: usize) {
+        self.toggles[idx] += 1;
+    }
+}
+
+/// Switching activity of a group of gates in a [PowerReport].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ModulePower {
+    /// Name of the module.
+    pub name: String,
+    /// Number of gates in the module, not counting levers and constants.
+    pub gates: usize,
+    /// Number of times the state of any of the gates changed.
+    pub toggles: u64,
+    /// Sum of the energy used by every toggle, according to the [CostTable].
+    pub energy: f64,
+    /// Energy used per cycle, the estimate of the power of the module.
+    pub power: f64,
+}
+impl ModulePower {
+    /// Returns the average number of toggles per gate per cycle, the activity factor of the module.
+    pub fn activity(&self, cycles: u64) -> f64 {
+        if self.gates == 0 || cycles == 0 {
+            0.0
+        } else {
+            self.toggles as f64 / (self.gates as u64 * cycles) as f64
+        }
+    }
+
+    fn add(&mut self, other: &ModulePower) {
+        self.gates += other.gates;
+        self.toggles += other.toggles;
+        self.energy += other.energy;
+        self.power += other.power;
+    }
+}
+
+/// Activity based power estimate of an [InitializedGateGraph] returned by [InitializedGateGraph::power_report].
+///
+/// Its [Display] implementation prints a table with the modules that use the most power first.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PowerReport {
+    /// Number of [ticks](InitializedGateGraph::tick) counted.
+    pub ticks: usize,
+    /// Number of cycles counted, see [InitializedGateGraph::enable_toggle_counting].
+    pub cycles: u64,
+    /// Activity of the whole graph.
+    pub total: ModulePower,
+    /// Activity of every module, sorted by power, highest first.
+    pub modules: Vec<ModulePower>,
+}
+impl PowerReport {
+    /// Returns the activity of the module named `name`.
+    pub fn module(&self, name: &str) -> Option<&ModulePower> {
+        self.modules.iter().find(|module| module.name == name)
+    }
+
+    /// Returns the activity of every module whose name starts with `prefix` together, like every gate of a
+    /// type of circuit, for example "ADDER:" for every [adder](crate::circuits::adder).
+    pub fn prefix(&self, prefix: &str) -> ModulePower {
+        self.sum(prefix, |name| name.starts_with(prefix))
+    }
+
+    /// Returns the activity of every module whose name ends with `suffix` together.
+    ///
+    /// Circuits prepend their type to the names of their gates, including the circuits they are made of,
+    /// so every gate of an instance ends with its name, for example "CNTR:counter" for a
+    /// [counter](crate::circuits::counter) named "counter".
+    pub fn suffix(&self, suffix: &str) -> ModulePower {
+        self.sum(suffix, |name| name.ends_with(suffix))
+    }
+
+    /// Returns the activity of every module whose name matches `filter` together, named `name`.
+    fn sum<F: Fn(&str) -> bool>(&self, name: &str, filter: F) -> ModulePower {
+        let mut power = ModulePower {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        for module in &self.modules {
+            if filter(&module.name) {
+                power.add(module);
+            }
+        }
+        power
+    }
+}
+impl Display for PowerReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let width = self
+            .modules
+            .iter()
+            .map(|module| module.name.len())
+            .chain(std::iter::once(self.total.name.len()))
+            .max()
+            .unwrap_or(0);
+        writeln!(f, "{} cycles in {} ticks", self.cycles, self.ticks)?;
+        writeln!(
+            f,
+            "{:width$} {:>8} {:>12} {:>8} {:>12}",
+            "module",
+            "gates",
+            "toggles",
+            "activity",
+            "power",
+            width = width
+        )?;
+        for module in self.modules.iter().chain(std::iter::once(&self.total)) {
+            writeln!(
+                f,
+                "{:width$} {:>8} {:>12} {:>8.3} {:>12.1}",
+                module.name,
+                module.gates,
+                module.toggles,
+                module.activity(self.cycles),
+                module.power,
+                width = width
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl InitializedGateGraph {
+    /// Starts counting how many times the state of every gate changes, from now until
+    /// [disable_toggle_counting](InitializedGateGraph::disable_toggle_counting) is called,
+    /// see [power_report](InitializedGateGraph::power_report).
+    ///
+    /// Every rising edge of `clock` at the end of a tick counts as a cycle, or every tick if it's [None].
+    /// Calling it again restarts the counts. Levers and constants are not counted.
+    ///
+    /// Toggle counting slows down the simulation slightly.
+    pub fn enable_toggle_counting(&mut self, clock: Option<LeverHandle>) {
+        let clock = clock.map(|clock| self.lever_handles[clock.handle]);
+        self.toggles = Some(ToggleCounter {
+            toggles: vec![0; self.nodes.len()],
+            start: self.ticks,
+            clock,
+            clock_state: clock.map(|clock| self.value(clock)).unwrap_or(false),
+            cycles: 0,
+        });
+    }
+
+    /// Stops counting toggles and discards the counts, see
+    /// [enable_toggle_counting](InitializedGateGraph::enable_toggle_counting).
+    pub fn disable_toggle_counting(&mut self) {
+        self.toggles = None;
+    }
+
+    /// Counts a cycle if the clock of the toggle counter had a rising edge during the last tick.
+    pub(super) fn count_cycles(&mut self) {
+        if let Some(counter) = &mut self.toggles {
+            match counter.clock {
+                Some(clock) => {
+                    let state = self.state.get_state(clock.idx);
+                    if state && !counter.clock_state {
+                        counter.cycles += 1;
+                    }
+                    counter.clock_state = state;
+                }
+                None => counter.cycles += 1,
+            }
+        }
+    }
+
+    /// Returns an activity based estimate of the power used by the graph since toggle counting was
+    /// [enabled](InitializedGateGraph::enable_toggle_counting), or [None] if it isn't enabled.
+    ///
+    /// Every toggle of a gate uses the energy `table` gives it, the power of a module is the energy its gates used
+    /// per cycle. Modules are grouped like in [cost_report](InitializedGateGraph::cost_report), by name with
+    /// the "debug_gates" feature, use [PowerReport::prefix] and [PowerReport::suffix] to add up related modules.
+    ///
+    /// This is as crude as the [CostTable], it doesn't know about wires or leakage, but the number of toggles
+    /// is what changes between implementations of the same unit, so it's good enough to compare them.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,CostTable,ON,OFF};
+    /// # use logicsim::{counter,zeros};
+    /// let mut g = GateGraphBuilder::new();
+    /// let clock = g.lever("clock");
+    /// let reset = g.lever("reset");
+    /// let count = counter(&mut g, clock.bit(), ON, OFF, ON, reset.bit(), &zeros(4), "counter");
+    /// g.output(&count, "count");
+    ///
+    /// let ig = &mut g.init();
+    /// ig.pulse_lever_stable(reset);
+    /// ig.enable_toggle_counting(Some(clock));
+    /// for _ in 0..16 {
+    ///     ig.pulse_lever_stable(clock);
+    /// }
+    /// let report = ig.power_report(&CostTable::default()).unwrap();
+    /// assert_eq!(report.cycles, 16);
+    /// assert!(report.total.power > 0.);
+    /// # #[cfg(feature = "debug_gates")]
+    /// assert_eq!(report.suffix("CNTR:counter").toggles, report.total.toggles);
+    /// # #[cfg(feature = "debug_gates")]
+    /// assert!(report.prefix("ADDER:").power > 0.);
+    /// println!("{}", report);
+    /// ```
+    pub fn power_report(&self, table: &CostTable) -> Option<PowerReport> {
+        let counter = self.toggles.as_ref()?;
+        let cycles = counter.cycles;
+        let per_cycle = |energy: f64| {
+            if cycles == 0 {
+                0.0
+            } else {
+                energy / cycles as f64
+            }
+        };
+
+        let mut modules = HashMap::<&str, ModulePower>::new();
+        let mut total = ModulePower {
+            name: "total".to_string(),
+            ..Default::default()
+        };
+        for (idx, gate) in self.nodes.iter().enumerate() {
+            if matches!(gate.ty, GateType::Off | GateType::On | GateType::Lever) {
+                continue;
+            }
+            let toggles = counter.toggles[idx];
+            let energy = table.cost(gate.ty, gate.dependencies.len()).energy * toggles as f64;
+            let power = ModulePower {
+                gates: 1,
+                toggles,
+                energy,
+                power: per_cycle(energy),
+                ..Default::default()
+            };
+            total.add(&power);
+
+            let name = self.module_name(idx);
+            modules
+                .entry(name)
+                .or_insert_with(|| ModulePower {
+                    name: name.to_string(),
+                    ..Default::default()
+                })
+                .add(&power);
+        }
+
+        let mut modules: Vec<ModulePower> = modules.into_values().collect();
+        modules.sort_unstable_by(|a, b| {
+            b.power
+                .partial_cmp(&a.power)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        Some(PowerReport {
+            ticks: self.ticks - counter.start,
+            cycles,
+            total,
+            modules,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{counter, zeros};
+
+    #[test]
+    fn test_toggle_counting() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let l = g.lever("l");
+        let not = g.not1(l.bit(), "not");
+        let and = g.and2(l.bit(), OFF, "and");
+        g.output(&[not, and], "out");
+        let ig = &mut graph.init_unoptimized();
+        assert!(ig.power_report(&CostTable::default()).is_none());
+
+        ig.enable_toggle_counting(None);
+        for _ in 0..3 {
+            ig.pulse_lever_stable(l);
+        }
+        let report = ig.power_report(&CostTable::default()).unwrap();
+        assert_eq!(report.cycles, report.ticks as u64);
+        // The not gate toggles twice per pulse, the and gate never does.
+        assert_eq!(report.total.toggles, 6);
+        assert_eq!(report.total.gates, 2);
+        assert_eq!(report.total.energy, 6. * CostTable::default().not.energy);
+        #[cfg(feature = "debug_gates")]
+        {
+            assert_eq!(report.modules[0].name, "not");
+            assert_eq!(report.module("and").unwrap().toggles, 0);
+            assert_eq!(report.module("not").unwrap().activity(6), 1.0);
+        }
+        assert_eq!(report.to_string().lines().count(), report.modules.len() + 3);
+
+        ig.disable_toggle_counting();
+        ig.pulse_lever_stable(l);
+        assert!(ig.power_report(&CostTable::default()).is_none());
+    }
+
+    #[test]
+    fn test_prefix_and_suffix() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let count = counter(
+            g,
+            clock.bit(),
+            ON,
+            OFF,
+            ON,
+            reset.bit(),
+            &zeros(4),
+            "counter",
+        );
+        let parity = g.xorx(count.iter().copied(), "parity");
+        g.output(&count, "count");
+        g.output1(parity, "parity");
+        let ig = &mut graph.init();
+        ig.pulse_lever_stable(reset);
+
+        ig.enable_toggle_counting(Some(clock));
+        for _ in 0..32 {
+            ig.pulse_lever_stable(clock);
+        }
+        let report = ig.power_report(&CostTable::default()).unwrap();
+        assert_eq!(report.cycles, 32);
+        let sum: f64 = report.modules.iter().map(|module| module.power).sum();
+        assert!((sum - report.total.power).abs() < 1e-9);
+        assert!(report.modules.windows(2).all(|w| w[0].power >= w[1].power));
+        assert_eq!(report.prefix("").toggles, report.total.toggles);
+        #[cfg(feature = "debug_gates")]
+        {
+            let counter = report.suffix("CNTR:counter");
+            assert!(counter.power > 0.);
+            assert_eq!(
+                counter.toggles + report.module("parity").unwrap().toggles,
+                report.total.toggles
+            );
+            assert!(report.prefix("ADDER:").toggles > 0);
+            assert_eq!(report.prefix("nothing").gates, 0);
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use super::gate::*;
+use super::InitializedGateGraph;
+use std::fmt::{self, Display, Formatter};
+
+/// Evaluation counts collected while profiling is enabled, see [InitializedGateGraph::enable_profiling].
+#[derive(Debug, Clone)]
+pub(super) struct Profiler {
+    /// Number of evaluations of every gate.
+    evaluations: Vec<u64>,
+    /// [Simulation time](InitializedGateGraph::now) when profiling was enabled.
+    start: usize,
+}
+impl Profiler {
+    /// Counts an evaluation of the gate with index `idx`.
+    #[inline(always)]
+    pub(super) fn count(&mut self, idx: usize) {
+        self.evaluations[idx] += 1;
+    }
+}
+
+/// Gate of an [ActivityProfile] and the number of times it was evaluated.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Hotspot {
+    /// Full name of the gate, the same one [InitializedGateGraph::dump_dot] uses.
+    pub gate: String,
+    /// Number of times the gate was evaluated.
+    pub evaluations: u64,
+}
+
+/// Report of the gates evaluated by an [InitializedGateGraph] while profiling, see
+/// [InitializedGateGraph::activity_profile].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ActivityProfile {
+    /// Number of [ticks](InitializedGateGraph::tick) profiled.
+    pub ticks: usize,
+    /// Number of evaluations of every gate together.
+    pub evaluations: u64,
+    /// Every gate that was evaluated at least once, the most evaluated first.
+    pub hotspots: Vec<Hotspot>,
+}
+impl ActivityProfile {
+    /// Returns the `n` most evaluated gates, or every gate that was evaluated if there are fewer.
+    pub fn top(&self, n: usize) -> &[Hotspot] {
+        &self.hotspots[..n.min(self.hotspots.len())]
+    }
+}
+impl Display for ActivityProfile {
+    /// Writes a line per hotspot with its rank, evaluations, share of the total and name.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} evaluations in {} ticks",
+            self.evaluations, self.ticks
+        )?;
+        for (rank, hotspot) in self.hotspots.iter().enumerate() {
+            writeln!(
+                f,
+                "{:>6} {:>12} {:>6.2}% {}",
+                rank + 1,
+                hotspot.evaluations,
+                hotspot.evaluations as f64 * 100.0 / self.evaluations as f64,
+                hotspot.gate
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl InitializedGateGraph {
+    /// Starts counting how many times every gate is evaluated, from now until
+    /// [disable_profiling](InitializedGateGraph::disable_profiling) is called,
+    /// see [activity_profile](InitializedGateGraph::activity_profile).
+    ///
+    /// Gates are evaluated when any of their dependencies change, the gates evaluated the most are the ones that
+    /// make the simulation slow, so they are the ones worth restructuring. Calling it again restarts the counts.
+    ///
+    /// Profiling slows down the simulation slightly.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// let mut g = GateGraphBuilder::new();
+    /// let l = g.lever("l");
+    /// let not = g.not1(l.bit(), "not");
+    /// let and = g.and2(l.bit(), not, "and");
+    /// g.output1(and, "and");
+    ///
+    /// let ig = &mut g.init_unoptimized();
+    /// ig.enable_profiling();
+    /// for _ in 0..3 {
+    ///     ig.pulse_lever_stable(l);
+    /// }
+    /// let profile = ig.activity_profile().unwrap();
+    /// # #[cfg(feature = "debug_gates")]
+    /// assert_eq!(profile.top(1)[0].gate, "OUT:And:and");
+    /// println!("{}", profile);
+    /// ```
+    pub fn enable_profiling(&mut self) {
+        self.profiler = Some(Profiler {
+            evaluations: vec![0; self.nodes.len()],
+            start: self.ticks,
+        });
+    }
+
+    /// Stops counting evaluations and discards the counts, see
+    /// [enable_profiling](InitializedGateGraph::enable_profiling).
+    pub fn disable_profiling(&mut self) {
+        self.profiler = None;
+    }
+
+    /// Returns a report of the gates evaluated since profiling was [enabled](InitializedGateGraph::enable_profiling),
+    /// ranked by the number of evaluations, or [None] if profiling isn't enabled.
+    pub fn activity_profile(&self) -> Option<ActivityProfile> {
+        let profiler = self.profiler.as_ref()?;
+        let mut hotspots: Vec<(usize, u64)> = profiler
+            .evaluations
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, evaluations)| *evaluations > 0)
+            .collect();
+        // Ties are broken by gate index so reports are stable.
+        hotspots.sort_by_key(|(idx, evaluations)| (std::cmp::Reverse(*evaluations), *idx));
+        Some(ActivityProfile {
+            ticks: self.ticks - profiler.start,
+            evaluations: profiler.evaluations.iter().sum(),
+            hotspots: hotspots
+                .into_iter()
+                .map(|(idx, evaluations)| Hotspot {
+                    gate: self.full_name(gi!(idx)),
+                    evaluations,
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{counter, zeros};
+
+    #[test]
+    fn test_activity_profile() {
+        let mut g = GateGraphBuilder::new();
+        let clock = g.lever("clock");
+        let out = counter(&mut g, clock.bit(), ON, OFF, ON, OFF, &zeros(4), "counter");
+        g.output(&out, "out");
+        let ig = &mut g.init();
+        assert!(ig.activity_profile().is_none());
+
+        ig.enable_profiling();
+        let profile = ig.activity_profile().unwrap();
+        assert_eq!(profile.evaluations, 0);
+        assert!(profile.hotspots.is_empty());
+
+        for _ in 0..8 {
+            ig.pulse_lever_stable(clock);
+        }
+        let profile = ig.activity_profile().unwrap();
+        assert!(profile.ticks > 0);
+        assert_eq!(
+            profile.evaluations,
+            profile.hotspots.iter().map(|h| h.evaluations).sum::<u64>()
+        );
+        assert!(profile
+            .hotspots
+            .windows(2)
+            .all(|w| w[0].evaluations >= w[1].evaluations));
+        assert_eq!(profile.top(3).len(), 3);
+        assert_eq!(
+            profile.to_string().lines().count(),
+            profile.hotspots.len() + 1
+        );
+
+        // Restarting resets the counts.
+        ig.enable_profiling();
+        assert_eq!(ig.activity_profile().unwrap().evaluations, 0);
+        ig.disable_profiling();
+        ig.pulse_lever_stable(clock);
+        assert!(ig.activity_profile().is_none());
+    }
+}
@@ -0,0 +1,204 @@
+use super::handles::OutputHandle;
+use super::InitializedGateGraph;
+use arc_swap::ArcSwap;
+use std::sync::Arc;
+
+/// Values of every output of an [InitializedGateGraph] at the end of a tick, see
+/// [InitializedGateGraph::publish_outputs].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OutputSnapshot {
+    tick: usize,
+    values: Vec<u128>,
+}
+impl OutputSnapshot {
+    /// Returns the [simulation time](InitializedGateGraph::now) when the values were published.
+    /// Snapshots are only published when an output changes, so this is the tick of the last change.
+    pub fn tick(&self) -> usize {
+        self.tick
+    }
+
+    /// Returns the value of `output` like [OutputHandle::u128].
+    pub fn get(&self, output: OutputHandle) -> u128 {
+        self.values[output.0]
+    }
+
+    /// Returns the state of the 0th bit of `output` like [OutputHandle::b0].
+    pub fn b0(&self, output: OutputHandle) -> bool {
+        self.get(output) & 1 == 1
+    }
+}
+
+/// Reads the latest [OutputSnapshot] published by an [InitializedGateGraph] from any thread,
+/// returned by [InitializedGateGraph::publish_outputs].
+///
+/// Reading never blocks the simulation and the simulation never waits for the readers.
+#[derive(Debug, Clone)]
+pub struct OutputReader(Arc<ArcSwap<OutputSnapshot>>);
+impl OutputReader {
+    /// Returns the latest published [OutputSnapshot], it will not change while you hold it.
+    pub fn latest(&self) -> Arc<OutputSnapshot> {
+        self.0.load_full()
+    }
+}
+
+impl InitializedGateGraph {
+    /// Starts publishing the values of every output at the end of every [tick](InitializedGateGraph::tick)
+    /// in which any of them changed, and returns an [OutputReader] that can be sent to other threads to read them.
+    ///
+    /// This way a UI thread can render the latest state without stopping the simulation thread,
+    /// instead of sharing the whole graph behind a lock.
+    /// Calling it again returns a reader of the same snapshots.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// # use logicsim::{counter,zeros,ON,OFF};
+    /// let mut g = GateGraphBuilder::new();
+    /// let clock = g.lever("clock");
+    /// let reset = g.lever("reset");
+    /// let count = counter(&mut g, clock.bit(), ON, OFF, ON, reset.bit(), &zeros(8), "counter");
+    /// let count = g.output(&count, "count");
+    ///
+    /// let mut ig = g.init();
+    /// ig.pulse_lever_stable(reset);
+    /// let reader = ig.publish_outputs();
+    ///
+    /// let simulation = std::thread::spawn(move || {
+    ///     for _ in 0..10 {
+    ///         ig.pulse_lever_stable(clock);
+    ///     }
+    /// });
+    /// // Meanwhile on the UI thread, without waiting for the simulation.
+    /// assert!(reader.latest().get(count) <= 10);
+    ///
+    /// simulation.join().unwrap();
+    /// assert_eq!(reader.latest().get(count), 10);
+    /// ```
+    pub fn publish_outputs(&mut self) -> OutputReader {
+        if self.published_outputs.is_none() {
+            self.published_outputs = Some(Arc::new(ArcSwap::from_pointee(self.output_snapshot())));
+        }
+        OutputReader(self.published_outputs.clone().unwrap())
+    }
+
+    /// Stops publishing the outputs, readers keep the last [OutputSnapshot] that was published.
+    pub fn stop_publishing_outputs(&mut self) {
+        self.published_outputs = None;
+    }
+
+    /// Returns the current values of every output.
+    fn output_snapshot(&self) -> OutputSnapshot {
+        OutputSnapshot {
+            tick: self.ticks,
+            values: self
+                .output_handles
+                .iter()
+                .map(|output| self.collect_u128_lossy(&output.bits))
+                .collect(),
+        }
+    }
+
+    /// Publishes a new [OutputSnapshot] if any output changed, called at the end of every tick.
+    pub(super) fn publish_output_snapshot(&mut self) {
+        let published = match &self.published_outputs {
+            Some(published) => published,
+            None => return,
+        };
+        let changed = self
+            .output_handles
+            .iter()
+            .zip(&published.load().values)
+            .any(|(output, value)| self.collect_u128_lossy(&output.bits) != *value);
+        if changed {
+            published.store(Arc::new(self.output_snapshot()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{counter, zeros};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_publish_outputs() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let count = counter(
+            g,
+            clock.bit(),
+            ON,
+            OFF,
+            ON,
+            reset.bit(),
+            &zeros(8),
+            "counter",
+        );
+        let count = g.output(&count, "count");
+        let clock_output = g.output1(clock.bit(), "clock");
+        let ig = &mut graph.init();
+        ig.pulse_lever_stable(reset);
+
+        let reader = ig.publish_outputs();
+        let first = reader.latest();
+        assert_eq!(first.get(count), 0);
+        assert_eq!(first.tick(), ig.ticks);
+
+        ig.set_lever_stable(clock);
+        let second = reader.latest();
+        // Snapshots don't change under the reader.
+        assert_eq!(first.get(count), 0);
+        assert_eq!(second.get(count), 1);
+        assert!(second.b0(clock_output));
+        assert!(second.tick() > first.tick());
+
+        // Nothing changed, nothing is published.
+        ig.tick();
+        assert!(Arc::ptr_eq(&second, &reader.latest()));
+
+        ig.stop_publishing_outputs();
+        ig.reset_lever_stable(clock);
+        assert!(reader.latest().b0(clock_output));
+    }
+
+    #[test]
+    fn test_publish_outputs_across_threads() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let count = counter(
+            g,
+            clock.bit(),
+            ON,
+            OFF,
+            ON,
+            reset.bit(),
+            &zeros(8),
+            "counter",
+        );
+        let count = g.output(&count, "count");
+        let mut ig = graph.init();
+        ig.pulse_lever_stable(reset);
+        let reader = ig.publish_outputs();
+
+        let simulation = std::thread::spawn(move || {
+            for _ in 0..200 {
+                ig.pulse_lever_stable(clock);
+            }
+        });
+        let mut last = 0;
+        while !simulation.is_finished() {
+            let value = reader.latest().get(count);
+            // Readers never see the counter go backwards.
+            assert!(value >= last);
+            last = value;
+            std::thread::yield_now();
+        }
+        simulation.join().unwrap();
+        assert_eq!(reader.latest().get(count), 200);
+    }
+}
@@ -0,0 +1,345 @@
+use super::dot::DotConfig;
+use super::handles::{LeverHandle, OutputHandle};
+use super::{InitializedGateGraph, Simulator};
+use indexmap::IndexMap;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+help                    Prints this message.
+outputs                 Prints the value of every output.
+print <output>          Prints the value of an output or test point.
+levers                  Prints the value of every group of levers with the same name.
+set <levers> <value>    Sets the levers named <levers> to the bits of <value>, bit 0 first,
+                        #n is the lever number n. Values can be decimal, 0x hex or 0b binary.
+step [cycles]           Simulates a number of clock cycles, 1 by default, stopping at watchpoints.
+watch <output> <value>  Stops step when <output> has the value <value>.
+unwatch <output>        Removes the watchpoints on <output>.
+watches                 Lists the watchpoints.
+dot <path> <names>...   Dumps the cones of the outputs and probes named <names> in dot format to <path>.
+quit                    Leaves the debugger.
+";
+
+/// Watchpoint set with the "watch" command of [Simulator::debug_repl].
+struct Watchpoint {
+    output: OutputHandle,
+    value: u128,
+}
+
+/// State of a [Simulator::debug_repl] session.
+struct Repl<'a> {
+    simulator: &'a Simulator,
+    watchpoints: Vec<Watchpoint>,
+}
+
+impl Simulator {
+    /// Runs an interactive debugger that reads commands from stdin until it reads "quit" or the input ends,
+    /// to read outputs, set levers, single step the clock, stop at watchpoints and dump parts of the graph.
+    /// "help" lists the commands.
+    ///
+    /// The simulation is [paused](Simulator::pause) while the debugger runs, and resumed afterwards
+    /// if it was running. Outputs and levers are found by name, levers only have names with the "debug_gates"
+    /// feature, without it they can be set by number.
+    ///
+    /// Only available with the "repl" feature.
+    pub fn debug_repl(&self) -> io::Result<()> {
+        let stdin = io::stdin();
+        self.debug_repl_with(stdin.lock(), io::stdout())
+    }
+
+    /// Runs the debugger of [Simulator::debug_repl] reading commands from `input` and writing to `output`,
+    /// to script debugging sessions.
+    ///
+    /// Only available with the "repl" feature.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,Simulator,SimulatorConfig};
+    /// # use logicsim::{counter,zeros,ON,OFF};
+    /// let mut g = GateGraphBuilder::new();
+    /// let clock = g.lever("clock");
+    /// let count = counter(&mut g, clock.bit(), ON, OFF, ON, OFF, &zeros(8), "counter");
+    /// g.output(&count, "count");
+    ///
+    /// let simulator = Simulator::spawn(g.init(), clock, SimulatorConfig::default());
+    /// let script = "watch count 0x10\nstep 1000\nprint count\n";
+    /// let mut output = Vec::new();
+    /// simulator.debug_repl_with(script.as_bytes(), &mut output).unwrap();
+    ///
+    /// let output = String::from_utf8(output).unwrap();
+    /// assert!(output.contains("count = 16 (0x10)"));
+    /// ```
+    pub fn debug_repl_with<R: BufRead, W: Write>(&self, input: R, mut output: W) -> io::Result<()> {
+        let was_paused = self.is_paused();
+        self.pause();
+        let mut repl = Repl {
+            simulator: self,
+            watchpoints: Vec::new(),
+        };
+
+        write!(output, "> ")?;
+        output.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match repl.execute(&words) {
+                Ok(Some(text)) => write!(output, "{}", text)?,
+                Ok(None) => break,
+                Err(e) => writeln!(output, "error: {}", e)?,
+            }
+            write!(output, "> ")?;
+            output.flush()?;
+        }
+        writeln!(output)?;
+
+        if !was_paused {
+            self.resume();
+        }
+        Ok(())
+    }
+}
+
+impl Repl<'_> {
+    /// Executes the command in `words` and returns what it printed, [None] if it was "quit".
+    fn execute(&mut self, words: &[&str]) -> Result<Option<String>, String> {
+        let mut text = String::new();
+        match words {
+            [] => {}
+            ["help"] => text.push_str(HELP),
+            ["quit"] => return Ok(None),
+            ["outputs"] => self.simulator.with(|ig| {
+                for output in (0..ig.output_handles.len()).map(OutputHandle) {
+                    print_output(&mut text, ig, output);
+                }
+            }),
+            ["print", name] => self.simulator.with(|ig| {
+                let output = find_output(ig, name)?;
+                print_output(&mut text, ig, output);
+                Ok::<_, String>(())
+            })?,
+            ["levers"] => self.simulator.with(|ig| {
+                for (name, levers) in lever_groups(ig) {
+                    let value = levers.iter().rev().fold(0u128, |value, lever| {
+                        value << 1 | ig.value(lever.bit()) as u128
+                    });
+                    writeln!(text, "{} = {} ({} levers)", name, value, levers.len()).unwrap();
+                }
+            }),
+            ["set", name, value] => {
+                let value = parse_value(value)?;
+                let max_ticks = self.simulator.max_ticks;
+                self.simulator.with(|ig| {
+                    let levers = find_levers(ig, name)?;
+                    ig.update_levers(
+                        &levers,
+                        (0..levers.len()).map(|i| i < 128 && value >> i & 1 == 1),
+                    );
+                    ig.run_until_stable(max_ticks).map_err(|e| e.to_string())?;
+                    Ok::<_, String>(())
+                })?
+            }
+            ["step"] => self.step(&mut text, 1),
+            ["step", cycles] => {
+                let cycles = cycles
+                    .parse()
+                    .map_err(|_| format!("invalid number of cycles: {}", cycles))?;
+                self.step(&mut text, cycles)
+            }
+            ["watch", name, value] => {
+                let value = parse_value(value)?;
+                let output = self.simulator.with(|ig| find_output(ig, name))?;
+                self.watchpoints.push(Watchpoint { output, value });
+            }
+            ["unwatch", name] => {
+                let output = self.simulator.with(|ig| find_output(ig, name))?;
+                self.watchpoints
+                    .retain(|watchpoint| watchpoint.output != output);
+            }
+            ["watches"] => self.simulator.with(|ig| {
+                for watchpoint in &self.watchpoints {
+                    writeln!(
+                        text,
+                        "{} == {}",
+                        watchpoint.output.name(ig),
+                        watchpoint.value
+                    )
+                    .unwrap();
+                }
+            }),
+            ["dot", path, names @ ..] if !names.is_empty() => {
+                let config = DotConfig::cone(names.iter().copied());
+                self.simulator
+                    .with(|ig| ig.dump_dot_with_config(path, &config))
+                    .map_err(|e| e.to_string())?;
+                writeln!(text, "wrote {}", path).unwrap();
+            }
+            _ => return Err(format!("unknown command: {}, try help", words.join(" "))),
+        }
+        Ok(Some(text))
+    }
+
+    /// Simulates up to `cycles` clock cycles, stopping after the first one in which a watchpoint matches.
+    fn step(&self, text: &mut String, cycles: u64) {
+        for _ in 0..cycles {
+            self.simulator.step();
+            let hit = self.simulator.with(|ig| {
+                self.watchpoints
+                    .iter()
+                    .find(|watchpoint| watchpoint.output.u128(ig) == watchpoint.value)
+                    .map(|watchpoint| watchpoint.output.name(ig).to_string())
+            });
+            if let Some(name) = hit {
+                writeln!(text, "watchpoint on {} hit", name).unwrap();
+                break;
+            }
+        }
+        writeln!(text, "cycle {}", self.simulator.cycles()).unwrap();
+    }
+}
+
+/// Returns the output or test point named `name`.
+fn find_output(ig: &InitializedGateGraph, name: &str) -> Result<OutputHandle, String> {
+    (0..ig.output_handles.len())
+        .map(OutputHandle)
+        .find(|output| output.name(ig) == name)
+        .or_else(|| ig.test_point(name))
+        .ok_or_else(|| format!("no output named {}", name))
+}
+
+/// Returns the levers named `name` in the order they were created, or the lever number n for "#n".
+fn find_levers(ig: &InitializedGateGraph, name: &str) -> Result<Vec<LeverHandle>, String> {
+    if let Some(number) = name.strip_prefix('#') {
+        return number
+            .parse::<usize>()
+            .ok()
+            .filter(|handle| *handle < ig.lever_handles.len())
+            .map(|handle| {
+                vec![LeverHandle {
+                    handle,
+                    idx: ig.lever_handles[handle],
+                }]
+            })
+            .ok_or_else(|| format!("no lever number {}", number));
+    }
+    lever_groups(ig)
+        .swap_remove(name)
+        .ok_or_else(|| format!("no levers named {}", name))
+}
+
+/// Returns every lever grouped by name, "#n" for the levers without one.
+fn lever_groups(ig: &InitializedGateGraph) -> IndexMap<String, Vec<LeverHandle>> {
+    let mut groups: IndexMap<String, Vec<LeverHandle>> = IndexMap::new();
+    for (handle, idx) in ig.lever_handles.iter().enumerate() {
+        let name = match ig.gate_name(*idx) {
+            Some(name) => name.to_string(),
+            None => format!("#{}", handle),
+        };
+        groups
+            .entry(name)
+            .or_default()
+            .push(LeverHandle { handle, idx: *idx });
+    }
+    groups
+}
+
+fn print_output(text: &mut String, ig: &InitializedGateGraph, output: OutputHandle) {
+    let value = output.u128(ig);
+    writeln!(text, "{} = {} ({:#x})", output.name(ig), value, value).unwrap();
+}
+
+/// Parses a decimal, 0x hexadecimal or 0b binary number.
+fn parse_value(value: &str) -> Result<u128, String> {
+    let parsed = if let Some(hex) = value.strip_prefix("0x") {
+        u128::from_str_radix(hex, 16)
+    } else if let Some(binary) = value.strip_prefix("0b") {
+        u128::from_str_radix(binary, 2)
+    } else {
+        value.parse()
+    };
+    parsed.map_err(|_| format!("invalid value: {}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{counter, zeros, WordInput};
+
+    fn run(simulator: &Simulator, script: &str) -> String {
+        let mut output = Vec::new();
+        simulator
+            .debug_repl_with(script.as_bytes(), &mut output)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    #[cfg(feature = "debug_gates")]
+    fn test_debug_repl() {
+        let mut g = GateGraphBuilder::new();
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let enable = g.lever("enable");
+        let count = counter(
+            &mut g,
+            clock.bit(),
+            enable.bit(),
+            OFF,
+            ON,
+            reset.bit(),
+            &zeros(8),
+            "counter",
+        );
+        let input = WordInput::new(&mut g, 4, "input");
+        g.output(&count, "count");
+        g.output(&input.bits(), "input");
+
+        let simulator = Simulator::spawn(g.init(), clock, SimulatorConfig::default());
+        let output = run(
+            &simulator,
+            "set reset 1\nset reset 0\nstep 3\nprint count\nset #2 1\nstep 2\nprint count\n\
+             watch count 0x10\nwatches\nstep 100\nunwatch count\nstep\nprint count\n",
+        );
+        assert!(output.contains("count = 0 (0x0)"), "{}", output);
+        assert!(output.contains("count = 2 (0x2)"), "{}", output);
+        assert!(output.contains("count == 16"), "{}", output);
+        assert!(output.contains("watchpoint on count hit"), "{}", output);
+        assert!(output.contains("count = 17 (0x11)"), "{}", output);
+        // The simulation was running, so it gets resumed.
+        assert!(!simulator.is_paused());
+
+        simulator.pause();
+        let output = run(
+            &simulator,
+            "set WI:input 0b1010\nprint input\nquit\nprint input\n",
+        );
+        assert_eq!(output.matches("input = 10 (0xa)").count(), 1, "{}", output);
+        assert!(simulator.is_paused());
+
+        let path = std::env::temp_dir().join("logicsim_debug_repl_test.dot");
+        let output = run(
+            &simulator,
+            &format!("levers\ndot {} count\n", path.display()),
+        );
+        assert!(output.contains("WI:input = 10 (4 levers)"), "{}", output);
+        assert!(output.contains("enable = 1 (1 levers)"), "{}", output);
+        assert!(std::fs::read_to_string(&path).unwrap().contains("digraph"));
+        std::fs::remove_file(&path).unwrap();
+
+        let output = run(&simulator, "print nothing\nset reset banana\nfrobnicate\n");
+        assert!(
+            output.contains("error: no output named nothing"),
+            "{}",
+            output
+        );
+        assert!(
+            output.contains("error: invalid value: banana"),
+            "{}",
+            output
+        );
+        assert!(
+            output.contains("error: unknown command: frobnicate"),
+            "{}",
+            output
+        );
+    }
+}
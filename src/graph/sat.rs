@@ -0,0 +1,531 @@
+use super::cnf::Cnf;
+use super::handles::*;
+use super::InitializedGateGraph;
+
+/// Literal of the [Solver], `2 * variable` for the positive one and `2 * variable + 1` for the negated one.
+type Literal = usize;
+
+/// Returns the [Literal] of a DIMACS literal.
+fn literal(dimacs: i64) -> Literal {
+    (dimacs.unsigned_abs() as usize) << 1 | (dimacs < 0) as usize
+}
+
+/// Reason a clause can't be satisfied, the solver backtracks until it isn't.
+type Conflict = usize;
+
+/// Conflicts before the first restart, the interval grows by half every restart.
+const FIRST_RESTART: usize = 100;
+
+/// Small [CDCL](https://en.wikipedia.org/wiki/Conflict-driven_clause_learning) SAT solver with two watched
+/// literals, first UIP clause learning and activity based decisions, good enough for the formulas of the circuits
+/// people simulate.
+struct Solver {
+    clauses: Vec<Vec<Literal>>,
+    /// Clauses watching every literal, the first two literals of a clause are the watched ones.
+    watches: Vec<Vec<usize>>,
+    /// Value of every variable, [None] if it's unassigned.
+    values: Vec<Option<bool>>,
+    /// Decision level every variable was assigned at.
+    levels: Vec<usize>,
+    /// Clause that implied the value of every variable, [None] for decisions.
+    reasons: Vec<Option<usize>>,
+    /// Last value of every variable, decisions reuse it.
+    phases: Vec<bool>,
+    activity: Vec<f64>,
+    bump: f64,
+    /// Assigned literals in assignment order.
+    trail: Vec<Literal>,
+    /// Length of the trail before every decision.
+    decisions: Vec<usize>,
+    /// Next literal of the trail to propagate.
+    head: usize,
+    seen: Vec<bool>,
+    /// False if a contradiction was found while adding clauses.
+    consistent: bool,
+}
+impl Solver {
+    /// Returns a [Solver] for a formula with `variables` variables numbered from 1.
+    fn new(variables: usize) -> Self {
+        let n = variables + 1;
+        Self {
+            clauses: Vec::new(),
+            watches: vec![Vec::new(); n * 2],
+            values: vec![None; n],
+            levels: vec![0; n],
+            reasons: vec![None; n],
+            phases: vec![false; n],
+            activity: vec![0.0; n],
+            bump: 1.0,
+            trail: Vec::new(),
+            decisions: Vec::new(),
+            head: 0,
+            seen: vec![false; n],
+            consistent: true,
+        }
+    }
+
+    /// Returns the value of `literal`, [None] if its variable is unassigned.
+    fn value(&self, literal: Literal) -> Option<bool> {
+        self.values[literal >> 1].map(|value| value != (literal & 1 == 1))
+    }
+
+    /// Assigns true to `literal`, implied by the clause `reason`.
+    fn assign(&mut self, literal: Literal, reason: Option<usize>) {
+        let variable = literal >> 1;
+        self.values[variable] = Some(literal & 1 == 0);
+        self.levels[variable] = self.decisions.len();
+        self.reasons[variable] = reason;
+        self.trail.push(literal);
+    }
+
+    /// Adds a clause of DIMACS literals, must be called before [solve](Solver::solve).
+    fn add_clause(&mut self, clause: &[i64]) {
+        let mut clause: Vec<Literal> = clause.iter().map(|l| literal(*l)).collect();
+        clause.sort_unstable();
+        clause.dedup();
+        // Contains a literal and its negation.
+        if clause.windows(2).any(|w| w[0] ^ 1 == w[1]) {
+            return;
+        }
+        match clause.len() {
+            0 => self.consistent = false,
+            1 => match self.value(clause[0]) {
+                Some(true) => {}
+                Some(false) => self.consistent = false,
+                None => self.assign(clause[0], None),
+            },
+            _ => {
+                self.watch(self.clauses.len(), &clause);
+                self.clauses.push(clause);
+            }
+        }
+    }
+
+    /// Makes the clause `index` watch its first two literals.
+    fn watch(&mut self, index: usize, clause: &[Literal]) {
+        self.watches[clause[0]].push(index);
+        self.watches[clause[1]].push(index);
+    }
+
+    /// Propagates the assignments in the trail, returns the clause that became false if any.
+    fn propagate(&mut self) -> Option<Conflict> {
+        while self.head < self.trail.len() {
+            let falsified = self.trail[self.head] ^ 1;
+            self.head += 1;
+            let mut watchers = std::mem::take(&mut self.watches[falsified]);
+            let mut i = 0;
+            while i < watchers.len() {
+                let index = watchers[i];
+                let clause = &mut self.clauses[index];
+                if clause[0] == falsified {
+                    clause.swap(0, 1);
+                }
+                let first = clause[0];
+                if self.values[first >> 1].map(|value| value != (first & 1 == 1)) == Some(true) {
+                    i += 1;
+                    continue;
+                }
+                // Looks for another literal to watch that isn't false.
+                let values = &self.values;
+                let replacement = (2..clause.len()).find(|k| {
+                    let l = clause[*k];
+                    values[l >> 1].map(|value| value != (l & 1 == 1)) != Some(false)
+                });
+                if let Some(k) = replacement {
+                    clause.swap(1, k);
+                    let watched = clause[1];
+                    self.watches[watched].push(index);
+                    watchers.swap_remove(i);
+                    continue;
+                }
+                if self.value(first) == Some(false) {
+                    self.watches[falsified] = watchers;
+                    return Some(index);
+                }
+                self.assign(first, Some(index));
+                i += 1;
+            }
+            self.watches[falsified] = watchers;
+        }
+        None
+    }
+
+    /// Returns the first UIP clause learnt from `conflict` and the level to backtrack to.
+    fn analyze(&mut self, conflict: Conflict) -> (Vec<Literal>, usize) {
+        let level = self.decisions.len();
+        // The asserting literal goes first, it's filled in at the end.
+        let mut learnt = vec![0];
+        let mut pending = 0;
+        let mut index = self.trail.len();
+        let mut clause = conflict;
+        let mut implied = None;
+        loop {
+            for k in 0..self.clauses[clause].len() {
+                let l = self.clauses[clause][k];
+                let variable = l >> 1;
+                if Some(l) == implied || self.seen[variable] || self.levels[variable] == 0 {
+                    continue;
+                }
+                self.seen[variable] = true;
+                self.bump_activity(variable);
+                if self.levels[variable] == level {
+                    pending += 1;
+                } else {
+                    learnt.push(l);
+                }
+            }
+            // The most recent assignment involved in the conflict.
+            loop {
+                index -= 1;
+                if self.seen[self.trail[index] >> 1] {
+                    break;
+                }
+            }
+            let l = self.trail[index];
+            self.seen[l >> 1] = false;
+            pending -= 1;
+            if pending == 0 {
+                learnt[0] = l ^ 1;
+                break;
+            }
+            implied = Some(l);
+            clause = self.reasons[l >> 1].unwrap();
+        }
+        for l in &learnt[1..] {
+            self.seen[l >> 1] = false;
+        }
+        // The literal of the highest level is watched along with the asserting one.
+        let mut back_level = 0;
+        for k in 1..learnt.len() {
+            let l = self.levels[learnt[k] >> 1];
+            if l > back_level {
+                back_level = l;
+                learnt.swap(1, k);
+            }
+        }
+        (learnt, back_level)
+    }
+
+    fn bump_activity(&mut self, variable: usize) {
+        self.activity[variable] += self.bump;
+        if self.activity[variable] > 1e100 {
+            for activity in &mut self.activity {
+                *activity *= 1e-100;
+            }
+            self.bump *= 1e-100;
+        }
+    }
+
+    /// Unassigns every variable assigned after decision level `level`.
+    fn backtrack(&mut self, level: usize) {
+        if self.decisions.len() <= level {
+            return;
+        }
+        for l in self.trail.drain(self.decisions[level]..) {
+            self.phases[l >> 1] = l & 1 == 0;
+            self.values[l >> 1] = None;
+        }
+        self.decisions.truncate(level);
+        self.head = self.trail.len();
+    }
+
+    /// Returns the value of every variable, index 0 is unused, or [None] if the formula can't be satisfied.
+    fn solve(mut self) -> Option<Vec<bool>> {
+        if !self.consistent {
+            return None;
+        }
+        let mut conflicts = 0;
+        let mut restart = FIRST_RESTART;
+        loop {
+            if let Some(conflict) = self.propagate() {
+                if self.decisions.is_empty() {
+                    return None;
+                }
+                let (learnt, back_level) = self.analyze(conflict);
+                self.backtrack(back_level);
+                if learnt.len() == 1 {
+                    self.assign(learnt[0], None);
+                } else {
+                    let index = self.clauses.len();
+                    self.watch(index, &learnt);
+                    self.assign(learnt[0], Some(index));
+                    self.clauses.push(learnt);
+                }
+                self.bump /= 0.95;
+                conflicts += 1;
+                if conflicts == restart {
+                    conflicts = 0;
+                    restart += restart / 2;
+                    self.backtrack(0);
+                }
+                continue;
+            }
+            let decision = (1..self.values.len())
+                .filter(|v| self.values[*v].is_none())
+                .max_by(|a, b| self.activity[*a].total_cmp(&self.activity[*b]));
+            match decision {
+                Some(variable) => {
+                    self.decisions.push(self.trail.len());
+                    let l = variable << 1 | !self.phases[variable] as usize;
+                    self.assign(l, None);
+                }
+                None => return Some(self.values.iter().map(|v| v == &Some(true)).collect()),
+            }
+        }
+    }
+}
+
+impl InitializedGateGraph {
+    /// Returns true if `a` and `b` have the same value for every value of the levers, proving it with a SAT solver,
+    /// or the values of the levers for which they differ. Available with the "sat" feature.
+    ///
+    /// Simulation can only show that two circuits agree on the inputs it tries, 2^64 combinations for a 32 bit
+    /// comparator are out of reach, while the formula of the circuits usually takes a fraction of a second to solve.
+    /// The fanin cones of both outputs are encoded like in [export_cnf](InitializedGateGraph::export_cnf), along
+    /// with a clause that requires a bit to differ, then solved by a small built in
+    /// [CDCL](https://en.wikipedia.org/wiki/Conflict-driven_clause_learning) solver.
+    ///
+    /// The returned levers are the ones in the cones, in creation order, the rest don't affect the outputs.
+    /// Loops are encoded as constraints, so for circuits with state this compares the stable states, two latches
+    /// that can hold either value will be found to differ.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::{GateGraphBuilder,WordInput,adder,OFF};
+    /// let mut g = GateGraphBuilder::new();
+    /// let a = WordInput::new(&mut g, 32, "a");
+    /// let b = WordInput::new(&mut g, 32, "b");
+    /// let a_plus_b = adder(&mut g, OFF, &a.bits(), &b.bits(), "a+b");
+    /// let b_plus_a = adder(&mut g, OFF, &b.bits(), &a.bits(), "b+a");
+    /// let a_plus_b = g.output(&a_plus_b, "a+b");
+    /// let b_plus_a = g.output(&b_plus_a, "b+a");
+    /// let a_only = g.output(&a.bits(), "a");
+    ///
+    /// let ig = &mut g.init();
+    /// assert_eq!(ig.prove_equal(a_plus_b, b_plus_a), Ok(()));
+    ///
+    /// let counterexample = ig.prove_equal(a_plus_b, a_only).unwrap_err();
+    /// for (lever, value) in counterexample {
+    ///     ig.update_lever(lever, value);
+    /// }
+    /// ig.run_until_stable(100).unwrap();
+    /// assert_ne!(a_plus_b.u32(ig), a_only.u32(ig));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `a` and `b` have different widths.
+    pub fn prove_equal(
+        &self,
+        a: OutputHandle,
+        b: OutputHandle,
+    ) -> Result<(), Vec<(LeverHandle, bool)>> {
+        let a = &self.get_output(a).bits;
+        let b = &self.get_output(b).bits;
+        assert_eq!(a.len(), b.len(), "The outputs must have the same width");
+        let mut cnf = Cnf::default();
+        self.encode_cnf(&mut cnf, a);
+        self.encode_cnf(&mut cnf, b);
+        // A miter, at least one pair of bits differs.
+        let mut differences = Vec::with_capacity(a.len());
+        for (bit_a, bit_b) in a.iter().zip(b.iter()) {
+            let (bit_a, _) = cnf.variable(*bit_a);
+            let (bit_b, _) = cnf.variable(*bit_b);
+            let difference = cnf.auxiliary();
+            cnf.xor(difference, bit_a, bit_b);
+            differences.push(difference);
+        }
+        cnf.clauses.push(differences);
+        match self.solve_cnf(&cnf) {
+            Some(levers) => Err(levers),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns values of the levers for which every bit of `output` is on, found with a SAT solver, or [None] if
+    /// there aren't any. Available with the "sat" feature.
+    ///
+    /// The returned levers are the ones in the fanin cone of `output`, in creation order, see
+    /// [prove_equal](InitializedGateGraph::prove_equal) for how the circuit is encoded.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::{GateGraphBuilder,WordInput,matches_constant};
+    /// let mut g = GateGraphBuilder::new();
+    /// let input = WordInput::new(&mut g, 16, "input");
+    /// let is_magic = matches_constant(&mut g, &input.bits(), 0xBEEFu16, "is_magic");
+    /// let output = g.output1(is_magic, "is_magic");
+    ///
+    /// let ig = &mut g.init();
+    /// let levers = ig.find_input_satisfying(output).unwrap();
+    /// assert_eq!(levers.len(), 16);
+    /// let value = levers
+    ///     .iter()
+    ///     .enumerate()
+    ///     .fold(0, |acc, (bit, (_, value))| acc | (*value as u16) << bit);
+    /// assert_eq!(value, 0xBEEF);
+    /// ```
+    pub fn find_input_satisfying(&self, output: OutputHandle) -> Option<Vec<(LeverHandle, bool)>> {
+        let bits = &self.get_output(output).bits;
+        let mut cnf = Cnf::default();
+        self.encode_cnf(&mut cnf, bits);
+        for bit in bits.iter() {
+            let (variable, _) = cnf.variable(*bit);
+            cnf.clauses.push(vec![variable]);
+        }
+        self.solve_cnf(&cnf)
+    }
+
+    /// Solves `cnf` and returns the value of every lever in it, or [None] if it can't be satisfied.
+    fn solve_cnf(&self, cnf: &Cnf) -> Option<Vec<(LeverHandle, bool)>> {
+        let mut solver = Solver::new(cnf.next_variable as usize);
+        for clause in &cnf.clauses {
+            solver.add_clause(clause);
+        }
+        let values = solver.solve()?;
+        Some(
+            self.cnf_levers(cnf)
+                .into_iter()
+                .map(|(lever, variable)| (lever, values[variable as usize]))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::Solver;
+    use crate::circuits::{adder, WordInput};
+
+    /// Returns true if `values` satisfies every clause.
+    fn satisfies(clauses: &[Vec<i64>], values: &[bool]) -> bool {
+        clauses.iter().all(|clause| {
+            clause
+                .iter()
+                .any(|l| values[l.unsigned_abs() as usize] == (*l > 0))
+        })
+    }
+
+    #[test]
+    fn test_solver_pigeonhole_and_random_formulas() {
+        // 5 pigeons in 4 holes, variable 4 * pigeon + hole + 1.
+        let mut solver = Solver::new(20);
+        for pigeon in 0..5 {
+            solver.add_clause(&(0..4).map(|hole| 4 * pigeon + hole + 1).collect::<Vec<_>>());
+        }
+        for hole in 0..4 {
+            for a in 0..5 {
+                for b in a + 1..5 {
+                    solver.add_clause(&[-(4 * a + hole + 1), -(4 * b + hole + 1)]);
+                }
+            }
+        }
+        assert_eq!(solver.solve(), None);
+
+        // Random 3-SAT around the threshold, checked against brute force.
+        let mut seed = 0x2545F4914F6CDD1Du64;
+        let mut random = move |n: u64| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed % n
+        };
+        for _ in 0..200 {
+            let variables = 12;
+            let clauses: Vec<Vec<i64>> = (0..51)
+                .map(|_| {
+                    (0..3)
+                        .map(|_| {
+                            let variable = random(variables) as i64 + 1;
+                            if random(2) == 0 {
+                                variable
+                            } else {
+                                -variable
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            let mut solver = Solver::new(variables as usize);
+            for clause in &clauses {
+                solver.add_clause(clause);
+            }
+            let brute_force = (0..1u32 << variables).any(|assignment| {
+                let values: Vec<bool> = (0..=variables)
+                    .map(|v| v > 0 && assignment >> (v - 1) & 1 == 1)
+                    .collect();
+                satisfies(&clauses, &values)
+            });
+            match solver.solve() {
+                Some(values) => assert!(satisfies(&clauses, &values)),
+                None => assert!(!brute_force),
+            }
+        }
+    }
+
+    #[test]
+    fn test_prove_comparators_equal() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let a = WordInput::new(g, 32, "a");
+        let b = WordInput::new(g, 32, "b");
+        // a < b as the sign of a - b, with 33 bits so it can't overflow.
+        let mut wide_a = a.bits();
+        wide_a.push(OFF);
+        let mut not_b: Vec<_> = b.bits().iter().map(|bit| g.not1(*bit, "not_b")).collect();
+        not_b.push(ON);
+        let difference = adder(g, ON, &wide_a, &not_b, "difference");
+        let borrow = difference[32];
+        // a < b bit by bit, the most significant difference decides.
+        let mut less = OFF;
+        for (bit_a, bit_b) in a.bits().iter().zip(b.bits().iter()) {
+            let not_a = g.not1(*bit_a, "not_a");
+            let lower = g.and2(not_a, *bit_b, "lower");
+            let same = g.xnor2(*bit_a, *bit_b, "same");
+            let keep = g.and2(same, less, "keep");
+            less = g.or2(lower, keep, "less");
+        }
+        let both_odd = g.and2(a.bits()[0], b.bits()[0], "both_odd");
+        let less_equal = g.or2(less, both_odd, "less_equal");
+        let borrow = g.output1(borrow, "borrow");
+        let less = g.output1(less, "less");
+        let less_equal = g.output1(less_equal, "less_equal");
+        let ig = &mut graph.init();
+
+        assert_eq!(ig.prove_equal(borrow, less), Ok(()));
+        assert_eq!(ig.prove_equal(less, borrow), Ok(()));
+
+        let counterexample = ig.prove_equal(less, less_equal).unwrap_err();
+        assert_eq!(counterexample.len(), 64);
+        for (lever, value) in counterexample {
+            ig.update_lever(lever, value);
+        }
+        ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        assert_ne!(less.b0(ig), less_equal.b0(ig));
+    }
+
+    #[test]
+    fn test_find_input_satisfying() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let a = WordInput::new(g, 8, "a");
+        let b = WordInput::new(g, 8, "b");
+        let sum = adder(g, OFF, &a.bits(), &b.bits(), "adder");
+        let sum_is_200 = crate::circuits::matches_constant(g, &sum[..8], 200u8, "is_200");
+        let both = g.and2(sum_is_200, a.bits()[7], "both");
+        let both = g.output1(both, "both");
+        let not = g.not1(a.bits()[0], "not");
+        let never = g.and2(a.bits()[0], not, "never");
+        let never = g.output1(never, "never");
+        let ig = &mut graph.init_unoptimized();
+
+        let levers = ig.find_input_satisfying(both).unwrap();
+        for (lever, value) in levers {
+            ig.update_lever(lever, value);
+        }
+        ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        assert!(both.b0(ig));
+        assert_eq!(ig.find_input_satisfying(never), None);
+    }
+}
@@ -0,0 +1,121 @@
+use super::gate::GateIndex;
+use super::InitializedGateGraph;
+use std::fmt::{self, Display, Formatter};
+
+/// Maximum number of gate names [SimulationError] prints, the rest are summarized.
+const DISPLAYED_NAMES: usize = 10;
+
+/// Error returned when a simulation can't do what it was asked to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SimulationError {
+    /// The graph was still changing after `ticks` ticks, see [InitializedGateGraph::run_until_stable].
+    NotStabilized {
+        /// Number of ticks it ran for.
+        ticks: usize,
+        /// Gates that changed state during the last tick, sorted, usually part of or fed by a loop that keeps
+        /// oscillating, see [InitializedGateGraph::find_combinational_loops].
+        oscillating_gates: Vec<GateIndex>,
+        /// Full names of `oscillating_gates`, the same ones [InitializedGateGraph::dump_dot] uses.
+        names: Vec<String>,
+    },
+    /// The graph is idle, nothing will change until a lever is updated, see [InitializedGateGraph::try_tick].
+    Idle,
+    /// The circuit has more than `max_states` states, see [InitializedGateGraph::extract_state_machine].
+    TooManyStates {
+        /// Maximum number of states that was requested.
+        max_states: usize,
+    },
+    /// An assertion declared with [GateGraphBuilder::assert_always](super::GateGraphBuilder::assert_always) or
+    /// [GateGraphBuilder::assert_never](super::GateGraphBuilder::assert_never) failed,
+    /// see [InitializedGateGraph::arm_assertions].
+    AssertionFailed {
+        /// Message of the assertion.
+        message: String,
+        /// [Simulation time](InitializedGateGraph::now) of the tick at the end of which it failed.
+        tick: usize,
+    },
+}
+impl SimulationError {
+    /// Returns a [SimulationError::NotStabilized] with the names of `oscillating_gates` in `g`.
+    pub(super) fn not_stabilized(
+        g: &InitializedGateGraph,
+        ticks: usize,
+        oscillating_gates: Vec<GateIndex>,
+    ) -> Self {
+        let names = oscillating_gates
+            .iter()
+            .map(|gate| g.full_name(*gate))
+            .collect();
+        SimulationError::NotStabilized {
+            ticks,
+            oscillating_gates,
+            names,
+        }
+    }
+}
+impl Display for SimulationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SimulationError::NotStabilized { ticks, names, .. } => {
+                write!(f, "Your graph didn't stabilize after {} ticks", ticks)?;
+                if !names.is_empty() {
+                    let shown = names.len().min(DISPLAYED_NAMES);
+                    write!(f, ", oscillating gates: {}", names[..shown].join(", "))?;
+                    if names.len() > shown {
+                        write!(f, " and {} more", names.len() - shown)?;
+                    }
+                }
+                Ok(())
+            }
+            SimulationError::Idle => write!(
+                f,
+                "The graph is idle, nothing will change until a lever is updated"
+            ),
+            SimulationError::TooManyStates { max_states } => {
+                write!(f, "The state machine has more than {} states", max_states)
+            }
+            SimulationError::AssertionFailed { message, .. } => {
+                write!(f, "Assertion failed: {}", message)
+            }
+        }
+    }
+}
+impl std::error::Error for SimulationError {}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+
+    #[test]
+    fn test_not_stabilized() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let enable = g.lever("enable");
+        let ring = g.nand1(enable.bit(), "ring");
+        g.dpush(ring, ring);
+        g.output1(ring, "ring");
+        let stable = g.lever("stable");
+        g.output1(stable.bit(), "stable");
+        let ig = &mut graph.init();
+
+        ig.update_lever(stable, true);
+        ig.update_lever(enable, true);
+        let error = ig.run_until_stable(10).unwrap_err();
+        match &error {
+            SimulationError::NotStabilized {
+                ticks,
+                oscillating_gates,
+                names,
+            } => {
+                assert_eq!(*ticks, 10);
+                assert_eq!(oscillating_gates.len(), 1);
+                assert!(ig.nodes[oscillating_gates[0].idx].ty.is_negated());
+                #[cfg(feature = "debug_gates")]
+                assert!(names[0].contains("ring"));
+                assert_eq!(names.len(), 1);
+            }
+            _ => panic!("Unexpected error {:?}", error),
+        }
+        assert!(error.to_string().contains("oscillating gates"));
+    }
+}
@@ -0,0 +1,515 @@
+use super::handles::{LeverHandle, OutputHandle};
+use super::peripherals::{HostPeripheral, PeripheralHandle};
+use super::{InitializedGateGraph, DEFAULT_STABLE_MAX};
+#[cfg(feature = "serde")]
+use std::io;
+#[cfg(feature = "serde")]
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Configuration of a [Simulator].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimulatorConfig {
+    /// Target number of clock cycles per second, [None] runs the simulation as fast as possible.
+    pub frequency: Option<f64>,
+    /// Maximum number of ticks the circuit gets to stabilize after every clock edge,
+    /// see [InitializedGateGraph::run_until_stable].
+    pub max_ticks: usize,
+    /// Starts the simulation [paused](Simulator::pause).
+    pub paused: bool,
+}
+impl Default for SimulatorConfig {
+    fn default() -> Self {
+        Self {
+            frequency: None,
+            max_ticks: DEFAULT_STABLE_MAX,
+            paused: false,
+        }
+    }
+}
+
+/// Hook registered with [Simulator::on_cycle].
+type CycleHook = Box<dyn FnMut(&mut InitializedGateGraph, u64) -> bool + Send>;
+
+/// State shared by a [Simulator] and its simulation thread.
+#[derive(Default)]
+struct Control {
+    stop: AtomicBool,
+    /// Only changed with the graph locked, so that no cycle starts after [Simulator::pause] returns.
+    paused: AtomicBool,
+    cycles: AtomicU64,
+    hooks: Mutex<Vec<CycleHook>>,
+}
+
+/// Runs an [InitializedGateGraph] on a background thread, flipping its clock at a target rate,
+/// so that frontends like GUIs can drive the circuit from their own event loop.
+///
+/// Every method takes `&self` and is safe to call from any thread, wrap the [Simulator] in an [Arc] to share it.
+/// The graph is behind a lock which the simulation thread takes for every clock edge, so the values read through
+/// the [Simulator] are always stable. To read outputs without waiting for the lock, call
+/// [InitializedGateGraph::publish_outputs] before spawning the [Simulator].
+///
+/// Long running simulations can be [paused](Simulator::pause), [single stepped](Simulator::step),
+/// observed every cycle with [hooks](Simulator::on_cycle), connected to IO devices with
+/// [add_peripheral](Simulator::add_peripheral) and, with the "serde" feature, checkpointed to disk
+/// with `checkpoint`.
+///
+/// Dropping the [Simulator] stops the simulation thread, [Simulator::stop] also gives the graph back.
+///
+/// # Panics
+///
+/// If the circuit doesn't stabilize within [SimulatorConfig::max_ticks] after a clock edge the simulation thread
+/// panics, and so will every method of the [Simulator] afterwards.
+///
+/// # Example
+/// ```
+/// # use logicsim::graph::{GateGraphBuilder,Simulator,SimulatorConfig};
+/// # use logicsim::{counter,zeros,ON,OFF};
+/// let mut g = GateGraphBuilder::new();
+/// let clock = g.lever("clock");
+/// let reset = g.lever("reset");
+/// let count = counter(&mut g, clock.bit(), ON, OFF, ON, reset.bit(), &zeros(8), "counter");
+/// let output = g.output(&count, "count");
+///
+/// let simulator = Simulator::spawn(g.init(), clock, SimulatorConfig::default());
+/// simulator.update_lever(reset, true);
+/// simulator.update_lever(reset, false);
+/// let start = simulator.cycles();
+/// while simulator.cycles() < start + 10 {
+///     std::thread::yield_now();
+/// }
+/// assert!(simulator.u128(output) > 0);
+///
+/// let ig = &mut simulator.stop();
+/// let value = output.u8(ig);
+/// ig.pulse_lever_stable(clock);
+/// assert_eq!(output.u8(ig), value.wrapping_add(1));
+/// ```
+pub struct Simulator {
+    ig: Arc<Mutex<InitializedGateGraph>>,
+    control: Arc<Control>,
+    thread: Option<JoinHandle<()>>,
+    clock: LeverHandle,
+    pub(super) max_ticks: usize,
+}
+
+impl Simulator {
+    /// Moves `ig` to a new thread which flips `clock` at the rate set in `config`
+    /// and returns a [Simulator] to interact with it.
+    pub fn spawn(ig: InitializedGateGraph, clock: LeverHandle, config: SimulatorConfig) -> Self {
+        let ig = Arc::new(Mutex::new(ig));
+        let control = Arc::new(Control::default());
+        control.paused.store(config.paused, Ordering::Relaxed);
+
+        let thread = {
+            let ig = ig.clone();
+            let control = control.clone();
+            thread::Builder::new()
+                .name("logicsim-simulator".to_string())
+                .spawn(move || run(&ig, clock, config, &control))
+                .unwrap()
+        };
+
+        Self {
+            ig,
+            control,
+            thread: Some(thread),
+            clock,
+            max_ticks: config.max_ticks,
+        }
+    }
+
+    /// Returns the number of clock cycles simulated since the [Simulator] was spawned.
+    pub fn cycles(&self) -> u64 {
+        self.control.cycles.load(Ordering::Relaxed)
+    }
+
+    /// Pauses the simulation, no clock cycle starts after it returns until [Simulator::resume] is called.
+    pub fn pause(&self) {
+        let _ig = self.lock();
+        self.control.paused.store(true, Ordering::Relaxed);
+    }
+
+    /// Resumes the simulation after [Simulator::pause] or a [hook](Simulator::on_cycle) paused it.
+    pub fn resume(&self) {
+        {
+            let _ig = self.lock();
+            self.control.paused.store(false, Ordering::Relaxed);
+        }
+        self.unpark();
+    }
+
+    /// Returns true if the simulation is paused.
+    pub fn is_paused(&self) -> bool {
+        self.control.paused.load(Ordering::Relaxed)
+    }
+
+    /// Simulates one clock cycle on the calling thread, hooks included, to single step a paused simulation.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the circuit does not stabilize.
+    pub fn step(&self) {
+        cycle(&mut self.lock(), self.clock, self.max_ticks, &self.control);
+    }
+
+    /// Adds a hook which the simulation thread calls with the graph and the number of cycles simulated
+    /// after every clock cycle, the simulation [pauses](Simulator::pause) when it returns false.
+    ///
+    /// Hooks run with the graph locked, so they can't call the methods of the [Simulator].
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,Simulator,SimulatorConfig};
+    /// # use logicsim::{counter,zeros,ON,OFF};
+    /// let mut g = GateGraphBuilder::new();
+    /// let clock = g.lever("clock");
+    /// let count = counter(&mut g, clock.bit(), ON, OFF, ON, OFF, &zeros(8), "counter");
+    /// let output = g.output(&count, "count");
+    ///
+    /// let config = SimulatorConfig {
+    ///     paused: true,
+    ///     ..Default::default()
+    /// };
+    /// let simulator = Simulator::spawn(g.init(), clock, config);
+    /// // Breakpoint.
+    /// simulator.on_cycle(move |ig, _| output.u8(ig) != 42);
+    /// simulator.resume();
+    /// while !simulator.is_paused() {
+    ///     std::thread::yield_now();
+    /// }
+    /// assert_eq!(simulator.u128(output), 42);
+    /// simulator.step();
+    /// assert_eq!(simulator.u128(output), 43);
+    /// ```
+    pub fn on_cycle<F: FnMut(&mut InitializedGateGraph, u64) -> bool + Send + 'static>(
+        &self,
+        hook: F,
+    ) {
+        self.control.hooks.lock().unwrap().push(Box::new(hook));
+    }
+
+    /// Adds `peripheral` to the graph, clocked by the clock of the simulator,
+    /// see [InitializedGateGraph::add_peripheral].
+    pub fn add_peripheral<P: HostPeripheral + 'static>(&self, peripheral: P) -> PeripheralHandle {
+        self.lock().add_peripheral(self.clock, peripheral)
+    }
+
+    /// Sets the state of `lever` to `value` and runs the graph until it's stable, in between clock edges.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the circuit does not stabilize.
+    pub fn update_lever(&self, lever: LeverHandle, value: bool) {
+        let ig = &mut self.lock();
+        ig.update_lever(lever, value);
+        ig.run_until_stable(self.max_ticks).unwrap();
+    }
+
+    /// Returns the value of `output` like [OutputHandle::u128].
+    pub fn u128(&self, output: OutputHandle) -> u128 {
+        output.u128(&self.lock())
+    }
+
+    /// Calls `f` with exclusive access to the graph in between clock edges and returns its result,
+    /// the simulation is paused until it returns.
+    pub fn with<R, F: FnOnce(&mut InitializedGateGraph) -> R>(&self, f: F) -> R {
+        f(&mut self.lock())
+    }
+
+    /// Stores the state of the graph in `path` in between clock cycles, see [InitializedGateGraph::save_state].
+    ///
+    /// Only available with the "serde" feature.
+    #[cfg(feature = "serde")]
+    pub fn checkpoint<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.lock().save_state(path)
+    }
+
+    /// Restores the state stored in `path` by [Simulator::checkpoint], see [InitializedGateGraph::load_state].
+    /// The number of [cycles](Simulator::cycles) is not part of the state.
+    ///
+    /// Only available with the "serde" feature.
+    #[cfg(feature = "serde")]
+    pub fn restore_checkpoint<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.lock().load_state(path)
+    }
+
+    /// Stops the simulation thread and returns the graph, stable after the last clock edge.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the simulation thread panicked.
+    pub fn stop(mut self) -> InitializedGateGraph {
+        if let Err(e) = self.join() {
+            std::panic::resume_unwind(e)
+        }
+        let ig = self.ig.clone();
+        drop(self);
+        Arc::try_unwrap(ig)
+            .ok()
+            .expect("The simulation thread has stopped")
+            .into_inner()
+            .unwrap()
+    }
+
+    /// Stops the simulation thread and waits for it to finish.
+    fn join(&mut self) -> thread::Result<()> {
+        self.control.stop.store(true, Ordering::Relaxed);
+        self.unpark();
+        match self.thread.take() {
+            Some(thread) => thread.join(),
+            None => Ok(()),
+        }
+    }
+
+    /// Wakes the simulation thread up if it's paused.
+    fn unpark(&self) {
+        if let Some(thread) = &self.thread {
+            thread.thread().unpark();
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, InitializedGateGraph> {
+        self.ig
+            .lock()
+            .expect("The simulation thread panicked, the circuit probably didn't stabilize")
+    }
+}
+impl Drop for Simulator {
+    fn drop(&mut self) {
+        // Panics in the simulation thread are already reported by the methods of the simulator.
+        let _ = self.join();
+    }
+}
+
+/// Flips `clock` twice, counts the cycle and calls the hooks.
+fn cycle(ig: &mut InitializedGateGraph, clock: LeverHandle, max_ticks: usize, control: &Control) {
+    ig.flip_lever(clock);
+    ig.run_until_stable(max_ticks).unwrap();
+    ig.flip_lever(clock);
+    ig.run_until_stable(max_ticks).unwrap();
+    let cycles = control.cycles.fetch_add(1, Ordering::Relaxed) + 1;
+
+    for hook in control.hooks.lock().unwrap().iter_mut() {
+        if !hook(ig, cycles) {
+            control.paused.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Simulation loop, simulates a clock cycle at a time until `control.stop` becomes true.
+fn run(
+    ig: &Mutex<InitializedGateGraph>,
+    clock: LeverHandle,
+    config: SimulatorConfig,
+    control: &Control,
+) {
+    let mut pacer = Pacer::new(config.frequency, Instant::now());
+
+    while !control.stop.load(Ordering::Relaxed) {
+        let paused = {
+            let ig = &mut ig.lock().unwrap();
+            let paused = control.paused.load(Ordering::Relaxed);
+            if !paused {
+                cycle(ig, clock, config.max_ticks, control);
+            }
+            paused
+        };
+        if paused {
+            // Simulator::resume and Simulator::stop wake the thread up.
+            thread::park();
+            pacer.restart(Instant::now());
+            continue;
+        }
+
+        if let Some(delay) = pacer.delay(Instant::now()) {
+            thread::sleep(delay);
+        }
+    }
+}
+
+/// Spaces clock cycles out to run them at a target frequency.
+struct Pacer {
+    period: Option<Duration>,
+    deadline: Instant,
+}
+impl Pacer {
+    /// Returns a [Pacer] for `frequency` cycles per second starting at `now`,
+    /// [None] runs the cycles as fast as possible.
+    fn new(frequency: Option<f64>, now: Instant) -> Self {
+        Self {
+            period: frequency.map(|frequency| Duration::from_secs_f64(1. / frequency)),
+            deadline: now,
+        }
+    }
+
+    /// Starts counting periods from `now` again, after the simulation was paused.
+    fn restart(&mut self, now: Instant) {
+        self.deadline = now;
+    }
+
+    /// Returns how long to wait at `now`, after a cycle has finished, before starting the next one.
+    fn delay(&mut self, now: Instant) -> Option<Duration> {
+        self.deadline += self.period?;
+        if self.deadline > now {
+            Some(self.deadline - now)
+        } else {
+            // Don't try to catch up after falling behind, it would run in bursts.
+            self.deadline = now;
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::{Duration, Instant, Pacer};
+    use crate::circuits::{counter, zeros};
+
+    #[test]
+    fn test_pacer() {
+        let ms = Duration::from_millis;
+        let start = Instant::now();
+        let mut pacer = Pacer::new(Some(100.), start);
+        assert_eq!(pacer.delay(start + ms(2)), Some(ms(8)));
+        assert_eq!(pacer.delay(start + ms(11)), Some(ms(9)));
+        // Fell behind, the next period starts when the late cycle finishes.
+        assert_eq!(pacer.delay(start + ms(50)), None);
+        assert_eq!(pacer.delay(start + ms(51)), Some(ms(9)));
+
+        pacer.restart(start + ms(100));
+        assert_eq!(pacer.delay(start + ms(100)), Some(ms(10)));
+
+        let mut pacer = Pacer::new(None, start);
+        assert_eq!(pacer.delay(start + ms(100)), None);
+    }
+
+    #[test]
+    fn test_simulator_shared() {
+        let mut g = GateGraphBuilder::new();
+        let clock = g.lever("clock");
+        let l = g.lever("l");
+        let and = g.and2(l.bit(), clock.bit(), "and");
+        let output = g.output1(and, "and");
+        let lever_output = g.output1(l.bit(), "l");
+
+        let simulator = std::sync::Arc::new(Simulator::spawn(
+            g.init(),
+            clock,
+            SimulatorConfig::default(),
+        ));
+        let writer = {
+            let simulator = simulator.clone();
+            std::thread::spawn(move || simulator.update_lever(l, true))
+        };
+        writer.join().unwrap();
+        assert_eq!(simulator.u128(lever_output), 1);
+        // The clock is always low in between cycles.
+        assert_eq!(simulator.u128(output), 0);
+        assert!(simulator.with(|ig| ig.ticks) > 0);
+    }
+
+    #[test]
+    fn test_simulator_pause() {
+        let mut g = GateGraphBuilder::new();
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let count = counter(
+            &mut g,
+            clock.bit(),
+            ON,
+            OFF,
+            ON,
+            reset.bit(),
+            &zeros(8),
+            "counter",
+        );
+        let output = g.output(&count, "count");
+
+        let config = SimulatorConfig {
+            paused: true,
+            ..Default::default()
+        };
+        let simulator = Simulator::spawn(g.init(), clock, config);
+        // Counters start in an undefined state.
+        simulator.update_lever(reset, true);
+        simulator.update_lever(reset, false);
+        assert!(simulator.is_paused());
+        assert_eq!(simulator.cycles(), 0);
+        simulator.step();
+        simulator.step();
+        assert_eq!((simulator.cycles(), simulator.u128(output)), (2, 2));
+
+        // Hooks see every cycle and can pause the simulation.
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        {
+            let seen = seen.clone();
+            simulator.on_cycle(move |ig, cycles| {
+                seen.lock().unwrap().push((cycles, output.u8(ig)));
+                cycles != 5
+            });
+        }
+        simulator.resume();
+        while !simulator.is_paused() {
+            std::thread::yield_now();
+        }
+        // The hook pauses the simulation before the graph is unlocked, so no other cycle can start.
+        assert_eq!(simulator.cycles(), 5);
+        assert_eq!(*seen.lock().unwrap(), [(3, 3), (4, 4), (5, 5)]);
+
+        simulator.resume();
+        while simulator.cycles() < 10 {
+            std::thread::yield_now();
+        }
+        simulator.pause();
+        assert!(simulator.is_paused());
+        // No cycle starts after pause returns, the last one the hook saw is the last one simulated.
+        let (cycles, value) = simulator.with(|ig| (simulator.cycles(), output.u8(ig)));
+        assert_eq!(seen.lock().unwrap().last(), Some(&(cycles, value)));
+        assert_eq!(value, cycles as u8);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_simulator_checkpoint() {
+        let path = std::env::temp_dir().join("logicsim_simulator_checkpoint_test");
+        let mut g = GateGraphBuilder::new();
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let count = counter(
+            &mut g,
+            clock.bit(),
+            ON,
+            OFF,
+            ON,
+            reset.bit(),
+            &zeros(8),
+            "counter",
+        );
+        let output = g.output(&count, "count");
+
+        let config = SimulatorConfig {
+            paused: true,
+            ..Default::default()
+        };
+        let simulator = Simulator::spawn(g.init(), clock, config);
+        // Counters start in an undefined state.
+        simulator.update_lever(reset, true);
+        simulator.update_lever(reset, false);
+        for _ in 0..3 {
+            simulator.step();
+        }
+        simulator.checkpoint(&path).unwrap();
+        simulator.step();
+        assert_eq!(simulator.u128(output), 4);
+        simulator.restore_checkpoint(&path).unwrap();
+        assert_eq!(simulator.u128(output), 3);
+        simulator.step();
+        assert_eq!(simulator.u128(output), 4);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
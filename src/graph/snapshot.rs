@@ -0,0 +1,242 @@
+use super::behavioral_memory::MemoryState;
+use super::faults::Faults;
+use super::gate::GateIndex;
+use super::gate_delays::ScheduledChanges;
+use super::InitializedGateGraph;
+use crate::data_structures::{DoubleStack, State};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Everything in an [InitializedGateGraph] that changes while it's being simulated,
+/// returned by [InitializedGateGraph::snapshot].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StateSnapshot {
+    pub(super) state: State,
+    pub(super) pending_updates: DoubleStack<GateIndex>,
+    pub(super) delayed: Vec<GateIndex>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(super) scheduled: ScheduledChanges,
+    pub(super) triggered: Vec<bool>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(super) memories: Vec<MemoryState>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(super) faults: Faults,
+    pub(super) ticks: usize,
+}
+impl StateSnapshot {
+    /// Returns the [simulation time](InitializedGateGraph::now) when the snapshot was taken.
+    pub fn ticks(&self) -> usize {
+        self.ticks
+    }
+}
+
+impl InitializedGateGraph {
+    /// Returns a copy of the state of every gate along with the pending updates, the tick count, the injected
+    /// [faults](InitializedGateGraph::inject_fault) and the contents of the
+    /// [behavioral memories](super::GateGraphBuilder::behavioral_memory), which can be
+    /// [restored](InitializedGateGraph::restore) to rewind the simulation to this point.
+    ///
+    /// Events, subscriptions, peripherals and traces are not part of the snapshot.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// # use logicsim::{counter,zeros,ON,OFF};
+    /// let mut g = GateGraphBuilder::new();
+    /// let clock = g.lever("clock");
+    /// let reset = g.lever("reset");
+    /// let count = counter(&mut g, clock.bit(), ON, OFF, ON, reset.bit(), &zeros(8), "counter");
+    /// let output = g.output(&count, "count");
+    ///
+    /// let ig = &mut g.init();
+    /// ig.pulse_lever_stable(reset);
+    /// let snapshot = ig.snapshot();
+    ///
+    /// // Replay the same cycles over and over.
+    /// for _ in 0..3 {
+    ///     ig.restore(&snapshot);
+    ///     for _ in 0..5 {
+    ///         ig.pulse_lever_stable(clock);
+    ///     }
+    ///     assert_eq!(output.u8(ig), 5);
+    /// }
+    /// ```
+    pub fn snapshot(&self) -> StateSnapshot {
+        StateSnapshot {
+            state: self.state.clone(),
+            pending_updates: self.pending_updates.clone(),
+            delayed: self.delayed.clone(),
+            scheduled: self.scheduled.clone(),
+            triggered: self.event_log.triggered.clone(),
+            memories: self.memories.states.clone(),
+            faults: self.faults.clone(),
+            ticks: self.ticks,
+        }
+    }
+
+    /// Rewinds the simulation to the point where `snapshot` was [taken](InitializedGateGraph::snapshot).
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `snapshot` was taken from a graph with a different number of gates, events or memories.
+    pub fn restore(&mut self, snapshot: &StateSnapshot) {
+        assert!(
+            snapshot.state.len() == self.state.len()
+                && snapshot.triggered.len() == self.event_log.triggered.len()
+                && snapshot.memories.len() == self.memories.states.len(),
+            "The snapshot was taken from a different graph"
+        );
+        self.state = snapshot.state.clone();
+        self.pending_updates = snapshot.pending_updates.clone();
+        self.delayed = snapshot.delayed.clone();
+        self.scheduled = snapshot.scheduled.clone();
+        self.event_log.triggered = snapshot.triggered.clone();
+        self.memories.states = snapshot.memories.clone();
+        self.faults = snapshot.faults.clone();
+        self.ticks = snapshot.ticks;
+    }
+
+    /// Calls `f` and restores the state of the graph afterwards, for analyses that need to drive the graph.
+    /// Peripherals, subscriptions, events, traces, published outputs, contention, driver checks, assertions, profiling
+    /// and toggle counting are paused in the meantime.
+    pub(super) fn with_restored_state<R, F: FnOnce(&mut Self) -> R>(&mut self, f: F) -> R {
+        let snapshot = self.snapshot();
+        let peripherals = std::mem::take(&mut self.peripherals);
+        let subscriptions = std::mem::take(&mut self.subscriptions);
+        let event_log = std::mem::take(&mut self.event_log);
+        let json_traces = std::mem::take(&mut self.json_traces);
+        let published_outputs = self.published_outputs.take();
+        let contention = std::mem::take(&mut self.contention);
+        let assertions = std::mem::take(&mut self.assertions);
+        let profiler = self.profiler.take();
+        let toggles = self.toggles.take();
+        #[cfg(feature = "debug_gates")]
+        let drivers = std::mem::take(&mut self.drivers);
+
+        let result = f(self);
+
+        self.peripherals = peripherals;
+        self.subscriptions = subscriptions;
+        self.event_log = event_log;
+        self.json_traces = json_traces;
+        self.published_outputs = published_outputs;
+        self.contention = contention;
+        self.assertions = assertions;
+        self.profiler = profiler;
+        self.toggles = toggles;
+        #[cfg(feature = "debug_gates")]
+        {
+            self.drivers = drivers;
+        }
+        self.restore(&snapshot);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{counter, zeros};
+
+    #[test]
+    fn test_snapshot_restore_mid_tick() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let count = counter(
+            g,
+            clock.bit(),
+            ON,
+            OFF,
+            ON,
+            reset.bit(),
+            &zeros(4),
+            "counter",
+        );
+        let output = g.output(&count, "count");
+        g.event("overflow", count[3], &count);
+        let ig = &mut graph.init();
+        ig.pulse_lever_stable(reset);
+
+        // In the middle of a tick, with updates still pending.
+        ig.set_lever(clock);
+        let snapshot = ig.snapshot();
+        let run = |ig: &mut InitializedGateGraph| {
+            ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+            ig.reset_lever_stable(clock);
+            for _ in 0..10 {
+                ig.pulse_lever_stable(clock);
+            }
+            (output.u8(ig), ig.ticks, ig.take_events().len())
+        };
+        let expected = run(ig);
+        assert_eq!(expected.0, 11);
+        assert_eq!(expected.2, 1);
+
+        ig.restore(&snapshot);
+        assert_eq!(ig.snapshot(), snapshot);
+        assert_eq!(run(ig), expected);
+    }
+
+    #[test]
+    fn test_snapshot_restore_faults() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let l = g.lever("l");
+        let not = g.not1(l.bit(), "not");
+        let output = g.output1(not, "not");
+        let ig = &mut graph.init();
+        ig.run_until_stable(10).unwrap();
+        let gate = output.gates(ig)[0];
+
+        let clean = ig.snapshot();
+        ig.inject_fault(gate, Fault::StuckAt(false));
+        ig.run_until_stable(10).unwrap();
+        let faulty = ig.snapshot();
+
+        ig.restore(&clean);
+        ig.set_lever_stable(l);
+        ig.reset_lever_stable(l);
+        assert_eq!(output.b0(ig), true);
+
+        ig.restore(&faulty);
+        ig.set_lever_stable(l);
+        ig.reset_lever_stable(l);
+        assert_eq!(output.b0(ig), false);
+    }
+
+    #[test]
+    fn test_with_restored_state_pauses_profiling() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let l = g.lever("l");
+        let not = g.not1(l.bit(), "not");
+        g.output1(not, "not");
+        let ig = &mut graph.init();
+        ig.enable_profiling();
+        ig.enable_toggle_counting(None);
+
+        ig.with_restored_state(|ig| {
+            for _ in 0..10 {
+                ig.pulse_lever_stable(l);
+            }
+        });
+        assert_eq!(ig.activity_profile().unwrap().evaluations, 0);
+        let report = ig.power_report(&CostTable::default()).unwrap();
+        assert_eq!(report.total.toggles, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "The snapshot was taken from a different graph")]
+    fn test_restore_different_graph() {
+        let mut g = GateGraphBuilder::new();
+        let snapshot = g.clone().init().snapshot();
+        g.lever("lever");
+        for i in 0..100 {
+            g.not1(ON, format!("{}", i));
+        }
+        g.init_unoptimized().restore(&snapshot);
+    }
+}
@@ -0,0 +1,157 @@
+use super::{InitializedGateGraph, StateSnapshot};
+use crate::data_structures::Fnv64;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+/// [StateSnapshot] stored by [InitializedGateGraph::save_state] along with the hash of the graph it was taken from.
+#[derive(Serialize, Deserialize)]
+struct SavedState {
+    graph_hash: u64,
+    snapshot: StateSnapshot,
+}
+
+impl InitializedGateGraph {
+    /// Stores the state of every gate in `path`, along with the pending updates and the tick count,
+    /// so that [InitializedGateGraph::load_state] can resume the simulation exactly where it was left,
+    /// even from a different process.
+    ///
+    /// Events, subscriptions and custom gates are not part of the state.
+    ///
+    /// Only available with the "serde" feature.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// # use logicsim::{counter,zeros,ON,OFF};
+    /// # let path = std::env::temp_dir().join("logicsim_save_state_doctest");
+    /// let mut g = GateGraphBuilder::new();
+    /// let clock = g.lever("clock");
+    /// let reset = g.lever("reset");
+    /// let count = counter(&mut g, clock.bit(), ON, OFF, ON, reset.bit(), &zeros(4), "counter");
+    /// let output = g.output(&count, "count");
+    ///
+    /// let ig = &mut g.clone().init();
+    /// ig.pulse_lever_stable(reset);
+    /// for _ in 0..5 {
+    ///     ig.pulse_lever_stable(clock);
+    /// }
+    /// ig.save_state(&path).unwrap();
+    ///
+    /// // Later, maybe after restarting the program.
+    /// let ig = &mut g.init();
+    /// ig.load_state(&path).unwrap();
+    /// assert_eq!(output.u8(ig), 5);
+    /// ig.pulse_lever_stable(clock);
+    /// assert_eq!(output.u8(ig), 6);
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn save_state<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let saved = SavedState {
+            graph_hash: self.graph_hash(),
+            snapshot: self.snapshot(),
+        };
+        let mut writer = BufWriter::new(File::create(path)?);
+        bincode::serialize_into(&mut writer, &saved).map_err(invalid_data)
+    }
+
+    /// Restores the state stored in `path` by [InitializedGateGraph::save_state].
+    ///
+    /// Returns an error of kind [InvalidData](io::ErrorKind::InvalidData) if the file is not a state file or
+    /// it was saved from a different graph, in which case `self` is left untouched.
+    /// The graph must have been built and optimized in the same way, the state of a graph initialized
+    /// with [init_unoptimized](super::GateGraphBuilder::init_unoptimized) won't load into one
+    /// initialized with [init](super::GateGraphBuilder::init).
+    ///
+    /// Only available with the "serde" feature.
+    pub fn load_state<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let reader = BufReader::new(File::open(path)?);
+        let saved: SavedState = bincode::deserialize_from(reader).map_err(invalid_data)?;
+        if saved.graph_hash != self.graph_hash() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the state was saved from a different graph",
+            ));
+        }
+        self.restore(&saved.snapshot);
+        Ok(())
+    }
+
+    /// Returns a hash of the structure of the graph, states can only be loaded into graphs with the same hash.
+    fn graph_hash(&self) -> u64 {
+        let mut hasher = Fnv64::default();
+        self.nodes.hash(&mut hasher);
+        self.lever_handles.hash(&mut hasher);
+        self.custom_gates.len().hash(&mut hasher);
+        self.event_log.triggered.len().hash(&mut hasher);
+        self.memories
+            .sizes()
+            .for_each(|size| size.hash(&mut hasher));
+        hasher.finish()
+    }
+}
+
+fn invalid_data(e: bincode::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{counter, zeros};
+
+    fn counter_graph(width: usize) -> (GateGraphBuilder, LeverHandle, OutputHandle) {
+        let mut g = GateGraphBuilder::new();
+        let clock = g.lever("clock");
+        let count = counter(
+            &mut g,
+            clock.bit(),
+            ON,
+            OFF,
+            ON,
+            OFF,
+            &zeros(width),
+            "counter",
+        );
+        let output = g.output(&count, "count");
+        (g, clock, output)
+    }
+
+    #[test]
+    fn test_save_load_state() {
+        let path = std::env::temp_dir().join("logicsim_save_load_state_test");
+        let (g, clock, output) = counter_graph(4);
+        let ig = &mut g.clone().init();
+        for _ in 0..9 {
+            ig.pulse_lever_stable(clock);
+        }
+        // Save in the middle of a tick, with updates still pending.
+        ig.set_lever(clock);
+        ig.save_state(&path).unwrap();
+        ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        ig.reset_lever_stable(clock);
+        let expected = (output.u8(ig), ig.ticks);
+
+        let resumed = &mut g.init();
+        resumed.load_state(&path).unwrap();
+        resumed.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        resumed.reset_lever_stable(clock);
+        assert_eq!((output.u8(resumed), resumed.ticks), expected);
+
+        let (g, _, output) = counter_graph(5);
+        let other = &mut g.init();
+        let before = (output.u8(other), other.ticks);
+        let error = other.load_state(&path).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!((output.u8(other), other.ticks), before);
+
+        std::fs::write(&path, b"definitely not a state").unwrap();
+        let error = resumed.load_state(&path).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(output.u8(resumed), expected.0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,108 @@
+use super::gate::*;
+use super::graph_builder::GateGraphBuilder;
+use super::handles::LeverHandle;
+use crate::circuits::d_flip_flop;
+
+/// Number of flip-flops between the lever and the output of [GateGraphBuilder::synced_lever].
+const SYNC_STAGES: usize = 2;
+
+impl GateGraphBuilder {
+    /// Returns a new lever along with a version of it synchronized to `clock`, which only changes right after
+    /// the raising edge of `clock`, to be used by the circuit instead of the lever itself.
+    ///
+    /// The lever goes through a chain of 2 edge triggered flip-flops, so host flips that happen while `clock` is active
+    /// can't glitch the synchronous logic that reads the lever, they take effect 1 or 2 cycles later.
+    /// Like in any latch, the output is undefined until 2 clock cycles have passed.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// let mut g = GateGraphBuilder::new();
+    /// let clock = g.lever("clock");
+    /// let (button, synced) = g.synced_lever(clock.bit(), "button");
+    /// let output = g.output1(synced, "button");
+    ///
+    /// let ig = &mut g.init();
+    /// ig.pulse_lever_stable(clock);
+    /// ig.pulse_lever_stable(clock);
+    /// assert_eq!(output.b0(ig), false);
+    ///
+    /// // Flipping the lever in the middle of a cycle doesn't change the output.
+    /// ig.set_lever_stable(clock);
+    /// ig.set_lever_stable(button);
+    /// ig.reset_lever_stable(clock);
+    /// assert_eq!(output.b0(ig), false);
+    ///
+    /// ig.pulse_lever_stable(clock);
+    /// assert_eq!(output.b0(ig), false);
+    /// ig.pulse_lever_stable(clock);
+    /// assert_eq!(output.b0(ig), true);
+    /// ```
+    pub fn synced_lever<S: Into<String>>(
+        &mut self,
+        clock: GateIndex,
+        name: S,
+    ) -> (LeverHandle, GateIndex) {
+        let name = name.into();
+        let lever = self.lever(name.clone());
+        let nclock = self.not1(clock, format!("{}:nclock", name));
+
+        let mut synced = lever.bit();
+        for stage in 0..SYNC_STAGES {
+            let stage_name = format!("{}:sync{}", name, stage);
+            // Master-slave, the master follows the input while the clock is inactive
+            // and the slave copies it while the clock is active.
+            let master = d_flip_flop(self, synced, nclock, OFF, ON, ON, stage_name.clone());
+            synced = d_flip_flop(self, master, clock, OFF, ON, ON, stage_name);
+        }
+        (lever, synced)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::counter;
+    use crate::zeros;
+
+    #[test]
+    fn test_synced_lever_no_glitch() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let (enable, synced) = g.synced_lever(clock.bit(), "enable");
+        // A counter enabled by the lever, an asynchronous flip while the clock is active would count mid-cycle.
+        let count = counter(
+            g,
+            clock.bit(),
+            synced,
+            OFF,
+            ON,
+            reset.bit(),
+            &zeros(4),
+            "counter",
+        );
+        let output = g.output(&count, "count");
+
+        let ig = &mut graph.init();
+        ig.pulse_lever_stable(clock);
+        ig.pulse_lever_stable(clock);
+        ig.pulse_lever_stable(reset);
+
+        ig.set_lever_stable(clock);
+        ig.set_lever_stable(enable);
+        assert_eq!(output.u8(ig), 0);
+        ig.reset_lever_stable(clock);
+        assert_eq!(output.u8(ig), 0);
+
+        let mut counts = Vec::new();
+        for _ in 0..4 {
+            ig.pulse_lever_stable(clock);
+            counts.push(output.u8(ig));
+        }
+        // The enable reaches the counter after the second raising edge, so the third one is the first to count.
+        assert_eq!(counts, [0, 0, 1, 2]);
+    }
+}
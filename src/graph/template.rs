@@ -0,0 +1,256 @@
+use super::gate::*;
+use super::graph_builder::GateGraphBuilder;
+use smallvec::SmallVec;
+use std::collections::HashMap;
+
+/// Reference to a gate from inside a [Template].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum TemplateRef {
+    /// One of the input ports.
+    Input(usize),
+    /// One of the gates of the template.
+    Gate(usize),
+    /// [ON] or [OFF].
+    Const(GateIndex),
+}
+
+/// Gate of a [Template].
+#[derive(Debug, Clone)]
+struct TemplateGate {
+    ty: GateType,
+    dependencies: SmallVec<[TemplateRef; GATE_DEPENDENCIES_TINYVEC_SIZE]>,
+    #[cfg(feature = "debug_gates")]
+    name: String,
+}
+
+/// Sub-graph captured with [GateGraphBuilder::capture_template] that can be stamped out any number of times
+/// with [GateGraphBuilder::instantiate].
+#[derive(Debug, Clone)]
+pub struct Template {
+    gates: Vec<TemplateGate>,
+    inputs: usize,
+    outputs: Vec<TemplateRef>,
+}
+impl Template {
+    /// Returns the number of input ports.
+    pub fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    /// Returns the number of output ports.
+    pub fn outputs(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// Returns the number of gates created by every instance.
+    pub fn len(&self) -> usize {
+        self.gates.len()
+    }
+
+    /// Returns true if the template has no gates, for example if every output is an input.
+    pub fn is_empty(&self) -> bool {
+        self.gates.is_empty()
+    }
+}
+
+impl GateGraphBuilder {
+    /// Returns a [Template] of the sub-graph between `inputs` and `outputs`, every gate that `outputs` depend on,
+    /// going backwards until reaching an input or a constant.
+    ///
+    /// Instantiating the template creates a copy of those gates connected to new inputs, which is much cheaper
+    /// than calling the function that built them again, for example to build 64 identical register slices.
+    /// Outputs, probes, events and test points of the original gates are not part of the template.
+    /// The gates used to capture the template are left untouched, you can use them as the first instance.
+    ///
+    /// Returns an error if `outputs` depend on a lever that is not in `inputs`.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// # use logicsim::{d_flip_flop,ON};
+    /// let mut g = GateGraphBuilder::new();
+    /// let d = g.lever("d");
+    /// let clock = g.lever("clock");
+    /// let reset = g.lever("reset");
+    /// let q = d_flip_flop(&mut g, d.bit(), clock.bit(), reset.bit(), ON, ON, "slice");
+    /// let slice = g
+    ///     .capture_template(&[d.bit(), clock.bit(), reset.bit()], &[q])
+    ///     .unwrap();
+    ///
+    /// let d2 = g.lever("d2");
+    /// let q2 = g.instantiate(&slice, &[d2.bit(), clock.bit(), reset.bit()], "slice2")[0];
+    /// let q = g.output1(q, "q");
+    /// let q2 = g.output1(q2, "q2");
+    ///
+    /// let ig = &mut g.init();
+    /// ig.pulse_lever_stable(reset);
+    /// ig.set_lever_stable(d2);
+    /// ig.pulse_lever_stable(clock);
+    /// assert_eq!(q.b0(ig), false);
+    /// assert_eq!(q2.b0(ig), true);
+    /// ```
+    pub fn capture_template(
+        &self,
+        inputs: &[GateIndex],
+        outputs: &[GateIndex],
+    ) -> Result<Template, &'static str> {
+        let mut refs: HashMap<GateIndex, TemplateRef> = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| (*input, TemplateRef::Input(i)))
+            .collect();
+        refs.insert(OFF, TemplateRef::Const(OFF));
+        refs.insert(ON, TemplateRef::Const(ON));
+
+        // Number the gates first, they can depend on each other in loops.
+        let mut captured = Vec::new();
+        let mut pending = outputs.to_vec();
+        while let Some(idx) = pending.pop() {
+            if refs.contains_key(&idx) {
+                continue;
+            }
+            let gate = self.get(idx);
+            if gate.ty.is_lever() {
+                return Err("The outputs depend on a lever that is not an input of the template");
+            }
+            refs.insert(idx, TemplateRef::Gate(captured.len()));
+            captured.push(idx);
+            pending.extend(gate.dependencies.iter().copied());
+        }
+
+        let gates = captured
+            .iter()
+            .map(|idx| {
+                let gate = self.get(*idx);
+                TemplateGate {
+                    ty: gate.ty,
+                    dependencies: gate.dependencies.iter().map(|dep| refs[dep]).collect(),
+                    #[cfg(feature = "debug_gates")]
+                    name: self.names[idx].clone(),
+                }
+            })
+            .collect();
+        Ok(Template {
+            gates,
+            inputs: inputs.len(),
+            outputs: outputs.iter().map(|output| refs[output]).collect(),
+        })
+    }
+
+    /// Creates a copy of the gates of `template` connected to `inputs` and returns its outputs,
+    /// see [capture_template](GateGraphBuilder::capture_template).
+    ///
+    /// The gates of the instance are placed in a [module](GateGraphBuilder::module) named `name`.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `inputs.len()` != `template.inputs()`.
+    pub fn instantiate<S: Into<String>>(
+        &mut self,
+        template: &Template,
+        inputs: &[GateIndex],
+        name: S,
+    ) -> Vec<GateIndex> {
+        assert_eq!(
+            inputs.len(),
+            template.inputs,
+            "The number of inputs doesn't match the template"
+        );
+        let gates: Vec<GateIndex> = template
+            .gates
+            .iter()
+            .map(|gate| self.insert(Gate::new(gate.ty, SmallVec::new())))
+            .collect();
+        let resolve = |r: &TemplateRef| match r {
+            TemplateRef::Input(i) => inputs[*i],
+            TemplateRef::Gate(i) => gates[*i],
+            TemplateRef::Const(idx) => *idx,
+        };
+
+        self.module(name, |g| {
+            for (gate, idx) in template.gates.iter().zip(&gates) {
+                let dependencies: SmallVec<[GateIndex; GATE_DEPENDENCIES_TINYVEC_SIZE]> =
+                    gate.dependencies.iter().map(resolve).collect();
+                g.get_mut(*idx).dependencies = dependencies.clone();
+                #[cfg(feature = "debug_gates")]
+                let name = gate.name.clone();
+                #[cfg(not(feature = "debug_gates"))]
+                let name = "";
+                g.create_gate(*idx, dependencies.into_iter(), name);
+            }
+        });
+        template.outputs.iter().map(resolve).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{register, WordInput};
+
+    #[test]
+    fn test_register_slices() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let input = WordInput::new(g, 4, "input");
+        let slice = register(g, clock.bit(), ON, ON, reset.bit(), &input.bits(), "slice");
+        let mut inputs = vec![clock.bit(), reset.bit()];
+        inputs.extend(input.bits());
+        let template = g.capture_template(&inputs, &slice).unwrap();
+        assert_eq!(template.inputs(), 6);
+        assert_eq!(template.outputs(), 4);
+
+        let slices: Vec<_> = (0..4)
+            .map(|i| {
+                let input = WordInput::new(g, 4, "input");
+                let mut inputs = vec![clock.bit(), reset.bit()];
+                inputs.extend(input.bits());
+                let bits = g.instantiate(&template, &inputs, format!("slice{}", i));
+                (input, g.output(&bits, "slice"))
+            })
+            .collect();
+        let original = g.output(&slice, "original");
+        #[cfg(feature = "debug_gates")]
+        assert!(g.names.values().any(|name| name.starts_with("slice3/")));
+
+        let ig = &mut graph.init();
+        ig.pulse_lever_stable(reset);
+        input.set_to(ig, 15);
+        for (i, (input, _)) in slices.iter().enumerate() {
+            input.set_to(ig, i as u8);
+        }
+        ig.run_until_stable(10).unwrap();
+        ig.pulse_lever_stable(clock);
+        assert_eq!(original.u8(ig), 15);
+        for (i, (_, output)) in slices.iter().enumerate() {
+            assert_eq!(output.u8(ig), i as u8);
+        }
+    }
+
+    #[test]
+    fn test_template_edge_cases() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let a = g.lever("a");
+        let b = g.lever("b");
+        let and = g.and2(a.bit(), b.bit(), "and");
+        assert!(g.capture_template(&[a.bit()], &[and]).is_err());
+
+        // Outputs can be inputs or constants.
+        let template = g.capture_template(&[a.bit()], &[a.bit(), ON]).unwrap();
+        assert!(template.is_empty());
+        assert_eq!(g.instantiate(&template, &[b.bit()], "empty"), [b.bit(), ON]);
+
+        // Loops.
+        let or = g.or2(a.bit(), OFF, "or");
+        let not = g.not1(or, "not");
+        g.d1(or, not);
+        let template = g.capture_template(&[a.bit()], &[not]).unwrap();
+        assert_eq!(template.len(), 2);
+        let not2 = g.instantiate(&template, &[b.bit()], "loop")[0];
+        let or2 = g.get(not2).dependencies[0];
+        assert_eq!(g.get(or2).dependencies.as_slice(), [b.bit(), not2]);
+    }
+}
@@ -0,0 +1,184 @@
+use super::gate::*;
+use super::graph_builder::GateGraphBuilder;
+use smallvec::smallvec;
+use std::collections::{HashMap, HashSet};
+
+impl GateGraphBuilder {
+    /// Applies [triple modular redundancy](https://en.wikipedia.org/wiki/Triple_modular_redundancy) to the module
+    /// that computes `outputs` from `inputs`, and returns the outputs of each of the 3 replicas.
+    ///
+    /// Every gate that `outputs` depend on, up to `inputs`, levers and constants, gets copied 3 times,
+    /// and every gate in `outputs` becomes a majority voter of the corresponding replica outputs.
+    /// The [GateIndex] of every output stays the same, so anything connected to the module,
+    /// including [OutputHandles](super::OutputHandle), keeps working and now sees the voted result.
+    ///
+    /// The replicas are [kept](GateGraphBuilder::keep), otherwise the optimizations would merge them back into
+    /// a single copy. Gates inside the module that are also used outside of it stay as they were, unprotected.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// let mut g = GateGraphBuilder::new();
+    /// let a = g.lever("a");
+    /// let b = g.lever("b");
+    ///
+    /// let and = g.and2(a.bit(), b.bit(), "and");
+    /// let xor = g.xor2(and, b.bit(), "xor");
+    /// let output = g.output1(xor, "xor");
+    ///
+    /// let replicas = g.triplicate(&[a.bit(), b.bit()], &[xor], "module");
+    /// assert_eq!(replicas[0].len(), 1);
+    ///
+    /// let ig = &mut g.init();
+    /// ig.set_lever_stable(b);
+    /// assert_eq!(output.b0(ig), true);
+    /// ig.set_lever_stable(a);
+    /// assert_eq!(output.b0(ig), false);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Will panic if any of the `outputs` is a lever or a constant.
+    pub fn triplicate<S: Into<String>>(
+        &mut self,
+        inputs: &[GateIndex],
+        outputs: &[GateIndex],
+        name: S,
+    ) -> [Vec<GateIndex>; 3] {
+        let name = name.into();
+        let inputs: HashSet<GateIndex> = inputs.iter().copied().collect();
+        for output in outputs {
+            assert!(
+                !output.is_const() && !self.get(*output).ty.is_lever(),
+                "Levers and constants can't be triplicated"
+            );
+        }
+
+        // Every gate of the module, found by walking backwards from the outputs.
+        let mut module = Vec::new();
+        let mut visited = HashSet::new();
+        let mut work: Vec<GateIndex> = outputs.to_vec();
+        while let Some(gate) = work.pop() {
+            if inputs.contains(&gate)
+                || gate.is_const()
+                || self.get(gate).ty.is_lever()
+                || !visited.insert(gate)
+            {
+                continue;
+            }
+            module.push(gate);
+            work.extend_from_slice(&self.get(gate).dependencies);
+        }
+
+        let mut replicas: [Vec<GateIndex>; 3] = Default::default();
+        for (i, replica) in replicas.iter_mut().enumerate() {
+            let replica_name = format!("{}:TMR{}", name, i);
+            let mut copies = HashMap::with_capacity(module.len());
+            // Create the gates first so loops inside the module can be connected.
+            for gate in &module {
+                let copy = self.insert(Gate::new(self.get(*gate).ty, smallvec![]));
+                #[cfg(feature = "debug_gates")]
+                let copy_name = format!("{}:{}", replica_name, self.name(*gate));
+                #[cfg(not(feature = "debug_gates"))]
+                let copy_name = replica_name.clone();
+                self.create_gate(copy, std::iter::empty(), copy_name);
+                self.keep(copy);
+                copies.insert(*gate, copy);
+            }
+            for gate in &module {
+                let copy = copies[gate];
+                let dependencies: Vec<GateIndex> = self
+                    .get(*gate)
+                    .dependencies
+                    .iter()
+                    .map(|dependency| *copies.get(dependency).unwrap_or(dependency))
+                    .collect();
+                for dependency in &dependencies {
+                    self.get_mut(*dependency).dependents.insert(copy);
+                }
+                self.get_mut(copy).dependencies = dependencies.into();
+            }
+            replica.extend(outputs.iter().map(|output| copies[output]));
+        }
+
+        for (i, output) in outputs.iter().enumerate() {
+            let [a, b, c] = [replicas[0][i], replicas[1][i], replicas[2][i]];
+            let votes = [
+                self.and2(a, b, name.clone()),
+                self.and2(a, c, name.clone()),
+                self.and2(b, c, name.clone()),
+            ];
+
+            let old_dependencies = std::mem::take(&mut self.get_mut(*output).dependencies);
+            for dependency in old_dependencies {
+                self.get_mut(dependency).dependents.remove(output);
+            }
+            for vote in &votes {
+                self.get_mut(*vote).dependents.insert(*output);
+            }
+            let voter = self.get_mut(*output);
+            voter.ty = GateType::Or;
+            voter.dependencies = votes.iter().copied().collect();
+        }
+        replicas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{adder, WordInput};
+
+    #[test]
+    fn test_triplicate_adder() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let a = WordInput::new(g, 3, "a");
+        let b = WordInput::new(g, 3, "b");
+        let sum = adder(g, OFF, &a.bits(), &b.bits(), "adder");
+        let output = g.output(&sum, "sum");
+        let reference = graph.clone();
+
+        let mut inputs = a.bits().to_vec();
+        inputs.extend(b.bits());
+        let replicas = graph.triplicate(&inputs, &sum, "tmr");
+        for replica in &replicas {
+            assert_eq!(replica.len(), 3);
+        }
+        let mut levers = a.levers().to_vec();
+        levers.extend_from_slice(b.levers());
+
+        let reference = &mut reference.init();
+        let tmr = &mut graph.init();
+        assert_eq!(
+            tmr.truth_table(output, &levers),
+            reference.truth_table(output, &levers)
+        );
+        // The replicas survived optimization, every gate but the levers and constants is tripled.
+        assert!(tmr.len() - 8 >= (reference.len() - 8) * 3);
+    }
+
+    #[test]
+    fn test_triplicate_loop() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        // An sr latch, which has a loop inside.
+        let s = g.lever("s");
+        let r = g.lever("r");
+        let q = g.nor2(r.bit(), OFF, "q");
+        let nq = g.nor2(s.bit(), q, "nq");
+        g.d1(q, nq);
+        let output = g.output1(q, "q");
+
+        let replicas = graph.triplicate(&[s.bit(), r.bit()], &[q], "tmr");
+        assert_ne!(replicas[0][0], replicas[1][0]);
+
+        let g = &mut graph.init();
+        g.pulse_lever_stable(s);
+        assert_eq!(output.b0(g), true);
+        g.pulse_lever_stable(r);
+        assert_eq!(output.b0(g), false);
+    }
+}
@@ -0,0 +1,114 @@
+use super::handles::*;
+use super::{InitializedGateGraph, DEFAULT_STABLE_MAX};
+
+/// Maximum number of levers [InitializedGateGraph::truth_table] will sweep, 2^20 rows is already a lot.
+pub const TRUTH_TABLE_MAX_LEVERS: usize = 20;
+
+impl InitializedGateGraph {
+    /// Returns the truth table of `output` as a function of `levers`, sorted by input.
+    ///
+    /// Every row is `(input, output)`, where bit `n` of `input` is the state of `levers[n]`,
+    /// the output is read as [OutputHandle::u64] after running the graph until it's stable.
+    ///
+    /// The levers are swept in [gray code](https://en.wikipedia.org/wiki/Gray_code) order so that only one lever
+    /// changes between rows, and they are set back to their original state at the end.
+    /// The result only makes sense for combinational logic, if `output` depends on some state,
+    /// it will depend on the sweep order too.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// let mut g = GateGraphBuilder::new();
+    /// let a = g.lever("a");
+    /// let b = g.lever("b");
+    ///
+    /// let xor = g.xor2(a.bit(), b.bit(), "xor");
+    /// let and = g.and2(a.bit(), b.bit(), "and");
+    /// let output = g.output(&[xor, and], "half_adder");
+    ///
+    /// let ig = &mut g.init();
+    /// assert_eq!(
+    ///     ig.truth_table(output, &[a, b]),
+    ///     [(0b00, 0b00), (0b01, 0b01), (0b10, 0b01), (0b11, 0b10)]
+    /// );
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `levers.len()` > [TRUTH_TABLE_MAX_LEVERS] or if the graph doesn't stabilize
+    /// after [DEFAULT_STABLE_MAX] ticks for any row.
+    pub fn truth_table(&mut self, output: OutputHandle, levers: &[LeverHandle]) -> Vec<(u64, u64)> {
+        assert!(
+            levers.len() <= TRUTH_TABLE_MAX_LEVERS,
+            "Refusing to sweep {} levers, the maximum is {}",
+            levers.len(),
+            TRUTH_TABLE_MAX_LEVERS
+        );
+        let original: Vec<bool> = levers
+            .iter()
+            .map(|lever| self.value(self.lever_handles[lever.handle]))
+            .collect();
+
+        let rows = 1u64 << levers.len();
+        let mut table = Vec::with_capacity(rows as usize);
+        self.update_levers(levers, (0..levers.len()).map(|_| false));
+        self.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        table.push((0, output.u64(self)));
+
+        let mut input = 0u64;
+        for row in 1..rows {
+            let flipped = row.trailing_zeros() as usize;
+            input ^= 1 << flipped;
+            self.update_lever(levers[flipped], input & (1 << flipped) != 0);
+            self.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+            table.push((input, output.u64(self)));
+        }
+        table.sort_unstable_by_key(|(input, _)| *input);
+
+        self.update_levers(levers, original.into_iter());
+        self.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::adder;
+
+    #[test]
+    fn test_truth_table_adder() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let levers: Vec<_> = (0..7).map(|i| g.lever(format!("l{}", i))).collect();
+        let bits: Vec<_> = levers.iter().map(|lever| lever.bit()).collect();
+        let sum = adder(g, bits[6], &bits[0..3], &bits[3..6], "adder");
+        let output = g.output(&sum, "sum");
+
+        let g = &mut graph.init();
+        g.set_lever_stable(levers[2]);
+
+        let table = g.truth_table(output, &levers);
+        assert_eq!(table.len(), 128);
+        for (i, (input, output)) in table.into_iter().enumerate() {
+            assert_eq!(input, i as u64);
+            let expected = (input & 0b111) + ((input >> 3) & 0b111) + (input >> 6);
+            assert_eq!(output, expected & 0b111);
+        }
+
+        // The levers are restored.
+        assert_eq!(output.u8(g), 0b100);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_truth_table_too_big() {
+        let mut graph = GateGraphBuilder::new();
+        let levers: Vec<_> = (0..=TRUTH_TABLE_MAX_LEVERS)
+            .map(|i| graph.lever(format!("l{}", i)))
+            .collect();
+        let output = graph.output1(levers[0].bit(), "out");
+        graph.init().truth_table(output, &levers);
+    }
+}
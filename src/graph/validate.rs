@@ -0,0 +1,270 @@
+use super::gate::*;
+use super::graph_builder::GateGraphBuilder;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+/// Named group of gates that components connect to, like a [Wire](crate::Wire) or a [Bus](crate::Bus),
+/// checked by [GateGraphBuilder::validate].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(super) struct Port {
+    name: String,
+    bits: Vec<GateIndex>,
+    /// Widths of the connections that didn't match the width of the port.
+    mismatched_widths: Vec<usize>,
+}
+
+/// Wiring mistake found by [GateGraphBuilder::validate], ports are identified by the name of their gates.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ValidationIssue {
+    /// Nothing was ever connected to bit `bit` of `port`, it will always be 0.
+    Floating { port: String, bit: usize },
+    /// No gate or output reads any bit of `port`.
+    Unused { port: String },
+    /// Bit `bit` of `port` is always driven by more than one gate, its value is the or of `drivers`.
+    MultipleDrivers {
+        port: String,
+        bit: usize,
+        drivers: Vec<String>,
+    },
+    /// A word of `width` bits was connected to `port`, which is `port_width` bits wide.
+    WidthMismatch {
+        port: String,
+        port_width: usize,
+        width: usize,
+    },
+}
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationIssue::Floating { port, bit } => {
+                write!(f, "Bit {} of {} is never driven", bit, port)
+            }
+            ValidationIssue::Unused { port } => write!(f, "{} is never read", port),
+            ValidationIssue::MultipleDrivers { port, bit, drivers } => write!(
+                f,
+                "Bit {} of {} is driven by: {}",
+                bit,
+                port,
+                drivers.join(", ")
+            ),
+            ValidationIssue::WidthMismatch {
+                port,
+                port_width,
+                width,
+            } => write!(
+                f,
+                "{} bits were connected to {}, which is {} bits wide",
+                width, port, port_width
+            ),
+        }
+    }
+}
+
+impl GateGraphBuilder {
+    /// Registers the gates in `bits` as a port named `name` to be checked by [GateGraphBuilder::validate],
+    /// returns its id.
+    pub(crate) fn add_port(&mut self, name: String, bits: Vec<GateIndex>) -> usize {
+        self.ports.push(Port {
+            name,
+            bits,
+            mismatched_widths: Vec::new(),
+        });
+        self.ports.len() - 1
+    }
+
+    /// Records that a word of `width` bits was connected to the port with id `port`.
+    pub(crate) fn connect_port(&mut self, port: usize, width: usize) {
+        let port = &mut self.ports[port];
+        if port.bits.len() != width {
+            port.mismatched_widths.push(width);
+        }
+    }
+
+    /// Replaces every appearance of `gate` in the ports with `new`.
+    pub(super) fn replace_in_ports(&mut self, gate: GateIndex, new: GateIndex) {
+        for port in &mut self.ports {
+            for bit in &mut port.bits {
+                if *bit == gate {
+                    *bit = new
+                }
+            }
+        }
+    }
+
+    /// Updates the gates of every port after [compacting](GateGraphBuilder::compact_now) the graph.
+    pub(super) fn remap_ports(&mut self, index_map: &HashMap<GateIndex, GateIndex>) {
+        for port in &mut self.ports {
+            for bit in &mut port.bits {
+                // Gates removed by optimizations aren't in the map.
+                *bit = index_map.get(bit).copied().unwrap_or(OFF)
+            }
+        }
+    }
+
+    /// Checks the connections of every [Wire](crate::Wire) and [Bus](crate::Bus) in the graph and returns the
+    /// wiring mistakes it finds, which would otherwise only show up as wrong results in the simulation.
+    ///
+    /// The checks are:
+    /// - A bit nothing was connected to, see [ValidationIssue::Floating].
+    /// - A port none of whose bits are read by a gate or an output, see [ValidationIssue::Unused].
+    /// - A bit connected to more than one gate that is always driving it, see [ValidationIssue::MultipleDrivers].
+    ///   [Tri-state](GateGraphBuilder::tristate) connections are only checked in the simulation,
+    ///   see [InitializedGateGraph::contentions](super::InitializedGateGraph::contentions), and levers are
+    ///   considered test overrides like the ones created by [Wire::make_lever](crate::Wire::make_lever).
+    /// - A word connected with [Bus::connect_some](crate::Bus::connect_some) that doesn't have the width of
+    ///   the bus, see [ValidationIssue::WidthMismatch].
+    ///
+    /// Run it before [init](GateGraphBuilder::init), optimizations remove the gates it looks at.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,ValidationIssue};
+    /// # use logicsim::{Bus,Wire};
+    /// let mut g = GateGraphBuilder::new();
+    /// let a = g.lever("a");
+    /// let b = g.not1(a.bit(), "b");
+    /// let c = g.not1(b, "c");
+    ///
+    /// let bus = Bus::new(&mut g, 2, "bus");
+    /// bus.connect_some(&mut g, &[a.bit()]);
+    /// g.output(bus.bits(), "bus");
+    /// let enable = Wire::new(&mut g, "enable");
+    /// enable.connect(&mut g, b);
+    /// enable.connect(&mut g, c);
+    ///
+    /// let issues = g.validate();
+    /// assert_eq!(issues.len(), 4);
+    /// assert!(issues.contains(&ValidationIssue::Floating { port: "BUS:bus".to_string(), bit: 1 }));
+    /// assert!(issues.contains(&ValidationIssue::Unused { port: "WIRE:enable".to_string() }));
+    /// ```
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        for port in &self.ports {
+            // Removed gates are replaced with OFF.
+            if port.bits.iter().all(|gate| gate.is_const()) {
+                continue;
+            }
+            let mut read = false;
+            for (bit, gate) in port.bits.iter().enumerate() {
+                if gate.is_const() {
+                    continue;
+                }
+                let build_gate = self.get(*gate);
+                read |= !build_gate.dependents.is_empty() || self.outputs.contains(gate);
+                if build_gate.dependencies.is_empty() {
+                    issues.push(ValidationIssue::Floating {
+                        port: port.name.clone(),
+                        bit,
+                    });
+                }
+                let drivers: Vec<_> = build_gate
+                    .dependencies
+                    .iter()
+                    .filter(|dependency| {
+                        let ty = self.get(**dependency).ty;
+                        **dependency != OFF && !ty.is_lever() && ty != GateType::TriState
+                    })
+                    .collect();
+                if drivers.len() > 1 {
+                    issues.push(ValidationIssue::MultipleDrivers {
+                        port: port.name.clone(),
+                        bit,
+                        drivers: drivers.iter().map(|gate| self.full_name(**gate)).collect(),
+                    });
+                }
+            }
+            if !read {
+                issues.push(ValidationIssue::Unused {
+                    port: port.name.clone(),
+                });
+            }
+            for width in &port.mismatched_widths {
+                issues.push(ValidationIssue::WidthMismatch {
+                    port: port.name.clone(),
+                    port_width: port.bits.len(),
+                    width: *width,
+                });
+            }
+        }
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::ValidationIssue;
+    use crate::circuits::{Bus, Wire};
+
+    #[test]
+    fn test_validate_clean() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let a = g.lever("a");
+        let enable = g.lever("enable");
+
+        let mut reset = Wire::new(g, "reset");
+        reset.make_lever(g);
+        reset.connect(g, a.bit());
+        let bus = Bus::new(g, 2, "bus");
+        bus.connect(g, &[a.bit(), reset.bit()]);
+        let not = g.not1(a.bit(), "not");
+        bus.connect_tristate(g, enable.bit(), &[not, OFF]);
+        g.output(bus.bits(), "bus");
+
+        assert_eq!(graph.validate(), vec![]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let a = g.lever("a");
+        let b = g.not1(a.bit(), "b");
+        let c = g.not1(b, "c");
+
+        let bus = Bus::new(g, 3, "bus");
+        bus.connect_some(g, &[c, b]);
+        bus.connect_some(g, &[b, c, c, c]);
+        g.output1(bus.bx(1), "bit");
+        let unused = Wire::new(g, "unused");
+        unused.connect(g, a.bit());
+        let removed = g.not1(a.bit(), "removed");
+        let wire = Wire::new(g, "wire");
+        wire.connect(g, removed);
+        g.output1(wire.bit(), "wire");
+        g.remove_gate(wire.bit());
+
+        let issues = graph.validate();
+        assert_eq!(issues.len(), 5, "{:?}", issues);
+        assert!(matches!(
+            &issues[0],
+            ValidationIssue::MultipleDrivers { bit: 0, drivers, .. } if drivers.len() == 2
+        ));
+        assert!(matches!(
+            issues[1],
+            ValidationIssue::MultipleDrivers { bit: 1, .. }
+        ));
+        assert_eq!(
+            issues[2],
+            ValidationIssue::WidthMismatch {
+                port: "BUS:bus".to_string(),
+                port_width: 3,
+                width: 2
+            }
+        );
+        assert!(matches!(
+            issues[3],
+            ValidationIssue::WidthMismatch { width: 4, .. }
+        ));
+        assert_eq!(
+            issues[4],
+            ValidationIssue::Unused {
+                port: "WIRE:unused".to_string()
+            }
+        );
+    }
+}
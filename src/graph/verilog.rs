@@ -0,0 +1,364 @@
+use super::gate::*;
+use super::{GateGraphBuilder, InitializedGateGraph};
+use std::collections::HashSet;
+use std::fmt::Write;
+
+/// Reserved words that can't be used as port names.
+const VERILOG_KEYWORDS: &[&str] = &[
+    "always",
+    "and",
+    "assign",
+    "begin",
+    "buf",
+    "case",
+    "default",
+    "else",
+    "end",
+    "endcase",
+    "endfunction",
+    "endmodule",
+    "for",
+    "function",
+    "if",
+    "initial",
+    "inout",
+    "input",
+    "integer",
+    "module",
+    "nand",
+    "negedge",
+    "nor",
+    "not",
+    "or",
+    "output",
+    "parameter",
+    "posedge",
+    "reg",
+    "wire",
+    "xnor",
+    "xor",
+];
+
+/// Returns the name of the wire of `gate` in the exported module.
+fn wire(gate: GateIndex) -> String {
+    format!("g{}", gate.idx)
+}
+
+/// Returns true if `name` could be the name of the wire of a gate.
+fn is_wire_name(name: &str) -> bool {
+    name.len() > 1 && name.starts_with('g') && name[1..].chars().all(|c| c.is_ascii_digit())
+}
+
+/// Turns `name` into a valid Verilog identifier that is not in `used`, and adds it to `used`.
+///
+/// Invalid characters are replaced by underscores and a number is appended to repeated names.
+fn identifier(name: &str, used: &mut HashSet<String>) -> String {
+    let mut base: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if base.is_empty() || base.starts_with(|c: char| c.is_ascii_digit()) {
+        base.insert(0, '_');
+    }
+    let mut candidate = base.clone();
+    let mut n = 1;
+    while used.contains(&candidate)
+        || VERILOG_KEYWORDS.contains(&candidate.as_str())
+        || is_wire_name(&candidate)
+    {
+        candidate = format!("{}_{}", base, n);
+        n += 1;
+    }
+    used.insert(candidate.clone());
+    candidate
+}
+
+impl InitializedGateGraph {
+    /// Returns the graph as a synthesizable [Verilog](https://en.wikipedia.org/wiki/Verilog) module named `module`,
+    /// with an input port per lever and an output port per output, so circuits can be taken to FPGA toolchains.
+    ///
+    /// Every gate becomes a wire with a continuous assignment, named after its index like "g42".
+    /// Ports are named after the levers and outputs, with invalid characters replaced by underscores,
+    /// levers are named "lever{handle}" if the "debug_gates" feature is disabled.
+    /// Custom gates are written as a sum of products of their truth table, and tri-state buffers as and gates
+    /// because the buses they drive are or gates.
+    ///
+    /// Circuits with state are exported as the gate level loops that implement them, which synthesis tools
+    /// accept but warn about, and delay gates become plain buffers because Verilog doesn't know about ticks.
+    /// For anything running on real hardware you'll want to replace those with proper clocked registers.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::GateGraphBuilder;
+    /// let mut g = GateGraphBuilder::new();
+    /// let a = g.lever("a");
+    /// let b = g.lever("b");
+    /// let xor = g.xor2(a.bit(), b.bit(), "xor");
+    /// g.output1(xor, "sum");
+    ///
+    /// let verilog = g.init().to_verilog("half_adder");
+    /// assert!(verilog.starts_with("module half_adder ("));
+    /// assert!(verilog.contains("input wire a"));
+    /// assert!(verilog.contains("output wire sum"));
+    /// ```
+    pub fn to_verilog(&self, module: &str) -> String {
+        let mut used = HashSet::new();
+        let module = identifier(module, &mut used);
+        used.clear();
+
+        let mut ports = Vec::new();
+        let mut assigns = String::new();
+        for (handle, idx) in self.lever_handles.iter().enumerate() {
+            let name = identifier(&self.lever_name(handle), &mut used);
+            ports.push(format!("input wire {}", name));
+            writeln!(assigns, "    assign {} = {};", wire(*idx), name).unwrap();
+        }
+        for output in self.output_handles.iter() {
+            let name = identifier(&output.name, &mut used);
+            let bits: Vec<String> = output.bits.iter().rev().map(|bit| wire(*bit)).collect();
+            if bits.len() == 1 {
+                ports.push(format!("output wire {}", name));
+                writeln!(assigns, "    assign {} = {};", name, bits[0]).unwrap();
+            } else {
+                ports.push(format!("output wire [{}:0] {}", bits.len() - 1, name));
+                writeln!(assigns, "    assign {} = {{{}}};", name, bits.join(", ")).unwrap();
+            }
+        }
+
+        let mut verilog = String::new();
+        writeln!(verilog, "module {} (", module).unwrap();
+        writeln!(verilog, "    {}", ports.join(",\n    ")).unwrap();
+        writeln!(verilog, ");").unwrap();
+        for i in 0..self.nodes.len() {
+            writeln!(verilog, "    wire {};", wire(gi!(i))).unwrap();
+        }
+        for (i, node) in self.nodes.iter().enumerate() {
+            let inputs: Vec<String> = node.dependencies.iter().map(|d| wire(*d)).collect();
+            let expression = match node.ty {
+                GateType::Lever => continue,
+                GateType::On => "1'b1".to_string(),
+                GateType::Off => "1'b0".to_string(),
+                GateType::Not => format!("~{}", inputs[0]),
+                GateType::Delay => inputs[0].clone(),
+                GateType::TriState => inputs.join(" & "),
+                GateType::Custom(id) => {
+                    let eval = self.custom_gates[id as usize].eval;
+                    let mut values = vec![false; inputs.len()];
+                    let mut products = Vec::new();
+                    for combination in 0..1usize << inputs.len() {
+                        for (i, value) in values.iter_mut().enumerate() {
+                            *value = combination >> i & 1 == 1;
+                        }
+                        if eval(&values) {
+                            let literals: Vec<String> = inputs
+                                .iter()
+                                .zip(&values)
+                                .map(|(input, value)| {
+                                    if *value {
+                                        input.clone()
+                                    } else {
+                                        format!("~{}", input)
+                                    }
+                                })
+                                .collect();
+                            products.push(format!("({})", literals.join(" & ")));
+                        }
+                    }
+                    match products.len() {
+                        0 => "1'b0".to_string(),
+                        n if n == 1 << inputs.len() => "1'b1".to_string(),
+                        _ => products.join(" | "),
+                    }
+                }
+                _ if inputs.is_empty() => {
+                    let state = node.ty.init() != node.ty.is_negated();
+                    if state { "1'b1" } else { "1'b0" }.to_string()
+                }
+                GateType::And | GateType::Nand => inputs.join(" & "),
+                GateType::Or | GateType::Nor => inputs.join(" | "),
+                GateType::Xor | GateType::Xnor => inputs.join(" ^ "),
+            };
+            let negated = node.ty.is_negated() && !inputs.is_empty() && node.ty != GateType::Not;
+            if negated {
+                writeln!(verilog, "    assign g{} = ~({});", i, expression).unwrap();
+            } else {
+                writeln!(verilog, "    assign g{} = {};", i, expression).unwrap();
+            }
+        }
+        verilog.push_str(&assigns);
+        writeln!(verilog, "endmodule").unwrap();
+        verilog
+    }
+
+    /// Returns the name of the lever with handle `handle`, "lever{handle}" if the "debug_gates" feature is disabled.
+    fn lever_name(&self, handle: usize) -> String {
+        #[cfg(feature = "debug_gates")]
+        return self.name(self.lever_handles[handle]).to_string();
+        #[cfg(not(feature = "debug_gates"))]
+        format!("lever{}", handle)
+    }
+}
+
+impl GateGraphBuilder {
+    /// Returns the circuit as a synthesizable Verilog module named `module`, exactly as it was built,
+    /// see [InitializedGateGraph::to_verilog].
+    ///
+    /// Call [to_verilog](InitializedGateGraph::to_verilog) on the [InitializedGateGraph] instead
+    /// to export the optimized circuit.
+    pub fn to_verilog(&self, module: &str) -> String {
+        self.clone().init_unoptimized().to_verilog(module)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::identifier;
+    use crate::circuits::{adder, WordInput};
+    use std::collections::{HashMap, HashSet};
+
+    /// Evaluates the combinational Verilog written by [InitializedGateGraph::to_verilog]
+    /// with the input ports set to `inputs` and returns the value of the output port `output`.
+    fn evaluate(verilog: &str, inputs: &HashMap<&str, bool>, output: &str) -> u128 {
+        let assigns: HashMap<&str, &str> = verilog
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("assign "))
+            .map(|assign| {
+                let (name, expression) = assign.split_once(" = ").unwrap();
+                (name, expression.trim_end_matches(';'))
+            })
+            .collect();
+        fn value(name: &str, assigns: &HashMap<&str, &str>, inputs: &HashMap<&str, bool>) -> bool {
+            match inputs.get(name) {
+                Some(value) => *value,
+                None => expression(&mut assigns[name].chars().peekable(), assigns, inputs),
+            }
+        }
+        type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+        fn term(
+            chars: &mut Chars,
+            assigns: &HashMap<&str, &str>,
+            inputs: &HashMap<&str, bool>,
+        ) -> bool {
+            while chars.peek() == Some(&' ') {
+                chars.next();
+            }
+            match chars.next().unwrap() {
+                '~' => !term(chars, assigns, inputs),
+                '(' => {
+                    let value = expression(chars, assigns, inputs);
+                    assert_eq!(chars.next(), Some(')'));
+                    value
+                }
+                '1' => {
+                    let constant: String = chars.take(3).collect();
+                    constant == "'b1"
+                }
+                c => {
+                    let mut name = c.to_string();
+                    while let Some(c) = chars
+                        .peek()
+                        .filter(|c| c.is_ascii_alphanumeric() || **c == '_')
+                    {
+                        name.push(*c);
+                        chars.next();
+                    }
+                    value(&name, assigns, inputs)
+                }
+            }
+        }
+        fn expression(
+            chars: &mut Chars,
+            assigns: &HashMap<&str, &str>,
+            inputs: &HashMap<&str, bool>,
+        ) -> bool {
+            let mut acc = term(chars, assigns, inputs);
+            loop {
+                while chars.peek() == Some(&' ') {
+                    chars.next();
+                }
+                match chars.peek() {
+                    Some('&') => {
+                        chars.next();
+                        acc &= term(chars, assigns, inputs)
+                    }
+                    Some('|') => {
+                        chars.next();
+                        acc |= term(chars, assigns, inputs)
+                    }
+                    Some('^') => {
+                        chars.next();
+                        acc ^= term(chars, assigns, inputs)
+                    }
+                    _ => return acc,
+                }
+            }
+        }
+
+        let bits: Vec<&str> = assigns[output]
+            .trim_start_matches('{')
+            .trim_end_matches('}')
+            .split(", ")
+            .collect();
+        bits.iter().fold(0, |acc, bit| {
+            acc << 1 | value(bit, &assigns, inputs) as u128
+        })
+    }
+
+    #[test]
+    fn test_identifier() {
+        let used = &mut HashSet::new();
+        assert_eq!(identifier("count", used), "count");
+        assert_eq!(identifier("count", used), "count_1");
+        assert_eq!(identifier("alu:out 2", used), "alu_out_2");
+        assert_eq!(identifier("2x", used), "_2x");
+        assert_eq!(identifier("", used), "_");
+        assert_eq!(identifier("module", used), "module_1");
+        assert_eq!(identifier("g12", used), "g12_1");
+    }
+
+    #[test]
+    fn test_verilog_matches_simulation() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let a = WordInput::new(g, 3, "a");
+        let b = WordInput::new(g, 3, "b");
+        let sum = adder(g, OFF, &a.bits(), &b.bits(), "adder");
+        let maj3 = g.register_gate("MAJ3", 3, |i| i.iter().filter(|b| **b).count() >= 2);
+        let majority = g.custom(maj3, &[a.bits()[0], a.bits()[1], b.bits()[2]], "majority");
+        let nand = g.nand2(a.bits()[2], b.bits()[0], "nand");
+        let sum = g.output(&sum, "sum");
+        let other = g.output(&[majority, nand], "other");
+        let verilog = graph.to_verilog("test");
+        assert!(verilog.contains("output wire [2:0] sum"));
+
+        // Input ports are declared in lever handle order.
+        let ports: Vec<&str> = verilog
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("input wire "))
+            .map(|port| port.trim_end_matches(','))
+            .collect();
+
+        let ig = &mut graph.init();
+        let optimized = ig.to_verilog("test");
+        for value in 0..64usize {
+            a.set_to(ig, value % 8);
+            b.set_to(ig, value / 8);
+            ig.run_until_stable(10).unwrap();
+            let levers = a.levers().iter().chain(b.levers());
+            let inputs = levers
+                .enumerate()
+                .map(|(bit, lever)| (ports[lever.handle], value >> bit & 1 == 1))
+                .collect();
+            for verilog in [&verilog, &optimized] {
+                assert_eq!(evaluate(verilog, &inputs, "sum"), sum.u128(ig), "{}", value);
+                assert_eq!(
+                    evaluate(verilog, &inputs, "other"),
+                    other.u128(ig),
+                    "{}",
+                    value
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use super::gate::*;
+use super::graph_builder::GateGraphBuilder;
+use super::InitializedGateGraph;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+
+/// Likely mistake found by the analyses that run when a graph is initialized, see [InitializedGateGraph::warnings].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InitWarning {
+    /// The gates named `names` are connected in a loop that doesn't go through a recognized latch, so they store
+    /// state in a way that's probably unintended. The names are full names, like the ones
+    /// [GateGraphBuilder::dump_dot] uses.
+    UnintendedLatch { names: Vec<String> },
+}
+impl Display for InitWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            InitWarning::UnintendedLatch { names, .. } => {
+                write!(f, "Unintended latch: {}", names.join(", "))
+            }
+        }
+    }
+}
+
+impl InitializedGateGraph {
+    /// Returns the likely mistakes found when the graph was initialized, they are also logged as warnings to the
+    /// "logicsim::init" target.
+    ///
+    /// Loops store state, which is what latches are for, but a loop created by accident, like a multiplexer whose
+    /// output is connected back to one of its inputs, is a level sensitive latch nobody asked for, and the usual
+    /// reason a circuit doesn't stabilize. Gates of a loop are reported as an [InitWarning::UnintendedLatch] if
+    /// some cycle of the loop doesn't go through a recognized latch or a delay gate.
+    ///
+    /// Recognized latches are pairs of cross coupled inverting gates (nor, nand, not or an or/and followed by a not),
+    /// like [sr_latch](crate::sr_latch) and every flip-flop, register and memory built on it. Loops that can oscillate
+    /// are reported too, see [find_combinational_loops](InitializedGateGraph::find_combinational_loops).
+    ///
+    /// The analysis runs before the optimizations, which can rewrite latches into shapes that aren't recognized.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,InitWarning};
+    /// # use logicsim::{d_flip_flop,ON,OFF};
+    /// let mut g = GateGraphBuilder::new();
+    /// let d = g.lever("d");
+    /// let enable = g.lever("enable");
+    /// let q = d_flip_flop(&mut g, d.bit(), enable.bit(), OFF, ON, ON, "ff");
+    /// g.output1(q, "q");
+    ///
+    /// // A multiplexer that keeps its output while enable is off, by mistake.
+    /// let not_enable = g.not1(enable.bit(), "not_enable");
+    /// let keep = g.and("keep");
+    /// let load = g.and2(enable.bit(), d.bit(), "load");
+    /// let mux = g.or2(load, keep, "mux");
+    /// g.dpush(keep, not_enable);
+    /// g.dpush(keep, mux);
+    /// g.output1(mux, "mux");
+    ///
+    /// let ig = g.init();
+    /// let warnings = ig.warnings();
+    /// assert_eq!(warnings.len(), 1);
+    /// let InitWarning::UnintendedLatch { names } = &warnings[0];
+    /// # #[cfg(feature = "debug_gates")]
+    /// assert!(names.iter().any(|name| name.contains("mux")));
+    /// ```
+    pub fn warnings(&self) -> &[InitWarning] {
+        &self.warnings
+    }
+}
+
+impl GateGraphBuilder {
+    /// Runs the analyses behind [InitializedGateGraph::warnings] and logs what they find,
+    /// called by every init method before optimizing.
+    pub(super) fn find_init_warnings(&self) -> Vec<InitWarning> {
+        let warnings = self.find_unintended_latches();
+        for warning in &warnings {
+            tracing::warn!(target: "logicsim::init", "{}", warning);
+        }
+        warnings
+    }
+
+    /// Returns the loops that don't go through a recognized latch, see [InitializedGateGraph::warnings].
+    fn find_unintended_latches(&self) -> Vec<InitWarning> {
+        let latched = self.recognized_latches();
+        let breaks = |gate: GateIndex| latched.contains(&gate) || self.get(gate).ty.is_delay();
+        let mut graph = petgraph::Graph::<GateIndex, ()>::new();
+        let mut index = HashMap::new();
+        for (i, _) in self.nodes.iter() {
+            index.insert(GateIndex::from(i), graph.add_node(i.into()));
+        }
+        for (i, gate) in self.nodes.iter() {
+            let i = GateIndex::from(i);
+            if breaks(i) {
+                continue;
+            }
+            for dependency in &gate.dependencies {
+                if !breaks(*dependency) {
+                    graph.add_edge(index[dependency], index[&i], ());
+                }
+            }
+        }
+
+        let mut loops: Vec<Vec<GateIndex>> = petgraph::algo::tarjan_scc(&graph)
+            .into_iter()
+            .map(|scc| scc.into_iter().map(|node| graph[node]).collect::<Vec<_>>())
+            .filter(|gates| {
+                gates.len() > 1
+                    || (!breaks(gates[0]) && self.get(gates[0]).dependencies.contains(&gates[0]))
+            })
+            .collect();
+        for gates in &mut loops {
+            gates.sort_unstable();
+        }
+        loops.sort_unstable_by_key(|gates| gates[0]);
+        loops
+            .into_iter()
+            .map(|gates| InitWarning::UnintendedLatch {
+                names: gates.iter().map(|gate| self.full_name(*gate)).collect(),
+            })
+            .collect()
+    }
+
+    /// Returns every gate that is part of a pair of cross coupled inverting stages.
+    fn recognized_latches(&self) -> HashSet<GateIndex> {
+        let mut latched = HashSet::new();
+        for (i, _) in self.nodes.iter() {
+            let i = GateIndex::from(i);
+            let (gates, inputs) = match self.inverting_stage(i) {
+                Some(stage) => stage,
+                None => continue,
+            };
+            for input in inputs {
+                if gates.contains(input) {
+                    continue;
+                }
+                if let Some((other_gates, other_inputs)) = self.inverting_stage(*input) {
+                    if other_inputs.contains(&i) {
+                        latched.extend(gates.iter().copied());
+                        latched.extend(other_gates);
+                    }
+                }
+            }
+        }
+        latched
+    }
+
+    /// Returns the gates and the inputs of the inverting stage whose output is `gate`, or None if `gate` isn't
+    /// one. Stages are nor, nand and not gates, and or and and gates followed by a not gate.
+    fn inverting_stage(&self, gate: GateIndex) -> Option<(Vec<GateIndex>, &[GateIndex])> {
+        let dependencies = &self.get(gate).dependencies;
+        match self.get(gate).ty {
+            GateType::Nor | GateType::Nand => Some((vec![gate], dependencies)),
+            GateType::Not => match dependencies[..] {
+                [dependency]
+                    if dependency != gate
+                        && matches!(self.get(dependency).ty, GateType::Or | GateType::And) =>
+                {
+                    Some((vec![gate, dependency], &self.get(dependency).dependencies))
+                }
+                _ => Some((vec![gate], dependencies)),
+            },
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{counter, ram, register, sr_latch, zeros, WordInput};
+
+    #[test]
+    fn test_recognized_latches() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let input = WordInput::new(g, 4, "input");
+        let address = WordInput::new(g, 2, "address");
+        let q = sr_latch(g, clock.bit(), reset.bit(), "latch");
+        g.output1(q, "q");
+        let count = counter(
+            g,
+            clock.bit(),
+            ON,
+            OFF,
+            ON,
+            reset.bit(),
+            &zeros(4),
+            "counter",
+        );
+        g.output(&count, "count");
+        let stored = register(g, clock.bit(), ON, ON, reset.bit(), &count, "register");
+        g.output(&stored, "register");
+        let memory = ram(
+            g,
+            ON,
+            ON,
+            clock.bit(),
+            reset.bit(),
+            &address.bits(),
+            &input.bits(),
+            "ram",
+        );
+        g.output(&memory, "ram");
+        // Or and and gates followed by a not gate work like nor and nand gates.
+        let flip = g.or1(reset.bit(), "flip");
+        let nq = g.not1(flip, "nq");
+        let flop = g.or2(clock.bit(), nq, "flop");
+        let q = g.not1(flop, "q");
+        g.dpush(flip, q);
+        g.output1(q, "q");
+        // Delay gates break loops.
+        let toggle = g.xor1(clock.bit(), "toggle");
+        let delayed = g.delay(toggle, "delayed");
+        g.dpush(toggle, delayed);
+        g.output1(toggle, "toggle");
+
+        assert!(graph.init().warnings().is_empty());
+    }
+
+    #[test]
+    fn test_unintended_latches() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let set = g.lever("set");
+        let enable = g.lever("enable");
+        // Sticky bit.
+        let sticky = g.or1(set.bit(), "sticky");
+        g.dpush(sticky, sticky);
+        let sticky_output = g.output1(sticky, "sticky");
+        // Ring oscillator.
+        let ring = g.nand1(enable.bit(), "ring");
+        g.dpush(ring, ring);
+        g.output1(ring, "ring");
+        let ig = &mut graph.init();
+
+        let warnings = ig.warnings();
+        assert_eq!(warnings.len(), 2);
+        let InitWarning::UnintendedLatch { names } = &warnings[0];
+        assert_eq!(names.len(), 1);
+        assert!(warnings[1].to_string().starts_with("Unintended latch: "));
+        // It still works as a latch.
+        ig.pulse_lever_stable(set);
+        assert_eq!(sticky_output.b0(ig), true);
+    }
+}
@@ -0,0 +1,144 @@
+use super::handles::{OutputHandle, WatchHandle};
+use super::InitializedGateGraph;
+
+/// Predicate registered with [InitializedGateGraph::add_watch].
+type WatchPredicate = Box<dyn FnMut(&[u128]) -> bool + Send>;
+
+/// Watchpoint added with [InitializedGateGraph::add_watch].
+pub(super) struct Watch {
+    outputs: Vec<OutputHandle>,
+    predicate: WatchPredicate,
+}
+
+impl InitializedGateGraph {
+    /// Adds a watchpoint on `outputs`, [run_until_watch](InitializedGateGraph::run_until_watch) stops as soon as
+    /// `predicate` returns true when called with their values.
+    ///
+    /// The values are collected like [OutputHandle::u128], in the same order as `outputs`, so only the first 128
+    /// bits of every output are taken into account.
+    ///
+    /// Returns a [WatchHandle] which can be used to [remove](InitializedGateGraph::remove_watch) the watchpoint.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::graph::{GateGraphBuilder,ON,OFF};
+    /// # use logicsim::{counter,zeros};
+    /// let mut g = GateGraphBuilder::new();
+    /// let clock = g.clock(2, "clock");
+    /// let count = counter(&mut g, clock.bit(), ON, OFF, ON, OFF, &zeros(8), "counter");
+    /// let count = g.output(&count, "count");
+    ///
+    /// let ig = &mut g.init();
+    /// let watch = ig.add_watch(&[count], |values| values[0] == 10);
+    /// assert_eq!(ig.run_until_watch(1000), Some(watch));
+    /// assert_eq!(count.u8(ig), 10);
+    /// ```
+    pub fn add_watch<F: FnMut(&[u128]) -> bool + Send + 'static>(
+        &mut self,
+        outputs: &[OutputHandle],
+        predicate: F,
+    ) -> WatchHandle {
+        for output in outputs {
+            // Panics early for handles of a different graph.
+            self.get_output(*output);
+        }
+        self.watches.push(Some(Watch {
+            outputs: outputs.to_vec(),
+            predicate: Box::new(predicate),
+        }));
+        WatchHandle(self.watches.len() - 1)
+    }
+
+    /// Removes the watchpoint added with [add_watch](InitializedGateGraph::add_watch) that returned `handle`.
+    pub fn remove_watch(&mut self, handle: WatchHandle) {
+        self.watches[handle.0] = None;
+    }
+
+    /// Runs up to `max_ticks` ticks like [step_clocks](InitializedGateGraph::step_clocks), checking the
+    /// watchpoints added with [add_watch](InitializedGateGraph::add_watch) after every one.
+    ///
+    /// Returns the [WatchHandle] of the first watchpoint, in the order they were added, whose predicate returned
+    /// true, [now](InitializedGateGraph::now) tells when it happened. Returns [None] if none did within
+    /// `max_ticks` ticks.
+    ///
+    /// Watchpoints are level triggered, one that is already true stops the run after a single tick.
+    pub fn run_until_watch(&mut self, max_ticks: usize) -> Option<WatchHandle> {
+        if self.watches.iter().all(Option::is_none) {
+            self.step_clocks(max_ticks);
+            return None;
+        }
+        let mut watches = std::mem::take(&mut self.watches);
+        let mut values = Vec::new();
+        let mut hit = None;
+        'ticks: for _ in 0..max_ticks {
+            self.step_clocks(1);
+            for (i, watch) in watches.iter_mut().enumerate() {
+                if let Some(watch) = watch {
+                    values.clear();
+                    values.extend(
+                        watch
+                            .outputs
+                            .iter()
+                            .map(|output| self.collect_u128_lossy(&self.get_output(*output).bits)),
+                    );
+                    if (watch.predicate)(&values) {
+                        hit = Some(WatchHandle(i));
+                        break 'ticks;
+                    }
+                }
+            }
+        }
+        self.watches = watches;
+        hit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use crate::circuits::{counter, zeros};
+
+    #[test]
+    fn test_watches() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let count = counter(
+            g,
+            clock.bit(),
+            ON,
+            OFF,
+            ON,
+            reset.bit(),
+            &zeros(4),
+            "counter",
+        );
+        let count = g.output(&count, "count");
+        let l = g.lever("l");
+        let not = g.not1(l.bit(), "not");
+        let not = g.output1(not, "not");
+        let ig = &mut graph.init();
+        ig.pulse_lever_stable(reset);
+
+        // No clocks, nothing changes.
+        assert_eq!(ig.run_until_watch(10), None);
+        let start = ig.now();
+        let never = ig.add_watch(&[count], |_| false);
+        assert_eq!(ig.run_until_watch(10), None);
+        assert_eq!(ig.now(), start + 10);
+
+        let both = ig.add_watch(&[count, not], |values| values == [0, 0]);
+        ig.set_lever(l);
+        assert_eq!(ig.run_until_watch(10), Some(both));
+        assert_eq!(ig.now(), start + 12);
+
+        // Level triggered.
+        assert_eq!(ig.run_until_watch(10), Some(both));
+        assert_eq!(ig.now(), start + 13);
+        ig.remove_watch(both);
+        ig.remove_watch(never);
+        assert_eq!(ig.run_until_watch(3), None);
+        assert_eq!(ig.now(), start + 16);
+    }
+}
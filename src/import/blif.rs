@@ -0,0 +1,471 @@
+//! Reads combinational and sequential circuits in the
+//! [Berkeley Logic Interchange Format](https://course.ece.cmu.edu/~ee760/760docs/blif.pdf).
+//!
+//! The supported subset is what synthesis tools like Yosys write for flat designs:
+//! `.model`, `.inputs`, `.outputs`, `.names`, `.latch`, `.conn` and `.end`.
+//! Hierarchical designs with `.subckt` or `.gate` need to be flattened first.
+//!
+//! # Example
+//! ```
+//! # use logicsim::import::blif::read_blif;
+//! let blif = "
+//! .model half_adder
+//! .inputs a b
+//! .outputs sum carry
+//! .names a b sum
+//! 01 1
+//! 10 1
+//! .names a b carry
+//! 11 1
+//! .end
+//! ";
+//! let circuit = read_blif(blif.as_bytes()).unwrap();
+//! let a = circuit.input("a").unwrap();
+//! let b = circuit.input("b").unwrap();
+//! let sum = circuit.output("sum").unwrap();
+//! let carry = circuit.output("carry").unwrap();
+//!
+//! let ig = &mut circuit.graph.init();
+//! ig.set_lever_stable(a);
+//! ig.set_lever_stable(b);
+//! assert_eq!(sum.b0(ig), false);
+//! assert_eq!(carry.b0(ig), true);
+//! ```
+use crate::circuits::{d_flip_flop, Wire};
+use crate::graph::*;
+use std::collections::HashMap;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+/// Circuit read by [read_blif].
+#[derive(Debug, Clone)]
+pub struct BlifCircuit {
+    /// Name of the model.
+    pub name: String,
+    /// The circuit, ready to be [initialized](GateGraphBuilder::init).
+    pub graph: GateGraphBuilder,
+    /// A lever for every primary input, in the order they were declared.
+    pub inputs: Vec<(String, LeverHandle)>,
+    /// A single bit output for every primary output, in the order they were declared.
+    pub outputs: Vec<(String, OutputHandle)>,
+    /// Async reset of every latch, [None] if there are no latches.
+    /// Latches start undefined, pulse it to set them to their initial values.
+    pub reset: Option<LeverHandle>,
+}
+impl BlifCircuit {
+    /// Returns the lever of the primary input called `name`.
+    pub fn input(&self, name: &str) -> Option<LeverHandle> {
+        self.inputs
+            .iter()
+            .find(|(input, _)| input == name)
+            .map(|(_, lever)| *lever)
+    }
+
+    /// Returns the output of the primary output called `name`.
+    pub fn output(&self, name: &str) -> Option<OutputHandle> {
+        self.outputs
+            .iter()
+            .find(|(output, _)| output == name)
+            .map(|(_, output)| *output)
+    }
+}
+
+/// Returns an error of kind [InvalidData](io::ErrorKind::InvalidData) for line `line`.
+fn invalid(line: usize, message: String) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("line {}: {}", line, message),
+    )
+}
+
+/// Wires of every signal in the model and which ones are driven.
+struct Signals {
+    wires: HashMap<String, (Wire, bool)>,
+    /// Line where every signal was first used, to report undriven signals.
+    first_use: HashMap<String, usize>,
+}
+impl Signals {
+    /// Returns the [GateIndex] of `name`, creating its wire if it's the first time it's used.
+    fn get(&mut self, g: &mut GateGraphBuilder, name: &str, line: usize) -> GateIndex {
+        self.first_use.entry(name.to_string()).or_insert(line);
+        self.wires
+            .entry(name.to_string())
+            .or_insert_with(|| (Wire::new(g, format!("BLIF:{}", name)), false))
+            .0
+            .bit()
+    }
+
+    /// Connects `driver` to the wire of `name`.
+    fn drive(
+        &mut self,
+        g: &mut GateGraphBuilder,
+        name: &str,
+        driver: GateIndex,
+        line: usize,
+    ) -> io::Result<()> {
+        self.get(g, name, line);
+        let (wire, driven) = self.wires.get_mut(name).unwrap();
+        if *driven {
+            return Err(invalid(line, format!("{} is driven more than once", name)));
+        }
+        *driven = true;
+        wire.connect(g, driver);
+        Ok(())
+    }
+}
+
+/// Reads the `.names` cover of the signals in `names` from `rows`, the last one is the output.
+fn build_cover(
+    g: &mut GateGraphBuilder,
+    signals: &mut Signals,
+    names: &[&str],
+    rows: &[(usize, String)],
+    line: usize,
+) -> io::Result<()> {
+    let (output, inputs) = names.split_last().unwrap();
+    let inputs: Vec<GateIndex> = inputs
+        .iter()
+        .map(|input| signals.get(g, input, line))
+        .collect();
+
+    let mut on_set = None;
+    let mut products = Vec::new();
+    for (row_line, row) in rows {
+        let fields: Vec<&str> = row.split_whitespace().collect();
+        let (plane, value) = match (inputs.len(), fields.as_slice()) {
+            (0, [value]) => ("", *value),
+            (_, [plane, value]) => (*plane, *value),
+            _ => return Err(invalid(*row_line, format!("invalid cover row: {}", row))),
+        };
+        if plane.len() != inputs.len() {
+            return Err(invalid(
+                *row_line,
+                format!("expected {} inputs in the row: {}", inputs.len(), row),
+            ));
+        }
+        let value = match value {
+            "1" => true,
+            "0" => false,
+            _ => {
+                return Err(invalid(
+                    *row_line,
+                    format!("invalid output value: {}", value),
+                ))
+            }
+        };
+        if *on_set.get_or_insert(value) != value {
+            return Err(invalid(
+                *row_line,
+                "rows of the same cover must have the same output value".to_string(),
+            ));
+        }
+
+        let mut literals = Vec::new();
+        for (c, input) in plane.chars().zip(&inputs) {
+            match c {
+                '1' => literals.push(*input),
+                '0' => literals.push(g.not1(*input, format!("BLIF:{}:not", output))),
+                '-' => {}
+                _ => return Err(invalid(*row_line, format!("invalid cover row: {}", row))),
+            }
+        }
+        // Rows without literals are always true, gates without dependencies are not.
+        products.push(if literals.is_empty() {
+            ON
+        } else {
+            g.andx(literals.into_iter(), format!("BLIF:{}:product", output))
+        });
+    }
+
+    let on_set = on_set.unwrap_or(true);
+    let cover = match (products.is_empty(), on_set) {
+        // A cover without rows is always false.
+        (true, _) => OFF,
+        (false, true) => g.orx(products.into_iter(), format!("BLIF:{}", output)),
+        (false, false) => g.norx(products.into_iter(), format!("BLIF:{}", output)),
+    };
+    signals.drive(g, output, cover, line)
+}
+
+/// Reads a BLIF model from `reader` and builds it, see the [module](self) documentation for the supported subset.
+///
+/// Every primary input becomes a lever and every primary output a single bit output, both named after the signal.
+/// Latches become a [d_flip_flop] for the level sensitive types ("ah" and "al") and two of them in a
+/// master slave configuration for the edge triggered ones ("re" and "fe"). Like any other circuit with state, latches start undefined,
+/// pulsing [BlifCircuit::reset] sets the ones with an initial value of 1 to 1 and the rest to 0.
+///
+/// Returns an error of kind [InvalidData](io::ErrorKind::InvalidData) with the line number if the netlist
+/// can't be read, for example if a signal is driven more than once or used but never driven.
+pub fn read_blif<R: BufRead>(reader: R) -> io::Result<BlifCircuit> {
+    // Joins continued lines and strips comments.
+    let mut lines = Vec::new();
+    let mut pending = String::new();
+    let mut pending_line = 0;
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.split('#').next().unwrap().trim_end();
+        if pending.is_empty() {
+            pending_line = i + 1;
+        }
+        match line.strip_suffix('\\') {
+            Some(continued) => {
+                pending.push_str(continued);
+                pending.push(' ');
+            }
+            None => {
+                pending.push_str(line);
+                if !pending.trim().is_empty() {
+                    lines.push((pending_line, pending.trim().to_string()));
+                }
+                pending.clear();
+            }
+        }
+    }
+
+    let mut graph = GateGraphBuilder::new();
+    let g = &mut graph;
+    let mut signals = Signals {
+        wires: HashMap::new(),
+        first_use: HashMap::new(),
+    };
+    let mut name = String::new();
+    let mut inputs = Vec::new();
+    let mut output_names = Vec::new();
+    let mut reset = None;
+
+    let mut i = 0;
+    while i < lines.len() {
+        let (line, text) = &lines[i];
+        let line = *line;
+        i += 1;
+        let mut fields = text.split_whitespace();
+        let command = fields.next().unwrap();
+        let fields: Vec<&str> = fields.collect();
+        match command {
+            ".model" => name = fields.first().unwrap_or(&"").to_string(),
+            ".inputs" => {
+                for input in fields {
+                    let lever = g.lever(input);
+                    signals.drive(g, input, lever.bit(), line)?;
+                    inputs.push((input.to_string(), lever));
+                }
+            }
+            ".outputs" => {
+                for output in fields {
+                    signals.get(g, output, line);
+                    output_names.push(output.to_string());
+                }
+            }
+            ".names" => {
+                if fields.is_empty() {
+                    return Err(invalid(line, ".names without signals".to_string()));
+                }
+                let mut rows = Vec::new();
+                while i < lines.len() && !lines[i].1.starts_with('.') {
+                    rows.push(lines[i].clone());
+                    i += 1;
+                }
+                build_cover(g, &mut signals, &fields, &rows, line)?;
+            }
+            ".conn" => match fields.as_slice() {
+                [from, to] => {
+                    let from = signals.get(g, from, line);
+                    signals.drive(g, to, from, line)?;
+                }
+                _ => return Err(invalid(line, format!("invalid connection: {}", text))),
+            },
+            ".latch" => {
+                let (d, q, ty, control, init) = match fields.as_slice() {
+                    [d, q, ty, control] => (d, q, *ty, control, "3"),
+                    [d, q, ty, control, init] => (d, q, *ty, control, *init),
+                    _ => {
+                        return Err(invalid(
+                            line,
+                            format!(
+                                "only latches with a type and a control are supported: {}",
+                                text
+                            ),
+                        ))
+                    }
+                };
+                let latch_name = format!("BLIF:{}", q);
+                let mut d = signals.get(g, d, line);
+                // Latches that start at 1 store the inverted value so the reset sets them to 1.
+                let inverted = init == "1";
+                if inverted {
+                    d = g.not1(d, latch_name.clone());
+                }
+                let control = signals.get(g, control, line);
+                let reset = reset.get_or_insert_with(|| g.lever("BLIF:reset")).bit();
+                let ncontrol = g.not1(control, latch_name.clone());
+                let latch = match ty {
+                    // Edge triggered latches are a master and a slave level sensitive latch.
+                    "re" | "fe" => {
+                        let (master_enable, slave_enable) = if ty == "re" {
+                            (ncontrol, control)
+                        } else {
+                            (control, ncontrol)
+                        };
+                        let master =
+                            d_flip_flop(g, d, master_enable, reset, ON, ON, latch_name.clone());
+                        d_flip_flop(g, master, slave_enable, reset, ON, ON, latch_name.clone())
+                    }
+                    "ah" => d_flip_flop(g, d, control, reset, ON, ON, latch_name.clone()),
+                    "al" => d_flip_flop(g, d, ncontrol, reset, ON, ON, latch_name.clone()),
+                    _ => return Err(invalid(line, format!("unsupported latch type: {}", ty))),
+                };
+                let latch = if inverted {
+                    g.not1(latch, latch_name)
+                } else {
+                    latch
+                };
+                signals.drive(g, q, latch, line)?;
+            }
+            ".end" => break,
+            _ => return Err(invalid(line, format!("unsupported command: {}", command))),
+        }
+    }
+
+    let mut undriven: Vec<(&usize, &String)> = signals
+        .wires
+        .iter()
+        .filter(|(_, (_, driven))| !driven)
+        .map(|(name, _)| (&signals.first_use[name], name))
+        .collect();
+    undriven.sort();
+    if let Some((line, name)) = undriven.first() {
+        return Err(invalid(**line, format!("{} is never driven", name)));
+    }
+
+    let outputs = output_names
+        .into_iter()
+        .map(|name| {
+            let output = g.output1(signals.wires[&name].0.bit(), name.as_str());
+            (name, output)
+        })
+        .collect();
+    Ok(BlifCircuit {
+        name,
+        graph,
+        inputs,
+        outputs,
+        reset,
+    })
+}
+
+/// Reads the BLIF model in `path`, see [read_blif].
+pub fn load_blif<P: AsRef<Path>>(path: P) -> io::Result<BlifCircuit> {
+    read_blif(io::BufReader::new(std::fs::File::open(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(blif: &str) -> String {
+        read_blif(blif.as_bytes()).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_read_blif_full_adder() {
+        let blif = r"
+# Full adder written with continued lines and an off-set cover.
+.model full_adder
+.inputs a b \
+  cin
+.outputs sum cout
+.names a b cin sum
+100 1
+010 1
+001 1
+111 1
+.names a b cin cout
+00- 0
+0-0 0
+-00 0
+.end
+";
+        let circuit = read_blif(blif.as_bytes()).unwrap();
+        assert_eq!(circuit.name, "full_adder");
+        let levers: Vec<LeverHandle> = circuit.inputs.iter().map(|(_, l)| *l).collect();
+        let sum = circuit.output("sum").unwrap();
+        let cout = circuit.output("cout").unwrap();
+        let ig = &mut circuit.graph.init();
+        for value in 0..8u8 {
+            ig.update_levers(&levers, (0..3).map(|bit| value >> bit & 1 == 1));
+            ig.run_until_stable(10).unwrap();
+            let ones = value.count_ones() as u8;
+            assert_eq!(sum.u8(ig), ones % 2, "{}", value);
+            assert_eq!(cout.u8(ig), ones / 2, "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_read_blif_latches_and_constants() {
+        // A 2 bit counter, the way Yosys writes it.
+        let blif = "
+.model counter
+.inputs clk rst
+.outputs q0 q1 one
+.names $true
+1
+.conn $true one
+.names rst q0 d0
+00 1
+.names rst q0 q1 d1
+001 1
+010 1
+.latch d0 q0 re clk 1
+.latch d1 q1 re clk 2
+.end
+";
+        let circuit = read_blif(blif.as_bytes()).unwrap();
+        let clk = circuit.input("clk").unwrap();
+        let rst = circuit.input("rst").unwrap();
+        let q0 = circuit.output("q0").unwrap();
+        let q1 = circuit.output("q1").unwrap();
+        let one = circuit.output("one").unwrap();
+        let ig = &mut circuit.graph.init();
+        ig.pulse_lever_stable(circuit.reset.unwrap());
+        assert_eq!(q0.u8(ig), 1);
+
+        ig.set_lever_stable(rst);
+        ig.pulse_lever_stable(clk);
+        ig.reset_lever_stable(rst);
+        let mut count = Vec::new();
+        for _ in 0..5 {
+            count.push(q0.u8(ig) | q1.u8(ig) << 1);
+            ig.pulse_lever_stable(clk);
+        }
+        assert_eq!(count, [0, 1, 2, 3, 0]);
+        assert!(one.b0(ig));
+    }
+
+    #[test]
+    fn test_read_blif_errors() {
+        assert_eq!(
+            error(".model m\n.inputs a\n.outputs b\n.names a c b\n11 1\n"),
+            "line 4: c is never driven"
+        );
+        assert_eq!(
+            error(".inputs a\n.names a a\n1 1\n"),
+            "line 2: a is driven more than once"
+        );
+        assert_eq!(
+            error(".inputs a\n.names a b\n1 1\n0 0\n"),
+            "line 4: rows of the same cover must have the same output value"
+        );
+        assert_eq!(
+            error(".inputs a\n.names a b\n11 1\n"),
+            "line 3: expected 1 inputs in the row: 11 1"
+        );
+        assert_eq!(
+            error(".subckt adder a=a\n"),
+            "line 1: unsupported command: .subckt"
+        );
+        assert_eq!(
+            error(".inputs a\n.latch a q as a\n"),
+            "line 2: unsupported latch type: as"
+        );
+    }
+}
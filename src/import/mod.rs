@@ -0,0 +1,5 @@
+//! Importers that build a [GateGraphBuilder](crate::GateGraphBuilder) from netlists written by other tools.
+//!
+//! Only [BLIF](blif) is supported for now, to simulate a Verilog design synthesize it with
+//! [Yosys](https://yosyshq.net/yosys/) and write it with `write_blif`.
+pub mod blif;
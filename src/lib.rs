@@ -16,6 +16,8 @@ All constants and dead gates will be optimized away and the remaining graph simp
 
 **Zero overhead abstractions!**
 
+The [prelude][prelude] brings the builder, the handles and the most common circuits into scope with a single `use logicsim::prelude::*;`.
+
 # Examples
 Simple gates.
 ```
@@ -118,6 +120,21 @@ cd logicsim
 cargo run --release --example computer greeter
 ```
 
+With the "serde" feature you can pass a state file as the second argument, pressing ctrl-c suspends the computer to it
+and the next run resumes from where it was left:
+```sh
+cargo run --release --features serde --example computer greeter greeter.state
+```
+
+The computer is put together by a `ComputerBuilder`, the `COMPUTER_PRESET` environment variable picks one of its presets:
+`interactive` (the default), `headless` or `debug`, which runs at 100Hz with probes on the bus and the registers:
+```sh
+COMPUTER_PRESET=debug RUST_LOG=logicsim=info cargo run --release --example computer hello_world
+```
+
+Instead of a program name you can also pass the path of a ROM image in Intel HEX (.hex) or raw binary (.bin) format,
+which gets loaded with [read_rom_image][read_rom_image].
+
 # Built in circuits
 
 The `circuits` module features a lot of useful pre-built generic components like:
@@ -127,17 +144,45 @@ The `circuits` module features a lot of useful pre-built generic components like
 - [Wire][Wire]
 - [d_flip_flop][d_flip_flop]
 - [rom][rom]
+- [text_display][text_display]
 
 [and many more!][circuits]
 
+The `text_display` example shows a text mode display controller scanning its character RAM and font ROM
+one pixel per clock cycle, while a monitor model rebuilds the frame from the video signals:
+```sh
+cargo run --release --example text_display
+```
+
 # Debugging
 
-Currently there are 2 debugging tools:
+logicsim comes with the following debugging tools:
+
+## Logging
+
+logicsim doesn't print anything by itself, everything goes through the [tracing](https://docs.rs/tracing) facade,
+which also forwards to the [log](https://docs.rs/log) crate if no tracing subscriber is installed.
+This way you can filter, capture or silence the output using the following targets:
+
+- `logicsim::init`: Spans around [init][init] and compaction, and a warning for each of the [warnings][warnings] found.
+- `logicsim::optimizer`: A span around each optimization pass and an info record with the size reduction it achieved
+  and how long it took.
+- `logicsim::probe`: Info records with the values of the [probes][probe].
+- `logicsim::minimize`: Debug records with the progress of [GateGraphBuilder::minimize][minimize].
+
+For example with [env_logger](https://docs.rs/env_logger): `RUST_LOG=logicsim::probe=info cargo run`.
+
+The optimization passes run until none of them can simplify the graph any further.
+To pick which ones run, or get their statistics as values instead of log records,
+use [GateGraphBuilder::init_with_config][init_with_config].
+With [OptimizerConfig::assuming_reset][assuming_reset] it also simplifies the logic that depends on the gates whose
+state after reset was declared with [GateGraphBuilder::assume_after_reset][assume_after_reset].
 
 ## Probes
 
-Calling [GateGraphBuilder::probe][probe] allows you to create probes, which will print the value of all of the bits provided
+Calling [GateGraphBuilder::probe][probe] allows you to create probes, which will log the value of all of the bits provided
 along with their name whenever any of the bits change state within a [tick][tick].
+Every record has a `tick` field with the [simulation time][now] of the change, to correlate it with events and traces.
 
 ## Example:
 ```
@@ -169,19 +214,66 @@ assert_eq!(xor_output.b0(ig), true);
 ig.reset_lever_stable(l2);
 assert_eq!(xor_output.b0(ig), false);
 ```
-In the terminal you'll see:
+With a logger installed, you'll see:
 ```sh
-or_xor: 3
-or_xor: 1
-or_xor: 3
-or_xor: 0
+or_xor:2 tick=2
+or_xor:3 tick=2
+or_xor:1 tick=4
+or_xor:0 tick=4
+or_xor:2 tick=6
+or_xor:3 tick=6
+or_xor:1 tick=8
+or_xor:0 tick=8
 ```
 
+## Logic analyzer
+
+With the "logic_analyzer" feature, [LogicAnalyzer][analyzer] serves a web viewer on a local port where you can watch
+many outputs at once, tick by tick, while the simulation runs:
+
+```ignore
+let analyzer = LogicAnalyzer::serve("127.0.0.1:8080").unwrap();
+analyzer.attach(ig, &[count, carry]);
+// Open http://127.0.0.1:8080 in a browser.
+```
+
+JSON traces written with [InitializedGateGraph::add_json_trace][add_json_trace] can be turned into a self contained
+HTML file with the same viewer with `logicsim::analyzer::write_offline_viewer`.
+
+## Property checking
+
+With the "sat" feature, [InitializedGateGraph::prove_equal][prove_equal] proves that two outputs have the same value
+for every value of the levers with a built in SAT solver, or returns the values for which they differ, and
+[InitializedGateGraph::find_input_satisfying][find_input_satisfying] looks for values of the levers that turn an output on.
+
+## Debugger
+
+With the "repl" feature, [Simulator::debug_repl][debug_repl] pauses a running simulation and opens an interactive
+console where you can read outputs and set levers by name, single step the clock, stop at watchpoints and dump
+the cone of an output to a .dot file:
+
+```text
+> watch pc 0x20
+> step 10000
+watchpoint on pc hit
+cycle 1317
+> print a_register
+a_register = 42 (0x2a)
+```
+
+## Fault injection
+
+[InitializedGateGraph::inject_fault][inject_fault] makes a gate stuck at a state or flips it during the simulation, to
+see how a circuit behaves when the hardware fails, and [InitializedGateGraph::fault_coverage][fault_coverage] measures
+how many stuck at faults a test detects, like the ones [triplicated][triplicate] modules mask.
+
 ## .dot files
 
 Using the method [InitializedGateGraph::dump_dot][dump_dot] you can generate [.dot](https://en.wikipedia.org/wiki/DOT_(graph_description_language))
 files which can be viewed in many different graph viewers. I recommend [gephi](https://gephi.org/), many others can't handle the size of the graphs
 generated by logicsim.
+With [InitializedGateGraph::dump_dot_with_config][dump_dot_with_config] you can dump only the cone of some outputs or probes,
+and group the gates in clusters by module, which keeps big circuits manageable.
 
 For example here is the graph representation of the [8 bit computer](#the-8-bit-computer):
 
@@ -191,11 +283,19 @@ If we zoom in a bit we can see each node is labeled with its name which can help
 
 <img src="https://i.imgur.com/4Y5SOx0.png" width="400px" height="271px">
 
+# Benchmarks
+
+The "bench" feature enables [Criterion](https://docs.rs/criterion) benchmarks of initialization, simulation and every
+optimization pass on a set of reference circuits, including the 8 bit computer, so performance can be compared across releases:
+```sh
+cargo bench --features bench
+```
+
 # Next steps
 
-- Better debugging: I want a gui where I can see many outputs at once with logic-analyzer-like features, probably web based.
-- More thorough optimization testing and documentation: I have documented and tested a lot of the public API surface but the optimizations folder
-needs some love.
+- Better debugging: the [logic analyzer](#logic-analyzer) is a first step, next it needs triggers and a way to show probes.
+- More thorough optimization documentation: every pass is now tested on its own against golden netlists and random graphs,
+  but the optimizations folder still needs some documentation love.
 - RISC-V: I want to test out the limits of logicsim by implementing a RISC-V core and running Rust programs in it!
 - Compiling: Right now logicsim is just an interpreter, I might try making it compile circuits to either Rust or x86_64 directly.
 - Synthesizing: I have a nice fpga dev kit next to me and it would be pretty cool if I could synthesize circuits built in logicsim into it.
@@ -212,15 +312,45 @@ needs some love.
 [Wire]: https://docs.rs/logicsim/0.1.7/logicsim/circuits/struct.Wire.html
 [d_flip_flop]: https://docs.rs/logicsim/0.1.7/logicsim/circuits/fn.d_flip_flop.html
 [rom]: https://docs.rs/logicsim/0.1.7/logicsim/circuits/fn.rom.html
+[text_display]: https://docs.rs/logicsim/0.1.7/logicsim/circuits/fn.text_display.html
 [circuits]: https://docs.rs/logicsim/0.1.7/logicsim/circuits/index.html
+[prelude]: https://docs.rs/logicsim/0.1.7/logicsim/prelude/index.html
 [probe]: https://docs.rs/logicsim/0.1.7/logicsim/graph/struct.GateGraphBuilder.html#method.probe
+[init]: https://docs.rs/logicsim/0.1.7/logicsim/graph/struct.GateGraphBuilder.html#method.init
+[warnings]: https://docs.rs/logicsim/0.1.7/logicsim/graph/struct.InitializedGateGraph.html#method.warnings
+[minimize]: https://docs.rs/logicsim/0.1.7/logicsim/graph/struct.GateGraphBuilder.html#method.minimize
+[init_with_config]: https://docs.rs/logicsim/0.1.7/logicsim/graph/struct.GateGraphBuilder.html#method.init_with_config
+[assuming_reset]: https://docs.rs/logicsim/0.1.7/logicsim/graph/struct.OptimizerConfig.html#method.assuming_reset
+[assume_after_reset]: https://docs.rs/logicsim/0.1.7/logicsim/graph/struct.GateGraphBuilder.html#method.assume_after_reset
+[read_rom_image]: https://docs.rs/logicsim/0.1.7/logicsim/circuits/fn.read_rom_image.html
 [tick]: https://docs.rs/logicsim/0.1.7/logicsim/graph/struct.InitializedGateGraph.html#method.tick
+[now]: https://docs.rs/logicsim/0.1.7/logicsim/graph/struct.InitializedGateGraph.html#method.now
 [dump_dot]: https://docs.rs/logicsim/0.1.7/logicsim/graph/struct.InitializedGateGraph.html#method.dump_dot
+[dump_dot_with_config]: https://docs.rs/logicsim/0.1.7/logicsim/graph/struct.InitializedGateGraph.html#method.dump_dot_with_config
+[add_json_trace]: https://docs.rs/logicsim/0.1.7/logicsim/graph/struct.InitializedGateGraph.html#method.add_json_trace
+[prove_equal]: https://docs.rs/logicsim/0.1.7/logicsim/graph/struct.InitializedGateGraph.html#method.prove_equal
+[inject_fault]: https://docs.rs/logicsim/0.1.7/logicsim/graph/struct.InitializedGateGraph.html#method.inject_fault
+[fault_coverage]: https://docs.rs/logicsim/0.1.7/logicsim/graph/struct.InitializedGateGraph.html#method.fault_coverage
+[triplicate]: https://docs.rs/logicsim/0.1.7/logicsim/graph/struct.GateGraphBuilder.html#method.triplicate
+[find_input_satisfying]: https://docs.rs/logicsim/0.1.7/logicsim/graph/struct.InitializedGateGraph.html#method.find_input_satisfying
+[analyzer]: https://docs.rs/logicsim/0.1.7/logicsim/analyzer/struct.LogicAnalyzer.html
+[debug_repl]: https://docs.rs/logicsim/0.1.7/logicsim/graph/struct.Simulator.html#method.debug_repl
 */
+// The tests compare against literal bools on purpose, it reads better next to the truth tables.
+#![cfg_attr(test, allow(clippy::bool_assert_comparison))]
 #[macro_use]
 pub mod graph;
+#[cfg(feature = "logic_analyzer")]
+pub mod analyzer;
+#[cfg(feature = "bench")]
+pub mod bench;
 pub mod data_structures;
+#[doc(hidden)]
 pub extern crate concat_idents;
 pub mod circuits;
+pub mod import;
+pub mod prelude;
+pub mod testing;
+pub mod verify;
 pub use circuits::*;
 pub use graph::*;
@@ -0,0 +1,27 @@
+//! The most commonly used parts of logicsim, glob import it to get started quickly.
+//!
+//! # Example
+//! ```
+//! use logicsim::prelude::*;
+//!
+//! let mut g = GateGraphBuilder::new();
+//! let input = WordInput::new(&mut g, 8, "input");
+//! let result = adder(&mut g, OFF, &input.bits(), &constant(2u8), "adder");
+//! let output = g.output(&result, "result");
+//!
+//! let ig = &mut g.init();
+//! input.set_to(ig, 40u8);
+//! ig.run_until_stable(100).unwrap();
+//! assert_eq!(output.u8(ig), 42);
+//! ```
+pub use crate::circuits::{
+    adder, bus_multiplexer, constant, counter, d_flip_flop, decoder, multiplexer, ones, ram,
+    register, rom, sr_latch, zeros, Bus, Wire, WordInput,
+};
+pub use crate::graph::{
+    GateGraphBuilder, GateIndex, InitializedGateGraph, LeverHandle, OutputHandle, OFF, ON,
+};
+pub use crate::{
+    assert_propagation, assert_propagation_range, circuit, control_signal_set, signals_to_bits,
+    wire,
+};
@@ -0,0 +1,158 @@
+use crate::graph::Event;
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+/// Performance counters aggregated from the [events](crate::graph::GateGraphBuilder::event) of a simulation,
+/// meant for comparing microarchitectural changes to a CPU quantitatively.
+///
+/// The harness driving the simulation counts clock cycles with [PerfCounters::cycle] and feeds it the events
+/// with [PerfCounters::record], every event name becomes a counter. By convention CPUs declare the events
+/// [INSTRUCTIONS_RETIRED], [STALLS], [MEMORY_READS] and [MEMORY_WRITES], [PerfCounters::ipc] relies on the first one.
+///
+/// Its [Display] implementation prints every counter along with its rate per cycle.
+///
+/// # Example
+/// ```
+/// # use logicsim::GateGraphBuilder;
+/// # use logicsim::testing::{PerfCounters,INSTRUCTIONS_RETIRED};
+/// let mut g = GateGraphBuilder::new();
+/// let clock = g.lever("clock");
+/// let retire = g.lever("retire");
+/// g.event(INSTRUCTIONS_RETIRED, retire.bit(), &[]);
+///
+/// let ig = &mut g.init();
+/// let mut counters = PerfCounters::new();
+/// for cycle in 0..4 {
+///     ig.update_lever(retire, cycle % 2 == 0);
+///     ig.pulse_lever_stable(clock);
+///     counters.cycle();
+///     counters.record(&ig.take_events());
+/// }
+/// println!("{}", counters);
+///
+/// assert_eq!(counters.count(INSTRUCTIONS_RETIRED), 2);
+/// assert_eq!(counters.ipc(), 0.5);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PerfCounters {
+    cycles: u64,
+    counts: BTreeMap<String, u64>,
+}
+
+/// Name of the event CPUs declare every time they finish an instruction, see [PerfCounters].
+pub const INSTRUCTIONS_RETIRED: &str = "instructions_retired";
+/// Name of the event CPUs declare every time they wait a cycle without doing useful work, see [PerfCounters].
+pub const STALLS: &str = "stalls";
+/// Name of the event CPUs declare every time they read memory, see [PerfCounters].
+pub const MEMORY_READS: &str = "memory_reads";
+/// Name of the event CPUs declare every time they write memory, see [PerfCounters].
+pub const MEMORY_WRITES: &str = "memory_writes";
+
+impl PerfCounters {
+    /// Returns a new [PerfCounters] with every counter at 0.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Counts a clock cycle.
+    pub fn cycle(&mut self) {
+        self.cycles += 1;
+    }
+
+    /// Counts every event in `events` under its name.
+    pub fn record(&mut self, events: &[Event]) {
+        for event in events {
+            *self.counts.entry(event.name.clone()).or_default() += 1;
+        }
+    }
+
+    /// Returns the number of clock cycles counted.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Returns the number of events named `name` recorded.
+    pub fn count(&self, name: &str) -> u64 {
+        self.counts.get(name).copied().unwrap_or(0)
+    }
+
+    /// Returns the number of events named `name` per clock cycle, 0 if no cycles have been counted.
+    pub fn per_cycle(&self, name: &str) -> f64 {
+        if self.cycles == 0 {
+            return 0.;
+        }
+        self.count(name) as f64 / self.cycles as f64
+    }
+
+    /// Returns the instructions per cycle, the number of [INSTRUCTIONS_RETIRED] events per clock cycle.
+    pub fn ipc(&self) -> f64 {
+        self.per_cycle(INSTRUCTIONS_RETIRED)
+    }
+
+    /// Sets every counter back to 0.
+    pub fn reset(&mut self) {
+        self.cycles = 0;
+        self.counts.clear();
+    }
+}
+impl Display for PerfCounters {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let width = self
+            .counts
+            .keys()
+            .map(String::len)
+            .chain(std::iter::once("cycles".len()))
+            .max()
+            .unwrap_or(0);
+        writeln!(f, "{:width$} {:>12}", "cycles", self.cycles, width = width)?;
+        for (name, count) in &self.counts {
+            writeln!(
+                f,
+                "{:width$} {:>12} {:>8.3}/cycle",
+                name,
+                count,
+                self.per_cycle(name),
+                width = width
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(name: &str) -> Event {
+        Event {
+            name: name.to_string(),
+            value: 0,
+            tick: 0,
+        }
+    }
+
+    #[test]
+    fn test_perf_counters() {
+        let mut counters = PerfCounters::new();
+        assert_eq!(counters.ipc(), 0.);
+
+        for _ in 0..10 {
+            counters.cycle();
+        }
+        counters.record(&[event(INSTRUCTIONS_RETIRED), event(MEMORY_READS)]);
+        counters.record(&[event(INSTRUCTIONS_RETIRED)]);
+
+        assert_eq!(counters.cycles(), 10);
+        assert_eq!(counters.count(INSTRUCTIONS_RETIRED), 2);
+        assert_eq!(counters.count(STALLS), 0);
+        assert_eq!(counters.ipc(), 0.2);
+        assert_eq!(counters.per_cycle(MEMORY_READS), 0.1);
+
+        let report = counters.to_string();
+        assert!(report.contains(INSTRUCTIONS_RETIRED));
+        assert!(!report.contains(STALLS));
+
+        counters.reset();
+        assert_eq!(counters, PerfCounters::new());
+    }
+}
@@ -0,0 +1,566 @@
+use super::Waveform;
+use crate::circuits::*;
+use crate::graph::*;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Circuit in the [gallery], `run` builds it, drives it and returns it along with the recording of its signals.
+struct Specimen {
+    name: &'static str,
+    description: &'static str,
+    run: fn() -> (InitializedGateGraph, Waveform),
+}
+
+const SPECIMENS: &[Specimen] = &[
+    Specimen {
+        name: "adder",
+        description: "4 bit adder adding pairs of numbers, the sum wraps around.",
+        run: adder_specimen,
+    },
+    Specimen {
+        name: "aluish",
+        description: "4 bit aluish adding, then subtracting by inverting input2 and setting cin.",
+        run: aluish_specimen,
+    },
+    Specimen {
+        name: "counter",
+        description: "4 bit counter which gets disabled for a while and then overwritten.",
+        run: counter_specimen,
+    },
+    Specimen {
+        name: "register",
+        description: "4 bit register which only stores its input while write is active.",
+        run: register_specimen,
+    },
+    Specimen {
+        name: "ram",
+        description: "4 words of 4 bits of ram, written and then read back.",
+        run: ram_specimen,
+    },
+    Specimen {
+        name: "rom",
+        description: "rom with the data [3, 1, 4, 1] read at every address.",
+        run: rom_specimen,
+    },
+    Specimen {
+        name: "decoder",
+        description: "2 bit decoder activating one of its 4 outputs.",
+        run: decoder_specimen,
+    },
+    Specimen {
+        name: "multiplexer",
+        description: "multiplexer selecting each of the bits of its input.",
+        run: multiplexer_specimen,
+    },
+    Specimen {
+        name: "d_flip_flop",
+        description: "d_flip_flop storing d on every clock cycle.",
+        run: d_flip_flop_specimen,
+    },
+    Specimen {
+        name: "sr_latch",
+        description: "sr_latch being set and reset.",
+        run: sr_latch_specimen,
+    },
+    Specimen {
+        name: "johnson_counter",
+        description: "4 bit johnson_counter going through its 8 states.",
+        run: johnson_counter_specimen,
+    },
+    Specimen {
+        name: "ring_counter",
+        description: "4 bit ring_counter rotating its single active bit.",
+        run: ring_counter_specimen,
+    },
+    Specimen {
+        name: "matches_constant",
+        description: "matches_constant recognizing 5 among all 3 bit numbers.",
+        run: matches_constant_specimen,
+    },
+    Specimen {
+        name: "majority",
+        description: "majority of 3 bits, for every combination of them.",
+        run: majority_specimen,
+    },
+];
+
+/// Builds every component of the [circuits](crate::circuits) module at a small width, drives it with a canonical
+/// stimulus and writes the results to `dir`, which gets created if it doesn't exist:
+///
+/// - `<name>.vcd` the waveform of the inputs and outputs of the component, see [Waveform::write_vcd].
+/// - `<name>.svg` the same waveform rendered as a timing diagram, see [Waveform::write_svg].
+/// - `<name>.dot` the schematic of the optimized circuit, see [InitializedGateGraph::dump_dot].
+/// - `index.md` a page with a description and the timing diagram of every component.
+///
+/// The gallery doubles as documentation and as regression material, diffing the VCD files of two versions of
+/// logicsim shows any change in behavior. Run it with `cargo run --example gallery <dir>`.
+///
+/// Returns the names of the components.
+///
+/// # Example
+/// ```
+/// # use logicsim::testing::gallery;
+/// let dir = std::env::temp_dir().join("logicsim_gallery_doctest");
+/// let names = gallery(&dir).unwrap();
+///
+/// assert!(names.contains(&"counter"));
+/// assert!(dir.join("counter.vcd").exists());
+/// assert!(dir.join("counter.svg").exists());
+/// assert!(dir.join("counter.dot").exists());
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn gallery<P: AsRef<Path>>(dir: P) -> io::Result<Vec<&'static str>> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+
+    let mut index = BufWriter::new(File::create(dir.join("index.md"))?);
+    writeln!(index, "# logicsim circuit gallery")?;
+    for specimen in SPECIMENS {
+        let (ig, waveform) = (specimen.run)();
+        waveform.write_vcd(BufWriter::new(File::create(
+            dir.join(format!("{}.vcd", specimen.name)),
+        )?))?;
+        waveform.write_svg(BufWriter::new(File::create(
+            dir.join(format!("{}.svg", specimen.name)),
+        )?))?;
+        ig.dump_dot(dir.join(format!("{}.dot", specimen.name)))?;
+
+        writeln!(index)?;
+        writeln!(index, "## {}", specimen.name)?;
+        writeln!(index)?;
+        writeln!(index, "{}", specimen.description)?;
+        writeln!(index)?;
+        writeln!(index, "![{0}]({0}.svg)", specimen.name)?;
+        writeln!(
+            index,
+            "[waveform]({0}.vcd) [schematic]({0}.dot)",
+            specimen.name
+        )?;
+    }
+    index.flush()?;
+
+    Ok(SPECIMENS.iter().map(|specimen| specimen.name).collect())
+}
+
+/// Returns a new [WordInput] along with an output of its bits, to record it.
+fn word(g: &mut GateGraphBuilder, width: usize, name: &str) -> (WordInput, OutputHandle) {
+    let input = WordInput::new(g, width, name);
+    let output = g.output(&input.bits(), name);
+    (input, output)
+}
+
+fn adder_specimen() -> (InitializedGateGraph, Waveform) {
+    let mut g = GateGraphBuilder::new();
+    let (a, a_output) = word(&mut g, 4, "a");
+    let (b, b_output) = word(&mut g, 4, "b");
+    let sum = adder(&mut g, OFF, &a.bits(), &b.bits(), "adder");
+    let sum = g.output(&sum, "sum");
+
+    let mut initialized = g.init();
+    let ig = &mut initialized;
+    let mut waveform = Waveform::new()
+        .signal("a", a_output, 4)
+        .signal("b", b_output, 4)
+        .signal("sum", sum, 4);
+    for (a_value, b_value) in [(1u8, 2u8), (3, 5), (7, 9), (15, 1), (10, 10)].iter() {
+        a.set_to(ig, *a_value);
+        b.set_to(ig, *b_value);
+        ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        waveform.sample(ig);
+    }
+    (initialized, waveform)
+}
+
+fn aluish_specimen() -> (InitializedGateGraph, Waveform) {
+    let mut g = GateGraphBuilder::new();
+    let (a, a_output) = word(&mut g, 4, "a");
+    let (b, b_output) = word(&mut g, 4, "b");
+    let subtract = g.lever("subtract");
+    let subtract_output = g.output1(subtract.bit(), "subtract");
+    let result = aluish(
+        &mut g,
+        subtract.bit(),
+        ON,
+        subtract.bit(),
+        &a.bits(),
+        &b.bits(),
+        "alu",
+    );
+    let result = g.output(&result, "result");
+
+    let mut initialized = g.init();
+    let ig = &mut initialized;
+    let mut waveform = Waveform::new()
+        .signal("a", a_output, 4)
+        .signal("b", b_output, 4)
+        .signal("subtract", subtract_output, 1)
+        .signal("result", result, 4);
+    for (a_value, b_value, sub) in
+        [(5u8, 3u8, false), (9, 4, false), (5, 3, true), (9, 4, true)].iter()
+    {
+        a.set_to(ig, *a_value);
+        b.set_to(ig, *b_value);
+        ig.update_lever(subtract, *sub);
+        ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        waveform.sample(ig);
+    }
+    (initialized, waveform)
+}
+
+fn counter_specimen() -> (InitializedGateGraph, Waveform) {
+    let mut g = GateGraphBuilder::new();
+    let clock = g.lever("clock");
+    let reset = g.lever("reset");
+    let enable = g.lever("enable");
+    let write = g.lever("write");
+    let (input, input_output) = word(&mut g, 4, "input");
+    let count = counter(
+        &mut g,
+        clock.bit(),
+        enable.bit(),
+        write.bit(),
+        ON,
+        reset.bit(),
+        &input.bits(),
+        "counter",
+    );
+    let count = g.output(&count, "count");
+    let enable_output = g.output1(enable.bit(), "enable");
+    let write_output = g.output1(write.bit(), "write");
+
+    let mut initialized = g.init();
+    let ig = &mut initialized;
+    ig.pulse_lever_stable(reset);
+    input.set_to(ig, 12u8);
+    let mut waveform = Waveform::new()
+        .signal("enable", enable_output, 1)
+        .signal("write", write_output, 1)
+        .signal("input", input_output, 4)
+        .signal("count", count, 4);
+    for cycle in 0..12 {
+        ig.update_lever(enable, !(4..6).contains(&cycle));
+        ig.update_lever(write, cycle == 8);
+        ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        ig.pulse_lever_stable(clock);
+        waveform.sample(ig);
+    }
+    (initialized, waveform)
+}
+
+fn register_specimen() -> (InitializedGateGraph, Waveform) {
+    let mut g = GateGraphBuilder::new();
+    let clock = g.lever("clock");
+    let reset = g.lever("reset");
+    let write = g.lever("write");
+    let (input, input_output) = word(&mut g, 4, "input");
+    let output = register(
+        &mut g,
+        clock.bit(),
+        write.bit(),
+        ON,
+        reset.bit(),
+        &input.bits(),
+        "register",
+    );
+    let output = g.output(&output, "output");
+    let write_output = g.output1(write.bit(), "write");
+
+    let mut initialized = g.init();
+    let ig = &mut initialized;
+    ig.pulse_lever_stable(reset);
+    let mut waveform = Waveform::new()
+        .signal("write", write_output, 1)
+        .signal("input", input_output, 4)
+        .signal("output", output, 4);
+    for cycle in 0..8u8 {
+        input.set_to(ig, cycle * 3);
+        ig.update_lever(write, cycle % 3 == 0);
+        ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        ig.pulse_lever_stable(clock);
+        waveform.sample(ig);
+    }
+    (initialized, waveform)
+}
+
+fn ram_specimen() -> (InitializedGateGraph, Waveform) {
+    let mut g = GateGraphBuilder::new();
+    let clock = g.lever("clock");
+    let reset = g.lever("reset");
+    let write = g.lever("write");
+    let read = g.lever("read");
+    let (address, address_output) = word(&mut g, 2, "address");
+    let (input, input_output) = word(&mut g, 4, "input");
+    let output = ram(
+        &mut g,
+        read.bit(),
+        write.bit(),
+        clock.bit(),
+        reset.bit(),
+        &address.bits(),
+        &input.bits(),
+        "ram",
+    );
+    let output = g.output(&output, "output");
+    let write_output = g.output1(write.bit(), "write");
+    let read_output = g.output1(read.bit(), "read");
+
+    let mut initialized = g.init();
+    let ig = &mut initialized;
+    ig.pulse_lever_stable(reset);
+    let mut waveform = Waveform::new()
+        .signal("write", write_output, 1)
+        .signal("read", read_output, 1)
+        .signal("address", address_output, 2)
+        .signal("input", input_output, 4)
+        .signal("output", output, 4);
+    for cycle in 0..8u8 {
+        let writing = cycle < 4;
+        address.set_to(ig, cycle % 4);
+        input.set_to(ig, if writing { 9 - cycle * 2 } else { 0 });
+        ig.update_lever(write, writing);
+        ig.update_lever(read, !writing);
+        ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        ig.pulse_lever_stable(clock);
+        waveform.sample(ig);
+    }
+    (initialized, waveform)
+}
+
+fn rom_specimen() -> (InitializedGateGraph, Waveform) {
+    let mut g = GateGraphBuilder::new();
+    let (address, address_output) = word(&mut g, 2, "address");
+    let output = rom(&mut g, ON, &address.bits(), &[3u8, 1, 4, 1], "rom");
+    let output = g.output(&output, "output");
+
+    let mut initialized = g.init();
+    let ig = &mut initialized;
+    let mut waveform = Waveform::new()
+        .signal("address", address_output, 2)
+        .signal("output", output, 8);
+    for value in 0..4u8 {
+        address.set_to(ig, value);
+        ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        waveform.sample(ig);
+    }
+    (initialized, waveform)
+}
+
+fn decoder_specimen() -> (InitializedGateGraph, Waveform) {
+    let mut g = GateGraphBuilder::new();
+    let (address, address_output) = word(&mut g, 2, "address");
+    let outputs = decoder(&mut g, &address.bits(), "decoder");
+    let outputs: Vec<_> = outputs
+        .iter()
+        .enumerate()
+        .map(|(i, bit)| g.output1(*bit, format!("output{}", i)))
+        .collect();
+
+    let mut initialized = g.init();
+    let ig = &mut initialized;
+    let mut waveform = Waveform::new().signal("address", address_output, 2);
+    for (i, output) in outputs.into_iter().enumerate() {
+        waveform = waveform.signal(format!("output{}", i), output, 1);
+    }
+    for value in 0..4u8 {
+        address.set_to(ig, value);
+        ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        waveform.sample(ig);
+    }
+    (initialized, waveform)
+}
+
+fn multiplexer_specimen() -> (InitializedGateGraph, Waveform) {
+    let mut g = GateGraphBuilder::new();
+    let (address, address_output) = word(&mut g, 2, "address");
+    let (input, input_output) = word(&mut g, 4, "input");
+    let output = multiplexer(&mut g, &address.bits(), &input.bits(), "multiplexer");
+    let output = g.output1(output, "output");
+
+    let mut initialized = g.init();
+    let ig = &mut initialized;
+    input.set_to(ig, 0b0110u8);
+    let mut waveform = Waveform::new()
+        .signal("input", input_output, 4)
+        .signal("address", address_output, 2)
+        .signal("output", output, 1);
+    for value in 0..4u8 {
+        address.set_to(ig, value);
+        ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        waveform.sample(ig);
+    }
+    (initialized, waveform)
+}
+
+fn d_flip_flop_specimen() -> (InitializedGateGraph, Waveform) {
+    let mut g = GateGraphBuilder::new();
+    let clock = g.lever("clock");
+    let reset = g.lever("reset");
+    let d = g.lever("d");
+    let q = d_flip_flop(
+        &mut g,
+        d.bit(),
+        clock.bit(),
+        reset.bit(),
+        ON,
+        ON,
+        "flip_flop",
+    );
+    let q = g.output1(q, "q");
+    let d_output = g.output1(d.bit(), "d");
+
+    let mut initialized = g.init();
+    let ig = &mut initialized;
+    ig.pulse_lever_stable(reset);
+    let mut waveform = Waveform::new().signal("d", d_output, 1).signal("q", q, 1);
+    for value in [true, true, false, true, false, false].iter() {
+        // Flip d while the clock is active, the flip flop follows it right away.
+        ig.set_lever_stable(clock);
+        ig.update_lever(d, *value);
+        ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        ig.reset_lever_stable(clock);
+        waveform.sample(ig);
+    }
+    (initialized, waveform)
+}
+
+fn sr_latch_specimen() -> (InitializedGateGraph, Waveform) {
+    let mut g = GateGraphBuilder::new();
+    let s = g.lever("s");
+    let r = g.lever("r");
+    let q = sr_latch(&mut g, s.bit(), r.bit(), "latch");
+    let q = g.output1(q, "q");
+    let s_output = g.output1(s.bit(), "s");
+    let r_output = g.output1(r.bit(), "r");
+
+    let mut initialized = g.init();
+    let ig = &mut initialized;
+    ig.pulse_lever_stable(r);
+    let mut waveform = Waveform::new()
+        .signal("s", s_output, 1)
+        .signal("r", r_output, 1)
+        .signal("q", q, 1);
+    for (s_value, r_value) in [
+        (false, false),
+        (true, false),
+        (false, false),
+        (false, true),
+        (false, false),
+    ]
+    .iter()
+    {
+        ig.update_lever(s, *s_value);
+        ig.update_lever(r, *r_value);
+        ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        waveform.sample(ig);
+    }
+    (initialized, waveform)
+}
+
+/// Drives a counter built by `build` from its clock, enable and reset for `cycles` cycles.
+fn free_running_counter(
+    build: fn(&mut GateGraphBuilder, GateIndex, GateIndex, GateIndex) -> Vec<GateIndex>,
+    cycles: usize,
+) -> (InitializedGateGraph, Waveform) {
+    let mut g = GateGraphBuilder::new();
+    let clock = g.lever("clock");
+    let reset = g.lever("reset");
+    let count = build(&mut g, clock.bit(), ON, reset.bit());
+    let width = count.len();
+    let count = g.output(&count, "count");
+
+    let mut initialized = g.init();
+    let ig = &mut initialized;
+    ig.pulse_lever_stable(reset);
+    let mut waveform = Waveform::new().signal("count", count, width);
+    for _ in 0..cycles {
+        waveform.sample(ig);
+        ig.pulse_lever_stable(clock);
+    }
+    (initialized, waveform)
+}
+
+fn johnson_counter_specimen() -> (InitializedGateGraph, Waveform) {
+    free_running_counter(
+        |g, clock, enable, reset| johnson_counter(g, clock, enable, reset, 4, "johnson"),
+        9,
+    )
+}
+
+fn ring_counter_specimen() -> (InitializedGateGraph, Waveform) {
+    free_running_counter(
+        |g, clock, enable, reset| ring_counter(g, clock, enable, reset, 4, "ring"),
+        5,
+    )
+}
+
+/// Drives a combinational circuit built by `build` with every value of a `width` bit input.
+fn exhaustive(
+    build: fn(&mut GateGraphBuilder, &[GateIndex]) -> GateIndex,
+    width: usize,
+) -> (InitializedGateGraph, Waveform) {
+    let mut g = GateGraphBuilder::new();
+    let (input, input_output) = word(&mut g, width, "input");
+    let output = build(&mut g, &input.bits());
+    let output = g.output1(output, "output");
+
+    let mut initialized = g.init();
+    let ig = &mut initialized;
+    let mut waveform = Waveform::new()
+        .signal("input", input_output, width)
+        .signal("output", output, 1);
+    for value in 0..1u32 << width {
+        input.set_to(ig, value);
+        ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        waveform.sample(ig);
+    }
+    (initialized, waveform)
+}
+
+fn matches_constant_specimen() -> (InitializedGateGraph, Waveform) {
+    exhaustive(|g, input| matches_constant(g, input, 5u8, "matches"), 3)
+}
+
+fn majority_specimen() -> (InitializedGateGraph, Waveform) {
+    exhaustive(|g, input| majority(g, input, "majority"), 3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_specimens_behave() {
+        let values = |specimen: &str, signal: &str| {
+            let specimen = SPECIMENS.iter().find(|s| s.name == specimen).unwrap();
+            (specimen.run)().1.values(signal).unwrap()
+        };
+        assert_eq!(values("adder", "sum"), [3, 8, 0, 0, 4]);
+        assert_eq!(values("aluish", "result"), [8, 13, 2, 5]);
+        assert_eq!(
+            values("counter", "count"),
+            [1, 2, 3, 4, 4, 4, 5, 6, 12, 13, 14, 15]
+        );
+        assert_eq!(values("ram", "output"), [0, 0, 0, 0, 9, 7, 5, 3]);
+        assert_eq!(values("rom", "output"), [3, 1, 4, 1]);
+        assert_eq!(values("ring_counter", "count"), [1, 2, 4, 8, 1]);
+        assert_eq!(
+            values("matches_constant", "output"),
+            [0, 0, 0, 0, 0, 1, 0, 0]
+        );
+        assert_eq!(values("majority", "output"), [0, 0, 0, 1, 0, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_gallery_index() {
+        let dir = std::env::temp_dir().join("logicsim_gallery_test");
+        let names = gallery(&dir).unwrap();
+        assert_eq!(names.len(), SPECIMENS.len());
+
+        let index = std::fs::read_to_string(dir.join("index.md")).unwrap();
+        assert!(index.contains("## adder\n\n4 bit adder adding"));
+        assert!(index.contains("![ram](ram.svg)"));
+        assert!(!index.contains("crate::"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,17 @@
+//! Utilities to test circuits built with logicsim.
+mod counters;
+mod gallery;
+mod optimizations;
+mod random_graph;
+mod stimulus;
+mod test_bench;
+mod waveform;
+mod widths;
+pub use counters::*;
+pub use gallery::gallery;
+pub use optimizations::{check_optimizations, random_stimulus};
+pub use random_graph::*;
+pub use stimulus::*;
+pub use test_bench::{Mismatch, TestBench, TestBenchError};
+pub use waveform::Waveform;
+pub use widths::{check_circuit_widths, for_each_width, SMOKE_WIDTHS};
@@ -0,0 +1,140 @@
+use crate::data_structures::SplitMix64;
+use crate::graph::*;
+
+/// Returns a stimulus for [check_optimizations] that sets every lever to a pseudo random value each cycle,
+/// the same `seed` always generates the same sequence.
+pub fn random_stimulus(seed: u64) -> impl FnMut(usize, &mut [bool]) {
+    let mut rng = SplitMix64(seed);
+    move |_, levers| {
+        for lever in levers {
+            *lever = rng.one_in(2);
+        }
+    }
+}
+
+/// Checks that the optimizations don't change the behavior of the circuit in `builder`.
+///
+/// Initializes one optimized and one unoptimized copy of `builder` and drives both with the same stimulus
+/// for `cycles` cycles. Every cycle, `stimulus` gets the cycle number and the values of every lever in the
+/// order they were created, holding the values of the previous cycle, it can change any of them.
+/// Then both graphs run until they are stable and all of their outputs are compared as [u128].
+///
+/// The outputs are only compared once the graphs are stable, the optimizations change how many ticks
+/// it takes for a change to propagate so the intermediate states don't have to match.
+///
+/// Circuits with state start undefined, `stimulus` must reset them in the first cycles,
+/// if both copies start in different states the check will fail. In the same way, changing the inputs of a
+/// flip flop in the same cycle as its clock edge is a race whose winner can change with the optimizations.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,counter,constant,ON,OFF};
+/// # use logicsim::testing::check_optimizations;
+/// let mut g = GateGraphBuilder::new();
+/// let clock = g.lever("clock");
+/// let reset = g.lever("reset");
+/// let count = counter(&mut g, clock.bit(), ON, OFF, ON, reset.bit(), &constant(0u8), "counter");
+/// g.output(&count, "count");
+///
+/// // Levers are in creation order: clock, reset.
+/// check_optimizations(
+///     &g,
+///     |cycle, levers| {
+///         levers[0] = cycle % 2 == 1;
+///         levers[1] = cycle < 2;
+///     },
+///     40,
+/// );
+/// ```
+/// # Panics
+///
+/// Will panic if any output is different between the two graphs or if either of them doesn't stabilize
+/// in [DEFAULT_STABLE_MAX] ticks.
+pub fn check_optimizations<F: FnMut(usize, &mut [bool])>(
+    builder: &GateGraphBuilder,
+    mut stimulus: F,
+    cycles: usize,
+) {
+    let levers = builder.lever_handles();
+    let outputs = builder.output_handles();
+    let optimized = &mut builder.clone().init();
+    let reference = &mut builder.clone().init_unoptimized();
+
+    let mut values = vec![false; levers.len()];
+    for cycle in 0..cycles {
+        stimulus(cycle, &mut values);
+        for ig in [&mut *optimized, &mut *reference] {
+            ig.update_levers(&levers, values.iter().copied());
+            if let Err(err) = ig.run_until_stable(DEFAULT_STABLE_MAX) {
+                panic!("Cycle {}: {}, levers: {:?}", cycle, err, values);
+            }
+        }
+        for output in &outputs {
+            let expected = output.u128(reference);
+            let actual = output.u128(optimized);
+            assert_eq!(
+                actual,
+                expected,
+                "Cycle {}: output {} differs between the optimized and unoptimized graphs, levers: {:?}",
+                cycle,
+                output.name(reference),
+                values
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{random_graph, RandomGraphParams};
+    use super::*;
+    use crate::circuits::{counter, WordInput};
+
+    #[test]
+    fn test_check_optimizations_random_graphs() {
+        let params = RandomGraphParams {
+            levers: 6,
+            gates: 150,
+            ..Default::default()
+        };
+        for seed in 0..20 {
+            let random = random_graph(seed, &params);
+            check_optimizations(&random.graph, random_stimulus(seed), 50);
+        }
+    }
+
+    #[test]
+    fn test_check_optimizations_counter() {
+        let mut g = GateGraphBuilder::new();
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let enable = g.lever("enable");
+        let write = g.lever("write");
+        let input = WordInput::new(&mut g, 8, "input");
+        let count = counter(
+            &mut g,
+            clock.bit(),
+            enable.bit(),
+            write.bit(),
+            ON,
+            reset.bit(),
+            &input.bits(),
+            "counter",
+        );
+        g.output(&count, "count");
+
+        let mut random = random_stimulus(3);
+        check_optimizations(
+            &g,
+            |cycle, levers| {
+                // Inputs only change while the clock is low.
+                if cycle % 2 == 0 {
+                    random(cycle, levers);
+                }
+                levers[0] = cycle % 2 == 1;
+                levers[1] = cycle < 2;
+            },
+            200,
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use crate::data_structures::SplitMix64;
+use crate::graph::*;
+
+/// Parameters of [random_graph].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RandomGraphParams {
+    /// Number of levers in the graph.
+    pub levers: usize,
+    /// Number of gates in the graph, not counting levers, [OFF] and [ON].
+    pub gates: usize,
+    /// Number of outputs in the graph.
+    pub outputs: usize,
+    /// Maximum number of bits in each output, at most 64 so every output fits in a [u64].
+    pub max_output_width: usize,
+    /// Maximum number of dependencies of gates with a variable number of dependencies.
+    pub max_dependencies: usize,
+    /// Gates prefer to depend on one of the last `locality` gates, which creates deep chains
+    /// like the ones found in real circuits instead of a shallow soup of gates.
+    pub locality: usize,
+}
+impl Default for RandomGraphParams {
+    fn default() -> Self {
+        Self {
+            levers: 8,
+            gates: 200,
+            outputs: 4,
+            max_output_width: 8,
+            max_dependencies: 4,
+            locality: 16,
+        }
+    }
+}
+
+/// Graph returned by [random_graph] along with the handles needed to drive it.
+#[derive(Debug, Clone)]
+pub struct RandomGraph {
+    /// The generated graph.
+    pub graph: GateGraphBuilder,
+    /// Every lever in `graph`.
+    pub levers: Vec<LeverHandle>,
+    /// Every output in `graph`.
+    pub outputs: Vec<OutputHandle>,
+}
+
+/// Number of gate types [random_graph] picks from, the last one is [NOT].
+const GATE_TYPES: usize = 7;
+const NOT: usize = GATE_TYPES - 1;
+
+/// Returns a random combinational [GateGraphBuilder] generated from `seed`, the same seed and `params`
+/// always generate the same graph.
+///
+/// The graph is well formed, it has no loops, so it always stabilizes, and every gate has
+/// as many dependencies as its type allows. Every gate type with inputs shows up, along with the patterns the
+/// optimization passes look for: constant dependencies, repeated dependencies, gates with a single dependency and
+/// identical gates.
+///
+/// This is meant to stress the optimization passes, a graph initialized with [GateGraphBuilder::init]
+/// must behave exactly like a clone of it initialized with [GateGraphBuilder::init_unoptimized].
+///
+/// # Example
+/// ```
+/// # use logicsim::testing::{random_graph,RandomGraphParams};
+/// let params = RandomGraphParams::default();
+/// let random = random_graph(42, &params);
+/// let levers = random.levers.clone();
+///
+/// let optimized = &mut random.graph.clone().init();
+/// let reference = &mut random.graph.init_unoptimized();
+///
+/// for output in random.outputs {
+///     assert_eq!(
+///         optimized.truth_table(output, &levers),
+///         reference.truth_table(output, &levers)
+///     );
+/// }
+/// ```
+///
+/// # Panics
+///
+/// Will panic if `params.max_output_width` > 64 or any of the other fields is 0.
+pub fn random_graph(seed: u64, params: &RandomGraphParams) -> RandomGraph {
+    assert!(
+        params.max_output_width <= 64,
+        "Outputs can't be wider than 64 bits"
+    );
+    assert!(
+        params.levers > 0
+            && params.gates > 0
+            && params.outputs > 0
+            && params.max_output_width > 0
+            && params.max_dependencies > 0
+            && params.locality > 0,
+        "Every parameter must be greater than 0, got: {:?}",
+        params
+    );
+    let mut rng = SplitMix64(seed);
+    let mut g = GateGraphBuilder::new();
+
+    let levers: Vec<LeverHandle> = (0..params.levers)
+        .map(|i| g.lever(format!("lever{}", i)))
+        .collect();
+    let mut gates: Vec<GateIndex> = levers.iter().map(|lever| lever.bit()).collect();
+
+    // (type, dependencies) of every generated gate, to create identical copies.
+    let mut generated: Vec<(usize, Vec<GateIndex>)> = Vec::with_capacity(params.gates);
+    for i in 0..params.gates {
+        let (ty, dependencies) = if i > 0 && rng.one_in(32) {
+            // Identical to a previous gate.
+            generated[rng.below(i)].clone()
+        } else {
+            let pick = |rng: &mut SplitMix64| {
+                if rng.one_in(16) {
+                    if rng.one_in(2) {
+                        ON
+                    } else {
+                        OFF
+                    }
+                } else if rng.one_in(4) {
+                    gates[rng.below(gates.len())]
+                } else {
+                    let start = gates.len().saturating_sub(params.locality);
+                    gates[start + rng.below(gates.len() - start)]
+                }
+            };
+            let ty = rng.below(GATE_TYPES);
+            let first = pick(&mut rng);
+            let count = if ty == NOT || rng.one_in(8) {
+                1
+            } else {
+                1 + rng.below(params.max_dependencies)
+            };
+            let mut dependencies = vec![first];
+            for _ in 1..count {
+                // Repeated dependency.
+                let dependency = if rng.one_in(16) {
+                    first
+                } else {
+                    pick(&mut rng)
+                };
+                dependencies.push(dependency);
+            }
+            (ty, dependencies)
+        };
+
+        let name = format!("gate{}", i);
+        let gate = if ty == NOT {
+            g.not1(dependencies[0], name)
+        } else {
+            let gate = match ty {
+                0 => g.or(name),
+                1 => g.nor(name),
+                2 => g.and(name),
+                3 => g.nand(name),
+                4 => g.xor(name),
+                _ => g.xnor(name),
+            };
+            for dependency in &dependencies {
+                g.dpush(gate, *dependency);
+            }
+            gate
+        };
+        gates.push(gate);
+        generated.push((ty, dependencies));
+    }
+
+    let outputs = (0..params.outputs)
+        .map(|i| {
+            let width = 1 + rng.below(params.max_output_width);
+            let bits: Vec<GateIndex> = (0..width)
+                .map(|_| {
+                    // Outputs tend to be at the end of the circuit.
+                    let start = if rng.one_in(2) {
+                        gates.len().saturating_sub(params.locality)
+                    } else {
+                        0
+                    };
+                    gates[start + rng.below(gates.len() - start)]
+                })
+                .collect();
+            g.output(&bits, format!("output{}", i))
+        })
+        .collect();
+
+    RandomGraph {
+        graph: g,
+        levers,
+        outputs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_graph_deterministic() {
+        let params = RandomGraphParams::default();
+        let a = random_graph(7, &params);
+        let b = random_graph(7, &params);
+        assert_eq!(a.graph.len(), b.graph.len());
+        assert_eq!(a.graph.len(), params.levers + params.gates + 2);
+
+        let a_ig = &mut a.graph.init_unoptimized();
+        let b_ig = &mut b.graph.init_unoptimized();
+        for (a_output, b_output) in a.outputs.into_iter().zip(b.outputs) {
+            assert_eq!(
+                a_ig.truth_table(a_output, &a.levers),
+                b_ig.truth_table(b_output, &b.levers)
+            );
+        }
+    }
+
+    #[test]
+    fn test_optimizations_preserve_behavior() {
+        let params = RandomGraphParams {
+            levers: 6,
+            gates: 150,
+            ..Default::default()
+        };
+        for seed in 0..40 {
+            let random = random_graph(seed, &params);
+            let optimized = &mut random.graph.clone().init();
+            let reference = &mut random.graph.init_unoptimized();
+
+            for output in random.outputs {
+                assert_eq!(
+                    optimized.truth_table(output, &random.levers),
+                    reference.truth_table(output, &random.levers),
+                    "seed: {}",
+                    seed
+                );
+            }
+        }
+    }
+}
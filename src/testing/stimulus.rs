@@ -0,0 +1,208 @@
+use crate::circuits::WordInput;
+use crate::data_structures::BitIter;
+use crate::graph::*;
+
+/// One clock cycle worth of stimulus for [drive_sequence], the values to set a group of levers to
+/// and optionally the values some outputs are expected to have at the end of the cycle.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,WordInput};
+/// # use logicsim::testing::StimulusRecord;
+/// # let mut g = GateGraphBuilder::new();
+/// let a = WordInput::new(&mut g, 4, "a");
+/// let b = WordInput::new(&mut g, 4, "b");
+/// let output = g.output(&a.bits(), "a");
+///
+/// let record = StimulusRecord::new()
+///     .word(&a, 3)
+///     .word(&b, 5)
+///     .expect(output, 3);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StimulusRecord {
+    pub(super) levers: Vec<(Vec<LeverHandle>, u128)>,
+    pub(super) expected: Vec<(OutputHandle, u128)>,
+}
+impl StimulusRecord {
+    /// Returns an empty [StimulusRecord], driving it is the same as just pulsing the clock.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets `levers` to the bits of `value`, bit 0 goes to `levers[0]`.
+    /// If there are more levers than bits in `value`, the rest will be set to false.
+    pub fn set(mut self, levers: &[LeverHandle], value: u128) -> Self {
+        self.levers.push((levers.to_vec(), value));
+        self
+    }
+
+    /// Sets the levers of `word` to the bits of `value`, see [StimulusRecord::set].
+    pub fn word(self, word: &WordInput, value: u128) -> Self {
+        self.set(word.levers(), value)
+    }
+
+    /// Expects `output` to be equal to `value` as read by [OutputHandle::u128],
+    /// after the clock has been pulsed.
+    pub fn expect(mut self, output: OutputHandle, value: u128) -> Self {
+        self.expected.push((output, value));
+        self
+    }
+}
+
+/// Drives the circuit in `ig` one clock cycle per record in `stimulus`.
+///
+/// For every record, the levers get set to their new values and the graph runs until it's stable,
+/// then `clock` gets [pulsed](InitializedGateGraph::pulse_lever_stable) and the expected outputs are checked.
+/// This turns tables of expected behavior into tests without writing the loop every time.
+///
+/// Returns the number of cycles driven.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,WordInput,register,ON,OFF};
+/// # use logicsim::testing::{drive_sequence,StimulusRecord};
+/// # let mut g = GateGraphBuilder::new();
+/// let input = WordInput::new(&mut g, 4, "input");
+/// let clock = g.lever("clock");
+///
+/// let register_output = register(&mut g, clock.bit(), ON, ON, OFF, &input.bits(), "register");
+/// let output = g.output(&register_output, "result");
+///
+/// let ig = &mut g.init();
+/// let table = [(6, 6), (2, 2), (15, 15)];
+/// let cycles = drive_sequence(
+///     ig,
+///     clock,
+///     table.iter().map(|(input_value, expected)| {
+///         StimulusRecord::new()
+///             .word(&input, *input_value)
+///             .expect(output, *expected)
+///     }),
+/// );
+/// assert_eq!(cycles, 3);
+/// ```
+///
+/// # Panics
+///
+/// Will panic if an output doesn't have its expected value, or if the circuit doesn't stabilize
+/// after [DEFAULT_STABLE_MAX] ticks.
+pub fn drive_sequence<I: IntoIterator<Item = StimulusRecord>>(
+    ig: &mut InitializedGateGraph,
+    clock: LeverHandle,
+    stimulus: I,
+) -> usize {
+    let mut cycles = 0;
+    for record in stimulus {
+        for (levers, value) in &record.levers {
+            ig.update_levers(levers, BitIter::new(*value));
+        }
+        ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        ig.pulse_lever_stable(clock);
+
+        for (output, expected) in &record.expected {
+            let actual = output.u128(ig);
+            assert!(
+                actual == *expected,
+                "Cycle {}: expected {} to be {}, got {}",
+                cycles,
+                output.name(ig),
+                expected,
+                actual
+            );
+        }
+        cycles += 1;
+    }
+    cycles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuits::{adder, counter};
+
+    #[test]
+    fn test_drive_sequence_adder() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let a = WordInput::new(g, 4, "a");
+        let b = WordInput::new(g, 4, "b");
+        let cin = g.lever("cin");
+        let clock = g.lever("clock");
+        let sum = adder(g, cin.bit(), &a.bits(), &b.bits(), "adder");
+        let output = g.output(&sum, "sum");
+
+        let ig = &mut graph.init();
+        let stimulus = (0..16).flat_map(|a_value| {
+            let a = &a;
+            let b = &b;
+            (0..16).map(move |b_value| {
+                StimulusRecord::new()
+                    .word(a, a_value)
+                    .word(b, b_value)
+                    .set(&[cin], (a_value + b_value) % 2)
+                    .expect(output, (a_value + b_value + (a_value + b_value) % 2) % 16)
+            })
+        });
+        assert_eq!(drive_sequence(ig, clock, stimulus), 256);
+    }
+
+    #[test]
+    fn test_drive_sequence_counter() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let count = counter(
+            g,
+            clock.bit(),
+            ON,
+            OFF,
+            ON,
+            reset.bit(),
+            &[OFF; 4],
+            "counter",
+        );
+        let output = g.output(&count, "count");
+
+        let ig = &mut graph.init();
+        ig.pulse_lever_stable(reset);
+        // Records without levers still advance the clock.
+        let stimulus = (1..20).map(|i| StimulusRecord::new().expect(output, i % 16));
+        assert_eq!(drive_sequence(ig, clock, stimulus), 19);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cycle 1: expected count to be 5, got 2")]
+    fn test_drive_sequence_mismatch() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let count = counter(
+            g,
+            clock.bit(),
+            ON,
+            OFF,
+            ON,
+            reset.bit(),
+            &[OFF; 4],
+            "counter",
+        );
+        let output = g.output(&count, "count");
+
+        let ig = &mut graph.init();
+        ig.pulse_lever_stable(reset);
+        drive_sequence(
+            ig,
+            clock,
+            vec![
+                StimulusRecord::new().expect(output, 1),
+                StimulusRecord::new().expect(output, 5),
+            ],
+        );
+    }
+}
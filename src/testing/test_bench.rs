@@ -0,0 +1,340 @@
+use super::StimulusRecord;
+use crate::data_structures::BitIter;
+use crate::graph::*;
+use std::fmt::{self, Display, Formatter};
+
+/// Output of a [TestBench] cycle that didn't have its expected value.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Mismatch {
+    /// Index of the cycle, starting at 0.
+    pub cycle: usize,
+    /// Name of the output.
+    pub output: String,
+    /// Number of bits of the output, capped at 128.
+    pub width: usize,
+    /// Value the output was expected to have.
+    pub expected: u128,
+    /// Value the output had.
+    pub actual: u128,
+}
+impl Display for Mismatch {
+    /// Writes the expected and actual values in binary, one above the other, with the bits that differ marked.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let width = self.width.max(1);
+        writeln!(f, "cycle {}, {}:", self.cycle, self.output)?;
+        writeln!(
+            f,
+            "  expected {:0width$b} ({})",
+            self.expected,
+            self.expected,
+            width = width
+        )?;
+        writeln!(
+            f,
+            "  actual   {:0width$b} ({})",
+            self.actual,
+            self.actual,
+            width = width
+        )?;
+        let marks: String = (0..width)
+            .rev()
+            .map(|bit| {
+                if (self.expected ^ self.actual) >> bit & 1 == 1 {
+                    '^'
+                } else {
+                    ' '
+                }
+            })
+            .collect();
+        writeln!(f, "           {}", marks.trim_end())
+    }
+}
+
+/// Error returned by [TestBench::run].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TestBenchError {
+    /// Some outputs didn't have their expected values, every cycle was run.
+    Mismatches(Vec<Mismatch>),
+    /// The graph didn't stabilize during cycle `cycle`, the cycles after it weren't run.
+    NotStabilized {
+        /// Index of the cycle, starting at 0.
+        cycle: usize,
+        /// Error returned by [InitializedGateGraph::run_until_stable].
+        error: SimulationError,
+    },
+}
+impl Display for TestBenchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TestBenchError::Mismatches(mismatches) => {
+                let cycles = {
+                    let mut cycles: Vec<_> = mismatches.iter().map(|m| m.cycle).collect();
+                    cycles.dedup();
+                    cycles.len()
+                };
+                writeln!(f, "{} mismatches in {} cycles:", mismatches.len(), cycles)?;
+                for mismatch in mismatches {
+                    write!(f, "{}", mismatch)?;
+                }
+                Ok(())
+            }
+            TestBenchError::NotStabilized { cycle, error } => {
+                write!(f, "cycle {}: {}", cycle, error)
+            }
+        }
+    }
+}
+impl std::error::Error for TestBenchError {}
+
+/// Sequence of cycles to drive a circuit through, each with its lever values and the values some outputs are
+/// expected to have at the end of it, see [TestBench::run].
+///
+/// Unlike [drive_sequence](super::drive_sequence) it doesn't stop at the first wrong output, every mismatch gets
+/// reported with the bits that differ, so a single run tells the whole story of a failing test.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,WordInput,register,ON,OFF};
+/// # use logicsim::testing::{StimulusRecord,TestBench};
+/// let mut g = GateGraphBuilder::new();
+/// let input = WordInput::new(&mut g, 4, "input");
+/// let clock = g.lever("clock");
+/// let register_output = register(&mut g, clock.bit(), ON, ON, OFF, &input.bits(), "register");
+/// let output = g.output(&register_output, "register");
+///
+/// let bench = TestBench::clocked(clock)
+///     .cycle(StimulusRecord::new().word(&input, 6).expect(output, 6))
+///     // The register keeps its value.
+///     .cycle(StimulusRecord::new().expect(output, 6))
+///     .cycle(StimulusRecord::new().word(&input, 9).expect(output, 9));
+///
+/// let ig = &mut g.init();
+/// assert_eq!(bench.run(ig), Ok(3));
+///
+/// let wrong = TestBench::clocked(clock).cycle(StimulusRecord::new().word(&input, 2).expect(output, 3));
+/// let error = wrong.run(ig).unwrap_err();
+/// assert_eq!(
+///     error.to_string(),
+///     "1 mismatches in 1 cycles:
+/// cycle 0, register:
+///   expected 0011 (3)
+///   actual   0010 (2)
+///               ^
+/// "
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TestBench {
+    clock: Option<LeverHandle>,
+    cycles: Vec<StimulusRecord>,
+}
+impl TestBench {
+    /// Returns an empty [TestBench] for combinational circuits, the outputs are checked once the graph is stable
+    /// after setting the levers of every cycle.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns an empty [TestBench] for sequential circuits, the outputs are checked after
+    /// [pulsing](InitializedGateGraph::pulse_lever_stable) `clock` at the end of every cycle.
+    pub fn clocked(clock: LeverHandle) -> Self {
+        Self {
+            clock: Some(clock),
+            cycles: Vec::new(),
+        }
+    }
+
+    /// Appends a cycle.
+    pub fn cycle(mut self, cycle: StimulusRecord) -> Self {
+        self.cycles.push(cycle);
+        self
+    }
+
+    /// Appends every cycle in `cycles`.
+    pub fn cycles<I: IntoIterator<Item = StimulusRecord>>(mut self, cycles: I) -> Self {
+        self.cycles.extend(cycles);
+        self
+    }
+
+    /// Returns the number of cycles.
+    pub fn len(&self) -> usize {
+        self.cycles.len()
+    }
+
+    /// Returns true if there are no cycles.
+    pub fn is_empty(&self) -> bool {
+        self.cycles.is_empty()
+    }
+
+    /// Drives `ig` through every cycle and checks the expected outputs, returns the number of cycles run.
+    ///
+    /// Every cycle sets its levers and runs the graph until it's stable, then pulses the clock if the bench is
+    /// [clocked](TestBench::clocked). Outputs are read like [OutputHandle::u128].
+    ///
+    /// Returns [TestBenchError::Mismatches] with every wrong output if there were any, or
+    /// [TestBenchError::NotStabilized] if the graph didn't stabilize within [DEFAULT_STABLE_MAX] ticks.
+    pub fn run(&self, ig: &mut InitializedGateGraph) -> Result<usize, TestBenchError> {
+        let mut mismatches = Vec::new();
+        for (cycle, record) in self.cycles.iter().enumerate() {
+            let stable = |ig: &mut InitializedGateGraph| {
+                ig.run_until_stable(DEFAULT_STABLE_MAX)
+                    .map_err(|error| TestBenchError::NotStabilized { cycle, error })
+            };
+            for (levers, value) in &record.levers {
+                ig.update_levers(levers, BitIter::new(*value));
+            }
+            stable(ig)?;
+            if let Some(clock) = self.clock {
+                ig.set_lever(clock);
+                stable(ig)?;
+                ig.reset_lever(clock);
+                stable(ig)?;
+            }
+            for (output, expected) in &record.expected {
+                let actual = output.u128(ig);
+                if actual != *expected {
+                    mismatches.push(Mismatch {
+                        cycle,
+                        output: output.name(ig).to_string(),
+                        width: output.width(ig).min(128),
+                        expected: *expected,
+                        actual,
+                    });
+                }
+            }
+        }
+        if mismatches.is_empty() {
+            Ok(self.cycles.len())
+        } else {
+            Err(TestBenchError::Mismatches(mismatches))
+        }
+    }
+
+    /// Measures how many stuck at faults the bench detects, see [InitializedGateGraph::fault_coverage].
+    ///
+    /// The bench is run once without faults first, its error is returned if it fails. Every run starts from the
+    /// state `ig` had when this was called, which is restored afterwards.
+    ///
+    /// # Example
+    /// ```
+    /// # use logicsim::{GateGraphBuilder,WordInput};
+    /// # use logicsim::testing::{TestBench,StimulusRecord};
+    /// let mut g = GateGraphBuilder::new();
+    /// let input = WordInput::new(&mut g, 2, "input");
+    /// let bits = input.bits();
+    /// let and = g.and2(bits[0], bits[1], "and");
+    /// let output = g.output1(and, "and");
+    ///
+    /// let ig = &mut g.init();
+    /// let bench = TestBench::new().cycle(StimulusRecord::new().word(&input, 3).expect(output, 1));
+    /// // Stuck at 1 goes unnoticed.
+    /// assert_eq!(bench.fault_coverage(ig).unwrap().ratio(), 0.5);
+    ///
+    /// let bench = bench.cycle(StimulusRecord::new().word(&input, 1).expect(output, 0));
+    /// assert_eq!(bench.fault_coverage(ig).unwrap().ratio(), 1.0);
+    /// ```
+    pub fn fault_coverage(
+        &self,
+        ig: &mut InitializedGateGraph,
+    ) -> Result<FaultCoverage, TestBenchError> {
+        let start = ig.snapshot();
+        let result = self.run(ig);
+        ig.restore(&start);
+        result?;
+        Ok(ig.fault_coverage(|ig| self.run(ig).is_ok()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuits::{adder, counter, WordInput};
+
+    #[test]
+    fn test_combinational_bench() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let a = WordInput::new(g, 4, "a");
+        let b = WordInput::new(g, 4, "b");
+        let sum = adder(g, OFF, &a.bits(), &b.bits(), "adder");
+        let output = g.output(&sum, "sum");
+        let ig = &mut graph.init();
+
+        let bench = TestBench::new().cycles((0..16).flat_map(|x| {
+            let (a, b) = (&a, &b);
+            (0..16).map(move |y| {
+                StimulusRecord::new()
+                    .word(a, x)
+                    .word(b, y)
+                    .expect(output, (x + y) % 16)
+            })
+        }));
+        assert_eq!(bench.len(), 256);
+        assert_eq!(bench.run(ig), Ok(256));
+
+        // Every mismatch is reported, not just the first one.
+        let wrong = TestBench::new().cycles((0..4).map(|x| {
+            StimulusRecord::new()
+                .word(&a, x)
+                .word(&b, 1)
+                .expect(output, x)
+        }));
+        match wrong.run(ig) {
+            Err(TestBenchError::Mismatches(mismatches)) => {
+                assert_eq!(mismatches.len(), 4);
+                assert_eq!(mismatches[3].cycle, 3);
+                assert_eq!(mismatches[3].expected, 3);
+                assert_eq!(mismatches[3].actual, 4);
+                assert_eq!(mismatches[3].width, 4);
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_clocked_bench() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let count = counter(
+            g,
+            clock.bit(),
+            ON,
+            OFF,
+            ON,
+            reset.bit(),
+            &[OFF; 4],
+            "counter",
+        );
+        let output = g.output(&count, "count");
+        let ig = &mut graph.init();
+
+        let bench = TestBench::clocked(clock)
+            .cycle(StimulusRecord::new().set(&[reset], 1).expect(output, 0))
+            .cycle(StimulusRecord::new().set(&[reset], 0).expect(output, 1))
+            .cycles((2..20).map(|i| StimulusRecord::new().expect(output, i % 16)));
+        assert_eq!(bench.run(ig), Ok(20));
+        assert!(TestBench::clocked(clock).is_empty());
+    }
+
+    #[test]
+    fn test_not_stabilized() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let enable = g.lever("enable");
+        let not = g.nand1(enable.bit(), "not");
+        g.dpush(not, not);
+        let output = g.output1(not, "not");
+        let ig = &mut graph.init();
+
+        let bench = TestBench::new()
+            .cycle(StimulusRecord::new().expect(output, 1))
+            .cycle(StimulusRecord::new().set(&[enable], 1))
+            .cycle(StimulusRecord::new());
+        match bench.run(ig) {
+            Err(TestBenchError::NotStabilized { cycle, .. }) => assert_eq!(cycle, 1),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+}
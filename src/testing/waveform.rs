@@ -0,0 +1,330 @@
+use crate::graph::*;
+use std::io::{self, Write};
+
+/// Signal recorded by a [Waveform].
+#[derive(Debug, Clone)]
+struct Signal {
+    name: String,
+    output: OutputHandle,
+    width: usize,
+}
+
+/// Recording of the values of some outputs over time, which can be written as a
+/// [VCD](https://en.wikipedia.org/wiki/Value_change_dump) file for waveform viewers like GTKWave,
+/// or rendered as an SVG timing diagram.
+///
+/// Every call to [Waveform::sample] records one step of time, usually once per clock cycle.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,counter,zeros,ON,OFF};
+/// # use logicsim::testing::Waveform;
+/// let mut g = GateGraphBuilder::new();
+/// let clock = g.lever("clock");
+/// let reset = g.lever("reset");
+/// let count = counter(&mut g, clock.bit(), ON, OFF, ON, reset.bit(), &zeros(2), "counter");
+/// let count = g.output(&count, "count");
+///
+/// let ig = &mut g.init();
+/// ig.pulse_lever_stable(reset);
+/// let mut waveform = Waveform::new().signal("count", count, 2);
+/// for _ in 0..4 {
+///     waveform.sample(ig);
+///     ig.pulse_lever_stable(clock);
+/// }
+/// assert_eq!(waveform.values("count").unwrap(), [0, 1, 2, 3]);
+/// assert_eq!(waveform.ticks().len(), 4);
+///
+/// let mut vcd = Vec::new();
+/// waveform.write_vcd(&mut vcd).unwrap();
+/// assert!(String::from_utf8(vcd).unwrap().contains("$var wire 2 ! count $end"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Waveform {
+    signals: Vec<Signal>,
+    /// Value of every signal at every step, indexed by step then by signal.
+    samples: Vec<Vec<u128>>,
+    /// Simulation time of every step.
+    ticks: Vec<usize>,
+}
+
+/// Horizontal size of a step in the SVG.
+const SVG_STEP: usize = 40;
+/// Vertical size of a signal in the SVG.
+const SVG_ROW: usize = 30;
+/// Height of the trace of a signal in the SVG.
+const SVG_TRACE: usize = 20;
+/// Horizontal size of the signal names in the SVG.
+const SVG_LABELS: usize = 120;
+/// Horizontal size of the slope at value changes of multi bit signals in the SVG.
+const SVG_SLOPE: usize = 4;
+
+impl Waveform {
+    /// Returns an empty [Waveform] without signals.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds the first `width` bits of `output` to the recorded signals, with name `name`.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if samples have already been taken or if `width` is not in 1..=128.
+    pub fn signal<S: Into<String>>(mut self, name: S, output: OutputHandle, width: usize) -> Self {
+        assert!(
+            self.samples.is_empty(),
+            "Signals can't be added after sampling"
+        );
+        assert!(
+            (1..=128).contains(&width),
+            "Signals must be between 1 and 128 bits wide"
+        );
+        self.signals.push(Signal {
+            name: name.into(),
+            output,
+            width,
+        });
+        self
+    }
+
+    /// Records the current value of every signal as a new step.
+    pub fn sample(&mut self, ig: &InitializedGateGraph) {
+        let sample = self
+            .signals
+            .iter()
+            .map(|signal| signal.output.u128(ig) & mask(signal.width))
+            .collect();
+        self.samples.push(sample);
+        self.ticks.push(ig.now());
+    }
+
+    /// Returns the [simulation time](InitializedGateGraph::now) at which every step was recorded.
+    pub fn ticks(&self) -> &[usize] {
+        &self.ticks
+    }
+
+    /// Returns the number of steps recorded.
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Returns true if no steps have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Returns the values of the signal named `name` at every step, [None] if there is no signal named `name`.
+    pub fn values(&self, name: &str) -> Option<Vec<u128>> {
+        let i = self.signals.iter().position(|signal| signal.name == name)?;
+        Some(self.samples.iter().map(|sample| sample[i]).collect())
+    }
+
+    /// Writes the recording in VCD format, every step is 1ns long.
+    /// Whitespace in signal names is replaced by underscores.
+    pub fn write_vcd<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "$timescale 1ns $end")?;
+        writeln!(writer, "$scope module logicsim $end")?;
+        for (i, signal) in self.signals.iter().enumerate() {
+            let name: String = signal
+                .name
+                .chars()
+                .map(|c| if c.is_whitespace() { '_' } else { c })
+                .collect();
+            writeln!(
+                writer,
+                "$var wire {} {} {} $end",
+                signal.width,
+                vcd_id(i),
+                name
+            )?;
+        }
+        writeln!(writer, "$upscope $end")?;
+        writeln!(writer, "$enddefinitions $end")?;
+
+        let mut last: Option<&Vec<u128>> = None;
+        for (time, sample) in self.samples.iter().enumerate() {
+            writeln!(writer, "#{}", time)?;
+            for (i, (signal, value)) in self.signals.iter().zip(sample).enumerate() {
+                if last.is_some_and(|last| last[i] == *value) {
+                    continue;
+                }
+                if signal.width == 1 {
+                    writeln!(writer, "{}{}", value, vcd_id(i))?;
+                } else {
+                    writeln!(writer, "b{:b} {}", value, vcd_id(i))?;
+                }
+            }
+            last = Some(sample);
+        }
+        writeln!(writer, "#{}", self.samples.len())
+    }
+
+    /// Writes the recording as an SVG timing diagram, with a row per signal.
+    /// Single bit signals are drawn as square waves and multi bit signals as buses annotated with their values.
+    pub fn write_svg<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let width = SVG_LABELS + self.samples.len() * SVG_STEP;
+        let height = self.signals.len() * SVG_ROW;
+        writeln!(
+            writer,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="monospace" font-size="12">"#,
+            w = width,
+            h = height
+        )?;
+        writeln!(
+            writer,
+            r#"<rect width="{}" height="{}" fill="white"/>"#,
+            width, height
+        )?;
+        for step in 0..=self.samples.len() {
+            let x = SVG_LABELS + step * SVG_STEP;
+            writeln!(
+                writer,
+                r#"<line x1="{x}" y1="0" x2="{x}" y2="{}" stroke="lightgray"/>"#,
+                height,
+                x = x
+            )?;
+        }
+
+        for (i, signal) in self.signals.iter().enumerate() {
+            let top = i * SVG_ROW + (SVG_ROW - SVG_TRACE) / 2;
+            let bottom = top + SVG_TRACE;
+            let middle = top + SVG_TRACE / 2;
+            writeln!(
+                writer,
+                r#"<text x="4" y="{}" dominant-baseline="middle">{}</text>"#,
+                middle,
+                escape_xml(&signal.name)
+            )?;
+            if self.samples.is_empty() {
+                continue;
+            }
+
+            if signal.width == 1 {
+                let mut points = Vec::new();
+                for (step, sample) in self.samples.iter().enumerate() {
+                    let y = if sample[i] == 1 { top } else { bottom };
+                    points.push((SVG_LABELS + step * SVG_STEP, y));
+                    points.push((SVG_LABELS + (step + 1) * SVG_STEP, y));
+                }
+                let points: Vec<_> = points.iter().map(|(x, y)| format!("{},{}", x, y)).collect();
+                writeln!(
+                    writer,
+                    r#"<polyline points="{}" fill="none" stroke="black"/>"#,
+                    points.join(" ")
+                )?;
+                continue;
+            }
+
+            // Runs of steps with the same value.
+            let mut start = 0;
+            for step in 1..=self.samples.len() {
+                if step < self.samples.len() && self.samples[step][i] == self.samples[start][i] {
+                    continue;
+                }
+                let x0 = SVG_LABELS + start * SVG_STEP;
+                let x1 = SVG_LABELS + step * SVG_STEP;
+                writeln!(
+                    writer,
+                    r#"<polygon points="{},{m} {},{t} {},{t} {},{m} {},{b} {},{b}" fill="lightyellow" stroke="black"/>"#,
+                    x0,
+                    x0 + SVG_SLOPE,
+                    x1 - SVG_SLOPE,
+                    x1,
+                    x1 - SVG_SLOPE,
+                    x0 + SVG_SLOPE,
+                    t = top,
+                    m = middle,
+                    b = bottom
+                )?;
+                writeln!(
+                    writer,
+                    r#"<text x="{}" y="{}" text-anchor="middle" dominant-baseline="middle">{}</text>"#,
+                    (x0 + x1) / 2,
+                    middle,
+                    self.samples[start][i]
+                )?;
+                start = step;
+            }
+        }
+        writeln!(writer, "</svg>")
+    }
+}
+
+/// Returns a mask with the lower `width` bits set.
+fn mask(width: usize) -> u128 {
+    if width >= 128 {
+        u128::MAX
+    } else {
+        (1 << width) - 1
+    }
+}
+
+/// Returns the VCD identifier of signal number `i`, made of the printable ASCII characters.
+fn vcd_id(mut i: usize) -> String {
+    const FIRST: u8 = b'!';
+    const COUNT: usize = (b'~' - b'!' + 1) as usize;
+    let mut id = String::new();
+    loop {
+        id.push((FIRST + (i % COUNT) as u8) as char);
+        i /= COUNT;
+        if i == 0 {
+            return id;
+        }
+        i -= 1;
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vcd_id() {
+        assert_eq!(vcd_id(0), "!");
+        assert_eq!(vcd_id(93), "~");
+        assert_eq!(vcd_id(94), "!!");
+        assert_eq!(vcd_id(95), "\"!");
+    }
+
+    #[test]
+    fn test_waveform_files() {
+        let mut g = GateGraphBuilder::new();
+        let l = g.lever("l");
+        let not = g.not1(l.bit(), "not");
+        let bit = g.output1(l.bit(), "l");
+        let bus = g.output(&[l.bit(), not, l.bit()], "bus <3>");
+
+        let ig = &mut g.init();
+        let mut waveform = Waveform::new()
+            .signal("l", bit, 1)
+            .signal("bus <3>", bus, 3);
+        for _ in 0..3 {
+            waveform.sample(ig);
+            ig.flip_lever_stable(l);
+        }
+        waveform.sample(ig);
+        assert_eq!(waveform.len(), 4);
+        assert_eq!(waveform.values("bus <3>").unwrap(), [2, 5, 2, 5]);
+        assert_eq!(waveform.values("nope"), None);
+
+        let mut vcd = Vec::new();
+        waveform.write_vcd(&mut vcd).unwrap();
+        let vcd = String::from_utf8(vcd).unwrap();
+        assert!(vcd.contains("$var wire 3 \" bus_<3> $end"));
+        assert!(vcd.contains("#0\n0!\nb10 \"\n#1\n1!\nb101 \"\n"));
+        assert!(vcd.ends_with("#4\n"));
+
+        let mut svg = Vec::new();
+        waveform.write_svg(&mut svg).unwrap();
+        let svg = String::from_utf8(svg).unwrap();
+        assert!(svg.contains("bus &lt;3&gt;"));
+        assert_eq!(svg.matches("<polygon").count(), 4);
+        assert_eq!(svg.matches("<polyline").count(), 1);
+    }
+}
@@ -0,0 +1,420 @@
+use crate::circuits::*;
+use crate::graph::*;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+
+/// Widths that width generic circuits get checked at by [check_circuit_widths], the edges of a bit, of a byte,
+/// of the 64 bit words [State](crate::data_structures::State) is made of and one past each of them.
+pub const SMOKE_WIDTHS: &[usize] = &[1, 2, 7, 8, 64, 65];
+/// Widths for circuits whose size grows exponentially with their width, like [decoder].
+const SMALL_WIDTHS: &[usize] = &[1, 2, 7, 8];
+
+/// Calls `check` with every width in `widths`, if it panics the panic message gets prefixed by the width
+/// that failed.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,WordInput};
+/// # use logicsim::testing::{for_each_width,SMOKE_WIDTHS};
+/// for_each_width(SMOKE_WIDTHS, |width| {
+///     let mut g = GateGraphBuilder::new();
+///     let input = WordInput::new(&mut g, width, "input");
+///     let output = g.output(&input.bits(), "output");
+///
+///     let ig = &mut g.init();
+///     input.set_to(ig, 1u8);
+///     ig.run_until_stable(10).unwrap();
+///     assert_eq!(output.u128(ig), 1);
+/// });
+/// ```
+pub fn for_each_width<F: FnMut(usize)>(widths: &[usize], mut check: F) {
+    for width in widths {
+        if let Err(payload) = catch_unwind(AssertUnwindSafe(|| check(*width))) {
+            let message = payload
+                .downcast_ref::<String>()
+                .map(String::as_str)
+                .or_else(|| payload.downcast_ref::<&str>().copied());
+            match message {
+                Some(message) => panic!("Width {}: {}", width, message),
+                None => resume_unwind(payload),
+            }
+        }
+    }
+}
+
+/// Width generic circuit checked by [check_circuit_widths], `check` builds it at the width it gets
+/// and compares a short simulation against a reference model.
+struct WidthCheck {
+    name: &'static str,
+    widths: &'static [usize],
+    check: fn(usize),
+}
+
+const WIDTH_CHECKS: &[WidthCheck] = &[
+    WidthCheck {
+        name: "adder",
+        widths: SMOKE_WIDTHS,
+        check: check_adder,
+    },
+    WidthCheck {
+        name: "aluish",
+        widths: SMOKE_WIDTHS,
+        check: check_aluish,
+    },
+    WidthCheck {
+        name: "counter",
+        widths: SMOKE_WIDTHS,
+        check: check_counter,
+    },
+    WidthCheck {
+        name: "register",
+        widths: SMOKE_WIDTHS,
+        check: check_register,
+    },
+    WidthCheck {
+        name: "ram",
+        widths: SMOKE_WIDTHS,
+        check: check_ram,
+    },
+    WidthCheck {
+        name: "bus_multiplexer",
+        widths: SMOKE_WIDTHS,
+        check: check_bus_multiplexer,
+    },
+    WidthCheck {
+        name: "matches_constant",
+        widths: SMOKE_WIDTHS,
+        check: check_matches_constant,
+    },
+    WidthCheck {
+        name: "majority",
+        widths: SMOKE_WIDTHS,
+        check: check_majority,
+    },
+    WidthCheck {
+        name: "ring_counter",
+        widths: SMOKE_WIDTHS,
+        check: check_ring_counter,
+    },
+    WidthCheck {
+        name: "johnson_counter",
+        widths: SMOKE_WIDTHS,
+        check: check_johnson_counter,
+    },
+    WidthCheck {
+        name: "decoder",
+        widths: SMALL_WIDTHS,
+        check: check_decoder,
+    },
+];
+
+/// Builds every width generic component of the [circuits](crate::circuits) module at the [SMOKE_WIDTHS]
+/// (or the small ones for components that grow exponentially) and checks a short simulation of each one
+/// against a reference model, catching bugs that only show up at widths like 1 or 65.
+///
+/// Returns the names of the components checked.
+///
+/// # Panics
+///
+/// Will panic with the name of the component and the width if any of them misbehaves.
+pub fn check_circuit_widths() -> Vec<&'static str> {
+    for check in WIDTH_CHECKS {
+        if let Err(payload) = catch_unwind(|| for_each_width(check.widths, check.check)) {
+            match payload.downcast_ref::<String>() {
+                Some(message) => panic!("{}: {}", check.name, message),
+                None => resume_unwind(payload),
+            }
+        }
+    }
+    WIDTH_CHECKS.iter().map(|check| check.name).collect()
+}
+
+/// Returns a mask with the lower `width` bits set.
+fn mask(width: usize) -> u128 {
+    if width >= 128 {
+        u128::MAX
+    } else {
+        (1 << width) - 1
+    }
+}
+
+/// Returns interesting values of `width` bits, all zeros, all ones, the lowest and highest bits
+/// and alternating patterns.
+fn values(width: usize) -> Vec<u128> {
+    let mask = mask(width);
+    vec![
+        0,
+        1,
+        mask,
+        1 << (width - 1),
+        0x5555_5555_5555_5555_5555_5555_5555_5555 & mask,
+        0xaaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa & mask,
+    ]
+}
+
+/// Returns the indexes of the active bits of `outputs`, [OutputHandle::u128] only reads 128 bits
+/// so wider outputs get split into 128 bit handles.
+fn active_bits(ig: &InitializedGateGraph, outputs: &[OutputHandle]) -> Vec<usize> {
+    outputs
+        .iter()
+        .enumerate()
+        .flat_map(|(chunk, output)| {
+            let value = output.u128(ig);
+            (0..128)
+                .filter(move |bit| value >> bit & 1 == 1)
+                .map(move |bit| chunk * 128 + bit)
+        })
+        .collect()
+}
+
+/// Returns 128 bit wide [OutputHandle]s covering `bits`, see [active_bits].
+fn wide_output(g: &mut GateGraphBuilder, bits: &[GateIndex], name: &str) -> Vec<OutputHandle> {
+    bits.chunks(128)
+        .map(|chunk| g.output(chunk, name))
+        .collect()
+}
+
+fn check_adder(width: usize) {
+    let mut g = GateGraphBuilder::new();
+    let a = WordInput::new(&mut g, width, "a");
+    let b = WordInput::new(&mut g, width, "b");
+    let cin = g.lever("cin");
+    let sum = adder(&mut g, cin.bit(), &a.bits(), &b.bits(), "adder");
+    let output = g.output(&sum, "sum");
+
+    let ig = &mut g.init();
+    for a_value in values(width) {
+        for b_value in values(width) {
+            for cin_value in [false, true] {
+                a.set_to(ig, a_value);
+                b.set_to(ig, b_value);
+                ig.update_lever(cin, cin_value);
+                ig.run_until_stable(DEFAULT_STABLE_MAX * 4).unwrap();
+                let expected = a_value
+                    .wrapping_add(b_value)
+                    .wrapping_add(cin_value as u128);
+                assert_eq!(output.u128(ig), expected & mask(width));
+            }
+        }
+    }
+}
+
+fn check_aluish(width: usize) {
+    let mut g = GateGraphBuilder::new();
+    let a = WordInput::new(&mut g, width, "a");
+    let b = WordInput::new(&mut g, width, "b");
+    let result = aluish(&mut g, ON, ON, ON, &a.bits(), &b.bits(), "aluish");
+    let output = g.output(&result, "result");
+
+    let ig = &mut g.init();
+    for a_value in values(width) {
+        for b_value in values(width) {
+            a.set_to(ig, a_value);
+            b.set_to(ig, b_value);
+            ig.run_until_stable(DEFAULT_STABLE_MAX * 4).unwrap();
+            assert_eq!(output.u128(ig), a_value.wrapping_sub(b_value) & mask(width));
+        }
+    }
+}
+
+fn check_counter(width: usize) {
+    let mut g = GateGraphBuilder::new();
+    let clock = g.lever("clock");
+    let write = g.lever("write");
+    let reset = g.lever("reset");
+    let input = WordInput::new(&mut g, width, "input");
+    let count = counter(
+        &mut g,
+        clock.bit(),
+        ON,
+        write.bit(),
+        ON,
+        reset.bit(),
+        &input.bits(),
+        "counter",
+    );
+    let output = g.output(&count, "count");
+
+    let ig = &mut g.init();
+    ig.pulse_lever_stable(reset);
+    for i in 1..=3 {
+        ig.pulse_lever_stable(clock);
+        assert_eq!(output.u128(ig), i & mask(width));
+    }
+    // Overflow.
+    input.set_to(ig, mask(width));
+    ig.set_lever_stable(write);
+    ig.pulse_lever_stable(clock);
+    ig.reset_lever_stable(write);
+    assert_eq!(output.u128(ig), mask(width));
+    ig.pulse_lever_stable(clock);
+    assert_eq!(output.u128(ig), 0);
+}
+
+fn check_register(width: usize) {
+    let mut g = GateGraphBuilder::new();
+    let clock = g.lever("clock");
+    let input = WordInput::new(&mut g, width, "input");
+    let stored = register(&mut g, clock.bit(), ON, ON, OFF, &input.bits(), "register");
+    let output = g.output(&stored, "stored");
+
+    let ig = &mut g.init();
+    for value in values(width) {
+        input.set_to(ig, value);
+        ig.pulse_lever_stable(clock);
+        input.set_to(ig, !value);
+        ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        assert_eq!(output.u128(ig), value);
+    }
+}
+
+fn check_ram(width: usize) {
+    let mut g = GateGraphBuilder::new();
+    let clock = g.lever("clock");
+    let write = g.lever("write");
+    let address = WordInput::new(&mut g, 2, "address");
+    let input = WordInput::new(&mut g, width, "input");
+    let stored = ram(
+        &mut g,
+        ON,
+        write.bit(),
+        clock.bit(),
+        OFF,
+        &address.bits(),
+        &input.bits(),
+        "ram",
+    );
+    let output = g.output(&stored, "stored");
+
+    let ig = &mut g.init();
+    let words = &values(width)[2..];
+    for (i, word) in words.iter().enumerate() {
+        address.set_to(ig, i as u8);
+        input.set_to(ig, *word);
+        ig.set_lever_stable(write);
+        ig.pulse_lever_stable(clock);
+        ig.reset_lever_stable(write);
+    }
+    for (i, word) in words.iter().enumerate() {
+        address.set_to(ig, i as u8);
+        ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        assert_eq!(output.u128(ig), *word, "address {}", i);
+    }
+}
+
+fn check_bus_multiplexer(width: usize) {
+    let mut g = GateGraphBuilder::new();
+    let select = g.lever("select");
+    let a = WordInput::new(&mut g, width, "a");
+    let b = WordInput::new(&mut g, width, "b");
+    let selected = bus_multiplexer(&mut g, &[select.bit()], &[&a.bits(), &b.bits()], "mux");
+    let output = g.output(&selected, "selected");
+
+    let ig = &mut g.init();
+    for value in values(width) {
+        a.set_to(ig, value);
+        b.set_to(ig, !value);
+        ig.reset_lever_stable(select);
+        assert_eq!(output.u128(ig), value);
+        ig.set_lever_stable(select);
+        assert_eq!(output.u128(ig), !value & mask(width));
+    }
+}
+
+fn check_matches_constant(width: usize) {
+    for constant in values(width) {
+        let mut g = GateGraphBuilder::new();
+        let input = WordInput::new(&mut g, width, "input");
+        let matches = matches_constant(&mut g, &input.bits(), constant, "matches");
+        let output = g.output1(matches, "matches");
+
+        let ig = &mut g.init();
+        for value in values(width) {
+            input.set_to(ig, value);
+            ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+            assert_eq!(
+                output.b0(ig),
+                value == constant,
+                "{:x} {:x}",
+                value,
+                constant
+            );
+        }
+    }
+}
+
+fn check_majority(width: usize) {
+    let mut g = GateGraphBuilder::new();
+    let input = WordInput::new(&mut g, width, "input");
+    let vote = majority(&mut g, &input.bits(), "vote");
+    let output = g.output1(vote, "vote");
+
+    let ig = &mut g.init();
+    for value in values(width) {
+        input.set_to(ig, value);
+        ig.run_until_stable(DEFAULT_STABLE_MAX * 4).unwrap();
+        let expected = value.count_ones() as usize > width / 2;
+        assert_eq!(output.b0(ig), expected, "{:x}", value);
+    }
+}
+
+fn check_ring_counter(width: usize) {
+    let mut g = GateGraphBuilder::new();
+    let clock = g.lever("clock");
+    let reset = g.lever("reset");
+    let ring = ring_counter(&mut g, clock.bit(), ON, reset.bit(), width, "ring");
+    let output = wide_output(&mut g, &ring, "ring");
+
+    let ig = &mut g.init();
+    ig.pulse_lever_stable(reset);
+    for i in 0..=width {
+        assert_eq!(active_bits(ig, &output), [i % width]);
+        ig.pulse_lever_stable(clock);
+    }
+}
+
+fn check_johnson_counter(width: usize) {
+    let mut g = GateGraphBuilder::new();
+    let clock = g.lever("clock");
+    let reset = g.lever("reset");
+    let phases = johnson_counter(&mut g, clock.bit(), ON, reset.bit(), width, "johnson");
+    let output = wide_output(&mut g, &phases, "phases");
+
+    let ig = &mut g.init();
+    ig.pulse_lever_stable(reset);
+    for i in 0..=2 * width {
+        assert_eq!(active_bits(ig, &output), [i % (2 * width)]);
+        ig.pulse_lever_stable(clock);
+    }
+}
+
+fn check_decoder(width: usize) {
+    let mut g = GateGraphBuilder::new();
+    let address = WordInput::new(&mut g, width, "address");
+    let lines = decoder(&mut g, &address.bits(), "decoder");
+    assert_eq!(lines.len(), 1 << width);
+    let output = wide_output(&mut g, &lines, "lines");
+
+    let ig = &mut g.init();
+    for value in values(width) {
+        address.set_to(ig, value);
+        ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        assert_eq!(active_bits(ig, &output), [value as usize]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_circuit_widths() {
+        let names = check_circuit_widths();
+        assert_eq!(names.len(), WIDTH_CHECKS.len());
+    }
+
+    #[test]
+    #[should_panic(expected = "Width 7: too wide")]
+    fn test_for_each_width_reports_width() {
+        for_each_width(SMOKE_WIDTHS, |width| assert!(width < 7, "too wide"));
+    }
+}
@@ -0,0 +1,529 @@
+//! Exhaustive checks of small circuits against reference models written in Rust.
+//!
+//! [exhaustive] sweeps every combination of the inputs of a combinational circuit and
+//! [exhaustive_sequences] every sequence of combinations up to a length for sequential ones, comparing the
+//! outputs with what the model returns. It's not a proof for circuits of any size, but for the handful of
+//! inputs of an ALU slice or a decoder it leaves nothing untested.
+//!
+//! # Example
+//! ```
+//! # use logicsim::{GateGraphBuilder,WordInput,adder,OFF};
+//! use logicsim::verify::exhaustive;
+//! let mut g = GateGraphBuilder::new();
+//! let a = WordInput::new(&mut g, 4, "a");
+//! let b = WordInput::new(&mut g, 4, "b");
+//! let sum = adder(&mut g, OFF, &a.bits(), &b.bits(), "adder");
+//! let sum = g.output(&sum, "sum");
+//!
+//! let ig = &mut g.init();
+//! let checked = exhaustive(ig, &[a.levers(), b.levers()], &[sum], |inputs| {
+//!     vec![(inputs[0] + inputs[1]) % 16]
+//! });
+//! assert_eq!(checked, Ok(256));
+//!
+//! // The carry out is missing.
+//! let counterexample = exhaustive(ig, &[a.levers(), b.levers()], &[sum], |inputs| {
+//!     vec![inputs[0] + inputs[1]]
+//! })
+//! .unwrap_err();
+//! assert_eq!(counterexample.inputs, [[15, 1]]);
+//! assert_eq!(counterexample.to_string(), "inputs [15, 1]: expected [16], got [0]");
+//! ```
+use crate::graph::*;
+use std::fmt::{self, Display, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+
+/// Maximum number of levers [exhaustive] will sweep, and of levers times steps for [exhaustive_sequences].
+pub const EXHAUSTIVE_MAX_LEVERS: usize = 32;
+
+/// Number of input combinations evaluated at once, one per bit of a [u64].
+const LANES: u64 = 64;
+
+/// Inputs for which a circuit doesn't do what its model says, returned by [exhaustive] and
+/// [exhaustive_sequences].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Counterexample {
+    /// Value of every input group at every step, the last step is the one that failed.
+    /// [exhaustive] only has one step.
+    pub inputs: Vec<Vec<u128>>,
+    /// Values returned by the model, one per output.
+    pub expected: Vec<u128>,
+    /// Values of the outputs, read like [OutputHandle::u128].
+    pub actual: Vec<u128>,
+}
+impl Display for Counterexample {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "inputs ")?;
+        for (i, step) in self.inputs.iter().enumerate() {
+            if i != 0 {
+                write!(f, " then ")?;
+            }
+            write!(f, "{:?}", step)?;
+        }
+        write!(f, ": expected {:?}, got {:?}", self.expected, self.actual)
+    }
+}
+impl std::error::Error for Counterexample {}
+
+/// Maps combination numbers to input values, the bits of the number are assigned to the levers in order.
+struct Sweep<'a> {
+    inputs: &'a [&'a [LeverHandle]],
+    levers: Vec<LeverHandle>,
+}
+impl<'a> Sweep<'a> {
+    fn new(inputs: &'a [&'a [LeverHandle]], steps: usize) -> Self {
+        let levers: Vec<_> = inputs
+            .iter()
+            .flat_map(|group| group.iter())
+            .copied()
+            .collect();
+        assert!(
+            levers.len() * steps <= EXHAUSTIVE_MAX_LEVERS,
+            "Refusing to sweep {} levers for {} steps, the maximum is {} levers in total",
+            levers.len(),
+            steps,
+            EXHAUSTIVE_MAX_LEVERS
+        );
+        Self { inputs, levers }
+    }
+
+    /// Returns the number of combinations.
+    fn combinations(&self) -> u64 {
+        1 << self.levers.len()
+    }
+
+    /// Returns the value of every input group in combination `n`.
+    fn values(&self, n: u64) -> Vec<u128> {
+        let mut offset = 0;
+        self.inputs
+            .iter()
+            .map(|group| {
+                let mask = (1u64 << group.len()) - 1;
+                let value = n >> offset & mask;
+                offset += group.len();
+                value as u128
+            })
+            .collect()
+    }
+}
+
+/// Checks `ig` against `model` for every combination of the values of the levers in `inputs` and returns the
+/// number of combinations checked, or the [Counterexample] with the lowest combination number if it failed.
+///
+/// `inputs` are groups of levers, like the ones of a [WordInput](crate::WordInput), bit 0 first. `model` gets
+/// the value of every group and returns the expected value of every output in `outputs`, which are read like
+/// [OutputHandle::u128]. Levers that are not in `inputs` keep their value.
+///
+/// Graphs without loops are evaluated 64 combinations at a time like [InitializedGateGraph::tick_vector], split
+/// between all the available threads, which is why `model` has to be [Sync]. Graphs with loops are simulated one
+/// combination at a time like [InitializedGateGraph::evaluate_many]. The state of the graph is left untouched.
+///
+/// See the [module](self) documentation for an example.
+///
+/// # Panics
+///
+/// Will panic if there are more than [EXHAUSTIVE_MAX_LEVERS] levers in `inputs`, if `model` doesn't return a
+/// value per output, or if the graph has loops and it doesn't stabilize after [DEFAULT_STABLE_MAX] ticks for any
+/// combination.
+pub fn exhaustive<F>(
+    ig: &mut InitializedGateGraph,
+    inputs: &[&[LeverHandle]],
+    outputs: &[OutputHandle],
+    model: F,
+) -> Result<u64, Counterexample>
+where
+    F: Fn(&[u128]) -> Vec<u128> + Sync,
+{
+    let sweep = Sweep::new(inputs, 1);
+    let combinations = sweep.combinations();
+    let blocks = combinations.div_ceil(LANES);
+    let lanes = combinations.min(LANES);
+    // Compares every lane of a block, `actual(lane, output)` returns the value of an output in a lane.
+    let check_block = |block: u64, actual: &dyn Fn(usize, OutputHandle) -> u128| {
+        for lane in 0..lanes {
+            let values = sweep.values(block * LANES + lane);
+            let expected = model(&values);
+            assert_eq!(
+                expected.len(),
+                outputs.len(),
+                "The model must return a value per output"
+            );
+            let actual: Vec<_> = outputs
+                .iter()
+                .map(|output| actual(lane as usize, *output))
+                .collect();
+            if expected != actual {
+                return Err(Counterexample {
+                    inputs: vec![values],
+                    expected,
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    };
+
+    let evaluator = match ig.lane_evaluator() {
+        Some(evaluator) => evaluator,
+        None => {
+            for block in 0..blocks {
+                let assignments: Vec<_> = (0..lanes)
+                    .map(|lane| {
+                        sweep.levers.iter().enumerate().fold(
+                            LeverAssignment::new(),
+                            |assignment, (bit, lever)| {
+                                assignment.lever(*lever, (block * LANES + lane) >> bit & 1 == 1)
+                            },
+                        )
+                    })
+                    .collect();
+                let values = ig.evaluate_many(&assignments);
+                check_block(block, &|lane, output| values[lane].get(output))?;
+            }
+            return Ok(combinations);
+        }
+    };
+    // The first 6 levers take every value within a block, the rest are the same in every lane.
+    let lane_patterns: Vec<u64> = (0..6)
+        .map(|bit| (0..LANES).fold(0, |word, lane| word | (lane >> bit & 1) << lane))
+        .collect();
+    let words = |block: u64| -> Vec<u64> {
+        (0..sweep.levers.len())
+            .map(|bit| {
+                if bit < 6 {
+                    lane_patterns[bit]
+                } else if block >> (bit - 6) & 1 == 1 {
+                    !0
+                } else {
+                    0
+                }
+            })
+            .collect()
+    };
+
+    let threads = thread::available_parallelism()
+        .map_or(1, |n| n.get() as u64)
+        .min(blocks);
+    // Lowest block that failed so far, blocks after it don't need to be checked.
+    let first_failure = AtomicU64::new(u64::MAX);
+    let failures: Vec<(u64, Counterexample)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|thread| {
+                let (sweep, evaluator, words, check_block, first_failure) =
+                    (&sweep, &evaluator, &words, &check_block, &first_failure);
+                scope.spawn(move || {
+                    let start = blocks * thread / threads;
+                    let end = blocks * (thread + 1) / threads;
+                    for block in start..end {
+                        if block > first_failure.load(Ordering::Relaxed) {
+                            return None;
+                        }
+                        let values = evaluator.evaluate(&sweep.levers, &words(block));
+                        if let Err(counterexample) =
+                            check_block(block, &|lane, output| values.get(output, lane))
+                        {
+                            first_failure.fetch_min(block, Ordering::Relaxed);
+                            return Some((block, counterexample));
+                        }
+                    }
+                    None
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .filter_map(|handle| handle.join().unwrap())
+            .collect()
+    });
+    match failures.into_iter().min_by_key(|(block, _)| *block) {
+        Some((_, counterexample)) => Err(counterexample),
+        None => Ok(combinations),
+    }
+}
+
+/// Checks `ig` against `model` for every sequence of `steps` combinations of the values of the levers in
+/// `inputs` and returns the number of sequences checked, or the first [Counterexample] found.
+///
+/// Every sequence starts from the current state of the graph and from `initial`, the state of the model. At every
+/// step the levers are set and the graph runs until it's stable, then `clock` is
+/// [pulsed](InitializedGateGraph::pulse_lever_stable) if there is one and the outputs are compared with the
+/// values `model` returns when called with the state of the model and the value of every input group.
+/// Sequences that share a prefix share its simulation. The state of the graph is restored afterwards.
+///
+/// See [exhaustive] for how `inputs` and `outputs` are handled.
+///
+/// # Example
+/// ```
+/// # use logicsim::{GateGraphBuilder,WordInput,register,ON,OFF};
+/// use logicsim::verify::exhaustive_sequences;
+/// let mut g = GateGraphBuilder::new();
+/// let input = WordInput::new(&mut g, 2, "input");
+/// let write = g.lever("write");
+/// let clock = g.lever("clock");
+/// let reset = g.lever("reset");
+/// let register_output = register(&mut g, clock.bit(), write.bit(), ON, reset.bit(), &input.bits(), "register");
+/// let output = g.output(&register_output, "register");
+///
+/// let ig = &mut g.init();
+/// ig.pulse_lever_stable(reset);
+/// // The register starts at 0, every write replaces its value.
+/// let checked = exhaustive_sequences(
+///     ig,
+///     Some(clock),
+///     &[input.levers(), &[write]],
+///     &[output],
+///     3,
+///     0,
+///     |value, inputs| {
+///         if inputs[1] == 1 {
+///             *value = inputs[0];
+///         }
+///         vec![*value]
+///     },
+/// );
+/// assert_eq!(checked, Ok(512));
+/// ```
+///
+/// # Panics
+///
+/// Will panic if there are more than [EXHAUSTIVE_MAX_LEVERS] levers in `inputs` times `steps`, if `model` doesn't
+/// return a value per output, or if the graph doesn't stabilize after [DEFAULT_STABLE_MAX] ticks at any step.
+pub fn exhaustive_sequences<S, F>(
+    ig: &mut InitializedGateGraph,
+    clock: Option<LeverHandle>,
+    inputs: &[&[LeverHandle]],
+    outputs: &[OutputHandle],
+    steps: usize,
+    initial: S,
+    mut model: F,
+) -> Result<u64, Counterexample>
+where
+    S: Clone,
+    F: FnMut(&mut S, &[u128]) -> Vec<u128>,
+{
+    let sweep = Sweep::new(inputs, steps);
+    let start = ig.snapshot();
+    let mut prefix = Vec::with_capacity(steps);
+    let result = explore(
+        ig,
+        &sweep,
+        clock,
+        outputs,
+        steps,
+        &initial,
+        &mut model,
+        &mut prefix,
+    );
+    ig.restore(&start);
+    result.map(|()| sweep.combinations().pow(steps as u32))
+}
+
+/// Checks every sequence of `steps` combinations from the current state of `ig` and `state`, see
+/// [exhaustive_sequences], `prefix` holds the inputs of the previous steps.
+#[allow(clippy::too_many_arguments)]
+fn explore<S, F>(
+    ig: &mut InitializedGateGraph,
+    sweep: &Sweep<'_>,
+    clock: Option<LeverHandle>,
+    outputs: &[OutputHandle],
+    steps: usize,
+    state: &S,
+    model: &mut F,
+    prefix: &mut Vec<Vec<u128>>,
+) -> Result<(), Counterexample>
+where
+    S: Clone,
+    F: FnMut(&mut S, &[u128]) -> Vec<u128>,
+{
+    if steps == 0 {
+        return Ok(());
+    }
+    let snapshot = ig.snapshot();
+    for n in 0..sweep.combinations() {
+        if n != 0 {
+            ig.restore(&snapshot);
+        }
+        for (bit, lever) in sweep.levers.iter().enumerate() {
+            ig.update_lever(*lever, n >> bit & 1 == 1);
+        }
+        ig.run_until_stable(DEFAULT_STABLE_MAX).unwrap();
+        if let Some(clock) = clock {
+            ig.pulse_lever_stable(clock);
+        }
+
+        let values = sweep.values(n);
+        let mut state = state.clone();
+        let expected = model(&mut state, &values);
+        assert_eq!(
+            expected.len(),
+            outputs.len(),
+            "The model must return a value per output"
+        );
+        let actual: Vec<_> = outputs.iter().map(|output| output.u128(ig)).collect();
+        prefix.push(values);
+        if expected != actual {
+            return Err(Counterexample {
+                inputs: prefix.clone(),
+                expected,
+                actual,
+            });
+        }
+        explore(ig, sweep, clock, outputs, steps - 1, &state, model, prefix)?;
+        prefix.pop();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuits::{counter, decoder, WordInput};
+
+    #[test]
+    fn test_exhaustive_finds_lowest_counterexample() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let input = WordInput::new(g, 10, "input");
+        let high = g.andx(input.bits()[6..].iter().copied(), "high");
+        let low = g.orx(input.bits()[..6].iter().copied(), "low");
+        let high = g.output1(high, "high");
+        let low = g.output1(low, "low");
+        let ig = &mut graph.init();
+
+        let model = |inputs: &[u128]| {
+            vec![
+                (inputs[0] >> 6 == 15) as u128,
+                (inputs[0] % 64 != 0) as u128,
+            ]
+        };
+        assert_eq!(
+            exhaustive(ig, &[input.levers()], &[high, low], model),
+            Ok(1024)
+        );
+
+        // Wrong from 0b1111000000 onwards, every block after it fails too.
+        let counterexample = exhaustive(ig, &[input.levers()], &[high, low], |inputs| {
+            vec![0, (inputs[0] % 64 != 0) as u128]
+        })
+        .unwrap_err();
+        assert_eq!(counterexample.inputs, [[0b1111000000]]);
+        assert_eq!(counterexample.expected, [0, 0]);
+        assert_eq!(counterexample.actual, [1, 0]);
+    }
+
+    #[test]
+    fn test_exhaustive_small_and_looping_graphs() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let select = WordInput::new(g, 2, "select");
+        let decoded = decoder(g, &select.bits(), "decoder");
+        let decoded = g.output(&decoded, "decoded");
+        let ig = &mut graph.init();
+        // Fewer combinations than lanes.
+        assert_eq!(
+            exhaustive(ig, &[select.levers()], &[decoded], |inputs| vec![
+                1 << inputs[0]
+            ]),
+            Ok(4)
+        );
+
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let set = g.lever("set");
+        let reset = g.lever("reset");
+        let q = g.nor2(reset.bit(), OFF, "q");
+        let nq = g.nor2(set.bit(), q, "nq");
+        g.d1(q, nq);
+        let q = g.output1(q, "q");
+        let ig = &mut graph.init();
+        ig.pulse_lever_stable(reset);
+        // Simulated from the current state for every combination.
+        assert_eq!(
+            exhaustive(ig, &[&[set], &[reset]], &[q], |inputs| vec![
+                inputs[0] & !inputs[1] & 1
+            ]),
+            Ok(4)
+        );
+        assert_eq!(q.b0(ig), false);
+    }
+
+    #[test]
+    fn test_exhaustive_sequences() {
+        let mut graph = GateGraphBuilder::new();
+        let g = &mut graph;
+        let clock = g.lever("clock");
+        let reset = g.lever("reset");
+        let enable = g.lever("enable");
+        let count = counter(
+            g,
+            clock.bit(),
+            enable.bit(),
+            OFF,
+            ON,
+            reset.bit(),
+            &[OFF; 3],
+            "counter",
+        );
+        let count = g.output(&count, "count");
+        let ig = &mut graph.init();
+        ig.pulse_lever_stable(reset);
+        let start = count.u8(ig);
+
+        let model = |count: &mut u128, inputs: &[u128]| {
+            *count = if inputs[1] == 1 {
+                0
+            } else {
+                (*count + inputs[0]) % 8
+            };
+            vec![*count]
+        };
+        let result = exhaustive_sequences(
+            ig,
+            Some(clock),
+            &[&[enable], &[reset]],
+            &[count],
+            4,
+            0,
+            model,
+        );
+        assert_eq!(result, Ok(256));
+        assert_eq!(count.u8(ig), start);
+
+        // Off by one after 3 increments.
+        let counterexample = exhaustive_sequences(
+            ig,
+            Some(clock),
+            &[&[enable], &[reset]],
+            &[count],
+            3,
+            0,
+            |count: &mut u128, inputs| {
+                *count = if inputs[1] == 1 {
+                    0
+                } else {
+                    *count + inputs[0]
+                };
+                vec![if *count == 3 { 4 } else { *count }]
+            },
+        )
+        .unwrap_err();
+        assert_eq!(counterexample.inputs, [[1, 0], [1, 0], [1, 0]]);
+        assert_eq!(
+            counterexample.to_string(),
+            "inputs [1, 0] then [1, 0] then [1, 0]: expected [4], got [3]"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Refusing to sweep")]
+    fn test_too_many_levers() {
+        let mut graph = GateGraphBuilder::new();
+        let input = WordInput::new(&mut graph, 9, "input");
+        let output = graph.output(&input.bits(), "output");
+        let ig = &mut graph.init();
+        let _ = exhaustive_sequences(ig, None, &[input.levers()], &[output], 4, (), |_, _| {
+            vec![0]
+        });
+    }
+}